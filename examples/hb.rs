@@ -1,15 +1,19 @@
 extern crate clap;
 
 use core::convert::AsRef;
+use core::slice;
+use core::cell::Cell;
 use core::cell::RefCell;
 use core::fmt;
 use core::fmt::Write as FmtWrite;
 
-use std::io::stderr;
-use std::io::stdout;
 use std::io::Error as StdIOError;
+use std::io::ErrorKind as StdIOErrorKind;
+use std::io::Read as StdRead;
 use std::string::String as StdString;
 use std::fs::File as StdFile;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
 
 use halfbit::ExecutionContext;
 use halfbit::LogLevel;
@@ -18,19 +22,38 @@ use halfbit::data_cell::DataCellOps;
 use halfbit::data_cell::DataCellOpsMut;
 use halfbit::data_cell::Error;
 use halfbit::data_cell::content_stream::ContentStream;
+use halfbit::data_cell::cost::ExprCost;
+use halfbit::data_cell::dump::HexDumpOptions;
+use halfbit::data_cell::eval::Environment;
 use halfbit::data_cell::eval::Eval;
-use halfbit::data_cell::expr::BasicTokenType;
+use halfbit::data_cell::eval::FunctionRegistry;
+use halfbit::data_cell::eval::eval_with_provenance;
 use halfbit::data_cell::expr::Expr;
+use halfbit::data_cell::expr::Statement;
 use halfbit::data_cell::expr::Parser;
 use halfbit::data_cell::expr::Source;
+use halfbit::data_cell::json;
+use halfbit::data_cell::json::BytesEncoding;
+use halfbit::data_cell::json::JsonOptions;
+use halfbit::data_cell::redact::Redaction;
 use halfbit::data_cell;
 use halfbit::dyn_rc;
 use halfbit::convert_rc;
 use halfbit::io::ErrorCode as IOErrorCode;
+use halfbit::io::std_stream::StdErr;
+use halfbit::io::std_stream::StdOut;
 use halfbit::io::IOError;
 use halfbit::io::stream::Write;
 use halfbit::io::stream::RandomAccessRead;
 use halfbit::io::stream::BufferAsROStream;
+use halfbit::io::stream::ByteVectorStream;
+use halfbit::io::stream::CountingReader;
+use halfbit::io::stream::MmapFile;
+use halfbit::io::stream::Tee;
+use halfbit::report::TableLayout;
+use halfbit::report::TableWriter;
+use halfbit::time::StdTimeSource;
+use halfbit::io::remote::RemoteClient;
 use halfbit::log_crit;
 use halfbit::log_debug;
 use halfbit::log_error;
@@ -43,24 +66,44 @@ use halfbit::mm::Malloc;
 use halfbit::mm::Rc;
 use halfbit::mm::Vector;
 use halfbit::mm::String;
+use halfbit::num::pos::u64_to_usize_checked;
 
 const HB_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 dyn_rc!(make_data_cell_ops_rc, DataCellOps);
 convert_rc!(std_file_rc_as_reader, RefCell<StdFile>, RefCell<dyn RandomAccessRead + 'a>);
+convert_rc!(mmap_file_rc_as_reader, RefCell<MmapFile>, RefCell<dyn RandomAccessRead + 'a>);
+convert_rc!(remote_file_rc_as_reader, RefCell<RemoteClient<StdFile>>, RefCell<dyn RandomAccessRead + 'a>);
+convert_rc!(byte_vector_stream_rc_as_reader, RefCell<ByteVectorStream<'a>>, RefCell<dyn RandomAccessRead + 'a>);
 convert_rc!(buf_ro_stream_rc_as_reader, RefCell<BufferAsROStream<'a>>, RefCell<dyn RandomAccessRead + 'a>);
 
 /* ExitCode *****************************************************************/
 #[derive(Copy, Clone, Debug)]
 struct ExitCode(u8);
 
+/* OutputFormat *************************************************************/
+#[derive(Copy, Clone, Debug)]
+enum OutputFormat {
+    Text,
+    HexDump(HexDumpOptions),
+    Json(JsonOptions),
+}
+
 /* Invocation ***************************************************************/
 #[derive(Debug)]
 struct Invocation {
     verbose: bool,
+    provenance: bool,
+    align: bool,
+    timing: bool,
+    mmap: bool,
+    format: OutputFormat,
+    report_path: Option<StdString>,
     item_paths: Vec<StdString>,
     item_raw_strings: Vec<StdString>,
+    item_remote_paths: Vec<StdString>,
     expressions: Vec<StdString>,
+    redact_paths: Vec<StdString>,
 }
 
 /* ItemError ****************************************************************/
@@ -108,16 +151,50 @@ impl fmt::Display for ItemError {
 struct ItemData<'a> {
     name: String<'a>,
     file: Rc<'a, RefCell<dyn RandomAccessRead + 'a>>,
+    read_counter: Option<&'a Cell<u64>>, // bytes read are added here if set
 }
 impl<'a> ItemData<'a> {
 
+    /* files are read through the regular file API, or mapped when asked to;
+     * a mapped file truncated by someone else while being parsed kills the
+     * process with SIGBUS, so mapping is opt-in; pipes are read whole into
+     * memory, which makes them seekable */
     fn from_file_path(
         path: &str,
+        mmap: bool,
         xc: &mut ExecutionContext<'a>
     ) -> Result<Self, ItemError> {
+        let mut f = std::fs::File::open(path)?;
+        let file_type = f.metadata()?.file_type();
+        let file = if mmap && file_type.is_file() {
+            // the tool never writes to its inputs
+            match unsafe { MmapFile::map(&f, xc) } {
+                Ok(m) => mmap_file_rc_as_reader(xc.rc(RefCell::new(m))?),
+                Err(_) => std_file_rc_as_reader(xc.rc(RefCell::new(f))?),
+            }
+        } else if is_fifo(&file_type) {
+            let data = read_whole_file(&mut f, xc)?;
+            byte_vector_stream_rc_as_reader(xc.rc(RefCell::new(ByteVectorStream::new(data)))?)
+        } else {
+            std_file_rc_as_reader(xc.rc(RefCell::new(f))?)
+        };
         Ok(ItemData {
             name: xc.string_clone(path)?,
-            file: std_file_rc_as_reader(xc.rc(RefCell::new(std::fs::File::open(path)?))?)
+            file,
+            read_counter: None,
+        })
+    }
+
+    fn from_remote_path(
+        path: &str,
+        xc: &mut ExecutionContext<'a>
+    ) -> Result<Self, ItemError> {
+        let f = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let file = remote_file_rc_as_reader(xc.rc(RefCell::new(RemoteClient::new(f)))?);
+        Ok(ItemData {
+            name: xc.string_clone(path)?,
+            file,
+            read_counter: None,
         })
     }
 
@@ -130,10 +207,57 @@ impl<'a> ItemData<'a> {
         let file = xc.rc(RefCell::new(file))?;
         let file = buf_ro_stream_rc_as_reader(file);
         let name = xc.string_clone(name)?;
-        Ok(ItemData { name, file })
+        Ok(ItemData { name, file, read_counter: None })
+    }
+
+    /* reads are counted only when asked for, as counting hides the mapped
+     * content of files from the parsers */
+    fn with_content<R>(
+        &self,
+        f: impl FnOnce(&mut ContentStream<'_, dyn RandomAccessRead + '_>) -> R,
+    ) -> R {
+        let mut x = self.file.as_ref().borrow_mut();
+        match self.read_counter {
+            None => f(&mut ContentStream::new(&mut *x)),
+            Some(counter) => {
+                let mut cr = CountingReader::new(&mut *x);
+                let r = f(&mut ContentStream::new(&mut cr as &mut dyn RandomAccessRead));
+                counter.set(counter.get() + cr.bytes_read());
+                r
+            }
+        }
     }
 
+}
+
+#[cfg(unix)]
+fn is_fifo(file_type: &std::fs::FileType) -> bool {
+    file_type.is_fifo()
+}
 
+#[cfg(not(unix))]
+fn is_fifo(_file_type: &std::fs::FileType) -> bool {
+    false
+}
+
+fn read_whole_file<'a>(
+    f: &mut StdFile,
+    xc: &mut ExecutionContext<'a>,
+) -> Result<Vector<'a, u8>, ItemError> {
+    let mut data = xc.byte_vector();
+    // the size is only a hint, the file may change while being read
+    let size_hint = f.metadata()?.len();
+    data.reserve(u64_to_usize_checked(size_hint).map_err(|_| AllocError::UnsupportedSize)?)?;
+    let mut buf = [0_u8; 0x10000];
+    loop {
+        match f.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => data.append_from_slice(&buf[..n])?,
+            Err(e) if e.kind() == StdIOErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(data)
 }
 
 impl<'a> fmt::Debug for ItemData<'a> {
@@ -155,9 +279,7 @@ impl<'a> DataCellOps for ItemData<'a> {
         property_name: &str,
         xc: &mut ExecutionContext<'x>,
     ) -> Result<DataCell<'x>, data_cell::Error<'x>> {
-        let mut x = self.file.as_ref().borrow_mut();
-        let mut cs = ContentStream::new(&mut *x);
-        cs.get_property_mut(property_name, xc)
+        self.with_content(|cs| cs.get_property_mut(property_name, xc))
     }
 
     fn output_as_human_readable<'w, 'x>(
@@ -165,9 +287,15 @@ impl<'a> DataCellOps for ItemData<'a> {
         out: &mut (dyn Write + 'w),
         xc: &mut ExecutionContext<'x>,
     ) -> Result<(), Error<'x>> {
-        let mut x = self.file.as_ref().borrow_mut();
-        let mut cs = ContentStream::new(&mut *x);
-        cs.output_as_human_readable_mut(out, xc)
+        self.with_content(|cs| cs.output_as_human_readable_mut(out, xc))
+    }
+
+    fn get_property_byte_range(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        self.with_content(|cs| cs.get_property_byte_range_mut(property_name, xc))
     }
 
 }
@@ -177,26 +305,47 @@ struct Item<'a>(Rc<'a, ItemData<'a>>);
 
 impl<'a> Item<'a> {
 
-    fn from_data(item_data: ItemData<'a>, allocator: AllocatorRef<'a>) -> Result<Self, AllocError> {
+    fn from_data(
+        mut item_data: ItemData<'a>,
+        read_counter: Option<&'a Cell<u64>>,
+        allocator: AllocatorRef<'a>,
+    ) -> Result<Self, AllocError> {
+        item_data.read_counter = read_counter;
         Rc::new(allocator, item_data).map(|rc| Item(rc)).map_err(|e| e.0)
     }
 
     fn from_file_path(
         path: &str,
+        mmap: bool,
+        read_counter: Option<&'a Cell<u64>>,
         xc: &mut ExecutionContext<'a>
     ) -> Result<Self, ItemError> {
         Ok(Item::from_data(
-                ItemData::from_file_path(path, xc)?,
+                ItemData::from_file_path(path, mmap, xc)?,
+                read_counter,
+                xc.get_main_allocator())?)
+    }
+
+    fn from_remote_path(
+        path: &str,
+        read_counter: Option<&'a Cell<u64>>,
+        xc: &mut ExecutionContext<'a>
+    ) -> Result<Self, ItemError> {
+        Ok(Item::from_data(
+                ItemData::from_remote_path(path, xc)?,
+                read_counter,
                 xc.get_main_allocator())?)
     }
 
     fn from_raw_string(
         name: &str,
         data: &'a [u8],
+        read_counter: Option<&'a Cell<u64>>,
         xc: &mut ExecutionContext<'a>
     ) -> Result<Self, ItemError> {
         Ok(Item::from_data(
                 ItemData::from_raw_string(name, data, xc)?,
+                read_counter,
                 xc.get_main_allocator())?)
     }
 
@@ -253,6 +402,31 @@ impl ProcessingStatus {
     }
 }
 
+/* Timing *******************************************************************/
+/* cost of each expression, by index in the expression list */
+struct Timing<'c> {
+    costs: Vec<ExprCost>,
+    bytes_read: &'c Cell<u64>,
+}
+
+/* Report *******************************************************************/
+/* what every item is put through, set up once per run */
+struct Report<'r, 'x> {
+    statements: &'r [Statement<'x>],
+    functions: &'r FunctionRegistry<'x>,
+    with_provenance: bool,
+    redaction: &'r Redaction<'r>,
+    format: &'r OutputFormat,
+}
+
+/* one computed expression, as output on a report row */
+struct ReportRow<'r, 'x> {
+    item_name: &'r str,
+    expr: &'r Expr<'x>,
+    value: &'r DataCell<'x>,
+    provenance: Option<&'r DataCell<'x>>,
+}
+
 /* process_args *************************************************************/
 fn process_args(args: Vec<StdString>) -> Invocation {
     let m = clap::App::new("halfbit")
@@ -263,13 +437,52 @@ fn process_args(args: Vec<StdString>) -> Invocation {
                 .short("v")
                 .long("verbose")
                 .help("prints what it does verbosely"))
+        .arg(clap::Arg::with_name("provenance")
+                .long("provenance")
+                .help("adds a column describing where each value comes from"))
+        .arg(clap::Arg::with_name("align")
+                .long("align")
+                .help("aligns report columns with spaces instead of separating them with tabs (prints the report at the end)"))
+        .arg(clap::Arg::with_name("timing")
+                .long("timing")
+                .help("measures the time and bytes read taken by each expression and prints them at the end"))
+        .arg(clap::Arg::with_name("mmap")
+                .long("mmap")
+                .help("maps files into memory instead of reading them; faster for large files, but a file truncated while being examined kills the process (SIGBUS)"))
+        .arg(clap::Arg::with_name("format")
+                .long("format")
+                .help("report format: text (default) or json (one object per line)")
+                .takes_value(true)
+                .possible_values(&["text", "json"]))
+        .arg(clap::Arg::with_name("json_bytes")
+                .long("json-bytes")
+                .help("encoding of byte values in json output: base64 (default) or hex")
+                .takes_value(true)
+                .possible_values(&["base64", "hex"]))
+        .arg(clap::Arg::with_name("hex_dump")
+                .long("hex-dump")
+                .help("prints byte values as hex dumps on the lines following their row"))
+        .arg(clap::Arg::with_name("dump_width")
+                .long("dump-width")
+                .help("bytes per hex dump row (implies --hex-dump)")
+                .takes_value(true))
+        .arg(clap::Arg::with_name("redact")
+                .long("redact")
+                .help("replaces values at the given dot-separated path (like elf_header.e_entry or zip_entries.*.name) with <redacted>")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+        .arg(clap::Arg::with_name("save_report")
+                .long("save-report")
+                .help("also writes the report to the given file")
+                .takes_value(true))
         .arg(clap::Arg::with_name("items")
                 .help("item(s) to process (as file paths by default)")
                 .multiple(true))
         .arg(clap::Arg::with_name("eval")
                 .short("e")
                 .long("eval")
-                .help("computes given comma-separated expressions on each item")
+                .help("computes given comma-separated expressions on each item; `let name = expr;` or `name := expr` binds a value for later expressions")
                 .takes_value(true)
                 .multiple(true)
                 .number_of_values(1))
@@ -279,6 +492,11 @@ fn process_args(args: Vec<StdString>) -> Invocation {
                 .help("treat following arguments as file content for items")
                 .takes_value(true)
                 .multiple(true))
+        .arg(clap::Arg::with_name("remote")
+                .long("remote")
+                .help("treat following arguments as devices or pipes serving items over the halfbit remote stream protocol")
+                .takes_value(true)
+                .multiple(true))
         .arg(clap::Arg::with_name("file_path")
                 .short("p")
                 .long("file-path")
@@ -289,12 +507,41 @@ Item properties:
     first_8_bytes       byte array with first 8 bytes (or entire content if shorter)
     tof_ids             array of identifiers with matching top-of-file exact data formats
     elf_header          treat content as ELF file header record
+
+A leading dot refers to the item itself: .first_byte is first_byte even
+after `let first_byte = ...` binds that name.
 ")
         .setting(clap::AppSettings::ArgRequiredElseHelp)
         .get_matches_from(args);
 
+    let dump_width = m.value_of("dump_width").map(|w| w.parse::<usize>()
+        .unwrap_or_else(|_| {
+            eprintln!("error: bad dump width: {:?}", w);
+            std::process::exit(64);
+        }));
     let inv = Invocation {
         verbose: m.is_present("verbose"),
+        provenance: m.is_present("provenance"),
+        align: m.is_present("align"),
+        timing: m.is_present("timing"),
+        mmap: m.is_present("mmap"),
+        format:
+            if m.value_of("format") == Some("json") {
+                OutputFormat::Json(JsonOptions {
+                    bytes: match m.value_of("json_bytes") {
+                        Some("hex") => BytesEncoding::Hex,
+                        _ => BytesEncoding::Base64,
+                    },
+                })
+            } else if m.is_present("hex_dump") || dump_width.is_some() {
+                OutputFormat::HexDump(HexDumpOptions {
+                    row_len: dump_width.unwrap_or(0),
+                    ..HexDumpOptions::default()
+                })
+            } else {
+                OutputFormat::Text
+            },
+        report_path: m.value_of("save_report").map(StdString::from),
         item_paths:
             if let Some(values) = m.values_of("items") {
                 values.map(|x| StdString::from(x)).collect()
@@ -306,12 +553,18 @@ Item properties:
                 .map_or_else(
                     || Vec::new(),
                     |v| v.map(|x| StdString::from(x)).collect()),
+        item_remote_paths:
+            m.values_of("remote")
+                .map_or_else(Vec::new, |v| v.map(StdString::from).collect()),
         expressions:
             if let Some(values) = m.values_of("eval") {
                 values.map(|x| StdString::from(x)).collect()
             } else {
                 Vec::new()
             },
+        redact_paths:
+            m.values_of("redact")
+                .map_or_else(Vec::new, |v| v.map(StdString::from).collect()),
     };
 
     if cfg!(debug_assertions) && inv.verbose {
@@ -322,35 +575,151 @@ Item properties:
     inv
 }
 
+/* one object per row, kept in a single cell so that the table writer adds
+ * nothing but the end of line */
+fn output_expr_value_as_json<'x>(
+    row: &ReportRow<'_, 'x>,
+    redaction: &Redaction<'_>,
+    options: &JsonOptions,
+    table: &mut TableWriter<'x, '_>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let expr_text = row.expr.to_string();
+    table.write_all(b"{\"item\":", xc)
+        .map_err(|e| Error::Output(e.to_error()))
+        .and_then(|_| json::output_text_as_json_string(row.item_name.as_bytes(), table, xc))
+        .and_then(|_| table.write_all(b",\"expression\":", xc)
+            .map_err(|e| Error::Output(e.to_error())))
+        .and_then(|_| json::output_text_as_json_string(expr_text.as_bytes(), table, xc))
+        .and_then(|_| table.write_all(b",\"value\":", xc)
+            .map_err(|e| Error::Output(e.to_error())))
+        .and_then(|_| if redaction.is_empty() {
+            json::output_as_json_with_options(row.value, options, table, xc)
+        } else {
+            redaction.output_as_json(row.value, &expr_text, options, table, xc)
+        })
+        .and_then(|_| match row.provenance {
+            Some(p) => table.write_all(b",\"provenance\":", xc)
+                .map_err(|e| Error::Output(e.to_error()))
+                .and_then(|_| json::output_as_json_with_options(p, options, table, xc)),
+            None => Ok(()),
+        })
+        .and_then(|_| table.write_all(b"}", xc).map_err(|e| Error::Output(e.to_error())))
+        .and_then(|_| table.end_row().map_err(Error::Alloc))
+        .inspect_err(|_| table.cancel_row())
+        .and_then(|_| match table.layout() {
+            TableLayout::Delimited(_) =>
+                table.flush(xc).map_err(|e| Error::Output(e.to_error())),
+            TableLayout::Aligned { .. } => Ok(()),
+        })
+}
+
 fn output_expr_value<'x>(
-    item_name: &str,
-    expr: &Expr<'x>,
-    value: &DataCell<'x>,
-    out: &mut (dyn Write + '_),
+    row: &ReportRow<'_, 'x>,
+    report: &Report<'_, 'x>,
+    table: &mut TableWriter<'x, '_>,
     xc: &mut ExecutionContext<'x>,
 ) -> Result<(), Error<'x>> {
-    write!(out, "{:?}\t{}\t", item_name, expr)
+    /* the dump goes below the row, so values without bytes are printed
+     * as usual; redaction works on the text form only */
+    let redaction = report.redaction;
+    let mut dump = xc.byte_vector();
+    let dumped = match report.format {
+        OutputFormat::Json(options) =>
+            return output_expr_value_as_json(row, redaction, options, table, xc),
+        OutputFormat::HexDump(options) if redaction.is_empty() =>
+            match row.value.output_as_hex_dump(&mut dump, xc, options) {
+                Ok(()) => true,
+                Err(Error::NotApplicable) => false,
+                Err(e) => return Err(e),
+            },
+        _ => false,
+    };
+    write!(table, "{:?}", row.item_name)
+        .and_then(|_| table.end_cell().map_err(|_| core::fmt::Error))
+        .and_then(|_| write!(table, "{}", row.expr))
+        .and_then(|_| table.end_cell().map_err(|_| core::fmt::Error))
         .map_err(|_| Error::Output(
                     IOError::with_str(IOErrorCode::Unsuccessful, "output error")))
-        .and_then(|_| value.output_as_human_readable(out, xc))
-        .and_then(|_| out.write_all(b"\n", xc).map_err(|e| Error::Output(e.to_error())))
+        .and_then(|_| if dumped {
+            table.write_all(b"\n", xc)
+                .and_then(|_| table.write_all(dump.as_slice().strip_suffix(b"\n").unwrap_or(&[]), xc))
+                .map_err(|e| Error::Output(e.to_error()))
+        } else if redaction.is_empty() {
+            row.value.output_as_human_readable(table, xc)
+        } else {
+            redaction.output_as_human_readable(row.value, &row.expr.to_string(), table, xc)
+        })
+        .and_then(|_| match row.provenance {
+            Some(p) => table.end_cell()
+                .map_err(Error::Alloc)
+                .and_then(|_| p.output_as_human_readable(table, xc)),
+            None => Ok(()),
+        })
+        .and_then(|_| table.end_row().map_err(Error::Alloc))
+        .inspect_err(|_| table.cancel_row())
+        .and_then(|_| match table.layout() {
+            TableLayout::Delimited(_) =>
+                table.flush(xc).map_err(|e| Error::Output(e.to_error())),
+            TableLayout::Aligned { .. } => Ok(()),
+        })
 }
 
-fn process_expression_list<'n, 'x>(
-    item_name: &'n str,
+/* let statements only bind their value, without output */
+fn eval_and_output<'x>(
+    item_name: &str,
     root: &mut DataCell<'x>,
-    eval_expr_list: &[Expr<'x>],
-    out: &mut (dyn Write + '_),
+    statement: &Statement<'x>,
+    env: &mut Environment<'x>,
+    report: &Report<'_, 'x>,
+    table: &mut TableWriter<'x, '_>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let expr = match statement {
+        Statement::Let(_) =>
+            return env.exec(statement, slice::from_mut(root), report.functions, xc).map(|_| ()),
+        Statement::Expr(expr) => expr,
+    };
+    if report.with_provenance {
+        let (value, p) = eval_with_provenance(expr, item_name, root, env, xc)?;
+        let p = p.to_data_cell(xc)?;
+        let row = ReportRow { item_name, expr, value: &value, provenance: Some(&p) };
+        output_expr_value(&row, report, table, xc)
+    } else {
+        let value = expr.eval_in(slice::from_mut(root), env, report.functions, xc)?;
+        let row = ReportRow { item_name, expr, value: &value, provenance: None };
+        output_expr_value(&row, report, table, xc)
+    }
+}
+
+fn process_expression_list<'x>(
+    item_name: &str,
+    root: &mut DataCell<'x>,
+    report: &Report<'_, 'x>,
+    mut timing: Option<&mut Timing<'_>>,
+    table: &mut TableWriter<'x, '_>,
     xc: &mut ExecutionContext<'x>,
 ) -> ProcessingStatus {
-    log_info!(xc, "info:{:?}: evaluating {:?}", item_name, eval_expr_list);
+    log_info!(xc, "info:{:?}: evaluating {:?}", item_name, report.statements);
     let mut status = ProcessingStatus::new();
-    for expr in eval_expr_list {
+    /* names are bound per item */
+    let mut env = Environment::new(xc.get_main_allocator());
+    for (index, expr) in report.statements.iter().enumerate() {
         log_info!(xc, "info:{:?}: computing expression {}", item_name, expr);
-        if expr.eval_on_cell(root, xc)
-            .and_then(|v| output_expr_value(item_name, expr, &v, out, xc))
+        let start_bytes = timing.as_ref().map(|t| t.bytes_read.get());
+        let (result, ticks) = xc.time(|xc| eval_and_output(
+            item_name, root, expr, &mut env, report, table, xc));
+        if let (Some(t), Some(start_bytes)) = (timing.as_mut(), start_bytes) {
+            let time_ns = match (xc.get_time_source(), ticks) {
+                (Some(ts), Some(ticks)) => ts.ticks_to_ns(ticks),
+                _ => 0,
+            };
+            t.costs[index].add_item(time_ns, t.bytes_read.get() - start_bytes);
+        }
+        if result
             .map(|_| { status.attributes_computed_ok += 1; })
-            .or_else(|e| match e {
+            /* the error says which part of the expression failed */
+            .or_else(|e| match e.cause() {
                 Error::NotApplicable => {
                     status.attributes_not_applicable += 1;
                     log_warn!(xc, "warning:{:?}:{}: {}", item_name, expr, e);
@@ -376,23 +745,25 @@ fn process_expression_list<'n, 'x>(
 fn process_item<'x>(
     item_name: &str,
     item: &Item<'x>,
-    eval_expr_list: &[Expr<'x>],
-    out: &mut (dyn Write + '_),
+    report: &Report<'_, 'x>,
+    timing: Option<&mut Timing<'_>>,
+    table: &mut TableWriter<'x, '_>,
     xc: &mut ExecutionContext<'x>,
 ) -> ProcessingStatus {
     let mut root = item.as_data_cell();
-    process_expression_list(item_name, &mut root, eval_expr_list, out, xc)
+    process_expression_list(item_name, &mut root, report, timing, table, xc)
 }
 
 fn process_item_result<'x>(
     item_name: &str,
     item_result: Result<Item<'x>, ItemError>,
-    eval_expr_list: &[Expr<'x>],
-    out: &mut (dyn Write + '_),
+    report: &Report<'_, 'x>,
+    timing: Option<&mut Timing<'_>>,
+    table: &mut TableWriter<'x, '_>,
     xc: &mut ExecutionContext<'x>,
 ) -> ProcessingStatus {
     match item_result {
-        Ok(item) => process_item(item_name, &item, eval_expr_list, out, xc),
+        Ok(item) => process_item(item_name, &item, report, timing, table, xc),
         Err(e) => {
             log_error!(xc, "error:{}: {}", item_name, e);
             e.into()
@@ -400,25 +771,49 @@ fn process_item_result<'x>(
     }
 }
 
+/* every bad expression in the text gets reported */
 fn parse_eval_expr_list<'a>(
     text: &str,
     xc: &mut ExecutionContext<'a>,
-) -> Result<Vector<'a, Expr<'a>>, ExitCode> {
+) -> Result<Vector<'a, Statement<'a>>, ExitCode> {
     let s = Source::new(text, "eval-expression-arg");
     let mut p = Parser::new(&s, &xc);
-    p.parse_expr_list()
-        .and_then(|x|
-            p.expect_token(BasicTokenType::End.to_bitmap())
-                .map(|_e| x.unwrap_data().unwrap_items()))
-        .map_err(|e| {
-            log_error!(xc, "error in expression: {}\nerror: {}", text, e.get_msg());
-            ExitCode::new(64)
-        })
+    let r = p.parse_statement_list_recovering().map_err(|e| {
+        log_error!(xc, "error in expression: {}\nerror: {}", text, e.get_msg());
+        ExitCode::new(64)
+    })?;
+    if r.errors.is_empty() {
+        return Ok(r.items);
+    }
+    for e in r.errors.as_slice() {
+        log_error!(xc, "error in expression: {}\nerror: {}", e.source_slice().as_str(), e.data().get_msg());
+    }
+    Err(ExitCode::new(64))
+}
+
+/* one expr_cost record per line on the log stream, whatever the log level */
+fn output_timing<'x>(
+    eval_expr_list: &[Statement<'x>],
+    costs: &[ExprCost],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    for (statement, cost) in eval_expr_list.iter().zip(costs) {
+        let cell = cost.to_data_cell(statement.expr(), xc)?;
+        let mut line = xc.byte_vector();
+        line.append_from_slice(b"timing: ")?;
+        cell.output_as_human_readable(&mut line, xc)?;
+        line.push(b'\n').map_err(|(e, _)| e)?;
+        let mut nxc = xc.to_non_logging();
+        xc.get_log_stream().write_all(line.as_slice(), &mut nxc)
+            .map_err(|e| Error::Output(e.to_error()))?;
+    }
+    Ok(())
 }
 
 /* run **********************************************************************/
 fn run<'x>(
     invocation: &'x Invocation,
+    read_counter: &'x Cell<u64>,
     out: &mut (dyn Write + '_),
     xc: &mut ExecutionContext<'x>
 ) -> Result<(), ExitCode> {
@@ -427,19 +822,51 @@ fn run<'x>(
     }
     let mut summary = ProcessingStatus::new();
     let mut expressions = xc.vector();
+    let mut parse_result = Ok(());
     for expr_text in &invocation.expressions[..] {
-        if let Err(ae) = expressions.append_vector(parse_eval_expr_list(expr_text.as_str(), xc)?) {
-            log_error!(xc, "failed to allocate memory for parsing eval expressions: {:?}", ae);
-            return Err(ExitCode::new(16));
+        match parse_eval_expr_list(expr_text.as_str(), xc) {
+            Ok(v) => if let Err(ae) = expressions.append_vector(v) {
+                log_error!(xc, "failed to allocate memory for parsing eval expressions: {:?}", ae);
+                return Err(ExitCode::new(16));
+            },
+            Err(rc) => parse_result = Err(rc),
         }
     }
+    parse_result?;
     log_debug!(xc, "expressions: {:?}", expressions);
 
     let expr_list = expressions.as_slice();
+    let redact_paths: Vec<&str> = invocation.redact_paths.iter().map(|p| p.as_str()).collect();
+    let redaction = Redaction::new(&redact_paths);
+    let functions = FunctionRegistry::new(xc.get_main_allocator());
+    let report = Report {
+        statements: expr_list,
+        functions: &functions,
+        with_provenance: invocation.provenance,
+        redaction: &redaction,
+        format: &invocation.format,
+    };
+    let layout = if invocation.align {
+        TableLayout::Aligned { gap: 2 }
+    } else {
+        TableLayout::Delimited(b"\t")
+    };
+    let mut table = TableWriter::new(out, layout, xc.get_main_allocator());
+    let mut timing = if invocation.timing {
+        Some(Timing { costs: vec![ExprCost::new(); expr_list.len()], bytes_read: read_counter })
+    } else {
+        None
+    };
+    let read_counter = timing.as_ref().map(|t| t.bytes_read);
 
     for item_path in &invocation.item_paths {
-        let item_result = Item::from_file_path(item_path, xc);
-        summary.add(&process_item_result(item_path, item_result, expr_list, out, xc));
+        let item_result = Item::from_file_path(item_path, invocation.mmap, read_counter, xc);
+        summary.add(&process_item_result(item_path, item_result, &report, timing.as_mut(), &mut table, xc));
+        if summary.output_error { break; }
+    }
+    for remote_path in &invocation.item_remote_paths {
+        let item_result = Item::from_remote_path(remote_path, read_counter, xc);
+        summary.add(&process_item_result(remote_path, item_result, &report, timing.as_mut(), &mut table, xc));
         if summary.output_error { break; }
     }
     for (index, data) in invocation.item_raw_strings.iter().enumerate() {
@@ -450,10 +877,18 @@ fn run<'x>(
                 name = String::map_str("<raw-arg>");
                 ItemError::Alloc(AllocError::OperationFailed)
             })
-            .and_then(|_| Item::from_raw_string(name.as_str(), data.as_bytes(), xc));
-        summary.add(&process_item_result(name.as_str(), item_result, expr_list, out, xc));
+            .and_then(|_| Item::from_raw_string(name.as_str(), data.as_bytes(), read_counter, xc));
+        summary.add(&process_item_result(name.as_str(), item_result, &report, timing.as_mut(), &mut table, xc));
 
     }
+    if let Err(e) = table.flush(xc) {
+        log_crit!(xc, "fatal: {}", e.to_error());
+    }
+    if let Some(t) = &timing {
+        if let Err(e) = output_timing(expr_list, &t.costs, xc) {
+            log_error!(xc, "error: cannot output timing: {}", e);
+        }
+    }
     if invocation.verbose {
         log_info!(xc, "accessible items: {}", summary.accessible_items);
         log_info!(xc, "inaccessible items: {}", summary.inaccessible_items);
@@ -477,18 +912,35 @@ fn run<'x>(
 /* main *********************************************************************/
 fn main() {
     let invocation = process_args(std::env::args().collect());
+    let read_counter = Cell::new(0_u64);
     let a = Malloc::new();
-    let err = stderr();
-    let mut log = err.lock();
-    let out = stdout();
-    let mut out = out.lock();
+    let mut log = StdErr::new();
+    let mut out = StdOut::new();
+    let clock = StdTimeSource::new();
     let mut xc = ExecutionContext::new(
         a.to_ref(),
         a.to_ref(),
         &mut log,
         if invocation.verbose { LogLevel::Debug } else { LogLevel::Warning },
     );
-    run(&invocation, &mut out, &mut xc)
+    xc.set_time_source(Some(&clock));
+    let result = match &invocation.report_path {
+        Some(report_path) => {
+            let mut report: Vector<'_, u8> = Vector::new(a.to_ref());
+            let r = {
+                let mut tee = Tee::new(&mut out, &mut report);
+                run(&invocation, &read_counter, &mut tee, &mut xc)
+            };
+            std::fs::write(report_path, report.as_slice())
+                .map_err(|e| {
+                    log_error!(xc, "error:{:?}: cannot save report: {}", report_path, e);
+                    ExitCode::new(8)
+                })
+                .and(r)
+        },
+        None => run(&invocation, &read_counter, &mut out, &mut xc),
+    };
+    result
         .unwrap_or_else(|e| {
             log_debug!(xc, "* exiting with code {}", e.0);
             std::process::exit(e.0 as i32);
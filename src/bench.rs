@@ -0,0 +1,291 @@
+/* entry points for throughput benchmarks (criterion or the like) over
+ * content held in memory; each one runs with a malloc backed context and
+ * returns a count taken from the result so that the work is not optimized
+ * away; malformed content gives 0 */
+
+use crate::ExecutionContext;
+use crate::data_cell::DataCell;
+use crate::data_cell::content_stream::ContentStream;
+use crate::data_cell::formats::elf::ElfFile;
+use crate::data_cell::formats::pe;
+use crate::data_cell::formats::zip;
+use crate::io::compress::Crc32;
+use crate::io::compress::InflateFormat;
+use crate::io::compress::InflateReader;
+use crate::io::stream::BufferAsOnePassROStream;
+use crate::io::stream::BufferAsROStream;
+use crate::io::stream::Read;
+use crate::mm::AllocError;
+use crate::mm::Allocator;
+use crate::mm::Malloc;
+use crate::mm::Vector;
+
+fn run<F>(f: F) -> usize
+where F: for<'x> FnOnce(&mut ExecutionContext<'x>) -> Option<usize> {
+    let a = Malloc::new();
+    let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+    f(&mut xc).unwrap_or(0)
+}
+
+fn cell_count(c: &DataCell) -> usize {
+    match c {
+        DataCell::CellVector(v) => v.borrow().0.len(),
+        DataCell::Nothing => 0,
+        _ => 1,
+    }
+}
+
+/* count of top of file ids */
+pub fn tof_identify(data: &[u8]) -> usize {
+    run(|xc| {
+        let mut s = BufferAsROStream::new(data);
+        let ids = ContentStream::new(&mut s).identify_top_of_file_records(xc).ok()?;
+        Some(cell_count(&ids))
+    })
+}
+
+/* count of sections plus count of symbols */
+pub fn parse_elf(data: &[u8]) -> usize {
+    run(|xc| {
+        let mut s = BufferAsROStream::new(data);
+        let f = ElfFile::load(&mut s, xc).ok()?;
+        let sections = f.section_headers(&mut s, xc).ok()?;
+        let symbols = f.symbols(&mut s, xc).ok()?;
+        Some(cell_count(&sections) + cell_count(&symbols))
+    })
+}
+
+/* count of central directory entries */
+pub fn parse_zip(data: &[u8]) -> usize {
+    run(|xc| {
+        let mut s = BufferAsROStream::new(data);
+        let entries = zip::zip_entries(&mut s, xc).ok()?;
+        Some(cell_count(&entries))
+    })
+}
+
+/* 1 for a valid PE header */
+pub fn parse_pe(data: &[u8]) -> usize {
+    run(|xc| {
+        let mut s = BufferAsROStream::new(data);
+        let h = pe::pe_header(&mut s, xc).ok()?;
+        Some(cell_count(&h))
+    })
+}
+
+/* size of the decompressed content of a gzip stream */
+pub fn gunzip(data: &[u8]) -> usize {
+    run(|xc| {
+        let src = BufferAsOnePassROStream::new(data);
+        let mut r = InflateReader::new(src, InflateFormat::Gzip, xc.get_main_allocator()).ok()?;
+        let mut buf = [0_u8; 0x1000];
+        let mut total = 0;
+        loop {
+            let n = r.read_uninterrupted(&mut buf, xc).ok()?;
+            if n == 0 { break; }
+            total += n;
+        }
+        Some(total)
+    })
+}
+
+/* count of little endian u32 values read one by one through the stream
+ * helpers */
+pub fn read_u32le(data: &[u8]) -> usize {
+    run(|xc| {
+        let mut s = BufferAsOnePassROStream::new(data);
+        let mut count = 0;
+        while s.read_u32le(xc).is_ok() {
+            count += 1;
+        }
+        Some(count)
+    })
+}
+
+/* Synthetic corpora ********************************************************/
+
+/* xorshift64* bytes; same seed, same content */
+pub fn random_bytes(len: usize, seed: u64, out: &mut Vector<'_, u8>) -> Result<(), AllocError> {
+    let mut x = seed | 1;
+    out.reserve(len)?;
+    for _ in 0..len / 8 + 1 {
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        let v = x.wrapping_mul(0x2545_F491_4F6C_DD1D).to_le_bytes();
+        let n = core::cmp::min(8, len - core::cmp::min(len, out.len()));
+        out.append_from_slice(&v[0..n])?;
+    }
+    Ok(())
+}
+
+fn hex_name(prefix: &[u8], n: u32, suffix: &[u8], out: &mut Vector<'_, u8>) -> Result<(), AllocError> {
+    out.append_from_slice(prefix)?;
+    for i in (0..8).rev() {
+        out.append_from_slice(&[b"0123456789ABCDEF"[((n >> (i * 4)) & 0xF) as usize]])?;
+    }
+    out.append_from_slice(suffix)
+}
+
+fn pad_to(align: usize, out: &mut Vector<'_, u8>) -> Result<(), AllocError> {
+    while !out.len().is_multiple_of(align) {
+        out.append_from_slice(&[0])?;
+    }
+    Ok(())
+}
+
+/* ELF64 little endian relocatable with the given count of global function
+ * symbols named sym_XXXXXXXX */
+pub fn elf64_corpus(symbol_count: u32, out: &mut Vector<'_, u8>) -> Result<(), AllocError> {
+    const NAME_LEN: usize = 4 + 8 + 1;
+    const SHSTRTAB: &[u8] = b"\0.strtab\0.symtab\0.shstrtab\0";
+    let base = out.len();
+    let strtab_off = 0x40;
+    let strtab_len = 1 + NAME_LEN * symbol_count as usize;
+    let symtab_off = (strtab_off + strtab_len + 7) & !7;
+    let symtab_len = 24 * (symbol_count as usize + 1);
+    let shstrtab_off = symtab_off + symtab_len;
+    let shoff = (shstrtab_off + SHSTRTAB.len() + 7) & !7;
+
+    out.append_from_slice(b"\x7FELF\x02\x01\x01\0\0\0\0\0\0\0\0\0")?;
+    out.append_from_slice(&1_u16.to_le_bytes())?; // ET_REL
+    out.append_from_slice(&62_u16.to_le_bytes())?; // EM_X86_64
+    out.append_from_slice(&1_u32.to_le_bytes())?;
+    out.append_from_slice(&0_u64.to_le_bytes())?;
+    out.append_from_slice(&0_u64.to_le_bytes())?;
+    out.append_from_slice(&(shoff as u64).to_le_bytes())?;
+    out.append_from_slice(&0_u32.to_le_bytes())?;
+    for v in [0x40_u16, 0x38, 0, 0x40, 4, 3] {
+        out.append_from_slice(&v.to_le_bytes())?;
+    }
+
+    out.append_from_slice(&[0])?;
+    for i in 0..symbol_count {
+        hex_name(b"sym_", i, b"\0", out)?;
+    }
+    pad_to(8, out)?;
+    out.append_from_slice(&[0; 24])?;
+    for i in 0..symbol_count {
+        out.append_from_slice(&(1 + NAME_LEN as u32 * i).to_le_bytes())?;
+        out.append_from_slice(&[0x12, 0])?; // STB_GLOBAL, STT_FUNC
+        out.append_from_slice(&0xFFF1_u16.to_le_bytes())?;
+        out.append_from_slice(&(0x1000 + 0x10 * i as u64).to_le_bytes())?;
+        out.append_from_slice(&0x10_u64.to_le_bytes())?;
+    }
+    out.append_from_slice(SHSTRTAB)?;
+    pad_to(8, out)?;
+
+    let sections: [(u32, u32, usize, usize, u32, u32, u64); 4] = [
+        (0, 0, 0, 0, 0, 0, 0),
+        (1, 3, strtab_off, strtab_len, 0, 0, 0),
+        (9, 2, symtab_off, symtab_len, 1, 1, 24),
+        (17, 3, shstrtab_off, SHSTRTAB.len(), 0, 0, 0),
+    ];
+    for (name, sh_type, offset, size, link, info, entsize) in sections {
+        out.append_from_slice(&name.to_le_bytes())?;
+        out.append_from_slice(&sh_type.to_le_bytes())?;
+        out.append_from_slice(&0_u64.to_le_bytes())?;
+        out.append_from_slice(&0_u64.to_le_bytes())?;
+        out.append_from_slice(&(offset as u64).to_le_bytes())?;
+        out.append_from_slice(&(size as u64).to_le_bytes())?;
+        out.append_from_slice(&link.to_le_bytes())?;
+        out.append_from_slice(&info.to_le_bytes())?;
+        out.append_from_slice(&1_u64.to_le_bytes())?;
+        out.append_from_slice(&entsize.to_le_bytes())?;
+    }
+    debug_assert_eq!(out.len() - base, shoff + 4 * 0x40);
+    Ok(())
+}
+
+/* zip archive with stored entries of random content named
+ * fXXXXXXXX.bin */
+pub fn zip_corpus(
+    entry_count: u16,
+    entry_size: u32,
+    out: &mut Vector<'_, u8>,
+) -> Result<(), AllocError> {
+    const NAME_LEN: u16 = 1 + 8 + 4;
+    let base = out.len();
+    let a = out.allocator();
+    let mut content: Vector<'_, u8> = Vector::new(a);
+    random_bytes(entry_size as usize, 0x5EED, &mut content)?;
+    let crc = Crc32::of(content.as_slice());
+    let mut central: Vector<'_, u8> = Vector::new(a);
+    for i in 0..entry_count {
+        let offset = (out.len() - base) as u32;
+        for (dir, v) in [(false, &mut *out), (true, &mut central)] {
+            v.append_from_slice(if dir { b"PK\x01\x02\x14\x00" } else { b"PK\x03\x04" })?;
+            v.append_from_slice(&[0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0])?;
+            v.append_from_slice(&crc.to_le_bytes())?;
+            v.append_from_slice(&entry_size.to_le_bytes())?;
+            v.append_from_slice(&entry_size.to_le_bytes())?;
+            v.append_from_slice(&NAME_LEN.to_le_bytes())?;
+            v.append_from_slice(&0_u16.to_le_bytes())?;
+            if dir {
+                v.append_from_slice(&[0; 10])?;
+                v.append_from_slice(&offset.to_le_bytes())?;
+            }
+            hex_name(b"f", i as u32, b".bin", v)?;
+        }
+        out.append_from_slice(content.as_slice())?;
+    }
+    let cd_offset = (out.len() - base) as u32;
+    out.append_from_slice(central.as_slice())?;
+    out.append_from_slice(b"PK\x05\x06\0\0\0\0")?;
+    out.append_from_slice(&entry_count.to_le_bytes())?;
+    out.append_from_slice(&entry_count.to_le_bytes())?;
+    out.append_from_slice(&(central.len() as u32).to_le_bytes())?;
+    out.append_from_slice(&cd_offset.to_le_bytes())?;
+    out.append_from_slice(&0_u16.to_le_bytes())
+}
+
+/* gzip stream of random content in stored blocks, which keeps the
+ * generator free of a compressor */
+pub fn gzip_stored_corpus(len: usize, out: &mut Vector<'_, u8>) -> Result<(), AllocError> {
+    let mut content: Vector<'_, u8> = Vector::new(out.allocator());
+    random_bytes(len, 0x6A1B, &mut content)?;
+    out.append_from_slice(b"\x1F\x8B\x08\0\0\0\0\0\0\xFF")?;
+    let mut blocks = content.as_slice().chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.append_from_slice(b"\x01\0\0\xFF\xFF")?;
+    }
+    while let Some(b) = blocks.next() {
+        out.append_from_slice(&[blocks.peek().is_none() as u8])?;
+        out.append_from_slice(&(b.len() as u16).to_le_bytes())?;
+        out.append_from_slice(&(!(b.len() as u16)).to_le_bytes())?;
+        out.append_from_slice(b)?;
+    }
+    out.append_from_slice(&Crc32::of(content.as_slice()).to_le_bytes())?;
+    out.append_from_slice(&(len as u32).to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corpora_parse_back() {
+        let a = Malloc::new();
+        let mut v: Vector<u8> = Vector::new(a.to_ref());
+        elf64_corpus(300, &mut v).unwrap();
+        assert_eq!(tof_identify(v.as_slice()), 1);
+        assert_eq!(parse_elf(v.as_slice()), 4 + 301);
+
+        let mut v: Vector<u8> = Vector::new(a.to_ref());
+        zip_corpus(20, 100, &mut v).unwrap();
+        assert_eq!(parse_zip(v.as_slice()), 20);
+        assert_eq!(parse_pe(v.as_slice()), 0);
+
+        let mut v: Vector<u8> = Vector::new(a.to_ref());
+        gzip_stored_corpus(0x12345, &mut v).unwrap();
+        assert_eq!(gunzip(v.as_slice()), 0x12345);
+        assert_eq!(read_u32le(&v.as_slice()[0..11]), 2);
+
+        let mut x: Vector<u8> = Vector::new(a.to_ref());
+        let mut y: Vector<u8> = Vector::new(a.to_ref());
+        random_bytes(13, 7, &mut x).unwrap();
+        random_bytes(13, 7, &mut y).unwrap();
+        assert_eq!(x.len(), 13);
+        assert_eq!(x.as_slice(), y.as_slice());
+    }
+}
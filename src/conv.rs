@@ -1,5 +1,21 @@
 use crate::num::PrimitiveInt;
 use crate::num::BITS_PER_BYTE;
+use crate::io::ErrorCode;
+use crate::io::IOPartialError;
+use crate::io::IOPartialResult;
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::io::stream::Write;
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::String;
+use crate::mm::string::Utf8Decoder;
+use crate::mm::Vector;
+use crate::ExecutionContext;
+use crate::xc_err;
+use core::fmt;
+use core::hash::Hash;
+use core::hash::Hasher;
 
 pub fn int_le_decode<T: PrimitiveInt>(src: &[u8]) -> Option<T> {
     if src.len() < T::SIZE {
@@ -20,22 +36,964 @@ pub fn int_be_decode<T: PrimitiveInt>(src: &[u8]) -> Option<T> {
         None
     } else {
         let mut v = T::ZERO;
+        let mut sh = T::SIZE * BITS_PER_BYTE;
         for b in src[..T::SIZE].iter() {
-            v = (v << BITS_PER_BYTE) | T::reinterpret_u8(*b);
+            sh -= BITS_PER_BYTE;
+            v = v | (T::reinterpret_u8(*b) << sh);
         }
         Some(v)
     }
 }
 
+/* write the value in the first T::SIZE bytes of dst; None if it is shorter */
+pub fn int_le_encode<T: PrimitiveInt>(v: T, dst: &mut [u8]) -> Option<()> {
+    let dst = dst.get_mut(..T::SIZE)?;
+    for (i, b) in dst.iter_mut().enumerate() {
+        *b = (v >> (i * BITS_PER_BYTE)).trunc_to_u8();
+    }
+    Some(())
+}
+
+pub fn int_be_encode<T: PrimitiveInt>(v: T, dst: &mut [u8]) -> Option<()> {
+    let dst = dst.get_mut(..T::SIZE)?;
+    for (i, b) in dst.iter_mut().rev().enumerate() {
+        *b = (v >> (i * BITS_PER_BYTE)).trunc_to_u8();
+    }
+    Some(())
+}
+
+/* Endianness ***************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+
+    pub fn from_big_endian_flag(big_endian: bool) -> Self {
+        if big_endian { Endianness::Big } else { Endianness::Little }
+    }
+
+    pub fn decode<T: PrimitiveInt>(self, src: &[u8]) -> Option<T> {
+        match self {
+            Endianness::Little => int_le_decode(src),
+            Endianness::Big => int_be_decode(src),
+        }
+    }
+
+    pub fn encode<T: PrimitiveInt>(self, v: T, dst: &mut [u8]) -> Option<()> {
+        match self {
+            Endianness::Little => int_le_encode(v, dst),
+            Endianness::Big => int_be_encode(v, dst),
+        }
+    }
+
+}
+
+/* OutOfBounds **************************************************************/
+/* an integer at the given offset does not fit in the slice */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub offset: usize,
+    pub size: usize,
+    pub len: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} bytes at offset {} past the end of {} bytes",
+            self.size, self.offset, self.len)
+    }
+}
+
+fn out_of_bounds<T: PrimitiveInt>(offset: usize, len: usize) -> OutOfBounds {
+    OutOfBounds { offset, size: T::SIZE, len }
+}
+
+pub fn try_decode_at<T: PrimitiveInt>(
+    src: &[u8],
+    offset: usize,
+    endianness: Endianness,
+) -> Result<T, OutOfBounds> {
+    src.get(offset..)
+        .and_then(|s| endianness.decode(s))
+        .ok_or_else(|| out_of_bounds::<T>(offset, src.len()))
+}
+
+/* like try_decode_at() but panics when the integer is out of bounds, the
+ * way slice indexing does */
+pub fn decode_at<T: PrimitiveInt>(src: &[u8], offset: usize, endianness: Endianness) -> T {
+    match try_decode_at(src, offset, endianness) {
+        Ok(v) => v,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/* unsigned integer of size bytes, for sizes only known at run time (like
+ * fields that depend on the ELF class or DWARF forms); sizes above 8 are
+ * reported as out of bounds */
+pub fn try_decode_uint_at(
+    src: &[u8],
+    offset: usize,
+    size: usize,
+    endianness: Endianness,
+) -> Result<u64, OutOfBounds> {
+    let b = offset.checked_add(size)
+        .filter(|_| size <= 8)
+        .and_then(|end| src.get(offset..end))
+        .ok_or(OutOfBounds { offset, size, len: src.len() })?;
+    let mut raw = [0_u8; 8];
+    Ok(match endianness {
+        Endianness::Little => {
+            raw[..size].copy_from_slice(b);
+            u64::from_le_bytes(raw)
+        },
+        Endianness::Big => {
+            raw[8 - size..].copy_from_slice(b);
+            u64::from_be_bytes(raw)
+        },
+    })
+}
+
+pub fn decode_uint_at(src: &[u8], offset: usize, size: usize, endianness: Endianness) -> u64 {
+    match try_decode_uint_at(src, offset, size, endianness) {
+        Ok(v) => v,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_encode_at<T: PrimitiveInt>(
+    dst: &mut [u8],
+    offset: usize,
+    v: T,
+    endianness: Endianness,
+) -> Result<(), OutOfBounds> {
+    let len = dst.len();
+    dst.get_mut(offset..)
+        .and_then(|d| endianness.encode(v, d))
+        .ok_or_else(|| out_of_bounds::<T>(offset, len))
+}
+
+pub fn encode_at<T: PrimitiveInt>(dst: &mut [u8], offset: usize, v: T, endianness: Endianness) {
+    if let Err(e) = try_encode_at(dst, offset, v, endianness) {
+        panic!("{}", e);
+    }
+}
+
+/* IEEE 754 values from the bytes of their bit patterns */
+pub fn f32_le_decode(src: &[u8]) -> Option<f32> {
+    int_le_decode::<u32>(src).map(f32::from_bits)
+}
+pub fn f32_be_decode(src: &[u8]) -> Option<f32> {
+    int_be_decode::<u32>(src).map(f32::from_bits)
+}
+pub fn f64_le_decode(src: &[u8]) -> Option<f64> {
+    int_le_decode::<u64>(src).map(f64::from_bits)
+}
+pub fn f64_be_decode(src: &[u8]) -> Option<f64> {
+    int_be_decode::<u64>(src).map(f64::from_bits)
+}
+
+/* signed value of the low bit_count bits of n, for signed fields narrower
+ * than 64 bits that were read as unsigned */
+pub fn sign_extend(n: u64, bit_count: u32) -> i64 {
+    if bit_count == 0 || bit_count >= 64 {
+        return n as i64;
+    }
+    let sh = 64 - bit_count;
+    ((n << sh) as i64) >> sh
+}
+
+/* the value as the other type, if it fits */
+pub fn u64_to_i64(n: u64) -> Option<i64> {
+    if n <= i64::MAX as u64 { Some(n as i64) } else { None }
+}
+pub fn i64_to_u64(n: i64) -> Option<u64> {
+    if n >= 0 { Some(n as u64) } else { None }
+}
+
+/* Utf8Validator ************************************************************/
+/* validates UTF-8 fed in chunks (for instance from a Read loop); errors
+ * carry the offset of the first invalid byte */
+pub use crate::mm::string::Utf8Decoder as Utf8Validator;
+pub use crate::mm::string::Utf8DecodeError as Utf8ValidationError;
+
+/* Utf8LossyWriter **********************************************************/
+/* forwards bytes to the inner writer replacing invalid UTF-8 sequences with
+ * U+FFFD (one per maximal invalid subpart, same as String::from_utf8_lossy
+ * in std); chars split across writes are held until completed */
+pub struct Utf8LossyWriter<'w> {
+    out: &'w mut (dyn Write + 'w),
+    decoder: Utf8Decoder,
+    pending: [u8; 4],
+    pending_len: usize,
+    replacement_count: u64,
+}
+
+const REPLACEMENT_CHAR: &[u8] = "\u{FFFD}".as_bytes();
+
+impl<'w> Utf8LossyWriter<'w> {
+
+    pub fn new(out: &'w mut (dyn Write + 'w)) -> Self {
+        Utf8LossyWriter {
+            out,
+            decoder: Utf8Decoder::new(),
+            pending: [0; 4],
+            pending_len: 0,
+            replacement_count: 0,
+        }
+    }
+
+    /* number of replacement chars written so far */
+    pub fn replacement_count(&self) -> u64 {
+        self.replacement_count
+    }
+
+    fn replace<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        self.pending_len = 0;
+        self.replacement_count += 1;
+        self.out.write_all(REPLACEMENT_CHAR, xc).map_err(|e| e.to_error())
+    }
+
+    /* writes a replacement char for a char left incomplete at the end of
+     * the input */
+    pub fn finish<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if self.pending_len != 0 {
+            self.decoder.recover();
+            self.replace(xc)?;
+        }
+        Ok(())
+    }
+
+}
+
+impl<'w> Write for Utf8LossyWriter<'w> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let mut valid_start = 0;
+        let mut i = 0;
+        while i < buf.len() {
+            let at_boundary = self.decoder.is_at_char_boundary();
+            if self.decoder.feed(&buf[i..i + 1]).is_ok() {
+                if !self.decoder.is_at_char_boundary() {
+                    if at_boundary {
+                        self.out.write_all(&buf[valid_start..i], xc)
+                            .map_err(|e| e.to_error())?;
+                    }
+                    self.pending[self.pending_len] = buf[i];
+                    self.pending_len += 1;
+                    valid_start = i + 1;
+                } else if self.pending_len != 0 {
+                    self.pending[self.pending_len] = buf[i];
+                    let n = self.pending_len + 1;
+                    self.pending_len = 0;
+                    self.out.write_all(&self.pending[0..n], xc)
+                        .map_err(|e| e.to_error())?;
+                    valid_start = i + 1;
+                }
+                i += 1;
+                continue;
+            }
+            if at_boundary {
+                self.out.write_all(&buf[valid_start..i], xc)
+                    .map_err(|e| e.to_error())?;
+                i += 1;
+            }
+            self.decoder.recover();
+            self.replace(xc)?;
+            valid_start = i;
+        }
+        self.out.write_all(&buf[valid_start..], xc).map_err(|e| e.to_error())?;
+        Ok(buf.len())
+    }
+}
+
+/* DecodeError **************************************************************/
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    Alloc(AllocError),
+    InvalidEscape(usize), // offset of the bad escape sequence in the input
+    InvalidChar(usize), // offset of a char that does not belong to the encoding
+    UnexpectedEnd(usize), // input length, when it ends in the middle of a group
+}
+
+impl From<AllocError> for DecodeError {
+    fn from(e: AllocError) -> Self {
+        DecodeError::Alloc(e)
+    }
+}
+
+impl<T> From<(AllocError, T)> for DecodeError {
+    fn from(e: (AllocError, T)) -> Self {
+        DecodeError::Alloc(e.0)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Alloc(e) => write!(fmt, "decode failed: {}", e),
+            DecodeError::InvalidEscape(offset) =>
+                write!(fmt, "invalid escape sequence at offset {}", offset),
+            DecodeError::InvalidChar(offset) =>
+                write!(fmt, "invalid char at offset {}", offset),
+            DecodeError::UnexpectedEnd(offset) =>
+                write!(fmt, "incomplete data at offset {}", offset),
+        }
+    }
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+fn hex_digit_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+/* percent encoding *********************************************************/
+/* keeps the unreserved chars of RFC 3986 (letters, digits, "-._~"), all
+ * other bytes become %XX */
+pub fn percent_encode<'a>(
+    src: &[u8],
+    allocator: AllocatorRef<'a>,
+) -> Result<String<'a>, AllocError> {
+    let mut s = String::new(allocator);
+    for &b in src {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            s.push(b as char)?;
+        } else {
+            s.push('%')?;
+            s.push(HEX_DIGITS[(b >> 4) as usize] as char)?;
+            s.push(HEX_DIGITS[(b & 15) as usize] as char)?;
+        }
+    }
+    Ok(s)
+}
+
+/* decodes %XX sequences (either letter case); other bytes are copied as
+ * they are */
+pub fn percent_decode<'a>(
+    src: &[u8],
+    allocator: AllocatorRef<'a>,
+) -> Result<Vector<'a, u8>, DecodeError> {
+    let mut v = Vector::new(allocator);
+    v.reserve(src.len())?;
+    let mut i = 0;
+    while i < src.len() {
+        let b = src[i];
+        if b != b'%' {
+            v.push(b)?;
+            i += 1;
+            continue;
+        }
+        let hi = src.get(i + 1).copied().and_then(hex_digit_value);
+        let lo = src.get(i + 2).copied().and_then(hex_digit_value);
+        match (hi, lo) {
+            (Some(hi), Some(lo)) => v.push((hi << 4) | lo)?,
+            _ => return Err(DecodeError::InvalidEscape(i)),
+        }
+        i += 3;
+    }
+    Ok(v)
+}
+
+/* C escapes ****************************************************************/
+/* produces the content of a C string literal: printable ASCII is kept,
+ * quotes, backslash and the second '?' of "??" (trigraphs) are escaped,
+ * the usual control chars use their letter escapes and any other byte is
+ * written as 3 octal digits, which unlike \x cannot swallow a following
+ * digit */
+pub fn c_escape<'a>(
+    src: &[u8],
+    allocator: AllocatorRef<'a>,
+) -> Result<String<'a>, AllocError> {
+    let mut s = String::new(allocator);
+    let mut prev = 0_u8;
+    for &b in src {
+        let named = match b {
+            0x07 => Some('a'),
+            0x08 => Some('b'),
+            0x0C => Some('f'),
+            b'\n' => Some('n'),
+            b'\r' => Some('r'),
+            b'\t' => Some('t'),
+            0x0B => Some('v'),
+            b'\\' | b'"' | b'\'' => Some(b as char),
+            b'?' if prev == b'?' => Some('?'),
+            _ => None,
+        };
+        if let Some(c) = named {
+            s.push('\\')?;
+            s.push(c)?;
+        } else if (0x20..0x7F).contains(&b) {
+            s.push(b as char)?;
+        } else {
+            s.push('\\')?;
+            s.push((b'0' + (b >> 6)) as char)?;
+            s.push((b'0' + ((b >> 3) & 7)) as char)?;
+            s.push((b'0' + (b & 7)) as char)?;
+        }
+        prev = b;
+    }
+    Ok(s)
+}
+
+/* decodes the escapes valid in C string literals: the letter escapes,
+ * 1 to 3 octal digits and \x with any number of hex digits as long as the
+ * value fits in a byte */
+pub fn c_unescape<'a>(
+    src: &[u8],
+    allocator: AllocatorRef<'a>,
+) -> Result<Vector<'a, u8>, DecodeError> {
+    let mut v = Vector::new(allocator);
+    v.reserve(src.len())?;
+    let mut i = 0;
+    while i < src.len() {
+        if src[i] != b'\\' {
+            v.push(src[i])?;
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        let b = match src.get(i).copied() {
+            Some(b'a') => 0x07,
+            Some(b'b') => 0x08,
+            Some(b'f') => 0x0C,
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'v') => 0x0B,
+            Some(c @ (b'\\' | b'"' | b'\'' | b'?')) => c,
+            Some(b'0'..=b'7') => {
+                let mut n = 0_u32;
+                let mut digits = 0;
+                while digits < 3 {
+                    match src.get(i) {
+                        Some(&d @ b'0'..=b'7') => n = n * 8 + (d - b'0') as u32,
+                        _ => break,
+                    }
+                    i += 1;
+                    digits += 1;
+                }
+                if n > 0xFF {
+                    return Err(DecodeError::InvalidEscape(start));
+                }
+                v.push(n as u8)?;
+                continue;
+            },
+            Some(b'x') => {
+                i += 1;
+                let mut n = 0_u32;
+                let digits_start = i;
+                while let Some(d) = src.get(i).copied().and_then(hex_digit_value) {
+                    n = n * 16 + d as u32;
+                    if n > 0xFF {
+                        return Err(DecodeError::InvalidEscape(start));
+                    }
+                    i += 1;
+                }
+                if i == digits_start {
+                    return Err(DecodeError::InvalidEscape(start));
+                }
+                v.push(n as u8)?;
+                continue;
+            },
+            _ => return Err(DecodeError::InvalidEscape(start)),
+        };
+        v.push(b)?;
+        i += 1;
+    }
+    Ok(v)
+}
+
+/* base64 and hex ***********************************************************/
+/* Strict takes only the canonical text: no whitespace, base64 padded to a
+ * multiple of 4 chars and hex with an even count of digits; Relaxed skips
+ * ASCII whitespace and takes base64 without its padding */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DecodeMode {
+    Strict,
+    Relaxed,
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/* streams are converted in chunks of this size, a multiple of 3 so that
+ * base64 padding only shows up at the end */
+const CONV_CHUNK_SIZE: usize = 3 * 0x200;
+
+fn base64_value(b: u8) -> Option<u32> {
+    match b {
+        b'A'..=b'Z' => Some((b - b'A') as u32),
+        b'a'..=b'z' => Some((b - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((b - b'0') as u32 + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn is_skipped(mode: DecodeMode, b: u8) -> bool {
+    mode == DecodeMode::Relaxed && matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+/* appends the encoding of src to dst; all groups but the last must be
+ * whole when a stream is encoded in pieces */
+fn base64_encode_into<E>(
+    src: &[u8],
+    dst: &mut dyn FnMut(u8) -> Result<(), E>,
+) -> Result<(), E> {
+    for group in src.chunks(3) {
+        let mut n = 0_u32;
+        for (i, &b) in group.iter().enumerate() {
+            n |= (b as u32) << (16 - 8 * i);
+        }
+        for i in 0..4 {
+            if i <= group.len() {
+                dst(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 63) as usize])?;
+            } else {
+                dst(b'=')?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/* Base64Decoder ************************************************************/
+/* takes the text in any number of pieces; offsets in errors count from the
+ * start of the whole text */
+#[derive(Debug)]
+pub struct Base64Decoder {
+    mode: DecodeMode,
+    acc: u32,
+    count: u8, // chars of the current group
+    padding: u8,
+    offset: usize,
+}
+
+impl Base64Decoder {
+
+    pub fn new(mode: DecodeMode) -> Self {
+        Base64Decoder { mode, acc: 0, count: 0, padding: 0, offset: 0 }
+    }
+
+    /* bytes of a group cut short by padding or by the end */
+    fn flush(&mut self, dst: &mut [u8]) -> usize {
+        let n = match self.count {
+            2 => { dst[0] = (self.acc >> 4) as u8; 1 },
+            3 => {
+                dst[0] = (self.acc >> 10) as u8;
+                dst[1] = (self.acc >> 2) as u8;
+                2
+            },
+            _ => 0,
+        };
+        self.count = 0;
+        n
+    }
+
+    /* dst must have room for as many bytes as src; returns the count of
+     * bytes decoded */
+    pub fn feed(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeError> {
+        let mut n = 0;
+        for &b in src {
+            let offset = self.offset;
+            self.offset += 1;
+            if is_skipped(self.mode, b) {
+                continue;
+            }
+            if b == b'=' {
+                if self.count + self.padding < 2 || self.count + self.padding >= 4 {
+                    return Err(DecodeError::InvalidChar(offset));
+                }
+                self.padding += 1;
+                if self.count + self.padding == 4 {
+                    n += self.flush(&mut dst[n..]);
+                    self.padding = 4;
+                }
+                continue;
+            }
+            let v = match base64_value(b) {
+                Some(v) if self.padding == 0 => v,
+                _ => return Err(DecodeError::InvalidChar(offset)),
+            };
+            self.acc = (self.acc << 6) | v;
+            self.count += 1;
+            if self.count == 4 {
+                dst[n] = (self.acc >> 16) as u8;
+                dst[n + 1] = (self.acc >> 8) as u8;
+                dst[n + 2] = self.acc as u8;
+                n += 3;
+                self.count = 0;
+            }
+        }
+        Ok(n)
+    }
+
+    /* the bytes of an unpadded last group, which only Relaxed takes */
+    pub fn finish(&mut self, dst: &mut [u8; 2]) -> Result<usize, DecodeError> {
+        match (self.count, self.padding, self.mode) {
+            (0, 0, _) | (0, 4, _) => Ok(0),
+            (2, 0, DecodeMode::Relaxed) | (3, 0, DecodeMode::Relaxed) => Ok(self.flush(dst)),
+            _ => Err(DecodeError::UnexpectedEnd(self.offset)),
+        }
+    }
+
+}
+
+/* HexDecoder ***************************************************************/
+#[derive(Debug)]
+pub struct HexDecoder {
+    mode: DecodeMode,
+    high: Option<u8>,
+    offset: usize,
+}
+
+impl HexDecoder {
+
+    pub fn new(mode: DecodeMode) -> Self {
+        HexDecoder { mode, high: None, offset: 0 }
+    }
+
+    /* digits of either letter case; dst must have room for half as many
+     * bytes as src, rounded up */
+    pub fn feed(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeError> {
+        let mut n = 0;
+        for &b in src {
+            let offset = self.offset;
+            self.offset += 1;
+            if is_skipped(self.mode, b) {
+                continue;
+            }
+            let d = hex_digit_value(b).ok_or(DecodeError::InvalidChar(offset))?;
+            match self.high.take() {
+                None => self.high = Some(d),
+                Some(h) => {
+                    dst[n] = (h << 4) | d;
+                    n += 1;
+                },
+            }
+        }
+        Ok(n)
+    }
+
+    pub fn finish(&mut self) -> Result<(), DecodeError> {
+        match self.high {
+            None => Ok(()),
+            Some(_) => Err(DecodeError::UnexpectedEnd(self.offset)),
+        }
+    }
+
+}
+
+pub fn base64_encode<'a>(
+    src: &[u8],
+    allocator: AllocatorRef<'a>,
+) -> Result<String<'a>, AllocError> {
+    let mut s = String::new(allocator);
+    base64_encode_into(src, &mut |b| s.push(b as char))?;
+    Ok(s)
+}
+
+pub fn base64_decode<'a>(
+    src: &[u8],
+    mode: DecodeMode,
+    allocator: AllocatorRef<'a>,
+) -> Result<Vector<'a, u8>, DecodeError> {
+    let mut v = Vector::new(allocator);
+    v.reserve(src.len() / 4 * 3 + 2)?;
+    let mut d = Base64Decoder::new(mode);
+    let mut buf = [0_u8; CONV_CHUNK_SIZE];
+    for chunk in src.chunks(CONV_CHUNK_SIZE) {
+        let n = d.feed(chunk, &mut buf)?;
+        v.append_from_slice(&buf[0..n])?;
+    }
+    let mut tail = [0_u8; 2];
+    let n = d.finish(&mut tail)?;
+    v.append_from_slice(&tail[0..n])?;
+    Ok(v)
+}
+
+/* uppercase digits, as percent_encode() uses */
+pub fn hex_encode<'a>(
+    src: &[u8],
+    allocator: AllocatorRef<'a>,
+) -> Result<String<'a>, AllocError> {
+    let mut s = String::new(allocator);
+    for &b in src {
+        s.push(HEX_DIGITS[(b >> 4) as usize] as char)?;
+        s.push(HEX_DIGITS[(b & 15) as usize] as char)?;
+    }
+    Ok(s)
+}
+
+pub fn hex_decode<'a>(
+    src: &[u8],
+    mode: DecodeMode,
+    allocator: AllocatorRef<'a>,
+) -> Result<Vector<'a, u8>, DecodeError> {
+    let mut v = Vector::new(allocator);
+    v.reserve(src.len() / 2)?;
+    let mut d = HexDecoder::new(mode);
+    let mut buf = [0_u8; CONV_CHUNK_SIZE];
+    for chunk in src.chunks(CONV_CHUNK_SIZE) {
+        let n = d.feed(chunk, &mut buf)?;
+        v.append_from_slice(&buf[0..n])?;
+    }
+    d.finish()?;
+    Ok(v)
+}
+
+/* the stream converters return the count of bytes written; errors carry the
+ * count of input bytes consumed before them, which for InvalidData is the
+ * offset of the text that does not decode */
+fn decode_io_error<'a>(
+    e: DecodeError,
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialError<'a> {
+    match e {
+        DecodeError::Alloc(_) => IOPartialError::from_parts(
+            ErrorCode::NoSpace, 0, String::map_str("decode failed")),
+        DecodeError::InvalidChar(o) | DecodeError::InvalidEscape(o)
+        | DecodeError::UnexpectedEnd(o) =>
+            xc_err!(xc, (ErrorCode::InvalidData, o), "invalid encoded data", "{}", e),
+    }
+}
+
+fn encode_stream<'a>(
+    src: &mut (dyn Read + '_),
+    dst: &mut (dyn Write + '_),
+    encode: fn(&[u8], &mut [u8]) -> usize,
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, u64> {
+    let mut buf = [0_u8; CONV_CHUNK_SIZE];
+    let mut text = [0_u8; CONV_CHUNK_SIZE * 2];
+    let mut consumed = 0_usize;
+    let mut written = 0_u64;
+    loop {
+        let n = src.read_uninterrupted(&mut buf, xc)
+            .map_err(|e| IOPartialError::from_parts(
+                e.get_error_code(), consumed + e.get_processed_size(), e.to_parts().1))?;
+        let m = encode(&buf[0..n], &mut text);
+        dst.write_all(&text[0..m], xc)
+            .map_err(|e| IOPartialError::from_error_and_size(e.to_error(), consumed))?;
+        consumed += n;
+        written += m as u64;
+        if n < buf.len() {
+            return Ok(written);
+        }
+    }
+}
+
+pub fn base64_encode_stream<'a>(
+    src: &mut (dyn Read + '_),
+    dst: &mut (dyn Write + '_),
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, u64> {
+    encode_stream(src, dst, |data, text| {
+        let mut n = 0;
+        let _ = base64_encode_into(data, &mut |b| -> Result<(), ()> {
+            text[n] = b;
+            n += 1;
+            Ok(())
+        });
+        n
+    }, xc)
+}
+
+pub fn hex_encode_stream<'a>(
+    src: &mut (dyn Read + '_),
+    dst: &mut (dyn Write + '_),
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, u64> {
+    encode_stream(src, dst, |data, text| {
+        for (i, &b) in data.iter().enumerate() {
+            text[2 * i] = HEX_DIGITS[(b >> 4) as usize];
+            text[2 * i + 1] = HEX_DIGITS[(b & 15) as usize];
+        }
+        2 * data.len()
+    }, xc)
+}
+
+/* Decoder ******************************************************************/
+trait Decoder {
+    fn feed(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeError>;
+    fn finish(&mut self, dst: &mut [u8; 2]) -> Result<usize, DecodeError>;
+}
+
+impl Decoder for Base64Decoder {
+    fn feed(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeError> {
+        Base64Decoder::feed(self, src, dst)
+    }
+    fn finish(&mut self, dst: &mut [u8; 2]) -> Result<usize, DecodeError> {
+        Base64Decoder::finish(self, dst)
+    }
+}
+
+impl Decoder for HexDecoder {
+    fn feed(&mut self, src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeError> {
+        HexDecoder::feed(self, src, dst)
+    }
+    fn finish(&mut self, _dst: &mut [u8; 2]) -> Result<usize, DecodeError> {
+        HexDecoder::finish(self).map(|_| 0)
+    }
+}
+
+fn decode_stream<'a>(
+    src: &mut (dyn Read + '_),
+    dst: &mut (dyn Write + '_),
+    decoder: &mut dyn Decoder,
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, u64> {
+    let mut text = [0_u8; CONV_CHUNK_SIZE];
+    let mut buf = [0_u8; CONV_CHUNK_SIZE];
+    let mut consumed = 0_usize;
+    let mut written = 0_u64;
+    loop {
+        let n = src.read_uninterrupted(&mut text, xc)
+            .map_err(|e| IOPartialError::from_parts(
+                e.get_error_code(), consumed + e.get_processed_size(), e.to_parts().1))?;
+        let mut m = decoder.feed(&text[0..n], &mut buf).map_err(|e| decode_io_error(e, xc))?;
+        if n < text.len() {
+            let mut tail = [0_u8; 2];
+            let t = decoder.finish(&mut tail).map_err(|e| decode_io_error(e, xc))?;
+            buf[m..m + t].copy_from_slice(&tail[0..t]);
+            m += t;
+        }
+        dst.write_all(&buf[0..m], xc)
+            .map_err(|e| IOPartialError::from_error_and_size(e.to_error(), consumed))?;
+        consumed += n;
+        written += m as u64;
+        if n < text.len() {
+            return Ok(written);
+        }
+    }
+}
+
+pub fn base64_decode_stream<'a>(
+    src: &mut (dyn Read + '_),
+    dst: &mut (dyn Write + '_),
+    mode: DecodeMode,
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, u64> {
+    decode_stream(src, dst, &mut Base64Decoder::new(mode), xc)
+}
+
+pub fn hex_decode_stream<'a>(
+    src: &mut (dyn Read + '_),
+    dst: &mut (dyn Write + '_),
+    mode: DecodeMode,
+    xc: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, u64> {
+    decode_stream(src, dst, &mut HexDecoder::new(mode), xc)
+}
+
+/* ASCII case folding *******************************************************/
+/* only A-Z and a-z are folded, whatever the locale; other bytes, non-ASCII
+ * ones included, must match exactly */
+pub fn ascii_eq_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+pub fn starts_with_ignore_case(data: &[u8], prefix: &[u8]) -> bool {
+    data.len() >= prefix.len() && ascii_eq_ignore_case(&data[..prefix.len()], prefix)
+}
+
+pub fn ends_with_ignore_case(data: &[u8], suffix: &[u8]) -> bool {
+    data.len() >= suffix.len()
+        && ascii_eq_ignore_case(&data[data.len() - suffix.len()..], suffix)
+}
+
+/* feeds the lowercase form of data to the hasher, so that strings equal
+ * under ascii_eq_ignore_case hash the same */
+pub fn hash_ignore_case<H: Hasher>(data: &[u8], state: &mut H) {
+    let mut buf = [0_u8; 32];
+    state.write_usize(data.len());
+    for chunk in data.chunks(buf.len()) {
+        let folded = &mut buf[..chunk.len()];
+        folded.copy_from_slice(chunk);
+        folded.make_ascii_lowercase();
+        state.write(folded);
+    }
+}
+
+/* AsciiCaseless ************************************************************/
+/* byte string key that compares and hashes ignoring ASCII case */
+#[derive(Copy, Clone, Debug)]
+pub struct AsciiCaseless<'a>(pub &'a [u8]);
+
+impl PartialEq for AsciiCaseless<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        ascii_eq_ignore_case(self.0, other.0)
+    }
+}
+
+impl Eq for AsciiCaseless<'_> {}
+
+impl Hash for AsciiCaseless<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_ignore_case(self.0, state);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn ascii_case_folding() {
+        assert!(ascii_eq_ignore_case(b"LEN", b"len"));
+        assert!(!ascii_eq_ignore_case(b"len", b"lens"));
+        assert!(!ascii_eq_ignore_case(b"\xC9", b"\xE9"));
+        assert!(starts_with_ignore_case(b"GIF89a", b"gif"));
+        assert!(!starts_with_ignore_case(b"GI", b"gif"));
+        assert!(ends_with_ignore_case(b"archive.TAR.gz", b".tar.GZ"));
+        assert!(!ends_with_ignore_case(b"gz", b".gz"));
+
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut m = crate::mm::HashMap::new(a.to_ref());
+        let long = AsciiCaseless(b"Property_Name_Longer_Than_The_Fold_Buffer");
+        m.insert(AsciiCaseless(b"Length"), 1).unwrap();
+        m.insert(long, 2).unwrap();
+        assert_eq!(m.get(&AsciiCaseless(b"LENGTH")), Some(&1));
+        assert_eq!(m.get(&AsciiCaseless(b"property_name_longer_than_the_fold_buffer")), Some(&2));
+        assert_eq!(m.get(&AsciiCaseless(b"len")), None);
+    }
 
     #[test]
     fn u16le_on_truncated_buffer() {
         assert_eq!(int_le_decode::<u16>(b"\x12"), None);
     }
 
+    #[test]
+    fn float_decode_and_sign_extension() {
+        assert_eq!(f32_le_decode(b"\x00\x00\xC0\x3F"), Some(1.5));
+        assert_eq!(f32_be_decode(b"\xBF\xC0\x00\x00"), Some(-1.5));
+        assert_eq!(f64_le_decode(b"\x00\x00\x00\x00\x00\x00\xF8\x3F"), Some(1.5));
+        assert_eq!(f64_be_decode(b"\x40\x09\x21\xFB\x54\x44\x2D\x18"), Some(core::f64::consts::PI));
+        assert_eq!(f64_be_decode(b"\x40\x09"), None);
+        assert_eq!(sign_extend(0xFF, 8), -1);
+        assert_eq!(sign_extend(0x7F, 8), 127);
+        assert_eq!(sign_extend(0xFFFF_FFF0, 32), -16);
+        assert_eq!(sign_extend(u64::MAX, 64), -1);
+        assert_eq!(u64_to_i64(1 << 63), None);
+        assert_eq!(u64_to_i64(5), Some(5));
+        assert_eq!(i64_to_u64(-1), None);
+    }
+
     #[test]
     fn u16le_decode() {
         assert_eq!(int_le_decode::<u16>(b"\x12\x34").unwrap(), 0x3412);
@@ -51,4 +1009,244 @@ mod tests {
         assert_eq!(int_be_decode::<u16>(b"\x12\x34").unwrap(), 0x1234);
     }
 
+    #[test]
+    fn utf8_validator_in_chunks() {
+        let mut v = Utf8Validator::new();
+        v.feed(b"caf\xC3").unwrap();
+        v.feed(b"\xA9 ok").unwrap();
+        assert!(v.finish().is_ok());
+
+        let mut v = Utf8Validator::new();
+        v.feed(b"ab\xE2\x82").unwrap();
+        assert_eq!(v.finish().map_err(|e| e.offset()), Err(2));
+        let e: Utf8ValidationError = v.feed(b"\xFF").unwrap_err();
+        assert_eq!(e.offset(), 2);
+    }
+
+    #[test]
+    fn lossy_writer_matches_std() {
+        extern crate std;
+        let samples: [&[u8]; 8] = [
+            b"plain ascii",
+            "\u{e9}\u{20ac}\u{10348}".as_bytes(),
+            b"\xC0\x80",
+            b"\xED\xA0\x80z",
+            b"ab\xFFcd",
+            b"ab\xE2\x82(",
+            b"\xF0\x90\x8D",
+            b"\xE0\x80\xF0\x90\x8D\x88",
+        ];
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        for sample in samples.iter() {
+            let expected = std::string::String::from_utf8_lossy(sample);
+            let replacements = expected.matches('\u{FFFD}').count() as u64;
+            for chunk_len in 1..5 {
+                let mut out = xc.byte_vector();
+                {
+                    let mut w = Utf8LossyWriter::new(&mut out);
+                    for c in sample.chunks(chunk_len) {
+                        assert_eq!(w.write(c, &mut xc).unwrap(), c.len());
+                    }
+                    w.finish(&mut xc).unwrap();
+                    assert_eq!(w.replacement_count(), replacements);
+                }
+                assert_eq!(core::str::from_utf8(out.as_slice()).unwrap(), expected,
+                    "{:?} in chunks of {}", sample, chunk_len);
+            }
+        }
+    }
+
+    #[test]
+    fn percent_encode_keeps_unreserved() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let s = percent_encode(b"a-Z_0.~ /%\xFF", a.to_ref()).unwrap();
+        assert_eq!(s.as_str(), "a-Z_0.~%20%2F%25%FF");
+    }
+
+    #[test]
+    fn percent_decode_round_trip() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let data: [u8; 6] = [0, b'%', b' ', 0x7F, 0xC3, 0xA9];
+        let s = percent_encode(&data, a.to_ref()).unwrap();
+        let v = percent_decode(s.as_bytes(), a.to_ref()).unwrap();
+        assert_eq!(v.as_slice(), data);
+        assert_eq!(percent_decode(b"a%2fb+", a.to_ref()).unwrap().as_slice(), b"a/b+");
+    }
+
+    #[test]
+    fn percent_decode_rejects_bad_escapes() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        assert_eq!(percent_decode(b"ab%4", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(2));
+        assert_eq!(percent_decode(b"%G0", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(0));
+    }
+
+    #[test]
+    fn c_escape_output() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let s = c_escape(b"say \"hi\"\n\\\x00\x7F1??=", a.to_ref()).unwrap();
+        assert_eq!(s.as_str(), "say \\\"hi\\\"\\n\\\\\\000\\1771?\\?=");
+    }
+
+    #[test]
+    fn c_unescape_round_trip() {
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut data = [0_u8; 256];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        let s = c_escape(&data, a.to_ref()).unwrap();
+        let v = c_unescape(s.as_bytes(), a.to_ref()).unwrap();
+        assert_eq!(v.as_slice(), &data[..]);
+    }
+
+    #[test]
+    fn c_unescape_forms() {
+        let mut buffer = [0_u8; 0x200];
+        let a = BumpAllocator::new(&mut buffer);
+        assert_eq!(c_unescape(b"\\x41\\x4a\\101\\0x\\7\\'\\?", a.to_ref()).unwrap().as_slice(),
+                   b"AJA\0x\x07'?");
+        assert_eq!(c_unescape(b"ab\\q", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(2));
+        assert_eq!(c_unescape(b"\\x", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(0));
+        assert_eq!(c_unescape(b"\\x100", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(0));
+        assert_eq!(c_unescape(b"\\400", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(0));
+        assert_eq!(c_unescape(b"x\\", a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidEscape(1));
+    }
+
+    #[test]
+    fn base64_vectors() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        // RFC 4648 test vectors
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="), (b"fooba", "Zm9vYmE="), (b"foobar", "Zm9vYmFy"),
+        ];
+        for &(data, text) in vectors.iter() {
+            assert_eq!(base64_encode(data, a.to_ref()).unwrap().as_str(), text);
+            let v = base64_decode(text.as_bytes(), DecodeMode::Strict, a.to_ref()).unwrap();
+            assert_eq!(v.as_slice(), data);
+        }
+        let v = base64_decode(b" Zm9v\r\nYmE ", DecodeMode::Relaxed, a.to_ref()).unwrap();
+        assert_eq!(v.as_slice(), b"fooba");
+    }
+
+    #[test]
+    fn base64_errors() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let strict = |text: &[u8]| base64_decode(text, DecodeMode::Strict, a.to_ref()).unwrap_err();
+        assert_eq!(strict(b"Zm9vYmE"), DecodeError::UnexpectedEnd(7));
+        assert_eq!(strict(b"Zm9v YmE="), DecodeError::InvalidChar(4));
+        assert_eq!(strict(b"Zm=9"), DecodeError::InvalidChar(3));
+        assert_eq!(strict(b"Zm9v="), DecodeError::InvalidChar(4));
+        assert_eq!(strict(b"Zg==="), DecodeError::InvalidChar(4));
+        assert_eq!(base64_decode(b"Zg", DecodeMode::Relaxed, a.to_ref()).unwrap().as_slice(), b"f");
+        assert_eq!(base64_decode(b"Z", DecodeMode::Relaxed, a.to_ref()).unwrap_err(),
+                   DecodeError::UnexpectedEnd(1));
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let s = hex_encode(b"\x00\x7F\xAB", a.to_ref()).unwrap();
+        assert_eq!(s.as_str(), "007FAB");
+        let v = hex_decode(b"007fAB", DecodeMode::Strict, a.to_ref()).unwrap();
+        assert_eq!(v.as_slice(), b"\x00\x7F\xAB");
+        let v = hex_decode(b"00 7f\nab", DecodeMode::Relaxed, a.to_ref()).unwrap();
+        assert_eq!(v.as_slice(), b"\x00\x7F\xAB");
+        assert_eq!(hex_decode(b"00 7f", DecodeMode::Strict, a.to_ref()).unwrap_err(),
+                   DecodeError::InvalidChar(2));
+        assert_eq!(hex_decode(b"007", DecodeMode::Relaxed, a.to_ref()).unwrap_err(),
+                   DecodeError::UnexpectedEnd(3));
+    }
+
+    #[test]
+    fn stream_conversions() {
+        use crate::io::stream::BufferAsROStream;
+        use crate::io::stream::ChunkedReader;
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut data = [0_u8; 2000];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = (i * 7) as u8;
+        }
+        let mut text = xc.byte_vector();
+        let mut src = ChunkedReader::new(BufferAsROStream::new(&data), 100, 3);
+        let n = base64_encode_stream(&mut src, &mut text, &mut xc).unwrap();
+        assert_eq!(n as usize, text.len());
+        assert_eq!(text.as_slice(), base64_encode(&data, a.to_ref()).unwrap().as_bytes());
+        let mut out = xc.byte_vector();
+        let mut src = ChunkedReader::new(BufferAsROStream::new(text.as_slice()), 77, 0);
+        assert_eq!(base64_decode_stream(&mut src, &mut out, DecodeMode::Strict, &mut xc).unwrap(), 2000);
+        assert_eq!(out.as_slice(), &data[..]);
+
+        let mut text = xc.byte_vector();
+        hex_encode_stream(&mut BufferAsROStream::new(&data), &mut text, &mut xc).unwrap();
+        assert_eq!(text.len(), 4000);
+        let mut out = xc.byte_vector();
+        hex_decode_stream(&mut BufferAsROStream::new(text.as_slice()), &mut out, DecodeMode::Strict, &mut xc).unwrap();
+        assert_eq!(out.as_slice(), &data[..]);
+
+        let mut out = xc.byte_vector();
+        let e = hex_decode_stream(&mut BufferAsROStream::new(b"00112x"), &mut out, DecodeMode::Strict, &mut xc)
+            .unwrap_err();
+        assert_eq!((e.get_error_code(), e.get_processed_size()), (ErrorCode::InvalidData, 5));
+    }
+
+    #[test]
+    fn int_encode_decode_matrix() {
+        let mut b = [0_u8; 9];
+        int_le_encode(0x1234_5678_u32, &mut b).unwrap();
+        assert_eq!(b[..4], [0x78, 0x56, 0x34, 0x12]);
+        int_be_encode(-2_i16, &mut b[4..]).unwrap();
+        assert_eq!(b[4..6], [0xFF, 0xFE]);
+        assert_eq!(int_be_encode(1_u64, &mut b[2..]), None);
+        int_be_encode(0xAB_u8, &mut b[8..]).unwrap();
+        assert_eq!(int_be_decode::<u8>(&b[8..]), Some(0xAB));
+        assert_eq!(int_le_decode::<u32>(&b), Some(0x1234_5678));
+        assert_eq!(int_be_decode::<i16>(&b[4..]), Some(-2));
+
+        encode_at(&mut b, 1, 0x0102_0304_0506_0708_u64, Endianness::Big);
+        assert_eq!(decode_at::<u64>(&b, 1, Endianness::Big), 0x0102_0304_0506_0708);
+        assert_eq!(decode_at::<u64>(&b, 1, Endianness::Little), 0x0807_0605_0403_0201);
+        assert_eq!(try_decode_at::<i32>(&b, 6, Endianness::Little),
+            Err(OutOfBounds { offset: 6, size: 4, len: 9 }));
+        assert!(try_decode_at::<u8>(&b, usize::MAX, Endianness::Big).is_err());
+        assert!(try_encode_at(&mut b, 8, 0_u16, Endianness::Little).is_err());
+    }
+
+    #[test]
+    fn uint_of_run_time_size() {
+        let b = [1_u8, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert_eq!(decode_uint_at(&b, 1, 3, Endianness::Big), 0x02_0304);
+        assert_eq!(decode_uint_at(&b, 1, 3, Endianness::Little), 0x04_0302);
+        assert_eq!(decode_uint_at(&b, 1, 8, Endianness::Big), 0x0203_0405_0607_0809);
+        assert_eq!(decode_uint_at(&b, 9, 0, Endianness::Little), 0);
+        assert_eq!(try_decode_uint_at(&b, 7, 4, Endianness::Little),
+            Err(OutOfBounds { offset: 7, size: 4, len: 9 }));
+        assert!(try_decode_uint_at(&b, 0, 9, Endianness::Big).is_err());
+        assert!(try_decode_uint_at(&b, usize::MAX, 2, Endianness::Big).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn decode_at_panics_past_the_end() {
+        decode_at::<u16>(&[1, 2, 3], 2, Endianness::Big);
+    }
 }
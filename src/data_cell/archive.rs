@@ -0,0 +1,518 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::io::stream::ByteVectorStream;
+use crate::io::stream::Stream;
+use crate::mm::AllocatorRef;
+use crate::mm::Vector;
+use crate::num::fmt as num_fmt;
+
+use super::DataCell;
+use super::DataCellOps;
+use super::DataCellOpsMut;
+use super::Error;
+use super::Record;
+use super::RecordDesc;
+use super::U64Cell;
+use super::compressed::HeaderError;
+use super::content_stream::ContentStream;
+
+pub const AR_MAGIC: &[u8; 8] = b"!<arch>\n";
+pub const AR_HEADER_SIZE: usize = 60;
+pub const TAR_BLOCK_SIZE: usize = 512;
+/* members larger than this are listed without their content, which
+ * entries keep in memory */
+pub const MAX_ENTRY_CONTENT_SIZE: u64 = 64 << 20;
+
+const AR_MEMBER: RecordDesc<'static> = RecordDesc::new(
+    "ar_member",
+    &[ "name", "offset", "size", "mtime", "uid", "gid", "mode" ]);
+
+const AR_ENTRY: RecordDesc<'static> = RecordDesc::new(
+    "ar_entry",
+    &[ "name", "offset", "size", "mtime", "uid", "gid", "mode" ]);
+
+const TAR_ENTRY: RecordDesc<'static> = RecordDesc::new(
+    "tar_entry",
+    &[
+        "name", "type", "offset", "size", "mtime", "uid", "gid", "mode",
+        "linkname",
+    ]);
+
+crate::convert_rc!(byte_vector_stream_as_stream,
+                   RefCell<ByteVectorStream<'a>>, RefCell<dyn Stream + 'a>);
+
+/* unsigned number in a space padded text field; None if the field is
+ * blank, as uid and gid are in Windows import libraries */
+fn text_number(field: &[u8], radix: u64) -> Result<Option<u64>, HeaderError> {
+    let digits = trim_end(field, b' ');
+    if digits.is_empty() {
+        return Ok(None);
+    }
+    let mut v = 0_u64;
+    for &c in digits {
+        let d = (c as u64).wrapping_sub(b'0' as u64);
+        if d >= radix {
+            return Err(HeaderError::Invalid("bad number in ar member header"));
+        }
+        v = v.checked_mul(radix).and_then(|v| v.checked_add(d))
+            .ok_or(HeaderError::Invalid("number too large in ar member header"))?;
+    }
+    Ok(Some(v))
+}
+
+fn trim_end(data: &[u8], pad: u8) -> &[u8] {
+    let n = data.iter().rposition(|&c| c != pad).map_or(0, |i| i + 1);
+    &data[0..n]
+}
+
+fn octal_cell<'x>(n: u64) -> DataCell<'x> {
+    DataCell::from_u64_cell(U64Cell::with_fmt(n, num_fmt::MiniNumFmtPack::new(
+        num_fmt::Radix::new(8).unwrap(),
+        num_fmt::RadixNotation::DefaultExplicitPrefix,
+        num_fmt::MinDigitCount::new(1).unwrap(),
+        num_fmt::PositiveSign::Hidden,
+        num_fmt::ZeroSign::Hidden)))
+}
+
+/* ArName *******************************************************************/
+/* how the name field of a member header gives the member name */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArName<'h> {
+    /* the name itself, without the '/' terminator GNU adds */
+    Inline(&'h [u8]),
+    /* GNU and Windows "/N": offset N in the "//" name table */
+    Table(u64),
+    /* BSD "#1/N": the N bytes after the header, counted in the size */
+    Following(u64),
+    /* symbol tables ("/", "/SYM64/", "__.SYMDEF...") and the "//" name
+     * table, named as in the header */
+    Special(&'h [u8]),
+}
+
+/* ArMemberHeader ***********************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArMemberHeader<'h> {
+    pub name: ArName<'h>,
+    pub mtime: Option<u64>,
+    pub uid: Option<u64>,
+    pub gid: Option<u64>,
+    pub mode: Option<u64>,
+    pub size: u64, // data size, the BSD name included
+}
+
+impl<'h> ArMemberHeader<'h> {
+
+    pub fn parse(h: &'h [u8; AR_HEADER_SIZE]) -> Result<Self, HeaderError> {
+        if h[58..60] != *b"`\n" {
+            return Err(HeaderError::Invalid("bad ar member header terminator"));
+        }
+        let raw_name = trim_end(&h[0..16], b' ');
+        let name = if raw_name == b"/" || raw_name == b"//" || raw_name == b"/SYM64/"
+            || raw_name.starts_with(b"__.SYMDEF") {
+            ArName::Special(raw_name)
+        } else if let Some(len) = raw_name.strip_prefix(b"#1/") {
+            ArName::Following(text_number(len, 10)?
+                .ok_or(HeaderError::Invalid("bad BSD ar member name length"))?)
+        } else if let Some(offset) = raw_name.strip_prefix(b"/") {
+            ArName::Table(text_number(offset, 10)?
+                .ok_or(HeaderError::Invalid("bad ar name table offset"))?)
+        } else {
+            ArName::Inline(raw_name.strip_suffix(b"/").unwrap_or(raw_name))
+        };
+        Ok(ArMemberHeader {
+            name,
+            mtime: text_number(&h[16..28], 10)?,
+            uid: text_number(&h[28..34], 10)?,
+            gid: text_number(&h[34..40], 10)?,
+            mode: text_number(&h[40..48], 8)?,
+            size: text_number(&h[48..58], 10)?
+                .ok_or(HeaderError::Invalid("missing ar member size"))?,
+        })
+    }
+
+    /* record for the member with the given name, whose data (past the name
+     * for BSD members) is at offset and has the given size */
+    pub fn to_data_cell<'x>(
+        &self,
+        name: &[u8],
+        offset: u64,
+        size: u64,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let mut r = Record::new(&AR_MEMBER, a)?;
+        r.set_field("name", DataCell::from_byte_slice(a, name)?);
+        r.set_field("offset", DataCell::from_u64_cell(U64Cell::hex(offset)));
+        r.set_field("size", DataCell::from_u64(size));
+        if let Some(n) = self.mtime {
+            r.set_field("mtime", DataCell::from_u64(n));
+        }
+        if let Some(n) = self.uid {
+            r.set_field("uid", DataCell::from_u64(n));
+        }
+        if let Some(n) = self.gid {
+            r.set_field("gid", DataCell::from_u64(n));
+        }
+        if let Some(n) = self.mode {
+            r.set_field("mode", octal_cell(n));
+        }
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+/* name starting at the given offset of a GNU or Windows name table; names
+ * end with "/\n" (GNU) or NUL (Windows) */
+pub fn ar_table_name(table: &[u8], offset: u64) -> Result<&[u8], HeaderError> {
+    let rest = table.get(offset as usize..)
+        .filter(|_| offset < table.len() as u64)
+        .ok_or(HeaderError::Invalid("ar name offset outside name table"))?;
+    let end = rest.iter().position(|&c| c == b'\n' || c == 0).unwrap_or(rest.len());
+    let name = &rest[0..end];
+    Ok(name.strip_suffix(b"/").unwrap_or(name))
+}
+
+/* TarHeader ****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TarHeader<'h> {
+    pub name: &'h [u8],
+    pub prefix: &'h [u8], // ustar: directory part of long names
+    pub link_name: &'h [u8],
+    pub type_flag: u8,
+    pub mode: Option<u64>,
+    pub uid: Option<u64>,
+    pub gid: Option<u64>,
+    pub size: u64,
+    pub mtime: Option<u64>,
+}
+
+/* NUL terminated text field */
+fn c_str(field: &[u8]) -> &[u8] {
+    let n = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+    &field[0..n]
+}
+
+/* octal text padded with spaces or NULs, or a big endian base-256 number
+ * marked by the top bit of the first byte (GNU); None if blank */
+fn tar_number(field: &[u8]) -> Result<Option<u64>, HeaderError> {
+    if field.first().is_some_and(|&c| c & 0x80 != 0) {
+        let mut v = (field[0] & 0x7F) as u64;
+        for &c in &field[1..] {
+            if v >> 56 != 0 {
+                return Err(HeaderError::Invalid("number too large in tar header"));
+            }
+            v = (v << 8) | c as u64;
+        }
+        return Ok(Some(v));
+    }
+    let start = field.iter().position(|&c| c != b' ').unwrap_or(field.len());
+    let digits = &field[start..];
+    let end = digits.iter().position(|&c| c == b' ' || c == 0).unwrap_or(digits.len());
+    text_number(&digits[0..end], 8)
+        .map_err(|_| HeaderError::Invalid("bad number in tar header"))
+}
+
+impl<'h> TarHeader<'h> {
+
+    /* None for the zero blocks that end the archive */
+    pub fn parse(h: &'h [u8; TAR_BLOCK_SIZE]) -> Result<Option<Self>, HeaderError> {
+        if h.iter().all(|&c| c == 0) {
+            return Ok(None);
+        }
+        let checksum = tar_number(&h[148..156])?
+            .ok_or(HeaderError::Invalid("missing tar header checksum"))?;
+        let sum: u64 = h.iter().enumerate()
+            .map(|(i, &c)| if (148..156).contains(&i) { b' ' as u64 } else { c as u64 })
+            .sum();
+        if sum != checksum {
+            return Err(HeaderError::BadMagic);
+        }
+        let ustar = h[257..262] == *b"ustar";
+        Ok(Some(TarHeader {
+            name: c_str(&h[0..100]),
+            prefix: if ustar && h[263..265] == *b"00" { c_str(&h[345..500]) } else { b"" },
+            link_name: c_str(&h[157..257]),
+            type_flag: h[156],
+            mode: tar_number(&h[100..108])?,
+            uid: tar_number(&h[108..116])?,
+            gid: tar_number(&h[116..124])?,
+            size: tar_number(&h[124..136])?.unwrap_or(0),
+            mtime: tar_number(&h[136..148])?,
+        }))
+    }
+
+}
+
+/* calls f with key and value of each "<len> <key>=<value>\n" record of a pax
+ * extended header */
+pub fn pax_records<'d, F>(data: &'d [u8], mut f: F) -> Result<(), HeaderError>
+where F: FnMut(&'d [u8], &'d [u8]) {
+    let bad = HeaderError::Invalid("bad pax extended header record");
+    let mut rest = data;
+    while !rest.is_empty() && rest[0] != 0 {
+        let space = rest.iter().position(|&c| c == b' ').ok_or(bad)?;
+        let len = text_number(&rest[0..space], 10).map_err(|_| bad)?.ok_or(bad)? as usize;
+        if len <= space + 1 || len > rest.len() || rest[len - 1] != b'\n' {
+            return Err(bad);
+        }
+        let record = &rest[space + 1..len - 1];
+        let eq = record.iter().position(|&c| c == b'=').ok_or(bad)?;
+        f(&record[0..eq], &record[eq + 1..]);
+        rest = &rest[len..];
+    }
+    Ok(())
+}
+
+/* decimal value of a pax record; fractions of seconds are dropped */
+pub fn pax_number(value: &[u8]) -> Option<u64> {
+    let int = value.split(|&c| c == b'.').next().unwrap();
+    text_number(int, 10).ok().flatten()
+}
+
+pub fn tar_type_name(type_flag: u8) -> Option<&'static str> {
+    Some(match type_flag {
+        b'0' | 0 => "file",
+        b'1' => "hardlink",
+        b'2' => "symlink",
+        b'3' => "char_device",
+        b'4' => "block_device",
+        b'5' => "directory",
+        b'6' => "fifo",
+        b'7' => "contiguous",
+        _ => return None,
+    })
+}
+
+/* ArchiveEntry *************************************************************/
+/* member of an ar or tar archive: the fields of its header and a copy of
+ * its content, whose properties are available on the entry itself, so that
+ * expressions like ar_entries[0].elf_header look into the member */
+#[derive(Debug)]
+pub struct ArchiveEntry<'a> {
+    desc: &'static RecordDesc<'static>,
+    pub name: Vector<'a, u8>,
+    pub entry_type: Option<u8>,
+    pub link_name: Vector<'a, u8>,
+    pub offset: u64,
+    pub size: u64,
+    pub mtime: Option<u64>,
+    pub uid: Option<u64>,
+    pub gid: Option<u64>,
+    pub mode: Option<u64>,
+    content: Option<RefCell<ByteVectorStream<'a>>>,
+}
+
+impl<'a> ArchiveEntry<'a> {
+
+    fn new(
+        desc: &'static RecordDesc<'static>,
+        offset: u64,
+        size: u64,
+        allocator: AllocatorRef<'a>,
+    ) -> Self {
+        ArchiveEntry {
+            desc,
+            name: Vector::new(allocator),
+            entry_type: None,
+            link_name: Vector::new(allocator),
+            offset,
+            size,
+            mtime: None,
+            uid: None,
+            gid: None,
+            mode: None,
+            content: None,
+        }
+    }
+
+    pub fn ar(header: &ArMemberHeader, offset: u64, size: u64, allocator: AllocatorRef<'a>) -> Self {
+        let mut e = ArchiveEntry::new(&AR_ENTRY, offset, size, allocator);
+        e.mtime = header.mtime;
+        e.uid = header.uid;
+        e.gid = header.gid;
+        e.mode = header.mode;
+        e
+    }
+
+    pub fn tar(header: &TarHeader, offset: u64, size: u64, allocator: AllocatorRef<'a>) -> Self {
+        let mut e = ArchiveEntry::new(&TAR_ENTRY, offset, size, allocator);
+        e.entry_type = Some(header.type_flag);
+        e.mtime = header.mtime;
+        e.uid = header.uid;
+        e.gid = header.gid;
+        e.mode = header.mode;
+        e
+    }
+
+    pub fn set_content(&mut self, data: Vector<'a, u8>) {
+        self.content = Some(RefCell::new(ByteVectorStream::new(data)));
+    }
+
+    /* Nothing for fields the entry does not have */
+    fn field<'x>(
+        &self,
+        name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let n = |v: Option<u64>| v.map_or(DataCell::Nothing, DataCell::from_u64);
+        Ok(match name {
+            "name" => DataCell::from_byte_slice(a, self.name.as_slice())?,
+            "type" => match self.entry_type {
+                Some(t) => match tar_type_name(t) {
+                    Some(id) => DataCell::from_static_id(id),
+                    None => DataCell::from_byte_slice(a, &[t])?,
+                },
+                None => DataCell::Nothing,
+            },
+            "offset" => DataCell::from_u64_cell(U64Cell::hex(self.offset)),
+            "size" => DataCell::from_u64(self.size),
+            "mtime" => n(self.mtime),
+            "uid" => n(self.uid),
+            "gid" => n(self.gid),
+            "mode" => self.mode.map_or(DataCell::Nothing, octal_cell),
+            "linkname" if !self.link_name.is_empty() =>
+                DataCell::from_byte_slice(a, self.link_name.as_slice())?,
+            _ => DataCell::Nothing,
+        })
+    }
+
+    pub fn to_record<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Record<'x>, Error<'x>> {
+        let mut r = Record::new(self.desc, xc.get_main_allocator())?;
+        for (i, name) in self.desc.field_names.iter().enumerate() {
+            r.get_fields_mut()[i] = self.field(name, xc)?;
+        }
+        Ok(r)
+    }
+
+}
+
+impl<'a> DataCellOps for ArchiveEntry<'a> {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        if self.desc.field_index(property_name).is_some() {
+            let v = self.field(property_name, xc)?;
+            return if v.is_nothing() { Err(Error::NotApplicable) } else { Ok(v) };
+        }
+        let content = self.content.as_ref().ok_or(Error::NotApplicable)?;
+        let mut content = content.try_borrow_mut()?;
+        if property_name == "data" {
+            let mut data: Vector<'x, u8> = Vector::new(xc.get_main_allocator());
+            data.append_from_slice(content.as_ref().as_slice())?;
+            let s = xc.rc(RefCell::new(ByteVectorStream::new(data)))?;
+            return Ok(DataCell::ByteStream(byte_vector_stream_as_stream(s)));
+        }
+        ContentStream::new(&mut *content).get_property_mut(property_name, xc)
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn crate::io::stream::Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        self.to_record(xc)?.output_as_human_readable_mut(out, xc)
+    }
+
+    /* ranges of properties of the content, as offsets in the archive */
+    fn get_property_byte_range(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        if self.desc.field_index(property_name).is_some() {
+            return None;
+        }
+        let mut content = self.content.as_ref()?.try_borrow_mut().ok()?;
+        let (start, end) = ContentStream::new(&mut *content)
+            .get_property_byte_range_mut(property_name, xc)?;
+        Some((self.offset + start, self.offset + end))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(s: &[u8]) -> [u8; AR_HEADER_SIZE] {
+        let mut h = [0_u8; AR_HEADER_SIZE];
+        h.copy_from_slice(s);
+        h
+    }
+
+    #[test]
+    fn member_header_variants() {
+        let h = header(b"hello.o/        1700000000  1000  100   100644  42        `\n");
+        let m = ArMemberHeader::parse(&h).unwrap();
+        assert_eq!(m.name, ArName::Inline(b"hello.o"));
+        assert_eq!((m.mtime, m.uid, m.gid), (Some(1700000000), Some(1000), Some(100)));
+        assert_eq!((m.mode, m.size), (Some(0o100644), 42));
+
+        let h = header(b"/27             0           0     0     0       12        `\n");
+        assert_eq!(ArMemberHeader::parse(&h).unwrap().name, ArName::Table(27));
+        let h = header(b"#1/20           0           0     0     0       25        `\n");
+        assert_eq!(ArMemberHeader::parse(&h).unwrap().name, ArName::Following(20));
+        let h = header(b"//                                              12        `\n");
+        let m = ArMemberHeader::parse(&h).unwrap();
+        assert_eq!((m.name, m.mtime), (ArName::Special(b"//"), None));
+
+        let h = header(b"bad.o/          0           0     0     0       9x        `\n");
+        assert!(ArMemberHeader::parse(&h).is_err());
+        let h = header(b"bad.o/          0           0     0     0       9         `X");
+        assert!(ArMemberHeader::parse(&h).is_err());
+    }
+
+    #[test]
+    fn table_names() {
+        let table = b"first_long_name.o/\nsecond.obj\0";
+        assert_eq!(ar_table_name(table, 0).unwrap(), b"first_long_name.o");
+        assert_eq!(ar_table_name(table, 19).unwrap(), b"second.obj");
+        assert!(ar_table_name(table, 30).is_err());
+    }
+
+    #[test]
+    fn tar_headers() {
+        let mut h = [0_u8; TAR_BLOCK_SIZE];
+        h[0..5].copy_from_slice(b"a.txt");
+        h[100..108].copy_from_slice(b"0000644\0");
+        h[124..136].copy_from_slice(b"        12 \0");
+        h[136..148].copy_from_slice(b"\x80\0\0\0\0\0\0\0\x65\x53\xF1\x00");
+        h[156] = b'0';
+        h[257..265].copy_from_slice(b"ustar\x0000");
+        h[345..348].copy_from_slice(b"dir");
+        let sum: u32 = h.iter().map(|&c| c as u32).sum::<u32>() + 8 * b' ' as u32;
+        for i in 0..6 {
+            h[148 + i] = b'0' + ((sum >> (3 * (5 - i))) & 7) as u8;
+        }
+        h[154] = 0;
+        h[155] = b' ';
+        let t = TarHeader::parse(&h).unwrap().unwrap();
+        assert_eq!((t.name, t.prefix, t.type_flag), (&b"a.txt"[..], &b"dir"[..], b'0'));
+        assert_eq!((t.mode, t.size, t.mtime, t.uid), (Some(0o644), 0o12, Some(1700000000), None));
+        h[0] = b'b';
+        assert_eq!(TarHeader::parse(&h).unwrap_err(), HeaderError::BadMagic);
+        assert_eq!(TarHeader::parse(&[0; TAR_BLOCK_SIZE]).unwrap(), None);
+    }
+
+    #[test]
+    fn pax_extended_records() {
+        let mut seen = [(&b""[..], &b""[..]); 2];
+        let mut n = 0;
+        pax_records(b"30 mtime=1700000000.123456789\n21 path=dir/long.txt\n", |k, v| {
+            seen[n] = (k, v);
+            n += 1;
+        }).unwrap();
+        assert_eq!(seen, [(&b"mtime"[..], &b"1700000000.123456789"[..]), (b"path", b"dir/long.txt")]);
+        assert_eq!(pax_number(seen[0].1), Some(1700000000));
+        assert!(pax_records(b"12 path=x\n", |_, _| {}).is_err());
+        assert!(pax_records(b"7 pathx\n", |_, _| {}).is_err());
+    }
+}
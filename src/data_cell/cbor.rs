@@ -0,0 +1,662 @@
+use core::cell::RefCell;
+use core::convert::TryFrom;
+use core::ops::Deref;
+
+use crate::ExecutionContext;
+use crate::conv::Utf8LossyWriter;
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::stream::Read;
+use crate::io::stream::SeekFrom;
+use crate::io::stream::Stream;
+use crate::io::stream::Write;
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::Box;
+use crate::mm::CowBytes;
+use crate::mm::Rc;
+use crate::mm::String;
+use crate::mm::Vector;
+use crate::num::fmt::MiniNumFmtPack;
+
+use super::ByteVector;
+use super::DCOMap;
+use super::DCOVector;
+use super::DataCell;
+use super::Error;
+use super::Record;
+use super::RecordDesc;
+use super::I64Cell;
+use super::U64Cell;
+use super::U128Cell;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NEGINT: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+const BIGNUM_TAG: u64 = 2;
+const SIMPLE_FALSE: u64 = 20;
+const SIMPLE_TRUE: u64 = 21;
+const SIMPLE_NULL: u64 = 22;
+const SIMPLE_UNDEFINED: u64 = 23;
+const INFO_F32: u8 = 26;
+const INFO_F64: u8 = 27;
+const INDEFINITE_BYTES: u8 = 0x5F;
+const BREAK: u8 = 0xFF;
+
+/* tags for what plain CBOR has no room for: the name of a record, as
+ * [name, {field: value...}], the format of an integer, as [fmt, n], and
+ * ids, as text strings */
+pub const RECORD_TAG: u64 = 0x6862_0001;
+pub const FORMATTED_INT_TAG: u64 = 0x6862_0002;
+pub const ID_TAG: u64 = 0x6862_0003;
+
+const MAX_DEPTH: usize = 0x40;
+/* streams are encoded, and byte strings decoded, in chunks of this size */
+const CHUNK_SIZE: usize = 0x1000;
+
+/* encode *******************************************************************/
+fn write_head<'w, 'x>(
+    major: u8,
+    n: u64,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let mut buf = [0_u8; 9];
+    let len = if n < 24 {
+        buf[0] = n as u8;
+        1
+    } else if n <= 0xFF {
+        buf[0] = 24;
+        buf[1] = n as u8;
+        2
+    } else if n <= 0xFFFF {
+        buf[0] = 25;
+        buf[1..3].copy_from_slice(&(n as u16).to_be_bytes());
+        3
+    } else if n <= 0xFFFF_FFFF {
+        buf[0] = 26;
+        buf[1..5].copy_from_slice(&(n as u32).to_be_bytes());
+        5
+    } else {
+        buf[0] = 27;
+        buf[1..9].copy_from_slice(&n.to_be_bytes());
+        9
+    };
+    buf[0] |= major << 5;
+    out.write_all(&buf[0..len], xc)?;
+    Ok(())
+}
+
+/* CBOR negative integers store -1 - n */
+fn write_int<'w, 'x>(
+    n: i64,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if n < 0 {
+        write_head(MAJOR_NEGINT, !(n as u64), out, xc)
+    } else {
+        write_head(MAJOR_UINT, n as u64, out, xc)
+    }
+}
+
+/* always as a bignum so that the value reads back as U128 */
+fn write_bignum<'w, 'x>(
+    n: u128,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let bytes = n.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    write_head(MAJOR_TAG, BIGNUM_TAG, out, xc)?;
+    write_string(MAJOR_BYTES, &bytes[skip..], out, xc)
+}
+
+fn write_int_format<'w, 'x>(
+    fmt_pack: MiniNumFmtPack,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if fmt_pack != MiniNumFmtPack::default() {
+        write_head(MAJOR_TAG, FORMATTED_INT_TAG, out, xc)?;
+        write_head(MAJOR_ARRAY, 2, out, xc)?;
+        write_head(MAJOR_UINT, fmt_pack.to_u32() as u64, out, xc)?;
+    }
+    Ok(())
+}
+
+fn write_string<'w, 'x>(
+    major: u8,
+    data: &[u8],
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    write_head(major, data.len() as u64, out, xc)?;
+    out.write_all(data, xc)?;
+    Ok(())
+}
+
+/* streams without a content slice go as indefinite length byte strings, so
+ * that their size does not have to be known upfront */
+fn write_stream<'w, 'x>(
+    stream: &mut (dyn Stream + '_),
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if let Some(content) = stream.content_slice() {
+        return write_string(MAJOR_BYTES, content, out, xc);
+    }
+    stream.seek(SeekFrom::Start(0), xc)?;
+    out.write_all(&[INDEFINITE_BYTES], xc)?;
+    let mut buf = [0_u8; CHUNK_SIZE];
+    loop {
+        let n = stream.read_uninterrupted(&mut buf, xc)?;
+        if n != 0 {
+            write_string(MAJOR_BYTES, &buf[0..n], out, xc)?;
+        }
+        if n < buf.len() { break; }
+    }
+    out.write_all(&[BREAK], xc)?;
+    Ok(())
+}
+
+/* byte content becomes byte strings, text becomes text strings, records
+ * become tagged maps of the fields that are set; cells of other types are
+ * stored as their human readable text; as CBOR integers carry no
+ * signedness, non-negative I64 values are read back as U64 */
+pub fn encode<'w, 'x>(
+    cell: &DataCell<'_>,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    match cell {
+        DataCell::Nothing => write_head(MAJOR_SIMPLE, SIMPLE_NULL, out, xc)?,
+        DataCell::U64(v) => {
+            write_int_format(v.fmt_pack, out, xc)?;
+            write_head(MAJOR_UINT, v.n, out, xc)?;
+        },
+        DataCell::U128(v) => {
+            write_int_format(v.fmt_pack, out, xc)?;
+            write_bignum(v.n, out, xc)?;
+        },
+        DataCell::I64(v) => {
+            write_int_format(v.fmt_pack, out, xc)?;
+            write_int(v.n, out, xc)?;
+        },
+        DataCell::F64(v) => {
+            out.write_all(&[MAJOR_SIMPLE << 5 | INFO_F64], xc)?;
+            out.write_all(&v.v.to_bits().to_be_bytes(), xc)?;
+        },
+        DataCell::Bool(b) =>
+            write_head(MAJOR_SIMPLE, if *b { SIMPLE_TRUE } else { SIMPLE_FALSE }, out, xc)?,
+        DataCell::StaticId(s) => {
+            write_head(MAJOR_TAG, ID_TAG, out, xc)?;
+            write_string(MAJOR_TEXT, s.as_bytes(), out, xc)?;
+        },
+        DataCell::Text(t) => write_string(MAJOR_TEXT, t.try_borrow()?.as_bytes(), out, xc)?,
+        DataCell::ByteVector(v) =>
+            write_string(MAJOR_BYTES, v.try_borrow()?.0.as_slice(), out, xc)?,
+        DataCell::ByteStream(s) => write_stream(&mut *s.try_borrow_mut()?, out, xc)?,
+        DataCell::Dyn(o) => {
+            let mut text = xc.byte_vector();
+            {
+                let mut w = Utf8LossyWriter::new(&mut text);
+                o.deref().output_as_human_readable(&mut w, xc)?;
+                w.finish(xc)?;
+            }
+            write_string(MAJOR_TEXT, text.as_slice(), out, xc)?;
+        },
+        DataCell::CellVector(v) => {
+            let v = v.try_borrow()?;
+            write_head(MAJOR_ARRAY, v.0.len() as u64, out, xc)?;
+            for c in v.0.as_slice() {
+                encode(c, out, xc)?;
+            }
+        },
+        DataCell::Record(r) => {
+            let r = r.try_borrow()?;
+            let set_count = r.data.as_slice().iter().filter(|v| !v.is_nothing()).count();
+            write_head(MAJOR_TAG, RECORD_TAG, out, xc)?;
+            write_head(MAJOR_ARRAY, 2, out, xc)?;
+            write_string(MAJOR_TEXT, r.desc.record_name.as_bytes(), out, xc)?;
+            write_head(MAJOR_MAP, set_count as u64, out, xc)?;
+            for (&name, v) in r.desc.field_names.iter().zip(r.data.as_slice()) {
+                if v.is_nothing() { continue; }
+                write_string(MAJOR_TEXT, name.as_bytes(), out, xc)?;
+                encode(v, out, xc)?;
+            }
+        },
+        DataCell::Map(m) => {
+            let m = m.try_borrow()?;
+            write_head(MAJOR_MAP, m.0.len() as u64, out, xc)?;
+            for (k, v) in m.0.iter() {
+                write_string(MAJOR_TEXT, k.as_bytes(), out, xc)?;
+                encode(v, out, xc)?;
+            }
+        },
+    }
+    Ok(())
+}
+
+/* decode *******************************************************************/
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(ErrorCode::InvalidData, msg))
+}
+
+/* major type, additional info and argument; the argument is None for
+ * indefinite lengths and for the break code */
+fn read_head<'x>(
+    input: &mut (dyn Read + '_),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(u8, u8, Option<u64>), Error<'x>> {
+    let mut buf = [0_u8; 8];
+    input.read_exact(&mut buf[0..1], xc)?;
+    let major = buf[0] >> 5;
+    let info = buf[0] & 31;
+    let len = match info {
+        0..=23 => return Ok((major, info, Some(info as u64))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 => return Ok((major, info, None)),
+        _ => return Err(invalid("reserved CBOR additional info")),
+    };
+    input.read_exact(&mut buf[0..len], xc)?;
+    Ok((major, info, Some(buf[0..len].iter().fold(0, |v, &b| v << 8 | b as u64))))
+}
+
+fn negint<'x>(n: u64) -> Result<i64, Error<'x>> {
+    i64::try_from(n).map(|n| !n).map_err(|_| invalid("CBOR negative integer out of range"))
+}
+
+fn expect_head<'x>(
+    major: u8,
+    arg: u64,
+    input: &mut (dyn Read + '_),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let (m, _, a) = read_head(input, xc)?;
+    if (m, a) == (major, Some(arg)) {
+        Ok(())
+    } else {
+        Err(invalid("malformed tagged CBOR item"))
+    }
+}
+
+/* Decoder ******************************************************************/
+/* ids and record descriptors must live as long as the allocator since the
+ * cells only borrow them; they are leaked, once per distinct value */
+struct Decoder<'a> {
+    allocator: AllocatorRef<'a>,
+    ids: Vector<'a, &'a str>,
+    descs: Vector<'a, &'a RecordDesc<'a>>,
+}
+
+impl<'a> Decoder<'a> {
+
+    fn new(allocator: AllocatorRef<'a>) -> Self {
+        Decoder {
+            allocator,
+            ids: Vector::new(allocator),
+            descs: Vector::new(allocator),
+        }
+    }
+
+    fn intern(&mut self, s: &str) -> Result<&'a str, AllocError> {
+        if let Some(&id) = self.ids.as_slice().iter().find(|&&id| id == s) {
+            return Ok(id);
+        }
+        let id: &'a str = Box::from_str(self.allocator, s)?.leak();
+        self.ids.push(id).map_err(|(e, _)| e)?;
+        Ok(id)
+    }
+
+    fn record_desc(
+        &mut self,
+        name: &'a str,
+        field_names: &[&'a str],
+    ) -> Result<&'a RecordDesc<'a>, AllocError> {
+        if let Some(&d) = self.descs.as_slice().iter()
+            .find(|d| d.record_name == name && d.field_names == field_names) {
+            return Ok(d);
+        }
+        let field_names: &'a [&'a str] = Box::from_slice(self.allocator, field_names)?.leak();
+        let desc: &'a RecordDesc<'a> = Box::new(self.allocator, RecordDesc::new(name, field_names))
+            .map_err(|(e, _)| e)?.leak();
+        self.descs.push(desc).map_err(|(e, _)| e)?;
+        Ok(desc)
+    }
+
+    /* definite length strings or the chunks of indefinite length ones */
+    fn read_string<'x>(
+        &mut self,
+        major: u8,
+        len: Option<u64>,
+        input: &mut (dyn Read + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Vector<'a, u8>, Error<'x>> {
+        let mut v = Vector::new(self.allocator);
+        let mut buf = [0_u8; CHUNK_SIZE];
+        let mut next_len = len;
+        loop {
+            let mut left = match (len, next_len) {
+                (_, Some(n)) => n,
+                (None, None) => match read_head(input, xc)? {
+                    (MAJOR_SIMPLE, _, None) => break,
+                    (m, _, Some(n)) if m == major => n,
+                    _ => return Err(invalid("bad chunk in CBOR string")),
+                },
+                (Some(_), None) => break,
+            };
+            while left != 0 {
+                let n = core::cmp::min(left, CHUNK_SIZE as u64) as usize;
+                input.read_exact(&mut buf[0..n], xc)?;
+                v.append_from_slice(&buf[0..n])?;
+                left -= n as u64;
+            }
+            next_len = None;
+        }
+        Ok(v)
+    }
+
+    /* the byte string of a bignum: the value if it fits in 128 bits, the
+     * bytes otherwise */
+    fn read_bignum<'x>(
+        &mut self,
+        input: &mut (dyn Read + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Result<u128, Vector<'a, u8>>, Error<'x>> {
+        let v = match read_head(input, xc)? {
+            (MAJOR_BYTES, _, len) => self.read_string(MAJOR_BYTES, len, input, xc)?,
+            _ => return Err(invalid("malformed tagged CBOR item")),
+        };
+        let bytes = v.as_slice();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        if bytes.len() - skip > 16 {
+            return Ok(Err(v));
+        }
+        Ok(Ok(bytes[skip..].iter().fold(0, |n, &b| n << 8 | b as u128)))
+    }
+
+    fn read_text<'x>(
+        &mut self,
+        len: Option<u64>,
+        input: &mut (dyn Read + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<String<'a>, Error<'x>> {
+        let v = self.read_string(MAJOR_TEXT, len, input, xc)?;
+        String::from_utf8(v).map_err(|_| invalid("CBOR text string is not UTF-8"))
+    }
+
+    fn read_id<'x>(
+        &mut self,
+        len: Option<u64>,
+        input: &mut (dyn Read + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<&'a str, Error<'x>> {
+        let s = self.read_text(len, input, xc)?;
+        Ok(self.intern(s.as_str())?)
+    }
+
+    fn decode_record<'x>(
+        &mut self,
+        name: &'a str,
+        len: Option<u64>,
+        input: &mut (dyn Read + '_),
+        depth: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'a>, Error<'x>> {
+        let mut names: Vector<'a, &'a str> = Vector::new(self.allocator);
+        let mut values: Vector<'a, DataCell<'a>> = Vector::new(self.allocator);
+        while len != Some(names.len() as u64) {
+            let key = match read_head(input, xc)? {
+                (MAJOR_TEXT, _, key_len) => self.read_id(key_len, input, xc)?,
+                (MAJOR_SIMPLE, _, None) if len.is_none() => break,
+                _ => return Err(invalid("CBOR map key is not a text string")),
+            };
+            if names.as_slice().contains(&key) {
+                return Err(invalid("duplicate key in CBOR map"));
+            }
+            let value = self.decode_item(input, depth + 1, xc)?
+                .ok_or_else(|| invalid("unexpected CBOR break"))?;
+            names.push(key)?;
+            values.push(value)?;
+        }
+        let desc = self.record_desc(name, names.as_slice())?;
+        let r = Record { data: values, desc };
+        Ok(DataCell::Record(Rc::new(self.allocator, RefCell::new(r))?))
+    }
+
+    /* untagged maps; keys are not interned as they may be anything */
+    fn decode_map<'x>(
+        &mut self,
+        len: Option<u64>,
+        input: &mut (dyn Read + '_),
+        depth: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'a>, Error<'x>> {
+        let mut m = DCOMap::new(self.allocator);
+        while len != Some(m.0.len() as u64) {
+            let key = match read_head(input, xc)? {
+                (MAJOR_TEXT, _, key_len) => self.read_text(key_len, input, xc)?,
+                (MAJOR_SIMPLE, _, None) if len.is_none() => break,
+                _ => return Err(invalid("CBOR map key is not a text string")),
+            };
+            if m.0.contains_key(key.as_str()) {
+                return Err(invalid("duplicate key in CBOR map"));
+            }
+            let value = self.decode_item(input, depth + 1, xc)?
+                .ok_or_else(|| invalid("unexpected CBOR break"))?;
+            m.0.insert(key, value).map_err(|(e, _)| e)?;
+        }
+        Ok(DataCell::Map(Rc::new(self.allocator, RefCell::new(m))?))
+    }
+
+    /* None for the break ending an indefinite length item */
+    fn decode_item<'x>(
+        &mut self,
+        input: &mut (dyn Read + '_),
+        depth: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Option<DataCell<'a>>, Error<'x>> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("CBOR items nested too deep"));
+        }
+        let cell = match read_head(input, xc)? {
+            (MAJOR_UINT, _, Some(n)) => DataCell::from_u64(n),
+            (MAJOR_NEGINT, _, Some(n)) => DataCell::from_i64(negint(n)?),
+            (MAJOR_BYTES, _, len) => {
+                let v = self.read_string(MAJOR_BYTES, len, input, xc)?;
+                DataCell::ByteVector(Rc::new(self.allocator, RefCell::new(ByteVector(CowBytes::Owned(v))))?)
+            },
+            (MAJOR_TEXT, _, len) => {
+                let s = self.read_text(len, input, xc)?;
+                DataCell::Text(Rc::new(self.allocator, RefCell::new(s))?)
+            },
+            (MAJOR_ARRAY, _, len) => {
+                let mut v = Vector::new(self.allocator);
+                while len != Some(v.len() as u64) {
+                    match self.decode_item(input, depth + 1, xc)? {
+                        Some(c) => v.push(c)?,
+                        None if len.is_none() => break,
+                        None => return Err(invalid("unexpected CBOR break")),
+                    }
+                }
+                DataCell::CellVector(Rc::new(self.allocator, RefCell::new(DCOVector(v)))?)
+            },
+            (MAJOR_MAP, _, len) => self.decode_map(len, input, depth, xc)?,
+            (MAJOR_TAG, _, Some(RECORD_TAG)) => {
+                expect_head(MAJOR_ARRAY, 2, input, xc)?;
+                let name = match read_head(input, xc)? {
+                    (MAJOR_TEXT, _, len) => self.read_id(len, input, xc)?,
+                    _ => return Err(invalid("malformed tagged CBOR item")),
+                };
+                match read_head(input, xc)? {
+                    (MAJOR_MAP, _, len) => self.decode_record(name, len, input, depth, xc)?,
+                    _ => return Err(invalid("malformed tagged CBOR item")),
+                }
+            },
+            (MAJOR_TAG, _, Some(FORMATTED_INT_TAG)) => {
+                expect_head(MAJOR_ARRAY, 2, input, xc)?;
+                let fmt_pack = match read_head(input, xc)? {
+                    (MAJOR_UINT, _, Some(f)) => u32::try_from(f).ok()
+                        .and_then(MiniNumFmtPack::from_u32)
+                        .ok_or_else(|| invalid("bad number format in CBOR"))?,
+                    _ => return Err(invalid("malformed tagged CBOR item")),
+                };
+                match read_head(input, xc)? {
+                    (MAJOR_UINT, _, Some(n)) => DataCell::from_u64_cell(U64Cell::with_fmt(n, fmt_pack)),
+                    (MAJOR_NEGINT, _, Some(n)) =>
+                        DataCell::from_i64_cell(I64Cell::with_fmt(negint(n)?, fmt_pack)),
+                    (MAJOR_TAG, _, Some(BIGNUM_TAG)) => match self.read_bignum(input, xc)? {
+                        Ok(n) => DataCell::from_u128_cell(U128Cell::with_fmt(n, fmt_pack)),
+                        Err(_) => return Err(invalid("CBOR bignum out of range")),
+                    },
+                    _ => return Err(invalid("malformed tagged CBOR item")),
+                }
+            },
+            (MAJOR_TAG, _, Some(BIGNUM_TAG)) => match self.read_bignum(input, xc)? {
+                Ok(n) => DataCell::from_u128(n),
+                Err(v) => DataCell::ByteVector(Rc::new(self.allocator, RefCell::new(ByteVector(CowBytes::Owned(v))))?),
+            },
+            (MAJOR_TAG, _, Some(ID_TAG)) => match read_head(input, xc)? {
+                (MAJOR_TEXT, _, len) => DataCell::StaticId(self.read_id(len, input, xc)?),
+                _ => return Err(invalid("malformed tagged CBOR item")),
+            },
+            /* other tags do not change what the item is */
+            (MAJOR_TAG, _, Some(_)) => self.decode_item(input, depth + 1, xc)?
+                .ok_or_else(|| invalid("unexpected CBOR break"))?,
+            (MAJOR_SIMPLE, INFO_F32, Some(bits)) =>
+                DataCell::from_f64(f32::from_bits(bits as u32) as f64),
+            (MAJOR_SIMPLE, INFO_F64, Some(bits)) => DataCell::from_f64(f64::from_bits(bits)),
+            (MAJOR_SIMPLE, 0..=24, Some(SIMPLE_FALSE)) => DataCell::Bool(false),
+            (MAJOR_SIMPLE, 0..=24, Some(SIMPLE_TRUE)) => DataCell::Bool(true),
+            (MAJOR_SIMPLE, 0..=24, Some(SIMPLE_NULL))
+            | (MAJOR_SIMPLE, 0..=24, Some(SIMPLE_UNDEFINED)) => DataCell::Nothing,
+            (MAJOR_SIMPLE, _, None) => return Ok(None),
+            _ => return Err(invalid("unsupported CBOR item")),
+        };
+        Ok(Some(cell))
+    }
+
+}
+
+/* reads one item; decoded records, ids and byte strings are allocated with
+ * the given allocator */
+pub fn decode<'a, 'x>(
+    input: &mut (dyn Read + '_),
+    allocator: AllocatorRef<'a>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'a>, Error<'x>> {
+    Decoder::new(allocator).decode_item(input, 0, xc)?
+        .ok_or_else(|| invalid("unexpected CBOR break"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    const PAIR: RecordDesc<'static> = RecordDesc::new("pair", &["name", "data", "unset", "items"]);
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut items = xc.vector();
+        items.push(DataCell::from_u64(1000)).unwrap();
+        items.push(DataCell::from_u64_cell(U64Cell::hex(0x7F))).unwrap();
+        items.push(DataCell::Nothing).unwrap();
+        let mut r = Record::new(&PAIR, a.to_ref()).unwrap();
+        r.set_field("name", DataCell::from_static_id("elf"));
+        r.set_field("data", DataCell::from_byte_slice(a.to_ref(), b"\x7FELF").unwrap());
+        r.set_field("items", DataCell::CellVector(xc.rc(RefCell::new(DCOVector(items))).unwrap()));
+        let c = DataCell::Record(xc.rc(RefCell::new(r)).unwrap());
+
+        let mut o = xc.byte_vector();
+        encode(&c, &mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), &b"\xDA\x68\x62\x00\x01\x82\x64pair\xA3\
+            \x64name\xDA\x68\x62\x00\x03\x63elf\
+            \x64data\x44\x7FELF\
+            \x65items\x83\x19\x03\xE8\
+            \xDA\x68\x62\x00\x02\x82\x19\xD0\x02\x18\x7F\xF6"[..]);
+
+        let mut s = ChunkedReader::new(BufferAsROStream::new(o.as_slice()), 3, 0);
+        let d = decode(&mut s, a.to_ref(), &mut xc).unwrap();
+        let mut text = xc.byte_vector();
+        d.output_as_human_readable(&mut text, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text.as_slice()).unwrap(),
+            "pair(name: elf, data: b\"\\x7FELF\", items: [10000x7F])");
+
+        let mut o = xc.byte_vector();
+        for c in &[DataCell::from_i64(-100), DataCell::from_i64(i64::MIN),
+                   DataCell::from_f64(1.5), DataCell::from_bool(true)] {
+            encode(c, &mut o, &mut xc).unwrap();
+        }
+        assert_eq!(o.as_slice(), &b"\x38\x63\x3B\x7F\xFF\xFF\xFF\xFF\xFF\xFF\xFF\
+            \xFB\x3F\xF8\x00\x00\x00\x00\x00\x00\xF5"[..]);
+
+        let mut o = xc.byte_vector();
+        let uuid = 0x0123_4567_89AB_CDEF_FEDC_BA98_7654_3210_u128;
+        encode(&DataCell::from_u128(uuid), &mut o, &mut xc).unwrap();
+        encode(&DataCell::from_u128_cell(U128Cell::hex(0x100)), &mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), &b"\xC2\x50\x01\x23\x45\x67\x89\xAB\xCD\xEF\
+            \xFE\xDC\xBA\x98\x76\x54\x32\x10\
+            \xDA\x68\x62\x00\x02\x82\x19\xD0\x02\xC2\x42\x01\x00"[..]);
+        let mut s = BufferAsROStream::new(o.as_slice());
+        assert!(matches!(decode(&mut s, a.to_ref(), &mut xc), Ok(DataCell::U128(n)) if n.n == uuid));
+        let mut text = xc.byte_vector();
+        decode(&mut s, a.to_ref(), &mut xc).unwrap().output_as_human_readable(&mut text, &mut xc).unwrap();
+        assert_eq!(text.as_slice(), b"0x100");
+
+        let mut m = DCOMap::new(a.to_ref());
+        m.insert("b", DataCell::from_u64(1)).unwrap();
+        m.insert("a", DataCell::from_str(a.to_ref(), "x").unwrap()).unwrap();
+        let mut o = xc.byte_vector();
+        encode(&DataCell::from_map(a.to_ref(), m).unwrap(), &mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"\xA2\x61b\x01\x61a\x61x");
+        let mut s = BufferAsROStream::new(o.as_slice());
+        let d = decode(&mut s, a.to_ref(), &mut xc).unwrap();
+        let mut text = xc.byte_vector();
+        d.output_as_human_readable(&mut text, &mut xc).unwrap();
+        assert_eq!(text.as_slice(), b"{b: 1, a: \"x\"}");
+    }
+
+    #[test]
+    fn decode_indefinite_and_malformed() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut decode_text = |data: &[u8]| {
+            let mut s = BufferAsROStream::new(data);
+            let c = decode(&mut s, a.to_ref(), &mut xc)?;
+            let mut text = xc.byte_vector();
+            c.output_as_human_readable(&mut text, &mut xc).unwrap();
+            Ok(text)
+        };
+        assert_eq!(decode_text(b"\x9F\x5F\x41a\x42bc\xFF\xBF\x61k\xF6\xFF\xC1\x05\xFF").unwrap().as_slice(),
+            b"[b\"abc\"{k: }5]");
+        assert_eq!(decode_text(b"\x84\x38\x63\xF5\xFA\x3F\xC0\x00\x00\xF4").unwrap().as_slice(),
+            b"[-100true1.5false]");
+        assert_eq!(decode_text(b"\x7F\x61a\x62\"b\xFF").unwrap().as_slice(), b"\"a\\\"b\"");
+        assert!(matches!(decode_text(b"\x83\x01"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(b"\xF9\x00\x14"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(b"\x3B\x80\x00\x00\x00\x00\x00\x00\x00"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(b"\xA2\x61k\x01\x61k\x02"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(b"\xBF\x01\x02\xFF"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(b"\xFF"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(b"\xF8\x10"), Err(Error::IO(_))));
+        assert!(matches!(decode_text(&[0x81; MAX_DEPTH + 2]), Err(Error::IO(_))));
+    }
+}
@@ -0,0 +1,312 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::OutOfBounds;
+use crate::conv::try_decode_at;
+use crate::conv::try_decode_uint_at;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+
+use super::DataCell;
+use super::Error;
+use super::Record;
+use super::RecordDesc;
+use super::U64Cell;
+
+pub const ZSTD_MAGIC: u32 = 0xFD2F_B528;
+pub const LZ4_FRAME_MAGIC: u32 = 0x184D_2204;
+pub const LZ4_LEGACY_MAGIC: u32 = 0x184C_2102;
+
+/* zstd skippable frames use 16 magic values: 0x184D2A50..=0x184D2A5F */
+pub fn is_zstd_skippable_magic(magic: u32) -> bool {
+    magic & 0xFFFF_FFF0 == 0x184D_2A50
+}
+
+/* HeaderError **************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HeaderError {
+    BadMagic,
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl From<OutOfBounds> for HeaderError {
+    fn from(_e: OutOfBounds) -> Self {
+        HeaderError::Truncated
+    }
+}
+
+impl<'x> From<HeaderError> for Error<'x> {
+    fn from(e: HeaderError) -> Self {
+        match e {
+            HeaderError::BadMagic => Error::NotApplicable,
+            HeaderError::Truncated => Error::IO(IOError::with_str(
+                IOErrorCode::UnexpectedEnd, "truncated frame header")),
+            HeaderError::Invalid(msg) => Error::IO(IOError::with_str(
+                IOErrorCode::InvalidData, msg)),
+        }
+    }
+}
+
+fn flag_cell<'x>(flag: bool) -> DataCell<'x> {
+    DataCell::from_u64(flag as u64)
+}
+
+/* ZstdFrameHeader **********************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ZstdFrameHeader {
+    pub single_segment: bool,
+    pub content_checksum: bool,
+    pub dictionary_id: Option<u32>,
+    pub window_size: Option<u64>,
+    pub content_size: Option<u64>,
+    pub header_len: usize, // magic included
+}
+
+const ZSTD_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "zstd_header",
+    &[
+        "single_segment", "content_checksum", "dictionary_id",
+        "window_size", "content_size",
+    ]);
+
+impl ZstdFrameHeader {
+
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderError> {
+        let magic = try_decode_at::<u32>(data, 0, Endianness::Little)
+            .map_err(|_| HeaderError::BadMagic)?;
+        if magic != ZSTD_MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+        let fhd = *data.get(4).ok_or(HeaderError::Truncated)?;
+        if fhd & 0x08 != 0 {
+            return Err(HeaderError::Invalid("zstd frame header reserved bit set"));
+        }
+        let single_segment = fhd & 0x20 != 0;
+        let mut pos = 5;
+        let mut window_size = None;
+        if !single_segment {
+            let wd = *data.get(pos).ok_or(HeaderError::Truncated)?;
+            let window_log = 10 + (wd >> 3) as u32;
+            let window_base = 1_u64 << window_log;
+            window_size = Some(window_base + (window_base / 8) * (wd & 7) as u64);
+            pos += 1;
+        }
+        let dictionary_id = match [0, 1, 2, 4][(fhd & 3) as usize] {
+            0 => None,
+            n => {
+                let id = try_decode_uint_at(data, pos, n, Endianness::Little)? as u32;
+                pos += n;
+                Some(id)
+            },
+        };
+        let fcs_size = match fhd >> 6 {
+            0 => if single_segment { 1 } else { 0 },
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        let content_size = match fcs_size {
+            0 => None,
+            n => {
+                let v = try_decode_uint_at(data, pos, n, Endianness::Little)?;
+                pos += n;
+                Some(if n == 2 { v + 256 } else { v })
+            },
+        };
+        if single_segment {
+            window_size = content_size;
+        }
+        Ok(ZstdFrameHeader {
+            single_segment,
+            content_checksum: fhd & 0x04 != 0,
+            dictionary_id,
+            window_size,
+            content_size,
+            header_len: pos,
+        })
+    }
+
+    pub fn to_data_cell<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut r = Record::new(&ZSTD_HEADER, xc.get_main_allocator())?;
+        r.set_field("single_segment", flag_cell(self.single_segment));
+        r.set_field("content_checksum", flag_cell(self.content_checksum));
+        if let Some(id) = self.dictionary_id {
+            r.set_field("dictionary_id", DataCell::from_u64_cell(U64Cell::hex(id as u64)));
+        }
+        if let Some(n) = self.window_size {
+            r.set_field("window_size", DataCell::from_u64(n));
+        }
+        if let Some(n) = self.content_size {
+            r.set_field("content_size", DataCell::from_u64(n));
+        }
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+/* Lz4FrameHeader ***********************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lz4FrameHeader {
+    pub version: u8,
+    pub block_independence: bool,
+    pub block_checksum: bool,
+    pub content_checksum: bool,
+    pub block_max_size: u32,
+    pub content_size: Option<u64>,
+    pub dictionary_id: Option<u32>,
+    pub header_checksum: u8, // not verified
+    pub header_len: usize, // magic included
+}
+
+const LZ4_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "lz4_header",
+    &[
+        "version", "block_independence", "block_checksum",
+        "content_checksum", "block_max_size", "content_size",
+        "dictionary_id", "header_checksum",
+    ]);
+
+impl Lz4FrameHeader {
+
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderError> {
+        let magic = try_decode_at::<u32>(data, 0, Endianness::Little)
+            .map_err(|_| HeaderError::BadMagic)?;
+        if magic != LZ4_FRAME_MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+        let flg = *data.get(4).ok_or(HeaderError::Truncated)?;
+        let bd = *data.get(5).ok_or(HeaderError::Truncated)?;
+        let version = flg >> 6;
+        if version != 1 {
+            return Err(HeaderError::Invalid("unsupported lz4 frame version"));
+        }
+        if flg & 0x02 != 0 || bd & 0x8F != 0 {
+            return Err(HeaderError::Invalid("lz4 frame header reserved bits set"));
+        }
+        let block_max_size = match (bd >> 4) & 7 {
+            4 => 0x1_0000,
+            5 => 0x4_0000,
+            6 => 0x10_0000,
+            7 => 0x40_0000,
+            _ => return Err(HeaderError::Invalid("bad lz4 block maximum size")),
+        };
+        let mut pos = 6;
+        let content_size = if flg & 0x08 != 0 {
+            pos += 8;
+            Some(try_decode_uint_at(data, pos - 8, 8, Endianness::Little)?)
+        } else {
+            None
+        };
+        let dictionary_id = if flg & 0x01 != 0 {
+            pos += 4;
+            Some(try_decode_uint_at(data, pos - 4, 4, Endianness::Little)? as u32)
+        } else {
+            None
+        };
+        let header_checksum = *data.get(pos).ok_or(HeaderError::Truncated)?;
+        Ok(Lz4FrameHeader {
+            version,
+            block_independence: flg & 0x20 != 0,
+            block_checksum: flg & 0x10 != 0,
+            content_checksum: flg & 0x04 != 0,
+            block_max_size,
+            content_size,
+            dictionary_id,
+            header_checksum,
+            header_len: pos + 1,
+        })
+    }
+
+    pub fn to_data_cell<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut r = Record::new(&LZ4_HEADER, xc.get_main_allocator())?;
+        r.set_field("version", DataCell::from_u64(self.version as u64));
+        r.set_field("block_independence", flag_cell(self.block_independence));
+        r.set_field("block_checksum", flag_cell(self.block_checksum));
+        r.set_field("content_checksum", flag_cell(self.content_checksum));
+        r.set_field("block_max_size", DataCell::from_u64(self.block_max_size as u64));
+        if let Some(n) = self.content_size {
+            r.set_field("content_size", DataCell::from_u64(n));
+        }
+        if let Some(id) = self.dictionary_id {
+            r.set_field("dictionary_id", DataCell::from_u64_cell(U64Cell::hex(id as u64)));
+        }
+        r.set_field("header_checksum",
+                    DataCell::from_u64_cell(U64Cell::hex(self.header_checksum as u64)));
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zstd_single_segment() {
+        // FHD: FCS flag 0 + single segment => 1 byte content size
+        let h = ZstdFrameHeader::parse(b"\x28\xB5\x2F\xFD\x24\x05").unwrap();
+        assert_eq!(h, ZstdFrameHeader {
+            single_segment: true,
+            content_checksum: true,
+            dictionary_id: None,
+            window_size: Some(5),
+            content_size: Some(5),
+            header_len: 6,
+        });
+    }
+
+    #[test]
+    fn zstd_window_dict_and_2_byte_size() {
+        // FHD: FCS flag 1, dict flag 2; WD: exponent 1, mantissa 2
+        let h = ZstdFrameHeader::parse(b"\x28\xB5\x2F\xFD\x42\x0A\x34\x12\x00\x01").unwrap();
+        assert_eq!(h.window_size, Some(2048 + 512));
+        assert_eq!(h.dictionary_id, Some(0x1234));
+        assert_eq!(h.content_size, Some(0x100 + 256));
+        assert!(!h.content_checksum);
+        assert_eq!(h.header_len, 10);
+        assert_eq!(ZstdFrameHeader::parse(b"\x28\xB5\x2F\xFD\x42\x0A\x34\x12\x00"),
+                   Err(HeaderError::Truncated));
+        assert_eq!(ZstdFrameHeader::parse(b"\x28\xB5\x2F"), Err(HeaderError::BadMagic));
+        assert!(ZstdFrameHeader::parse(b"\x28\xB5\x2F\xFD\x08\x00").is_err());
+    }
+
+    #[test]
+    fn lz4_headers() {
+        let h = Lz4FrameHeader::parse(b"\x04\x22\x4D\x18\x64\x40\xA7").unwrap();
+        assert_eq!(h, Lz4FrameHeader {
+            version: 1,
+            block_independence: true,
+            block_checksum: false,
+            content_checksum: true,
+            block_max_size: 0x1_0000,
+            content_size: None,
+            dictionary_id: None,
+            header_checksum: 0xA7,
+            header_len: 7,
+        });
+        let h = Lz4FrameHeader::parse(
+            b"\x04\x22\x4D\x18\x49\x70\x10\x00\x00\x00\x00\x00\x00\x00\x78\x56\x34\x12\x00").unwrap();
+        assert_eq!(h.block_max_size, 0x40_0000);
+        assert_eq!(h.content_size, Some(0x10));
+        assert_eq!(h.dictionary_id, Some(0x1234_5678));
+        assert_eq!(h.header_len, 19);
+        assert_eq!(Lz4FrameHeader::parse(b"\x04\x22\x4D\x18\x24\x40\x00"),
+                   Err(HeaderError::Invalid("unsupported lz4 frame version")));
+        assert_eq!(Lz4FrameHeader::parse(b"\x04\x22\x4D\x18\x64\x40"),
+                   Err(HeaderError::Truncated));
+    }
+
+    #[test]
+    fn skippable_magic() {
+        assert!(is_zstd_skippable_magic(0x184D_2A5F));
+        assert!(!is_zstd_skippable_magic(0x184D_2A60));
+    }
+}
@@ -1,23 +1,62 @@
 use core::cell::RefCell;
 
 use crate::ExecutionContext;
-use crate::conv::int_be_decode;
 use crate::data_cell::DCOVector;
 use crate::data_cell::DataCell;
+use crate::data_cell::DataCellOps;
 use crate::data_cell::DataCellOpsMut;
 use crate::data_cell::Error;
 use crate::data_cell::Record;
 use crate::data_cell::RecordDesc;
 use crate::data_cell::U64Cell;
+use crate::data_cell::archive::AR_HEADER_SIZE;
+use crate::data_cell::archive::AR_MAGIC;
+use crate::data_cell::archive::ArMemberHeader;
+use crate::data_cell::archive::ArName;
+use crate::data_cell::archive::ArchiveEntry;
+use crate::data_cell::archive::MAX_ENTRY_CONTENT_SIZE;
+use crate::data_cell::archive::TAR_BLOCK_SIZE;
+use crate::data_cell::archive::TarHeader;
+use crate::data_cell::archive::ar_table_name;
+use crate::data_cell::archive::pax_number;
+use crate::data_cell::archive::pax_records;
+use crate::data_cell::compressed::HeaderError;
+use crate::data_cell::compressed::Lz4FrameHeader;
+use crate::data_cell::compressed::ZstdFrameHeader;
+use crate::data_cell::dump::HexDump;
+use crate::data_cell::dump::HexDumpOptions;
+use crate::data_cell::dump::output_stream_hex_dump;
+use crate::data_cell::formats::dwarf;
+use crate::data_cell::formats::elf::ElfFile;
+use crate::data_cell::formats::image;
+use crate::data_cell::formats::macho;
+use crate::data_cell::formats::pe;
+use crate::data_cell::formats::qcow;
+use crate::data_cell::formats::registry::FormatRegistry;
+use crate::data_cell::formats::zip;
 use crate::data_cell::output_byte_slice_as_human_readable_text;
+use crate::data_cell::stats;
+use crate::data_cell::strings;
 use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
 use crate::io::IOPartialError;
 use crate::io::IOPartialResult;
+use crate::io::compress::Bzip2Reader;
+use crate::io::compress::InflateFormat;
+use crate::io::compress::InflateReader;
+use crate::io::compress::XzReader;
+use crate::io::stream::ByteVectorStream;
 use crate::io::stream::RandomAccessRead;
+use crate::io::stream::Read;
 use crate::io::stream::SeekFrom;
+use crate::io::stream::Stream;
+use crate::io::stream::Window;
 use crate::io::stream::Write;
+use crate::mm::Allocator;
+use crate::mm::NOP_ALLOCATOR;
 use crate::mm::Vector;
 use crate::num::fmt as num_fmt;
+use crate::xc_err;
 
 pub const ELFCLASSNONE: u8 = 0;
 pub const ELFCLASS32: u8 = 1;
@@ -35,17 +74,128 @@ const ELF_HEADER: RecordDesc<'static> = RecordDesc::new(
         "e_type", "e_machine", "e_version", "e_entry", "e_phoff", "e_shoff",
     ]);
 
+/* limit for the size of decompressed content; it is kept in memory */
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 << 20;
+
+crate::convert_rc!(byte_vector_stream_as_stream,
+                   RefCell<ByteVectorStream<'a>>, RefCell<dyn Stream + 'a>);
+
+/* reads a decompressor to its end into a nested byte stream */
+fn decompressed_stream<'x, R: Read>(
+    mut r: R,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut data: Vector<'x, u8> = Vector::new(xc.get_main_allocator());
+    let mut buf = [0_u8; 0x400];
+    loop {
+        let n = r.read_uninterrupted(&mut buf, xc)?;
+        if n == 0 { break; }
+        if data.len() + n > MAX_DECOMPRESSED_SIZE {
+            return Err(Error::IO(xc_err!(
+                xc, IOErrorCode::NoSpace, "decompressed content too large",
+                "decompressed content exceeds {} bytes", MAX_DECOMPRESSED_SIZE)));
+        }
+        data.append_from_slice(&buf[0..n])?;
+    }
+    let s = xc.rc(RefCell::new(ByteVectorStream::new(data)))?;
+    Ok(DataCell::ByteStream(byte_vector_stream_as_stream(s)))
+}
+
 /* ContentStream ************************************************************/
 #[derive(Debug)]
 pub struct ContentStream<'a, T: ?Sized + RandomAccessRead> {
-    stream: &'a mut T
+    stream: &'a mut T,
+    formats: Option<&'a FormatRegistry<'a>>, // built-in formats only if None
 }
 
 impl<'a, T: ?Sized + RandomAccessRead> ContentStream<'a, T> {
 
     pub fn new(stream: &'a mut T) -> Self {
-        ContentStream { stream }
+        ContentStream { stream, formats: None }
+    }
+
+    /* identifies the top of file with the given registry */
+    pub fn with_formats(stream: &'a mut T, formats: &'a FormatRegistry<'a>) -> Self {
+        ContentStream { stream, formats: Some(formats) }
+    }
+
+    /* the given byte range as a stream of its own, for handing regions
+     * such as ELF sections to nested parsers */
+    pub fn window(&mut self, offset: u64, len: u64) -> Window<'_, T> {
+        Window::new(self.stream, offset, len)
+    }
+
+    /* like seek_read but copies straight from the content when the stream
+     * has it in memory; the stream position is left unspecified */
+    fn read_at<'x>(
+        &mut self,
+        pos: u64,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOPartialResult<'x, usize> {
+        if let Some(content) = self.stream.content_slice() {
+            let start = core::cmp::min(pos, content.len() as u64) as usize;
+            let n = core::cmp::min(buf.len(), content.len() - start);
+            buf[0..n].copy_from_slice(&content[start..start + n]);
+            return Ok(n);
+        }
+        self.stream.seek_read(pos, buf, xc)
+    }
+    /* exactly len bytes starting at pos, appended to out */
+    fn read_exact_at<'x>(
+        &mut self,
+        pos: u64,
+        len: u64,
+        out: &mut Vector<'x, u8>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        let mut buffer = [0_u8; 1024];
+        let mut done = 0_u64;
+        while done < len {
+            let chunk_len = core::cmp::min(len - done, buffer.len() as u64) as usize;
+            let n = self.read_at(pos + done, &mut buffer[0..chunk_len], xc)?;
+            if n == 0 {
+                return Err(Error::IO(IOError::with_str(
+                    IOErrorCode::UnexpectedEnd, "content truncated")));
+            }
+            out.append_from_slice(&buffer[0..n])?;
+            done += n as u64;
+        }
+        Ok(())
+    }
+
+    fn content_len<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<u64, Error<'x>> {
+        Ok(match self.stream.content_slice() {
+            Some(content) => content.len() as u64,
+            None => self.stream.seek(SeekFrom::End(0), xc)?,
+        })
+    }
+
+    /* hex dump of up to len bytes starting at offset; the dump is shorter
+     * if the content ends earlier */
+    pub fn dump_range<'x>(
+        &mut self,
+        offset: u64,
+        len: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        crate::dyn_rc!(hex_dump_rc, DataCellOps);
+        let mut data: Vector<'x, u8> = Vector::new(xc.get_main_allocator());
+        data.reserve(len)?;
+        let mut buffer = [0_u8; 1024];
+        while data.len() < len {
+            let chunk_len = core::cmp::min(len - data.len(), buffer.len());
+            let n = self.read_at(offset + data.len() as u64, &mut buffer[0..chunk_len], xc)?;
+            data.append_from_slice(&buffer[0..n])?;
+            if n < chunk_len { break; }
+        }
+        let dump = xc.rc(HexDump::new(offset, data))?;
+        Ok(DataCell::Dyn(hex_dump_rc(dump)))
     }
+
     fn extract_first_byte <'x>(
         &mut self,
         xc: &mut ExecutionContext<'x>,
@@ -75,7 +225,7 @@ impl<'a, T: ?Sized + RandomAccessRead> ContentStream<'a, T> {
         xc: &mut ExecutionContext<'x>,
     ) -> Result<DataCell<'x>, Error<'x>> {
         let mut buf = [0_u8; 8];
-        let n = self.stream.seek_read(0, &mut buf, xc)?;
+        let n = self.read_at(0, &mut buf, xc)?;
         Ok(DataCell::from_byte_slice(xc.get_main_allocator(), &buf[0..n])?)
     }
 
@@ -84,53 +234,297 @@ impl<'a, T: ?Sized + RandomAccessRead> ContentStream<'a, T> {
         xc: &mut ExecutionContext<'x>,
     ) -> Result<DataCell<'x>, Error<'x>> {
         let mut ids: Vector<'x, DataCell> = Vector::new(xc.get_main_allocator());
-        let mut tof_buffer = [0_u8; 0x40];
-        let tof_len = self.stream.seek_read(0, &mut tof_buffer, xc)?;
-        let tof = &tof_buffer[0..tof_len];
-        if tof_len == 0 {
-            ids.push(DataCell::StaticId("empty"))?;
-        } else if tof.starts_with(b"PK") {
-            ids.push(DataCell::StaticId("zip_record"))?;
-        } else if tof.starts_with(b"#!") {
-            ids.push(DataCell::StaticId("shebang"))?;
-        } else if tof.starts_with(b"\x7FELF") {
-            ids.push(DataCell::StaticId("elf"))?;
-        } else if tof.starts_with(b"MZ") {
-            ids.push(DataCell::StaticId("dos_exe"))?;
-        } else if tof.starts_with(b"ZM") {
-            ids.push(DataCell::StaticId("dos_exe"))?;
-            ids.push(DataCell::StaticId("dos_exe_zm"))?;
-        } else if tof.starts_with(b"\x1F\x8B") {
-            ids.push(DataCell::StaticId("gzip"))?;
-        } else if tof.starts_with(b"BZh") {
-            ids.push(DataCell::StaticId("bzip2"))?;
-        } else if tof.starts_with(b"\xFD7zXZ\x00") {
-            ids.push(DataCell::StaticId("xz"))?;
-        } else if tof.starts_with(b"7z\xBC\xAF\x27\x1C") {
-            ids.push(DataCell::StaticId("seven_zip"))?;
-        } else if tof.starts_with(b"!<arch>\n") {
-            ids.push(DataCell::StaticId("ar"))?;
-        } else if tof.starts_with(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
-            ids.push(DataCell::StaticId("ms_cfb"))?;
-        } else if tof.starts_with(b"QFI\xFB") {
-            ids.push(DataCell::StaticId("qcow"))?;
-            if tof_len >= 8 {
-                let ver: u32 = int_be_decode(&tof[4..8]).unwrap();
-                match ver {
-                    1 => ids.push(DataCell::StaticId("qcow1"))?,
-                    2 => ids.push(DataCell::StaticId("qcow2"))?,
-                    3 => ids.push(DataCell::StaticId("qcow3"))?,
-                    _ => {}
-                }
-            }
-        } else if tof.starts_with(b"SQLite format 3\x00") {
-            ids.push(DataCell::StaticId("sqlite3"))?;
-        } else if tof.starts_with(b"qres\x00\x00\x00\x01") {
-            ids.push(DataCell::StaticId("qt_rcc"))?;
+        match self.formats {
+            Some(f) => f.identify_content(self.stream, &mut ids, xc)?,
+            None => FormatRegistry::new(NOP_ALLOCATOR.to_ref())
+                .identify_content(self.stream, &mut ids, xc)?,
         }
         Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(ids)))?))
     }
 
+    /* start of content, enough for any zstd or lz4 frame header */
+    fn frame_header_bytes<'x>(
+        &mut self,
+        buf: &mut [u8; 0x20],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOPartialResult<'x, usize> {
+        self.read_at(0, buf, xc)
+    }
+
+    fn extract_zstd_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut buf = [0_u8; 0x20];
+        let n = self.frame_header_bytes(&mut buf, xc)?;
+        ZstdFrameHeader::parse(&buf[0..n])?.to_data_cell(xc)
+    }
+
+    fn extract_lz4_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut buf = [0_u8; 0x20];
+        let n = self.frame_header_bytes(&mut buf, xc)?;
+        Lz4FrameHeader::parse(&buf[0..n])?.to_data_cell(xc)
+    }
+
+    /* decompressed content of a gzip stream, as a nested byte stream */
+    fn gunzip<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut magic = [0_u8; 2];
+        if self.read_at(0, &mut magic, xc)? != 2 || magic != *b"\x1F\x8B" {
+            return Err(Error::NotApplicable);
+        }
+        let a = xc.get_main_allocator();
+        let r = InflateReader::new(self.window(0, u64::MAX), InflateFormat::Gzip, a)?;
+        decompressed_stream(r, xc)
+    }
+
+    /* decompressed content for any of the supported compressed formats */
+    fn decompress<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut magic_buf = [0_u8; 6];
+        let n = self.read_at(0, &mut magic_buf, xc)?;
+        let magic = &magic_buf[0..n];
+        let a = xc.get_main_allocator();
+        let src = self.window(0, u64::MAX);
+        if magic.starts_with(b"\x1F\x8B") {
+            decompressed_stream(InflateReader::new(src, InflateFormat::Gzip, a)?, xc)
+        } else if magic.starts_with(b"BZh") {
+            decompressed_stream(Bzip2Reader::new(src, a), xc)
+        } else if magic.starts_with(b"\xFD7zXZ\x00") {
+            decompressed_stream(XzReader::new(src, a), xc)
+        } else {
+            Err(Error::NotApplicable)
+        }
+    }
+
+    /* members of an ar archive (unix static libraries, deb packages and
+     * Windows .lib files) in the GNU, BSD or Windows header variants */
+    /* calls f for each member of an ar archive with its header, name and
+     * the offset and size of its data */
+    fn walk_ar_members<'x, F>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+        mut f: F,
+    ) -> Result<(), Error<'x>>
+    where F: FnMut(&mut Self, &ArMemberHeader<'_>, &[u8], u64, u64, &mut ExecutionContext<'x>) -> Result<(), Error<'x>> {
+        let mut magic = [0_u8; 8];
+        if self.read_at(0, &mut magic, xc)? != 8 || magic != *AR_MAGIC {
+            return Err(Error::NotApplicable);
+        }
+        let a = xc.get_main_allocator();
+        let content_len = self.content_len(xc)?;
+        let mut name_table: Vector<'x, u8> = Vector::new(a);
+        let mut name: Vector<'x, u8> = Vector::new(a);
+        let mut pos = magic.len() as u64;
+        while pos < content_len {
+            xc.check_cancelled()?;
+            let mut hbuf = [0_u8; AR_HEADER_SIZE];
+            if self.read_at(pos, &mut hbuf, xc)? != AR_HEADER_SIZE {
+                return Err(Error::IO(IOError::with_str(
+                    IOErrorCode::UnexpectedEnd, "truncated ar member header")));
+            }
+            let h = ArMemberHeader::parse(&hbuf)?;
+            let mut offset = pos + AR_HEADER_SIZE as u64;
+            let mut size = h.size;
+            if content_len - offset < size {
+                return Err(Error::IO(IOError::with_str(
+                    IOErrorCode::UnexpectedEnd, "ar member data truncated")));
+            }
+            name.truncate(0);
+            match h.name {
+                ArName::Inline(n) => name.append_from_slice(n)?,
+                ArName::Special(n) => {
+                    name.append_from_slice(n)?;
+                    if n == b"//" {
+                        name_table.truncate(0);
+                        self.read_exact_at(offset, size, &mut name_table, xc)?;
+                    }
+                },
+                ArName::Table(n) => name.append_from_slice(
+                    ar_table_name(name_table.as_slice(), n)?)?,
+                ArName::Following(n) => {
+                    if n > size {
+                        return Err(Error::IO(IOError::with_str(
+                            IOErrorCode::InvalidData,
+                            "BSD ar member name longer than member")));
+                    }
+                    self.read_exact_at(offset, n, &mut name, xc)?;
+                    let len = name.as_slice().iter().position(|&c| c == 0)
+                        .unwrap_or(name.len());
+                    name.truncate(len);
+                    offset += n;
+                    size -= n;
+                },
+            }
+            f(self, &h, name.as_slice(), offset, size, xc)?;
+            pos = offset + size;
+            pos += pos & 1;
+        }
+        Ok(())
+    }
+
+    fn extract_ar_members<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut members: Vector<'x, DataCell> = Vector::new(xc.get_main_allocator());
+        self.walk_ar_members(xc, |_, h, name, offset, size, xc| {
+            members.push(h.to_data_cell(name, offset, size, xc)?)?;
+            Ok(())
+        })?;
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(members)))?))
+    }
+
+    /* dynamic cell for an archive entry, with a copy of the member content
+     * unless that is too large */
+    fn archive_entry_cell<'x>(
+        &mut self,
+        mut entry: ArchiveEntry<'x>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        crate::dyn_rc!(archive_entry_rc, DataCellOps);
+        if entry.size <= MAX_ENTRY_CONTENT_SIZE {
+            let mut data: Vector<'x, u8> = Vector::new(xc.get_main_allocator());
+            self.read_exact_at(entry.offset, entry.size, &mut data, xc)?;
+            entry.set_content(data);
+        }
+        Ok(DataCell::Dyn(archive_entry_rc(xc.rc(entry)?)))
+    }
+
+    fn extract_ar_entries<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let mut entries: Vector<'x, DataCell> = Vector::new(a);
+        self.walk_ar_members(xc, |cs, h, name, offset, size, xc| {
+            let mut e = ArchiveEntry::ar(h, offset, size, a);
+            e.name.append_from_slice(name)?;
+            entries.push(cs.archive_entry_cell(e, xc)?)?;
+            Ok(())
+        })?;
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(entries)))?))
+    }
+
+    /* entries of a tar archive, with GNU long names and pax extended
+     * headers applied to the entry that follows them */
+    fn extract_tar_entries<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let content_len = self.content_len(xc)?;
+        let mut entries: Vector<'x, DataCell> = Vector::new(a);
+        let mut long_name: Vector<'x, u8> = Vector::new(a);
+        let mut long_link: Vector<'x, u8> = Vector::new(a);
+        let mut pax_size: Option<u64> = None;
+        let mut pax_mtime: Option<u64> = None;
+        let mut ext: Vector<'x, u8> = Vector::new(a);
+        let mut pos = 0_u64;
+        loop {
+            xc.check_cancelled()?;
+            let mut block = [0_u8; TAR_BLOCK_SIZE];
+            let n = self.read_at(pos, &mut block, xc)?;
+            if n == 0 && pos != 0 { break; }
+            if n != TAR_BLOCK_SIZE {
+                if pos == 0 { return Err(Error::NotApplicable); }
+                return Err(Error::IO(IOError::with_str(
+                    IOErrorCode::UnexpectedEnd, "truncated tar header")));
+            }
+            let h = match TarHeader::parse(&block) {
+                Ok(Some(h)) => h,
+                Ok(None) if pos != 0 => break,
+                Err(HeaderError::Invalid(msg)) if pos != 0 => return Err(Error::IO(
+                    IOError::with_str(IOErrorCode::InvalidData, msg))),
+                Err(HeaderError::BadMagic) if pos != 0 => return Err(Error::IO(
+                    IOError::with_str(IOErrorCode::InvalidData, "bad tar header checksum"))),
+                _ => return Err(Error::NotApplicable),
+            };
+            let offset = pos + TAR_BLOCK_SIZE as u64;
+            let size = match h.type_flag {
+                b'L' | b'K' | b'x' | b'g' => h.size,
+                _ => pax_size.take().unwrap_or(h.size),
+            };
+            if content_len - offset < size {
+                return Err(Error::IO(IOError::with_str(
+                    IOErrorCode::UnexpectedEnd, "tar entry data truncated")));
+            }
+            match h.type_flag {
+                b'L' | b'K' => {
+                    let v = if h.type_flag == b'L' { &mut long_name } else { &mut long_link };
+                    v.truncate(0);
+                    self.read_exact_at(offset, size, v, xc)?;
+                    let len = v.as_slice().iter().position(|&c| c == 0).unwrap_or(v.len());
+                    v.truncate(len);
+                },
+                b'x' => {
+                    ext.truncate(0);
+                    self.read_exact_at(offset, size, &mut ext, xc)?;
+                    let mut r = Ok(());
+                    pax_records(ext.as_slice(), |key, value| {
+                        let t = match key {
+                            b"path" => &mut long_name,
+                            b"linkpath" => &mut long_link,
+                            b"size" => {
+                                pax_size = pax_number(value);
+                                return;
+                            },
+                            b"mtime" => {
+                                pax_mtime = pax_number(value);
+                                return;
+                            },
+                            _ => return,
+                        };
+                        t.truncate(0);
+                        if let Err(e) = t.append_from_slice(value) { r = Err(e); }
+                    })?;
+                    r?;
+                },
+                b'g' => {},
+                _ => {
+                    let mut e = ArchiveEntry::tar(&h, offset, size, a);
+                    if !long_name.is_empty() {
+                        e.name.append_from_slice(long_name.as_slice())?;
+                        long_name.truncate(0);
+                    } else {
+                        if !h.prefix.is_empty() {
+                            e.name.append_from_slice(h.prefix)?;
+                            e.name.append_from_slice(b"/")?;
+                        }
+                        e.name.append_from_slice(h.name)?;
+                    }
+                    if !long_link.is_empty() {
+                        e.link_name.append_from_slice(long_link.as_slice())?;
+                        long_link.truncate(0);
+                    } else {
+                        e.link_name.append_from_slice(h.link_name)?;
+                    }
+                    if let Some(t) = pax_mtime.take() {
+                        e.mtime = Some(t);
+                    }
+                    /* links and special files have no data whatever size
+                     * their header gives */
+                    let has_data = !matches!(h.type_flag, b'1' | b'2' | b'3' | b'4' | b'5' | b'6');
+                    if !has_data { e.size = 0; }
+                    entries.push(self.archive_entry_cell(e, xc)?)?;
+                    if !has_data {
+                        pos = offset;
+                        continue;
+                    }
+                },
+            }
+            pos = offset + size.div_ceil(TAR_BLOCK_SIZE as u64) * TAR_BLOCK_SIZE as u64;
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(entries)))?))
+    }
+
     fn extract_elf_header<'x>(
         &mut self,
         xc: &mut ExecutionContext<'x>,
@@ -273,15 +667,87 @@ impl<'a, T: ?Sized + RandomAccessRead> DataCellOpsMut for ContentStream<'a, T> {
             "first_8_bytes" => self.first_8_bytes(xc),
             "tof_ids" => self.identify_top_of_file_records(xc),
             "elf_header" => self.extract_elf_header(xc),
+            "zstd_header" => self.extract_zstd_header(xc),
+            "lz4_header" => self.extract_lz4_header(xc),
+            "gunzip" => self.gunzip(xc),
+            "decompressed" => self.decompress(xc),
+            "ar_members" => self.extract_ar_members(xc),
+            "ar_entries" => self.extract_ar_entries(xc),
+            "tar_entries" => self.extract_tar_entries(xc),
+            "zip_entries" => zip::zip_entries(self.stream, xc),
+            "elf_program_headers" =>
+                ElfFile::load(self.stream, xc)?.program_headers(self.stream, xc),
+            "elf_section_headers" =>
+                ElfFile::load(self.stream, xc)?.section_headers(self.stream, xc),
+            "elf_symbols" => ElfFile::load(self.stream, xc)?.symbols(self.stream, xc),
+            "elf_dynamic" => ElfFile::load(self.stream, xc)?.dynamic(self.stream, xc),
+            "dwarf_abbrev_tables" => dwarf::dwarf_abbrev_tables(self.stream, xc),
+            "dwarf_line_headers" => dwarf::dwarf_line_headers(self.stream, xc),
+            "dwarf_compile_units" => dwarf::dwarf_compile_units(self.stream, xc),
+            "pe_header" => pe::pe_header(self.stream, xc),
+            "macho_header" => macho::macho_header(self.stream, xc),
+            "macho_load_commands" => macho::macho_load_commands(self.stream, xc),
+            "image_info" => image::image_info(self.stream, xc),
+            "qcow_header" => qcow::qcow_header(self.stream, xc),
+            "byte_histogram" => stats::byte_histogram(self.stream, xc),
+            "entropy" => stats::entropy(self.stream, xc),
+            "strings" => strings::strings(self.stream, xc),
             _ => Err(Error::NotApplicable),
         }
     }
 
+    fn get_property_byte_range_mut(
+        &mut self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        let size = match property_name {
+            "first_byte" => 1,
+            "first_8_bytes" => 8,
+            "tof_ids" => {
+                let len = self.content_len(xc).ok()?;
+                match self.formats {
+                    Some(f) => f.identified_len(len),
+                    None => FormatRegistry::new(NOP_ALLOCATOR.to_ref()).identified_len(len),
+                }
+            },
+            "elf_header" => {
+                // the record stops after e_shoff
+                let mut ident = [0_u8; 5];
+                self.read_at(0, &mut ident, xc).ok()?;
+                match ident[4] {
+                    ELFCLASS32 => 0x24,
+                    ELFCLASS64 => 0x30,
+                    _ => 0x10,
+                }
+            },
+            "zstd_header" => {
+                let mut buf = [0_u8; 0x20];
+                let n = self.frame_header_bytes(&mut buf, xc).ok()?;
+                ZstdFrameHeader::parse(&buf[0..n]).ok()?.header_len as u64
+            },
+            "lz4_header" => {
+                let mut buf = [0_u8; 0x20];
+                let n = self.frame_header_bytes(&mut buf, xc).ok()?;
+                Lz4FrameHeader::parse(&buf[0..n]).ok()?.header_len as u64
+            },
+            _ => return None,
+        };
+        let len = match self.stream.content_slice() {
+            Some(content) => content.len() as u64,
+            None => self.stream.seek(SeekFrom::End(0), xc).ok()?,
+        };
+        Some((0, core::cmp::min(size, len)))
+    }
+
     fn output_as_human_readable_mut<'w, 'x>(
         &mut self,
         out: &mut (dyn Write + 'w),
         xc: &mut ExecutionContext<'x>,
     ) -> Result<(), Error<'x>> {
+        if let Some(content) = self.stream.content_slice() {
+            return output_byte_slice_as_human_readable_text(content, out, xc);
+        }
         self.stream.seek(SeekFrom::Start(0), xc)?;
         let mut buffer = [0_u8; 1024];
         loop {
@@ -292,4 +758,424 @@ impl<'a, T: ?Sized + RandomAccessRead> DataCellOpsMut for ContentStream<'a, T> {
         Ok(())
     }
 
+    fn output_as_hex_dump_mut<'w, 'x>(
+        &mut self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        output_stream_hex_dump(self.stream, options, out, xc)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn in_memory_content_is_used_directly() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let data = b"\x7FELF\x02\x01\x01";
+        let mut s = BufferAsROStream::new(data);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[elf]");
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("first_8_bytes", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"b\"\\x7FELF\\x02\\x01\\x01\"");
+        assert_eq!(cs.get_property_byte_range_mut("elf_header", &mut xc), Some((0, 7)));
+        let mut o = xc.byte_vector();
+        cs.output_as_human_readable_mut(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"\\x7FELF\\x02\\x01\\x01");
+    }
+
+    #[test]
+    fn zstd_and_lz4_frames() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(b"\x28\xB5\x2F\xFD\x24\x05xxxxx");
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[zstd]");
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("zstd_header", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(),
+            "zstd_header(single_segment: 1, content_checksum: 1, window_size: 5, content_size: 5)");
+        assert_eq!(cs.get_property_byte_range_mut("zstd_header", &mut xc), Some((0, 6)));
+        assert_eq!(cs.get_property_mut("lz4_header", &mut xc).unwrap_err(), Error::NotApplicable);
+
+        let mut s = BufferAsROStream::new(b"\x04\x22\x4D\x18\x64\x70");
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[lz4]");
+        let e = cs.get_property_mut("lz4_header", &mut xc).unwrap_err();
+        match e {
+            Error::IO(e) => assert_eq!(e.get_error_code(), IOErrorCode::UnexpectedEnd),
+            _ => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn dump_range_stops_at_end_of_content() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(b"0123456789abcdefXYZ");
+        let mut cs = ContentStream::new(&mut s);
+        let d = cs.dump_range(0xE, 0x100, &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        d.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(),
+            "0000000e: 6566 5859 5a                             efXYZ\n");
+        let d = cs.dump_range(0x40, 4, &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        d.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert!(o.is_empty());
+    }
+
+    #[test]
+    fn gunzip_gives_nested_stream() {
+        let mut buffer = [0_u8; 0xA000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let gz = b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x02\x03\xCB\x48\xCD\xC9\xC9\xD7\x51\xC8\x40\xA2\x14\x32\x12\x73\xD2\x92\x32\x4B\xB8\x00\xA5\x46\x75\x62\x1C\x00\x00\x00";
+        let mut s = BufferAsROStream::new(gz);
+        let mut cs = ContentStream::new(&mut s);
+        let inner = cs.get_property_mut("gunzip", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        inner.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"b\"hello, hello, hello halfbit\\x0A\"");
+        let mut o = xc.byte_vector();
+        inner.get_property("first_byte", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"0x68");
+
+        let mut s = BufferAsROStream::new(b"\x28\xB5\x2F\xFD");
+        let mut cs = ContentStream::new(&mut s);
+        assert_eq!(cs.get_property_mut("gunzip", &mut xc).unwrap_err(), Error::NotApplicable);
+    }
+
+    #[test]
+    fn decompressed_bzip2_and_xz() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let bz = b"\x42\x5A\x68\x39\x31\x41\x59\x26\x53\x59\xE9\xAD\xBE\x77\x00\x00\x01\x81\x80\x31\x64\x04\x00\x20\x00\x31\x0C\x01\x06\x4F\x43\x64\x09\x45\xDC\x91\x4E\x14\x24\x3A\x6B\x6F\x9D\xC0";
+        let mut s = BufferAsROStream::new(bz);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("decompressed", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"b\"halfbit\"");
+        assert_eq!(cs.get_property_mut("gunzip", &mut xc).unwrap_err(), Error::NotApplicable);
+
+        // "\x7FELF\x02\x01\x01" in an xz stream with a CRC32 check
+        let xz = b"\xFD\x37\x7A\x58\x5A\x00\x00\x01\x69\x22\xDE\x36\x02\x00\x21\x01\x16\x00\x00\x00\x74\x2F\xE5\xA3\x01\x00\x06\x7F\x45\x4C\x46\x02\x01\x01\x00\x00\x73\x83\x3D\x96\x00\x01\x1B\x07\x12\xEB\xD4\x17\x90\x42\x99\x0D\x01\x00\x00\x00\x00\x01\x59\x5A";
+        let mut s = BufferAsROStream::new(xz);
+        let mut cs = ContentStream::new(&mut s);
+        let inner = cs.get_property_mut("decompressed", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        inner.get_property("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[elf]");
+    }
+
+    #[test]
+    fn window_views_a_region() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(b"junk\x7FELF\x02\x01\x01");
+        let mut cs = ContentStream::new(&mut s);
+        let mut w = cs.window(4, 0x100);
+        let mut inner = ContentStream::new(&mut w);
+        let mut o = xc.byte_vector();
+        inner.get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[elf]");
+    }
+
+    #[test]
+    fn ar_members_gnu_and_bsd() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let gnu = b"!<arch>\n//                                              20        `\nlong_member_name.o/\n/0              1700000000  1000  1000  100644  3         `\nabc\nshort.o/        0           0     0     644     2         `\nxy";
+        let mut s = BufferAsROStream::new(gnu);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("ar_members", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[ar_member(name: b\"//\", offset: 0x44, size: 20)",
+            "ar_member(name: b\"long_member_name.o\", offset: 0x94, size: 3, ",
+            "mtime: 1700000000, uid: 1000, gid: 1000, mode: 0o100644)",
+            "ar_member(name: b\"short.o\", offset: 0xD4, size: 2, ",
+            "mtime: 0, uid: 0, gid: 0, mode: 0o644)]"));
+
+        let bsd = b"!<arch>\n#1/12           1700000000  501   20    100644  16        `\nbsd_name.o\0\0data";
+        let mut s = BufferAsROStream::new(bsd);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("ar_members", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[ar_member(name: b\"bsd_name.o\", offset: 0x50, size: 4, ",
+            "mtime: 1700000000, uid: 501, gid: 20, mode: 0o100644)]"));
+
+        let mut s = BufferAsROStream::new(&gnu[0..200]);
+        let mut cs = ContentStream::new(&mut s);
+        let e = cs.get_property_mut("ar_members", &mut xc).unwrap_err();
+        match e {
+            Error::IO(e) => assert_eq!(e.get_error_code(), IOErrorCode::UnexpectedEnd),
+            _ => panic!("unexpected error {}", e),
+        }
+        let mut s = BufferAsROStream::new(b"!<arch>");
+        let mut cs = ContentStream::new(&mut s);
+        assert_eq!(cs.get_property_mut("ar_members", &mut xc).unwrap_err(), Error::NotApplicable);
+    }
+
+    fn entry_property<'x>(
+        entries: &DataCell<'x>,
+        index: usize,
+        name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Vector<'x, u8> {
+        let mut o = xc.byte_vector();
+        match entries {
+            DataCell::CellVector(v) => v.borrow().0.as_slice()[index]
+                .get_property(name, xc).unwrap()
+                .output_as_human_readable(&mut o, xc).unwrap(),
+            _ => panic!("not a vector"),
+        }
+        o
+    }
+
+    fn tar_block(name: &[u8], type_flag: u8, size: usize) -> [u8; TAR_BLOCK_SIZE] {
+        let mut h = [0_u8; TAR_BLOCK_SIZE];
+        h[0..name.len()].copy_from_slice(name);
+        h[100..107].copy_from_slice(b"0000644");
+        for i in 0..11 {
+            h[124 + i] = b'0' + ((size >> (3 * (10 - i))) & 7) as u8;
+        }
+        h[156] = type_flag;
+        h[257..265].copy_from_slice(b"ustar\x0000");
+        let sum: usize = h.iter().map(|&c| c as usize).sum::<usize>() + 8 * b' ' as usize;
+        for i in 0..6 {
+            h[148 + i] = b'0' + ((sum >> (3 * (5 - i))) & 7) as u8;
+        }
+        h[155] = b' ';
+        h
+    }
+
+    #[test]
+    fn ar_and_tar_entries_expose_member_content() {
+        let mut buffer = [0_u8; 0x8000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let ar = b"!<arch>\ne.o/            0           0     0     644     7         `\n\x7FELF\x02\x01\x01\n";
+        let mut s = BufferAsROStream::new(ar);
+        let mut cs = ContentStream::new(&mut s);
+        let entries = cs.get_property_mut("ar_entries", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        entries.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[ar_entry(name: b\"e.o\", offset: 0x44, size: 7, ",
+            "mtime: 0, uid: 0, gid: 0, mode: 0o644)]"));
+        assert_eq!(entry_property(&entries, 0, "tof_ids", &mut xc).as_slice(), b"[elf]");
+        assert_eq!(entry_property(&entries, 0, "data", &mut xc).as_slice(), b"b\"\\x7FELF\\x02\\x01\\x01\"");
+        match &entries {
+            DataCell::CellVector(v) => assert_eq!(
+                v.borrow().0.as_slice()[0].get_property_byte_range("first_8_bytes", &mut xc),
+                Some((0x44, 0x4B))),
+            _ => panic!("not a vector"),
+        }
+
+        let pax = b"22 path=long/name.elf\n";
+        let mut tar = [0_u8; TAR_BLOCK_SIZE * 6];
+        tar[0..512].copy_from_slice(&tar_block(b"PaxHeaders/x", b'x', pax.len()));
+        tar[512..512 + pax.len()].copy_from_slice(pax);
+        tar[1024..1536].copy_from_slice(&tar_block(b"short", b'0', 7));
+        tar[1536..1543].copy_from_slice(b"\x7FELF\x02\x01\x01");
+        tar[2048..2560].copy_from_slice(&tar_block(b"dir/", b'5', 0));
+        let mut s = BufferAsROStream::new(&tar);
+        let mut cs = ContentStream::new(&mut s);
+        let entries = cs.get_property_mut("tar_entries", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        entries.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[tar_entry(name: b\"long/name.elf\", type: file, offset: 0x600, size: 7, mode: 0o644)",
+            "tar_entry(name: b\"dir/\", type: directory, offset: 0xA00, size: 0, mode: 0o644)]"));
+        assert_eq!(entry_property(&entries, 0, "tof_ids", &mut xc).as_slice(), b"[elf]");
+        assert_eq!(entry_property(&entries, 1, "tof_ids", &mut xc).as_slice(), b"[empty]");
+
+        let mut s = BufferAsROStream::new(&tar[0..1540]);
+        let mut cs = ContentStream::new(&mut s);
+        assert!(cs.get_property_mut("tar_entries", &mut xc).is_err());
+        let mut s = BufferAsROStream::new(ar);
+        let mut cs = ContentStream::new(&mut s);
+        assert_eq!(cs.get_property_mut("tar_entries", &mut xc).unwrap_err(), Error::NotApplicable);
+    }
+
+    #[test]
+    fn zip_entries_from_central_directory() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let z = b"\x50\x4B\x03\x04\x14\x00\x00\x00\x00\x00\x83\x18\x22\x58\xE5\x52\x4C\x92\x0E\x00\x00\x00\x0E\x00\x00\x00\x09\x00\x00\x00\x68\x65\x6C\x6C\x6F\x2E\x74\x78\x74\x68\x65\x6C\x6C\x6F\x20\x68\x61\x6C\x66\x62\x69\x74\x0A\x50\x4B\x03\x04\x14\x00\x00\x00\x08\x00\x83\x18\x22\x58\x64\x7A\x70\xAF\x06\x00\x00\x00\x64\x00\x00\x00\x0C\x00\x00\x00\x64\x69\x72\x2F\x64\x61\x74\x61\x2E\x62\x69\x6E\x4B\x4C\xA4\x3D\x00\x00\x50\x4B\x01\x02\x14\x03\x14\x00\x00\x00\x00\x00\x83\x18\x22\x58\xE5\x52\x4C\x92\x0E\x00\x00\x00\x0E\x00\x00\x00\x09\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x00\x00\x00\x00\x68\x65\x6C\x6C\x6F\x2E\x74\x78\x74\x50\x4B\x01\x02\x14\x03\x14\x00\x00\x00\x08\x00\x83\x18\x22\x58\x64\x7A\x70\xAF\x06\x00\x00\x00\x64\x00\x00\x00\x0C\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x35\x00\x00\x00\x64\x69\x72\x2F\x64\x61\x74\x61\x2E\x62\x69\x6E\x50\x4B\x05\x06\x00\x00\x00\x00\x02\x00\x02\x00\x71\x00\x00\x00\x65\x00\x00\x00\x04\x00\x6E\x6F\x74\x65";
+        let mut s = BufferAsROStream::new(z);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("zip_entries", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[zip_entry(name: \"hello.txt\", method: stored, compressed_size: 14, ",
+            "uncompressed_size: 14, crc32: 0x924C52E5, offset: 0x00)",
+            "zip_entry(name: \"dir/data.bin\", method: deflate, compressed_size: 6, ",
+            "uncompressed_size: 100, crc32: 0xAF707A64, offset: 0x35)]"));
+
+        let mut s = BufferAsROStream::new(&z[0..0xE0]);
+        let mut cs = ContentStream::new(&mut s);
+        assert_eq!(cs.get_property_mut("zip_entries", &mut xc).unwrap_err(), Error::NotApplicable);
+        let mut s = BufferAsROStream::new(&z[0x10..]);
+        let mut cs = ContentStream::new(&mut s);
+        assert!(cs.get_property_mut("zip_entries", &mut xc).is_err());
+    }
+
+    #[test]
+    fn elf64_lsb_tables() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let elf = b"\x7F\x45\x4C\x46\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x00\x3E\x00\x01\x00\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x00\x00\x00\x00\x50\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x40\x00\x38\x00\x01\x00\x40\x00\x06\x00\x05\x00\x02\x00\x00\x00\x06\x00\x00\x00\x88\x00\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x00\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x00\x6C\x69\x62\x63\x2E\x73\x6F\x2E\x36\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x6D\x61\x69\x6E\x00\x64\x61\x74\x61\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x01\x02\xF1\xFF\x00\x20\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x12\x00\x01\x00\x00\x10\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x2E\x73\x68\x73\x74\x72\x74\x61\x62\x00\x2E\x73\x74\x72\x74\x61\x62\x00\x2E\x73\x79\x6D\x74\x61\x62\x00\x2E\x64\x79\x6E\x61\x6D\x69\x63\x00\x2E\x64\x79\x6E\x73\x74\x72\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x24\x00\x00\x00\x03\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x78\x00\x00\x00\x00\x00\x00\x00\x0B\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1B\x00\x00\x00\x06\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x88\x00\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x0B\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xC8\x00\x00\x00\x00\x00\x00\x00\x0B\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x13\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xD8\x00\x00\x00\x00\x00\x00\x00\x48\x00\x00\x00\x00\x00\x00\x00\x03\x00\x00\x00\x02\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x18\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x20\x01\x00\x00\x00\x00\x00\x00\x2C\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        let mut s = BufferAsROStream::new(elf);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        match cs.get_property_mut("elf_section_headers", &mut xc).unwrap() {
+            DataCell::CellVector(v) => v.borrow().0.as_slice()[4]
+                .output_as_human_readable(&mut o, &mut xc).unwrap(),
+            _ => panic!("not a vector"),
+        }
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "elf_section_header(sh_name: b\".symtab\", sh_type: SHT_SYMTAB, sh_flags: 0x00, ",
+            "sh_addr: 0x00, sh_offset: 0xD8, sh_size: 0x48, sh_link: 3, sh_info: 2, ",
+            "sh_addralign: 8, sh_entsize: 24)"));
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("elf_dynamic", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[elf_dynamic(d_tag: DT_NEEDED, d_val: 0x01, d_str: b\"libc.so.6\")",
+            "elf_dynamic(d_tag: DT_STRTAB, d_val: 0x400)",
+            "elf_dynamic(d_tag: DT_NULL, d_val: 0x00)]"));
+        let mut o = xc.byte_vector();
+        match cs.get_property_mut("elf_symbols", &mut xc).unwrap() {
+            DataCell::CellVector(v) => v.borrow().0.as_slice()[2]
+                .output_as_human_readable(&mut o, &mut xc).unwrap(),
+            _ => panic!("not a vector"),
+        }
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "elf_symbol(table: b\".symtab\", st_name: b\"main\", st_value: 0x1000, ",
+            "st_size: 32, st_type: STT_FUNC, st_bind: STB_GLOBAL, st_visibility: STV_DEFAULT, ",
+            "st_shndx: 1)"));
+
+        let mut s = BufferAsROStream::new(b"\x28\xB5\x2F\xFD");
+        let mut cs = ContentStream::new(&mut s);
+        assert_eq!(cs.get_property_mut("elf_symbols", &mut xc).unwrap_err(), Error::NotApplicable);
+    }
+
+    #[test]
+    fn tof_ids_include_deep_signatures() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut tar = [0_u8; 0x200];
+        tar[0..6].copy_from_slice(b"a.txt\0");
+        tar[0x101..0x107].copy_from_slice(b"ustar\0");
+        let mut s = BufferAsROStream::new(&tar);
+        let mut cs = ContentStream::new(&mut s);
+        let mut o = xc.byte_vector();
+        cs.get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[tar]");
+        /* up to the end of the furthest magic that fits in the content */
+        assert_eq!(cs.get_property_byte_range_mut("tof_ids", &mut xc), Some((0, 0x106)));
+        let big = [0_u8; 0x2000];
+        let mut s = BufferAsROStream::new(&big);
+        let mut cs = ContentStream::new(&mut s);
+        assert_eq!(cs.get_property_byte_range_mut("tof_ids", &mut xc), Some((0, 0x1008)));
+    }
+
+    #[test]
+    fn tof_ids_with_registered_format() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut formats = FormatRegistry::new(xc.get_main_allocator());
+        formats.register(b"HBX1", 0x80, "hbx", None).unwrap();
+        let mut data = [0_u8; 0x84];
+        data[0..2].copy_from_slice(b"MZ");
+        data[0x80..].copy_from_slice(b"HBX1");
+        let mut s = BufferAsROStream::new(&data);
+        let mut o = xc.byte_vector();
+        ContentStream::with_formats(&mut s, &formats).get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[hbx]");
+        let mut o = xc.byte_vector();
+        ContentStream::new(&mut s).get_property_mut("tof_ids", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"[dos_exe]");
+    }
+
+    #[test]
+    fn tof_ids_for_macho() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        for (data, ids) in [
+            (&b"\xCF\xFA\xED\xFE\x07\x00\x00\x01"[..], &b"[machomacho64]"[..]),
+            (b"\xFE\xED\xFA\xCE\x00\x00\x00\x12", b"[machomacho32]"),
+            (b"\xCA\xFE\xBA\xBE\x00\x00\x00\x02", b"[macho_fat]"),
+            (b"\xCA\xFE\xBA\xBE\x00\x00\x00\x34", b"[]"),
+        ] {
+            let mut s = BufferAsROStream::new(data);
+            let mut cs = ContentStream::new(&mut s);
+            let mut o = xc.byte_vector();
+            cs.get_property_mut("tof_ids", &mut xc).unwrap()
+                .output_as_human_readable(&mut o, &mut xc).unwrap();
+            assert_eq!(o.as_slice(), ids);
+        }
+    }
+
+    #[test]
+    fn tof_ids_for_images() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        for (data, ids) in [
+            (&b"GIF89a\x01\x00"[..], &b"[imagegif]"[..]),
+            (b"\x89PNG\r\n\x1A\n", b"[imagepng]"),
+        ] {
+            let mut s = BufferAsROStream::new(data);
+            let mut cs = ContentStream::new(&mut s);
+            let mut o = xc.byte_vector();
+            cs.get_property_mut("tof_ids", &mut xc).unwrap()
+                .output_as_human_readable(&mut o, &mut xc).unwrap();
+            assert_eq!(o.as_slice(), ids);
+        }
+    }
 }
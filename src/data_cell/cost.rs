@@ -0,0 +1,98 @@
+use core::cell::RefCell;
+use core::fmt::Write as FmtWrite;
+
+use crate::ExecutionContext;
+use crate::num::fmt::SizeUnits;
+use super::DataCell;
+use super::Error;
+use super::Record;
+use super::RecordDesc;
+use super::expr::Expr;
+use super::human;
+
+const EXPR_COST: RecordDesc<'static> = RecordDesc::new(
+    "expr_cost",
+    &["expression", "items", "total_time_ns", "bytes_read", "total_time", "read"]);
+
+/* ExprCost *****************************************************************/
+/* what evaluating one expression took, summed over the items it ran on; the
+ * time is measured by the caller with whatever clock it has */
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ExprCost {
+    pub items: u64,
+    pub total_time_ns: u64,
+    pub bytes_read: u64,
+}
+
+impl ExprCost {
+
+    pub fn new() -> Self {
+        ExprCost::default()
+    }
+
+    pub fn add_item(&mut self, time_ns: u64, bytes_read: u64) {
+        self.items += 1;
+        self.total_time_ns = self.total_time_ns.saturating_add(time_ns);
+        self.bytes_read = self.bytes_read.saturating_add(bytes_read);
+    }
+
+    pub fn add(&mut self, other: &ExprCost) {
+        self.items += other.items;
+        self.total_time_ns = self.total_time_ns.saturating_add(other.total_time_ns);
+        self.bytes_read = self.bytes_read.saturating_add(other.bytes_read);
+    }
+
+    pub fn to_data_cell<'x>(
+        &self,
+        expr: &Expr<'_>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let mut text = xc.string();
+        write!(text, "{}", expr)?;
+        let mut r = Record::new(&EXPR_COST, a)?;
+        r.set_field("expression", DataCell::from_byte_slice(a, text.as_str().as_bytes())?);
+        r.set_field("items", DataCell::from_u64(self.items));
+        r.set_field("total_time_ns", DataCell::from_u64(self.total_time_ns));
+        r.set_field("bytes_read", DataCell::from_u64(self.bytes_read));
+        r.set_field("total_time", human::duration_cell(self.total_time_ns, xc)?);
+        r.set_field("read", human::size_cell(self.bytes_read, SizeUnits::Binary, xc)?);
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::data_cell::expr::Parser;
+    use crate::data_cell::expr::Source;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn accumulate_and_record() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("elf_header.e_entry", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_expr().unwrap().unwrap_data();
+
+        let mut c = ExprCost::new();
+        c.add_item(1500, 64);
+        c.add_item(500, 0);
+        let mut t = ExprCost::new();
+        t.add(&c);
+        t.add_item(u64::MAX, 1);
+        assert_eq!(t, ExprCost { items: 3, total_time_ns: u64::MAX, bytes_read: 65 });
+
+        let r = c.to_data_cell(&e, &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        r.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "expr_cost(expression: b\"elf_header.e_entry\", items: 2, ",
+            "total_time_ns: 2000, bytes_read: 64, total_time: 2.0 us, read: 64 B)"));
+    }
+}
@@ -0,0 +1,265 @@
+use core::fmt;
+use core::mem::size_of;
+
+use crate::ExecutionContext;
+use crate::mm::CowBytes;
+use crate::mm::Rc;
+use crate::mm::Vector;
+
+use super::DataCell;
+use super::Error;
+
+/* DeepSize *****************************************************************/
+/* allocated bytes reachable from a cell, split by the variant owning the
+ * allocation; a block shared by several Rc references is counted once, at
+ * the first place it is reached from */
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DeepSize {
+    pub byte_vector: usize,
+    pub text: usize,
+    pub cell_vector: usize,
+    pub record: usize,
+    pub map: usize,
+    pub dyn_cell: usize,
+    pub byte_stream: usize,
+    pub shared_refs: usize, // references to blocks that were already counted
+}
+
+impl DeepSize {
+    pub fn total(&self) -> usize {
+        self.byte_vector + self.text + self.cell_vector + self.record + self.map
+            + self.dyn_cell + self.byte_stream
+    }
+}
+
+impl fmt::Display for DeepSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte_vector: {}, text: {}, cell_vector: {}, record: {}, map: {}, dyn: {}, byte_stream: {}, total: {} (shared refs: {})",
+            self.byte_vector, self.text, self.cell_vector, self.record, self.map, self.dyn_cell,
+            self.byte_stream, self.total(), self.shared_refs)
+    }
+}
+
+struct Walker<'v> {
+    visited: Vector<'v, usize>, // sorted addresses of Rc blocks seen so far
+    size: DeepSize,
+}
+
+impl<'v> Walker<'v> {
+
+    /* returns the block size the first time an Rc is seen, None after */
+    fn first_visit<'x, T: ?Sized>(
+        &mut self,
+        rc: &Rc<'_, T>,
+    ) -> Result<Option<usize>, Error<'x>> {
+        let addr = rc.as_ref() as *const T as *const u8 as usize;
+        match self.visited.as_slice().binary_search(&addr) {
+            Ok(_) => {
+                self.size.shared_refs += 1;
+                Ok(None)
+            },
+            Err(i) => {
+                self.visited.insert(i, addr)?;
+                Ok(Some(Rc::alloc_size(rc)))
+            }
+        }
+    }
+
+    fn walk_cells<'x>(
+        &mut self,
+        cells: &[DataCell<'_>],
+    ) -> Result<(), Error<'x>> {
+        for c in cells {
+            self.walk(c)?;
+        }
+        Ok(())
+    }
+
+    fn walk<'x>(
+        &mut self,
+        cell: &DataCell<'_>,
+    ) -> Result<(), Error<'x>> {
+        match cell {
+            DataCell::Nothing | DataCell::U64(_) | DataCell::U128(_) | DataCell::I64(_)
+            | DataCell::F64(_) | DataCell::Bool(_) | DataCell::StaticId(_) => {},
+            DataCell::ByteVector(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    /* borrowed bytes belong to somebody else */
+                    self.size.byte_vector += n + match &rc.try_borrow()?.0 {
+                        CowBytes::Owned(v) => v.cap(),
+                        CowBytes::Borrowed(_) => 0,
+                    };
+                }
+            },
+            DataCell::Text(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    self.size.text += n + rc.try_borrow()?.cap();
+                }
+            },
+            DataCell::CellVector(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    let v = rc.try_borrow()?;
+                    self.size.cell_vector += n + v.0.cap() * size_of::<DataCell<'_>>();
+                    self.walk_cells(v.0.as_slice())?;
+                }
+            },
+            DataCell::Record(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    let r = rc.try_borrow()?;
+                    self.size.record += n + r.data.cap() * size_of::<DataCell<'_>>();
+                    self.walk_cells(r.data.as_slice())?;
+                }
+            },
+            DataCell::Map(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    let m = rc.try_borrow()?;
+                    self.size.map += n + m.0.table_size();
+                    for (k, v) in m.0.iter() {
+                        self.size.map += k.cap();
+                        self.walk(v)?;
+                    }
+                }
+            },
+            DataCell::Dyn(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    self.size.dyn_cell += n;
+                }
+            },
+            DataCell::ByteStream(rc) => {
+                if let Some(n) = self.first_visit(rc)? {
+                    self.size.byte_stream += n;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/* walks the cell tree; only the blocks directly owned by cells are known,
+ * anything allocated internally by dyn cells or streams is not included */
+pub fn deep_size<'x>(
+    cell: &DataCell<'_>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DeepSize, Error<'x>> {
+    let mut w = Walker {
+        visited: Vector::new(xc.get_main_allocator()),
+        size: DeepSize::default(),
+    };
+    w.walk(cell)?;
+    Ok(w.size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::data_cell::DCOMap;
+    use crate::data_cell::DCOVector;
+    use crate::data_cell::Record;
+    use crate::data_cell::RecordDesc;
+
+    #[test]
+    fn scalars_own_no_memory() {
+        let mut xc = ExecutionContext::nop();
+        assert_eq!(deep_size(&DataCell::Nothing, &mut xc).unwrap().total(), 0);
+        assert_eq!(deep_size(&DataCell::from_u64(5), &mut xc).unwrap().total(), 0);
+        assert_eq!(deep_size(&DataCell::from_static_id("x"), &mut xc).unwrap().total(), 0);
+    }
+
+    #[test]
+    fn text_counts_block_and_buffer() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_str(a.to_ref(), "text").unwrap();
+        let ds = deep_size(&c, &mut xc).unwrap();
+        assert!(ds.text >= 4);
+        assert_eq!(ds.total(), ds.text);
+    }
+
+    #[test]
+    fn byte_vector_counts_block_and_buffer() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_byte_slice(a.to_ref(), b"abc").unwrap();
+        let rc_size = match &c {
+            DataCell::ByteVector(rc) => Rc::alloc_size(rc),
+            _ => unreachable!(),
+        };
+        let left = a.space_left();
+        let ds = deep_size(&c, &mut xc).unwrap();
+        assert!(ds.byte_vector >= rc_size + 3);
+        assert_eq!(ds.total(), ds.byte_vector);
+        assert_eq!(a.space_left(), left);
+    }
+
+    #[test]
+    fn shared_cells_are_counted_once() {
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let bytes = DataCell::from_byte_slice(a.to_ref(), b"shared").unwrap();
+        let single = deep_size(&bytes, &mut xc).unwrap();
+
+        let mut v = Vector::new(a.to_ref());
+        for _ in 0..3 {
+            let c = match &bytes {
+                DataCell::ByteVector(rc) => DataCell::ByteVector(rc.clone()),
+                _ => unreachable!(),
+            };
+            v.push(c).unwrap();
+        }
+        let cv = DataCell::CellVector(
+            Rc::new(a.to_ref(), RefCell::new(DCOVector(v))).unwrap());
+        let ds = deep_size(&cv, &mut xc).unwrap();
+        assert_eq!(ds.byte_vector, single.byte_vector);
+        assert_eq!(ds.shared_refs, 2);
+        assert!(ds.cell_vector >= 3 * size_of::<DataCell<'_>>());
+    }
+
+    #[test]
+    fn record_fields_are_walked() {
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let desc = RecordDesc::new("r", &["a", "b"]);
+        let mut r = Record::new(&desc, a.to_ref()).unwrap();
+        r.set_field("a", DataCell::from_u64(1));
+        r.set_field("b", DataCell::from_byte_slice(a.to_ref(), b"xy").unwrap());
+        let c = DataCell::Record(Rc::new(a.to_ref(), RefCell::new(r)).unwrap());
+        let ds = deep_size(&c, &mut xc).unwrap();
+        assert!(ds.record >= 2 * size_of::<DataCell<'_>>());
+        assert!(ds.byte_vector > 2);
+        assert_eq!(ds.total(), ds.record + ds.byte_vector);
+    }
+
+    #[test]
+    fn map_keys_and_values_are_walked() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = DCOMap::new(a.to_ref());
+        m.insert("key", DataCell::from_byte_slice(a.to_ref(), b"value").unwrap()).unwrap();
+        let c = DataCell::from_map(a.to_ref(), m).unwrap();
+        let ds = deep_size(&c, &mut xc).unwrap();
+        assert!(ds.map > 3);
+        assert!(ds.byte_vector > 5);
+        assert_eq!(ds.total(), ds.map + ds.byte_vector);
+    }
+
+    #[test]
+    fn borrowed_cell_is_unavailable() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_byte_slice(a.to_ref(), b"abc").unwrap();
+        let _guard = match &c {
+            DataCell::ByteVector(rc) => rc.borrow_mut(),
+            _ => unreachable!(),
+        };
+        assert_eq!(deep_size(&c, &mut xc).unwrap_err(), Error::CellUnavailable);
+    }
+}
@@ -0,0 +1,313 @@
+use core::cell::RefCell;
+use core::convert::TryInto;
+use core::fmt::Write as FmtWrite;
+
+use crate::ExecutionContext;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::SeekFrom;
+use crate::io::stream::Write;
+use crate::mm::Vector;
+use crate::num::fmt as num_fmt;
+
+use super::DCOVector;
+use super::DataCell;
+use super::DataCellOps;
+use super::Error;
+use super::Record;
+use super::RecordDesc;
+use super::U64Cell;
+use super::BYTE_LEN_ALIASES;
+use super::is_property;
+
+pub const DUMP_ROW_LEN: usize = 16;
+pub const MAX_DUMP_ROW_LEN: usize = 0x100;
+/* streams are dumped in chunks of at most this size */
+const DUMP_CHUNK_SIZE: usize = 0x1000;
+
+const DUMP_ROW: RecordDesc<'static> = RecordDesc::new(
+    "dump_row",
+    &[ "offset", "bytes", "ascii" ]);
+
+fn hex_offset(n: u64) -> U64Cell {
+    U64Cell::with_fmt(n, num_fmt::MiniNumFmtPack::new(
+            num_fmt::Radix::new(16).unwrap(),
+            num_fmt::RadixNotation::DefaultPrefix,
+            num_fmt::MinDigitCount::new(8).unwrap(),
+            num_fmt::PositiveSign::Hidden,
+            num_fmt::ZeroSign::Hidden))
+}
+
+fn ascii_char(b: u8) -> u8 {
+    if (0x20..=0x7E).contains(&b) { b } else { b'.' }
+}
+
+/* HexDumpOptions ***********************************************************/
+/* layout of the text dumps: bytes per row (0 for the default, capped to
+ * MAX_DUMP_ROW_LEN), bytes per space-separated group (0 for no groups)
+ * and whether the ASCII gutter follows the bytes */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HexDumpOptions {
+    pub row_len: usize,
+    pub group_len: usize,
+    pub ascii: bool,
+}
+
+impl Default for HexDumpOptions {
+    fn default() -> Self {
+        HexDumpOptions { row_len: DUMP_ROW_LEN, group_len: 2, ascii: true }
+    }
+}
+
+impl HexDumpOptions {
+
+    pub fn effective_row_len(&self) -> usize {
+        match self.row_len {
+            0 => DUMP_ROW_LEN,
+            n => core::cmp::min(n, MAX_DUMP_ROW_LEN),
+        }
+    }
+
+}
+
+/* xxd style rows: offset, byte groups, ascii; a last partial row is padded
+ * so that its ascii gutter lines up with the ones above */
+pub fn output_hex_dump_rows<'w, 'x>(
+    data: &[u8],
+    offset: u64,
+    options: &HexDumpOptions,
+    out: &mut (dyn Write + 'w),
+) -> Result<(), Error<'x>> {
+    let row_len = options.effective_row_len();
+    let mut offset = offset;
+    for chunk in data.chunks(row_len) {
+        write!(out, "{:08x}:", offset)?;
+        for i in 0..row_len {
+            if i == 0 || (options.group_len != 0 && i % options.group_len == 0) {
+                write!(out, " ")?;
+            }
+            match chunk.get(i) {
+                Some(b) => write!(out, "{:02x}", b)?,
+                None => write!(out, "  ")?,
+            }
+        }
+        if options.ascii {
+            write!(out, "  ")?;
+            for &b in chunk {
+                write!(out, "{}", ascii_char(b) as char)?;
+            }
+        }
+        writeln!(out)?;
+        offset += chunk.len() as u64;
+    }
+    Ok(())
+}
+
+/* whole content of the stream, read in chunks holding whole rows */
+pub fn output_stream_hex_dump<'w, 'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    options: &HexDumpOptions,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if let Some(content) = stream.content_slice() {
+        return output_hex_dump_rows(content, 0, options, out);
+    }
+    stream.seek(SeekFrom::Start(0), xc)?;
+    let mut buffer = [0_u8; DUMP_CHUNK_SIZE];
+    let chunk_len = DUMP_CHUNK_SIZE - DUMP_CHUNK_SIZE % options.effective_row_len();
+    let mut offset = 0_u64;
+    loop {
+        let n = stream.read_uninterrupted(&mut buffer[0..chunk_len], xc)?;
+        output_hex_dump_rows(&buffer[0..n], offset, options, out)?;
+        offset += n as u64;
+        if n < chunk_len { break; }
+    }
+    Ok(())
+}
+
+/* HexDump ******************************************************************/
+/* bytes read from some offset of a stream, organized in rows of 16; the
+ * "rows" property gives records of (offset, bytes, ascii) for frontends
+ * that render dumps themselves while the text output is the classic
+ * xxd layout */
+#[derive(Debug)]
+pub struct HexDump<'a> {
+    offset: u64,
+    data: Vector<'a, u8>,
+}
+
+impl<'a> HexDump<'a> {
+
+    pub fn new(offset: u64, data: Vector<'a, u8>) -> Self {
+        HexDump { offset, data }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    fn rows<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let mut rows: Vector<'x, DataCell<'x>> = Vector::new(a);
+        rows.reserve(self.data.len().div_ceil(DUMP_ROW_LEN))?;
+        let mut offset = self.offset;
+        for chunk in self.data.as_slice().chunks(DUMP_ROW_LEN) {
+            let mut ascii = [0_u8; DUMP_ROW_LEN];
+            for (d, &s) in ascii.iter_mut().zip(chunk) {
+                *d = ascii_char(s);
+            }
+            let mut r = Record::new(&DUMP_ROW, a)?;
+            r.set_field("offset", DataCell::U64(hex_offset(offset)));
+            r.set_field("bytes", DataCell::from_byte_slice(a, chunk)?);
+            r.set_field("ascii", DataCell::from_byte_slice(a, &ascii[0..chunk.len()])?);
+            rows.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+            offset += chunk.len() as u64;
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(rows)))?))
+    }
+
+}
+
+impl<'a> DataCellOps for HexDump<'a> {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "offset" => Ok(DataCell::U64(hex_offset(self.offset))),
+            n if is_property(n, BYTE_LEN_ALIASES) => {
+                let v = self.data.len().try_into().unwrap();
+                Ok(DataCell::U64(U64Cell::new(v)))
+            },
+            "rows" => self.rows(xc),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    /* one line per row: offset, 8 groups of 2 bytes, ascii */
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        output_hex_dump_rows(self.data.as_slice(), self.offset, &HexDumpOptions::default(), out)
+    }
+
+    fn output_as_hex_dump<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        output_hex_dump_rows(self.data.as_slice(), self.offset, options, out)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn xxd_style_text() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let data = Vector::from_slice(b"\x7FELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00Hello!", a.to_ref()).unwrap();
+        let d = HexDump::new(0x40, data);
+        let mut o = xc.byte_vector();
+        d.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "00000040: 7f45 4c46 0201 0100 0000 0000 0000 0000  .ELF............\n",
+            "00000050: 4865 6c6c 6f21                           Hello!\n"));
+    }
+
+    #[test]
+    fn rows_property() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let data = Vector::from_slice(b"0123456789abcdef\x01z", a.to_ref()).unwrap();
+        let d = HexDump::new(0x10, data);
+        let rows = d.get_property("rows", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        rows.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[dump_row(offset: 0x00000010, bytes: b\"0123456789abcdef\", ascii: b\"0123456789abcdef\")",
+            "dump_row(offset: 0x00000020, bytes: b\"\\x01z\", ascii: b\".z\")]"));
+        for name in ["len", "LEN", "Size"] {
+            match d.get_property(name, &mut xc).unwrap() {
+                DataCell::U64(v) => assert_eq!(v.n, 18),
+                _ => panic!(),
+            }
+        }
+        assert!(matches!(d.get_property("lens", &mut xc), Err(Error::NotApplicable)));
+    }
+
+    #[test]
+    fn hex_dump_options() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_borrowed_bytes(a.to_ref(), b"\x7FELF\x02\x01\x01\x00\x00\x00Hi").unwrap();
+        let opts = HexDumpOptions { row_len: 8, group_len: 0, ascii: false };
+        let mut o = xc.byte_vector();
+        c.output_as_hex_dump(&mut o, &mut xc, &opts).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "00000000: 7f454c4602010100\n",
+            "00000008: 00004869        \n"));
+        let opts = HexDumpOptions { row_len: 4, group_len: 1, ascii: true };
+        let mut o = xc.byte_vector();
+        c.output_as_hex_dump(&mut o, &mut xc, &opts).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "00000000: 7f 45 4c 46  .ELF\n",
+            "00000004: 02 01 01 00  ....\n",
+            "00000008: 00 00 48 69  ..Hi\n"));
+        let c = DataCell::from_u64(1);
+        assert!(matches!(c.output_as_hex_dump(&mut o, &mut xc, &opts), Err(Error::NotApplicable)));
+    }
+
+    #[test]
+    fn stream_hex_dump_matches_slice() {
+        use crate::io::stream::BufferAsROStream;
+        use crate::io::stream::ChunkedReader;
+        let mut buffer = [0_u8; 0x20000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut content = [0_u8; 5000];
+        for (i, b) in content.iter_mut().enumerate() {
+            *b = (i * 7) as u8;
+        }
+        let opts = HexDumpOptions { row_len: 24, ..HexDumpOptions::default() };
+        let mut expected = xc.byte_vector();
+        output_hex_dump_rows(&content, 0, &opts, &mut expected).unwrap();
+        let mut r = ChunkedReader::new(BufferAsROStream::new(&content), 1000, 0);
+        let mut o = xc.byte_vector();
+        output_stream_hex_dump(&mut r, &opts, &mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), expected.as_slice());
+        // 208 full rows and one of 8 bytes
+        assert_eq!(o.len(), 209 * (9 + 12 * 5 + 2 + 24 + 1) - 16);
+    }
+
+    #[test]
+    fn empty_dump() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let d = HexDump::new(0, Vector::new(a.to_ref()));
+        let mut o = xc.byte_vector();
+        d.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert!(o.is_empty());
+    }
+}
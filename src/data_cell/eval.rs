@@ -1,23 +1,67 @@
 use core::slice;
+use core::cell::Ref;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt::Write as FmtWrite;
 
 use crate::ExecutionContext;
+use crate::data_cell::ByteVector;
 use crate::data_cell::DataCell;
 use crate::data_cell::DataCellOps;
 use crate::data_cell::Error;
+use crate::data_cell::I64Cell;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::data_cell::U64Cell;
+use crate::data_cell::expr::BinaryExpr;
+use crate::data_cell::expr::BinaryOp;
+use crate::data_cell::expr::ConditionalExpr;
 use crate::data_cell::expr::Expr;
+use crate::data_cell::expr::ExprList;
 use crate::data_cell::expr::PostfixExpr;
 use crate::data_cell::expr::PostfixRoot;
 use crate::data_cell::expr::PostfixItem;
 use crate::data_cell::expr::PrimaryExpr;
+use crate::data_cell::expr::Statement;
 use crate::log_debug;
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::CowBytes;
+use crate::mm::HashMap;
+use crate::mm::String;
+use crate::mm::Vector;
 
 pub trait Eval {
-    fn eval_with_cell_stack<'x>(
+    fn eval_in<'x>(
         &self,
         _cell_stack: &mut[DataCell<'x>],
+        _env: &Environment<'x>,
+        _functions: &FunctionRegistry<'_>,
         _xc: &mut ExecutionContext<'x>,
     ) -> Result<DataCell<'x>, Error<'x>>;
 
+    /* no names are bound */
+    fn eval_with_functions<'x>(
+        &self,
+        cell_stack: &mut[DataCell<'x>],
+        functions: &FunctionRegistry<'_>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let env = Environment::new(xc.get_main_allocator());
+        self.eval_in(cell_stack, &env, functions, xc)
+    }
+
+    /* only built-in functions can be called */
+    fn eval_with_cell_stack<'x>(
+        &self,
+        cell_stack: &mut[DataCell<'x>],
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let functions = FunctionRegistry::new(xc.get_main_allocator());
+        self.eval_with_functions(cell_stack, &functions, xc)
+    }
+
     fn eval_on_cell<'x>(
         &self,
         cell: &mut DataCell<'x>,
@@ -28,18 +72,23 @@ pub trait Eval {
 }
 
 impl Eval for PrimaryExpr<'_> {
-    fn eval_with_cell_stack<'x>(
+    fn eval_in<'x>(
         &self,
         cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
         xc: &mut ExecutionContext<'x>
     ) -> Result<DataCell<'x>, Error<'x>> {
         match self {
             PrimaryExpr::Identifier(s) => {
                 let s = s.as_str();
+                if let Some(v) = env.get(s) {
+                    return Ok(v.clone());
+                }
                 for c in cell_stack.rchunks_exact_mut(1) {
                     let c = &mut c[0];
                     log_debug!(xc, "querying {:?} for attr {:?}", c, s);
-                    match c.get_property(s, xc) {
+                    match c.get_member(s, xc) {
                         Ok(v) => {
                             return Ok(v);
                         },
@@ -52,47 +101,1058 @@ impl Eval for PrimaryExpr<'_> {
                 }
                 Err(Error::NotApplicable)
             },
+            PrimaryExpr::U64Literal(n) => Ok(DataCell::from_u64(*n)),
+            PrimaryExpr::BoolLiteral(b) => Ok(DataCell::from_bool(*b)),
+            PrimaryExpr::StringLiteral(s) =>
+                Ok(DataCell::from_str(xc.get_main_allocator(), s.as_str())?),
+            PrimaryExpr::Parenthesized(e) => e.eval_in(cell_stack, env, functions, xc),
         }
     }
 }
 
 impl Eval for PostfixRoot<'_> {
-    fn eval_with_cell_stack<'x>(
+    fn eval_in<'x>(
         &self,
         cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
         xc: &mut ExecutionContext<'x>
     ) -> Result<DataCell<'x>, Error<'x>> {
         match self {
-            PostfixRoot::Primary(pe) => pe.eval_with_cell_stack(cell_stack, xc),
+            PostfixRoot::Primary(pe) => pe.eval_in(cell_stack, env, functions, xc),
+            /* the innermost cell, which is the item when evaluating on one;
+             * bound names do not hide its properties */
+            PostfixRoot::Implied => cell_stack.last().cloned().ok_or(Error::NotApplicable),
         }
     }
 }
 
-impl Eval for PostfixExpr<'_> {
-    fn eval_with_cell_stack<'x>(
+impl<'t> PostfixExpr<'t> {
+
+    /* sets segment to the index of the part being evaluated: 0 for the
+     * root and i + 1 for items[i] */
+    fn eval_segments<'x>(
         &self,
+        segment: &mut usize,
         cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
         xc: &mut ExecutionContext<'x>
     ) -> Result<DataCell<'x>, Error<'x>> {
-        let mut v = self.root.eval_with_cell_stack(cell_stack, xc)?;
-        for pfi in self.items.as_slice() {
-            v = match pfi {
-                PostfixItem::Property(p) => v.get_property(p.as_str(), xc)?
-            };
+        let mut items = self.items.as_slice();
+        let mut v = match (&self.root, items.first()) {
+            (PostfixRoot::Primary(PrimaryExpr::Identifier(name)), Some(PostfixItem::Call(l))) => {
+                items = &items[1..];
+                let args = eval_args(None, l, cell_stack, env, functions, xc)?;
+                functions.call(name.as_str(), args.as_slice(), xc)?
+            },
+            _ => self.root.eval_in(cell_stack, env, functions, xc)?,
+        };
+        while let Some((pfi, rest)) = items.split_first() {
+            *segment = self.items.len() - rest.len();
+            items = rest;
+            match pfi {
+                PostfixItem::Property(p) => if let Some(PostfixItem::Call(l)) = items.first() {
+                    items = &items[1..];
+                    let args = eval_args(Some(v), l, cell_stack, env, functions, xc)?;
+                    v = functions.call(p.as_str(), args.as_slice(), xc)?;
+                } else {
+                    v = v.get_member(p.as_str(), xc)?;
+                },
+                PostfixItem::Subscript(l) => for e in l.as_slice() {
+                    let index = e.eval_in(cell_stack, env, functions, xc)?;
+                    v = v.get_item(&index)?;
+                },
+                /* cells cannot be called, only named functions */
+                PostfixItem::Call(_) => return Err(Error::NotApplicable),
+            }
         }
         Ok(v)
     }
+
+    /* wraps the error of a segment with the path leading to it; errors
+     * from inner expressions keep their own path */
+    fn eval_error<'x>(
+        &self,
+        segment: usize,
+        e: Error<'x>,
+        xc: &mut ExecutionContext<'x>
+    ) -> Error<'x> {
+        if let Error::Eval { .. } = e {
+            return e;
+        }
+        let mut path = xc.string();
+        if write!(path, "{}", self.root).is_err() {
+            return e;
+        }
+        for item in &self.items.as_slice()[..segment] {
+            if write!(path, "{}", item).is_err() {
+                return e;
+            }
+        }
+        let location = self.locations.as_slice().get(segment).copied();
+        match xc.boxed(e) {
+            Ok(cause) => Error::Eval { path, location, cause },
+            Err((_, e)) => e,
+        }
+    }
+
+}
+
+impl Eval for PostfixExpr<'_> {
+    fn eval_in<'x>(
+        &self,
+        cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
+        xc: &mut ExecutionContext<'x>
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut segment = 0;
+        self.eval_segments(&mut segment, cell_stack, env, functions, xc)
+            .map_err(|e| self.eval_error(segment, e, xc))
+    }
+}
+
+/* && and || only evaluate the right side when the left one does not
+ * decide the result */
+impl Eval for BinaryExpr<'_> {
+    fn eval_in<'x>(
+        &self,
+        cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
+        xc: &mut ExecutionContext<'x>
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let l = self.left.eval_in(cell_stack, env, functions, xc)?;
+        match (self.op, &l) {
+            (BinaryOp::LogicalAnd, DataCell::Bool(false))
+            | (BinaryOp::LogicalOr, DataCell::Bool(true)) => Ok(l),
+            (BinaryOp::LogicalAnd, DataCell::Bool(_))
+            | (BinaryOp::LogicalOr, DataCell::Bool(_)) => {
+                match self.right.eval_in(cell_stack, env, functions, xc)? {
+                    DataCell::Bool(b) => Ok(DataCell::Bool(b)),
+                    _ => Err(Error::NotApplicable),
+                }
+            },
+            (BinaryOp::LogicalAnd, _) | (BinaryOp::LogicalOr, _) => Err(Error::NotApplicable),
+            _ => {
+                let r = self.right.eval_in(cell_stack, env, functions, xc)?;
+                binary_op(self.op, &l, &r)
+            },
+        }
+    }
 }
 
+/* a condition that is not applicable or not set counts as false and any
+ * other non-boolean value as true, so that `a ? a.b : 0` works whether
+ * the item has an `a` or not; only the branch taken is evaluated */
+impl Eval for ConditionalExpr<'_> {
+    fn eval_in<'x>(
+        &self,
+        cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
+        xc: &mut ExecutionContext<'x>
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let taken = match self.condition.eval_in(cell_stack, env, functions, xc) {
+            Ok(DataCell::Bool(b)) => b,
+            Ok(DataCell::Nothing) => false,
+            Err(e) if *e.cause() == Error::NotApplicable => false,
+            Ok(_) => true,
+            Err(e) => return Err(e),
+        };
+        if taken {
+            self.if_true.eval_in(cell_stack, env, functions, xc)
+        } else {
+            self.if_false.eval_in(cell_stack, env, functions, xc)
+        }
+    }
+}
+
+/* each expression node evaluated takes one step of fuel */
 impl Eval for Expr<'_> {
-    fn eval_with_cell_stack<'x>(
+    fn eval_in<'x>(
         &self,
         cell_stack: &mut[DataCell<'x>],
+        env: &Environment<'x>,
+        functions: &FunctionRegistry<'_>,
         xc: &mut ExecutionContext<'x>
     ) -> Result<DataCell<'x>, Error<'x>> {
+        if !xc.consume_fuel(1) {
+            return Err(Error::LimitExceeded);
+        }
+        match self {
+            Expr::Postfix(pfe) => pfe.eval_in(cell_stack, env, functions, xc),
+            Expr::Binary(be) => be.eval_in(cell_stack, env, functions, xc),
+            Expr::Conditional(ce) => ce.eval_in(cell_stack, env, functions, xc),
+        }
+    }
+}
+
+/* Binary operators *********************************************************/
+fn int_operand(c: &DataCell<'_>) -> Option<i128> {
+    match c {
+        DataCell::U64(n) => Some(n.n as i128),
+        DataCell::U128(n) => i128::try_from(n.n).ok(),
+        DataCell::I64(n) => Some(n.n as i128),
+        _ => None,
+    }
+}
+
+enum Bytes<'c, 'd> {
+    Slice(&'c [u8]),
+    Vector(Ref<'c, ByteVector<'d>>),
+    Text(Ref<'c, String<'d>>),
+}
+
+impl Bytes<'_, '_> {
+    fn as_slice(&self) -> &[u8] {
         match self {
-            Expr::Postfix(pfe) => pfe.eval_with_cell_stack(cell_stack, xc),
+            Bytes::Slice(b) => b,
+            Bytes::Vector(v) => v.0.as_slice(),
+            Bytes::Text(t) => t.as_bytes(),
+        }
+    }
+}
+
+/* text and byte cells are compared by their bytes */
+fn bytes_operand<'c, 'd, 'x>(
+    c: &'c DataCell<'d>,
+) -> Result<Option<Bytes<'c, 'd>>, Error<'x>> {
+    Ok(match c {
+        DataCell::StaticId(s) => Some(Bytes::Slice(s.as_bytes())),
+        DataCell::ByteVector(v) => Some(Bytes::Vector(v.try_borrow()?)),
+        DataCell::Text(t) => Some(Bytes::Text(t.try_borrow()?)),
+        _ => None,
+    })
+}
+
+/* integers compare by value whatever their signedness, booleans only for
+ * equality */
+fn compare<'x>(
+    op: BinaryOp,
+    l: &DataCell<'_>,
+    r: &DataCell<'_>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let ord = if let (Some(a), Some(b)) = (int_operand(l), int_operand(r)) {
+        a.cmp(&b)
+    } else if let (DataCell::Bool(a), DataCell::Bool(b)) = (l, r) {
+        if op != BinaryOp::Equal && op != BinaryOp::NotEqual {
+            return Err(Error::NotApplicable);
+        }
+        a.cmp(b)
+    } else if let (Some(a), Some(b)) = (bytes_operand(l)?, bytes_operand(r)?) {
+        a.as_slice().cmp(b.as_slice())
+    } else {
+        return Err(Error::NotApplicable);
+    };
+    Ok(DataCell::Bool(match op {
+        BinaryOp::Equal => ord == Ordering::Equal,
+        BinaryOp::NotEqual => ord != Ordering::Equal,
+        BinaryOp::Less => ord == Ordering::Less,
+        BinaryOp::LessOrEqual => ord != Ordering::Greater,
+        BinaryOp::Greater => ord == Ordering::Greater,
+        BinaryOp::GreaterOrEqual => ord != Ordering::Less,
+        _ => unreachable!(),
+    }))
+}
+
+/* the result is U64 when both operands are U64 and I64 otherwise, with
+ * the number format of the left operand; results that do not fit are
+ * errors rather than wrapping around, shifts included */
+fn arithmetic<'x>(
+    op: BinaryOp,
+    l: &DataCell<'_>,
+    r: &DataCell<'_>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let (a, b, fmt_pack) = match (l, int_operand(r)) {
+        (DataCell::U64(n), Some(b)) => (n.n as i128, b, n.fmt_pack),
+        (DataCell::I64(n), Some(b)) => (n.n as i128, b, n.fmt_pack),
+        (DataCell::Bool(a), _) => return match (op, r) {
+            (BinaryOp::BitAnd, DataCell::Bool(b)) => Ok(DataCell::Bool(a & b)),
+            (BinaryOp::BitOr, DataCell::Bool(b)) => Ok(DataCell::Bool(a | b)),
+            (BinaryOp::BitXor, DataCell::Bool(b)) => Ok(DataCell::Bool(a ^ b)),
+            _ => Err(Error::NotApplicable),
+        },
+        _ => return Err(Error::NotApplicable),
+    };
+    let v = match op {
+        BinaryOp::Add => a.checked_add(b),
+        BinaryOp::Sub => a.checked_sub(b),
+        BinaryOp::Mul => a.checked_mul(b),
+        BinaryOp::Div | BinaryOp::Rem if b == 0 => return Err(Error::DivisionByZero),
+        BinaryOp::Div => a.checked_div(b),
+        BinaryOp::Rem => a.checked_rem(b),
+        BinaryOp::BitAnd => Some(a & b),
+        BinaryOp::BitOr => Some(a | b),
+        BinaryOp::BitXor => Some(a ^ b),
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+            let s = u32::try_from(b).ok().filter(|&s| s < 64).ok_or(Error::Overflow)?;
+            Some(if op == BinaryOp::ShiftLeft { a << s } else { a >> s })
+        },
+        _ => unreachable!(),
+    }.ok_or(Error::Overflow)?;
+    if let (DataCell::U64(_), DataCell::U64(_)) = (l, r) {
+        let n = u64::try_from(v).map_err(|_| Error::Overflow)?;
+        Ok(DataCell::from_u64_cell(U64Cell::with_fmt(n, fmt_pack)))
+    } else {
+        let n = i64::try_from(v).map_err(|_| Error::Overflow)?;
+        Ok(DataCell::from_i64_cell(I64Cell::with_fmt(n, fmt_pack)))
+    }
+}
+
+/* operators other than && and ||, which need lazy evaluation; operands of
+ * types the operator does not work with make it not applicable */
+pub fn binary_op<'x>(
+    op: BinaryOp,
+    l: &DataCell<'_>,
+    r: &DataCell<'_>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    match op {
+        BinaryOp::LogicalAnd | BinaryOp::LogicalOr => match (l, r) {
+            (DataCell::Bool(a), DataCell::Bool(b)) => Ok(DataCell::Bool(
+                if op == BinaryOp::LogicalAnd { *a && *b } else { *a || *b })),
+            _ => Err(Error::NotApplicable),
+        },
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less
+        | BinaryOp::LessOrEqual | BinaryOp::Greater
+        | BinaryOp::GreaterOrEqual => compare(op, l, r),
+        _ => arithmetic(op, l, r),
+    }
+}
+
+/* Functions ****************************************************************/
+pub type Function<'r> = dyn for<'x> Fn(
+    &[DataCell<'x>],
+    &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> + 'r;
+
+type Builtin = for<'x> fn(
+    &[DataCell<'x>],
+    &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>>;
+
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("len", builtin_len),
+    ("hex", builtin_hex),
+    ("min", builtin_min),
+    ("max", builtin_max),
+    ("slice", builtin_slice),
+    ("starts_with", builtin_starts_with),
+    ("ends_with", builtin_ends_with),
+];
+
+fn builtin_len<'x>(
+    args: &[DataCell<'x>],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    match args {
+        [c] => c.get_property("len", xc),
+        _ => Err(Error::NotApplicable),
+    }
+}
+
+fn builtin_hex<'x>(
+    args: &[DataCell<'x>],
+    _xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    match args {
+        [DataCell::U64(n)] => Ok(DataCell::from_u64_cell(U64Cell::hex(n.n))),
+        [DataCell::I64(n)] => Ok(DataCell::from_i64_cell(
+                I64Cell::with_fmt(n.n, U64Cell::hex(0).fmt_pack))),
+        _ => Err(Error::NotApplicable),
+    }
+}
+
+/* the first of the arguments that compares as `op` to all others */
+fn extreme<'x>(
+    op: BinaryOp,
+    args: &[DataCell<'x>],
+) -> Result<DataCell<'x>, Error<'x>> {
+    let (mut m, rest) = args.split_first().ok_or(Error::NotApplicable)?;
+    for c in rest {
+        if let DataCell::Bool(true) = compare(op, c, m)? {
+            m = c;
         }
     }
+    Ok(m.clone())
+}
+
+fn builtin_min<'x>(
+    args: &[DataCell<'x>],
+    _xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    extreme(BinaryOp::Less, args)
 }
 
+fn builtin_max<'x>(
+    args: &[DataCell<'x>],
+    _xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    extreme(BinaryOp::Greater, args)
+}
+
+/* slice(bytes, offset, len); borrowed bytes are sliced in place, other cells
+ * get their bytes copied */
+fn builtin_slice<'x>(
+    args: &[DataCell<'x>],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let (c, offset, len) = match args {
+        [c, DataCell::U64(offset), DataCell::U64(len)] => (c, offset.n, len.n),
+        _ => return Err(Error::NotApplicable),
+    };
+    let bytes = bytes_operand(c)?.ok_or(Error::NotApplicable)?;
+    let data = bytes.as_slice();
+    let start = usize::try_from(offset).ok()
+        .filter(|&start| start <= data.len())
+        .ok_or(Error::NotApplicable)?;
+    let end = usize::try_from(len).ok()
+        .and_then(|len| start.checked_add(len))
+        .filter(|&end| end <= data.len())
+        .ok_or(Error::NotApplicable)?;
+    if let DataCell::ByteVector(v) = c {
+        if let CowBytes::Borrowed(b) = v.try_borrow()?.0 {
+            return Ok(DataCell::from_borrowed_bytes(xc.get_main_allocator(), &b[start..end])?);
+        }
+    }
+    Ok(DataCell::from_byte_slice(xc.get_main_allocator(), &data[start..end])?)
+}
+
+fn affix_test<'x>(
+    args: &[DataCell<'x>],
+    test: fn(&[u8], &[u8]) -> bool,
+) -> Result<DataCell<'x>, Error<'x>> {
+    match args {
+        [c, affix] => match (bytes_operand(c)?, bytes_operand(affix)?) {
+            (Some(c), Some(affix)) => Ok(DataCell::Bool(test(c.as_slice(), affix.as_slice()))),
+            _ => Err(Error::NotApplicable),
+        },
+        _ => Err(Error::NotApplicable),
+    }
+}
+
+fn builtin_starts_with<'x>(
+    args: &[DataCell<'x>],
+    _xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    affix_test(args, <[u8]>::starts_with)
+}
+
+fn builtin_ends_with<'x>(
+    args: &[DataCell<'x>],
+    _xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    affix_test(args, <[u8]>::ends_with)
+}
+
+/* functions callable by name from expressions, either as f(a, b) or as
+ * a.f(b); registered functions hide built-in ones with the same name */
+pub struct FunctionRegistry<'r> {
+    custom: HashMap<'r, String<'r>, &'r Function<'r>>,
+}
+
+impl<'r> FunctionRegistry<'r> {
+
+    pub fn new(allocator: AllocatorRef<'r>) -> Self {
+        FunctionRegistry { custom: HashMap::new(allocator) }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &str,
+        function: &'r Function<'r>,
+    ) -> Result<(), AllocError> {
+        let name = String::from_str(name, self.custom.allocator())?;
+        self.custom.insert(name, function).map_err(|(e, _)| e)?;
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.custom.contains_key(name) || BUILTINS.iter().any(|(n, _)| *n == name)
+    }
+
+    /* unknown functions and arguments they do not take are not applicable */
+    pub fn call<'x>(
+        &self,
+        name: &str,
+        args: &[DataCell<'x>],
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        if let Some(f) = self.custom.get(name) {
+            return f(args, xc);
+        }
+        match BUILTINS.iter().find(|(n, _)| *n == name) {
+            Some((_, f)) => f(args, xc),
+            None => {
+                log_debug!(xc, "no function named {:?}", name);
+                Err(Error::NotApplicable)
+            },
+        }
+    }
+
+}
+
+/* the receiver of a.f(b) is the first argument */
+fn eval_args<'x>(
+    receiver: Option<DataCell<'x>>,
+    l: &ExprList<'_>,
+    cell_stack: &mut [DataCell<'x>],
+    env: &Environment<'x>,
+    functions: &FunctionRegistry<'_>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<Vector<'x, DataCell<'x>>, Error<'x>> {
+    let mut args = xc.vector();
+    args.reserve(l.as_slice().len() + receiver.is_some() as usize)?;
+    if let Some(c) = receiver {
+        args.push(c)?;
+    }
+    for e in l.as_slice() {
+        let v = e.eval_in(cell_stack, env, functions, xc)?;
+        args.push(v)?;
+    }
+    Ok(args)
+}
+
+/* Environment **************************************************************/
+/* values bound to names by let statements; names are looked up here before
+ * the cell stack, so a binding hides a property with the same name */
+#[derive(Debug)]
+pub struct Environment<'a> {
+    bindings: HashMap<'a, String<'a>, DataCell<'a>>,
+}
+
+impl<'a> Environment<'a> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        Environment { bindings: HashMap::new(allocator) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DataCell<'a>> {
+        self.bindings.get(name)
+    }
+
+    /* binding a name again replaces its value */
+    pub fn bind(
+        &mut self,
+        name: &str,
+        value: DataCell<'a>,
+    ) -> Result<(), AllocError> {
+        if let Some(v) = self.bindings.get_mut(name) {
+            *v = value;
+            return Ok(());
+        }
+        let name = String::from_str(name, self.bindings.allocator())?;
+        self.bindings.insert(name, value).map_err(|(e, _)| e)?;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+    }
+
+    /* a let binds its value and gives None, an expression gives its value;
+     * a let whose value fails leaves the name as it was */
+    pub fn exec(
+        &mut self,
+        statement: &Statement<'_>,
+        cell_stack: &mut [DataCell<'a>],
+        functions: &FunctionRegistry<'_>,
+        xc: &mut ExecutionContext<'a>,
+    ) -> Result<Option<DataCell<'a>>, Error<'a>> {
+        let v = statement.expr().eval_in(cell_stack, self, functions, xc)?;
+        match statement {
+            Statement::Let(ls) => {
+                self.bind(ls.name.as_str(), v)?;
+                Ok(None)
+            },
+            Statement::Expr(_) => Ok(Some(v)),
+        }
+    }
+
+}
+
+/* Provenance ***************************************************************/
+const PROVENANCE: RecordDesc<'static> = RecordDesc::new(
+    "provenance",
+    &[ "item", "path", "offset", "end" ]);
+
+/* describes where an evaluation result came from so that report rows can be
+ * post-processed without re-deriving the item and field */
+#[derive(Debug)]
+pub struct Provenance<'a> {
+    pub item_name: String<'a>,
+    pub path: String<'a>,
+    pub byte_range: Option<(u64, u64)>, // [start, end) in the item content
+}
+
+impl<'a> Provenance<'a> {
+
+    pub fn new(
+        item_name: &str,
+        expr: &Expr<'_>,
+        xc: &mut ExecutionContext<'a>,
+    ) -> Result<Self, AllocError> {
+        let item_name = xc.string_clone(item_name)?;
+        let mut path = xc.string();
+        write!(path, "{}", expr).map_err(|_| AllocError::NotEnoughMemory)?;
+        Ok(Provenance { item_name, path, byte_range: None })
+    }
+
+    pub fn to_data_cell(
+        &self,
+        xc: &mut ExecutionContext<'a>,
+    ) -> Result<DataCell<'a>, Error<'a>> {
+        let a = xc.get_main_allocator();
+        let mut r = Record::new(&PROVENANCE, a)?;
+        r.set_field("item", DataCell::from_byte_slice(a, self.item_name.as_str().as_bytes())?);
+        r.set_field("path", DataCell::from_byte_slice(a, self.path.as_str().as_bytes())?);
+        if let Some((start, end)) = self.byte_range {
+            r.set_field("offset", DataCell::from_u64_cell(U64Cell::hex(start)));
+            r.set_field("end", DataCell::from_u64_cell(U64Cell::hex(end)));
+        }
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+fn expr_byte_range(
+    expr: &Expr<'_>,
+    cell: &DataCell<'_>,
+    env: &Environment<'_>,
+    xc: &mut ExecutionContext<'_>,
+) -> Option<(u64, u64)> {
+    match expr {
+        Expr::Postfix(pfe) => match (&pfe.root, pfe.items.as_slice()) {
+            (PostfixRoot::Primary(PrimaryExpr::Identifier(s)), [])
+                if env.get(s.as_str()).is_none() =>
+                cell.get_property_byte_range(s.as_str(), xc),
+            (PostfixRoot::Implied, [PostfixItem::Property(s)]) =>
+                cell.get_property_byte_range(s.as_str(), xc),
+            _ => None,
+        },
+        Expr::Binary(_) | Expr::Conditional(_) => None,
+    }
+}
+
+/* evaluates the expression on the given item cell and also describes where
+ * the result comes from; values of bound names have no byte range */
+pub fn eval_with_provenance<'x>(
+    expr: &Expr<'_>,
+    item_name: &str,
+    cell: &mut DataCell<'x>,
+    env: &Environment<'x>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(DataCell<'x>, Provenance<'x>), Error<'x>> {
+    let functions = FunctionRegistry::new(xc.get_main_allocator());
+    let v = expr.eval_in(slice::from_mut(cell), env, &functions, xc)?;
+    let mut p = Provenance::new(item_name, expr, xc)?;
+    p.byte_range = expr_byte_range(expr, cell, env, xc);
+    Ok((v, p))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::data_cell::content_stream::ContentStream;
+    use crate::data_cell::DataCellOpsMut;
+    use crate::data_cell::expr::Parser;
+    use crate::data_cell::expr::Source;
+    use crate::dyn_rc;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::Rc;
+
+    dyn_rc!(make_data_cell_ops_rc, DataCellOps);
+
+    #[derive(Debug)]
+    struct Content(RefCell<BufferAsROStream<'static>>);
+    impl DataCellOps for Content {
+        fn get_property<'x>(
+            &self,
+            property_name: &str,
+            xc: &mut ExecutionContext<'x>,
+        ) -> Result<DataCell<'x>, Error<'x>> {
+            let mut s = self.0.borrow_mut();
+            ContentStream::new(&mut *s).get_property_mut(property_name, xc)
+        }
+        fn get_property_byte_range(
+            &self,
+            property_name: &str,
+            xc: &mut ExecutionContext<'_>,
+        ) -> Option<(u64, u64)> {
+            let mut s = self.0.borrow_mut();
+            ContentStream::new(&mut *s).get_property_byte_range_mut(property_name, xc)
+        }
+    }
+
+    fn provenance_text(expr_text: &str, content: &'static [u8]) -> std::string::String {
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new(expr_text, "test");
+        let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+        let c = Content(RefCell::new(BufferAsROStream::new(content)));
+        let mut root = DataCell::Dyn(make_data_cell_ops_rc(Rc::new(a.to_ref(), c).unwrap()));
+        let (_v, p) = eval_with_provenance(&expr, "item1", &mut root, &Environment::new(a.to_ref()), &mut xc).unwrap();
+        assert_eq!(p.item_name.as_str(), "item1");
+        let mut o = xc.byte_vector();
+        p.to_data_cell(&mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        std::string::String::from_utf8(o.as_slice().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn provenance_with_byte_range() {
+        assert_eq!(provenance_text("first_byte", b"\x7FELF"),
+                   "provenance(item: b\"item1\", path: b\"first_byte\", offset: 0x00, end: 0x01)");
+        assert_eq!(provenance_text(".first_byte", b"\x7FELF"),
+                   "provenance(item: b\"item1\", path: b\".first_byte\", offset: 0x00, end: 0x01)");
+        assert_eq!(provenance_text("first_8_bytes", b"abc"),
+                   "provenance(item: b\"item1\", path: b\"first_8_bytes\", offset: 0x00, end: 0x03)");
+    }
+
+    #[test]
+    fn provenance_without_byte_range() {
+        assert_eq!(provenance_text("first_8_bytes.len", b"abcdefghij"),
+                   "provenance(item: b\"item1\", path: b\"first_8_bytes.len\")");
+        assert_eq!(provenance_text("fourty_two", b""),
+                   "provenance(item: b\"item1\", path: b\"fourty_two\")");
+    }
+
+    #[test]
+    fn fuel_limits_evaluation() {
+        use crate::ExecLimits;
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("(1 + 2) * (3 + 4)", "test");
+        let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+        {
+            /* cells evaluated in a child cannot outlive it */
+            let mut item = DataCell::from_u64(0);
+            let mut child = xc.child(ExecLimits { fuel: Some(3), ..ExecLimits::default() });
+            let e = expr.eval_on_cell(&mut item, &mut child).unwrap_err();
+            assert_eq!(e.into_cause(), Error::LimitExceeded);
+            assert_eq!(child.get_fuel(), Some(0));
+        }
+        let mut item = DataCell::from_u64(0);
+        let mut child = xc.child(ExecLimits { fuel: Some(100), ..ExecLimits::default() });
+        let v = expr.eval_on_cell(&mut item, &mut child).unwrap();
+        assert!(matches!(v, DataCell::U64(n) if n.n == 21));
+        assert!(child.get_fuel().unwrap() < 97);
+    }
+
+    #[test]
+    fn map_entries_by_name() {
+        use crate::data_cell::DCOMap;
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut inner = DCOMap::new(a.to_ref());
+        inner.insert("machine", DataCell::from_u64(62)).unwrap();
+        let mut m = DCOMap::new(a.to_ref());
+        m.insert("header", DataCell::from_map(a.to_ref(), inner).unwrap()).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let mut eval_text = |expr_text: &str| {
+            let src = Source::new(expr_text, "test");
+            let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let v = expr.eval_on_cell(&mut root, &mut xc).map_err(Error::into_cause)?;
+            let mut o = xc.byte_vector();
+            v.output_as_human_readable(&mut o, &mut xc).unwrap();
+            Ok(std::string::String::from_utf8(o.as_slice().to_vec()).unwrap())
+        };
+        assert_eq!(eval_text("header.machine"), Ok("62".into()));
+        assert_eq!(eval_text("header.len"), Ok("1".into()));
+        assert_eq!(eval_text("header.keys"), Ok("[\"machine\"]".into()));
+        assert_eq!(eval_text("header.class"), Err(Error::NotApplicable));
+        assert_eq!(eval_text("header[\"machine\"]"), Ok("62".into()));
+        assert_eq!(eval_text("\"a\\x41\".len"), Ok("2".into()));
+        assert_eq!(eval_text("true"), Ok("true".into()));
+        assert_eq!(eval_text("0x10"), Ok("16".into()));
+    }
+
+    #[test]
+    fn errors_carry_path_and_location() {
+        use crate::data_cell::DCOMap;
+        use crate::data_cell::expr::SourceLocation;
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut inner = DCOMap::new(a.to_ref());
+        inner.insert("machine", DataCell::from_u64(62)).unwrap();
+        let mut m = DCOMap::new(a.to_ref());
+        m.insert("header", DataCell::from_map(a.to_ref(), inner).unwrap()).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let mut eval_error = |expr_text: &str| {
+            let src = Source::new(expr_text, "test");
+            let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let e = expr.eval_on_cell(&mut root, &mut xc).unwrap_err();
+            let text = std::format!("{}", e);
+            match e {
+                Error::Eval { path, location, cause } =>
+                    (std::string::String::from(path.as_str()), location, cause.into_inner(), text),
+                _ => panic!("no context for {}", e),
+            }
+        };
+        let (path, location, cause, text) = eval_error("header.machine + header.class.x");
+        assert_eq!(path, "header.class");
+        assert_eq!(location, Some(SourceLocation { line: 1, column: 24 }));
+        assert_eq!(cause, Error::NotApplicable);
+        assert_eq!(text, "1:24: header.class: not applicable");
+        let (path, location, _, _) = eval_error("header[missing]");
+        assert_eq!(path, "missing");
+        assert_eq!(location, Some(SourceLocation { line: 1, column: 8 }));
+        let (path, _, cause, _) = eval_error("header.machine[0]");
+        assert_eq!(path, "header.machine[0]");
+        assert_eq!(cause, Error::NotApplicable);
+    }
+
+    #[test]
+    fn subscripts_and_record_fields() {
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        const HDR: RecordDesc<'static> = RecordDesc::new("hdr", &["e_machine", "ids"]);
+        let mut ids = xc.vector();
+        ids.push(DataCell::from_u64(10)).unwrap();
+        ids.push(DataCell::from_u64(11)).unwrap();
+        let mut r = Record::new(&HDR, a.to_ref()).unwrap();
+        r.set_field("e_machine", DataCell::from_u64(62));
+        r.set_field("ids", DataCell::CellVector(xc.rc(RefCell::new(crate::data_cell::DCOVector(ids))).unwrap()));
+        let mut root = DataCell::Record(xc.rc(RefCell::new(r)).unwrap());
+        let mut eval_text = |expr_text: &str| {
+            let src = Source::new(expr_text, "test");
+            let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let v = expr.eval_on_cell(&mut root, &mut xc).map_err(Error::into_cause)?;
+            let mut o = xc.byte_vector();
+            v.output_as_human_readable(&mut o, &mut xc).unwrap();
+            Ok(std::string::String::from_utf8(o.as_slice().to_vec()).unwrap())
+        };
+        assert_eq!(eval_text("e_machine"), Ok("62".into()));
+        assert_eq!(eval_text("ids[1]"), Ok("11".into()));
+        assert_eq!(eval_text("ids[ids.len].x"), Err(Error::NotApplicable));
+        assert_eq!(eval_text("ids[0].bswap16"), Ok("2560".into()));
+        assert_eq!(eval_text("ids[1, 0]"), Err(Error::NotApplicable));
+        assert_eq!(eval_text("ids[0 ]"), Ok("10".into()));
+        assert_eq!(eval_text("ids.len"), Ok("2".into()));
+    }
+
+    #[test]
+    fn binary_operators() {
+        let mut buf = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = crate::data_cell::DCOMap::new(a.to_ref());
+        m.insert("entry", DataCell::from_u64_cell(U64Cell::hex(0x1000))).unwrap();
+        m.insert("delta", DataCell::from_i64(-3)).unwrap();
+        m.insert("class", DataCell::from_static_id("ELFCLASS64")).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let mut eval_text = |expr_text: &str| {
+            let src = Source::new(expr_text, "test");
+            let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let v = expr.eval_on_cell(&mut root, &mut xc).map_err(Error::into_cause)?;
+            let mut o = xc.byte_vector();
+            v.output_as_human_readable(&mut o, &mut xc).unwrap();
+            Ok(std::string::String::from_utf8(o.as_slice().to_vec()).unwrap())
+        };
+        assert_eq!(eval_text("entry + 0x20 * 2"), Ok("0x1040".into()));
+        assert_eq!(eval_text("(1 + 2) * 3 % 5"), Ok("4".into()));
+        assert_eq!(eval_text("delta * 2 + 1"), Ok("-5".into()));
+        assert_eq!(eval_text("10 / delta"), Ok("-3".into()));
+        assert_eq!(eval_text("1 << 4 | 3 ^ 1 & 7"), Ok("18".into()));
+        assert_eq!(eval_text("entry >> 8"), Ok("0x10".into()));
+        assert_eq!(eval_text("1 - 2"), Err(Error::Overflow));
+        assert_eq!(eval_text("0xFFFFFFFFFFFFFFFF + 1"), Err(Error::Overflow));
+        assert_eq!(eval_text("1 << 64"), Err(Error::Overflow));
+        assert_eq!(eval_text("0x8000000000000000 << 1"), Err(Error::Overflow));
+        assert_eq!(eval_text("5 % 0"), Err(Error::DivisionByZero));
+        assert_eq!(eval_text("delta < 0 && entry >= 4096"), Ok("true".into()));
+        assert_eq!(eval_text("class == \"ELFCLASS64\""), Ok("true".into()));
+        assert_eq!(eval_text("\"abc\" < \"abd\" != false"), Ok("true".into()));
+        assert_eq!(eval_text("true & false | true"), Ok("true".into()));
+        assert_eq!(eval_text("false && missing"), Ok("false".into()));
+        assert_eq!(eval_text("true || missing.x"), Ok("true".into()));
+        assert_eq!(eval_text("true && missing"), Err(Error::NotApplicable));
+        assert_eq!(eval_text("1 && true"), Err(Error::NotApplicable));
+        assert_eq!(eval_text("true < false"), Err(Error::NotApplicable));
+        assert_eq!(eval_text("class + 1"), Err(Error::NotApplicable));
+    }
+
+    #[test]
+    fn function_calls() {
+        let mut buf = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = crate::data_cell::DCOMap::new(a.to_ref());
+        m.insert("magic", DataCell::from_borrowed_bytes(a.to_ref(), b"\x7FELF\x02").unwrap()).unwrap();
+        m.insert("name", DataCell::from_str(a.to_ref(), "libc.so.6").unwrap()).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let mut functions = FunctionRegistry::new(a.to_ref());
+        functions.register("twice", &|args, _xc| match args {
+            [DataCell::U64(n)] => Ok(DataCell::from_u64(n.n * 2)),
+            _ => Err(Error::NotApplicable),
+        }).unwrap();
+        functions.register("len", &|_args, _xc| Ok(DataCell::from_u64(0))).unwrap();
+        let mut eval_text = |expr_text: &str, functions: &FunctionRegistry<'_>| {
+            let src = Source::new(expr_text, "test");
+            let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let v = expr.eval_with_functions(slice::from_mut(&mut root), functions, &mut xc)
+                .map_err(Error::into_cause)?;
+            let mut o = xc.byte_vector();
+            v.output_as_human_readable(&mut o, &mut xc).unwrap();
+            Ok(std::string::String::from_utf8(o.as_slice().to_vec()).unwrap())
+        };
+        let builtins = FunctionRegistry::new(a.to_ref());
+        assert_eq!(eval_text("len(magic) + name.len()", &builtins), Ok("14".into()));
+        assert_eq!(eval_text("hex(magic[0])", &builtins), Ok("0x7F".into()));
+        assert_eq!(eval_text("min(3, 1, 2) + max(3, 1, 2)", &builtins), Ok("4".into()));
+        assert_eq!(eval_text("min(name, \"libz\")", &builtins), Ok("\"libc.so.6\"".into()));
+        assert_eq!(eval_text("slice(magic, 1, 3)", &builtins), Ok("b\"ELF\"".into()));
+        assert_eq!(eval_text("name.slice(0, 4)", &builtins), Ok("b\"libc\"".into()));
+        assert_eq!(eval_text("slice(magic, 3, 3)", &builtins), Err(Error::NotApplicable));
+        assert_eq!(eval_text("magic.starts_with(\"\\x7FELF\")", &builtins), Ok("true".into()));
+        assert_eq!(eval_text("name.ends_with(\".so\")", &builtins), Ok("false".into()));
+        assert_eq!(eval_text("min()", &builtins), Err(Error::NotApplicable));
+        assert_eq!(eval_text("nope(1)", &builtins), Err(Error::NotApplicable));
+        assert_eq!(eval_text("magic[0](1)", &builtins), Err(Error::NotApplicable));
+        assert_eq!(eval_text("twice(21)", &builtins), Err(Error::NotApplicable));
+        assert_eq!(eval_text("twice(21)", &functions), Ok("42".into()));
+        assert_eq!(eval_text("magic.len().twice()", &functions), Ok("0".into()));
+        assert!(functions.contains("hex"));
+    }
+
+    #[test]
+    fn let_bindings() {
+        let mut buf = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = crate::data_cell::DCOMap::new(a.to_ref());
+        m.insert("machine", DataCell::from_u64(62)).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let calls = core::cell::Cell::new(0);
+        let table: &Function<'_> = &|_args, xc| {
+            calls.set(calls.get() + 1);
+            let mut v = xc.vector();
+            v.push(DataCell::from_u64(5))?;
+            v.push(DataCell::from_u64(8))?;
+            Ok(DataCell::CellVector(xc.rc(RefCell::new(crate::data_cell::DCOVector(v)))?))
+        };
+        let mut functions = FunctionRegistry::new(a.to_ref());
+        functions.register("table", table).unwrap();
+        let src = Source::new(
+            "let t = table(); t.len, t[0] + t[1]; machine := t[1]; machine, let t = 0; t, unknown", "test");
+        let statements = Parser::new(&src, &xc).parse_statement_list().unwrap().unwrap_data();
+        let mut env = Environment::new(a.to_ref());
+        let mut results = std::vec::Vec::new();
+        for st in statements.as_slice() {
+            let r = env.exec(st, slice::from_mut(&mut root), &functions, &mut xc)
+                .map_err(Error::into_cause);
+            results.push(r.map(|v| v.map(|v| {
+                let mut o = xc.byte_vector();
+                v.output_as_human_readable(&mut o, &mut xc).unwrap();
+                std::string::String::from_utf8(o.as_slice().to_vec()).unwrap()
+            })));
+        }
+        assert_eq!(results, [
+            Ok(None), Ok(Some("2".into())), Ok(Some("13".into())),
+            Ok(None), Ok(Some("8".into())),
+            Ok(None), Ok(Some("0".into())), Err(Error::NotApplicable),
+        ]);
+        assert_eq!(calls.get(), 1);
+        assert_eq!(env.len(), 2);
+
+        // a bound name has no byte range in the item
+        let expr = statements.as_slice()[4].expr();
+        let (v, p) = eval_with_provenance(expr, "item", &mut root, &env, &mut xc).unwrap();
+        assert!(matches!(v, DataCell::U64(ref n) if n.n == 8));
+        assert_eq!(p.byte_range, None);
+        env.clear();
+        assert!(env.is_empty());
+    }
+
+    #[test]
+    fn implied_root_is_the_item() {
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = crate::data_cell::DCOMap::new(a.to_ref());
+        m.insert("machine", DataCell::from_u64(62)).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let src = Source::new("machine := 1; machine, .machine, .machine + machine, .len(), .keys[0]", "test");
+        let statements = Parser::new(&src, &xc).parse_statement_list().unwrap().unwrap_data();
+        let functions = FunctionRegistry::new(a.to_ref());
+        let mut env = Environment::new(a.to_ref());
+        let mut results = std::vec::Vec::new();
+        for st in statements.as_slice() {
+            if let Some(v) = env.exec(st, slice::from_mut(&mut root), &functions, &mut xc).unwrap() {
+                let mut o = xc.byte_vector();
+                v.output_as_human_readable(&mut o, &mut xc).unwrap();
+                results.push(std::string::String::from_utf8(o.as_slice().to_vec()).unwrap());
+            }
+        }
+        assert_eq!(results, ["1", "62", "63", "1", "\"machine\""]);
+        let e = statements.as_slice()[2].expr();
+        assert!(matches!(e.eval_with_cell_stack(&mut [], &mut xc).map_err(Error::into_cause),
+                         Err(Error::NotApplicable)));
+    }
+
+    #[test]
+    fn conditional_evaluates_one_branch() {
+        let mut buf = [0_u8; 0x3000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = crate::data_cell::DCOMap::new(a.to_ref());
+        m.insert("machine", DataCell::from_u64(62)).unwrap();
+        m.insert("unset", DataCell::Nothing).unwrap();
+        let mut root = DataCell::from_map(a.to_ref(), m).unwrap();
+        let mut eval_text = |expr_text: &str| {
+            let src = Source::new(expr_text, "test");
+            let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let v = expr.eval_on_cell(&mut root, &mut xc).map_err(Error::into_cause)?;
+            let mut o = xc.byte_vector();
+            v.output_as_human_readable(&mut o, &mut xc).unwrap();
+            Ok(std::string::String::from_utf8(o.as_slice().to_vec()).unwrap())
+        };
+        assert_eq!(eval_text("machine == 62 ? \"x86_64\" : \"other\""), Ok("\"x86_64\"".into()));
+        assert_eq!(eval_text("machine ? machine + 1 : 0"), Ok("63".into()));
+        assert_eq!(eval_text("elf_header ? elf_header.e_machine : 0"), Ok("0".into()));
+        assert_eq!(eval_text("unset ? 1 : 2"), Ok("2".into()));
+        assert_eq!(eval_text("true ? 1 : 1 / 0"), Ok("1".into()));
+        assert_eq!(eval_text("false ? 1 / 0 : missing ? 2 : 3"), Ok("3".into()));
+        assert_eq!(eval_text("1 / 0 ? 1 : 2"), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn longest_operator_chain() {
+        extern crate std;
+        use core::fmt::Write;
+        use crate::data_cell::expr::MAX_EXPR_OPERATORS;
+        let mut buf = std::vec![0_u8; 0x100000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let text = "1 + ".repeat(MAX_EXPR_OPERATORS as usize) + "1";
+        let src = Source::new(&text, "test");
+        let expr = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+        let v = expr.eval_on_cell(&mut DataCell::Nothing, &mut xc).unwrap();
+        assert!(matches!(v, DataCell::U64(n) if n.n == MAX_EXPR_OPERATORS as u64 + 1));
+        let mut o = std::string::String::new();
+        write!(o, "{}", expr).unwrap();
+        assert_eq!(o.len(), text.len());
+    }
+
+    #[test]
+    fn elf_header_range_depends_on_class() {
+        assert!(provenance_text("elf_header", b"\x7FELF\x01\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00")
+                .ends_with("end: 0x24)"));
+    }
+}
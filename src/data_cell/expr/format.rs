@@ -0,0 +1,333 @@
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FmtResult;
+use core::fmt::Write as FmtWrite;
+
+use crate::ExecutionContext;
+use crate::data_cell::Error;
+use crate::io::stream::Write;
+
+use super::ConditionalExpr;
+use super::Expr;
+use super::ExprList;
+use super::PostfixExpr;
+use super::PostfixItem;
+use super::PostfixRoot;
+use super::PrimaryExpr;
+use super::fmt_string_literal;
+
+/* Spacing ******************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Spacing {
+    Canonical, // a + b, f(x, y), a ? b : c
+    Compact, // a+b, f(x,y), a?b:c
+}
+
+/* Radix ********************************************************************/
+/* of integer literals, in the forms the parser takes */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Radix {
+    Decimal,
+    Hex,
+    Binary,
+}
+
+/* Parens *******************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Parens {
+    Keep, // as written in the source
+    Minimal, // only where the grouping needs them
+    Full, // around every operand with an operator of its own
+}
+
+/* FormatOptions ************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FormatOptions {
+    pub spacing: Spacing,
+    pub radix: Radix,
+    pub parens: Parens,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            spacing: Spacing::Canonical,
+            radix: Radix::Decimal,
+            parens: Parens::Keep,
+        }
+    }
+}
+
+/* Slot *********************************************************************/
+/* where an expression sits in the one containing it */
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Slot {
+    Top, // whole expression, list item, argument or subscript
+    Left(u8), // operand before a binary operator of the given precedence
+    Right(u8),
+    Condition,
+    Branch,
+    Postfix { call_follows: bool }, // root followed by some items
+}
+
+struct Printer<'o> {
+    options: &'o FormatOptions,
+}
+
+impl<'o> Printer<'o> {
+
+    fn needs_parens(&self, e: &Expr<'_>, slot: Slot) -> bool {
+        match (e, slot) {
+            (_, Slot::Top) => false,
+            /* (f)(x) calls the value of f rather than the function f */
+            (_, Slot::Postfix { call_follows: true }) => true,
+            (Expr::Postfix(_), _) => false,
+            (_, Slot::Postfix { .. }) => true,
+            (_, _) if self.options.parens == Parens::Full => true,
+            (Expr::Conditional(_), Slot::Branch) => false,
+            (Expr::Conditional(_), _) => true,
+            (Expr::Binary(be), Slot::Left(p)) => be.op.precedence() < p,
+            (Expr::Binary(be), Slot::Right(p)) => be.op.precedence() <= p,
+            (Expr::Binary(_), _) => false,
+        }
+    }
+
+    fn write_sep(&self, sep: &str, f: &mut Formatter<'_>) -> FmtResult {
+        match self.options.spacing {
+            Spacing::Canonical => write!(f, " {} ", sep),
+            Spacing::Compact => f.write_str(sep),
+        }
+    }
+
+    fn write_u64(&self, n: u64, f: &mut Formatter<'_>) -> FmtResult {
+        match self.options.radix {
+            Radix::Decimal => write!(f, "{}", n),
+            Radix::Hex => write!(f, "0x{:X}", n),
+            Radix::Binary => write!(f, "0b{:b}", n),
+        }
+    }
+
+    fn write_list(&self, l: &ExprList<'_>, f: &mut Formatter<'_>) -> FmtResult {
+        for (i, e) in l.as_slice().iter().enumerate() {
+            if i != 0 {
+                f.write_char(',')?;
+                if self.options.spacing == Spacing::Canonical {
+                    f.write_char(' ')?;
+                }
+            }
+            self.write_expr(e, Slot::Top, f)?;
+        }
+        Ok(())
+    }
+
+    /* slot is the one of the parenthesized expression the primary is */
+    fn write_primary(
+        &self,
+        pe: &PrimaryExpr<'_>,
+        slot: Slot,
+        f: &mut Formatter<'_>
+    ) -> FmtResult {
+        match pe {
+            PrimaryExpr::Identifier(s) => f.write_str(s.as_str()),
+            PrimaryExpr::U64Literal(n) => self.write_u64(*n, f),
+            PrimaryExpr::BoolLiteral(b) => write!(f, "{}", b),
+            PrimaryExpr::StringLiteral(s) => fmt_string_literal(s.as_str(), f),
+            PrimaryExpr::Parenthesized(e) => if self.options.parens == Parens::Keep {
+                f.write_char('(')?;
+                self.write_expr(e, Slot::Top, f)?;
+                f.write_char(')')
+            } else {
+                self.write_expr(e, slot, f)
+            },
+        }
+    }
+
+    fn write_postfix(
+        &self,
+        pfe: &PostfixExpr<'_>,
+        slot: Slot,
+        f: &mut Formatter<'_>
+    ) -> FmtResult {
+        let items = pfe.items.as_slice();
+        if let PostfixRoot::Primary(pe) = &pfe.root {
+            let root_slot = match items.first() {
+                None => slot,
+                Some(item) => Slot::Postfix {
+                    call_follows: matches!(item, PostfixItem::Call(_)),
+                },
+            };
+            self.write_primary(pe, root_slot, f)?;
+        }
+        for item in items {
+            match item {
+                PostfixItem::Property(s) => write!(f, ".{}", s)?,
+                PostfixItem::Subscript(l) => {
+                    f.write_char('[')?;
+                    self.write_list(l, f)?;
+                    f.write_char(']')?;
+                },
+                PostfixItem::Call(l) => {
+                    f.write_char('(')?;
+                    self.write_list(l, f)?;
+                    f.write_char(')')?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn write_conditional(
+        &self,
+        ce: &ConditionalExpr<'_>,
+        f: &mut Formatter<'_>
+    ) -> FmtResult {
+        self.write_expr(&ce.condition, Slot::Condition, f)?;
+        self.write_sep("?", f)?;
+        self.write_expr(&ce.if_true, Slot::Branch, f)?;
+        self.write_sep(":", f)?;
+        self.write_expr(&ce.if_false, Slot::Branch, f)
+    }
+
+    fn write_expr(&self, e: &Expr<'_>, slot: Slot, f: &mut Formatter<'_>) -> FmtResult {
+        let parens = self.options.parens != Parens::Keep && self.needs_parens(e, slot);
+        if parens {
+            f.write_char('(')?;
+        }
+        match e {
+            Expr::Postfix(pfe) =>
+                self.write_postfix(pfe, if parens { Slot::Top } else { slot }, f)?,
+            Expr::Binary(be) => {
+                let p = be.op.precedence();
+                self.write_expr(&be.left, Slot::Left(p), f)?;
+                self.write_sep(be.op.as_str(), f)?;
+                self.write_expr(&be.right, Slot::Right(p), f)?;
+            },
+            Expr::Conditional(ce) => self.write_conditional(ce, f)?,
+        }
+        if parens {
+            f.write_char(')')?;
+        }
+        Ok(())
+    }
+
+}
+
+/* Format *******************************************************************/
+pub trait Format {
+    fn fmt_with(&self, options: &FormatOptions, f: &mut Formatter<'_>) -> FmtResult;
+}
+
+impl Format for Expr<'_> {
+    fn fmt_with(&self, options: &FormatOptions, f: &mut Formatter<'_>) -> FmtResult {
+        Printer { options }.write_expr(self, Slot::Top, f)
+    }
+}
+
+impl Format for ExprList<'_> {
+    fn fmt_with(&self, options: &FormatOptions, f: &mut Formatter<'_>) -> FmtResult {
+        Printer { options }.write_list(self, f)
+    }
+}
+
+/* Formatted ****************************************************************/
+/* Display adapter, for write!/format! into any text sink */
+pub struct Formatted<'a, T: ?Sized + Format> {
+    item: &'a T,
+    options: &'a FormatOptions,
+}
+
+impl<'a, T: ?Sized + Format> Formatted<'a, T> {
+    pub fn new(item: &'a T, options: &'a FormatOptions) -> Self {
+        Formatted { item, options }
+    }
+}
+
+impl<T: ?Sized + Format> Display for Formatted<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.item.fmt_with(self.options, f)
+    }
+}
+
+pub fn output<'w, 'x, T: ?Sized + Format>(
+    item: &T,
+    options: &FormatOptions,
+    out: &mut (dyn Write + 'w),
+    _xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    write!(out, "{}", Formatted::new(item, options))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::data_cell::expr::Parser;
+    use crate::data_cell::expr::Source;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn spacing_radix_and_parens() {
+        let mut buffer = [0; 0x6000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut render = |text: &str, options: &FormatOptions| {
+            let src = Source::new(text, "test");
+            let l = Parser::new(&src, &xc).parse_expr_list().unwrap().unwrap_data();
+            let mut o = xc.byte_vector();
+            output(&l, options, &mut o, &mut xc).unwrap();
+            std::string::String::from_utf8(o.as_slice().to_vec()).unwrap()
+        };
+        let canonical = FormatOptions::default();
+        let compact = FormatOptions { spacing: Spacing::Compact, ..canonical };
+        let minimal = FormatOptions { parens: Parens::Minimal, ..canonical };
+        let full = FormatOptions { parens: Parens::Full, ..canonical };
+        let hex = FormatOptions { radix: Radix::Hex, ..canonical };
+
+        assert_eq!(render("a+(b*c) ,f( x,0x1F )", &canonical), "a + (b * c), f(x, 31)");
+        assert_eq!(render("a + (b * c), f(x, 31)", &compact), "a+(b*c),f(x,31)");
+        assert_eq!(render("x[16] == 255 ? 0b11 : 0", &hex), "x[0x10] == 0xFF ? 0x3 : 0x0");
+        assert_eq!(render("x[5]", &FormatOptions { radix: Radix::Binary, ..canonical }), "x[0b101]");
+        assert_eq!(render("\"a\\x00\".len", &canonical), "\"a\\x00\".len");
+
+        assert_eq!(render("a + (b * c)", &minimal), "a + b * c");
+        assert_eq!(render("(a + b) * c", &minimal), "(a + b) * c");
+        assert_eq!(render("a - (b - c), (a - b) - c", &minimal), "a - (b - c), a - b - c");
+        assert_eq!(render("((a.b)).c[(1)]", &minimal), "a.b.c[1]");
+        assert_eq!(render("(a + b).len, ((f))(1), (.x).y", &minimal), "(a + b).len, (f)(1), .x.y");
+        assert_eq!(render("(a ? b : c) ? (d ? e : f) : g", &minimal), "(a ? b : c) ? d ? e : f : g");
+
+        assert_eq!(render("a + b * c - d", &full), "(a + (b * c)) - d");
+        assert_eq!(render("a || b ? c : d ? e : f", &full), "(a || b) ? c : (d ? e : f)");
+        assert_eq!(render("f(a + b)", &full), "f(a + b)");
+    }
+
+    #[test]
+    fn rendered_text_parses_to_the_same_expression() {
+        let mut buffer = [0; 0x8000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let all_options = [
+            FormatOptions::default(),
+            FormatOptions { spacing: Spacing::Compact, radix: Radix::Hex, parens: Parens::Minimal },
+            FormatOptions { spacing: Spacing::Compact, radix: Radix::Binary, parens: Parens::Full },
+        ];
+        for text in &[
+            "a << 1 + 2 & 3 != 4 || x",
+            "(a ? b : c) ? d : e ? f : g",
+            "x.y[1, 2](3).z - (4 - 5)",
+        ] {
+            let src = Source::new(text, "test");
+            let e = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+            let minimal = FormatOptions { parens: Parens::Minimal, ..FormatOptions::default() };
+            let expected = std::format!("{}", Formatted::new(&e, &minimal));
+            for options in &all_options {
+                let rendered = std::format!("{}", Formatted::new(&e, options));
+                let src = Source::new(&rendered, "test");
+                let e2 = Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data();
+                assert_eq!(std::format!("{}", Formatted::new(&e2, &minimal)), expected);
+            }
+        }
+    }
+}
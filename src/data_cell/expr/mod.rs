@@ -0,0 +1,2511 @@
+use core::iter::Iterator;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FmtResult;
+use core::fmt::Write as FmtWrite;
+
+use crate::ExecutionContext;
+use crate::mm::Box;
+use crate::mm::Vector;
+use crate::mm::SmallVector;
+use crate::mm::String;
+use crate::mm::AllocError;
+use crate::error::Error;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::num::fmt::ParseIntError;
+use crate::num::fmt::ParseIntOptions;
+use crate::num::fmt::parse_int;
+use crate::xc_err;
+
+pub mod format;
+pub mod stream;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorData {
+    ReachedEnd,
+    NotImplemented,
+    Alloc(AllocError),
+    IllegalChar(char),
+    UnexpectedChar(char),
+    UnexpectedToken,
+    LiteralOutOfRange,
+    MalformedLiteral,
+    IO(IOErrorCode), // reading the source from a stream failed
+    TooDeep, // expressions nested beyond the parser limit
+}
+pub type ParseError<'a> = Error<'a, ParseErrorData>;
+
+/* default limit of expression nesting; parsing, evaluating and dropping an
+ * expression recurse as deep as the expression tree, so the limit keeps
+ * untrusted input from exhausting the stack */
+pub const MAX_EXPR_DEPTH: u32 = 64;
+/* binary operators in an expression; a chain like a + b + c nests each
+ * operator as the left operand of the next one, so the tree gets as deep
+ * as the chain is long; this bounds that depth far above realistic filters
+ * like a == 1 || a == 2 || ... */
+pub const MAX_EXPR_OPERATORS: u32 = 1024;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Source<'s> {
+    content: &'s str,
+    name: &'s str,
+}
+
+/* line and column where some part of an expression starts */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SourceLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug)]
+pub struct SourceSlice<'s> {
+    source: &'s Source<'s>,
+    start_offset: usize,
+    end_offset: usize,
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+}
+
+#[derive(Debug)]
+pub struct Token<'s, T> {
+    data: T,
+    source_slice: SourceSlice<'s>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CharInfo {
+    codepoint: char,
+    width: u8,
+    size: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BasicTokenType {
+    End,
+    Identifier,
+    Dot,
+    Comma,
+    U64Literal,
+    OpenSquareBracket,
+    CloseSquareBracket,
+    BoolLiteral,
+    StringLiteral,
+    OpenParen,
+    CloseParen,
+    BinaryOperator,
+    QuestionMark,
+    Colon,
+    Equal,
+    ColonEqual,
+    Semicolon,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BasicTokenTypeBitmap(u64);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BasicTokenTypeBitmapIterator {
+    mask: u64,
+    pos: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BasicTokenData<'a> {
+    End,
+    BoolLiteral(bool),
+    U64Literal(u64),
+    StringLiteral(String<'a>),
+    //BinLiteral(Vector<'a, u8>),
+    Identifier(String<'a>),
+    OpenParen,
+    CloseParen,
+    OpenSquareBracket,
+    CloseSquareBracket,
+    BinaryOperator(BinaryOp),
+    //Tilde,
+    //Exclamation,
+    Equal,
+    Dot,
+    Comma,
+    QuestionMark,
+    Colon,
+    ColonEqual,
+    Semicolon,
+}
+
+/* from the loosest to the tightest binding, as in C */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BinaryOp {
+    LogicalOr,
+    LogicalAnd,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PrimaryExpr<'a> {
+    Identifier(String<'a>),
+    U64Literal(u64),
+    BoolLiteral(bool),
+    StringLiteral(String<'a>),
+    Parenthesized(Box<'a, Expr<'a>>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PostfixRoot<'a> {
+    Primary(PrimaryExpr<'a>), // points to foo in foo.bar
+    Implied, // the current item in expressions like .bla
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PostfixItem<'a> {
+    Property(String<'a>), // points to bar or baz in foo.bar.baz
+    Subscript(ExprList<'a>), // a[b, c] is a[b][c]
+    Call(ExprList<'a>), // a(b, c); the list may be empty
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PostfixExpr<'a> {
+    pub root: PostfixRoot<'a>,
+    /* chains are mostly short, like foo.bar or a[i] */
+    pub items: SmallVector<'a, PostfixItem<'a>, 2>,
+    /* of the root followed by those of the items; empty for expressions
+     * not parsed from a source */
+    pub locations: Vector<'a, SourceLocation>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BinaryExpr<'a> {
+    pub op: BinaryOp,
+    pub left: Box<'a, Expr<'a>>,
+    pub right: Box<'a, Expr<'a>>,
+}
+
+/* cond ? a : b */
+#[derive(Debug, PartialEq)]
+pub struct ConditionalExpr<'a> {
+    pub condition: Box<'a, Expr<'a>>,
+    pub if_true: Box<'a, Expr<'a>>,
+    pub if_false: Box<'a, Expr<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr<'a> {
+    Postfix(PostfixExpr<'a>),
+    Binary(BinaryExpr<'a>),
+    Conditional(ConditionalExpr<'a>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExprList<'a> {
+    items: Vector<'a, Expr<'a>>,
+}
+
+/* let name = value, also written as name := value */
+#[derive(Debug, PartialEq)]
+pub struct LetStatement<'a> {
+    pub name: String<'a>,
+    pub value: Expr<'a>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Statement<'a> {
+    Let(LetStatement<'a>),
+    Expr(Expr<'a>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StatementList<'a> {
+    items: Vector<'a, Statement<'a>>,
+}
+
+/* what parsing with error recovery gives: the items that parsed and, for
+ * each one that did not, the error with the source text skipped for it */
+#[derive(Debug)]
+pub struct Recovered<'s, 't, T> {
+    pub items: Vector<'t, T>,
+    pub errors: Vector<'t, Token<'s, ParseError<'t>>>,
+}
+
+pub struct Parser<'s, 't> {
+    source: &'s Source<'s>,
+    exectx: ExecutionContext<'t>,
+    lookup_token: Option<Token<'s, BasicTokenData<'t>>>,
+    cr_lf_to_lf: bool,
+    cr_to_lf: bool,
+    tab_width: Option<u8>,
+    remaining_text: &'s str,
+    current_line: u32,
+    current_column: u32,
+    depth: u32,
+    max_depth: u32,
+    operators: u32, // binary operators in the expression being parsed
+}
+
+impl<'a> From<AllocError> for ParseError<'a> {
+    fn from(e: AllocError) -> Self {
+        ParseError::with_str(ParseErrorData::Alloc(e), "alloc error")
+    }
+}
+
+impl<'a, T> From<(AllocError, T)> for ParseError<'a> {
+    fn from(e: (AllocError, T)) -> Self {
+        ParseError::with_str(ParseErrorData::Alloc(e.0), "alloc error")
+    }
+}
+
+impl BasicTokenType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BasicTokenType::End => "end-of-file",
+            BasicTokenType::Identifier => "identifier",
+            BasicTokenType::Dot => "dot",
+            BasicTokenType::Comma => "comma",
+            BasicTokenType::U64Literal => "integer literal",
+            BasicTokenType::OpenSquareBracket => "open square bracket",
+            BasicTokenType::CloseSquareBracket => "close square bracket",
+            BasicTokenType::BoolLiteral => "boolean literal",
+            BasicTokenType::StringLiteral => "string literal",
+            BasicTokenType::OpenParen => "open paren",
+            BasicTokenType::CloseParen => "close paren",
+            BasicTokenType::BinaryOperator => "binary operator",
+            BasicTokenType::QuestionMark => "question mark",
+            BasicTokenType::Colon => "colon",
+            BasicTokenType::Equal => "equal sign",
+            BasicTokenType::ColonEqual => "colon equal",
+            BasicTokenType::Semicolon => "semicolon",
+        }
+    }
+    pub fn to_bitmap(&self) -> BasicTokenTypeBitmap {
+        BasicTokenTypeBitmap(1_u64 << (*self as usize))
+    }
+    pub fn from_u8(v: u8) -> Option<BasicTokenType> {
+        if v == (BasicTokenType::End as u8) {
+            Some(BasicTokenType::End)
+        } else if v == (BasicTokenType::Identifier as u8) {
+            Some(BasicTokenType::Identifier)
+        } else if v == (BasicTokenType::Dot as u8) {
+            Some(BasicTokenType::Dot)
+        } else if v == (BasicTokenType::Comma as u8) {
+            Some(BasicTokenType::Comma)
+        } else if v == (BasicTokenType::U64Literal as u8) {
+            Some(BasicTokenType::U64Literal)
+        } else if v == (BasicTokenType::OpenSquareBracket as u8) {
+            Some(BasicTokenType::OpenSquareBracket)
+        } else if v == (BasicTokenType::CloseSquareBracket as u8) {
+            Some(BasicTokenType::CloseSquareBracket)
+        } else if v == (BasicTokenType::BoolLiteral as u8) {
+            Some(BasicTokenType::BoolLiteral)
+        } else if v == (BasicTokenType::StringLiteral as u8) {
+            Some(BasicTokenType::StringLiteral)
+        } else if v == (BasicTokenType::OpenParen as u8) {
+            Some(BasicTokenType::OpenParen)
+        } else if v == (BasicTokenType::CloseParen as u8) {
+            Some(BasicTokenType::CloseParen)
+        } else if v == (BasicTokenType::BinaryOperator as u8) {
+            Some(BasicTokenType::BinaryOperator)
+        } else if v == (BasicTokenType::QuestionMark as u8) {
+            Some(BasicTokenType::QuestionMark)
+        } else if v == (BasicTokenType::Colon as u8) {
+            Some(BasicTokenType::Colon)
+        } else if v == (BasicTokenType::Equal as u8) {
+            Some(BasicTokenType::Equal)
+        } else if v == (BasicTokenType::ColonEqual as u8) {
+            Some(BasicTokenType::ColonEqual)
+        } else if v == (BasicTokenType::Semicolon as u8) {
+            Some(BasicTokenType::Semicolon)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for BasicTokenType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(self.name(), f)
+    }
+}
+
+impl BasicTokenTypeBitmap {
+    pub fn new() -> Self {
+        BasicTokenTypeBitmap(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+    pub fn contains(&self, btt: BasicTokenType) -> bool {
+        ((self.0 >> (btt as usize)) & 1) != 0
+    }
+    pub fn len(&self) -> u32 {
+        self.0.count_ones()
+    }
+    pub fn add_types(&mut self, l: &[BasicTokenType]) {
+        for t in l {
+            self.0 |= t.to_bitmap().0;
+        }
+    }
+    pub fn from_list(l: &[BasicTokenType]) -> Self {
+        let mut b = Self::new();
+        b.add_types(l); b
+    }
+    pub fn iter(&self) -> BasicTokenTypeBitmapIterator {
+        BasicTokenTypeBitmapIterator {
+            mask: self.0,
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for BasicTokenTypeBitmapIterator {
+    type Item = BasicTokenType;
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < 64 && (self.mask & (1_u64 << self.pos)) == 0 { self.pos += 1; }
+        if self.pos == 64 {
+            None
+        } else {
+            let p = self.pos;
+            self.pos += 1;
+            BasicTokenType::from_u8(p)
+        }
+    }
+}
+
+impl Display for BasicTokenTypeBitmap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.is_empty() {
+            Display::fmt("no types", f)
+        } else {
+            let mut first = true;
+            for btt in self.iter() {
+                if !first {
+                    write!(f, ", ")?;
+                } else {
+                    first = false;
+                }
+                Display::fmt(&btt, f)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BinaryOp::LogicalOr => "||",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessOrEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterOrEqual => ">=",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+        }
+    }
+    /* operators with higher precedence bind tighter; all of them group
+     * left to right */
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::LogicalOr => 1,
+            BinaryOp::LogicalAnd => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Equal | BinaryOp::NotEqual => 6,
+            BinaryOp::Less | BinaryOp::LessOrEqual
+                | BinaryOp::Greater | BinaryOp::GreaterOrEqual => 7,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 8,
+            BinaryOp::Add | BinaryOp::Sub => 9,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 10,
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.as_str().fmt(f)
+    }
+}
+
+impl<'t> BasicTokenData<'t> {
+    pub fn to_type(&self) -> BasicTokenType {
+        match self {
+            BasicTokenData::End => BasicTokenType::End,
+            BasicTokenData::Identifier(_) => BasicTokenType::Identifier,
+            BasicTokenData::Dot => BasicTokenType::Dot,
+            BasicTokenData::Comma => BasicTokenType::Comma,
+            BasicTokenData::U64Literal(_) => BasicTokenType::U64Literal,
+            BasicTokenData::OpenSquareBracket => BasicTokenType::OpenSquareBracket,
+            BasicTokenData::CloseSquareBracket => BasicTokenType::CloseSquareBracket,
+            BasicTokenData::BoolLiteral(_) => BasicTokenType::BoolLiteral,
+            BasicTokenData::StringLiteral(_) => BasicTokenType::StringLiteral,
+            BasicTokenData::OpenParen => BasicTokenType::OpenParen,
+            BasicTokenData::CloseParen => BasicTokenType::CloseParen,
+            BasicTokenData::BinaryOperator(_) => BasicTokenType::BinaryOperator,
+            BasicTokenData::QuestionMark => BasicTokenType::QuestionMark,
+            BasicTokenData::Colon => BasicTokenType::Colon,
+            BasicTokenData::Equal => BasicTokenType::Equal,
+            BasicTokenData::ColonEqual => BasicTokenType::ColonEqual,
+            BasicTokenData::Semicolon => BasicTokenType::Semicolon,
+        }
+    }
+    pub fn type_str(&self) -> &'static str {
+        self.to_type().name()
+    }
+    pub fn unwrap_identifier_data(self) -> String<'t> {
+        if let BasicTokenData::Identifier(s) = self { s } else {
+            panic!("expecting Identifier, not {:?}", self);
+        }
+    }
+}
+
+impl<'t> Display for BasicTokenData<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            BasicTokenData::End => "<end-of-file>".fmt(f),
+            BasicTokenData::Dot => "'.'".fmt(f),
+            BasicTokenData::Comma => "','".fmt(f),
+            BasicTokenData::OpenSquareBracket => "'['".fmt(f),
+            BasicTokenData::CloseSquareBracket => "']'".fmt(f),
+            BasicTokenData::Identifier(s) => s.fmt(f),
+            BasicTokenData::U64Literal(n) => n.fmt(f),
+            BasicTokenData::BoolLiteral(b) => b.fmt(f),
+            BasicTokenData::StringLiteral(s) => fmt_string_literal(s.as_str(), f),
+            BasicTokenData::OpenParen => "'('".fmt(f),
+            BasicTokenData::CloseParen => "')'".fmt(f),
+            BasicTokenData::BinaryOperator(op) => write!(f, "'{}'", op),
+            BasicTokenData::QuestionMark => "'?'".fmt(f),
+            BasicTokenData::Colon => "':'".fmt(f),
+            BasicTokenData::Equal => "'='".fmt(f),
+            BasicTokenData::ColonEqual => "':='".fmt(f),
+            BasicTokenData::Semicolon => "';'".fmt(f),
+        }
+    }
+}
+
+/* the text is written back so that it parses to the same string */
+fn fmt_string_literal(s: &str, f: &mut Formatter<'_>) -> FmtResult {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ' '..='~' => f.write_char(c)?,
+            '\0'..='\x7F' => write!(f, "\\x{:02X}", c as u32)?,
+            _ => write!(f, "\\u{{{:X}}}", c as u32)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl<'t> Display for PrimaryExpr<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PrimaryExpr::Identifier(s) => s.fmt(f),
+            PrimaryExpr::U64Literal(n) => n.fmt(f),
+            PrimaryExpr::BoolLiteral(b) => b.fmt(f),
+            PrimaryExpr::StringLiteral(s) => fmt_string_literal(s.as_str(), f),
+            PrimaryExpr::Parenthesized(e) => write!(f, "({})", e),
+        }
+    }
+}
+
+impl<'t> Display for PostfixRoot<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PostfixRoot::Primary(pe) => pe.fmt(f),
+            PostfixRoot::Implied => Ok(()),
+        }
+    }
+}
+
+impl<'t> Display for PostfixItem<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PostfixItem::Property(s) => write!(f, ".{}", s),
+            PostfixItem::Subscript(l) => write!(f, "[{}]", l),
+            PostfixItem::Call(l) => write!(f, "({})", l),
+        }
+    }
+}
+
+impl<'t> Display for PostfixExpr<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.root)?;
+        for i in self.items.as_slice() {
+            write!(f, "{}", i)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'t> From<PostfixExpr<'t>> for Expr<'t> {
+    fn from(pe: PostfixExpr<'t>) -> Expr<'t> {
+        Expr::Postfix(pe)
+    }
+}
+
+impl<'t> Display for BinaryExpr<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} {} {}", self.left, self.op, self.right)
+    }
+}
+
+impl<'t> Display for ConditionalExpr<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ? {} : {}", self.condition, self.if_true, self.if_false)
+    }
+}
+
+impl<'t> Display for Expr<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Expr::Postfix(pfe) => pfe.fmt(f),
+            Expr::Binary(be) => be.fmt(f),
+            Expr::Conditional(ce) => ce.fmt(f),
+        }
+    }
+}
+
+impl<'s, 't> From<Token<'s, PostfixExpr<'t>>> for Token<'s, Expr<'t>> {
+    fn from(src: Token<'s, PostfixExpr<'t>>) -> Self {
+        Token {
+            data: src.data.into(),
+            source_slice: src.source_slice,
+        }
+    }
+}
+
+impl<'t> ExprList<'t> {
+    pub fn unwrap_items(self) -> Vector<'t, Expr<'t>> {
+        self.items
+    }
+    pub fn as_slice(&self) -> &[Expr<'t>] {
+        self.items.as_slice()
+    }
+}
+impl<'t> Display for ExprList<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut write_sep = false;
+        for e in self.items.as_slice() {
+            if write_sep {
+                write!(f, ", ")?;
+            } else {
+                write_sep = true;
+            }
+            e.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'t> Statement<'t> {
+    /* the value bound by a let */
+    pub fn expr(&self) -> &Expr<'t> {
+        match self {
+            Statement::Let(ls) => &ls.value,
+            Statement::Expr(e) => e,
+        }
+    }
+}
+
+impl<'t> Display for Statement<'t> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Statement::Let(ls) => write!(f, "let {} = {}", ls.name, ls.value),
+            Statement::Expr(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<'t> StatementList<'t> {
+    pub fn unwrap_items(self) -> Vector<'t, Statement<'t>> {
+        self.items
+    }
+    pub fn as_slice(&self) -> &[Statement<'t>] {
+        self.items.as_slice()
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl<'s> Source<'s> {
+    pub fn new(content: &'s str, name: &'s str) -> Self {
+        Source { content, name }
+    }
+    pub fn get_content(&self) -> &'s str {
+        self.content
+    }
+
+    pub fn get_name(&self) -> &'s str {
+        self.name
+    }
+}
+
+impl<'s> SourceSlice<'s> {
+    pub fn as_str(&self) -> &'s str {
+        &self.source.content[self.start_offset..self.end_offset]
+    }
+    pub fn update_end<'t>(&mut self, tail: &SourceSlice<'t>) {
+        self.end_offset = tail.end_offset;
+        self.end_line = tail.end_line;
+        self.end_column = tail.end_column;
+    }
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation { line: self.start_line, column: self.start_column }
+    }
+    /* the empty slice where this one starts */
+    fn start(&self) -> SourceSlice<'s> {
+        SourceSlice {
+            source: self.source,
+            start_offset: self.start_offset,
+            end_offset: self.start_offset,
+            start_line: self.start_line,
+            start_column: self.start_column,
+            end_line: self.start_line,
+            end_column: self.start_column,
+        }
+    }
+    /* offsets of the source line holding the start of the slice */
+    fn start_line_bounds(&self) -> (usize, usize) {
+        let content = self.source.content;
+        let is_eol = |c: char| c == '\n' || c == '\r';
+        let begin = content[..self.start_offset].rfind(is_eol).map_or(0, |o| o + 1);
+        let end = content[self.start_offset..].find(is_eol)
+            .map_or(content.len(), |o| self.start_offset + o);
+        (begin, end)
+    }
+}
+
+/* name:line:column of the start, followed by -column or -line:column of the
+ * last char for slices longer than 1 char; the alternate form ({:#}) adds
+ * the source line and a caret line marking the slice on it */
+impl<'s> Display for SourceSlice<'s> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}:{}:{}", self.source.name, self.start_line, self.start_column)?;
+        if self.end_offset > self.start_offset + 1 {
+            if self.end_line == self.start_line {
+                write!(f, "-{}", self.end_column - 1)?;
+            } else {
+                write!(f, "-{}:{}", self.end_line, core::cmp::max(self.end_column - 1, 1))?;
+            }
+        }
+        if f.alternate() {
+            let content = self.source.content;
+            let (begin, end) = self.start_line_bounds();
+            write!(f, "\n{}\n", &content[begin..end])?;
+            /* tabs are kept so the caret lines up whatever the tab width */
+            for c in content[begin..self.start_offset].chars() {
+                f.write_char(if c == '\t' { '\t' } else { ' ' })?;
+            }
+            let marked = content[self.start_offset..core::cmp::min(self.end_offset, end)].chars().count();
+            for _ in 0..core::cmp::max(marked, 1) {
+                f.write_char('^')?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'s, T> Token<'s, T> {
+    pub fn to_parts(self) -> (T, SourceSlice<'s>) {
+        (self.data, self.source_slice)
+    }
+    pub fn unwrap_data(self) -> T {
+        self.data
+    }
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+    pub fn source_slice(&self) -> &SourceSlice<'s> {
+        &self.source_slice
+    }
+}
+
+impl<'s, 't> Parser<'s, 't> {
+
+    pub fn new(src: &'s Source<'s>, xc: &ExecutionContext<'t>) -> Self {
+        Parser {
+            source: src,
+            exectx: xc.to_non_logging(),
+            lookup_token: None,
+            cr_lf_to_lf: true,
+            cr_to_lf: true,
+            tab_width: None,
+            remaining_text: src.content,
+            current_line: 1,
+            current_column: 1,
+            depth: 0,
+            max_depth: MAX_EXPR_DEPTH,
+            operators: 0,
+        }
+    }
+    pub fn set_new_line_handling(&mut self, cr_lf_to_lf: bool, cr_to_lf: bool) {
+        self.cr_lf_to_lf = cr_lf_to_lf;
+        self.cr_to_lf = cr_to_lf;
+    }
+
+    pub fn set_tab_handling(&mut self, tab_width: Option<u8>) {
+        self.tab_width = tab_width;
+    }
+
+    /* how deep expressions can nest, counting each sub-expression (in
+     * parentheses, a subscript, a call argument, a conditional branch or
+     * the right operand of a tighter operator) as one level */
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    fn enter_nested(&mut self) -> Result<(), ParseError<'t>> {
+        if self.depth >= self.max_depth {
+            let ss = self.here();
+            return Err(xc_err!(self.exectx, ParseErrorData::TooDeep, "expression too deep", "{}: expression nested deeper than {} levels", ss, self.max_depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn count_operator(&mut self) -> Result<(), ParseError<'t>> {
+        if self.operators >= MAX_EXPR_OPERATORS {
+            let ss = self.here();
+            return Err(xc_err!(self.exectx, ParseErrorData::TooDeep, "expression too long", "{}: expression with more than {} operators", ss, MAX_EXPR_OPERATORS));
+        }
+        self.operators += 1;
+        Ok(())
+    }
+
+    pub fn is_whitespace(&self, ch: char) -> bool {
+        ch == ' ' || ch == '\n' || ch == '\r' || (ch == '\t' && self.tab_width.is_some())
+    }
+    pub fn is_legal_char(&self, ch: char) -> bool {
+        ch < '\x7F' && (ch >= ' ' || self.is_whitespace(ch))
+    }
+
+    pub fn peek_raw_char(&self) -> Option<CharInfo> {
+        let mut it = self.remaining_text.chars();
+        it.next().map(|ch|
+            if (ch as u32) < 32 {
+                if ch == '\r' {
+                    if self.cr_lf_to_lf && Some('\n') == it.next() {
+                        CharInfo { codepoint: '\n', width: 0, size: 2 }
+                    } else if self.cr_to_lf {
+                        CharInfo { codepoint: '\n', width: 0, size: 1 }
+                    } else {
+                        CharInfo { codepoint: '\r', width: 0, size: 1 }
+                    }
+                } else {
+                    CharInfo { codepoint: ch, width: 0, size: 1 }
+                }
+            } else {
+                CharInfo { codepoint: ch, width: 1, size: ch.len_utf8() as u8 }
+            })
+    }
+    pub fn peek_char(&mut self) -> Result<CharInfo, ParseError<'t>> {
+        self.peek_raw_char()
+            .ok_or_else(|| Error::with_str(ParseErrorData::ReachedEnd, "reached end of source file"))
+            .and_then(|ci| {
+                if self.is_legal_char(ci.codepoint) {
+                    Ok(ci)
+                } else {
+                    Err(Error::with_str(ParseErrorData::IllegalChar(ci.codepoint), "illegal char"))
+                }
+            })
+    }
+
+    pub fn consume_char(&mut self, ci: CharInfo) {
+        debug_assert!(ci.size > 0);
+        match ci.codepoint {
+            '\n' | '\r' => {
+                self.current_line += 1;
+                self.current_column = 1;
+            },
+            '\t' => if let Some(w) = self.tab_width {
+                let w = w as u32;
+                self.current_column = ((self.current_column - 1) / w + 1) * w + 1;
+            },
+            _ => { self.current_column += ci.width as u32; }
+        }
+        self.remaining_text = &self.remaining_text[(ci.size as usize)..];
+    }
+
+    pub fn skip_whitespace(&mut self) {
+        while let Some(ci) = self.peek_raw_char() {
+            if !self.is_whitespace(ci.codepoint) { break; }
+            self.consume_char(ci);
+        }
+    }
+
+    pub fn current_offset(&self) -> usize {
+        (self.remaining_text.as_ptr() as usize)
+            - (self.source.content.as_ptr() as usize)
+    }
+    pub fn here(&self) -> SourceSlice<'s> {
+        SourceSlice {
+            source: &self.source,
+            start_offset: self.current_offset(),
+            end_offset: self.current_offset(),
+            start_line: self.current_line,
+            start_column: self.current_column,
+            end_line: self.current_line,
+            end_column: self.current_column,
+        }
+    }
+    /* like here() but before the token read ahead, if any */
+    fn before_lookup_token(&self) -> SourceSlice<'s> {
+        match &self.lookup_token {
+            Some(t) => t.source_slice.start(),
+            None => self.here(),
+        }
+    }
+    fn end_slice_here(&self, ss: &mut SourceSlice<'s>) {
+        ss.end_offset = self.current_offset();
+        ss.end_line = self.current_line;
+        ss.end_column = self.current_column;
+    }
+
+    pub fn can_start_identifier(c: char) -> bool {
+        c.is_ascii_alphabetic() || c == '_'
+    }
+
+    pub fn is_valid_identifier_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+
+    fn parse_identifier(
+        &mut self,
+    ) -> Result<Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        let mut id = self.exectx.string();
+        let mut source_slice = self.here();
+        while let Ok(ci) = self.peek_char() {
+            if !Parser::is_valid_identifier_char(ci.codepoint) { break; }
+            id.push(ci.codepoint)?;
+            self.consume_char(ci);
+        }
+        self.end_slice_here(&mut source_slice);
+        let data = match id.as_str() {
+            "true" => BasicTokenData::BoolLiteral(true),
+            "false" => BasicTokenData::BoolLiteral(false),
+            _ => BasicTokenData::Identifier(id),
+        };
+        Ok(Token {
+            data,
+            source_slice: source_slice
+        })
+    }
+
+    /* decimal or with any of the radix prefixes num::fmt emits (0x, 0b,
+     * 0o, 0rN_...); digits can be grouped with _ between them */
+    fn parse_u64_literal(
+        &mut self,
+    ) -> Result<Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        let text = self.remaining_text;
+        let mut source_slice = self.here();
+        while let Ok(ci) = self.peek_char() {
+            if !Parser::is_valid_identifier_char(ci.codepoint) { break; }
+            self.consume_char(ci);
+        }
+        self.end_slice_here(&mut source_slice);
+        let text = &text[0..(source_slice.end_offset - source_slice.start_offset)];
+        let options = ParseIntOptions { allow_sign: false, ..ParseIntOptions::default() };
+        match parse_int::<u64>(text, &options) {
+            Ok(n) => Ok(Token {
+                data: BasicTokenData::U64Literal(n),
+                source_slice,
+            }),
+            Err(ParseIntError::Overflow) =>
+                Err(xc_err!(self.exectx, ParseErrorData::LiteralOutOfRange, "literal out of range", "{}: integer literal does not fit in 64 bits", source_slice)),
+            Err(_) =>
+                Err(xc_err!(self.exectx, ParseErrorData::MalformedLiteral, "malformed literal", "{}: malformed integer literal", source_slice)),
+        }
+    }
+
+    /* up to max hex digits; None if there are none */
+    fn parse_hex_digits(&mut self, max: usize) -> Option<u32> {
+        let mut v = 0;
+        let mut count = 0;
+        while count < max {
+            let ci = self.peek_char().ok()?;
+            match ci.codepoint.to_digit(16) {
+                Some(d) => v = v * 16 + d,
+                None => break,
+            }
+            self.consume_char(ci);
+            count += 1;
+        }
+        if count == 0 { None } else { Some(v) }
+    }
+
+    /* the part after the backslash: one of \" \\ \n \r \t \0, \xNN for
+     * ASCII chars or \u{N...} for any char */
+    fn parse_escape(&mut self) -> Option<char> {
+        let ci = self.peek_char().ok()?;
+        let c = ci.codepoint;
+        self.consume_char(ci);
+        match c {
+            '"' | '\\' => Some(c),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            'x' => {
+                let start = self.current_offset();
+                let v = self.parse_hex_digits(2)?;
+                if self.current_offset() - start == 2 && v < 0x80 {
+                    char::from_u32(v)
+                } else {
+                    None
+                }
+            },
+            'u' => {
+                let ci = self.peek_char().ok()?;
+                if ci.codepoint != '{' { return None; }
+                self.consume_char(ci);
+                let v = self.parse_hex_digits(6)?;
+                let ci = self.peek_char().ok()?;
+                if ci.codepoint != '}' { return None; }
+                self.consume_char(ci);
+                char::from_u32(v)
+            },
+            _ => None,
+        }
+    }
+
+    fn parse_string_literal(
+        &mut self,
+    ) -> Result<Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        let mut text = self.exectx.string();
+        let mut source_slice = self.here();
+        let quote = self.peek_char()?;
+        self.consume_char(quote);
+        loop {
+            let ci = match self.peek_char() {
+                Ok(ci) => ci,
+                Err(e) if *e.get_data() == ParseErrorData::ReachedEnd => {
+                    self.end_slice_here(&mut source_slice);
+                    return Err(xc_err!(self.exectx, ParseErrorData::ReachedEnd, "unterminated string literal", "{}: unterminated string literal", source_slice));
+                },
+                Err(e) => return Err(e),
+            };
+            let c = ci.codepoint;
+            let mut escape_slice = self.here();
+            self.consume_char(ci);
+            match c {
+                '"' => break,
+                '\\' => match self.parse_escape() {
+                    Some(c) => text.push(c)?,
+                    None => {
+                        self.end_slice_here(&mut escape_slice);
+                        return Err(xc_err!(self.exectx, ParseErrorData::MalformedLiteral, "malformed literal", "{}: bad escape sequence in string literal", escape_slice));
+                    },
+                },
+                _ => text.push(c)?,
+            }
+        }
+        self.end_slice_here(&mut source_slice);
+        Ok(Token {
+            data: BasicTokenData::StringLiteral(text),
+            source_slice,
+        })
+    }
+
+    /* the rest of an operator starting with the given char, which was
+     * consumed; None for = and ! on their own */
+    fn parse_binary_operator(&mut self, first: char) -> Option<BinaryOp> {
+        let second = self.peek_char().ok().map(|ci| ci.codepoint);
+        let (op, size) = match (first, second) {
+            ('&', Some('&')) => (BinaryOp::LogicalAnd, 2),
+            ('|', Some('|')) => (BinaryOp::LogicalOr, 2),
+            ('<', Some('<')) => (BinaryOp::ShiftLeft, 2),
+            ('>', Some('>')) => (BinaryOp::ShiftRight, 2),
+            ('<', Some('=')) => (BinaryOp::LessOrEqual, 2),
+            ('>', Some('=')) => (BinaryOp::GreaterOrEqual, 2),
+            ('=', Some('=')) => (BinaryOp::Equal, 2),
+            ('!', Some('=')) => (BinaryOp::NotEqual, 2),
+            ('&', _) => (BinaryOp::BitAnd, 1),
+            ('|', _) => (BinaryOp::BitOr, 1),
+            ('^', _) => (BinaryOp::BitXor, 1),
+            ('<', _) => (BinaryOp::Less, 1),
+            ('>', _) => (BinaryOp::Greater, 1),
+            ('+', _) => (BinaryOp::Add, 1),
+            ('-', _) => (BinaryOp::Sub, 1),
+            ('*', _) => (BinaryOp::Mul, 1),
+            ('/', _) => (BinaryOp::Div, 1),
+            ('%', _) => (BinaryOp::Rem, 1),
+            _ => return None,
+        };
+        if size == 2 {
+            let ci = self.peek_char().ok()?;
+            self.consume_char(ci);
+        }
+        Some(op)
+    }
+
+    pub fn parse_basic_token(
+        &mut self
+    ) -> Result<Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        self.skip_whitespace();
+        if self.remaining_text.is_empty() {
+            return Ok(Token {
+                data: BasicTokenData::End,
+                source_slice: self.here()
+            })
+        }
+        let c = self.peek_char()?;
+        if Parser::can_start_identifier(c.codepoint) {
+            return self.parse_identifier();
+        }
+        if c.codepoint.is_ascii_digit() {
+            return self.parse_u64_literal();
+        }
+        if c.codepoint == '"' {
+            return self.parse_string_literal();
+        }
+        let mut ss = self.here();
+        let td = match c.codepoint {
+            '.' => {
+                self.consume_char(c);
+                BasicTokenData::Dot
+            },
+            ',' => {
+                self.consume_char(c);
+                BasicTokenData::Comma
+            },
+            '[' => {
+                self.consume_char(c);
+                BasicTokenData::OpenSquareBracket
+            },
+            ']' => {
+                self.consume_char(c);
+                BasicTokenData::CloseSquareBracket
+            },
+            '(' => {
+                self.consume_char(c);
+                BasicTokenData::OpenParen
+            },
+            ')' => {
+                self.consume_char(c);
+                BasicTokenData::CloseParen
+            },
+            '?' => {
+                self.consume_char(c);
+                BasicTokenData::QuestionMark
+            },
+            ':' => {
+                self.consume_char(c);
+                match self.peek_char() {
+                    Ok(ci) if ci.codepoint == '=' => {
+                        self.consume_char(ci);
+                        BasicTokenData::ColonEqual
+                    },
+                    _ => BasicTokenData::Colon,
+                }
+            },
+            ';' => {
+                self.consume_char(c);
+                BasicTokenData::Semicolon
+            },
+            '+' | '-' | '*' | '/' | '%' | '^' | '&' | '|' | '<' | '>' | '=' | '!' => {
+                let cp = c.codepoint;
+                self.consume_char(c);
+                match self.parse_binary_operator(cp) {
+                    Some(op) => BasicTokenData::BinaryOperator(op),
+                    None if cp == '=' => BasicTokenData::Equal,
+                    None => {
+                        self.end_slice_here(&mut ss);
+                        return Err(xc_err!(self.exectx, ParseErrorData::UnexpectedChar(cp), "unexpected char", "{}: unexpected char {:?}", ss, cp));
+                    },
+                }
+            },
+            _ => {
+                let cp = c.codepoint;
+                self.consume_char(c);
+                self.end_slice_here(&mut ss);
+                return Err(xc_err!(self.exectx, ParseErrorData::UnexpectedChar(cp), "unexpected char", "{}: unexpected char {:?}", ss, cp));
+            },
+        };
+        self.end_slice_here(&mut ss);
+        Ok(Token {
+            data: td,
+            source_slice: ss,
+        })
+    }
+
+    pub fn preview_next_token(
+        &mut self
+    ) -> Result<&Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        if self.lookup_token.is_none() {
+            self.lookup_token = Some(self.parse_basic_token()?);
+        }
+        Ok(self.lookup_token.as_ref().unwrap())
+    }
+
+    pub fn get_next_token(
+        &mut self
+    ) -> Result<Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        self.preview_next_token()?;
+        Ok(self.lookup_token.take().unwrap())
+    }
+
+    pub fn expect_token(
+        &mut self,
+        expected: BasicTokenTypeBitmap,
+    ) -> Result<Token<'s, BasicTokenData<'t>>, ParseError<'t>> {
+        let t = self.get_next_token()?;
+        if expected.contains(t.data.to_type()) {
+            Ok(t)
+        } else {
+            let e = xc_err!(self.exectx, ParseErrorData::UnexpectedToken, "unexpected token", "{}: expecting [{}] not {}", t.source_slice, expected, t.data.type_str());
+            /* left for error recovery, which may stop at it */
+            self.lookup_token = Some(t);
+            Err(e)
+        }
+    }
+
+    pub fn get_identifier_str(
+        &mut self
+    ) -> Result<String<'t>, ParseError<'t>> {
+        Ok(self.expect_token(BasicTokenType::Identifier.to_bitmap())?.data.unwrap_identifier_data())
+    }
+
+    // pub fn is_next_token_matching(
+    //     &mut self,
+    //     desired: BasicTokenTypeBitmap,
+    // ) -> Result<bool, ParseError<'t>> {
+    //     let t = self.preview_next_token()?;
+    //     Ok(desired.contains(t.data.to_type()))
+    // }
+
+    pub fn get_token_matching_types(
+        &mut self,
+        desired: BasicTokenTypeBitmap,
+    ) -> Result<Option<Token<'s, BasicTokenData<'t>>>, ParseError<'t>> {
+        let t = self.preview_next_token()?;
+        if desired.contains(t.data.to_type()) {
+            self.get_next_token().map(|t| Some(t))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn parse_primary_expr(
+        &mut self,
+    ) -> Result<Token<'s, PrimaryExpr<'t>>, ParseError<'t>> {
+        let t = self.get_next_token()?;
+        let data = match t.data {
+            BasicTokenData::Identifier(id) => PrimaryExpr::Identifier(id),
+            BasicTokenData::U64Literal(n) => PrimaryExpr::U64Literal(n),
+            BasicTokenData::BoolLiteral(b) => PrimaryExpr::BoolLiteral(b),
+            BasicTokenData::StringLiteral(s) => PrimaryExpr::StringLiteral(s),
+            BasicTokenData::OpenParen => {
+                let e = self.parse_expr()?;
+                let close = self.expect_token(BasicTokenType::CloseParen.to_bitmap())?;
+                let mut source_slice = t.source_slice;
+                source_slice.update_end(&close.source_slice);
+                return Ok(Token {
+                    data: PrimaryExpr::Parenthesized(Box::new(self.exectx.get_main_allocator(), e.data)?),
+                    source_slice,
+                });
+            },
+            _ => {
+                let e = xc_err!(self.exectx, ParseErrorData::UnexpectedToken, "identifier expected", "{}: identifier expected", t.source_slice);
+                self.lookup_token = Some(t);
+                return Err(e);
+            },
+        };
+        Ok(Token {
+            data,
+            source_slice: t.source_slice,
+        })
+    }
+
+    pub fn parse_postfix_expr(
+        &mut self,
+    ) -> Result<Token<'s, PostfixExpr<'t>>, ParseError<'t>> {
+        let mut items = SmallVector::new(self.exectx.get_main_allocator());
+        let mut locations = self.exectx.vector();
+        let (root, mut ss) = if let BasicTokenData::Dot = self.preview_next_token()?.data {
+            let mut ss = self.get_next_token()?.source_slice;
+            items.push(PostfixItem::Property(self.get_identifier_str()?))?;
+            self.end_slice_here(&mut ss);
+            locations.push(ss.location())?;
+            locations.push(ss.location())?;
+            (PostfixRoot::Implied, ss)
+        } else {
+            let (pe, ss) = self.parse_primary_expr()?.to_parts();
+            locations.push(ss.location())?;
+            (PostfixRoot::Primary(pe), ss)
+        };
+        let mut pfx_expr = PostfixExpr { root, items, locations };
+        let item_start = BasicTokenTypeBitmap::from_list(&[
+            BasicTokenType::Dot,
+            BasicTokenType::OpenSquareBracket,
+            BasicTokenType::OpenParen ]);
+        while let Some(t) = self.get_token_matching_types(item_start)? {
+            let item = match t.data {
+                BasicTokenData::Dot => PostfixItem::Property(self.get_identifier_str()?),
+                BasicTokenData::OpenParen => {
+                    let l = if let BasicTokenData::CloseParen = self.preview_next_token()?.data {
+                        ExprList { items: self.exectx.vector() }
+                    } else {
+                        self.parse_expr_list()?.data
+                    };
+                    self.expect_token(BasicTokenType::CloseParen.to_bitmap())?;
+                    PostfixItem::Call(l)
+                },
+                _ => {
+                    let l = self.parse_expr_list()?.data;
+                    self.expect_token(BasicTokenType::CloseSquareBracket.to_bitmap())?;
+                    PostfixItem::Subscript(l)
+                },
+            };
+            pfx_expr.items.push(item)?;
+            pfx_expr.locations.push(t.source_slice.location())?;
+            self.end_slice_here(&mut ss);
+        }
+        Ok(Token {
+            data: pfx_expr,
+            source_slice: ss,
+        })
+    }
+
+    /* precedence climbing: operators binding at least as tight as
+     * min_precedence are folded into the left operand */
+    fn parse_binary_expr(
+        &mut self,
+        min_precedence: u8,
+    ) -> Result<Token<'s, Expr<'t>>, ParseError<'t>> {
+        let (left, mut ss) = self.parse_postfix_expr()?.to_parts();
+        let mut left: Expr<'t> = left.into();
+        loop {
+            let op = match self.preview_next_token()?.data {
+                BasicTokenData::BinaryOperator(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            self.get_next_token()?;
+            self.count_operator()?;
+            let depth = self.depth;
+            let right = self.enter_nested()
+                .and_then(|_| self.parse_binary_expr(op.precedence() + 1));
+            self.depth = depth;
+            let right = right?;
+            ss.update_end(&right.source_slice);
+            let a = self.exectx.get_main_allocator();
+            left = Expr::Binary(BinaryExpr {
+                op,
+                left: Box::new(a, left)?,
+                right: Box::new(a, right.data)?,
+            });
+        }
+        Ok(Token {
+            data: left,
+            source_slice: ss,
+        })
+    }
+
+    /* the conditional operator binds looser than any binary one and groups
+     * to the right: a ? b : c ? d : e is a ? b : (c ? d : e) */
+    pub fn parse_expr(
+        &mut self,
+    ) -> Result<Token<'s, Expr<'t>>, ParseError<'t>> {
+        let depth = self.depth;
+        if depth == 0 {
+            self.operators = 0;
+        }
+        let r = self.enter_nested().and_then(|_| self.parse_expr_nested());
+        self.depth = depth;
+        r
+    }
+
+    fn parse_expr_nested(
+        &mut self,
+    ) -> Result<Token<'s, Expr<'t>>, ParseError<'t>> {
+        let (condition, mut ss) = self.parse_binary_expr(1)?.to_parts();
+        if self.get_token_matching_types(BasicTokenType::QuestionMark.to_bitmap())?.is_none() {
+            return Ok(Token { data: condition, source_slice: ss });
+        }
+        let if_true = self.parse_expr()?.data;
+        self.expect_token(BasicTokenType::Colon.to_bitmap())?;
+        let if_false = self.parse_expr()?;
+        ss.update_end(&if_false.source_slice);
+        let a = self.exectx.get_main_allocator();
+        Ok(Token {
+            data: Expr::Conditional(ConditionalExpr {
+                condition: Box::new(a, condition)?,
+                if_true: Box::new(a, if_true)?,
+                if_false: Box::new(a, if_false.data)?,
+            }),
+            source_slice: ss,
+        })
+    }
+
+    pub fn parse_expr_list(
+        &mut self,
+    ) -> Result<Token<'s, ExprList<'t>>, ParseError<'t>> {
+        let mut ss = self.here();
+        let mut iv = self.exectx.vector();
+        {
+            let t = self.parse_expr()?;
+            iv.push(t.data)?;
+            ss.update_end(&t.source_slice);
+        }
+        while let Some(_comma) = self.get_token_matching_types(
+            BasicTokenType::Comma.to_bitmap())? {
+            let t = self.parse_expr()?;
+            iv.push(t.data)?;
+            ss.update_end(&t.source_slice);
+        }
+        Ok(Token{
+            data: ExprList {
+                items: iv
+            },
+            source_slice: ss,
+        })
+    }
+
+    /* `let` starts a binding only when followed by a name, so that it can
+     * still be looked up as a property */
+    pub fn parse_statement(
+        &mut self,
+    ) -> Result<Token<'s, Statement<'t>>, ParseError<'t>> {
+        let (expr, mut ss) = self.parse_expr()?.to_parts();
+        let (mut name, locations) = match expr {
+            Expr::Postfix(PostfixExpr {
+                root: PostfixRoot::Primary(PrimaryExpr::Identifier(name)),
+                items,
+                locations,
+            }) if items.is_empty() => (name, locations),
+            _ => return Ok(Token { data: Statement::Expr(expr), source_slice: ss }),
+        };
+        match self.preview_next_token()?.data {
+            BasicTokenData::ColonEqual => {
+                self.get_next_token()?;
+            },
+            BasicTokenData::Identifier(_) if name.as_str() == "let" => {
+                name = self.get_identifier_str()?;
+                self.expect_token(BasicTokenType::Equal.to_bitmap())?;
+            },
+            _ => {
+                let expr = PostfixExpr {
+                    root: PostfixRoot::Primary(PrimaryExpr::Identifier(name)),
+                    items: SmallVector::new(self.exectx.get_main_allocator()),
+                    locations,
+                };
+                return Ok(Token { data: Statement::Expr(expr.into()), source_slice: ss });
+            },
+        }
+        let value = self.parse_expr()?;
+        ss.update_end(&value.source_slice);
+        Ok(Token {
+            data: Statement::Let(LetStatement { name, value: value.data }),
+            source_slice: ss,
+        })
+    }
+
+    /* statements are separated by commas or semicolons; the list may end
+     * with a semicolon */
+    pub fn parse_statement_list(
+        &mut self,
+    ) -> Result<Token<'s, StatementList<'t>>, ParseError<'t>> {
+        let mut ss = self.here();
+        let mut items = self.exectx.vector();
+        let separator = BasicTokenTypeBitmap::from_list(&[
+            BasicTokenType::Comma, BasicTokenType::Semicolon ]);
+        loop {
+            let t = self.parse_statement()?;
+            items.push(t.data)?;
+            ss.update_end(&t.source_slice);
+            match self.get_token_matching_types(separator)? {
+                None => break,
+                Some(t) => if let BasicTokenData::Semicolon = t.data {
+                    ss.update_end(&t.source_slice);
+                    if let BasicTokenData::End = self.preview_next_token()?.data {
+                        break;
+                    }
+                },
+            }
+        }
+        Ok(Token {
+            data: StatementList { items },
+            source_slice: ss,
+        })
+    }
+
+    /* skips to the next separator outside brackets, or to the end; tokens
+     * that do not lex are skipped as well */
+    fn skip_to_separator(
+        &mut self,
+        separators: BasicTokenTypeBitmap,
+        ss: &mut SourceSlice<'s>,
+    ) -> Result<(), ParseError<'t>> {
+        self.scan_to_separator(separators, &mut 0, false, ss).map(|_| ())
+    }
+
+    /* skip_to_separator starting at the given bracket depth, for source that
+     * is read in pieces; if more text may follow, it stops before a token
+     * reaching the end of the source, which that text could extend; returns
+     * the offset of the separator or of where it stopped, and whether a
+     * separator was found */
+    pub(super) fn scan_to_separator(
+        &mut self,
+        separators: BasicTokenTypeBitmap,
+        depth: &mut usize,
+        more_text: bool,
+        ss: &mut SourceSlice<'s>,
+    ) -> Result<(usize, bool), ParseError<'t>> {
+        loop {
+            let offset = self.current_offset();
+            let (tt, start) = match self.preview_next_token() {
+                Ok(t) => (t.data.to_type(), t.source_slice.start_offset),
+                Err(e) => {
+                    if let ParseErrorData::Alloc(_) = e.get_data() {
+                        return Err(e);
+                    }
+                    if more_text && self.remaining_text.is_empty() {
+                        return Ok((offset, false));
+                    }
+                    if self.current_offset() == offset {
+                        let ci = self.peek_raw_char().ok_or(e)?;
+                        self.consume_char(ci);
+                    }
+                    self.end_slice_here(ss);
+                    continue;
+                },
+            };
+            if more_text && self.remaining_text.is_empty() {
+                return Ok((offset, false));
+            }
+            match tt {
+                BasicTokenType::End => return Ok((start, false)),
+                BasicTokenType::OpenParen | BasicTokenType::OpenSquareBracket => *depth += 1,
+                BasicTokenType::CloseParen | BasicTokenType::CloseSquareBracket =>
+                    *depth = depth.saturating_sub(1),
+                _ if *depth == 0 && separators.contains(tt) => return Ok((start, true)),
+                _ => {},
+            }
+            let t = self.get_next_token()?;
+            ss.update_end(&t.source_slice);
+        }
+    }
+
+    /* goes on to the end of the source whatever the errors; only failing to
+     * allocate stops it */
+    fn parse_list_recovering<T>(
+        &mut self,
+        separators: BasicTokenTypeBitmap,
+        parse_item: fn(&mut Self) -> Result<Token<'s, T>, ParseError<'t>>,
+    ) -> Result<Recovered<'s, 't, T>, ParseError<'t>> {
+        let mut items = self.exectx.vector();
+        let mut errors = self.exectx.vector();
+        let mut item_end = separators;
+        item_end.add_types(&[ BasicTokenType::End ]);
+        loop {
+            if self.lookup_token.is_none() {
+                self.skip_whitespace();
+            }
+            let mut ss = self.before_lookup_token();
+            let item = parse_item(self).and_then(|t| {
+                let next = self.expect_token(item_end)?;
+                Ok((t.data, next))
+            });
+            let next = match item {
+                Ok((item, next)) => {
+                    items.push(item)?;
+                    next
+                },
+                Err(e) => {
+                    if let ParseErrorData::Alloc(_) = e.get_data() {
+                        return Err(e);
+                    }
+                    ss.update_end(&self.before_lookup_token());
+                    self.skip_to_separator(separators, &mut ss)?;
+                    errors.push(Token { data: e, source_slice: ss })?;
+                    self.get_next_token()?
+                },
+            };
+            match next.data {
+                BasicTokenData::End => break,
+                BasicTokenData::Semicolon => if let BasicTokenData::End = self.preview_next_token()?.data {
+                    break;
+                },
+                _ => {},
+            }
+        }
+        Ok(Recovered { items, errors })
+    }
+
+    /* a comma separated list running to the end of the source, where an
+     * expression with an error is skipped up to the next comma */
+    pub fn parse_expr_list_recovering(
+        &mut self,
+    ) -> Result<Recovered<'s, 't, Expr<'t>>, ParseError<'t>> {
+        self.parse_list_recovering(BasicTokenType::Comma.to_bitmap(), |p| p.parse_expr())
+    }
+
+    pub fn parse_statement_list_recovering(
+        &mut self,
+    ) -> Result<Recovered<'s, 't, Statement<'t>>, ParseError<'t>> {
+        let separators = BasicTokenTypeBitmap::from_list(&[
+            BasicTokenType::Comma, BasicTokenType::Semicolon ]);
+        self.parse_list_recovering(separators, |p| p.parse_statement())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mm::SingleAlloc;
+    use crate::mm::Allocator;
+    use crate::mm::NOP_ALLOCATOR;
+    use core::fmt::Write;
+
+    use super::*;
+
+    #[test]
+    fn parse_error_from_alloc() {
+        let pe: ParseError<'_> = AllocError::NotEnoughMemory.into();
+        assert_eq!(*pe.get_data(), ParseErrorData::Alloc(AllocError::NotEnoughMemory));
+        let pe: ParseError<'_> = (AllocError::UnsupportedOperation, 0).into();
+        assert_eq!(*pe.get_data(), ParseErrorData::Alloc(AllocError::UnsupportedOperation));
+    }
+
+    #[test]
+    fn basic_token_type_bitmap_iterate() {
+        let b = BasicTokenTypeBitmap::from_list(&[BasicTokenType::End, BasicTokenType::Identifier]);
+        let mut i = b.iter();
+        assert_eq!(b.len(), 2);
+        assert_eq!(i.next(), Some(BasicTokenType::End));
+        assert_eq!(i.next(), Some(BasicTokenType::Identifier));
+        assert_eq!(i.next(), None);
+    }
+
+    #[test]
+    fn basic_token_type_names() {
+        assert_eq!(BasicTokenType::Identifier.name(), "identifier");
+        assert_eq!(BasicTokenType::Dot.name(), "dot");
+        assert_eq!(BasicTokenType::Comma.name(), "comma");
+
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 2048];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let e = xc_err!(xc, (), "-grr-", "{}", BasicTokenType::End.to_bitmap());
+        assert_eq!(e.get_msg(), "end-of-file");
+    }
+
+    #[test]
+    fn empty_basic_token_type_bitmap_display() {
+        use core::fmt::Write;
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 2048];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let mut s = xc.string();
+        write!(s, "{}", BasicTokenTypeBitmap(0)).unwrap();
+        assert_eq!(s.as_str(), "no types");
+    }
+
+    #[test]
+    fn basic_token_type_bitmap_display() {
+        use core::fmt::Write;
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 2048];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let mut s = xc.string();
+        write!(s, "{}", BasicTokenTypeBitmap::from_list(&[BasicTokenType::End, BasicTokenType::Dot])).unwrap();
+        assert_eq!(s.as_str(), "end-of-file, dot");
+    }
+
+    #[test]
+    fn source_new() {
+        let c;
+        let n;
+        {
+            let src = Source::new("bla", "-");
+            c = src.get_content();
+            n = src.get_name();
+        }
+        assert_eq!(c, "bla");
+        assert_eq!(n, "-");
+    }
+
+    #[test]
+    fn peek_raw_ctl_char() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\x0B", "-");
+        let p = Parser::new(&src, &xc);
+        let ucp = CharInfo { codepoint: '\x0B', width: 0, size: 1 };
+        assert_eq!(p.peek_raw_char().unwrap(), ucp);
+    }
+
+    #[test]
+    fn peek_raw_large_code_point() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\u{10348}", "-");
+        let p = Parser::new(&src, &xc);
+        assert_eq!(p.peek_raw_char().unwrap(), CharInfo { codepoint: '\u{10348}', width: 1, size: 4 });
+    }
+
+    #[test]
+    fn peek_cr_lf_no_conv() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\r\n", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.set_new_line_handling(false, false);
+        assert_eq!(p.peek_raw_char().unwrap(), CharInfo { codepoint: '\r', width: 0, size: 1 });
+    }
+
+    #[test]
+    fn peek_cr_lf_all_conv() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\r\n", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.set_new_line_handling(true, true);
+        assert_eq!(p.peek_raw_char().unwrap(), CharInfo { codepoint: '\n', width: 0, size: 2 });
+    }
+    #[test]
+    fn peek_cr_lf_part_conv() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\r\n", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.set_new_line_handling(false, true);
+        assert_eq!(p.peek_raw_char().unwrap(), CharInfo { codepoint: '\n', width: 0, size: 1 });
+    }
+
+    #[test]
+    fn skip_whitespace() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\r\n\n\r      a", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.skip_whitespace();
+        assert_eq!(p.current_line, 4);
+        assert_eq!(p.current_column, 7);
+        assert_eq!(p.peek_raw_char().unwrap(), CharInfo { codepoint: 'a', width: 1, size: 1 });
+    }
+
+    #[test]
+    fn skip_whitespace_to_end() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\r\n\n\r      ", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.skip_whitespace();
+        assert_eq!(p.current_line, 4);
+        assert_eq!(p.current_column, 7);
+        assert_eq!(p.peek_raw_char(), None);
+    }
+
+    #[test]
+    fn peek_char_at_end() {
+        let src = Source::new("", "-");
+        let xc = ExecutionContext::nop();
+        let mut p = Parser::new(&src, &xc);
+        assert_eq!(*p.peek_char().unwrap_err().get_data(), ParseErrorData::ReachedEnd)
+    }
+
+    #[test]
+    fn peek_illegal_char() {
+        let src = Source::new("\x01", "-");
+        let xc = ExecutionContext::nop();
+        let mut p = Parser::new(&src, &xc);
+        assert_eq!(*p.peek_char().unwrap_err().get_data(), ParseErrorData::IllegalChar('\x01'))
+    }
+
+    #[test]
+    fn peek_char() {
+        let src = Source::new("!", "-");
+        let xc = ExecutionContext::nop();
+        let mut p = Parser::new(&src, &xc);
+        assert_eq!(p.peek_char().unwrap(), CharInfo { codepoint: '!', width: 1, size: 1 });
+    }
+
+    #[test]
+    fn consume_tab() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("\t", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.set_tab_handling(Some(5));
+        let ci = CharInfo { codepoint: '\t', width: 0, size: 1 };
+        assert_eq!(p.peek_char().unwrap(), ci);
+        p.consume_char(ci);
+        assert_eq!(p.current_line, 1);
+        assert_eq!(p.current_column, 6);
+
+    }
+
+    #[test]
+    fn only_whitespaces_produce_end_token() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("       \n         ", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.data, BasicTokenData::End);
+    }
+
+    #[test]
+    fn identifier_token() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("  best.worst", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.source_slice.as_str(), "best");
+        assert_eq!((t.source_slice.start_line, t.source_slice.start_column), (1, 3));
+        assert_eq!((t.source_slice.end_line, t.source_slice.end_column), (1, 7));
+        assert_eq!(t.data.unwrap_identifier_data().as_str(), "best");
+    }
+
+    #[test]
+    #[should_panic(expected = "expecting Identifier, not")]
+    fn unwrap_identifier_data_from_dot() {
+        BasicTokenData::Dot.unwrap_identifier_data();
+    }
+
+    #[test]
+    fn identifier_token_oom() {
+        use crate::mm::AllocError;
+        let xc = ExecutionContext::nop();
+        let src = Source::new("  best.worst", "-");
+        let mut p = Parser::new(&src, &xc);
+        assert_eq!(*p.parse_basic_token().unwrap_err().get_data(), ParseErrorData::Alloc(AllocError::UnsupportedOperation));
+    }
+
+    #[test]
+    fn dot_token() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new(".a", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.data, BasicTokenData::Dot);
+        assert_eq!(t.source_slice.as_str(), ".");
+        assert_eq!((t.source_slice.start_line, t.source_slice.start_column), (1, 1));
+        assert_eq!((t.source_slice.end_line, t.source_slice.end_column), (1, 2));
+    }
+
+    #[test]
+    fn comma_token() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new(" ,\n", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.data, BasicTokenData::Comma);
+        assert_eq!(t.source_slice.as_str(), ",");
+        assert_eq!((t.source_slice.start_line, t.source_slice.start_column), (1, 2));
+        assert_eq!((t.source_slice.end_line, t.source_slice.end_column), (1, 3));
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.data, BasicTokenData::End);
+    }
+
+    #[test]
+    fn next_token_encounters_bad_char() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("`", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_basic_token().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::UnexpectedChar('`'));
+        assert_eq!(e.get_msg(), "unexpected char");
+    }
+
+    #[test]
+    fn next_token_encounters_bad_char_with_error_msg() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("`", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_basic_token().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::UnexpectedChar('`'));
+        assert_eq!(e.get_msg(), "-:1:1: unexpected char '`'");
+    }
+
+    #[test]
+    fn token_to_parts() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new(".a", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        let (d, ss) = t.to_parts();
+        assert_eq!(d, BasicTokenData::Dot);
+        assert_eq!(ss.as_str(), ".");
+        assert_eq!((ss.start_line, ss.start_column), (1, 1));
+        assert_eq!((ss.end_line, ss.end_column), (1, 2));
+    }
+
+    #[test]
+    fn display_source_slice() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut xc_buffer = [0_u8; 1024];
+        let xa = BumpAllocator::new(&mut xc_buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(xa.to_ref());
+        let src = Source::new("x,\n\tfoo .bar,", "q.hbx");
+        let mut p = Parser::new(&src, &xc);
+        p.set_tab_handling(Some(4));
+        p.parse_basic_token().unwrap();
+        let comma = p.parse_basic_token().unwrap().source_slice;
+        p.skip_whitespace();
+        let pfx = p.parse_postfix_expr().unwrap().source_slice;
+        let end = p.here(); // past the comma read ahead
+        let mut s = String::new(a.to_ref());
+        write!(s, "{} {} {} {:#}", comma, pfx, end, pfx).unwrap();
+        assert_eq!(s.as_str(), "q.hbx:1:2 q.hbx:2:5-12 q.hbx:2:14 q.hbx:2:5-12\n\tfoo .bar,\n\t^^^^^^^^");
+    }
+
+    #[test]
+    fn display_source_slice_spanning_lines() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut xc_buffer = [0_u8; 1024];
+        let xa = BumpAllocator::new(&mut xc_buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(xa.to_ref());
+        let src = Source::new("a\r\n.b", "-");
+        let mut p = Parser::new(&src, &xc);
+        let pfx = p.parse_postfix_expr().unwrap().source_slice;
+        let mut s = String::new(a.to_ref());
+        write!(s, "{:#}", pfx).unwrap();
+        assert_eq!(s.as_str(), "-:1:1-2:2\na\n^");
+    }
+
+    #[test]
+    fn token_unwrap_data() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new(".a", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        let d = t.unwrap_data();
+        assert_eq!(d, BasicTokenData::Dot);
+    }
+
+    #[test]
+    fn id_as_primary_expr() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("foo.bar", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_primary_expr().unwrap();
+        assert_eq!(t.data, PrimaryExpr::Identifier(String::map_str("foo")));
+        assert_eq!((t.source_slice.start_line, t.source_slice.start_column), (1, 1));
+        assert_eq!((t.source_slice.end_line, t.source_slice.end_column), (1, 4));
+    }
+
+    #[test]
+    fn dot_as_primary_expr() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new(" .bar", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_primary_expr().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::UnexpectedToken);
+        assert_eq!(e.get_msg(), "-:1:2: identifier expected");
+    }
+
+    #[test]
+    fn id_dot_chain_postfix_expr() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("foo .bar baz", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_postfix_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), "foo .bar");
+        assert_eq!(p.get_identifier_str().unwrap().as_str(), "baz");
+    }
+
+    #[test]
+    fn postfix_dot_dot() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 2048];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("foo .bar.  .", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_postfix_expr().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::UnexpectedToken);
+        assert_eq!(e.get_msg(), "-:1:12: expecting [identifier] not dot");
+
+    }
+
+    #[test]
+    fn u64_literal_token() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new(" 1234]", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.data, BasicTokenData::U64Literal(1234));
+        assert_eq!(t.source_slice.as_str(), "1234");
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::CloseSquareBracket);
+        let src = Source::new("18446744073709551615 18446744073709551616", "-");
+        let mut p = Parser::new(&src, &xc);
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(u64::MAX));
+        let e = p.parse_basic_token().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::LiteralOutOfRange);
+    }
+
+    #[test]
+    fn hex_binary_and_grouped_literals() {
+        let xc = ExecutionContext::nop();
+        let src = Source::new("0x7f_FF 0b1010 1_000_000 0X0 0o17 0r3_12", "-");
+        let mut p = Parser::new(&src, &xc);
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(0x7FFF));
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(10));
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(1_000_000));
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(0));
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(0o17));
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::U64Literal(5));
+        for text in &["0x", "0b12", "1_", "0x_1", "12ab", "0x1_0000_0000_0000_0000"] {
+            let src = Source::new(text, "-");
+            let mut p = Parser::new(&src, &xc);
+            let e = p.parse_basic_token().unwrap_err();
+            assert!(matches!(*e.get_data(), ParseErrorData::MalformedLiteral | ParseErrorData::LiteralOutOfRange), "{}", text);
+        }
+    }
+
+    #[test]
+    fn string_and_bool_literals() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 2048];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new(r#" "a\"b\\\n\x41\u{103}" true false truth"#, "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_basic_token().unwrap();
+        assert_eq!(t.source_slice.as_str(), r#""a\"b\\\n\x41\u{103}""#);
+        assert_eq!(t.data, BasicTokenData::StringLiteral(String::map_str("a\"b\\\nA\u{103}")));
+        let mut s = xc.string();
+        write!(s, "{}", t.data).unwrap();
+        assert_eq!(s.as_str(), r#""a\"b\\\nA\u{103}""#);
+        assert_eq!(p.parse_basic_token().unwrap().data, BasicTokenData::BoolLiteral(true));
+        assert_eq!(p.parse_primary_expr().unwrap().data, PrimaryExpr::BoolLiteral(false));
+        assert_eq!(p.parse_primary_expr().unwrap().data, PrimaryExpr::Identifier(String::map_str("truth")));
+
+        for text in &[r#""\q""#, r#""\x80""#, r#""\x4""#, r#""\u{110000}""#, r#""\u41""#] {
+            let src = Source::new(text, "-");
+            let mut p = Parser::new(&src, &xc);
+            assert_eq!(*p.parse_basic_token().unwrap_err().get_data(), ParseErrorData::MalformedLiteral, "{}", text);
+        }
+        let src = Source::new("\"abc", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_basic_token().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::ReachedEnd);
+        assert_eq!(e.get_msg(), "-:1:1-4: unterminated string literal");
+    }
+
+    #[test]
+    fn subscript_postfix_expr() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 4096];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("a[0].b [c.d, 2][3] x", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_postfix_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), "a[0].b [c.d, 2][3]");
+        assert_eq!(t.data.items.len(), 4);
+        let mut s = xc.string();
+        write!(s, "{}", t.data).unwrap();
+        assert_eq!(s.as_str(), "a[0].b[c.d, 2][3]");
+
+        let src = Source::new("a[0", "-");
+        let mut p = Parser::new(&src, &xc);
+        let e = p.parse_postfix_expr().unwrap_err();
+        assert_eq!(e.get_msg(), "-:1:4: expecting [close square bracket] not end-of-file");
+    }
+
+    #[test]
+    fn implied_root() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("x; . tof_ids[0].len() + .first_byte", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_statement_list().unwrap();
+        let mut s = xc.string();
+        write!(s, "{}", t.data.as_slice()[1]).unwrap();
+        assert_eq!(s.as_str(), ".tof_ids[0].len() + .first_byte");
+        match t.data.as_slice()[1].expr() {
+            Expr::Binary(be) => match &*be.left {
+                Expr::Postfix(pfe) => {
+                    assert_eq!(pfe.root, PostfixRoot::Implied);
+                    assert_eq!(pfe.items.len(), 4);
+                },
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+
+        let src = Source::new("(x); .y.z w", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.parse_expr().unwrap();
+        p.expect_token(BasicTokenType::Semicolon.to_bitmap()).unwrap();
+        let t = p.parse_postfix_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), ".y.z");
+
+        let src = Source::new(".[0]", "-");
+        let e = Parser::new(&src, &xc).parse_expr().unwrap_err();
+        assert_eq!(e.get_msg(), "-:1:2: expecting [identifier] not open square bracket");
+    }
+
+    #[test]
+    fn call_postfix_expr() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("f() .g(a, b + 1)[0](x) y", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_postfix_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), "f() .g(a, b + 1)[0](x)");
+        assert_eq!(t.data.items.len(), 5);
+        let mut s = xc.string();
+        write!(s, "{}", t.data).unwrap();
+        assert_eq!(s.as_str(), "f().g(a, b + 1)[0](x)");
+
+        let src = Source::new("f(a b)", "-");
+        let e = Parser::new(&src, &xc).parse_postfix_expr().unwrap_err();
+        assert_eq!(e.get_msg(), "-:1:5: expecting [close paren] not identifier");
+    }
+
+    #[test]
+    fn binary_operator_precedence() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 0x3000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let top_op = |text: &str| {
+            let src = Source::new(text, "-");
+            match Parser::new(&src, &xc).parse_expr().unwrap().unwrap_data() {
+                Expr::Binary(be) => Some((be.op, be.left.binary_op(), be.right.binary_op())),
+                _ => None,
+            }
+        };
+        assert_eq!(top_op("a"), None);
+        assert_eq!(top_op("a + b * c"), Some((BinaryOp::Add, None, Some(BinaryOp::Mul))));
+        assert_eq!(top_op("a - b - c"), Some((BinaryOp::Sub, Some(BinaryOp::Sub), None)));
+        assert_eq!(top_op("a << 2 < b & 1 == 1"), Some((BinaryOp::BitAnd, Some(BinaryOp::Less), Some(BinaryOp::Equal))));
+        assert_eq!(top_op("a && b || c ^ d | e"), Some((BinaryOp::LogicalOr, Some(BinaryOp::LogicalAnd), Some(BinaryOp::BitOr))));
+        assert_eq!(top_op("(a + b) % c"), Some((BinaryOp::Rem, None, None)));
+
+        let src = Source::new("a>=b>>1 != (c<=d) , x", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), "a>=b>>1 != (c<=d)");
+        let mut s = xc.string();
+        write!(s, "{}", t.data).unwrap();
+        assert_eq!(s.as_str(), "a >= b >> 1 != (c <= d)");
+
+        for text in &["!a", "a +", "(a"] {
+            let src = Source::new(text, "-");
+            assert!(Parser::new(&src, &xc).parse_expr().is_err(), "{}", text);
+        }
+        // a single = is only used by let statements
+        let src = Source::new("a = b", "-");
+        let t = Parser::new(&src, &xc).parse_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), "a");
+    }
+
+    #[test]
+    fn let_statements() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("let s = a.b; n := s.len, let, let == 1; x;", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_statement_list().unwrap();
+        p.expect_token(BasicTokenType::End.to_bitmap()).unwrap();
+        assert_eq!(t.source_slice.as_str(), "let s = a.b; n := s.len, let, let == 1; x;");
+        let mut s = xc.string();
+        for st in t.data.as_slice() {
+            write!(s, "{}|", st).unwrap();
+        }
+        assert_eq!(s.as_str(), "let s = a.b|let n = s.len|let|let == 1|x|");
+        assert!(matches!(t.data.as_slice()[1], Statement::Let(_)));
+        assert!(matches!(t.data.as_slice()[2], Statement::Expr(_)));
+
+        let src = Source::new("let x := 1", "-");
+        let e = Parser::new(&src, &xc).parse_statement_list().unwrap_err();
+        assert_eq!(e.get_msg(), "-:1:7-8: expecting [equal sign] not colon equal");
+        let src = Source::new("a.b := 1", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.parse_statement_list().unwrap();
+        assert!(p.expect_token(BasicTokenType::End.to_bitmap()).is_err());
+        let src = Source::new("a = 1", "-");
+        let mut p = Parser::new(&src, &xc);
+        p.parse_statement_list().unwrap();
+        assert!(p.expect_token(BasicTokenType::End.to_bitmap()).is_err());
+    }
+
+    #[test]
+    fn list_with_errors_recovered() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("a +, f(b, ]) c, d, x $ y, \"e", "-");
+        let r = Parser::new(&src, &xc).parse_expr_list_recovering().unwrap();
+        assert_eq!(r.items.len(), 1);
+        let mut s = xc.string();
+        for t in r.errors.as_slice() {
+            writeln!(s, "{:?} <{}>", t.source_slice.as_str(), t.data.get_msg()).unwrap();
+        }
+        assert_eq!(s.as_str(), concat!(
+            "\"a +\" <-:1:4: identifier expected>\n",
+            "\"f(b, ]) c\" <-:1:11: identifier expected>\n",
+            "\"x $ y\" <-:1:22: unexpected char '$'>\n",
+            "\"\\\"e\" <-:1:27-28: unterminated string literal>\n"));
+
+        let src = Source::new("let x = 1; x; let = 2; y;", "-");
+        let r = Parser::new(&src, &xc).parse_statement_list_recovering().unwrap();
+        assert_eq!(r.items.len(), 3);
+        assert_eq!(r.errors.len(), 1);
+        assert_eq!(r.errors.as_slice()[0].source_slice.as_str(), "let = 2");
+
+        let src = Source::new("a, b", "-");
+        let r = Parser::new(&src, &xc).parse_expr_list_recovering().unwrap();
+        assert_eq!((r.items.len(), r.errors.len()), (2, 0));
+    }
+
+    #[test]
+    fn conditional_expr() {
+        use crate::mm::BumpAllocator;
+        let mut buffer = [0; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let src = Source::new("a || b ? c ? 1 : 2 : d ? 3 : 4 ]", "-");
+        let t = Parser::new(&src, &xc).parse_expr().unwrap();
+        assert_eq!(t.source_slice.as_str(), "a || b ? c ? 1 : 2 : d ? 3 : 4");
+        match t.data {
+            Expr::Conditional(ce) => {
+                assert_eq!(ce.condition.binary_op(), Some(BinaryOp::LogicalOr));
+                assert!(matches!(*ce.if_true, Expr::Conditional(_)));
+                assert!(matches!(*ce.if_false, Expr::Conditional(_)));
+            },
+            _ => panic!(),
+        }
+
+        let src = Source::new("a ? b", "-");
+        let e = Parser::new(&src, &xc).parse_expr().unwrap_err();
+        assert_eq!(e.get_msg(), "-:1:6: expecting [colon] not end-of-file");
+    }
+
+    impl Expr<'_> {
+        fn binary_op(&self) -> Option<BinaryOp> {
+            match self {
+                Expr::Binary(be) => Some(be.op),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn expr_list_2_items() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Allocator;
+        use crate::io::stream::NULL_STREAM;
+        use crate::exectx::LogLevel;
+        let mut buffer = [0; 2048];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::new(a.to_ref(), a.to_ref(), NULL_STREAM.get(), LogLevel::Critical);
+        let src = Source::new("foo .bar , \nmoo\n. mar baz", "-");
+        let mut p = Parser::new(&src, &xc);
+        let t = p.parse_expr_list().unwrap();
+        extern crate std; use std::dbg; dbg!(t.source_slice.as_str());
+        assert_eq!(t.data.items.len(), 2);
+        assert_eq!(t.source_slice.as_str(), "foo .bar , \nmoo\n. mar");
+    }
+
+    #[test]
+    fn display_basic_token_data() {
+        use crate::mm::SingleAlloc;
+        use crate::mm::Allocator;
+        use core::fmt::Write;
+        let mut buffer = [0; 2048];
+        let a = SingleAlloc::new(&mut buffer);
+
+        {
+            let mut s = String::new(a.to_ref());
+            write!(s, "{}", BasicTokenData::End).unwrap();
+            assert_eq!(s.as_str(), "<end-of-file>");
+        }
+
+        {
+            let mut s = String::new(a.to_ref());
+            write!(s, "{}", BasicTokenData::Identifier(String::map_str("abc"))).unwrap();
+            assert_eq!(s.as_str(), "abc");
+        }
+
+        {
+            let mut s = String::new(a.to_ref());
+            write!(s, "{}", BasicTokenData::Dot).unwrap();
+            assert_eq!(s.as_str(), "'.'");
+        }
+
+        {
+            let mut s = String::new(a.to_ref());
+            write!(s, "{}", BasicTokenData::Comma).unwrap();
+            assert_eq!(s.as_str(), "','");
+        }
+    }
+
+    #[test]
+    fn display_primary_expr_identifier() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let x = PrimaryExpr::Identifier(String::map_str("abc"));
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "abc");
+    }
+
+    #[test]
+    fn display_postfix_root_primary() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let x = PrimaryExpr::Identifier(String::map_str("abc"));
+        let x = PostfixRoot::Primary(x);
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "abc");
+    }
+
+    #[test]
+    fn display_postfix_item_property() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let x = PostfixItem::Property(String::map_str("abc"));
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), ".abc");
+    }
+
+    #[test]
+    fn display_postfix_expr_0() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let x = PostfixExpr {
+            root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("a"))),
+            items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+            locations: Vector::map_slice(&[]),
+        };
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "a");
+    }
+
+    #[test]
+    fn display_postfix_expr_1() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let mut items = SmallVector::new(NOP_ALLOCATOR.to_ref());
+        items.push(PostfixItem::Property(String::map_str("b"))).unwrap();
+        let x = PostfixExpr {
+            root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("a"))),
+            items,
+            locations: Vector::map_slice(&[]),
+        };
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "a.b");
+    }
+
+    #[test]
+    fn display_expr_postfix() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let x = Expr::Postfix(PostfixExpr {
+            root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("a"))),
+            items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+            locations: Vector::map_slice(&[]),
+        });
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "a");
+    }
+
+    #[test]
+    fn display_expr_list_0() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let items = [];
+        let x = ExprList { items: Vector::map_slice(&items), };
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "");
+    }
+
+    #[test]
+    fn display_expr_list_1() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let items = [
+            Expr::Postfix(PostfixExpr {
+                root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("a"))),
+                items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+                locations: Vector::map_slice(&[]),
+            }),
+        ];
+        let x = ExprList { items: Vector::map_slice(&items), };
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "a");
+    }
+
+    #[test]
+    fn display_expr_list_2() {
+        let mut buffer = [0_u8; 256];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        let items = [
+            Expr::Postfix(PostfixExpr {
+                root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("a"))),
+                items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+                locations: Vector::map_slice(&[]),
+            }),
+            Expr::Postfix(PostfixExpr {
+                root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("b"))),
+                items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+                locations: Vector::map_slice(&[]),
+            }),
+        ];
+        let x = ExprList { items: Vector::map_slice(&items), };
+        write!(s, "{}", x).unwrap();
+        assert_eq!(s.as_str(), "a, b");
+    }
+
+    #[test]
+    fn unwrap_expr_list_items() {
+        let items = [
+            Expr::Postfix(PostfixExpr {
+                root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("a"))),
+                items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+                locations: Vector::map_slice(&[]),
+            }),
+            Expr::Postfix(PostfixExpr {
+                root: PostfixRoot::Primary(PrimaryExpr::Identifier(String::map_str("b"))),
+                items: SmallVector::new(NOP_ALLOCATOR.to_ref()),
+                locations: Vector::map_slice(&[]),
+            }),
+        ];
+        let x = ExprList { items: Vector::map_slice(&items), };
+        let v = x.unwrap_items();
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn nesting_limit() {
+        extern crate std;
+        use std::string::String as StdString;
+        use crate::mm::BumpAllocator;
+        let mut buffer = std::vec![0_u8; 0x200000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let nested = |n: usize| "(".repeat(n) + "x" + &")".repeat(n);
+
+        let text = nested(MAX_EXPR_DEPTH as usize - 1);
+        let src = Source::new(&text, "-");
+        assert!(Parser::new(&src, &xc).parse_expr().is_ok());
+
+        /* operators in a flat chain do not nest, but count towards a limit
+         * of their own */
+        let chain = |n: usize| (0..n).map(|_| "1 + ").collect::<StdString>() + "1";
+        let text = chain(MAX_EXPR_OPERATORS as usize);
+        let src = Source::new(&text, "-");
+        assert!(Parser::new(&src, &xc).parse_expr().is_ok());
+        let text = StdString::from("(") + &chain(MAX_EXPR_OPERATORS as usize / 2) + ") * "
+            + &chain(MAX_EXPR_OPERATORS as usize / 2);
+        let src = Source::new(&text, "-");
+        let e = Parser::new(&src, &xc).parse_expr().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::TooDeep);
+
+        let text = nested(500);
+        let src = Source::new(&text, "-");
+        let e = Parser::new(&src, &xc).parse_expr().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::TooDeep);
+
+        let text = nested(3);
+        let src = Source::new(&text, "-");
+        let mut p = Parser::new(&src, &xc);
+        p.set_max_depth(3);
+        assert_eq!(*p.parse_expr().unwrap_err().get_data(), ParseErrorData::TooDeep);
+
+
+        let text = "c ? ".repeat(200) + "1" + &" : 0".repeat(200);
+        let src = Source::new(&text, "-");
+        let e = Parser::new(&src, &xc).parse_expr().unwrap_err();
+        assert_eq!(*e.get_data(), ParseErrorData::TooDeep);
+    }
+}
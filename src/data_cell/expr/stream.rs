@@ -0,0 +1,282 @@
+use crate::ExecutionContext;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::Read;
+use crate::mm::Deque;
+use crate::mm::Vector;
+
+use super::BasicTokenType;
+use super::BasicTokenTypeBitmap;
+use super::ParseError;
+use super::ParseErrorData;
+use super::Parser;
+use super::Source;
+use super::SourceLocation;
+use super::Statement;
+
+/* bytes read from the stream at a time */
+const READ_SIZE: usize = 0x100;
+
+impl<'a> From<IOError<'a>> for ParseError<'a> {
+    fn from(e: IOError<'a>) -> Self {
+        let (code, msg) = e.to_parts();
+        ParseError::new(ParseErrorData::IO(code), msg)
+    }
+}
+
+/* StreamSource *************************************************************/
+/* feeds the parser from a stream one statement at a time, so that only the
+ * statement being parsed is held in memory; the stream is read ahead in
+ * small chunks and split, with the parser's own tokenizer, on the commas
+ * and semicolons that are not inside brackets or parentheses */
+pub struct StreamSource<'a, T: Read> {
+    inner: T,
+    name: &'a str,
+    ahead: Deque<'a, u8>,
+    ended: bool,
+    text: Vector<'a, u8>,
+    line: u32,
+    column: u32,
+    after_comma: bool,
+    done: bool,
+}
+
+impl<'a, T: Read> StreamSource<'a, T> {
+
+    pub fn new(inner: T, name: &'a str, xc: &ExecutionContext<'a>) -> Self {
+        StreamSource {
+            inner,
+            name,
+            ahead: Deque::new(xc.get_main_allocator()),
+            ended: false,
+            text: xc.byte_vector(),
+            line: 1,
+            column: 1,
+            after_comma: false,
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /* where the next statement starts */
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation { line: self.line, column: self.column }
+    }
+
+    fn fill(&mut self, xc: &mut ExecutionContext<'a>) -> Result<(), ParseError<'a>> {
+        let mut buf = [0_u8; READ_SIZE];
+        loop {
+            match self.inner.read(&mut buf, xc) {
+                Ok(0) => self.ended = true,
+                Ok(n) => {
+                    self.ahead.reserve(n)?;
+                    for &b in &buf[0..n] {
+                        self.ahead.push_back(b)?;
+                    }
+                },
+                Err(e) if *e.get_data() == IOErrorCode::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+            return Ok(());
+        }
+    }
+
+    /* lines and columns are counted as the parser does, with CR LF and a
+     * lone CR each ending a line */
+    fn count_location(&mut self, len: usize) {
+        for i in 0..len {
+            let text = self.text.as_slice();
+            match text[i] {
+                b'\r' if text.get(i + 1) == Some(&b'\n') => {},
+                b'\n' | b'\r' => {
+                    self.line += 1;
+                    self.column = 1;
+                },
+                0x00..=0x1F | 0x80..=0xBF => {},
+                _ => self.column += 1,
+            }
+        }
+    }
+
+    /* text up to the next separator, which is returned; None at the end;
+     * separators are found with the parser's tokens, rescanning only the
+     * token that was cut short by the end of what was read so far */
+    fn read_statement(
+        &mut self,
+        xc: &mut ExecutionContext<'a>,
+    ) -> Result<Option<u8>, ParseError<'a>> {
+        self.text.truncate(0);
+        let separators = BasicTokenTypeBitmap::from_list(&[
+            BasicTokenType::Comma, BasicTokenType::Semicolon ]);
+        let mut scanned = 0;
+        let mut depth = 0;
+        let mut wanted = READ_SIZE;
+        loop {
+            while self.ahead.len() < wanted && !self.ended {
+                self.fill(xc)?;
+            }
+            self.text.reserve(self.ahead.len())?;
+            while let Some(b) = self.ahead.pop_front() {
+                self.text.push(b)?;
+            }
+            let (offset, found) = {
+                let tail = &self.text.as_slice()[scanned..];
+                let tail = match core::str::from_utf8(tail) {
+                    Ok(s) => s,
+                    Err(e) if e.error_len().is_none() && !self.ended =>
+                        core::str::from_utf8(&tail[..e.valid_up_to()]).unwrap(),
+                    Err(_) => return Err(ParseError::with_str(
+                        ParseErrorData::IllegalChar(char::REPLACEMENT_CHARACTER),
+                        "invalid UTF-8")),
+                };
+                let src = Source::new(tail, self.name);
+                let mut p = Parser::new(&src, xc);
+                let mut ss = p.here();
+                p.scan_to_separator(separators, &mut depth, !self.ended, &mut ss)?
+            };
+            if found {
+                let end = scanned + offset;
+                self.ahead.reserve(self.text.len() - end - 1)?;
+                for &b in self.text.as_slice()[end + 1..].iter().rev() {
+                    self.ahead.push_front(b)?;
+                }
+                self.count_location(end + 1);
+                let separator = self.text.as_slice()[end];
+                self.text.truncate(end);
+                return Ok(Some(separator));
+            }
+            if self.ended {
+                self.count_location(self.text.len());
+                return Ok(None);
+            }
+            scanned += offset;
+            /* a long token is rescanned only after as much text again */
+            wanted = core::cmp::max(READ_SIZE, self.text.len() - scanned);
+        }
+    }
+
+    /* None once the stream is over; like in parse_statement_list, the last
+     * statement may be followed by a semicolon; errors come with the
+     * location they were found at */
+    pub fn parse_statement(
+        &mut self,
+        xc: &mut ExecutionContext<'a>,
+    ) -> Result<Option<Statement<'a>>, (SourceLocation, ParseError<'a>)> {
+        if self.done {
+            return Ok(None);
+        }
+        let start = self.location();
+        let separator = match self.read_statement(xc) {
+            Ok(s) => s,
+            Err(e) => {
+                self.done = true;
+                return Err((self.location(), e));
+            },
+        };
+        let after_comma = self.after_comma;
+        self.after_comma = separator == Some(b',');
+        if separator.is_none() {
+            self.done = true;
+            let blank = self.text.as_slice().iter().all(|b| matches!(b, b' ' | b'\n' | b'\r'));
+            if blank && !after_comma {
+                return Ok(None);
+            }
+        }
+        let text = core::str::from_utf8(self.text.as_slice()).map_err(|_| (start,
+            ParseError::with_str(
+                ParseErrorData::IllegalChar(char::REPLACEMENT_CHARACTER),
+                "invalid UTF-8")))?;
+        let src = Source::new(text, self.name);
+        let mut p = Parser::new(&src, xc);
+        p.current_line = start.line;
+        p.current_column = start.column;
+        p.parse_statement()
+            .and_then(|t| {
+                p.expect_token(BasicTokenType::End.to_bitmap())?;
+                Ok(Some(t.unwrap_data()))
+            })
+            .map_err(|e| (p.before_lookup_token().location(), e))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn statements_from_small_reads() {
+        let mut buffer = [0; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let text = concat!(
+            "let n = f(1, \"a;b\"),\r\n",
+            "  x[1, 2] ; y ? \"\\\"\" : 0;\n",
+            "  z;\n");
+        let s = BufferAsROStream::new(text.as_bytes());
+        let mut ss = StreamSource::new(ChunkedReader::new(s, 3, 2), "test", &xc);
+        let mut statements = std::vec::Vec::new();
+        while let Some(st) = ss.parse_statement(&mut xc).unwrap() {
+            statements.push(std::format!("{} @ {}", st, ss.location()));
+        }
+        assert_eq!(statements, [
+            "let n = f(1, \"a;b\") @ 1:21",
+            "x[1, 2] @ 2:12",
+            "y ? \"\\\"\" : 0 @ 2:26",
+            "z @ 3:5",
+        ]);
+        assert!(ss.parse_statement(&mut xc).unwrap().is_none());
+    }
+
+    #[test]
+    fn separators_in_long_tokens() {
+        let mut buffer = std::vec![0_u8; 0x40000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut text = std::string::String::from("let s = \"");
+        text.push_str(&";,".repeat(0x300));
+        text.push_str("\"; f(\"]\", 1)");
+        let s = BufferAsROStream::new(text.as_bytes());
+        let mut ss = StreamSource::new(ChunkedReader::new(s, 5, 2), "test", &xc);
+        match ss.parse_statement(&mut xc).unwrap() {
+            Some(Statement::Let(l)) => assert_eq!(l.name.as_str(), "s"),
+            _ => panic!("expecting let statement"),
+        }
+        assert_eq!(ss.location(), SourceLocation { line: 1, column: 0x60C });
+        let st = ss.parse_statement(&mut xc).unwrap().unwrap();
+        assert_eq!(std::format!("{}", st), "f(\"]\", 1)");
+        assert!(ss.parse_statement(&mut xc).unwrap().is_none());
+    }
+
+    #[test]
+    fn errors_have_locations() {
+        let mut buffer = [0; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let s = BufferAsROStream::new(b"a;\n  b c");
+        let mut ss = StreamSource::new(s, "test", &xc);
+        assert!(ss.parse_statement(&mut xc).unwrap().is_some());
+        let (location, e) = ss.parse_statement(&mut xc).unwrap_err();
+        assert_eq!(location, SourceLocation { line: 2, column: 5 });
+        assert_eq!(*e.get_data(), ParseErrorData::UnexpectedToken);
+
+        // a trailing comma needs a statement after it
+        let s = BufferAsROStream::new(b"a, ");
+        let mut ss = StreamSource::new(s, "test", &xc);
+        assert!(ss.parse_statement(&mut xc).unwrap().is_some());
+        assert!(ss.parse_statement(&mut xc).is_err());
+
+        let s = BufferAsROStream::new(b"\xFF");
+        let mut ss = StreamSource::new(s, "test", &xc);
+        assert!(ss.parse_statement(&mut xc).is_err());
+        assert!(ss.parse_statement(&mut xc).unwrap().is_none());
+    }
+}
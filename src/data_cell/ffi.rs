@@ -0,0 +1,424 @@
+/* C ABI for property providers implemented outside of Rust (halfbit_ffi);
+ * a provider is an opaque object pointer plus a vtable of extern "C"
+ * functions, wrapped by FfiCell into a regular DataCellOps cell */
+
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr;
+use core::slice;
+
+use crate::ExecutionContext;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::Write;
+use super::DataCell;
+use super::DataCellOps;
+use super::Error;
+
+pub const HALFBIT_FFI_ABI_VERSION: u32 = 1;
+
+/* status codes returned by vtable functions */
+pub const FFI_OK: i32 = 0;
+pub const FFI_NOT_APPLICABLE: i32 = 1;
+pub const FFI_NO_MEMORY: i32 = 2;
+pub const FFI_FAILED: i32 = 3;
+
+/* FfiValue kinds */
+pub const FFI_VALUE_NOTHING: u32 = 0;
+pub const FFI_VALUE_U64: u32 = 1;
+pub const FFI_VALUE_BYTES: u32 = 2; // data/len, copied by the host
+pub const FFI_VALUE_CELL: u32 = 3; // object/vtable, owned by the host once accepted
+
+/* FfiValue *****************************************************************/
+/* property value filled in by get_property; after converting it, the host
+ * calls release_value (if any) so the provider can free data; a cell the
+ * host took over is handed back as FFI_VALUE_NOTHING, while one it rejected
+ * (bad vtable) is left as is for release_value to free */
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiValue {
+    pub kind: u32,
+    pub number: u64,
+    pub data: *const u8,
+    pub len: usize,
+    pub object: *mut c_void,
+    pub vtable: *const FfiCellVTable,
+}
+
+impl FfiValue {
+    pub const fn nothing() -> Self {
+        FfiValue {
+            kind: FFI_VALUE_NOTHING,
+            number: 0,
+            data: ptr::null(),
+            len: 0,
+            object: ptr::null_mut(),
+            vtable: ptr::null(),
+        }
+    }
+}
+
+/* FfiOutput ****************************************************************/
+/* sink passed to output_as_human_readable; write returns FFI_OK once all
+ * bytes are written */
+#[repr(C)]
+pub struct FfiOutput {
+    pub context: *mut c_void,
+    pub write: unsafe extern "C" fn(
+        context: *mut c_void,
+        data: *const u8,
+        len: usize,
+    ) -> i32,
+}
+
+/* FfiCellVTable ************************************************************/
+/* property names are UTF-8, given as pointer and length without a NUL;
+ * missing functions behave as returning FFI_NOT_APPLICABLE */
+#[repr(C)]
+pub struct FfiCellVTable {
+    pub abi_version: u32,
+    pub get_property: Option<unsafe extern "C" fn(
+        object: *mut c_void,
+        name: *const u8,
+        name_len: usize,
+        value: *mut FfiValue,
+    ) -> i32>,
+    pub output_as_human_readable: Option<unsafe extern "C" fn(
+        object: *mut c_void,
+        out: *const FfiOutput,
+    ) -> i32>,
+    pub release_value: Option<unsafe extern "C" fn(
+        object: *mut c_void,
+        value: *mut FfiValue,
+    )>,
+    pub drop: Option<unsafe extern "C" fn(object: *mut c_void)>,
+}
+
+/* FfiError *****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FfiError {
+    NullVTable,
+    AbiMismatch(u32), // version found in the vtable
+    LoadFailed,
+    EntryNotFound,
+    EntryFailed(i32), // status returned by the entry point
+}
+
+impl FfiError {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            FfiError::NullVTable => "provider has no vtable",
+            FfiError::AbiMismatch(_) => "provider ABI version mismatch",
+            FfiError::LoadFailed => "failed loading provider library",
+            FfiError::EntryNotFound => "provider entry point not found",
+            FfiError::EntryFailed(_) => "provider entry point failed",
+        }
+    }
+}
+
+impl fmt::Display for FfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfiError::AbiMismatch(v) => write!(f, "{} (found {}, expected {})", self.to_str(), v, HALFBIT_FFI_ABI_VERSION),
+            FfiError::EntryFailed(s) => write!(f, "{} (status {})", self.to_str(), s),
+            _ => self.to_str().fmt(f),
+        }
+    }
+}
+
+fn status_to_result<'x>(status: i32) -> Result<(), Error<'x>> {
+    match status {
+        FFI_OK => Ok(()),
+        FFI_NOT_APPLICABLE => Err(Error::NotApplicable),
+        FFI_NO_MEMORY => Err(Error::IO(IOError::with_str(IOErrorCode::NoSpace, "foreign provider out of memory"))),
+        _ => Err(Error::IO(IOError::with_str(IOErrorCode::Unsuccessful, "foreign provider failed"))),
+    }
+}
+
+/* OutputContext ************************************************************/
+struct OutputContext<'a, 'w, 'x> {
+    out: &'a mut (dyn Write + 'w),
+    xc: &'a mut ExecutionContext<'x>,
+    error: Option<IOError<'x>>,
+}
+
+unsafe extern "C" fn output_write(
+    context: *mut c_void,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let oc = &mut *(context as *mut OutputContext<'_, '_, '_>);
+    let buf = if len == 0 { &[][..] } else { slice::from_raw_parts(data, len) };
+    match oc.out.write_all(buf, oc.xc) {
+        Ok(_) => FFI_OK,
+        Err(e) => {
+            oc.error = Some(e.to_error());
+            FFI_FAILED
+        }
+    }
+}
+
+/* FfiCell ******************************************************************/
+/* owns the provider object, dropping it through the vtable */
+pub struct FfiCell {
+    object: *mut c_void,
+    vtable: &'static FfiCellVTable,
+}
+
+impl FfiCell {
+
+    /// # Safety
+    /// vtable, if not null, must point to a vtable valid for the rest of
+    /// the process, whose functions accept object.
+    pub unsafe fn new(
+        object: *mut c_void,
+        vtable: *const FfiCellVTable,
+    ) -> Result<Self, FfiError> {
+        let vtable = vtable.as_ref().ok_or(FfiError::NullVTable)?;
+        if vtable.abi_version != HALFBIT_FFI_ABI_VERSION {
+            return Err(FfiError::AbiMismatch(vtable.abi_version));
+        }
+        Ok(FfiCell { object, vtable })
+    }
+
+    pub fn to_data_cell<'x>(
+        self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        crate::dyn_rc!(ffi_cell_rc, DataCellOps);
+        Ok(DataCell::Dyn(ffi_cell_rc(xc.rc(self)?)))
+    }
+
+    fn convert_value<'x>(
+        &self,
+        value: &mut FfiValue,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match value.kind {
+            FFI_VALUE_NOTHING => Ok(DataCell::Nothing),
+            FFI_VALUE_U64 => Ok(DataCell::from_u64(value.number)),
+            FFI_VALUE_BYTES => {
+                let data = if value.len == 0 { &[][..] } else {
+                    unsafe { slice::from_raw_parts(value.data, value.len) }
+                };
+                Ok(DataCell::from_byte_slice(xc.get_main_allocator(), data)?)
+            },
+            FFI_VALUE_CELL => {
+                let c = unsafe { FfiCell::new(value.object, value.vtable) }
+                    .map_err(|_| Error::IO(IOError::with_str(IOErrorCode::InvalidData, "foreign provider returned a bad cell")))?;
+                value.kind = FFI_VALUE_NOTHING;
+                c.to_data_cell(xc)
+            },
+            _ => Err(Error::IO(IOError::with_str(IOErrorCode::InvalidData, "foreign provider returned an unknown value kind"))),
+        }
+    }
+
+}
+
+impl DataCellOps for FfiCell {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let f = self.vtable.get_property.ok_or(Error::NotApplicable)?;
+        let mut value = FfiValue::nothing();
+        status_to_result(unsafe {
+            f(self.object, property_name.as_ptr(), property_name.len(), &mut value)
+        })?;
+        let r = self.convert_value(&mut value, xc);
+        if let Some(release) = self.vtable.release_value {
+            unsafe { release(self.object, &mut value); }
+        }
+        r
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        let f = self.vtable.output_as_human_readable.ok_or(Error::NotApplicable)?;
+        let mut oc = OutputContext { out, xc, error: None };
+        let fo = FfiOutput {
+            context: &mut oc as *mut OutputContext<'_, '_, '_> as *mut c_void,
+            write: output_write,
+        };
+        let status = unsafe { f(self.object, &fo) };
+        match oc.error {
+            Some(e) => Err(Error::Output(e)),
+            None => status_to_result(status),
+        }
+    }
+
+}
+
+impl Drop for FfiCell {
+    fn drop(&mut self) {
+        if let Some(f) = self.vtable.drop {
+            unsafe { f(self.object); }
+        }
+    }
+}
+
+impl fmt::Debug for FfiCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfiCell").field("object", &self.object).finish()
+    }
+}
+
+/* FfiLibrary ***************************************************************/
+/* shared object exporting the entry point
+ *     int32_t halfbit_ffi_root(uint32_t abi_version, void **object,
+ *                              const FfiCellVTable **vtable);
+ * libraries are never unloaded, as vtables must outlive all cells */
+#[cfg(all(feature = "use-libc", unix))]
+pub struct FfiLibrary {
+    handle: *mut c_void,
+}
+
+#[cfg(all(feature = "use-libc", unix))]
+type FfiRootFn = unsafe extern "C" fn(
+    abi_version: u32,
+    object: *mut *mut c_void,
+    vtable: *mut *const FfiCellVTable,
+) -> i32;
+
+#[cfg(all(feature = "use-libc", unix))]
+impl FfiLibrary {
+
+    /// # Safety
+    /// Loading runs the initializers of the library.
+    pub unsafe fn open(path: &core::ffi::CStr) -> Result<Self, FfiError> {
+        let handle = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if handle.is_null() {
+            Err(FfiError::LoadFailed)
+        } else {
+            Ok(FfiLibrary { handle })
+        }
+    }
+
+    /* root provider of the library */
+    pub fn root(&self) -> Result<FfiCell, FfiError> {
+        unsafe {
+            let sym = libc::dlsym(self.handle, b"halfbit_ffi_root\0".as_ptr() as *const libc::c_char);
+            if sym.is_null() {
+                return Err(FfiError::EntryNotFound);
+            }
+            let entry: FfiRootFn = core::mem::transmute(sym);
+            let mut object = ptr::null_mut();
+            let mut vtable = ptr::null();
+            let status = entry(HALFBIT_FFI_ABI_VERSION, &mut object, &mut vtable);
+            if status != FFI_OK {
+                return Err(FfiError::EntryFailed(status));
+            }
+            FfiCell::new(object, vtable)
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    struct Demo {
+        drops: Cell<u32>,
+        released_cells: Cell<u32>,
+    }
+
+    unsafe extern "C" fn demo_get_property(
+        object: *mut c_void,
+        name: *const u8,
+        name_len: usize,
+        value: *mut FfiValue,
+    ) -> i32 {
+        let value = &mut *value;
+        match slice::from_raw_parts(name, name_len) {
+            b"answer" => { value.kind = FFI_VALUE_U64; value.number = 42; },
+            b"greeting" => { value.kind = FFI_VALUE_BYTES; value.data = b"hi".as_ptr(); value.len = 2; },
+            b"child" => { value.kind = FFI_VALUE_CELL; value.object = object; value.vtable = &DEMO_VTABLE; },
+            b"stale" => { value.kind = FFI_VALUE_CELL; value.object = object; value.vtable = &OLD_VTABLE; },
+            b"bad" => { value.kind = 77; },
+            _ => return FFI_NOT_APPLICABLE,
+        }
+        FFI_OK
+    }
+
+    unsafe extern "C" fn demo_output(_object: *mut c_void, out: *const FfiOutput) -> i32 {
+        let out = &*out;
+        (out.write)(out.context, b"demo".as_ptr(), 4)
+    }
+
+    unsafe extern "C" fn demo_release_value(object: *mut c_void, value: *mut FfiValue) {
+        if (*value).kind == FFI_VALUE_CELL {
+            let d = &*(object as *const Demo);
+            d.released_cells.set(d.released_cells.get() + 1);
+        }
+    }
+
+    unsafe extern "C" fn demo_drop(object: *mut c_void) {
+        let d = &*(object as *const Demo);
+        d.drops.set(d.drops.get() + 1);
+    }
+
+    static DEMO_VTABLE: FfiCellVTable = FfiCellVTable {
+        abi_version: HALFBIT_FFI_ABI_VERSION,
+        get_property: Some(demo_get_property),
+        output_as_human_readable: Some(demo_output),
+        release_value: Some(demo_release_value),
+        drop: Some(demo_drop),
+    };
+
+    static OLD_VTABLE: FfiCellVTable = FfiCellVTable {
+        abi_version: 0,
+        get_property: None,
+        output_as_human_readable: None,
+        release_value: None,
+        drop: None,
+    };
+
+    #[test]
+    fn foreign_provider_as_data_cell() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let demo = Demo { drops: Cell::new(0), released_cells: Cell::new(0) };
+        let object = &demo as *const Demo as *mut c_void;
+        {
+            let c = unsafe { FfiCell::new(object, &DEMO_VTABLE) }.unwrap().to_data_cell(&mut xc).unwrap();
+            assert!(matches!(c.get_property("answer", &mut xc), Ok(DataCell::U64(c)) if c.n == 42));
+            let g = c.get_property("greeting", &mut xc).unwrap();
+            let mut o = xc.byte_vector();
+            g.output_as_human_readable(&mut o, &mut xc).unwrap();
+            assert_eq!(o.as_slice(), b"b\"hi\"");
+            assert!(matches!(c.get_property("missing", &mut xc), Err(Error::NotApplicable)));
+            assert!(c.get_property("bad", &mut xc).is_err());
+            let child = c.get_property("child", &mut xc).unwrap();
+            let mut o = xc.byte_vector();
+            child.output_as_human_readable(&mut o, &mut xc).unwrap();
+            assert_eq!(o.as_slice(), b"demo");
+            assert_eq!(demo.released_cells.get(), 0);
+            assert!(c.get_property("stale", &mut xc).is_err());
+            assert_eq!(demo.released_cells.get(), 1);
+        }
+        assert_eq!(demo.drops.get(), 2);
+
+        assert_eq!(unsafe { FfiCell::new(object, &OLD_VTABLE) }.unwrap_err(), FfiError::AbiMismatch(0));
+        assert_eq!(unsafe { FfiCell::new(object, ptr::null()) }.unwrap_err(), FfiError::NullVTable);
+    }
+
+    #[cfg(all(feature = "use-libc", target_os = "linux"))]
+    #[test]
+    fn library_without_entry_point() {
+        let missing = core::ffi::CStr::from_bytes_with_nul(b"/nonexistent/libdemo.so\0").unwrap();
+        assert_eq!(unsafe { FfiLibrary::open(missing) }.err(), Some(FfiError::LoadFailed));
+        let libc_so = core::ffi::CStr::from_bytes_with_nul(b"libc.so.6\0").unwrap();
+        let lib = unsafe { FfiLibrary::open(libc_so) }.unwrap();
+        assert_eq!(lib.root().err(), Some(FfiError::EntryNotFound));
+    }
+}
@@ -0,0 +1,709 @@
+use core::cell::RefCell;
+use core::convert::TryFrom;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::try_decode_uint_at;
+use crate::data_cell::DCOVector;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::Window;
+use crate::mm::Vector;
+use super::elf::ElfFile;
+use super::elf::SHF_COMPRESSED;
+use super::elf::SHT_NOBITS;
+use super::elf::table_str;
+use super::hex_cell;
+use super::read_region;
+
+pub const DW_AT_NAME: u64 = 0x03;
+
+const DW_FORM_ADDR: u64 = 0x01;
+const DW_FORM_BLOCK2: u64 = 0x03;
+const DW_FORM_BLOCK4: u64 = 0x04;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_BLOCK1: u64 = 0x0A;
+const DW_FORM_DATA1: u64 = 0x0B;
+const DW_FORM_FLAG: u64 = 0x0C;
+const DW_FORM_SDATA: u64 = 0x0D;
+const DW_FORM_STRP: u64 = 0x0E;
+const DW_FORM_UDATA: u64 = 0x0F;
+const DW_FORM_REF_ADDR: u64 = 0x10;
+const DW_FORM_REF1: u64 = 0x11;
+const DW_FORM_REF2: u64 = 0x12;
+const DW_FORM_REF4: u64 = 0x13;
+const DW_FORM_REF8: u64 = 0x14;
+const DW_FORM_REF_UDATA: u64 = 0x15;
+const DW_FORM_INDIRECT: u64 = 0x16;
+const DW_FORM_SEC_OFFSET: u64 = 0x17;
+const DW_FORM_EXPRLOC: u64 = 0x18;
+const DW_FORM_FLAG_PRESENT: u64 = 0x19;
+const DW_FORM_STRX: u64 = 0x1A;
+const DW_FORM_ADDRX: u64 = 0x1B;
+const DW_FORM_REF_SUP4: u64 = 0x1C;
+const DW_FORM_STRP_SUP: u64 = 0x1D;
+const DW_FORM_DATA16: u64 = 0x1E;
+const DW_FORM_LINE_STRP: u64 = 0x1F;
+const DW_FORM_REF_SIG8: u64 = 0x20;
+const DW_FORM_IMPLICIT_CONST: u64 = 0x21;
+const DW_FORM_LOCLISTX: u64 = 0x22;
+const DW_FORM_RNGLISTX: u64 = 0x23;
+const DW_FORM_REF_SUP8: u64 = 0x24;
+const DW_FORM_STRX1: u64 = 0x25;
+const DW_FORM_STRX4: u64 = 0x28;
+const DW_FORM_ADDRX1: u64 = 0x29;
+const DW_FORM_ADDRX4: u64 = 0x2C;
+const DW_FORM_GNU_ADDR_INDEX: u64 = 0x1F01;
+const DW_FORM_GNU_STR_INDEX: u64 = 0x1F02;
+const DW_FORM_GNU_REF_ALT: u64 = 0x1F20;
+const DW_FORM_GNU_STRP_ALT: u64 = 0x1F21;
+
+const DW_UT_SKELETON: u8 = 4;
+const DW_UT_SPLIT_COMPILE: u8 = 5;
+const DW_UT_TYPE: u8 = 2;
+const DW_UT_SPLIT_TYPE: u8 = 6;
+
+const DWARF_ABBREV_TABLE: RecordDesc<'static> = RecordDesc::new(
+    "dwarf_abbrev_table",
+    &[ "offset", "abbrev_count", "attribute_count" ]);
+
+const DWARF_LINE_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "dwarf_line_header",
+    &[
+        "offset", "format", "unit_length", "version", "address_size",
+        "header_length", "minimum_instruction_length",
+        "maximum_operations_per_instruction", "default_is_stmt",
+        "line_base", "line_range", "opcode_base",
+        "include_directory_count", "file_name_count",
+    ]);
+
+const DWARF_COMPILE_UNIT: RecordDesc<'static> = RecordDesc::new(
+    "dwarf_compile_unit",
+    &[
+        "offset", "format", "unit_length", "version", "unit_type",
+        "address_size", "abbrev_offset", "tag", "name",
+    ]);
+
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::InvalidData, msg))
+}
+
+fn format_cell<'x>(offset_size: usize) -> DataCell<'x> {
+    DataCell::from_static_id(if offset_size == 8 { "dwarf64" } else { "dwarf32" })
+}
+
+/* DwarfReader **************************************************************/
+/* cursor over section data; reads past the end fail with InvalidData */
+#[derive(Clone)]
+struct DwarfReader<'d> {
+    data: &'d [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'d> DwarfReader<'d> {
+
+    fn new(data: &'d [u8], big_endian: bool) -> Self {
+        DwarfReader { data, pos: 0, big_endian }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes<'x>(&mut self, n: usize) -> Result<&'d [u8], Error<'x>> {
+        let b = self.data.get(self.pos..).and_then(|d| d.get(..n))
+            .ok_or_else(|| invalid("DWARF data truncated"))?;
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn uint<'x>(&mut self, size: usize) -> Result<u64, Error<'x>> {
+        let b = self.bytes(size)?;
+        let endianness = Endianness::from_big_endian_flag(self.big_endian);
+        try_decode_uint_at(b, 0, size, endianness)
+            .map_err(|_| invalid("DWARF field too wide"))
+    }
+
+    fn u8<'x>(&mut self) -> Result<u8, Error<'x>> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /* bits past 64 are dropped */
+    fn uleb<'x>(&mut self) -> Result<u64, Error<'x>> {
+        let mut v = 0_u64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                v |= ((b & 0x7F) as u64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 { return Ok(v); }
+        }
+    }
+
+    fn sleb<'x>(&mut self) -> Result<i64, Error<'x>> {
+        let mut v = 0_i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                v |= ((b & 0x7F) as i64) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    v |= -1_i64 << shift;
+                }
+                return Ok(v);
+            }
+        }
+    }
+
+    fn cstr<'x>(&mut self) -> Result<&'d [u8], Error<'x>> {
+        let rest = self.data.get(self.pos..).unwrap_or(b"");
+        let end = rest.iter().position(|&c| c == 0)
+            .ok_or_else(|| invalid("DWARF string not terminated"))?;
+        self.pos += end + 1;
+        Ok(&rest[0..end])
+    }
+
+    /* unit length and the size of offsets in the unit (4 or 8) */
+    fn initial_length<'x>(&mut self) -> Result<(u64, usize), Error<'x>> {
+        match self.uint(4)? {
+            0xFFFF_FFFF => Ok((self.uint(8)?, 8)),
+            l if l >= 0xFFFF_FFF0 => Err(invalid("reserved DWARF unit length")),
+            l => Ok((l, 4)),
+        }
+    }
+
+    /* the next len bytes as a reader of their own */
+    fn sub_reader<'x>(&mut self, len: u64) -> Result<DwarfReader<'d>, Error<'x>> {
+        let len = usize::try_from(len).map_err(|_| invalid("DWARF unit too large"))?;
+        Ok(DwarfReader::new(self.bytes(len)?, self.big_endian))
+    }
+
+}
+
+/* Form values **************************************************************/
+/* what is needed to size attribute values of a unit */
+#[derive(Copy, Clone, Debug)]
+struct UnitParams {
+    version: u16,
+    offset_size: usize,
+    address_size: usize,
+}
+
+/* attribute value, as far as this module looks into them */
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FormValue<'d> {
+    Str(&'d [u8]),
+    Strp(u64),
+    LineStrp(u64),
+    Uint(u64),
+    Other,
+}
+
+fn read_form<'d, 'x>(
+    r: &mut DwarfReader<'d>,
+    mut form: u64,
+    u: UnitParams,
+) -> Result<FormValue<'d>, Error<'x>> {
+    loop {
+        return Ok(match form {
+            DW_FORM_ADDR => FormValue::Uint(r.uint(u.address_size)?),
+            DW_FORM_DATA1 | DW_FORM_FLAG | DW_FORM_REF1 => FormValue::Uint(r.uint(1)?),
+            DW_FORM_DATA2 | DW_FORM_REF2 => FormValue::Uint(r.uint(2)?),
+            DW_FORM_DATA4 | DW_FORM_REF4 | DW_FORM_REF_SUP4 => FormValue::Uint(r.uint(4)?),
+            DW_FORM_DATA8 | DW_FORM_REF8 | DW_FORM_REF_SIG8 | DW_FORM_REF_SUP8 =>
+                FormValue::Uint(r.uint(8)?),
+            DW_FORM_STRX1..=DW_FORM_STRX4 =>
+                FormValue::Uint(r.uint((form - DW_FORM_STRX1 + 1) as usize)?),
+            DW_FORM_ADDRX1..=DW_FORM_ADDRX4 =>
+                FormValue::Uint(r.uint((form - DW_FORM_ADDRX1 + 1) as usize)?),
+            DW_FORM_DATA16 => { r.bytes(16)?; FormValue::Other },
+            DW_FORM_UDATA | DW_FORM_REF_UDATA | DW_FORM_STRX | DW_FORM_ADDRX
+                | DW_FORM_LOCLISTX | DW_FORM_RNGLISTX
+                | DW_FORM_GNU_ADDR_INDEX | DW_FORM_GNU_STR_INDEX =>
+                FormValue::Uint(r.uleb()?),
+            DW_FORM_SDATA => { r.sleb()?; FormValue::Other },
+            DW_FORM_STRING => FormValue::Str(r.cstr()?),
+            DW_FORM_STRP => FormValue::Strp(r.uint(u.offset_size)?),
+            DW_FORM_LINE_STRP => FormValue::LineStrp(r.uint(u.offset_size)?),
+            DW_FORM_SEC_OFFSET | DW_FORM_STRP_SUP
+                | DW_FORM_GNU_REF_ALT | DW_FORM_GNU_STRP_ALT =>
+                FormValue::Uint(r.uint(u.offset_size)?),
+            DW_FORM_REF_ADDR => FormValue::Uint(
+                r.uint(if u.version <= 2 { u.address_size } else { u.offset_size })?),
+            DW_FORM_BLOCK1 => { let n = r.uint(1)?; r.sub_reader(n)?; FormValue::Other },
+            DW_FORM_BLOCK2 => { let n = r.uint(2)?; r.sub_reader(n)?; FormValue::Other },
+            DW_FORM_BLOCK4 => { let n = r.uint(4)?; r.sub_reader(n)?; FormValue::Other },
+            DW_FORM_BLOCK | DW_FORM_EXPRLOC => {
+                let n = r.uleb()?;
+                r.sub_reader(n)?;
+                FormValue::Other
+            },
+            DW_FORM_FLAG_PRESENT | DW_FORM_IMPLICIT_CONST => FormValue::Other,
+            DW_FORM_INDIRECT => {
+                form = r.uleb()?;
+                if form == DW_FORM_INDIRECT {
+                    return Err(invalid("nested DW_FORM_indirect"));
+                }
+                continue;
+            },
+            _ => return Err(invalid("unknown DWARF form")),
+        });
+    }
+}
+
+/* Abbreviations ************************************************************/
+struct Abbrev<'d> {
+    code: u64,
+    tag: u64,
+    specs: DwarfReader<'d>, // (name, form) pairs up to (0, 0)
+    attribute_count: u64,
+}
+
+/* next declaration of an abbreviation table, None at the end of the table */
+fn next_abbrev<'d, 'x>(r: &mut DwarfReader<'d>) -> Result<Option<Abbrev<'d>>, Error<'x>> {
+    let code = r.uleb()?;
+    if code == 0 {
+        return Ok(None);
+    }
+    let tag = r.uleb()?;
+    r.u8()?; // DW_CHILDREN_*
+    let specs = r.clone();
+    let mut attribute_count = 0;
+    loop {
+        let name = r.uleb()?;
+        let form = r.uleb()?;
+        if name == 0 && form == 0 { break; }
+        if form == DW_FORM_IMPLICIT_CONST {
+            r.sleb()?;
+        }
+        attribute_count += 1;
+    }
+    Ok(Some(Abbrev { code, tag, specs, attribute_count }))
+}
+
+fn find_abbrev<'d, 'x>(
+    abbrev: &'d [u8],
+    offset: u64,
+    code: u64,
+    big_endian: bool,
+) -> Result<Abbrev<'d>, Error<'x>> {
+    let mut r = DwarfReader::new(abbrev, big_endian);
+    r.pos = usize::try_from(offset).ok().filter(|&o| o < abbrev.len())
+        .ok_or_else(|| invalid("DWARF abbreviation offset out of range"))?;
+    while let Some(a) = next_abbrev(&mut r)? {
+        if a.code == code {
+            return Ok(a);
+        }
+    }
+    Err(invalid("DWARF abbreviation code not found"))
+}
+
+/* one record per abbreviation table of a .debug_abbrev section */
+pub fn abbrev_tables<'x>(
+    abbrev: &[u8],
+    big_endian: bool,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut tables: Vector<'x, DataCell> = Vector::new(a);
+    let mut r = DwarfReader::new(abbrev, big_endian);
+    while !r.at_end() {
+        xc.check_cancelled()?;
+        let offset = r.pos as u64;
+        let mut abbrev_count = 0;
+        let mut attribute_count = 0;
+        while let Some(a) = next_abbrev(&mut r)? {
+            abbrev_count += 1;
+            attribute_count += a.attribute_count;
+        }
+        if abbrev_count == 0 { continue; } // padding
+        let mut t = Record::new(&DWARF_ABBREV_TABLE, a)?;
+        t.set_field("offset", hex_cell(offset));
+        t.set_field("abbrev_count", DataCell::from_u64(abbrev_count));
+        t.set_field("attribute_count", DataCell::from_u64(attribute_count));
+        tables.push(DataCell::Record(xc.rc(RefCell::new(t))?))?;
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(tables)))?))
+}
+
+/* Line program headers *****************************************************/
+/* number of entries of a DWARF 5 directory or file name table */
+fn skip_entry_table<'x>(r: &mut DwarfReader<'_>, u: UnitParams) -> Result<u64, Error<'x>> {
+    let format_count = r.u8()?;
+    let mut formats = r.clone();
+    for _ in 0..format_count {
+        r.uleb()?;
+        r.uleb()?;
+    }
+    let count = r.uleb()?;
+    let formats_pos = formats.pos;
+    for _ in 0..count {
+        formats.pos = formats_pos;
+        for _ in 0..format_count {
+            formats.uleb()?; // DW_LNCT_*
+            read_form(r, formats.uleb()?, u)?;
+        }
+    }
+    Ok(count)
+}
+
+fn line_header<'x>(
+    offset: u64,
+    offset_size: usize,
+    unit_length: u64,
+    r: &mut DwarfReader<'_>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut h = Record::new(&DWARF_LINE_HEADER, a)?;
+    h.set_field("offset", hex_cell(offset));
+    h.set_field("format", format_cell(offset_size));
+    h.set_field("unit_length", hex_cell(unit_length));
+    let version = r.uint(2)? as u16;
+    h.set_field("version", DataCell::from_u64(version as u64));
+    if !(2..=5).contains(&version) {
+        return Err(invalid("unsupported DWARF line table version"));
+    }
+    let mut address_size = 0;
+    if version >= 5 {
+        address_size = r.u8()? as usize;
+        r.u8()?; // segment_selector_size
+        h.set_field("address_size", DataCell::from_u64(address_size as u64));
+    }
+    let u = UnitParams { version, offset_size, address_size };
+    h.set_field("header_length", hex_cell(r.uint(offset_size)?));
+    h.set_field("minimum_instruction_length", DataCell::from_u64(r.u8()? as u64));
+    if version >= 4 {
+        h.set_field("maximum_operations_per_instruction", DataCell::from_u64(r.u8()? as u64));
+    }
+    h.set_field("default_is_stmt", DataCell::from_u64(r.u8()? as u64));
+    /* signed byte, kept as it is encoded */
+    h.set_field("line_base", hex_cell(r.u8()? as u64));
+    h.set_field("line_range", DataCell::from_u64(r.u8()? as u64));
+    let opcode_base = r.u8()?;
+    h.set_field("opcode_base", DataCell::from_u64(opcode_base as u64));
+    r.bytes((opcode_base as usize).saturating_sub(1))?; // standard_opcode_lengths
+    let (dir_count, file_count) = if version >= 5 {
+        let dirs = skip_entry_table(r, u)?;
+        (dirs, skip_entry_table(r, u)?)
+    } else {
+        let mut dirs = 0;
+        while !r.cstr()?.is_empty() { dirs += 1; }
+        let mut files = 0;
+        while !r.cstr()?.is_empty() {
+            r.uleb()?; // directory index
+            r.uleb()?; // modification time
+            r.uleb()?; // length
+            files += 1;
+        }
+        (dirs, files)
+    };
+    h.set_field("include_directory_count", DataCell::from_u64(dir_count));
+    h.set_field("file_name_count", DataCell::from_u64(file_count));
+    Ok(DataCell::Record(xc.rc(RefCell::new(h))?))
+}
+
+/* headers of the line number programs of a .debug_line section, in
+ * section order */
+pub fn line_headers<'x>(
+    line: &[u8],
+    big_endian: bool,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut headers: Vector<'x, DataCell> = Vector::new(xc.get_main_allocator());
+    let mut r = DwarfReader::new(line, big_endian);
+    while !r.at_end() {
+        xc.check_cancelled()?;
+        let offset = r.pos as u64;
+        let (unit_length, offset_size) = r.initial_length()?;
+        let mut unit = r.sub_reader(unit_length)?;
+        headers.push(line_header(offset, offset_size, unit_length, &mut unit, xc)?)?;
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(headers)))?))
+}
+
+/* Compile units ************************************************************/
+fn unit_type_cell<'x>(t: u8) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        1 => "DW_UT_compile",
+        2 => "DW_UT_type",
+        3 => "DW_UT_partial",
+        4 => "DW_UT_skeleton",
+        5 => "DW_UT_split_compile",
+        6 => "DW_UT_split_type",
+        _ => return hex_cell(t as u64),
+    })
+}
+
+fn tag_cell<'x>(t: u64) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        0x11 => "DW_TAG_compile_unit",
+        0x3C => "DW_TAG_partial_unit",
+        0x41 => "DW_TAG_type_unit",
+        0x4A => "DW_TAG_skeleton_unit",
+        _ => return hex_cell(t),
+    })
+}
+
+/* the string sections unit names may point to */
+#[derive(Copy, Clone, Debug)]
+pub struct DwarfStrings<'d> {
+    pub str: &'d [u8],
+    pub line_str: &'d [u8],
+}
+
+fn compile_unit<'x>(
+    offset: u64,
+    offset_size: usize,
+    unit_length: u64,
+    r: &mut DwarfReader<'_>,
+    abbrev: &[u8],
+    strings: DwarfStrings<'_>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut cu = Record::new(&DWARF_COMPILE_UNIT, a)?;
+    cu.set_field("offset", hex_cell(offset));
+    cu.set_field("format", format_cell(offset_size));
+    cu.set_field("unit_length", hex_cell(unit_length));
+    let version = r.uint(2)? as u16;
+    cu.set_field("version", DataCell::from_u64(version as u64));
+    if !(2..=5).contains(&version) {
+        return Err(invalid("unsupported DWARF unit version"));
+    }
+    let (abbrev_offset, address_size) = if version >= 5 {
+        let unit_type = r.u8()?;
+        cu.set_field("unit_type", unit_type_cell(unit_type));
+        let address_size = r.u8()? as usize;
+        let abbrev_offset = r.uint(offset_size)?;
+        match unit_type {
+            DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => { r.uint(8)?; }, // dwo_id
+            DW_UT_TYPE | DW_UT_SPLIT_TYPE => {
+                r.uint(8)?; // type_signature
+                r.uint(offset_size)?; // type_offset
+            },
+            _ => {},
+        }
+        (abbrev_offset, address_size)
+    } else {
+        let abbrev_offset = r.uint(offset_size)?;
+        (abbrev_offset, r.u8()? as usize)
+    };
+    cu.set_field("address_size", DataCell::from_u64(address_size as u64));
+    cu.set_field("abbrev_offset", hex_cell(abbrev_offset));
+    let code = r.uleb()?;
+    if code != 0 {
+        let mut die = find_abbrev(abbrev, abbrev_offset, code, r.big_endian)?;
+        cu.set_field("tag", tag_cell(die.tag));
+        let u = UnitParams { version, offset_size, address_size };
+        loop {
+            let name = die.specs.uleb()?;
+            let form = die.specs.uleb()?;
+            if name == 0 && form == 0 { break; }
+            if form == DW_FORM_IMPLICIT_CONST {
+                die.specs.sleb()?;
+            }
+            let v = read_form(r, form, u)?;
+            if name != DW_AT_NAME { continue; }
+            let s = match v {
+                FormValue::Str(s) => Some(s),
+                FormValue::Strp(o) => table_str(strings.str, o),
+                FormValue::LineStrp(o) => table_str(strings.line_str, o),
+                _ => None,
+            };
+            if let Some(s) = s {
+                cu.set_field("name", DataCell::from_byte_slice(a, s)?);
+            }
+            break;
+        }
+    }
+    Ok(DataCell::Record(xc.rc(RefCell::new(cu))?))
+}
+
+/* units of a .debug_info section with the tag and name of their first
+ * entry */
+pub fn compile_units<'x>(
+    info: &[u8],
+    abbrev: &[u8],
+    strings: DwarfStrings<'_>,
+    big_endian: bool,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut units: Vector<'x, DataCell> = Vector::new(xc.get_main_allocator());
+    let mut r = DwarfReader::new(info, big_endian);
+    while !r.at_end() {
+        xc.check_cancelled()?;
+        let offset = r.pos as u64;
+        let (unit_length, offset_size) = r.initial_length()?;
+        let mut unit = r.sub_reader(unit_length)?;
+        units.push(compile_unit(offset, offset_size, unit_length, &mut unit, abbrev, strings, xc)?)?;
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(units)))?))
+}
+
+/* ELF sections *************************************************************/
+/* content of the named section, None when the file has no such section;
+ * compressed sections are not supported */
+fn debug_section<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    elf: &ElfFile<'x>,
+    names: &[u8],
+    name: &[u8],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<Option<Vector<'x, u8>>, Error<'x>> {
+    let s = match elf.find_section(names, name) {
+        Some(s) if s.sh_type != SHT_NOBITS => s,
+        _ => return Ok(None),
+    };
+    if s.sh_flags & SHF_COMPRESSED != 0 {
+        return Err(invalid("compressed DWARF section"));
+    }
+    let mut w = Window::new(stream, s.sh_offset, s.sh_size);
+    let len = w.len();
+    Ok(Some(read_region(&mut w, 0, len, xc)?))
+}
+
+pub fn dwarf_abbrev_tables<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let elf = ElfFile::load(stream, xc)?;
+    let names = elf.section_names(stream, xc)?;
+    let abbrev = debug_section(stream, &elf, names.as_slice(), b".debug_abbrev", xc)?
+        .ok_or(Error::NotApplicable)?;
+    abbrev_tables(abbrev.as_slice(), elf.header.layout.big_endian, xc)
+}
+
+pub fn dwarf_line_headers<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let elf = ElfFile::load(stream, xc)?;
+    let names = elf.section_names(stream, xc)?;
+    let line = debug_section(stream, &elf, names.as_slice(), b".debug_line", xc)?
+        .ok_or(Error::NotApplicable)?;
+    line_headers(line.as_slice(), elf.header.layout.big_endian, xc)
+}
+
+pub fn dwarf_compile_units<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let elf = ElfFile::load(stream, xc)?;
+    let names = elf.section_names(stream, xc)?;
+    let info = debug_section(stream, &elf, names.as_slice(), b".debug_info", xc)?
+        .ok_or(Error::NotApplicable)?;
+    let abbrev = debug_section(stream, &elf, names.as_slice(), b".debug_abbrev", xc)?
+        .ok_or_else(|| invalid("DWARF info without abbreviations"))?;
+    let str = debug_section(stream, &elf, names.as_slice(), b".debug_str", xc)?;
+    let line_str = debug_section(stream, &elf, names.as_slice(), b".debug_line_str", xc)?;
+    let strings = DwarfStrings {
+        str: str.as_ref().map_or(b"", |s| s.as_slice()),
+        line_str: line_str.as_ref().map_or(b"", |s| s.as_slice()),
+    };
+    compile_units(info.as_slice(), abbrev.as_slice(), strings, elf.header.layout.big_endian, xc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    // two compile units built by gcc with DWARF 5, from a.c and b.c
+    const ABBREV: &[u8] = b"\x01\x05\x00\x03\x08\x3A\x21\x01\x3B\x21\x02\x39\x0B\x49\x13\x02\x18\x00\x00\x02\x11\x01\x25\x0E\x13\x0B\x03\x1F\x1B\x1F\x11\x01\x12\x07\x10\x17\x00\x00\x03\x34\x00\x03\x08\x3A\x0B\x3B\x0B\x39\x0B\x49\x13\x3F\x19\x02\x18\x00\x00\x04\x24\x00\x0B\x0B\x3E\x0B\x03\x08\x00\x00\x05\x2E\x00\x3F\x19\x03\x0E\x3A\x0B\x3B\x0B\x39\x0B\x27\x19\x11\x01\x12\x07\x40\x18\x7C\x19\x00\x00\x06\x2E\x01\x3F\x19\x03\x08\x3A\x0B\x3B\x0B\x39\x0B\x27\x19\x49\x13\x11\x01\x12\x07\x40\x18\x7A\x19\x00\x00\x00\x01\x11\x01\x25\x0E\x13\x0B\x03\x1F\x1B\x1F\x11\x01\x12\x07\x10\x17\x00\x00\x02\x34\x00\x03\x08\x3A\x0B\x3B\x0B\x39\x0B\x49\x13\x02\x18\x00\x00\x03\x24\x00\x0B\x0B\x3E\x0B\x03\x08\x00\x00\x04\x2E\x01\x3F\x19\x03\x08\x3A\x0B\x3B\x0B\x39\x0B\x27\x19\x49\x13\x11\x01\x12\x07\x40\x18\x7A\x19\x00\x00\x05\x05\x00\x03\x08\x3A\x0B\x3B\x0B\x39\x0B\x49\x13\x02\x18\x00\x00\x00";
+    const INFO: &[u8] = b"\x95\x00\x00\x00\x05\x00\x01\x08\x00\x00\x00\x00\x02\x00\x00\x00\x00\x1D\x08\x00\x00\x00\x00\x00\x00\x00\x00\x10\x40\x00\x00\x00\x00\x00\x31\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x76\x00\x01\x01\x05\x42\x00\x00\x00\x09\x03\x00\x20\x40\x00\x00\x00\x00\x00\x04\x04\x05\x69\x6E\x74\x00\x05\x0F\x00\x00\x00\x01\x03\x06\x1C\x10\x40\x00\x00\x00\x00\x00\x15\x00\x00\x00\x00\x00\x00\x00\x01\x9C\x06\x61\x64\x64\x00\x01\x02\x05\x42\x00\x00\x00\x00\x10\x40\x00\x00\x00\x00\x00\x1C\x00\x00\x00\x00\x00\x00\x00\x01\x9C\x01\x61\x00\x0D\x42\x00\x00\x00\x02\x91\x6C\x01\x62\x00\x14\x42\x00\x00\x00\x02\x91\x68\x00\x00\x72\x00\x00\x00\x05\x00\x01\x08\x79\x00\x00\x00\x01\x00\x00\x00\x00\x1D\x0C\x00\x00\x00\x00\x00\x00\x00\x31\x10\x40\x00\x00\x00\x00\x00\x1D\x00\x00\x00\x00\x00\x00\x00\x5B\x00\x00\x00\x02\x7A\x00\x01\x01\x0C\x42\x00\x00\x00\x09\x03\x04\x20\x40\x00\x00\x00\x00\x00\x03\x04\x05\x69\x6E\x74\x00\x04\x6D\x75\x6C\x00\x01\x02\x05\x42\x00\x00\x00\x31\x10\x40\x00\x00\x00\x00\x00\x1D\x00\x00\x00\x00\x00\x00\x00\x01\x9C\x05\x61\x00\x01\x02\x0D\x42\x00\x00\x00\x02\x91\x6C\x00\x00";
+    const LINE: &[u8] = b"\x57\x00\x00\x00\x05\x00\x08\x00\x2A\x00\x00\x00\x01\x01\x01\xFB\x0E\x0D\x00\x01\x01\x01\x01\x00\x00\x00\x01\x00\x00\x01\x01\x01\x1F\x01\x00\x00\x00\x00\x02\x01\x1F\x02\x0F\x02\x08\x00\x00\x00\x00\x08\x00\x00\x00\x00\x05\x17\x00\x09\x02\x00\x10\x40\x00\x00\x00\x00\x00\x13\x05\x22\x9E\x05\x26\x82\x05\x2B\x82\x05\x13\x2F\x05\x1E\x00\x02\x04\x01\x4A\x08\x00\x01\x01\x4E\x00\x00\x00\x05\x00\x08\x00\x2A\x00\x00\x00\x01\x01\x01\xFB\x0E\x0D\x00\x01\x01\x01\x01\x00\x00\x00\x01\x00\x00\x01\x01\x01\x1F\x01\x00\x00\x00\x00\x02\x01\x1F\x02\x0F\x02\x0C\x00\x00\x00\x00\x0C\x00\x00\x00\x00\x05\x10\x00\x09\x02\x31\x10\x40\x00\x00\x00\x00\x00\x13\x05\x1B\x74\x05\x1F\xBA\x05\x24\x82\x02\x02\x00\x01\x01";
+    const STR: &[u8] = b"\x47\x4E\x55\x20\x43\x31\x37\x20\x31\x32\x2E\x32\x2E\x30\x00\x5F\x73\x74\x61\x72\x74\x00";
+    const LINE_STR: &[u8] = b"\x2F\x74\x6D\x70\x2F\x64\x77\x00\x61\x2E\x63\x00\x62\x2E\x63\x00";
+    // line program of a.c with DWARF 4
+    const LINE_V4: &[u8] = b"\x46\x00\x00\x00\x04\x00\x1B\x00\x00\x00\x01\x01\x01\xFB\x0E\x0D\x00\x01\x01\x01\x01\x00\x00\x00\x01\x00\x00\x01\x00\x61\x2E\x63\x00\x00\x00\x00\x00\x05\x17\x00\x09\x02\x00\x10\x40\x00\x00\x00\x00\x00\x13\x05\x22\x9E\x05\x26\x82\x05\x2B\x82\x05\x13\x2F\x05\x1E\x00\x02\x04\x01\x4A\x08\x00\x01\x01";
+
+    fn text<'x>(c: DataCell<'x>, xc: &mut ExecutionContext<'x>) -> Vector<'x, u8> {
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, xc).unwrap();
+        o
+    }
+
+    #[test]
+    fn gcc_sections() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = abbrev_tables(ABBREV, false, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(c, &mut xc).as_slice()).unwrap(), concat!(
+            "[dwarf_abbrev_table(offset: 0x00, abbrev_count: 6, attribute_count: 44)",
+            "dwarf_abbrev_table(offset: 0x79, abbrev_count: 5, attribute_count: 33)]"));
+
+        let strings = DwarfStrings { str: STR, line_str: LINE_STR };
+        let c = compile_units(INFO, ABBREV, strings, false, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(c, &mut xc).as_slice()).unwrap(), concat!(
+            "[dwarf_compile_unit(offset: 0x00, format: dwarf32, unit_length: 0x95, version: 5, ",
+            "unit_type: DW_UT_compile, address_size: 8, abbrev_offset: 0x00, ",
+            "tag: DW_TAG_compile_unit, name: b\"a.c\")",
+            "dwarf_compile_unit(offset: 0x99, format: dwarf32, unit_length: 0x72, version: 5, ",
+            "unit_type: DW_UT_compile, address_size: 8, abbrev_offset: 0x79, ",
+            "tag: DW_TAG_compile_unit, name: b\"b.c\")]"));
+        let no_strings = DwarfStrings { str: b"", line_str: b"" };
+        let c = compile_units(&INFO[0..0x99], ABBREV, no_strings, false, &mut xc).unwrap();
+        assert!(!core::str::from_utf8(text(c, &mut xc).as_slice()).unwrap().contains("name"));
+        assert!(compile_units(&INFO[0..0x98], ABBREV, strings, false, &mut xc).is_err());
+        assert!(compile_units(INFO, &ABBREV[0..0x79], strings, false, &mut xc).is_err());
+
+        let c = line_headers(LINE, false, &mut xc).unwrap();
+        match c {
+            DataCell::CellVector(ref v) => assert_eq!(v.borrow().0.len(), 2),
+            _ => panic!("not a vector"),
+        }
+        let c = line_headers(&LINE[0..0x5B], false, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(c, &mut xc).as_slice()).unwrap(), concat!(
+            "[dwarf_line_header(offset: 0x00, format: dwarf32, unit_length: 0x57, version: 5, ",
+            "address_size: 8, header_length: 0x2A, minimum_instruction_length: 1, ",
+            "maximum_operations_per_instruction: 1, default_is_stmt: 1, line_base: 0xFB, ",
+            "line_range: 14, opcode_base: 13, include_directory_count: 1, file_name_count: 2)]"));
+        let c = line_headers(LINE_V4, false, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(c, &mut xc).as_slice()).unwrap(), concat!(
+            "[dwarf_line_header(offset: 0x00, format: dwarf32, unit_length: 0x46, version: 4, ",
+            "header_length: 0x1B, minimum_instruction_length: 1, ",
+            "maximum_operations_per_instruction: 1, default_is_stmt: 1, line_base: 0xFB, ",
+            "line_range: 14, opcode_base: 13, include_directory_count: 0, file_name_count: 1)]"));
+        assert!(line_headers(&LINE[0..0x30], false, &mut xc).is_err());
+    }
+
+    #[test]
+    fn leb128_and_forms() {
+        let u = UnitParams { version: 4, offset_size: 8, address_size: 4 };
+        let mut r = DwarfReader::new(b"\xE5\x8E\x26\x7F\x80\x7F\x12\x34\x56\x78\x9A\xBC\xDE\xF0\x02\x00\x41", true);
+        assert_eq!(r.uleb().unwrap(), 624485);
+        assert_eq!(r.sleb().unwrap(), -1);
+        assert_eq!(r.sleb().unwrap(), -128);
+        assert_eq!(read_form(&mut r, DW_FORM_STRP, u).unwrap(), FormValue::Strp(0x123456789ABCDEF0));
+        assert_eq!(read_form(&mut r, DW_FORM_BLOCK1, u).unwrap(), FormValue::Other);
+        assert!(r.at_end() && read_form(&mut r, DW_FORM_DATA1, u).is_err());
+        let mut r = DwarfReader::new(b"\x08ab\x00\x01\x02\x03\x04", false);
+        assert_eq!(read_form(&mut r, DW_FORM_INDIRECT, u).unwrap(), FormValue::Str(b"ab"));
+        let v2 = UnitParams { version: 2, ..u };
+        assert_eq!(read_form(&mut r.clone(), DW_FORM_REF_ADDR, v2).unwrap(), FormValue::Uint(0x04030201));
+        assert!(read_form(&mut r, DW_FORM_REF_ADDR, u).is_err());
+        assert!(read_form(&mut r, 0x99, u).is_err());
+        let mut r = DwarfReader::new(b"\xFF\xFF\xFF\xFF\x10\x00\x00\x00\x00\x00\x00\x00\xF0\xFF\xFF\xFF", false);
+        assert_eq!(r.initial_length().unwrap(), (0x10, 8));
+        assert!(r.initial_length().is_err());
+    }
+}
@@ -0,0 +1,655 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::decode_uint_at;
+use crate::data_cell::DCOVector;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::data_cell::content_stream::ELFCLASS32;
+use crate::data_cell::content_stream::ELFCLASS64;
+use crate::data_cell::content_stream::ELFDATA2LSB;
+use crate::data_cell::content_stream::ELFDATA2MSB;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use crate::mm::Vector;
+use super::hex_cell;
+use super::read_region;
+
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_DYNAMIC: u32 = 6;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_DYNSYM: u32 = 11;
+pub const SHF_COMPRESSED: u64 = 0x800;
+pub const PT_DYNAMIC: u32 = 2;
+pub const SHN_XINDEX: u16 = 0xFFFF;
+pub const PN_XNUM: u16 = 0xFFFF;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_SONAME: u64 = 14;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+const ELF_PROGRAM_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "elf_program_header",
+    &[
+        "p_type", "p_flags", "p_offset", "p_vaddr", "p_paddr",
+        "p_filesz", "p_memsz", "p_align",
+    ]);
+
+const ELF_SECTION_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "elf_section_header",
+    &[
+        "sh_name", "sh_type", "sh_flags", "sh_addr", "sh_offset", "sh_size",
+        "sh_link", "sh_info", "sh_addralign", "sh_entsize",
+    ]);
+
+const ELF_SYMBOL: RecordDesc<'static> = RecordDesc::new(
+    "elf_symbol",
+    &[
+        "table", "st_name", "st_value", "st_size", "st_type", "st_bind",
+        "st_visibility", "st_shndx",
+    ]);
+
+const ELF_DYNAMIC: RecordDesc<'static> = RecordDesc::new(
+    "elf_dynamic",
+    &[ "d_tag", "d_val", "d_str" ]);
+
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::InvalidData, msg))
+}
+
+/* NUL terminated string at the given offset of a string table */
+pub fn table_str(table: &[u8], offset: u64) -> Option<&[u8]> {
+    let s = table.get(offset as usize..).filter(|_| offset < table.len() as u64)?;
+    let end = s.iter().position(|&c| c == 0)?;
+    Some(&s[0..end])
+}
+
+/* ElfLayout ****************************************************************/
+/* class and byte order, which decide how every other field is decoded */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ElfLayout {
+    pub class64: bool,
+    pub big_endian: bool,
+}
+
+impl ElfLayout {
+
+    pub fn from_ident(ident: &[u8]) -> Option<Self> {
+        let class64 = match *ident.get(4)? {
+            ELFCLASS32 => false,
+            ELFCLASS64 => true,
+            _ => return None,
+        };
+        let big_endian = match *ident.get(5)? {
+            ELFDATA2LSB => false,
+            ELFDATA2MSB => true,
+            _ => return None,
+        };
+        Some(ElfLayout { class64, big_endian })
+    }
+
+    /* callers make sure data holds the field */
+    fn uint(&self, data: &[u8], offset: usize, size: usize) -> u64 {
+        decode_uint_at(data, offset, size, Endianness::from_big_endian_flag(self.big_endian))
+    }
+
+    fn half(&self, data: &[u8], offset: usize) -> u64 {
+        self.uint(data, offset, 2)
+    }
+
+    fn word(&self, data: &[u8], offset: usize) -> u64 {
+        self.uint(data, offset, 4)
+    }
+
+    /* Addr, Off and Xword: 4 or 8 bytes depending on class */
+    fn addr(&self, data: &[u8], offset: usize) -> u64 {
+        self.uint(data, offset, if self.class64 { 8 } else { 4 })
+    }
+
+    fn header_size(&self) -> usize {
+        if self.class64 { 0x40 } else { 0x34 }
+    }
+
+    fn program_header_size(&self) -> usize {
+        if self.class64 { 0x38 } else { 0x20 }
+    }
+
+    fn section_header_size(&self) -> usize {
+        if self.class64 { 0x40 } else { 0x28 }
+    }
+
+    fn symbol_size(&self) -> usize {
+        if self.class64 { 0x18 } else { 0x10 }
+    }
+
+    fn dynamic_size(&self) -> usize {
+        if self.class64 { 0x10 } else { 0x08 }
+    }
+
+}
+
+/* ElfFileHeader ************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ElfFileHeader {
+    pub layout: ElfLayout,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_phentsize: u64,
+    pub e_phnum: u64,
+    pub e_shentsize: u64,
+    pub e_shnum: u64,
+    pub e_shstrndx: u64,
+}
+
+impl ElfFileHeader {
+
+    /* NotApplicable for content other than ELF with a known layout; the
+     * extended numbering kept in section 0 is not applied here */
+    pub fn parse<'x>(data: &[u8]) -> Result<Self, Error<'x>> {
+        if !data.starts_with(b"\x7FELF") {
+            return Err(Error::NotApplicable);
+        }
+        let layout = ElfLayout::from_ident(data).ok_or(Error::NotApplicable)?;
+        if data.len() < layout.header_size() {
+            return Err(Error::IO(IOError::with_str(
+                IOErrorCode::UnexpectedEnd, "truncated ELF header")));
+        }
+        let (ph, sh) = if layout.class64 { (0x20, 0x28) } else { (0x1C, 0x20) };
+        // e_phentsize and the other sizes follow e_shoff, e_flags, e_ehsize
+        let sizes = sh + if layout.class64 { 14 } else { 10 };
+        Ok(ElfFileHeader {
+            layout,
+            e_phoff: layout.addr(data, ph),
+            e_shoff: layout.addr(data, sh),
+            e_phentsize: layout.half(data, sizes),
+            e_phnum: layout.half(data, sizes + 2),
+            e_shentsize: layout.half(data, sizes + 4),
+            e_shnum: layout.half(data, sizes + 6),
+            e_shstrndx: layout.half(data, sizes + 8),
+        })
+    }
+
+}
+
+/* ElfSectionHeader *********************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ElfSectionHeader {
+    pub sh_name: u64,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_offset: u64,
+    pub sh_size: u64,
+    pub sh_link: u64,
+    pub sh_info: u64,
+    pub sh_addralign: u64,
+    pub sh_entsize: u64,
+}
+
+impl ElfSectionHeader {
+
+    pub fn parse(l: ElfLayout, d: &[u8]) -> Self {
+        let a = if l.class64 { 8 } else { 4 };
+        ElfSectionHeader {
+            sh_name: l.word(d, 0),
+            sh_type: l.word(d, 4) as u32,
+            sh_flags: l.addr(d, 8),
+            sh_addr: l.addr(d, 8 + a),
+            sh_offset: l.addr(d, 8 + 2 * a),
+            sh_size: l.addr(d, 8 + 3 * a),
+            sh_link: l.word(d, 8 + 4 * a),
+            sh_info: l.word(d, 12 + 4 * a),
+            sh_addralign: l.addr(d, 16 + 4 * a),
+            sh_entsize: l.addr(d, 16 + 5 * a),
+        }
+    }
+
+}
+
+fn table_len<'x>(
+    entry_count: u64,
+    entry_size: u64,
+    min_entry_size: usize,
+) -> Result<u64, Error<'x>> {
+    if entry_count != 0 && entry_size < min_entry_size as u64 {
+        return Err(invalid("ELF table entry size too small"));
+    }
+    entry_count.checked_mul(entry_size).ok_or_else(|| invalid("ELF table too large"))
+}
+
+/* ElfFile ******************************************************************/
+/* file header and section headers, enough to find any other table */
+pub struct ElfFile<'x> {
+    pub header: ElfFileHeader,
+    pub sections: Vector<'x, ElfSectionHeader>,
+    pub phnum: u64,
+    pub shstrndx: u64,
+}
+
+impl<'x> ElfFile<'x> {
+
+    pub fn load<T: ?Sized + RandomAccessRead>(
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Self, Error<'x>> {
+        let mut buf = [0_u8; 0x40];
+        let n = stream.seek_read(0, &mut buf, xc)?;
+        let header = ElfFileHeader::parse(&buf[0..n])?;
+        let l = header.layout;
+        let mut sections: Vector<'x, ElfSectionHeader> = Vector::new(xc.get_main_allocator());
+        let mut shnum = header.e_shnum;
+        let mut phnum = header.e_phnum;
+        let mut shstrndx = header.e_shstrndx;
+        if header.e_shoff != 0 {
+            /* section 0 holds the counts that do not fit in the header */
+            let first = read_region(stream, header.e_shoff,
+                                    table_len(1, header.e_shentsize, l.section_header_size())?, xc)?;
+            let s0 = ElfSectionHeader::parse(l, first.as_slice());
+            if shnum == 0 { shnum = s0.sh_size; }
+            if phnum == PN_XNUM as u64 { phnum = s0.sh_info; }
+            if shstrndx == SHN_XINDEX as u64 { shstrndx = s0.sh_link; }
+            let len = table_len(shnum, header.e_shentsize, l.section_header_size())?;
+            let table = read_region(stream, header.e_shoff, len, xc)?;
+            sections.reserve(shnum as usize)?;
+            for entry in table.as_slice().chunks_exact(header.e_shentsize as usize) {
+                xc.check_cancelled()?;
+                sections.push(ElfSectionHeader::parse(l, entry)).map_err(|(e, _)| e)?;
+            }
+        }
+        Ok(ElfFile { header, sections, phnum, shstrndx })
+    }
+
+    pub fn section_data<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        index: u64,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Vector<'x, u8>, Error<'x>> {
+        let s = self.sections.as_slice().get(index as usize)
+            .ok_or_else(|| invalid("ELF section index out of range"))?;
+        if s.sh_type == SHT_NOBITS {
+            return Ok(Vector::new(xc.get_main_allocator()));
+        }
+        read_region(stream, s.sh_offset, s.sh_size, xc)
+    }
+
+    /* content of the section name table, empty when there is none */
+    pub fn section_names<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Vector<'x, u8>, Error<'x>> {
+        if self.shstrndx != 0 {
+            self.section_data(stream, self.shstrndx, xc)
+        } else {
+            Ok(Vector::new(xc.get_main_allocator()))
+        }
+    }
+
+    /* first section with the given name, looked up in names as returned
+     * by section_names */
+    pub fn find_section(&self, names: &[u8], name: &[u8]) -> Option<&ElfSectionHeader> {
+        self.sections.as_slice().iter()
+            .find(|s| table_str(names, s.sh_name) == Some(name))
+    }
+
+    pub fn program_headers<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let l = self.header.layout;
+        let len = table_len(self.phnum, self.header.e_phentsize, l.program_header_size())?;
+        let table = read_region(stream, self.header.e_phoff, len, xc)?;
+        let a = xc.get_main_allocator();
+        let mut headers: Vector<'x, DataCell> = Vector::new(a);
+        for d in table.as_slice().chunks_exact(self.header.e_phentsize.max(1) as usize) {
+            xc.check_cancelled()?;
+            let mut r = Record::new(&ELF_PROGRAM_HEADER, a)?;
+            let (flags, rest) = if l.class64 { (4, 8) } else { (24, 4) };
+            let w = if l.class64 { 8 } else { 4 };
+            r.set_field("p_type", p_type_cell(l.word(d, 0) as u32));
+            r.set_field("p_flags", hex_cell(l.word(d, flags)));
+            r.set_field("p_offset", hex_cell(l.addr(d, rest)));
+            r.set_field("p_vaddr", hex_cell(l.addr(d, rest + w)));
+            r.set_field("p_paddr", hex_cell(l.addr(d, rest + 2 * w)));
+            r.set_field("p_filesz", hex_cell(l.addr(d, rest + 3 * w)));
+            r.set_field("p_memsz", hex_cell(l.addr(d, rest + 4 * w)));
+            let align = if l.class64 { rest + 5 * w } else { 28 };
+            r.set_field("p_align", hex_cell(l.addr(d, align)));
+            headers.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(headers)))?))
+    }
+
+    pub fn section_headers<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let names = self.section_names(stream, xc)?;
+        let a = xc.get_main_allocator();
+        let mut headers: Vector<'x, DataCell> = Vector::new(a);
+        for s in self.sections.as_slice() {
+            let mut r = Record::new(&ELF_SECTION_HEADER, a)?;
+            match table_str(names.as_slice(), s.sh_name) {
+                Some(name) => r.set_field("sh_name", DataCell::from_byte_slice(a, name)?),
+                None => r.set_field("sh_name", DataCell::from_u64(s.sh_name)),
+            }
+            r.set_field("sh_type", sh_type_cell(s.sh_type));
+            r.set_field("sh_flags", hex_cell(s.sh_flags));
+            r.set_field("sh_addr", hex_cell(s.sh_addr));
+            r.set_field("sh_offset", hex_cell(s.sh_offset));
+            r.set_field("sh_size", hex_cell(s.sh_size));
+            r.set_field("sh_link", DataCell::from_u64(s.sh_link));
+            r.set_field("sh_info", DataCell::from_u64(s.sh_info));
+            r.set_field("sh_addralign", DataCell::from_u64(s.sh_addralign));
+            r.set_field("sh_entsize", DataCell::from_u64(s.sh_entsize));
+            headers.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(headers)))?))
+    }
+
+    /* entries of all SHT_SYMTAB and SHT_DYNSYM sections, in section
+     * order; each names the table it comes from */
+    pub fn symbols<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let l = self.header.layout;
+        let section_names = self.section_names(stream, xc)?;
+        let a = xc.get_main_allocator();
+        let mut symbols: Vector<'x, DataCell> = Vector::new(a);
+        for s in self.sections.as_slice() {
+            if s.sh_type != SHT_SYMTAB && s.sh_type != SHT_DYNSYM {
+                continue;
+            }
+            let entsize = if s.sh_entsize == 0 { l.symbol_size() as u64 } else { s.sh_entsize };
+            table_len(1, entsize, l.symbol_size())?;
+            let table_name = table_str(section_names.as_slice(), s.sh_name).unwrap_or(b"");
+            let table = read_region(stream, s.sh_offset, s.sh_size, xc)?;
+            let names = self.section_data(stream, s.sh_link, xc)?;
+            for d in table.as_slice().chunks_exact(entsize as usize) {
+                xc.check_cancelled()?;
+                let mut r = Record::new(&ELF_SYMBOL, a)?;
+                let (info_pos, value_pos, size_pos) = if l.class64 { (4, 8, 16) } else { (12, 4, 8) };
+                let info = d[info_pos];
+                let st_name = l.word(d, 0);
+                r.set_field("table", DataCell::from_byte_slice(a, table_name)?);
+                match table_str(names.as_slice(), st_name) {
+                    Some(name) => r.set_field("st_name", DataCell::from_byte_slice(a, name)?),
+                    None => r.set_field("st_name", DataCell::from_u64(st_name)),
+                }
+                r.set_field("st_value", hex_cell(l.addr(d, value_pos)));
+                r.set_field("st_size", DataCell::from_u64(l.addr(d, size_pos)));
+                r.set_field("st_type", st_type_cell(info & 0xF));
+                r.set_field("st_bind", st_bind_cell(info >> 4));
+                r.set_field("st_visibility", st_visibility_cell(d[info_pos + 1] & 3));
+                r.set_field("st_shndx", st_shndx_cell(l.half(d, info_pos + 2) as u16));
+                symbols.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+            }
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(symbols)))?))
+    }
+
+    /* entries of the dynamic section up to DT_NULL, with the strings some
+     * of them point to; the PT_DYNAMIC segment is used for files without
+     * section headers */
+    pub fn dynamic<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let l = self.header.layout;
+        let a = xc.get_main_allocator();
+        let (table, strings) = match self.sections.as_slice().iter().position(|s| s.sh_type == SHT_DYNAMIC) {
+            Some(i) => {
+                let link = self.sections.as_slice()[i].sh_link;
+                (self.section_data(stream, i as u64, xc)?, self.section_data(stream, link, xc)?)
+            },
+            None => {
+                let (offset, size) = self.dynamic_segment(stream, xc)?
+                    .ok_or(Error::NotApplicable)?;
+                (read_region(stream, offset, size, xc)?, Vector::new(a))
+            },
+        };
+        let mut entries: Vector<'x, DataCell> = Vector::new(a);
+        let w = if l.class64 { 8 } else { 4 };
+        for d in table.as_slice().chunks_exact(l.dynamic_size()) {
+            xc.check_cancelled()?;
+            let tag = l.addr(d, 0);
+            let val = l.addr(d, w);
+            let mut r = Record::new(&ELF_DYNAMIC, a)?;
+            r.set_field("d_tag", d_tag_cell(tag));
+            r.set_field("d_val", hex_cell(val));
+            if matches!(tag, DT_NEEDED | DT_SONAME | DT_RPATH | DT_RUNPATH) {
+                if let Some(s) = table_str(strings.as_slice(), val) {
+                    r.set_field("d_str", DataCell::from_byte_slice(a, s)?);
+                }
+            }
+            entries.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+            if tag == DT_NULL { break; }
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(entries)))?))
+    }
+
+    fn dynamic_segment<T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Option<(u64, u64)>, Error<'x>> {
+        let l = self.header.layout;
+        let len = table_len(self.phnum, self.header.e_phentsize, l.program_header_size())?;
+        let table = read_region(stream, self.header.e_phoff, len, xc)?;
+        let w = if l.class64 { 8 } else { 4 };
+        let rest = if l.class64 { 8 } else { 4 };
+        Ok(table.as_slice().chunks_exact(self.header.e_phentsize.max(1) as usize)
+            .find(|d| l.word(d, 0) as u32 == PT_DYNAMIC)
+            .map(|d| (l.addr(d, rest), l.addr(d, rest + 3 * w))))
+    }
+
+}
+
+fn p_type_cell<'x>(t: u32) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        0 => "PT_NULL",
+        1 => "PT_LOAD",
+        2 => "PT_DYNAMIC",
+        3 => "PT_INTERP",
+        4 => "PT_NOTE",
+        5 => "PT_SHLIB",
+        6 => "PT_PHDR",
+        7 => "PT_TLS",
+        0x6474_E550 => "PT_GNU_EH_FRAME",
+        0x6474_E551 => "PT_GNU_STACK",
+        0x6474_E552 => "PT_GNU_RELRO",
+        0x6474_E553 => "PT_GNU_PROPERTY",
+        _ => return hex_cell(t as u64),
+    })
+}
+
+fn sh_type_cell<'x>(t: u32) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        0 => "SHT_NULL",
+        1 => "SHT_PROGBITS",
+        2 => "SHT_SYMTAB",
+        3 => "SHT_STRTAB",
+        4 => "SHT_RELA",
+        5 => "SHT_HASH",
+        6 => "SHT_DYNAMIC",
+        7 => "SHT_NOTE",
+        8 => "SHT_NOBITS",
+        9 => "SHT_REL",
+        10 => "SHT_SHLIB",
+        11 => "SHT_DYNSYM",
+        14 => "SHT_INIT_ARRAY",
+        15 => "SHT_FINI_ARRAY",
+        16 => "SHT_PREINIT_ARRAY",
+        17 => "SHT_GROUP",
+        18 => "SHT_SYMTAB_SHNDX",
+        0x6FFF_FFF6 => "SHT_GNU_HASH",
+        0x6FFF_FFFD => "SHT_GNU_verdef",
+        0x6FFF_FFFE => "SHT_GNU_verneed",
+        0x6FFF_FFFF => "SHT_GNU_versym",
+        _ => return hex_cell(t as u64),
+    })
+}
+
+fn st_type_cell<'x>(t: u8) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        0 => "STT_NOTYPE",
+        1 => "STT_OBJECT",
+        2 => "STT_FUNC",
+        3 => "STT_SECTION",
+        4 => "STT_FILE",
+        5 => "STT_COMMON",
+        6 => "STT_TLS",
+        10 => "STT_GNU_IFUNC",
+        _ => return DataCell::from_u64(t as u64),
+    })
+}
+
+fn st_bind_cell<'x>(b: u8) -> DataCell<'x> {
+    DataCell::from_static_id(match b {
+        0 => "STB_LOCAL",
+        1 => "STB_GLOBAL",
+        2 => "STB_WEAK",
+        10 => "STB_GNU_UNIQUE",
+        _ => return DataCell::from_u64(b as u64),
+    })
+}
+
+fn st_visibility_cell<'x>(v: u8) -> DataCell<'x> {
+    DataCell::from_static_id(match v {
+        0 => "STV_DEFAULT",
+        1 => "STV_INTERNAL",
+        2 => "STV_HIDDEN",
+        _ => "STV_PROTECTED",
+    })
+}
+
+fn st_shndx_cell<'x>(i: u16) -> DataCell<'x> {
+    DataCell::from_static_id(match i {
+        0 => "SHN_UNDEF",
+        0xFFF1 => "SHN_ABS",
+        0xFFF2 => "SHN_COMMON",
+        SHN_XINDEX => "SHN_XINDEX",
+        _ => return DataCell::from_u64(i as u64),
+    })
+}
+
+fn d_tag_cell<'x>(t: u64) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        0 => "DT_NULL",
+        1 => "DT_NEEDED",
+        2 => "DT_PLTRELSZ",
+        3 => "DT_PLTGOT",
+        4 => "DT_HASH",
+        5 => "DT_STRTAB",
+        6 => "DT_SYMTAB",
+        7 => "DT_RELA",
+        8 => "DT_RELASZ",
+        9 => "DT_RELAENT",
+        10 => "DT_STRSZ",
+        11 => "DT_SYMENT",
+        12 => "DT_INIT",
+        13 => "DT_FINI",
+        14 => "DT_SONAME",
+        15 => "DT_RPATH",
+        16 => "DT_SYMBOLIC",
+        17 => "DT_REL",
+        18 => "DT_RELSZ",
+        19 => "DT_RELENT",
+        20 => "DT_PLTREL",
+        21 => "DT_DEBUG",
+        22 => "DT_TEXTREL",
+        23 => "DT_JMPREL",
+        24 => "DT_BIND_NOW",
+        25 => "DT_INIT_ARRAY",
+        26 => "DT_FINI_ARRAY",
+        27 => "DT_INIT_ARRAYSZ",
+        28 => "DT_FINI_ARRAYSZ",
+        29 => "DT_RUNPATH",
+        30 => "DT_FLAGS",
+        32 => "DT_PREINIT_ARRAY",
+        33 => "DT_PREINIT_ARRAYSZ",
+        0x6FFF_FEF5 => "DT_GNU_HASH",
+        0x6FFF_FFF0 => "DT_VERSYM",
+        0x6FFF_FFF9 => "DT_RELACOUNT",
+        0x6FFF_FFFA => "DT_RELCOUNT",
+        0x6FFF_FFFB => "DT_FLAGS_1",
+        0x6FFF_FFFE => "DT_VERNEED",
+        0x6FFF_FFFF => "DT_VERNEEDNUM",
+        _ => return hex_cell(t),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    // ELF32 big endian with a dynamic section and a symbol table
+    const ELF32_MSB: &[u8] = b"\x7F\x45\x4C\x46\x01\x02\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x00\x14\x00\x00\x00\x01\x00\x00\x10\x00\x00\x00\x00\x34\x00\x00\x00\xF0\x00\x00\x00\x00\x00\x34\x00\x20\x00\x01\x00\x28\x00\x06\x00\x05\x00\x00\x00\x02\x00\x00\x00\x60\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00\x00\x20\x00\x00\x00\x20\x00\x00\x00\x06\x00\x00\x00\x04\x00\x6C\x69\x62\x63\x2E\x73\x6F\x2E\x36\x00\x00\x00\x00\x00\x01\x00\x00\x00\x01\x00\x00\x00\x05\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x6D\x61\x69\x6E\x00\x64\x61\x74\x61\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x20\x00\x00\x00\x00\x04\x01\x02\xFF\xF1\x00\x00\x00\x01\x00\x00\x10\x00\x00\x00\x00\x20\x12\x00\x00\x01\x00\x2E\x73\x68\x73\x74\x72\x74\x61\x62\x00\x2E\x73\x74\x72\x74\x61\x62\x00\x2E\x73\x79\x6D\x74\x61\x62\x00\x2E\x64\x79\x6E\x61\x6D\x69\x63\x00\x2E\x64\x79\x6E\x73\x74\x72\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x24\x00\x00\x00\x03\x00\x00\x00\x02\x00\x00\x04\x00\x00\x00\x00\x54\x00\x00\x00\x0B\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x1B\x00\x00\x00\x06\x00\x00\x00\x03\x00\x00\x05\x00\x00\x00\x00\x60\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x08\x00\x00\x00\x08\x00\x00\x00\x0B\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\x00\x00\x0B\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x13\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x90\x00\x00\x00\x30\x00\x00\x00\x03\x00\x00\x00\x02\x00\x00\x00\x08\x00\x00\x00\x10\x00\x00\x00\x01\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\xC0\x00\x00\x00\x2C\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00";
+
+    #[test]
+    fn elf32_msb_tables() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(ELF32_MSB);
+        let f = ElfFile::load(&mut s, &mut xc).unwrap();
+        assert_eq!(f.header.layout, ElfLayout { class64: false, big_endian: true });
+        assert_eq!((f.sections.len(), f.phnum, f.shstrndx), (6, 1, 5));
+        assert_eq!(f.sections.as_slice()[4].sh_type, SHT_SYMTAB);
+        let names = f.section_names(&mut s, &mut xc).unwrap();
+        assert_eq!(f.find_section(names.as_slice(), b".dynamic").map(|s| s.sh_type), Some(SHT_DYNAMIC));
+        assert!(f.find_section(names.as_slice(), b".debug_info").is_none());
+
+        let mut o = xc.byte_vector();
+        f.program_headers(&mut s, &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[elf_program_header(p_type: PT_DYNAMIC, p_flags: 0x06, p_offset: 0x60, ",
+            "p_vaddr: 0x500, p_paddr: 0x500, p_filesz: 0x20, p_memsz: 0x20, p_align: 0x04)]"));
+
+        let mut o = xc.byte_vector();
+        f.symbols(&mut s, &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[elf_symbol(table: b\".symtab\", st_name: b\"\", st_value: 0x00, st_size: 0, ",
+            "st_type: STT_NOTYPE, st_bind: STB_LOCAL, st_visibility: STV_DEFAULT, st_shndx: SHN_UNDEF)",
+            "elf_symbol(table: b\".symtab\", st_name: b\"data\", st_value: 0x2000, st_size: 4, ",
+            "st_type: STT_OBJECT, st_bind: STB_LOCAL, st_visibility: STV_HIDDEN, st_shndx: SHN_ABS)",
+            "elf_symbol(table: b\".symtab\", st_name: b\"main\", st_value: 0x1000, st_size: 32, ",
+            "st_type: STT_FUNC, st_bind: STB_GLOBAL, st_visibility: STV_DEFAULT, st_shndx: 1)]"));
+
+        let mut o = xc.byte_vector();
+        f.dynamic(&mut s, &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "[elf_dynamic(d_tag: DT_NEEDED, d_val: 0x01, d_str: b\"libc.so.6\")",
+            "elf_dynamic(d_tag: DT_STRTAB, d_val: 0x400)",
+            "elf_dynamic(d_tag: DT_NULL, d_val: 0x00)]"));
+    }
+
+    #[test]
+    fn bad_headers() {
+        let mut xc = ExecutionContext::nop();
+        assert_eq!(ElfFileHeader::parse(b"\x7FELF\x03\x01").unwrap_err(), Error::NotApplicable);
+        assert_eq!(ElfFileHeader::parse(b"MZ").unwrap_err(), Error::NotApplicable);
+        assert!(ElfFileHeader::parse(&ELF32_MSB[0..0x30]).is_err());
+        let mut data = [0_u8; 0x34];
+        data.copy_from_slice(&ELF32_MSB[0..0x34]);
+        data[0x2F] = 0x10; // e_shentsize below the size of a section header
+        let mut s = BufferAsROStream::new(&data);
+        assert!(ElfFile::load(&mut s, &mut xc).is_err());
+    }
+}
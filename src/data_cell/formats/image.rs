@@ -0,0 +1,295 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::decode_at;
+use crate::conv::decode_uint_at;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use super::read_region;
+
+pub const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1A\n";
+pub const JPEG_SOI: &[u8; 3] = b"\xFF\xD8\xFF";
+const BMP_FILE_HEADER_SIZE: u64 = 14;
+/* JPEG markers without a length field */
+const JPEG_TEM: u8 = 0x01;
+const JPEG_RST0: u8 = 0xD0;
+const JPEG_EOI: u8 = 0xD9;
+const JPEG_SOS: u8 = 0xDA;
+
+const IMAGE_INFO: RecordDesc<'static> = RecordDesc::new(
+    "image_info",
+    &[ "format", "width", "height", "bit_depth", "color_type" ]);
+
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::InvalidData, msg))
+}
+
+/* ImageFormat **************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+}
+
+impl ImageFormat {
+
+    /* from the first bytes of content; BMP also needs a known DIB header
+     * size, "BM" alone being too weak */
+    pub fn from_magic(tof: &[u8]) -> Option<Self> {
+        if tof.starts_with(PNG_SIGNATURE) {
+            Some(ImageFormat::Png)
+        } else if tof.starts_with(JPEG_SOI) {
+            Some(ImageFormat::Jpeg)
+        } else if tof.starts_with(b"GIF87a") || tof.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if tof.starts_with(b"BM") && tof.len() >= 18
+                && matches!(decode_at::<u32>(tof, 14, Endianness::Little),
+                    12 | 40 | 52 | 56 | 64 | 108 | 124) {
+            Some(ImageFormat::Bmp)
+        } else {
+            None
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+
+}
+
+/* ImageInfo ****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u64,
+    pub height: u64,
+    pub bit_depth: u64, // bits per sample, or per pixel for indexed and BMP
+    pub color_type: &'static str,
+}
+
+impl ImageInfo {
+
+    /* NotApplicable for content other than the supported image formats */
+    pub fn load<'x, T: ?Sized + RandomAccessRead>(
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Self, Error<'x>> {
+        let mut tof = [0_u8; 18];
+        let n = stream.seek_read(0, &mut tof, xc)?;
+        match ImageFormat::from_magic(&tof[0..n]).ok_or(Error::NotApplicable)? {
+            ImageFormat::Png => png_info(stream, xc),
+            ImageFormat::Jpeg => jpeg_info(stream, xc),
+            ImageFormat::Gif => gif_info(stream, xc),
+            ImageFormat::Bmp => bmp_info(stream, xc),
+        }
+    }
+
+    pub fn to_data_cell<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut r = Record::new(&IMAGE_INFO, xc.get_main_allocator())?;
+        r.set_field("format", DataCell::from_static_id(self.format.id()));
+        r.set_field("width", DataCell::from_u64(self.width));
+        r.set_field("height", DataCell::from_u64(self.height));
+        r.set_field("bit_depth", DataCell::from_u64(self.bit_depth));
+        r.set_field("color_type", DataCell::from_static_id(self.color_type));
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+/* the IHDR chunk must come first */
+fn png_info<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<ImageInfo, Error<'x>> {
+    let h = read_region(stream, 8, 21, xc)?;
+    let h = h.as_slice();
+    if &h[4..8] != b"IHDR" || decode_uint_at(h, 0, 4, Endianness::Big) < 13 {
+        return Err(invalid("PNG does not start with IHDR"));
+    }
+    Ok(ImageInfo {
+        format: ImageFormat::Png,
+        width: decode_uint_at(h, 8, 4, Endianness::Big),
+        height: decode_uint_at(h, 12, 4, Endianness::Big),
+        bit_depth: h[16] as u64,
+        color_type: match h[17] {
+            0 => "grayscale",
+            2 => "rgb",
+            3 => "indexed",
+            4 => "grayscale_alpha",
+            6 => "rgba",
+            _ => return Err(invalid("unknown PNG color type")),
+        },
+    })
+}
+
+/* walks the marker segments up to the first start of frame */
+fn jpeg_info<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<ImageInfo, Error<'x>> {
+    let mut pos = 2_u64;
+    loop {
+        let m = read_region(stream, pos, 2, xc)?;
+        let (prefix, marker) = (m.as_slice()[0], m.as_slice()[1]);
+        if prefix != 0xFF {
+            return Err(invalid("JPEG marker expected"));
+        }
+        match marker {
+            0xFF => { pos += 1; continue; }, // fill byte
+            JPEG_TEM | JPEG_RST0..=0xD7 => { pos += 2; continue; },
+            JPEG_SOS | JPEG_EOI => return Err(invalid("JPEG has no frame header")),
+            _ => {},
+        }
+        /* SOF0..SOF15, except DHT, JPG and DAC which share the range */
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let f = read_region(stream, pos + 2, 8, xc)?;
+            let f = f.as_slice();
+            return Ok(ImageInfo {
+                format: ImageFormat::Jpeg,
+                width: decode_uint_at(f, 5, 2, Endianness::Big),
+                height: decode_uint_at(f, 3, 2, Endianness::Big),
+                bit_depth: f[2] as u64,
+                color_type: match f[7] {
+                    1 => "grayscale",
+                    3 => "ycbcr",
+                    4 => "cmyk",
+                    _ => "other",
+                },
+            });
+        }
+        let len = read_region(stream, pos + 2, 2, xc)?;
+        let len = decode_uint_at(len.as_slice(), 0, 2, Endianness::Big);
+        if len < 2 {
+            return Err(invalid("JPEG segment length too small"));
+        }
+        pos += 2 + len;
+    }
+}
+
+/* logical screen descriptor; bit depth is the color resolution */
+fn gif_info<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<ImageInfo, Error<'x>> {
+    let h = read_region(stream, 6, 5, xc)?;
+    let h = h.as_slice();
+    Ok(ImageInfo {
+        format: ImageFormat::Gif,
+        width: decode_uint_at(h, 0, 2, Endianness::Little),
+        height: decode_uint_at(h, 2, 2, Endianness::Little),
+        bit_depth: ((h[4] >> 4) & 7) as u64 + 1,
+        color_type: "indexed",
+    })
+}
+
+/* DIB header; heights of top-down bitmaps are negative and reported as
+ * their absolute value */
+fn bmp_info<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<ImageInfo, Error<'x>> {
+    let size = read_region(stream, BMP_FILE_HEADER_SIZE, 4, xc)?;
+    let size = decode_at::<u32>(size.as_slice(), 0, Endianness::Little) as u64;
+    let h = read_region(stream, BMP_FILE_HEADER_SIZE, size.min(56), xc)?;
+    let h = h.as_slice();
+    let (width, height, bit_count) = if size == 12 {
+        (
+            decode_uint_at(h, 4, 2, Endianness::Little),
+            decode_uint_at(h, 6, 2, Endianness::Little),
+            decode_uint_at(h, 10, 2, Endianness::Little),
+        )
+    } else {
+        (
+            decode_uint_at(h, 4, 4, Endianness::Little),
+            decode_at::<i32>(h, 8, Endianness::Little).unsigned_abs() as u64,
+            decode_uint_at(h, 14, 2, Endianness::Little),
+        )
+    };
+    let alpha = size >= 56 && decode_uint_at(h, 52, 4, Endianness::Little) != 0;
+    Ok(ImageInfo {
+        format: ImageFormat::Bmp,
+        width,
+        height,
+        bit_depth: bit_count,
+        color_type: match bit_count {
+            1..=8 => "indexed",
+            _ if alpha => "rgba",
+            _ => "rgb",
+        },
+    })
+}
+
+pub fn image_info<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    ImageInfo::load(stream, xc)?.to_data_cell(xc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    fn info(data: &[u8]) -> ImageInfo {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        ImageInfo::load(&mut BufferAsROStream::new(data), &mut xc).unwrap()
+    }
+
+    #[test]
+    fn headers() {
+        let png = b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR\x00\x00\x01\x00\x00\x00\x00\x30\x08\x06\x00\x00\x00\x5C\x72\xA8\x66";
+        assert_eq!(info(png), ImageInfo { format: ImageFormat::Png, width: 256, height: 48, bit_depth: 8, color_type: "rgba" });
+        let gif = b"GIF89a\x0F\x00\x0D\x00\xA2\x00\x00";
+        assert_eq!(info(gif), ImageInfo { format: ImageFormat::Gif, width: 15, height: 13, bit_depth: 3, color_type: "indexed" });
+        // APP0, then SOF0 for a 3x2 grayscale image
+        let jpeg = b"\xFF\xD8\xFF\xE0\x00\x04JF\xFF\xFF\xC0\x00\x0B\x08\x00\x02\x00\x03\x01\x01\x11\x00";
+        assert_eq!(info(jpeg), ImageInfo { format: ImageFormat::Jpeg, width: 3, height: 2, bit_depth: 8, color_type: "grayscale" });
+        // top-down 24-bit bitmap
+        let mut bmp = [0_u8; 54];
+        bmp[0..2].copy_from_slice(b"BM");
+        bmp[14] = 40;
+        bmp[18] = 5;
+        bmp[22..26].copy_from_slice(&(-7_i32).to_le_bytes());
+        bmp[28] = 24;
+        assert_eq!(info(&bmp), ImageInfo { format: ImageFormat::Bmp, width: 5, height: 7, bit_depth: 24, color_type: "rgb" });
+        bmp[14] = 41;
+        assert_eq!(ImageFormat::from_magic(&bmp), None);
+    }
+
+    #[test]
+    fn errors_and_record() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(b"GIF8");
+        assert!(matches!(image_info(&mut s, &mut xc), Err(Error::NotApplicable)));
+        let mut s = BufferAsROStream::new(b"\xFF\xD8\xFF\xDA\x00\x02");
+        assert!(image_info(&mut s, &mut xc).is_err());
+        let mut s = BufferAsROStream::new(b"GIF87a\x02\x00\x01\x00\xF7");
+        let mut o = xc.byte_vector();
+        image_info(&mut s, &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), &b"image_info(format: gif, width: 2, height: 1, bit_depth: 8, color_type: indexed)"[..]);
+    }
+}
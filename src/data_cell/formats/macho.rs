@@ -0,0 +1,500 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::try_decode_at;
+use crate::conv::try_decode_uint_at;
+use crate::data_cell::DCOVector;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use crate::mm::Vector;
+use super::hex_cell;
+use super::read_region;
+
+pub const MH_MAGIC: u32 = 0xFEED_FACE;
+pub const MH_MAGIC_64: u32 = 0xFEED_FACF;
+pub const FAT_MAGIC: u32 = 0xCAFE_BABE;
+pub const FAT_MAGIC_64: u32 = 0xCAFE_BABF;
+pub const MACH_HEADER_SIZE: usize = 28;
+pub const MACH_HEADER_64_SIZE: usize = 32;
+/* java class files share FAT_MAGIC; their version numbers read as
+ * nfat_arch are way above this */
+pub const MAX_FAT_ARCH: u32 = 0x20;
+
+const LC_REQ_DYLD: u32 = 0x8000_0000;
+const LC_SEGMENT: u32 = 0x1;
+const LC_LOAD_DYLIB: u32 = 0xC;
+const LC_ID_DYLIB: u32 = 0xD;
+const LC_LOAD_DYLINKER: u32 = 0xE;
+const LC_ID_DYLINKER: u32 = 0xF;
+const LC_LOAD_WEAK_DYLIB: u32 = 0x18 | LC_REQ_DYLD;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_UUID: u32 = 0x1B;
+const LC_RPATH: u32 = 0x1C | LC_REQ_DYLD;
+const LC_REEXPORT_DYLIB: u32 = 0x1F | LC_REQ_DYLD;
+const LC_DYLD_ENVIRONMENT: u32 = 0x27;
+const LC_MAIN: u32 = 0x28 | LC_REQ_DYLD;
+
+const MACHO_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "macho_header",
+    &[
+        "magic", "endianness", "cputype", "cpusubtype", "filetype", "ncmds",
+        "sizeofcmds", "flags", "reserved",
+    ]);
+
+const MACHO_FAT_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "macho_fat_header",
+    &[ "magic", "nfat_arch", "slices" ]);
+
+const MACHO_FAT_ARCH: RecordDesc<'static> = RecordDesc::new(
+    "macho_fat_arch",
+    &[ "cputype", "cpusubtype", "offset", "size", "align", "header" ]);
+
+const MACHO_LOAD_COMMAND: RecordDesc<'static> = RecordDesc::new(
+    "macho_load_command",
+    &[
+        "slice", "offset", "cmd", "cmdsize", "name", "uuid", "vmaddr",
+        "vmsize", "fileoff", "filesize", "nsects", "entryoff", "stacksize",
+    ]);
+
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::InvalidData, msg))
+}
+
+fn cputype_cell<'x>(t: u64) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        1 => "CPU_TYPE_VAX",
+        6 => "CPU_TYPE_MC680x0",
+        7 => "CPU_TYPE_X86",
+        0x0100_0007 => "CPU_TYPE_X86_64",
+        10 => "CPU_TYPE_MC98000",
+        11 => "CPU_TYPE_HPPA",
+        12 => "CPU_TYPE_ARM",
+        0x0100_000C => "CPU_TYPE_ARM64",
+        0x0200_000C => "CPU_TYPE_ARM64_32",
+        13 => "CPU_TYPE_MC88000",
+        14 => "CPU_TYPE_SPARC",
+        15 => "CPU_TYPE_I860",
+        18 => "CPU_TYPE_POWERPC",
+        0x0100_0012 => "CPU_TYPE_POWERPC64",
+        _ => return hex_cell(t),
+    })
+}
+
+fn filetype_cell<'x>(t: u64) -> DataCell<'x> {
+    DataCell::from_static_id(match t {
+        1 => "MH_OBJECT",
+        2 => "MH_EXECUTE",
+        3 => "MH_FVMLIB",
+        4 => "MH_CORE",
+        5 => "MH_PRELOAD",
+        6 => "MH_DYLIB",
+        7 => "MH_DYLINKER",
+        8 => "MH_BUNDLE",
+        9 => "MH_DYLIB_STUB",
+        10 => "MH_DSYM",
+        11 => "MH_KEXT_BUNDLE",
+        12 => "MH_FILESET",
+        _ => return DataCell::from_u64(t),
+    })
+}
+
+fn cmd_cell<'x>(cmd: u32) -> DataCell<'x> {
+    DataCell::from_static_id(match cmd {
+        LC_SEGMENT => "LC_SEGMENT",
+        0x2 => "LC_SYMTAB",
+        0x3 => "LC_SYMSEG",
+        0x4 => "LC_THREAD",
+        0x5 => "LC_UNIXTHREAD",
+        0xB => "LC_DYSYMTAB",
+        LC_LOAD_DYLIB => "LC_LOAD_DYLIB",
+        LC_ID_DYLIB => "LC_ID_DYLIB",
+        LC_LOAD_DYLINKER => "LC_LOAD_DYLINKER",
+        LC_ID_DYLINKER => "LC_ID_DYLINKER",
+        0x10 => "LC_PREBOUND_DYLIB",
+        0x11 => "LC_ROUTINES",
+        0x16 => "LC_TWOLEVEL_HINTS",
+        LC_LOAD_WEAK_DYLIB => "LC_LOAD_WEAK_DYLIB",
+        LC_SEGMENT_64 => "LC_SEGMENT_64",
+        0x1A => "LC_ROUTINES_64",
+        LC_UUID => "LC_UUID",
+        LC_RPATH => "LC_RPATH",
+        0x1D => "LC_CODE_SIGNATURE",
+        0x1E => "LC_SEGMENT_SPLIT_INFO",
+        LC_REEXPORT_DYLIB => "LC_REEXPORT_DYLIB",
+        0x21 => "LC_ENCRYPTION_INFO",
+        0x22 => "LC_DYLD_INFO",
+        0x8000_0022 => "LC_DYLD_INFO_ONLY",
+        0x24 => "LC_VERSION_MIN_MACOSX",
+        0x25 => "LC_VERSION_MIN_IPHONEOS",
+        0x26 => "LC_FUNCTION_STARTS",
+        LC_DYLD_ENVIRONMENT => "LC_DYLD_ENVIRONMENT",
+        LC_MAIN => "LC_MAIN",
+        0x29 => "LC_DATA_IN_CODE",
+        0x2A => "LC_SOURCE_VERSION",
+        0x2B => "LC_DYLIB_CODE_SIGN_DRS",
+        0x2C => "LC_ENCRYPTION_INFO_64",
+        0x2D => "LC_LINKER_OPTION",
+        0x2F => "LC_VERSION_MIN_TVOS",
+        0x30 => "LC_VERSION_MIN_WATCHOS",
+        0x31 => "LC_NOTE",
+        0x32 => "LC_BUILD_VERSION",
+        0x8000_0033 => "LC_DYLD_EXPORTS_TRIE",
+        0x8000_0034 => "LC_DYLD_CHAINED_FIXUPS",
+        _ => return hex_cell(cmd as u64),
+    })
+}
+
+/* MachOLayout **************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MachOLayout {
+    pub is64: bool,
+    pub big_endian: bool,
+}
+
+impl MachOLayout {
+
+    /* thin images store the magic in their own byte order */
+    pub fn from_magic(data: &[u8]) -> Option<Self> {
+        let be = try_decode_at::<u32>(data, 0, Endianness::Big).ok()?;
+        let le = be.swap_bytes();
+        match (be, le) {
+            (MH_MAGIC, _) => Some(MachOLayout { is64: false, big_endian: true }),
+            (MH_MAGIC_64, _) => Some(MachOLayout { is64: true, big_endian: true }),
+            (_, MH_MAGIC) => Some(MachOLayout { is64: false, big_endian: false }),
+            (_, MH_MAGIC_64) => Some(MachOLayout { is64: true, big_endian: false }),
+            _ => None,
+        }
+    }
+
+    pub fn header_size(&self) -> usize {
+        if self.is64 { MACH_HEADER_64_SIZE } else { MACH_HEADER_SIZE }
+    }
+
+    pub fn uint(&self, data: &[u8], offset: usize, size: usize) -> Option<u64> {
+        let endianness = Endianness::from_big_endian_flag(self.big_endian);
+        try_decode_uint_at(data, offset, size, endianness).ok()
+    }
+
+    fn u32(&self, data: &[u8], offset: usize) -> u64 {
+        self.uint(data, offset, 4).unwrap_or(0)
+    }
+
+}
+
+/* true for the universal binary header, which is always big endian */
+pub fn is_fat_magic(data: &[u8]) -> bool {
+    let be = |o: usize| try_decode_at::<u32>(data, o, Endianness::Big).ok();
+    matches!(be(0), Some(FAT_MAGIC) | Some(FAT_MAGIC_64))
+        && be(4).is_some_and(|n| n <= MAX_FAT_ARCH)
+}
+
+/* FatArch ******************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FatArch {
+    pub cputype: u64,
+    pub cpusubtype: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub align: u64,
+}
+
+/* slices of a universal binary, None for other content */
+pub fn fat_archs<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<Option<(bool, Vector<'x, FatArch>)>, Error<'x>> {
+    let mut head = [0_u8; 8];
+    if stream.seek_read(0, &mut head, xc)? != head.len() || !is_fat_magic(&head) {
+        return Ok(None);
+    }
+    let be = MachOLayout { is64: false, big_endian: true };
+    let is64 = be.u32(&head, 0) == FAT_MAGIC_64 as u64;
+    let count = be.u32(&head, 4);
+    let entry_size: u64 = if is64 { 32 } else { 20 };
+    let table = read_region(stream, 8, count * entry_size, xc)?;
+    let mut archs: Vector<'x, FatArch> = Vector::new(xc.get_main_allocator());
+    for e in table.as_slice().chunks_exact(entry_size as usize) {
+        let arch = if is64 {
+            FatArch {
+                cputype: be.u32(e, 0),
+                cpusubtype: be.u32(e, 4),
+                offset: be.uint(e, 8, 8).unwrap(),
+                size: be.uint(e, 16, 8).unwrap(),
+                align: be.u32(e, 24),
+            }
+        } else {
+            FatArch {
+                cputype: be.u32(e, 0),
+                cpusubtype: be.u32(e, 4),
+                offset: be.u32(e, 8),
+                size: be.u32(e, 12),
+                align: be.u32(e, 16),
+            }
+        };
+        archs.push(arch).map_err(|(e, _)| e)?;
+    }
+    Ok(Some((is64, archs)))
+}
+
+/* MachOImage ***************************************************************/
+/* thin image starting at some offset of the content: the whole file or one
+ * slice of a universal binary */
+#[derive(Copy, Clone, Debug)]
+pub struct MachOImage {
+    pub offset: u64,
+    pub layout: MachOLayout,
+    header: [u8; MACH_HEADER_64_SIZE],
+}
+
+impl MachOImage {
+
+    pub fn load<'x, T: ?Sized + RandomAccessRead>(
+        stream: &mut T,
+        offset: u64,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Self, Error<'x>> {
+        let mut header = [0_u8; MACH_HEADER_64_SIZE];
+        let n = stream.seek_read(offset, &mut header, xc)?;
+        let layout = MachOLayout::from_magic(&header[0..n])
+            .ok_or(Error::NotApplicable)?;
+        if n < layout.header_size() {
+            return Err(invalid("truncated Mach-O header"));
+        }
+        Ok(MachOImage { offset, layout, header })
+    }
+
+    fn field(&self, offset: usize) -> u64 {
+        self.layout.u32(&self.header, offset)
+    }
+
+    pub fn ncmds(&self) -> u64 { self.field(16) }
+    pub fn sizeofcmds(&self) -> u64 { self.field(20) }
+
+    pub fn to_data_cell<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut r = Record::new(&MACHO_HEADER, xc.get_main_allocator())?;
+        r.set_field("magic", DataCell::from_static_id(
+                if self.layout.is64 { "MH_MAGIC_64" } else { "MH_MAGIC" }));
+        r.set_field("endianness", DataCell::from_static_id(
+                if self.layout.big_endian { "big" } else { "little" }));
+        r.set_field("cputype", cputype_cell(self.field(4)));
+        r.set_field("cpusubtype", hex_cell(self.field(8)));
+        r.set_field("filetype", filetype_cell(self.field(12)));
+        r.set_field("ncmds", DataCell::from_u64(self.ncmds()));
+        r.set_field("sizeofcmds", hex_cell(self.sizeofcmds()));
+        r.set_field("flags", hex_cell(self.field(24)));
+        if self.layout.is64 {
+            r.set_field("reserved", hex_cell(self.field(28)));
+        }
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+    /* appends a record for each load command; slice is set for images
+     * inside universal binaries */
+    pub fn load_commands<'x, T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        slice: Option<usize>,
+        out: &mut Vector<'x, DataCell<'x>>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        let l = self.layout;
+        let cmds_pos = self.offset + l.header_size() as u64;
+        let cmds = read_region(stream, cmds_pos, self.sizeofcmds(), xc)?;
+        let cmds = cmds.as_slice();
+        let a = xc.get_main_allocator();
+        let mut pos = 0_usize;
+        for _ in 0..self.ncmds() {
+            xc.check_cancelled()?;
+            if cmds.len() - pos < 8 {
+                return Err(invalid("load commands exceed sizeofcmds"));
+            }
+            let cmd = l.u32(cmds, pos) as u32;
+            let cmdsize = l.u32(cmds, pos + 4) as usize;
+            if cmdsize < 8 || cmdsize > cmds.len() - pos {
+                return Err(invalid("bad load command size"));
+            }
+            let c = &cmds[pos..pos + cmdsize];
+            let mut r = Record::new(&MACHO_LOAD_COMMAND, a)?;
+            if let Some(s) = slice {
+                r.set_field("slice", DataCell::from_u64(s as u64));
+            }
+            r.set_field("offset", hex_cell(cmds_pos + pos as u64));
+            r.set_field("cmd", cmd_cell(cmd));
+            r.set_field("cmdsize", DataCell::from_u64(cmdsize as u64));
+            match cmd {
+                LC_SEGMENT | LC_SEGMENT_64 if c.len() >= 24 => {
+                    let name_len = c[8..24].iter().position(|&b| b == 0).unwrap_or(16);
+                    r.set_field("name", DataCell::from_byte_slice(a, &c[8..8 + name_len])?);
+                    let w = if cmd == LC_SEGMENT_64 { 8 } else { 4 };
+                    let f = |i: usize| l.uint(c, 24 + i * w, w);
+                    if let (Some(vmaddr), Some(vmsize), Some(fileoff), Some(filesize)) = (f(0), f(1), f(2), f(3)) {
+                        r.set_field("vmaddr", hex_cell(vmaddr));
+                        r.set_field("vmsize", hex_cell(vmsize));
+                        r.set_field("fileoff", hex_cell(fileoff));
+                        r.set_field("filesize", hex_cell(filesize));
+                    }
+                    if let Some(n) = l.uint(c, 24 + 4 * w + 8, 4) {
+                        r.set_field("nsects", DataCell::from_u64(n));
+                    }
+                },
+                LC_LOAD_DYLIB | LC_ID_DYLIB | LC_LOAD_WEAK_DYLIB | LC_REEXPORT_DYLIB
+                | LC_LOAD_DYLINKER | LC_ID_DYLINKER | LC_RPATH | LC_DYLD_ENVIRONMENT => {
+                    /* lc_str: offset of a NUL terminated string inside the
+                     * command */
+                    let start = l.u32(c, 8) as usize;
+                    if let Some(s) = c.get(start..) {
+                        let len = s.iter().position(|&b| b == 0).unwrap_or(s.len());
+                        r.set_field("name", DataCell::from_byte_slice(a, &s[0..len])?);
+                    }
+                },
+                LC_UUID if c.len() >= 24 => {
+                    let mut text = [0_u8; 36];
+                    let mut n = 0;
+                    for (i, &b) in c[8..24].iter().enumerate() {
+                        if matches!(i, 4 | 6 | 8 | 10) {
+                            text[n] = b'-';
+                            n += 1;
+                        }
+                        text[n] = b"0123456789ABCDEF"[(b >> 4) as usize];
+                        text[n + 1] = b"0123456789ABCDEF"[(b & 15) as usize];
+                        n += 2;
+                    }
+                    r.set_field("uuid", DataCell::from_byte_slice(a, &text)?);
+                },
+                LC_MAIN if c.len() >= 24 => {
+                    r.set_field("entryoff", hex_cell(l.uint(c, 8, 8).unwrap()));
+                    r.set_field("stacksize", hex_cell(l.uint(c, 16, 8).unwrap()));
+                },
+                _ => {},
+            }
+            out.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+            pos += cmdsize;
+        }
+        Ok(())
+    }
+
+}
+
+/* record of a thin image header, or of the universal binary header with
+ * the header of each slice; NotApplicable for other content */
+pub fn macho_header<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let (is64, archs) = match fat_archs(stream, xc)? {
+        Some(fat) => fat,
+        None => return MachOImage::load(stream, 0, xc)?.to_data_cell(xc),
+    };
+    let a = xc.get_main_allocator();
+    let mut slices: Vector<'x, DataCell> = Vector::new(a);
+    for arch in archs.as_slice() {
+        xc.check_cancelled()?;
+        let mut r = Record::new(&MACHO_FAT_ARCH, a)?;
+        r.set_field("cputype", cputype_cell(arch.cputype));
+        r.set_field("cpusubtype", hex_cell(arch.cpusubtype));
+        r.set_field("offset", hex_cell(arch.offset));
+        r.set_field("size", hex_cell(arch.size));
+        r.set_field("align", DataCell::from_u64(arch.align));
+        match MachOImage::load(stream, arch.offset, xc) {
+            Ok(image) => r.set_field("header", image.to_data_cell(xc)?),
+            Err(Error::NotApplicable) => {},
+            Err(e) => return Err(e),
+        }
+        slices.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+    }
+    let mut r = Record::new(&MACHO_FAT_HEADER, a)?;
+    r.set_field("magic", DataCell::from_static_id(if is64 { "FAT_MAGIC_64" } else { "FAT_MAGIC" }));
+    r.set_field("nfat_arch", DataCell::from_u64(archs.len() as u64));
+    r.set_field("slices", DataCell::CellVector(xc.rc(RefCell::new(DCOVector(slices)))?));
+    Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+}
+
+/* load commands of a thin image, or of all Mach-O slices of a universal
+ * binary, in slice order */
+pub fn macho_load_commands<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut cmds: Vector<'x, DataCell> = Vector::new(xc.get_main_allocator());
+    match fat_archs(stream, xc)? {
+        Some((_, archs)) => {
+            for (i, arch) in archs.as_slice().iter().enumerate() {
+                match MachOImage::load(stream, arch.offset, xc) {
+                    Ok(image) => image.load_commands(stream, Some(i), &mut cmds, xc)?,
+                    Err(Error::NotApplicable) => {},
+                    Err(e) => return Err(e),
+                }
+            }
+        },
+        None => MachOImage::load(stream, 0, xc)?.load_commands(stream, None, &mut cmds, xc)?,
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(cmds)))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    // universal binary with one big endian PPC slice at 0x20
+    const FAT: &[u8] = b"\xCA\xFE\xBA\xBE\x00\x00\x00\x01\x00\x00\x00\x12\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x6C\x00\x00\x00\x05\x00\x00\x00\x00\xFE\xED\xFA\xCE\x00\x00\x00\x12\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x02\x00\x00\x00\x50\x00\x00\x00\x85\x00\x00\x00\x01\x00\x00\x00\x38\x5F\x5F\x54\x45\x58\x54\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x10\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x00\x6C\x00\x00\x00\x05\x00\x00\x00\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1B\x00\x00\x00\x18\xA0\xA1\xA2\xA3\xA4\xA5\xA6\xA7\xA8\xA9\xAA\xAB\xAC\xAD\xAE\xAF";
+
+    fn text<'x>(c: DataCell<'x>, xc: &mut ExecutionContext<'x>) -> Vector<'x, u8> {
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, xc).unwrap();
+        o
+    }
+
+    #[test]
+    fn magics() {
+        assert_eq!(MachOLayout::from_magic(b"\xCF\xFA\xED\xFE"), Some(MachOLayout { is64: true, big_endian: false }));
+        assert_eq!(MachOLayout::from_magic(b"\xFE\xED\xFA\xCE"), Some(MachOLayout { is64: false, big_endian: true }));
+        assert_eq!(MachOLayout::from_magic(b"\xFE\xED\xFA"), None);
+        assert!(is_fat_magic(b"\xCA\xFE\xBA\xBF\x00\x00\x00\x02"));
+        // java class file, version 52.0
+        assert!(!is_fat_magic(b"\xCA\xFE\xBA\xBE\x00\x00\x00\x34"));
+    }
+
+    #[test]
+    fn fat_slices_and_load_commands() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(FAT);
+        let h = macho_header(&mut s, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(h, &mut xc).as_slice()).unwrap(), concat!(
+            "macho_fat_header(magic: FAT_MAGIC, nfat_arch: 1, slices: [",
+            "macho_fat_arch(cputype: CPU_TYPE_POWERPC, cpusubtype: 0x00, offset: 0x20, ",
+            "size: 0x6C, align: 5, header: macho_header(magic: MH_MAGIC, endianness: big, ",
+            "cputype: CPU_TYPE_POWERPC, cpusubtype: 0x00, filetype: MH_EXECUTE, ncmds: 2, ",
+            "sizeofcmds: 0x50, flags: 0x85))])"));
+        let c = macho_load_commands(&mut s, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(c, &mut xc).as_slice()).unwrap(), concat!(
+            "[macho_load_command(slice: 0, offset: 0x3C, cmd: LC_SEGMENT, cmdsize: 56, ",
+            "name: b\"__TEXT\", vmaddr: 0x1000, vmsize: 0x1000, fileoff: 0x00, ",
+            "filesize: 0x6C, nsects: 0)",
+            "macho_load_command(slice: 0, offset: 0x74, cmd: LC_UUID, cmdsize: 24, ",
+            "uuid: b\"A0A1A2A3-A4A5-A6A7-A8A9-AAABACADAEAF\")]"));
+
+        let mut s = BufferAsROStream::new(&FAT[0x20..]);
+        let h = macho_header(&mut s, &mut xc).unwrap();
+        assert!(core::str::from_utf8(text(h, &mut xc).as_slice()).unwrap()
+                .starts_with("macho_header(magic: MH_MAGIC, endianness: big,"));
+        let mut s = BufferAsROStream::new(&FAT[0x20..FAT.len() - 1]);
+        assert!(macho_load_commands(&mut s, &mut xc).is_err());
+        let mut s = BufferAsROStream::new(&FAT[0x24..]);
+        assert_eq!(macho_header(&mut s, &mut xc).unwrap_err(), Error::NotApplicable);
+    }
+}
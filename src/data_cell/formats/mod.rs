@@ -0,0 +1,68 @@
+use crate::ExecutionContext;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::U64Cell;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::SeekFrom;
+use crate::mm::Vector;
+
+pub mod dwarf;
+pub mod elf;
+pub mod image;
+pub mod macho;
+pub mod pe;
+pub mod qcow;
+pub mod registry;
+pub mod signature;
+pub mod zip;
+
+/* numbers such as addresses, offsets and flags, shown in hex */
+pub(crate) fn hex_cell<'x>(n: u64) -> DataCell<'x> {
+    DataCell::from_u64_cell(U64Cell::hex(n))
+}
+
+/* up to len bytes of the stream starting at pos, appended to out; fewer
+ * are appended if the content ends earlier */
+pub(crate) fn read_available<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    pos: u64,
+    len: u64,
+    out: &mut Vector<'x, u8>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if let Some(content) = stream.content_slice() {
+        let start = core::cmp::min(pos, content.len() as u64) as usize;
+        let n = core::cmp::min(len, (content.len() - start) as u64) as usize;
+        out.append_from_slice(&content[start..start + n])?;
+        return Ok(());
+    }
+    stream.seek(SeekFrom::Start(pos), xc)?;
+    let mut buffer = [0_u8; 1024];
+    let mut done = 0_u64;
+    while done < len {
+        let chunk_len = core::cmp::min(len - done, buffer.len() as u64) as usize;
+        let n = stream.read_uninterrupted(&mut buffer[0..chunk_len], xc)?;
+        out.append_from_slice(&buffer[0..n])?;
+        if n < chunk_len { break; }
+        done += n as u64;
+    }
+    Ok(())
+}
+
+/* exactly len bytes of the stream starting at pos */
+pub(crate) fn read_region<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    pos: u64,
+    len: u64,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<Vector<'x, u8>, Error<'x>> {
+    let mut data: Vector<'x, u8> = Vector::new(xc.get_main_allocator());
+    read_available(stream, pos, len, &mut data, xc)?;
+    if (data.len() as u64) < len {
+        return Err(Error::IO(IOError::with_str(
+            IOErrorCode::UnexpectedEnd, "content truncated")));
+    }
+    Ok(data)
+}
@@ -0,0 +1,323 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::decode_uint_at;
+use crate::conv::try_decode_at;
+use crate::conv::try_decode_uint_at;
+use crate::data_cell::DCOVector;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::io::stream::RandomAccessRead;
+use crate::mm::Vector;
+use super::hex_cell;
+use super::read_region;
+
+pub const IMAGE_NT_SIGNATURE: &[u8; 4] = b"PE\0\0";
+pub const COFF_HEADER_SIZE: usize = 20;
+pub const SECTION_HEADER_SIZE: usize = 40;
+pub const PE32_MAGIC: u16 = 0x10B;
+pub const PE32_PLUS_MAGIC: u16 = 0x20B;
+const E_LFANEW_OFFSET: u64 = 0x3C;
+
+const PE_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "pe_header",
+    &[
+        "e_lfanew", "machine", "number_of_sections", "time_date_stamp",
+        "pointer_to_symbol_table", "number_of_symbols",
+        "size_of_optional_header", "characteristics",
+        "optional_header", "data_directories", "sections",
+    ]);
+
+const PE_OPTIONAL_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "pe_optional_header",
+    &[
+        "magic", "major_linker_version", "minor_linker_version",
+        "size_of_code", "size_of_initialized_data",
+        "size_of_uninitialized_data", "address_of_entry_point",
+        "base_of_code", "base_of_data", "image_base",
+        "section_alignment", "file_alignment",
+        "major_operating_system_version", "minor_operating_system_version",
+        "major_image_version", "minor_image_version",
+        "major_subsystem_version", "minor_subsystem_version",
+        "win32_version_value", "size_of_image", "size_of_headers",
+        "check_sum", "subsystem", "dll_characteristics",
+        "size_of_stack_reserve", "size_of_stack_commit",
+        "size_of_heap_reserve", "size_of_heap_commit",
+        "loader_flags", "number_of_rva_and_sizes",
+    ]);
+
+const PE_DATA_DIRECTORY: RecordDesc<'static> = RecordDesc::new(
+    "pe_data_directory",
+    &[ "name", "virtual_address", "size" ]);
+
+const PE_SECTION: RecordDesc<'static> = RecordDesc::new(
+    "pe_section",
+    &[
+        "name", "virtual_size", "virtual_address", "size_of_raw_data",
+        "pointer_to_raw_data", "pointer_to_relocations",
+        "pointer_to_linenumbers", "number_of_relocations",
+        "number_of_linenumbers", "characteristics",
+    ]);
+
+const DATA_DIRECTORY_NAMES: [&str; 16] = [
+    "export", "import", "resource", "exception", "security", "basereloc",
+    "debug", "architecture", "globalptr", "tls", "load_config",
+    "bound_import", "iat", "delay_import", "com_descriptor", "reserved",
+];
+
+fn machine_cell<'x>(m: u64) -> DataCell<'x> {
+    DataCell::from_static_id(match m {
+        0 => "IMAGE_FILE_MACHINE_UNKNOWN",
+        0x14C => "IMAGE_FILE_MACHINE_I386",
+        0x1C0 => "IMAGE_FILE_MACHINE_ARM",
+        0x1C4 => "IMAGE_FILE_MACHINE_ARMNT",
+        0x200 => "IMAGE_FILE_MACHINE_IA64",
+        0x5064 => "IMAGE_FILE_MACHINE_RISCV64",
+        0x8664 => "IMAGE_FILE_MACHINE_AMD64",
+        0xAA64 => "IMAGE_FILE_MACHINE_ARM64",
+        0xEBC => "IMAGE_FILE_MACHINE_EBC",
+        _ => return hex_cell(m),
+    })
+}
+
+fn subsystem_cell<'x>(s: u64) -> DataCell<'x> {
+    DataCell::from_static_id(match s {
+        0 => "IMAGE_SUBSYSTEM_UNKNOWN",
+        1 => "IMAGE_SUBSYSTEM_NATIVE",
+        2 => "IMAGE_SUBSYSTEM_WINDOWS_GUI",
+        3 => "IMAGE_SUBSYSTEM_WINDOWS_CUI",
+        5 => "IMAGE_SUBSYSTEM_OS2_CUI",
+        7 => "IMAGE_SUBSYSTEM_POSIX_CUI",
+        9 => "IMAGE_SUBSYSTEM_WINDOWS_CE_GUI",
+        10 => "IMAGE_SUBSYSTEM_EFI_APPLICATION",
+        11 => "IMAGE_SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER",
+        12 => "IMAGE_SUBSYSTEM_EFI_RUNTIME_DRIVER",
+        13 => "IMAGE_SUBSYSTEM_EFI_ROM",
+        14 => "IMAGE_SUBSYSTEM_XBOX",
+        16 => "IMAGE_SUBSYSTEM_WINDOWS_BOOT_APPLICATION",
+        _ => return DataCell::from_u64(s),
+    })
+}
+
+/* fields of the optional header that fit in its declared size; returns
+ * the record and the offset of the data directories, None for unknown
+ * magics */
+fn optional_header<'x>(
+    opt: &[u8],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(DataCell<'x>, Option<(usize, u64)>), Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut r = Record::new(&PE_OPTIONAL_HEADER, a)?;
+    let magic = try_decode_at::<u16>(opt, 0, Endianness::Little).ok();
+    if let Some(m) = magic {
+        r.set_field("magic", match m {
+            PE32_MAGIC => DataCell::from_static_id("PE32"),
+            PE32_PLUS_MAGIC => DataCell::from_static_id("PE32+"),
+            _ => hex_cell(m as u64),
+        });
+    }
+    let plus = match magic {
+        Some(PE32_MAGIC) => false,
+        Some(PE32_PLUS_MAGIC) => true,
+        _ => return Ok((DataCell::Record(xc.rc(RefCell::new(r))?), None)),
+    };
+    let w = if plus { 8 } else { 4 };
+    let set = |r: &mut Record<'x>, name, offset, size, cell: fn(u64) -> DataCell<'x>| {
+        if let Ok(v) = try_decode_uint_at(opt, offset, size, Endianness::Little) {
+            r.set_field(name, cell(v));
+        }
+    };
+    set(&mut r, "major_linker_version", 2, 1, DataCell::from_u64);
+    set(&mut r, "minor_linker_version", 3, 1, DataCell::from_u64);
+    set(&mut r, "size_of_code", 4, 4, hex_cell);
+    set(&mut r, "size_of_initialized_data", 8, 4, hex_cell);
+    set(&mut r, "size_of_uninitialized_data", 12, 4, hex_cell);
+    set(&mut r, "address_of_entry_point", 16, 4, hex_cell);
+    set(&mut r, "base_of_code", 20, 4, hex_cell);
+    if !plus {
+        set(&mut r, "base_of_data", 24, 4, hex_cell);
+    }
+    set(&mut r, "image_base", 32 - w, w, hex_cell);
+    set(&mut r, "section_alignment", 32, 4, hex_cell);
+    set(&mut r, "file_alignment", 36, 4, hex_cell);
+    set(&mut r, "major_operating_system_version", 40, 2, DataCell::from_u64);
+    set(&mut r, "minor_operating_system_version", 42, 2, DataCell::from_u64);
+    set(&mut r, "major_image_version", 44, 2, DataCell::from_u64);
+    set(&mut r, "minor_image_version", 46, 2, DataCell::from_u64);
+    set(&mut r, "major_subsystem_version", 48, 2, DataCell::from_u64);
+    set(&mut r, "minor_subsystem_version", 50, 2, DataCell::from_u64);
+    set(&mut r, "win32_version_value", 52, 4, DataCell::from_u64);
+    set(&mut r, "size_of_image", 56, 4, hex_cell);
+    set(&mut r, "size_of_headers", 60, 4, hex_cell);
+    set(&mut r, "check_sum", 64, 4, hex_cell);
+    set(&mut r, "subsystem", 68, 2, subsystem_cell);
+    set(&mut r, "dll_characteristics", 70, 2, hex_cell);
+    set(&mut r, "size_of_stack_reserve", 72, w, hex_cell);
+    set(&mut r, "size_of_stack_commit", 72 + w, w, hex_cell);
+    set(&mut r, "size_of_heap_reserve", 72 + 2 * w, w, hex_cell);
+    set(&mut r, "size_of_heap_commit", 72 + 3 * w, w, hex_cell);
+    let loader_flags = 72 + 4 * w;
+    set(&mut r, "loader_flags", loader_flags, 4, hex_cell);
+    set(&mut r, "number_of_rva_and_sizes", loader_flags + 4, 4, DataCell::from_u64);
+    let dirs = try_decode_uint_at(opt, loader_flags + 4, 4, Endianness::Little).ok().map(|n| (loader_flags + 8, n));
+    Ok((DataCell::Record(xc.rc(RefCell::new(r))?), dirs))
+}
+
+fn data_directories<'x>(
+    opt: &[u8],
+    offset: usize,
+    count: u64,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut dirs: Vector<'x, DataCell> = Vector::new(a);
+    let entries = opt.get(offset..).unwrap_or(&[]).chunks_exact(8);
+    for (i, d) in entries.take(core::cmp::min(count, 16) as usize).enumerate() {
+        let mut r = Record::new(&PE_DATA_DIRECTORY, a)?;
+        r.set_field("name", DataCell::from_static_id(DATA_DIRECTORY_NAMES[i]));
+        r.set_field("virtual_address", hex_cell(decode_uint_at(d, 0, 4, Endianness::Little)));
+        r.set_field("size", hex_cell(decode_uint_at(d, 4, 4, Endianness::Little)));
+        dirs.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(dirs)))?))
+}
+
+fn sections<'x>(
+    table: &[u8],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut sections: Vector<'x, DataCell> = Vector::new(a);
+    for s in table.chunks_exact(SECTION_HEADER_SIZE) {
+        xc.check_cancelled()?;
+        let mut r = Record::new(&PE_SECTION, a)?;
+        let name_len = s[0..8].iter().position(|&c| c == 0).unwrap_or(8);
+        r.set_field("name", DataCell::from_byte_slice(a, &s[0..name_len])?);
+        r.set_field("virtual_size", hex_cell(decode_uint_at(s, 8, 4, Endianness::Little)));
+        r.set_field("virtual_address", hex_cell(decode_uint_at(s, 12, 4, Endianness::Little)));
+        r.set_field("size_of_raw_data", hex_cell(decode_uint_at(s, 16, 4, Endianness::Little)));
+        r.set_field("pointer_to_raw_data", hex_cell(decode_uint_at(s, 20, 4, Endianness::Little)));
+        r.set_field("pointer_to_relocations", hex_cell(decode_uint_at(s, 24, 4, Endianness::Little)));
+        r.set_field("pointer_to_linenumbers", hex_cell(decode_uint_at(s, 28, 4, Endianness::Little)));
+        r.set_field("number_of_relocations", DataCell::from_u64(decode_uint_at(s, 32, 2, Endianness::Little)));
+        r.set_field("number_of_linenumbers", DataCell::from_u64(decode_uint_at(s, 34, 2, Endianness::Little)));
+        r.set_field("characteristics", hex_cell(decode_uint_at(s, 36, 4, Endianness::Little)));
+        sections.push(DataCell::Record(xc.rc(RefCell::new(r))?))?;
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(sections)))?))
+}
+
+/* headers of a PE image found through e_lfanew of its DOS header;
+ * NotApplicable for content without the MZ and PE signatures */
+pub fn pe_header<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut dos = [0_u8; E_LFANEW_OFFSET as usize + 4];
+    if stream.seek_read(0, &mut dos, xc)? != dos.len() || !dos.starts_with(b"MZ") {
+        return Err(Error::NotApplicable);
+    }
+    let e_lfanew = decode_uint_at(&dos, E_LFANEW_OFFSET as usize, 4, Endianness::Little);
+    let mut nt = [0_u8; 4 + COFF_HEADER_SIZE];
+    if stream.seek_read(e_lfanew, &mut nt, xc)? != nt.len() || !nt.starts_with(IMAGE_NT_SIGNATURE) {
+        return Err(Error::NotApplicable);
+    }
+    let coff = &nt[4..];
+    let section_count = decode_uint_at(coff, 2, 2, Endianness::Little);
+    let opt_size = decode_uint_at(coff, 16, 2, Endianness::Little);
+    let opt_pos = e_lfanew + nt.len() as u64;
+    let opt = read_region(stream, opt_pos, opt_size, xc)?;
+    let table = read_region(stream, opt_pos + opt_size,
+                            section_count * SECTION_HEADER_SIZE as u64, xc)?;
+
+    let a = xc.get_main_allocator();
+    let mut r = Record::new(&PE_HEADER, a)?;
+    r.set_field("e_lfanew", hex_cell(e_lfanew));
+    r.set_field("machine", machine_cell(decode_uint_at(coff, 0, 2, Endianness::Little)));
+    r.set_field("number_of_sections", DataCell::from_u64(section_count));
+    r.set_field("time_date_stamp", DataCell::from_u64(decode_uint_at(coff, 4, 4, Endianness::Little)));
+    r.set_field("pointer_to_symbol_table", hex_cell(decode_uint_at(coff, 8, 4, Endianness::Little)));
+    r.set_field("number_of_symbols", DataCell::from_u64(decode_uint_at(coff, 12, 4, Endianness::Little)));
+    r.set_field("size_of_optional_header", hex_cell(opt_size));
+    r.set_field("characteristics", hex_cell(decode_uint_at(coff, 18, 2, Endianness::Little)));
+    if opt_size != 0 {
+        let (opt_header, dirs) = optional_header(opt.as_slice(), xc)?;
+        r.set_field("optional_header", opt_header);
+        if let Some((offset, count)) = dirs {
+            r.set_field("data_directories", data_directories(opt.as_slice(), offset, count, xc)?);
+        }
+    }
+    r.set_field("sections", sections(table.as_slice(), xc)?);
+    Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    // PE32 headers with two data directories and one section
+    const PE32: &[u8] = b"\x4D\x5A\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x50\x45\x00\x00\x4C\x01\x01\x00\x00\xF1\x53\x65\x00\x00\x00\x00\x00\x00\x00\x00\x70\x00\x02\x01\x0B\x01\x02\x26\x00\x02\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x10\x00\x00\x00\x10\x00\x00\x00\x20\x00\x00\x00\x00\x40\x00\x00\x10\x00\x00\x00\x02\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x05\x00\x02\x00\x00\x00\x00\x00\x00\x30\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x03\x00\x40\x01\x00\x00\x20\x00\x00\x10\x00\x00\x00\x00\x10\x00\x00\x10\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x28\x00\x00\x00\x2E\x74\x65\x78\x74\x00\x00\x00\x10\x00\x00\x00\x00\x10\x00\x00\x00\x02\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x60";
+
+    #[test]
+    fn pe32_headers() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(PE32);
+        let mut o = xc.byte_vector();
+        pe_header(&mut s, &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "pe_header(e_lfanew: 0x40, machine: IMAGE_FILE_MACHINE_I386, number_of_sections: 1, ",
+            "time_date_stamp: 1700000000, pointer_to_symbol_table: 0x00, number_of_symbols: 0, ",
+            "size_of_optional_header: 0x70, characteristics: 0x102, ",
+            "optional_header: pe_optional_header(magic: PE32, major_linker_version: 2, ",
+            "minor_linker_version: 38, size_of_code: 0x200, size_of_initialized_data: 0x400, ",
+            "size_of_uninitialized_data: 0x00, address_of_entry_point: 0x1000, ",
+            "base_of_code: 0x1000, base_of_data: 0x2000, image_base: 0x400000, ",
+            "section_alignment: 0x1000, file_alignment: 0x200, ",
+            "major_operating_system_version: 4, minor_operating_system_version: 0, ",
+            "major_image_version: 0, minor_image_version: 0, major_subsystem_version: 5, ",
+            "minor_subsystem_version: 2, win32_version_value: 0, size_of_image: 0x3000, ",
+            "size_of_headers: 0x200, check_sum: 0x00, subsystem: IMAGE_SUBSYSTEM_WINDOWS_CUI, ",
+            "dll_characteristics: 0x140, size_of_stack_reserve: 0x200000, ",
+            "size_of_stack_commit: 0x1000, size_of_heap_reserve: 0x100000, ",
+            "size_of_heap_commit: 0x1000, loader_flags: 0x00, number_of_rva_and_sizes: 2), ",
+            "data_directories: [pe_data_directory(name: export, virtual_address: 0x00, size: 0x00)",
+            "pe_data_directory(name: import, virtual_address: 0x2000, size: 0x28)], ",
+            "sections: [pe_section(name: b\".text\", virtual_size: 0x10, virtual_address: 0x1000, ",
+            "size_of_raw_data: 0x200, pointer_to_raw_data: 0x200, pointer_to_relocations: 0x00, ",
+            "pointer_to_linenumbers: 0x00, number_of_relocations: 0, number_of_linenumbers: 0, ",
+            "characteristics: 0x60000020)])"));
+
+        let mut s = BufferAsROStream::new(&PE32[0..PE32.len() - 1]);
+        assert!(pe_header(&mut s, &mut xc).is_err());
+        let mut dos = [0_u8; 0x80];
+        dos[0..0x40].copy_from_slice(&PE32[0..0x40]);
+        dos[0x40..0x44].copy_from_slice(b"NE\0\0");
+        let mut s = BufferAsROStream::new(&dos);
+        assert_eq!(pe_header(&mut s, &mut xc).unwrap_err(), Error::NotApplicable);
+    }
+
+    #[test]
+    fn section_loop_checks_cancellation() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let token = crate::CancellationToken::new();
+        xc.set_cancellation_token(Some(&token));
+        let table = [0_u8; SECTION_HEADER_SIZE * 2];
+        assert!(sections(&table, &mut xc).is_ok());
+        token.cancel();
+        match sections(&table, &mut xc) {
+            Err(Error::IO(e)) => assert_eq!(e.get_error_code(), crate::io::ErrorCode::Cancelled),
+            _ => panic!("section loop ignored cancellation"),
+        };
+    }
+}
@@ -0,0 +1,191 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::decode_uint_at;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use super::hex_cell;
+use super::read_region;
+
+pub const QCOW_MAGIC: &[u8; 4] = b"QFI\xFB";
+pub const QCOW1_HEADER_SIZE: usize = 48;
+pub const QCOW2_HEADER_SIZE: usize = 72;
+pub const QCOW3_HEADER_SIZE: usize = 104;
+const BACKING_FILE_NAME_MAX: u64 = 1023;
+
+const QCOW_HEADER: RecordDesc<'static> = RecordDesc::new(
+    "qcow_header",
+    &[
+        "version", "backing_file_offset", "backing_file_size",
+        "backing_file", "mtime", "size", "cluster_bits", "l2_bits",
+        "crypt_method", "l1_size", "l1_table_offset",
+        "refcount_table_offset", "refcount_table_clusters",
+        "nb_snapshots", "snapshots_offset", "incompatible_features",
+        "compatible_features", "autoclear_features", "refcount_order",
+        "header_length", "compression_type",
+    ]);
+
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::InvalidData, msg))
+}
+
+fn crypt_method_cell<'x>(m: u64) -> DataCell<'x> {
+    match m {
+        0 => DataCell::from_static_id("none"),
+        1 => DataCell::from_static_id("aes"),
+        2 => DataCell::from_static_id("luks"),
+        _ => DataCell::from_u64(m),
+    }
+}
+
+/* version 1 places cluster_bits and the L2 size after the image size, while
+ * later versions keep a 32-bit cluster_bits where version 1 has mtime;
+ * version 3 appends feature bitmaps and the header length */
+pub fn qcow_header<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut id = [0_u8; 8];
+    if stream.seek_read(0, &mut id, xc)? != id.len() || !id.starts_with(QCOW_MAGIC) {
+        return Err(Error::NotApplicable);
+    }
+    let version = decode_uint_at(&id, 4, 4, Endianness::Big);
+    let size = match version {
+        1 => QCOW1_HEADER_SIZE,
+        2 => QCOW2_HEADER_SIZE,
+        3 => QCOW3_HEADER_SIZE,
+        _ => return Err(invalid("unsupported qcow version")),
+    };
+    let h = read_region(stream, 0, size as u64, xc)?;
+    let h = h.as_slice();
+
+    let a = xc.get_main_allocator();
+    let mut r = Record::new(&QCOW_HEADER, a)?;
+    let backing_file_offset = decode_uint_at(h, 8, 8, Endianness::Big);
+    let backing_file_size = decode_uint_at(h, 16, 4, Endianness::Big);
+    r.set_field("version", DataCell::from_u64(version));
+    r.set_field("backing_file_offset", hex_cell(backing_file_offset));
+    r.set_field("backing_file_size", DataCell::from_u64(backing_file_size));
+    if backing_file_offset != 0 && backing_file_size != 0 {
+        if backing_file_size > BACKING_FILE_NAME_MAX {
+            return Err(invalid("qcow backing file name too long"));
+        }
+        let name = read_region(stream, backing_file_offset, backing_file_size, xc)?;
+        r.set_field("backing_file", DataCell::from_byte_slice(a, name.as_slice())?);
+    }
+    if version == 1 {
+        r.set_field("mtime", DataCell::from_u64(decode_uint_at(h, 20, 4, Endianness::Big)));
+        r.set_field("size", DataCell::from_u64(decode_uint_at(h, 24, 8, Endianness::Big)));
+        r.set_field("cluster_bits", DataCell::from_u64(decode_uint_at(h, 32, 1, Endianness::Big)));
+        r.set_field("l2_bits", DataCell::from_u64(decode_uint_at(h, 33, 1, Endianness::Big)));
+        r.set_field("crypt_method", crypt_method_cell(decode_uint_at(h, 36, 4, Endianness::Big)));
+        r.set_field("l1_table_offset", hex_cell(decode_uint_at(h, 40, 8, Endianness::Big)));
+    } else {
+        r.set_field("cluster_bits", DataCell::from_u64(decode_uint_at(h, 20, 4, Endianness::Big)));
+        r.set_field("size", DataCell::from_u64(decode_uint_at(h, 24, 8, Endianness::Big)));
+        r.set_field("crypt_method", crypt_method_cell(decode_uint_at(h, 32, 4, Endianness::Big)));
+        r.set_field("l1_size", DataCell::from_u64(decode_uint_at(h, 36, 4, Endianness::Big)));
+        r.set_field("l1_table_offset", hex_cell(decode_uint_at(h, 40, 8, Endianness::Big)));
+        r.set_field("refcount_table_offset", hex_cell(decode_uint_at(h, 48, 8, Endianness::Big)));
+        r.set_field("refcount_table_clusters", DataCell::from_u64(decode_uint_at(h, 56, 4, Endianness::Big)));
+        r.set_field("nb_snapshots", DataCell::from_u64(decode_uint_at(h, 60, 4, Endianness::Big)));
+        r.set_field("snapshots_offset", hex_cell(decode_uint_at(h, 64, 8, Endianness::Big)));
+    }
+    if version >= 3 {
+        let header_length = decode_uint_at(h, 100, 4, Endianness::Big);
+        r.set_field("incompatible_features", hex_cell(decode_uint_at(h, 72, 8, Endianness::Big)));
+        r.set_field("compatible_features", hex_cell(decode_uint_at(h, 80, 8, Endianness::Big)));
+        r.set_field("autoclear_features", hex_cell(decode_uint_at(h, 88, 8, Endianness::Big)));
+        r.set_field("refcount_order", DataCell::from_u64(decode_uint_at(h, 96, 4, Endianness::Big)));
+        r.set_field("header_length", DataCell::from_u64(header_length));
+        if header_length > QCOW3_HEADER_SIZE as u64 {
+            let ct = read_region(stream, QCOW3_HEADER_SIZE as u64, 1, xc)?;
+            r.set_field("compression_type", DataCell::from_static_id(
+                match ct.as_slice()[0] {
+                    0 => "zlib",
+                    1 => "zstd",
+                    _ => "unknown",
+                }));
+        }
+    }
+    Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::Vector;
+
+    fn text<'x>(data: &[u8], xc: &mut ExecutionContext<'x>) -> Vector<'x, u8> {
+        let mut s = BufferAsROStream::new(data);
+        let h = qcow_header(&mut s, xc).unwrap();
+        let mut o = xc.byte_vector();
+        h.output_as_human_readable(&mut o, xc).unwrap();
+        o
+    }
+
+    #[test]
+    fn versions() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+
+        let mut v1 = [0_u8; 64];
+        v1[0..8].copy_from_slice(b"QFI\xFB\x00\x00\x00\x01");
+        v1[15] = 48; // backing file name right after the header
+        v1[19] = 5;
+        v1[20..24].copy_from_slice(&0x5F5E1000_u32.to_be_bytes());
+        v1[29] = 0x10; // 1 MiB
+        v1[32] = 12;
+        v1[33] = 9;
+        v1[39] = 1;
+        v1[46] = 0x10;
+        v1[48..53].copy_from_slice(b"a.img");
+        assert_eq!(core::str::from_utf8(text(&v1, &mut xc).as_slice()).unwrap(), concat!(
+            "qcow_header(version: 1, backing_file_offset: 0x30, backing_file_size: 5, ",
+            "backing_file: b\"a.img\", mtime: 1600000000, size: 1048576, cluster_bits: 12, ",
+            "l2_bits: 9, crypt_method: aes, l1_table_offset: 0x1000)"));
+
+        // qcow3 with the header length of recent qemu, zstd compressed
+        let mut v3 = [0_u8; 112];
+        v3[0..8].copy_from_slice(b"QFI\xFB\x00\x00\x00\x03");
+        v3[23] = 16;
+        v3[28] = 0x40; // 1 GiB
+        v3[39] = 2;
+        v3[45] = 0x03;
+        v3[53] = 0x01;
+        v3[59] = 1;
+        v3[63] = 2;
+        v3[69] = 0x05;
+        v3[79] = 0x08;
+        v3[95] = 0x01;
+        v3[99] = 4;
+        v3[103] = 112;
+        v3[104] = 1;
+        assert_eq!(core::str::from_utf8(text(&v3, &mut xc).as_slice()).unwrap(), concat!(
+            "qcow_header(version: 3, backing_file_offset: 0x00, backing_file_size: 0, ",
+            "size: 1073741824, cluster_bits: 16, crypt_method: none, l1_size: 2, ",
+            "l1_table_offset: 0x30000, refcount_table_offset: 0x10000, ",
+            "refcount_table_clusters: 1, nb_snapshots: 2, snapshots_offset: 0x50000, ",
+            "incompatible_features: 0x08, compatible_features: 0x00, ",
+            "autoclear_features: 0x01, refcount_order: 4, header_length: 112, ",
+            "compression_type: zstd)"));
+
+        let mut s = BufferAsROStream::new(&v3[0..80]);
+        assert!(qcow_header(&mut s, &mut xc).is_err());
+        let mut s = BufferAsROStream::new(b"QFI\xFB\x00\x00\x00\x04");
+        assert!(qcow_header(&mut s, &mut xc).is_err());
+        let mut s = BufferAsROStream::new(b"\x7FELF\x02\x01\x01\x00");
+        assert!(matches!(qcow_header(&mut s, &mut xc), Err(Error::NotApplicable)));
+    }
+}
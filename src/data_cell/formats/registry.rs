@@ -0,0 +1,473 @@
+use crate::ExecutionContext;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::compressed;
+use crate::conv::ascii_eq_ignore_case;
+use crate::conv::int_be_decode;
+use crate::conv::int_le_decode;
+use crate::io::stream::RandomAccessRead;
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::Trie;
+use crate::mm::Vector;
+use super::image::ImageFormat;
+use super::macho;
+use super::qcow;
+use super::read_available;
+use super::signature::DEEP_SIGNATURES;
+use super::signature::Signature;
+use super::signature::identify_signatures;
+
+/* top of file bytes read when no registered format needs more */
+pub const DEFAULT_TOF_LEN: usize = 0x40;
+/* registered magics must end within this many bytes */
+pub const MAX_TOF_LEN: usize = 0x1000;
+
+/* TofMatch *****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TofMatch {
+    Rejected,
+    Matched,
+    Refined(&'static str), // matched, with a more specific id after the entry id
+}
+
+/* checks the top of file once the magic matched */
+pub type TofParser = fn(tof: &[u8]) -> TofMatch;
+
+/* FormatEntry **************************************************************/
+#[derive(Copy, Clone, Debug)]
+pub struct FormatEntry {
+    pub magic: &'static [u8],
+    pub offset: usize,
+    pub id: &'static str,
+    pub parser: Option<TofParser>,
+}
+
+impl FormatEntry {
+
+    pub const fn new(magic: &'static [u8], id: &'static str) -> Self {
+        FormatEntry { magic, offset: 0, id, parser: None }
+    }
+
+    pub const fn parsed(magic: &'static [u8], id: &'static str, parser: TofParser) -> Self {
+        FormatEntry { magic, offset: 0, id, parser: Some(parser) }
+    }
+
+    pub fn check(&self, tof: &[u8]) -> TofMatch {
+        let magic_found = tof.get(self.offset..)
+            .is_some_and(|t| t.starts_with(self.magic));
+        match (magic_found, self.parser) {
+            (false, _) => TofMatch::Rejected,
+            (true, None) => TofMatch::Matched,
+            (true, Some(p)) => p(tof),
+        }
+    }
+
+    /* appends the ids and returns true on a match */
+    pub fn push_ids<'x>(
+        &self,
+        tof: &[u8],
+        ids: &mut Vector<'x, DataCell<'x>>,
+    ) -> Result<bool, Error<'x>> {
+        match self.check(tof) {
+            TofMatch::Rejected => return Ok(false),
+            TofMatch::Matched => ids.push(DataCell::StaticId(self.id))?,
+            TofMatch::Refined(sub_id) => {
+                ids.push(DataCell::StaticId(self.id))?;
+                ids.push(DataCell::StaticId(sub_id))?;
+            },
+        }
+        Ok(true)
+    }
+
+}
+
+fn empty(tof: &[u8]) -> TofMatch {
+    if tof.is_empty() { TofMatch::Matched } else { TofMatch::Rejected }
+}
+
+fn macho_layout(tof: &[u8]) -> TofMatch {
+    match macho::MachOLayout::from_magic(tof) {
+        Some(l) => TofMatch::Refined(if l.is64 { "macho64" } else { "macho32" }),
+        None => TofMatch::Rejected,
+    }
+}
+
+fn macho_fat(tof: &[u8]) -> TofMatch {
+    if macho::is_fat_magic(tof) { TofMatch::Matched } else { TofMatch::Rejected }
+}
+
+fn dos_exe_zm(_tof: &[u8]) -> TofMatch {
+    TofMatch::Refined("dos_exe_zm")
+}
+
+fn qcow_version(tof: &[u8]) -> TofMatch {
+    match tof.get(4..8).and_then(int_be_decode::<u32>) {
+        Some(1) => TofMatch::Refined("qcow1"),
+        Some(2) => TofMatch::Refined("qcow2"),
+        Some(3) => TofMatch::Refined("qcow3"),
+        _ => TofMatch::Matched,
+    }
+}
+
+fn image_format(tof: &[u8]) -> TofMatch {
+    match ImageFormat::from_magic(tof) {
+        Some(f) => TofMatch::Refined(f.id()),
+        None => TofMatch::Rejected,
+    }
+}
+
+fn zstd_skippable(tof: &[u8]) -> TofMatch {
+    if int_le_decode::<u32>(tof).is_some_and(compressed::is_zstd_skippable_magic) {
+        TofMatch::Matched
+    } else {
+        TofMatch::Rejected
+    }
+}
+
+/* checked in order, the first match wins */
+pub const BUILTIN_FORMATS: &[FormatEntry] = &[
+    FormatEntry::parsed(b"", "empty", empty),
+    FormatEntry::new(b"PK", "zip_record"),
+    FormatEntry::new(b"#!", "shebang"),
+    FormatEntry::new(b"\x7FELF", "elf"),
+    FormatEntry::parsed(b"", "macho", macho_layout),
+    FormatEntry::parsed(b"", "macho_fat", macho_fat),
+    FormatEntry::new(b"MZ", "dos_exe"),
+    FormatEntry::parsed(b"ZM", "dos_exe", dos_exe_zm),
+    FormatEntry::new(b"\x1F\x8B", "gzip"),
+    FormatEntry::new(b"BZh", "bzip2"),
+    FormatEntry::new(b"\xFD7zXZ\x00", "xz"),
+    FormatEntry::new(b"7z\xBC\xAF\x27\x1C", "seven_zip"),
+    FormatEntry::new(b"!<arch>\n", "ar"),
+    FormatEntry::new(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "ms_cfb"),
+    FormatEntry::parsed(qcow::QCOW_MAGIC, "qcow", qcow_version),
+    FormatEntry::new(b"SQLite format 3\x00", "sqlite3"),
+    FormatEntry::new(b"qres\x00\x00\x00\x01", "qt_rcc"),
+    FormatEntry::parsed(b"", "image", image_format),
+    FormatEntry::new(&compressed::ZSTD_MAGIC.to_le_bytes(), "zstd"),
+    FormatEntry::parsed(b"", "zstd_skippable", zstd_skippable),
+    FormatEntry::new(&compressed::LZ4_FRAME_MAGIC.to_le_bytes(), "lz4"),
+    FormatEntry::new(&compressed::LZ4_LEGACY_MAGIC.to_le_bytes(), "lz4_legacy"),
+];
+
+/* EntryRef *****************************************************************/
+/* registered entries come before the built-in ones */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum EntryRef {
+    Registered(usize),
+    Builtin(usize),
+}
+
+/* FormatRegistry ***********************************************************/
+/* formats identified from the content, in 2 steps:
+ * - from the top of file: the registered entries, in order of registration,
+ *   then the built-in ones; the first match wins, so registered formats can
+ *   claim content that a built-in one would take (for instance containers
+ *   based on zip)
+ * - from signatures, which can be anywhere in the content: the built-in
+ *   ones then the registered ones, each match adding its id
+ * entries with the magic at offset 0 are found through a trie keyed by the
+ * magic, so only those whose magic starts the content get checked; when the
+ * allocator cannot hold the trie (an empty registry needs no allocation),
+ * all entries are checked in order */
+#[derive(Debug)]
+pub struct FormatRegistry<'a> {
+    entries: Vector<'a, FormatEntry>,
+    signatures: Vector<'a, Signature>,
+    magics: Trie<'a, Vector<'a, EntryRef>>, // entries with the same magic in order
+    far_entries: Vector<'a, EntryRef>, // entries with the magic past offset 0
+    indexed: bool,
+    tof_len: usize,
+}
+
+impl<'a> FormatRegistry<'a> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        let mut r = FormatRegistry {
+            entries: Vector::new(allocator),
+            signatures: Vector::new(allocator),
+            magics: Trie::new(allocator),
+            far_entries: Vector::new(allocator),
+            indexed: true,
+            tof_len: DEFAULT_TOF_LEN,
+        };
+        let magic_bytes = BUILTIN_FORMATS.iter().map(|e| e.magic.len()).sum();
+        r.indexed = r.magics.reserve(magic_bytes).is_ok();
+        for index in 0..BUILTIN_FORMATS.len() {
+            r.index_entry(EntryRef::Builtin(index));
+        }
+        r
+    }
+
+    fn entry(&self, entry_ref: EntryRef) -> &FormatEntry {
+        match entry_ref {
+            EntryRef::Registered(index) => &self.entries.as_slice()[index],
+            EntryRef::Builtin(index) => &BUILTIN_FORMATS[index],
+        }
+    }
+
+    /* each magic keeps its entries in order; the index is dropped on
+     * allocation failure */
+    fn index_entry(&mut self, entry_ref: EntryRef) {
+        if !self.indexed {
+            return;
+        }
+        let e = *self.entry(entry_ref);
+        let added = if e.offset != 0 {
+            self.far_entries.push(entry_ref).map_err(|(e, _)| e)
+        } else if let Some(refs) = self.magics.get_mut(e.magic) {
+            let at = refs.as_slice().partition_point(|r| *r < entry_ref);
+            refs.insert(at, entry_ref).map_err(|(e, _)| e)
+        } else {
+            let mut refs = Vector::new(self.entries.allocator());
+            refs.push(entry_ref).map_err(|(e, _)| e)
+                .and_then(|_| self.magics.insert(e.magic, refs).map(|_| ()).map_err(|(e, _)| e))
+        };
+        if added.is_err() {
+            self.indexed = false;
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        magic: &'static [u8],
+        offset: usize,
+        id: &'static str,
+        parser: Option<TofParser>,
+    ) -> Result<(), AllocError> {
+        let end = offset.checked_add(magic.len())
+            .filter(|&end| end <= MAX_TOF_LEN)
+            .ok_or(AllocError::UnsupportedSize)?;
+        let id = self.find_id(id).unwrap_or(id);
+        self.entries.push(FormatEntry { magic, offset, id, parser })
+            .map_err(|(e, _)| e)?;
+        self.index_entry(EntryRef::Registered(self.entries.len() - 1));
+        self.tof_len = core::cmp::max(self.tof_len, end);
+        Ok(())
+    }
+
+    /* formats recognized further than MAX_TOF_LEN or at one of several
+     * positions (see signature::Probe) */
+    pub fn register_signature(&mut self, mut signature: Signature) -> Result<(), AllocError> {
+        signature.id = self.find_id(signature.id).unwrap_or(signature.id);
+        if self.signatures.is_empty() {
+            self.signatures.append_from_slice(DEEP_SIGNATURES)?;
+        }
+        self.signatures.push(signature).map_err(|(e, _)| e)
+    }
+
+    /* number of top of file bytes needed by the entries */
+    pub fn tof_len(&self) -> usize {
+        self.tof_len
+    }
+
+    pub fn identify<'x>(
+        &self,
+        tof: &[u8],
+        ids: &mut Vector<'x, DataCell<'x>>,
+    ) -> Result<(), Error<'x>> {
+        if !self.indexed {
+            for e in self.entries.as_slice().iter().chain(BUILTIN_FORMATS) {
+                if e.push_ids(tof, ids)? {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+        /* candidates are the entries of each magic starting the content and
+         * the far ones, checked in order until one matches */
+        let mut tried: Option<EntryRef> = None;
+        loop {
+            let untried = |r: &&EntryRef| tried.is_none_or(|t| **r > t);
+            let next = self.magics.prefix_matches(tof)
+                .filter_map(|(_, refs)| refs.as_slice().iter().find(untried))
+                .chain(self.far_entries.as_slice().iter().find(untried))
+                .min();
+            let entry_ref = match next {
+                Some(r) => *r,
+                None => return Ok(()),
+            };
+            if self.entry(entry_ref).push_ids(tof, ids)? {
+                return Ok(());
+            }
+            tried = Some(entry_ref);
+        }
+    }
+
+    /* appends the id of the top of file entry that matches first, then
+     * those of the matching signatures */
+    pub fn identify_content<'x, T: ?Sized + RandomAccessRead>(
+        &self,
+        stream: &mut T,
+        ids: &mut Vector<'x, DataCell<'x>>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        let mut tof: Vector<'x, u8> = Vector::new(xc.get_main_allocator());
+        read_available(stream, 0, self.tof_len as u64, &mut tof, xc)?;
+        self.identify(tof.as_slice(), ids)?;
+        identify_signatures(self.signatures(), stream, ids, xc)
+    }
+
+    /* the spelling of a known format id, matched ignoring ASCII case */
+    pub fn find_id(&self, id: &str) -> Option<&'static str> {
+        let entry_ids = self.entries.as_slice().iter().chain(BUILTIN_FORMATS).map(|e| e.id);
+        entry_ids.chain(self.signatures().iter().map(|s| s.id))
+            .find(|known| ascii_eq_ignore_case(known.as_bytes(), id.as_bytes()))
+    }
+
+    fn signatures(&self) -> &[Signature] {
+        if self.signatures.is_empty() {
+            DEEP_SIGNATURES
+        } else {
+            self.signatures.as_slice()
+        }
+    }
+
+    /* how far identify_content reads into content of the given length */
+    pub fn identified_len(&self, content_len: u64) -> u64 {
+        self.signatures().iter()
+            .map(|s| s.probe.range(s.magic.len()))
+            .filter(|&(start, _)| start < content_len)
+            .map(|(_, end)| end)
+            .fold(self.tof_len as u64, core::cmp::max)
+            .min(content_len)
+    }
+
+}
+
+/* as identify on an empty registry */
+pub fn identify_builtin<'x>(
+    tof: &[u8],
+    ids: &mut Vector<'x, DataCell<'x>>,
+) -> Result<(), Error<'x>> {
+    for e in BUILTIN_FORMATS {
+        if e.push_ids(tof, ids)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    fn ids<'x>(r: &FormatRegistry<'_>, tof: &[u8], a: AllocatorRef<'x>) -> [&'x str; 3] {
+        let mut v: Vector<'x, DataCell<'x>> = Vector::new(a);
+        r.identify(tof, &mut v).unwrap();
+        let mut l = [""; 3];
+        for (d, c) in l.iter_mut().zip(v.as_slice()) {
+            if let DataCell::StaticId(id) = c { *d = id; }
+        }
+        l
+    }
+
+    fn odt(tof: &[u8]) -> TofMatch {
+        if tof.get(30..38) == Some(b"mimetype") { TofMatch::Refined("odf") } else { TofMatch::Rejected }
+    }
+
+    #[test]
+    fn builtin_and_registered() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut r = FormatRegistry::new(a.to_ref());
+        assert_eq!(ids(&r, b"", a.to_ref()), ["empty", "", ""]);
+        assert_eq!(ids(&r, b"ZM\x90\x00", a.to_ref()), ["dos_exe", "dos_exe_zm", ""]);
+        assert_eq!(ids(&r, b"QFI\xFB\x00\x00\x00\x03", a.to_ref()), ["qcow", "qcow3", ""]);
+        assert_eq!(ids(&r, b"QFI\xFB", a.to_ref()), ["qcow", "", ""]);
+        assert_eq!(ids(&r, b"\x28\xB5\x2F\xFD", a.to_ref()), ["zstd", "", ""]);
+        assert_eq!(ids(&r, b"\x5A\x2A\x4D\x18", a.to_ref()), ["zstd_skippable", "", ""]);
+        assert_eq!(ids(&r, b"\xCA\xFE\xBA\xBE\x00\x00\x00\x34", a.to_ref()), ["", "", ""]);
+
+        let mut zip = [0_u8; 0x40];
+        zip[0..4].copy_from_slice(b"PK\x03\x04");
+        zip[30..38].copy_from_slice(b"mimetype");
+        assert_eq!(ids(&r, &zip, a.to_ref()), ["zip_record", "", ""]);
+        r.register(b"PK\x03\x04", 0, "zip_container", Some(odt)).unwrap();
+        r.register(b"HBX1", 0x200, "hbx", None).unwrap();
+        assert_eq!(r.tof_len(), 0x204);
+        assert_eq!(ids(&r, &zip, a.to_ref()), ["zip_container", "odf", ""]);
+        assert_eq!(ids(&r, b"PK\x03\x04", a.to_ref()), ["zip_record", "", ""]);
+        let mut hbx = [0_u8; 0x204];
+        hbx[0x200..].copy_from_slice(b"HBX1");
+        assert_eq!(ids(&r, &hbx, a.to_ref()), ["hbx", "", ""]);
+        assert_eq!(r.register(b"X", MAX_TOF_LEN, "far", None), Err(AllocError::UnsupportedSize));
+    }
+
+    #[test]
+    fn find_id_ignores_case() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut r = FormatRegistry::new(a.to_ref());
+        assert_eq!(r.find_id("ZSTD"), Some("zstd"));
+        assert_eq!(r.find_id("Tar"), Some("tar"));
+        assert_eq!(r.find_id("hbx"), None);
+        r.register(b"HBX1", 0, "HBX", None).unwrap();
+        r.register(b"HBX2", 0, "hbx", None).unwrap();
+        assert_eq!(r.find_id("hbx"), Some("HBX"));
+        assert_eq!(ids(&r, b"HBX2", a.to_ref()), ["HBX", "", ""]);
+    }
+
+    #[test]
+    fn indexed_and_scanned_agree() {
+        use crate::mm::NOP_ALLOCATOR;
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut r = FormatRegistry::new(a.to_ref());
+        assert!(r.indexed);
+        r.register(b"PK\x03\x04", 0, "zip_container", Some(odt)).unwrap();
+        r.register(b"\x7FELF", 0, "elf_too", None).unwrap();
+        r.register(b"ustar", 0x101, "tar_too", None).unwrap();
+        assert!(r.indexed);
+        let builtin = FormatRegistry::new(a.to_ref());
+        let scan = FormatRegistry::new(NOP_ALLOCATOR.to_ref());
+        assert!(!scan.indexed);
+        let mut tar = [0_u8; 0x106];
+        tar[0x101..].copy_from_slice(b"ustar");
+        for tof in [&b""[..], b"PK\x03\x04", b"\x7FELF\x02", b"MZ", b"ZM", b"\x89PNG\r\n\x1A\n", &tar] {
+            assert_eq!(ids(&scan, tof, a.to_ref()), ids(&builtin, tof, a.to_ref()));
+        }
+        assert_eq!(ids(&r, b"PK\x03\x04", a.to_ref()), ["zip_record", "", ""]);
+        assert_eq!(ids(&r, b"\x7FELF\x02", a.to_ref()), ["elf_too", "", ""]);
+        assert_eq!(ids(&r, &tar, a.to_ref()), ["tar_too", "", ""]);
+        assert_eq!(ids(&r, b"\x89PNG\r\n\x1A\n", a.to_ref()), ["image", "png", ""]);
+        assert_eq!(ids(&r, b"", a.to_ref()), ["empty", "", ""]);
+    }
+
+    #[test]
+    fn registered_signatures() {
+        use crate::ExecutionContext;
+        use crate::io::stream::BufferAsROStream;
+        use super::super::signature::Probe;
+        let mut buffer = [0_u8; 0x8000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut r = FormatRegistry::new(xc.get_main_allocator());
+        /* past MAX_TOF_LEN, at one of several positions */
+        r.register_signature(Signature {
+            id: "hbx",
+            probe: Probe::Scan { start: 0x1000, len: 0x1000, step: 0x200 },
+            magic: b"HBX1",
+        }).unwrap();
+        let mut data = [0_u8; 0x1800];
+        data[0..4].copy_from_slice(b"\x7FELF");
+        data[0x101..0x106].copy_from_slice(b"ustar");
+        data[0x1600..0x1604].copy_from_slice(b"HBX1");
+        let mut s = BufferAsROStream::new(&data);
+        let mut v: Vector<DataCell> = Vector::new(xc.get_main_allocator());
+        r.identify_content(&mut s, &mut v, &mut xc).unwrap();
+        let mut l = [""; 4];
+        for (d, c) in l.iter_mut().zip(v.as_slice()) {
+            if let DataCell::StaticId(id) = c { *d = id; }
+        }
+        assert_eq!(l, ["elf", "tar", "hbx", ""]);
+
+        let mut v: Vector<DataCell> = Vector::new(xc.get_main_allocator());
+        FormatRegistry::new(xc.get_main_allocator())
+            .identify_content(&mut s, &mut v, &mut xc).unwrap();
+        assert_eq!(v.len(), 2);
+    }
+}
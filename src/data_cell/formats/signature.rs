@@ -0,0 +1,221 @@
+use crate::ExecutionContext;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::conv::ascii_eq_ignore_case;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::SeekFrom;
+use crate::mm::Vector;
+use super::read_available;
+
+/* probes this close to each other are served by a single read */
+const MAX_BATCH_GAP: u64 = 0x1000;
+/* probes are not merged into reads longer than this */
+const MAX_BATCH_LEN: u64 = 0x4000;
+
+/* Probe ********************************************************************/
+/* where a magic is looked for */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Probe {
+    At(u64),
+    /* at start, start + step, ... as long as the magic fits in len bytes */
+    Scan { start: u64, len: u64, step: u64 },
+}
+
+impl Probe {
+
+    /* byte range that holds every candidate position */
+    pub fn range(&self, magic_len: usize) -> (u64, u64) {
+        match *self {
+            Probe::At(offset) => (offset, offset.saturating_add(magic_len as u64)),
+            Probe::Scan { start, len, .. } => (start, start.saturating_add(len)),
+        }
+    }
+
+    /* data holds content starting at data_pos; candidates outside of it
+     * do not match */
+    pub fn matches(&self, magic: &[u8], data: &[u8], data_pos: u64) -> bool {
+        let at = |pos: u64| pos.checked_sub(data_pos)
+            .and_then(|i| data.get(i as usize..))
+            .is_some_and(|d| d.starts_with(magic));
+        match *self {
+            Probe::At(offset) => at(offset),
+            Probe::Scan { start, len, step } => {
+                let end = start.saturating_add(len);
+                let mut pos = start;
+                while pos.saturating_add(magic.len() as u64) <= end {
+                    if at(pos) { return true; }
+                    pos = match pos.checked_add(core::cmp::max(step, 1)) {
+                        Some(p) => p,
+                        None => break,
+                    };
+                }
+                false
+            },
+        }
+    }
+
+}
+
+/* Signature ****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub id: &'static str,
+    pub probe: Probe,
+    pub magic: &'static [u8],
+}
+
+/* formats recognized by magics past the top of file */
+pub const DEEP_SIGNATURES: &[Signature] = &[
+    Signature { id: "tar", probe: Probe::At(0x101), magic: b"ustar" },
+    Signature { id: "gpt", probe: Probe::At(0x200), magic: b"EFI PART" },
+    Signature { id: "gpt", probe: Probe::At(0x1000), magic: b"EFI PART" },
+    Signature { id: "extfs", probe: Probe::At(0x438), magic: b"\x53\xEF" },
+    Signature { id: "linux_swap", probe: Probe::At(0xFF6), magic: b"SWAPSPACE2" },
+    /* volume descriptors start at sector 16; the primary one may come
+     * after the UDF or boot record ones */
+    Signature {
+        id: "iso9660",
+        probe: Probe::Scan { start: 0x8001, len: 0x2000, step: 0x800 },
+        magic: b"CD001",
+    },
+    Signature {
+        id: "udf",
+        probe: Probe::Scan { start: 0x8001, len: 0x4000, step: 0x800 },
+        magic: b"NSR0",
+    },
+    Signature { id: "btrfs", probe: Probe::At(0x10040), magic: b"_BHRfS_M" },
+];
+
+/* appends the ids of matching signatures, in table order, skipping ids
+ * already in the vector; probes are visited by offset and nearby ones are
+ * read together, so that only a few seeks are made */
+pub fn identify_signatures<'x, T: ?Sized + RandomAccessRead>(
+    signatures: &[Signature],
+    stream: &mut T,
+    ids: &mut Vector<'x, DataCell<'x>>,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let content_len = match stream.content_slice() {
+        Some(content) => content.len() as u64,
+        None => stream.seek(SeekFrom::End(0), xc)?,
+    };
+    let a = xc.get_main_allocator();
+    let range = |i: usize| {
+        let s = &signatures[i];
+        s.probe.range(s.magic.len())
+    };
+    let mut order: Vector<'x, usize> = Vector::new(a);
+    let mut matched: Vector<'x, bool> = Vector::new(a);
+    for i in 0..signatures.len() {
+        matched.push(false).map_err(|(e, _)| e)?;
+        if range(i).0 < content_len {
+            order.push(i).map_err(|(e, _)| e)?;
+        }
+    }
+    order.as_mut_slice().sort_unstable_by_key(|&i| range(i).0);
+
+    let mut data: Vector<'x, u8> = Vector::new(a);
+    let mut first = 0;
+    while first < order.len() {
+        let (start, mut end) = range(order.as_slice()[first]);
+        let mut last = first + 1;
+        while let Some(&i) = order.as_slice().get(last) {
+            let (s, e) = range(i);
+            if s > end.saturating_add(MAX_BATCH_GAP)
+                || core::cmp::max(e, end) - start > MAX_BATCH_LEN {
+                break;
+            }
+            end = core::cmp::max(e, end);
+            last += 1;
+        }
+        data.truncate(0);
+        read_available(stream, start, core::cmp::min(end, content_len) - start, &mut data, xc)?;
+        for &i in &order.as_slice()[first..last] {
+            let s = &signatures[i];
+            matched.as_mut_slice()[i] = s.probe.matches(s.magic, data.as_slice(), start);
+        }
+        first = last;
+    }
+
+    for (s, &m) in signatures.iter().zip(matched.as_slice()) {
+        let known = ids.as_slice().iter().any(|c| matches!(c,
+            DataCell::StaticId(id) if ascii_eq_ignore_case(id.as_bytes(), s.id.as_bytes())));
+        if m && !known {
+            ids.push(DataCell::StaticId(s.id))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::IOResult;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::Read;
+    use crate::io::stream::Seek;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[derive(Debug)]
+    struct SeekCounter<'a> {
+        inner: BufferAsROStream<'a>,
+        seeks: usize,
+    }
+    impl Read for SeekCounter<'_> {
+        fn read<'x>(&mut self, buf: &mut [u8], xc: &mut ExecutionContext<'x>) -> IOResult<'x, usize> {
+            self.inner.read(buf, xc)
+        }
+    }
+    impl Seek for SeekCounter<'_> {
+        fn seek<'x>(&mut self, target: SeekFrom, xc: &mut ExecutionContext<'x>) -> IOResult<'x, u64> {
+            self.seeks += 1;
+            self.inner.seek(target, xc)
+        }
+    }
+
+    fn id_list<'x>(ids: &Vector<'x, DataCell<'x>>) -> [&'x str; 4] {
+        let mut l = [""; 4];
+        for (d, c) in l.iter_mut().zip(ids.as_slice()) {
+            if let DataCell::StaticId(id) = c { *d = id; }
+        }
+        l
+    }
+
+    #[test]
+    fn probes() {
+        let scan = Probe::Scan { start: 2, len: 7, step: 3 };
+        assert_eq!(scan.range(2), (2, 9));
+        assert!(scan.matches(b"ab", b".....ab", 0));
+        assert!(!scan.matches(b"ab", b"......ab", 0));
+        assert!(scan.matches(b"ab", b"ab", 5));
+        assert!(!scan.matches(b"ab", b"ab", 7));
+        assert!(Probe::At(5).matches(b"x", b"x", 5));
+        assert!(!Probe::At(5).matches(b"x", b"x", 6));
+        assert!(!Probe::At(5).matches(b"x", b"", 5));
+    }
+
+    #[test]
+    fn deep_signatures_in_few_reads() {
+        let mut buffer = [0_u8; 0x10000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut disk = [0_u8; 0x9000];
+        disk[0x200..0x208].copy_from_slice(b"EFI PART");
+        disk[0x1000..0x1008].copy_from_slice(b"EFI PART");
+        disk[0x438..0x43A].copy_from_slice(b"\x53\xEF");
+        disk[0x8801..0x8806].copy_from_slice(b"CD001");
+        let mut s = SeekCounter { inner: BufferAsROStream::new(&disk), seeks: 0 };
+        let mut ids: Vector<DataCell> = Vector::new(xc.get_main_allocator());
+        ids.push(DataCell::StaticId("extfs")).unwrap();
+        identify_signatures(DEEP_SIGNATURES, &mut s, &mut ids, &mut xc).unwrap();
+        assert_eq!(id_list(&ids), ["extfs", "gpt", "iso9660", ""]);
+        // one seek for the size, one per batch: 0x101..0x1008, 0x8001..0xC001
+        assert_eq!(s.seeks, 3);
+
+        let mut s = BufferAsROStream::new(&disk[0..0x8803]);
+        let mut ids: Vector<DataCell> = Vector::new(xc.get_main_allocator());
+        identify_signatures(DEEP_SIGNATURES, &mut s, &mut ids, &mut xc).unwrap();
+        assert_eq!(id_list(&ids), ["gpt", "extfs", "", ""]);
+    }
+}
@@ -0,0 +1,279 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::conv::Endianness;
+use crate::conv::decode_at;
+use crate::conv::try_decode_at;
+use crate::data_cell::DCOVector;
+use crate::data_cell::DataCell;
+use crate::data_cell::Error;
+use crate::data_cell::Record;
+use crate::data_cell::RecordDesc;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOError;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::SeekFrom;
+use crate::mm::Vector;
+use super::hex_cell;
+use super::read_region;
+
+pub const EOCD_SIGNATURE: u32 = 0x0605_4B50;
+pub const EOCD_SIZE: usize = 22;
+pub const ZIP64_EOCD_LOCATOR_SIGNATURE: u32 = 0x0706_4B50;
+pub const ZIP64_EOCD_LOCATOR_SIZE: usize = 20;
+pub const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4B50;
+pub const ZIP64_EOCD_SIZE: usize = 56;
+pub const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4B50;
+pub const CENTRAL_HEADER_SIZE: usize = 46;
+const MAX_COMMENT_LEN: usize = 0xFFFF;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+const ZIP_ENTRY: RecordDesc<'static> = RecordDesc::new(
+    "zip_entry",
+    &[
+        "name", "method", "compressed_size", "uncompressed_size",
+        "crc32", "offset",
+    ]);
+
+fn truncated<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::UnexpectedEnd, msg))
+}
+
+fn invalid<'x>(msg: &'static str) -> Error<'x> {
+    Error::IO(IOError::with_str(IOErrorCode::InvalidData, msg))
+}
+
+pub fn method_cell<'x>(method: u16) -> DataCell<'x> {
+    match method {
+        0 => DataCell::from_static_id("stored"),
+        8 => DataCell::from_static_id("deflate"),
+        9 => DataCell::from_static_id("deflate64"),
+        12 => DataCell::from_static_id("bzip2"),
+        14 => DataCell::from_static_id("lzma"),
+        93 => DataCell::from_static_id("zstd"),
+        95 => DataCell::from_static_id("xz"),
+        n => DataCell::from_u64(n as u64),
+    }
+}
+
+/* EndOfCentralDir **********************************************************/
+/* what the end of central directory record says, after the zip64 variant
+ * replaced it if needed */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EndOfCentralDir {
+    pub entry_count: u64,
+    pub cd_size: u64,
+    pub cd_offset: u64,
+}
+
+impl EndOfCentralDir {
+
+    /* offset of the record in the last bytes of the content; the last
+     * signature whose comment fits is taken */
+    pub fn find(tail: &[u8]) -> Option<usize> {
+        let last = tail.len().checked_sub(EOCD_SIZE)?;
+        (0..=last).rev().find(|&pos| {
+            try_decode_at::<u32>(tail, pos, Endianness::Little) == Ok(EOCD_SIGNATURE)
+                && pos + EOCD_SIZE + decode_at::<u16>(tail, pos + 20, Endianness::Little) as usize
+                    <= tail.len()
+        })
+    }
+
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if try_decode_at::<u32>(data, 0, Endianness::Little).ok()? != EOCD_SIGNATURE {
+            return None;
+        }
+        Some(EndOfCentralDir {
+            entry_count: try_decode_at::<u16>(data, 10, Endianness::Little).ok()? as u64,
+            cd_size: try_decode_at::<u32>(data, 12, Endianness::Little).ok()? as u64,
+            cd_offset: try_decode_at::<u32>(data, 16, Endianness::Little).ok()? as u64,
+        })
+    }
+
+    /* fields saturated to make room for the zip64 record */
+    pub fn needs_zip64(&self) -> bool {
+        self.entry_count == 0xFFFF
+            || self.cd_size == 0xFFFF_FFFF
+            || self.cd_offset == 0xFFFF_FFFF
+    }
+
+    pub fn parse_zip64(data: &[u8]) -> Option<Self> {
+        if try_decode_at::<u32>(data, 0, Endianness::Little).ok()? != ZIP64_EOCD_SIGNATURE {
+            return None;
+        }
+        Some(EndOfCentralDir {
+            entry_count: try_decode_at(data, 32, Endianness::Little).ok()?,
+            cd_size: try_decode_at(data, 40, Endianness::Little).ok()?,
+            cd_offset: try_decode_at(data, 48, Endianness::Little).ok()?,
+        })
+    }
+
+}
+
+/* CentralDirEntry **********************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CentralDirEntry<'a> {
+    pub name: &'a [u8],
+    pub flags: u16,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub offset: u64, // of the local header
+    pub header_len: usize, // including name, extra field and comment
+}
+
+impl<'a> CentralDirEntry<'a> {
+
+    pub fn parse<'x>(data: &'a [u8]) -> Result<Self, Error<'x>> {
+        if data.len() < CENTRAL_HEADER_SIZE {
+            return Err(truncated("zip central directory truncated"));
+        }
+        if try_decode_at::<u32>(data, 0, Endianness::Little) != Ok(CENTRAL_HEADER_SIGNATURE) {
+            return Err(invalid("bad zip central directory header signature"));
+        }
+        let name_len = decode_at::<u16>(data, 28, Endianness::Little) as usize;
+        let extra_len = decode_at::<u16>(data, 30, Endianness::Little) as usize;
+        let comment_len = decode_at::<u16>(data, 32, Endianness::Little) as usize;
+        let header_len = CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+        if data.len() < header_len {
+            return Err(truncated("zip central directory truncated"));
+        }
+        let name_end = CENTRAL_HEADER_SIZE + name_len;
+        let mut e = CentralDirEntry {
+            name: &data[CENTRAL_HEADER_SIZE..name_end],
+            flags: decode_at(data, 8, Endianness::Little),
+            method: decode_at(data, 10, Endianness::Little),
+            crc32: decode_at(data, 16, Endianness::Little),
+            compressed_size: decode_at::<u32>(data, 20, Endianness::Little) as u64,
+            uncompressed_size: decode_at::<u32>(data, 24, Endianness::Little) as u64,
+            offset: decode_at::<u32>(data, 42, Endianness::Little) as u64,
+            header_len,
+        };
+        e.apply_zip64_extra(&data[name_end..name_end + extra_len])?;
+        Ok(e)
+    }
+
+    /* the zip64 extra field holds, in order, only the sizes and offset
+     * that are saturated in the fixed part of the header */
+    fn apply_zip64_extra<'x>(&mut self, mut extra: &[u8]) -> Result<(), Error<'x>> {
+        while extra.len() >= 4 {
+            let id: u16 = decode_at(extra, 0, Endianness::Little);
+            let len = decode_at::<u16>(extra, 2, Endianness::Little) as usize;
+            let field = extra.get(4..4 + len)
+                .ok_or_else(|| invalid("zip extra field truncated"))?;
+            if id == ZIP64_EXTRA_ID {
+                let mut pos = 0;
+                for v in [
+                    &mut self.uncompressed_size,
+                    &mut self.compressed_size,
+                    &mut self.offset,
+                ] {
+                    if *v == 0xFFFF_FFFF {
+                        *v = try_decode_at(field, pos, Endianness::Little)
+                            .map_err(|_| invalid("zip64 extra field too short"))?;
+                        pos += 8;
+                    }
+                }
+            }
+            extra = &extra[4 + len..];
+        }
+        Ok(())
+    }
+
+    pub fn to_data_cell<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let a = xc.get_main_allocator();
+        let mut r = Record::new(&ZIP_ENTRY, a)?;
+        /* names are UTF-8 when flag bit 11 is set and CP437 otherwise,
+         * which only matches UTF-8 for ASCII names */
+        r.set_field("name", DataCell::from_utf8_or_bytes(a, self.name)?);
+        r.set_field("method", method_cell(self.method));
+        r.set_field("compressed_size", DataCell::from_u64(self.compressed_size));
+        r.set_field("uncompressed_size", DataCell::from_u64(self.uncompressed_size));
+        r.set_field("crc32", hex_cell(self.crc32 as u64));
+        r.set_field("offset", hex_cell(self.offset));
+        Ok(DataCell::Record(xc.rc(RefCell::new(r))?))
+    }
+
+}
+
+/* the end of central directory record, located from the end of the
+ * content; NotApplicable if there is none */
+pub fn end_of_central_dir<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<EndOfCentralDir, Error<'x>> {
+    let len = match stream.content_slice() {
+        Some(content) => content.len() as u64,
+        None => stream.seek(SeekFrom::End(0), xc)?,
+    };
+    let tail_len = core::cmp::min(len, (EOCD_SIZE + MAX_COMMENT_LEN) as u64);
+    let tail = read_region(stream, len - tail_len, tail_len, xc)?;
+    let tail_pos = EndOfCentralDir::find(tail.as_slice()).ok_or(Error::NotApplicable)?;
+    let eocd = EndOfCentralDir::parse(&tail.as_slice()[tail_pos..]).unwrap();
+    let eocd_pos = len - tail_len + tail_pos as u64;
+    if !eocd.needs_zip64() || eocd_pos < ZIP64_EOCD_LOCATOR_SIZE as u64 {
+        return Ok(eocd);
+    }
+    let locator = read_region(stream, eocd_pos - ZIP64_EOCD_LOCATOR_SIZE as u64,
+                              ZIP64_EOCD_LOCATOR_SIZE as u64, xc)?;
+    if try_decode_at::<u32>(locator.as_slice(), 0, Endianness::Little) != Ok(ZIP64_EOCD_LOCATOR_SIGNATURE) {
+        return Ok(eocd);
+    }
+    let zip64_pos: u64 = decode_at(locator.as_slice(), 8, Endianness::Little);
+    let data = read_region(stream, zip64_pos, ZIP64_EOCD_SIZE as u64, xc)?;
+    EndOfCentralDir::parse_zip64(data.as_slice())
+        .ok_or_else(|| invalid("bad zip64 end of central directory signature"))
+}
+
+/* central directory entries as zip_entry records */
+pub fn zip_entries<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let eocd = end_of_central_dir(stream, xc)?;
+    let cd = read_region(stream, eocd.cd_offset, eocd.cd_size, xc)?;
+    let mut entries: Vector<'x, DataCell> = Vector::new(xc.get_main_allocator());
+    let mut pos = 0_usize;
+    for _ in 0..eocd.entry_count {
+        xc.check_cancelled()?;
+        let e = CentralDirEntry::parse(&cd.as_slice()[pos..])?;
+        entries.push(e.to_data_cell(xc)?)?;
+        pos += e.header_len;
+    }
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(entries)))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eocd_with_comment_and_zip64_extra() {
+        let tail = b"xxPK\x05\x06\x00\x00\x00\x00\x02\x00\x02\x00\x71\x00\x00\x00\x65\x00\x00\x00\x04\x00note";
+        assert_eq!(EndOfCentralDir::find(tail), Some(2));
+        assert_eq!(EndOfCentralDir::parse(&tail[2..]),
+                   Some(EndOfCentralDir { entry_count: 2, cd_size: 0x71, cd_offset: 0x65 }));
+        assert_eq!(EndOfCentralDir::find(&tail[0..tail.len() - 1]), None);
+
+        let mut h = [0_u8; CENTRAL_HEADER_SIZE + 1 + 4 + 16];
+        h[0..4].copy_from_slice(b"PK\x01\x02");
+        h[10] = 8;
+        h[20..28].copy_from_slice(b"\x10\x00\x00\x00\xFF\xFF\xFF\xFF");
+        h[28] = 1;
+        h[30] = 20;
+        h[42..46].copy_from_slice(b"\xFF\xFF\xFF\xFF");
+        h[46] = b'a';
+        h[47..51].copy_from_slice(b"\x01\x00\x10\x00");
+        h[51..59].copy_from_slice(&0x1_0000_0000_u64.to_le_bytes());
+        h[59..67].copy_from_slice(&0x2_0000_0000_u64.to_le_bytes());
+        let e = CentralDirEntry::parse(&h).unwrap();
+        assert_eq!((e.name, e.method, e.header_len), (&b"a"[..], 8, h.len()));
+        assert_eq!((e.compressed_size, e.uncompressed_size, e.offset),
+                   (0x10, 0x1_0000_0000, 0x2_0000_0000));
+        assert!(CentralDirEntry::parse(&h[0..h.len() - 8]).is_err());
+    }
+}
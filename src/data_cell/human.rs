@@ -0,0 +1,134 @@
+use core::fmt::Write as FmtWrite;
+
+use crate::ExecutionContext;
+use crate::io::stream::Write;
+use crate::num::fmt::HumanDuration;
+use crate::num::fmt::HumanSize;
+use crate::num::fmt::SizeUnits;
+use crate::num::fmt::human_duration;
+use crate::num::fmt::human_size;
+
+use super::DataCell;
+use super::DataCellOps;
+use super::Error;
+use super::json::JsonOptions;
+
+/* sizes and durations are shown rounded for people while JSON gets the
+ * exact number */
+
+impl DataCellOps for HumanSize {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "bytes" => Ok(DataCell::from_u64(self.bytes)),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        write!(out, "{}", self)?;
+        Ok(())
+    }
+
+    fn output_as_json<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        write!(out, "{}", self.bytes)?;
+        Ok(())
+    }
+
+}
+
+impl DataCellOps for HumanDuration {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "ns" => Ok(DataCell::from_u64(self.ns)),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        write!(out, "{}", self)?;
+        Ok(())
+    }
+
+    fn output_as_json<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        write!(out, "{}", self.ns)?;
+        Ok(())
+    }
+
+}
+
+pub fn size_cell<'x>(
+    bytes: u64,
+    units: SizeUnits,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    crate::dyn_rc!(size_rc, DataCellOps);
+    Ok(DataCell::Dyn(size_rc(xc.rc(human_size(bytes, units))?)))
+}
+
+pub fn duration_cell<'x>(
+    ns: u64,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    crate::dyn_rc!(duration_rc, DataCellOps);
+    Ok(DataCell::Dyn(duration_rc(xc.rc(human_duration(ns))?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn rounded_text_exact_json() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_u64(3 << 30).get_property("human_size", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"3.0 GiB");
+        let mut o = xc.byte_vector();
+        c.output_as_json(&mut o, &mut xc, &JsonOptions::default()).unwrap();
+        assert_eq!(o.as_slice(), b"3221225472");
+        assert!(matches!(c.get_property("bytes", &mut xc), Ok(DataCell::U64(n)) if n.n == 3 << 30));
+
+        let c = DataCell::from_u64(1_500_000).get_property("human_size_si", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"1.5 MB");
+
+        let c = DataCell::from_u64(2_500_000).get_property("human_duration", &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"2.5 ms");
+    }
+}
@@ -0,0 +1,372 @@
+use core::fmt::Write as FmtWrite;
+use core::ops::Deref;
+
+use crate::ExecutionContext;
+use crate::conv::Utf8LossyWriter;
+use crate::io::IOResult;
+use crate::io::stream::SeekFrom;
+use crate::io::stream::Stream;
+use crate::io::stream::Write;
+use crate::mm::Vector;
+
+use super::DataCell;
+use super::DataCellOps;
+use super::Error;
+use super::redact::PathItem;
+use super::redact::entry_path;
+use super::redact::Redaction;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+/* streams are encoded in chunks of this size, a multiple of 3 so that base64
+ * padding only shows up at the end */
+const STREAM_CHUNK_SIZE: usize = 3 * 0x400;
+
+/* BytesEncoding ************************************************************/
+/* how byte content is put in JSON strings */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BytesEncoding {
+    Base64,
+    Hex,
+}
+
+/* JsonOptions **************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JsonOptions {
+    pub bytes: BytesEncoding,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions { bytes: BytesEncoding::Base64 }
+    }
+}
+
+/* JsonStringWriter *********************************************************/
+/* escapes the bytes written through it for the inside of a JSON string;
+ * bytes of 0x80 and above are passed as they are, so they should form
+ * valid UTF-8 (see output_text_as_json_string) */
+pub struct JsonStringWriter<'w> {
+    out: &'w mut (dyn Write + 'w),
+}
+
+impl<'w> JsonStringWriter<'w> {
+
+    pub fn new(out: &'w mut (dyn Write + 'w)) -> Self {
+        JsonStringWriter { out }
+    }
+
+}
+
+impl<'w> Write for JsonStringWriter<'w> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let mut plain_start = 0;
+        for (i, &b) in buf.iter().enumerate() {
+            let mut hex_esc = *b"\\u0000";
+            let esc: &[u8] = match b {
+                b'"' => b"\\\"",
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                0..=0x1F | 0x7F => {
+                    hex_esc[4] = HEX_DIGITS[(b >> 4) as usize];
+                    hex_esc[5] = HEX_DIGITS[(b & 15) as usize];
+                    &hex_esc
+                },
+                _ => continue,
+            };
+            self.out.write_all(&buf[plain_start..i], xc).map_err(|e| e.to_error())?;
+            self.out.write_all(esc, xc).map_err(|e| e.to_error())?;
+            plain_start = i + 1;
+        }
+        self.out.write_all(&buf[plain_start..], xc).map_err(|e| e.to_error())?;
+        Ok(buf.len())
+    }
+}
+
+/* text as a quoted JSON string; invalid UTF-8 is replaced */
+pub fn output_text_as_json_string<'w, 'x>(
+    text: &[u8],
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    out.write_all(b"\"", xc)?;
+    {
+        let mut esc = JsonStringWriter::new(out);
+        let mut w = Utf8LossyWriter::new(&mut esc);
+        w.write_all(text, xc)?;
+        w.finish(xc)?;
+    }
+    out.write_all(b"\"", xc)?;
+    Ok(())
+}
+
+/* the human readable text of the cell as a JSON string; this is what cells
+ * without a JSON form of their own produce */
+pub fn output_human_readable_as_json_string<'w, 'x, T: ?Sized + DataCellOps>(
+    cell: &T,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    out.write_all(b"\"", xc)?;
+    {
+        let mut esc = JsonStringWriter::new(out);
+        let mut w = Utf8LossyWriter::new(&mut esc);
+        cell.output_as_human_readable(&mut w, xc)?;
+        w.finish(xc)?;
+    }
+    out.write_all(b"\"", xc)?;
+    Ok(())
+}
+
+/* encodes a piece of the content; all pieces but the last must have a
+ * length multiple of 3 for base64 */
+fn output_encoded_bytes<'w, 'x>(
+    data: &[u8],
+    encoding: BytesEncoding,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let mut buf = [0_u8; 0x400];
+    match encoding {
+        BytesEncoding::Hex => for chunk in data.chunks(buf.len() / 2) {
+            for (d, &b) in buf.chunks_mut(2).zip(chunk) {
+                d[0] = HEX_DIGITS[(b >> 4) as usize];
+                d[1] = HEX_DIGITS[(b & 15) as usize];
+            }
+            out.write_all(&buf[0..chunk.len() * 2], xc)?;
+        },
+        BytesEncoding::Base64 => for chunk in data.chunks(buf.len() / 4 * 3) {
+            let mut n = 0;
+            for group in chunk.chunks(3) {
+                let v = group.iter().enumerate()
+                    .fold(0_u32, |v, (i, &b)| v | (b as u32) << (16 - 8 * i));
+                for i in 0..4 {
+                    buf[n + i] = if i <= group.len() {
+                        BASE64_ALPHABET[(v >> (18 - 6 * i) & 63) as usize]
+                    } else {
+                        b'='
+                    };
+                }
+                n += 4;
+            }
+            out.write_all(&buf[0..n], xc)?;
+        },
+    }
+    Ok(())
+}
+
+pub fn output_bytes_as_json_string<'w, 'x>(
+    data: &[u8],
+    options: &JsonOptions,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    out.write_all(b"\"", xc)?;
+    output_encoded_bytes(data, options.bytes, out, xc)?;
+    out.write_all(b"\"", xc)?;
+    Ok(())
+}
+
+fn output_stream_as_json_string<'w, 'x>(
+    stream: &mut (dyn Stream + '_),
+    options: &JsonOptions,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if let Some(content) = stream.content_slice() {
+        return output_bytes_as_json_string(content, options, out, xc);
+    }
+    stream.seek(SeekFrom::Start(0), xc)?;
+    out.write_all(b"\"", xc)?;
+    let mut buf = [0_u8; STREAM_CHUNK_SIZE];
+    loop {
+        let n = stream.read_uninterrupted(&mut buf, xc)?;
+        output_encoded_bytes(&buf[0..n], options.bytes, out, xc)?;
+        if n < buf.len() { break; }
+    }
+    out.write_all(b"\"", xc)?;
+    Ok(())
+}
+
+/* records become objects with the fields that are set, maps become objects
+ * with all their entries and vectors become arrays; with a redaction, the
+ * path of each nested cell is tracked in path and redacted cells are
+ * replaced by the placeholder string */
+pub(crate) fn output_cell<'c: 'p, 'p, 'w, 'x>(
+    cell: &DataCell<'c>,
+    options: &JsonOptions,
+    redaction: Option<&Redaction<'_>>,
+    path: &mut Vector<'x, PathItem<'p>>,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    if let Some(r) = redaction {
+        if r.matches(path.as_slice(), cell) {
+            return output_text_as_json_string(r.placeholder().as_bytes(), out, xc);
+        }
+    }
+    match cell {
+        DataCell::Nothing => out.write_all(b"null", xc)?,
+        DataCell::U64(v) => write!(out, "{}", v.n)?,
+        DataCell::U128(v) => write!(out, "{}", v.n)?,
+        DataCell::I64(v) => write!(out, "{}", v.n)?,
+        DataCell::F64(v) => v.output_as_json(out, xc, options)?,
+        DataCell::Bool(b) => out.write_all(if *b { b"true" } else { b"false" }, xc)?,
+        DataCell::StaticId(s) => output_text_as_json_string(s.as_bytes(), out, xc)?,
+        DataCell::Text(t) => output_text_as_json_string(t.try_borrow()?.as_bytes(), out, xc)?,
+        DataCell::ByteVector(v) =>
+            output_bytes_as_json_string(v.try_borrow()?.0.as_slice(), options, out, xc)?,
+        DataCell::ByteStream(s) =>
+            output_stream_as_json_string(&mut *s.try_borrow_mut()?, options, out, xc)?,
+        DataCell::Dyn(o) => o.deref().output_as_json(out, xc, options)?,
+        DataCell::Record(r) => {
+            let r = r.try_borrow()?;
+            out.write_all(b"{", xc)?;
+            let mut first = true;
+            for (&name, v) in r.desc.field_names.iter().zip(r.data.as_slice()) {
+                if v.is_nothing() { continue; }
+                if first {
+                    first = false;
+                } else {
+                    out.write_all(b",", xc)?;
+                }
+                output_text_as_json_string(name.as_bytes(), out, xc)?;
+                out.write_all(b":", xc)?;
+                if redaction.is_some() {
+                    path.push(PathItem::Field(name)).map_err(|(e, _)| e)?;
+                }
+                output_cell(v, options, redaction, path, out, xc)?;
+                if redaction.is_some() {
+                    path.pop();
+                }
+            }
+            out.write_all(b"}", xc)?;
+        },
+        DataCell::CellVector(v) => {
+            let v = v.try_borrow()?;
+            out.write_all(b"[", xc)?;
+            for (i, c) in v.0.as_slice().iter().enumerate() {
+                if i != 0 {
+                    out.write_all(b",", xc)?;
+                }
+                if redaction.is_some() {
+                    path.push(PathItem::Index(i)).map_err(|(e, _)| e)?;
+                }
+                output_cell(c, options, redaction, path, out, xc)?;
+                if redaction.is_some() {
+                    path.pop();
+                }
+            }
+            out.write_all(b"]", xc)?;
+        },
+        DataCell::Map(m) => {
+            let m = m.try_borrow()?;
+            out.write_all(b"{", xc)?;
+            for (i, (k, v)) in m.0.iter().enumerate() {
+                if i != 0 {
+                    out.write_all(b",", xc)?;
+                }
+                output_text_as_json_string(k.as_bytes(), out, xc)?;
+                out.write_all(b":", xc)?;
+                if redaction.is_some() {
+                    let mut p = entry_path(path.as_slice(), k.as_str(), xc)?;
+                    output_cell(v, options, redaction, &mut p, out, xc)?;
+                } else {
+                    output_cell(v, options, None, path, out, xc)?;
+                }
+            }
+            out.write_all(b"}", xc)?;
+        },
+    }
+    Ok(())
+}
+
+pub fn output_as_json_with_options<'w, 'x>(
+    cell: &DataCell<'_>,
+    options: &JsonOptions,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    let mut path = Vector::new(xc.get_main_allocator());
+    output_cell(cell, options, None, &mut path, out, xc)
+}
+
+/* compact JSON text of the cell, with byte content in base64 */
+pub fn output_as_json<'w, 'x>(
+    cell: &DataCell<'_>,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    output_as_json_with_options(cell, &JsonOptions::default(), out, xc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use crate::data_cell::DCOVector;
+    use crate::data_cell::Record;
+    use crate::data_cell::RecordDesc;
+    use crate::data_cell::U64Cell;
+    use crate::data_cell::stats::ByteHistogram;
+    use crate::data_cell::stats::Entropy;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    const PAIR: RecordDesc<'static> = RecordDesc::new("pair", &["name", "data", "unset", "items"]);
+
+    fn json<'x>(c: &DataCell<'x>, options: &JsonOptions, xc: &mut ExecutionContext<'x>) -> Vector<'x, u8> {
+        let mut o = xc.byte_vector();
+        output_as_json_with_options(c, options, &mut o, xc).unwrap();
+        o
+    }
+
+    #[test]
+    fn tree_to_json() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut items = xc.vector();
+        items.push(DataCell::from_u64_cell(U64Cell::hex(255))).unwrap();
+        items.push(DataCell::Nothing).unwrap();
+        crate::dyn_rc!(entropy_rc, DataCellOps);
+        items.push(DataCell::Dyn(entropy_rc(xc.rc(Entropy { millibits: 1585 }).unwrap()))).unwrap();
+        let mut r = Record::new(&PAIR, a.to_ref()).unwrap();
+        r.set_field("name", DataCell::from_static_id("a\"b\\\n\x01"));
+        r.set_field("data", DataCell::from_byte_slice(a.to_ref(), b"\x00\xFFhello").unwrap());
+        r.set_field("items", DataCell::CellVector(xc.rc(RefCell::new(DCOVector(items))).unwrap()));
+        let c = DataCell::Record(xc.rc(RefCell::new(r)).unwrap());
+        assert_eq!(core::str::from_utf8(json(&c, &JsonOptions::default(), &mut xc).as_slice()).unwrap(),
+            r#"{"name":"a\"b\\\n\u0001","data":"AP9oZWxsbw==","items":[255,null,1.585]}"#);
+        let hex = JsonOptions { bytes: BytesEncoding::Hex };
+        assert_eq!(json(&DataCell::from_borrowed_bytes(a.to_ref(), b"\x00\xFFhi").unwrap(), &hex, &mut xc).as_slice(), b"\"00ff6869\"");
+    }
+
+    #[test]
+    fn base64_padding_and_fallback() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let o = JsonOptions::default();
+        assert_eq!(json(&DataCell::from_borrowed_bytes(a.to_ref(), b"").unwrap(), &o, &mut xc).as_slice(), b"\"\"");
+        assert_eq!(json(&DataCell::from_borrowed_bytes(a.to_ref(), b"f").unwrap(), &o, &mut xc).as_slice(), b"\"Zg==\"");
+        assert_eq!(json(&DataCell::from_borrowed_bytes(a.to_ref(), b"fo").unwrap(), &o, &mut xc).as_slice(), b"\"Zm8=\"");
+        assert_eq!(json(&DataCell::from_borrowed_bytes(a.to_ref(), b"foo").unwrap(), &o, &mut xc).as_slice(), b"\"Zm9v\"");
+        let mut t = xc.byte_vector();
+        output_text_as_json_string(b"caf\xC3\xA9 \xFF", &mut t, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(t.as_slice()).unwrap(), "\"caf\u{e9} \u{fffd}\"");
+        // no JSON form of its own
+        let mut h = ByteHistogram::new();
+        h.feed(b"a\"");
+        let mut t = xc.byte_vector();
+        h.output_as_json(&mut t, &mut xc, &o).unwrap();
+        assert_eq!(t.as_slice(), b"\"byte_histogram(total: 2, 0x22: 1, 0x61: 1)\"");
+    }
+}
@@ -4,12 +4,17 @@ use core::ops::Deref;
 use core::cell::RefCell;
 use core::cell::BorrowError;
 use core::cell::BorrowMutError;
+use core::convert::TryFrom;
 use core::convert::TryInto;
 
 use crate::ExecutionContext;
+use crate::mm;
 use crate::mm::AllocatorRef;
 use crate::mm::AllocError;
+use crate::mm::CowBytes;
+use crate::mm::HashMap;
 use crate::mm::Rc;
+use crate::mm::String;
 use crate::mm::Vector;
 use crate::io::IOError;
 use crate::io::IOPartialError;
@@ -18,10 +23,29 @@ use crate::io::stream::Write;
 use crate::io::stream::SeekFrom;
 use crate::io::stream::Stream;
 use crate::num::fmt as num_fmt;
+use crate::mm::string::Utf8Decoder;
+use crate::conv::Utf8LossyWriter;
+use crate::conv::ascii_eq_ignore_case;
+use dump::HexDumpOptions;
+use json::JsonOptions;
+use expr::SourceLocation;
 
 pub mod expr;
 pub mod eval;
 pub mod content_stream;
+pub mod deep_size;
+pub mod dump;
+pub mod compressed;
+pub mod archive;
+pub mod formats;
+pub mod redact;
+pub mod stats;
+pub mod human;
+pub mod strings;
+pub mod cost;
+pub mod json;
+pub mod cbor;
+pub mod ffi;
 
 /* Error ********************************************************************/
 #[derive(Debug, PartialEq)]
@@ -31,6 +55,15 @@ pub enum Error<'e> {
     IO(IOError<'e>),
     Output(IOError<'e>), // used by report-generating functions like output_as_human_readable
     CellUnavailable, // borrow error on a RefCell while computing something
+    Overflow, // arithmetic result out of the range of its type
+    DivisionByZero,
+    LimitExceeded, // fuel of the execution context ran out
+    /* failure of the part of an expression given by path */
+    Eval {
+        path: String<'e>,
+        location: Option<SourceLocation>,
+        cause: mm::Box<'e, Error<'e>>,
+    },
 }
 
 impl fmt::Display for Error<'_> {
@@ -38,13 +71,39 @@ impl fmt::Display for Error<'_> {
         match self {
             Error::NotApplicable => "not applicable".fmt(f),
             Error::CellUnavailable => "data unavailable due to internal state".fmt(f),
+            Error::Overflow => "arithmetic overflow".fmt(f),
+            Error::DivisionByZero => "division by zero".fmt(f),
+            Error::LimitExceeded => "execution limit exceeded".fmt(f),
             Error::Alloc(v) => write!(f, "allocation error ({})", v),
             Error::IO(v) => write!(f, "I/O error ({})", v),
             Error::Output(v) => write!(f, "reporting output error ({})", v),
+            Error::Eval { path, location: Some(l), cause } =>
+                write!(f, "{}: {}: {}", l, path, cause),
+            Error::Eval { path, location: None, cause } =>
+                write!(f, "{}: {}", path, cause),
         }
     }
 }
 
+impl<'e> Error<'e> {
+
+    /* the error behind any evaluation context */
+    pub fn cause(&self) -> &Error<'e> {
+        match self {
+            Error::Eval { cause, .. } => cause.cause(),
+            _ => self,
+        }
+    }
+
+    pub fn into_cause(self) -> Error<'e> {
+        match self {
+            Error::Eval { cause, .. } => cause.into_inner().into_cause(),
+            _ => self,
+        }
+    }
+
+}
+
 impl From<fmt::Error> for Error<'_> {
     fn from (_: fmt::Error) -> Self {
         Error::Output(
@@ -126,6 +185,26 @@ pub trait DataCellOpsMut: fmt::Debug {
         Err(Error::NotApplicable)
     }
 
+    // byte content as rows of hex and ascii, for cells holding bytes
+    fn output_as_hex_dump_mut<'w, 'x>(
+        &mut self,
+        _out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        Err(Error::NotApplicable)
+    }
+
+    // byte range [start, end) in the underlying content where the given
+    // property is extracted from, if that is known
+    fn get_property_byte_range_mut(
+        &mut self,
+        _property_name: &str,
+        _xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        None
+    }
+
 }
 
 /* DataCellOps **************************************************************/
@@ -147,6 +226,34 @@ pub trait DataCellOps: fmt::Debug {
         Err(Error::NotApplicable)
     }
 
+    fn output_as_hex_dump<'w, 'x>(
+        &self,
+        _out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        Err(Error::NotApplicable)
+    }
+
+    // JSON value for the cell; the human readable text as a JSON string
+    // unless the cell knows better
+    fn output_as_json<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        json::output_human_readable_as_json_string(self, out, xc)
+    }
+
+    fn get_property_byte_range(
+        &self,
+        _property_name: &str,
+        _xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        None
+    }
+
 }
 
 impl<T> DataCellOps for RefCell<T>
@@ -170,6 +277,25 @@ where T: DataCellOpsMut {
         c.output_as_human_readable_mut(out, xc)
     }
 
+    fn output_as_hex_dump<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        let mut c = self.try_borrow_mut()?;
+        c.output_as_hex_dump_mut(out, xc, options)
+    }
+
+    fn get_property_byte_range(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        let mut c = self.try_borrow_mut().ok()?;
+        c.get_property_byte_range_mut(property_name, xc)
+    }
+
 }
 
 impl<'a, T> DataCellOps for Rc<'a, T>
@@ -193,10 +319,36 @@ where T: DataCellOps {
         c.output_as_human_readable(out, xc)
     }
 
+    fn output_as_hex_dump<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        self.as_ref().output_as_hex_dump(out, xc, options)
+    }
+
+    fn output_as_json<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        self.as_ref().output_as_json(out, xc, options)
+    }
+
+    fn get_property_byte_range(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        self.as_ref().get_property_byte_range(property_name, xc)
+    }
+
 }
 
 /* U64Cell ******************************************************************/
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct U64Cell {
     pub n: u64,
     pub fmt_pack: num_fmt::MiniNumFmtPack,
@@ -224,47 +376,323 @@ impl U64Cell {
 
 impl DataCellOps for U64Cell {
 
+    /* byte order helpers for fields whose endianness was guessed wrong;
+     * bswapN refuses values that do not fit in N bits; to_le and to_be
+     * convert from the host byte order like u64::to_le and u64::to_be, so
+     * one of them leaves the value as is and the other is bswap64;
+     * human_size(_si) and human_duration read the value as bytes or
+     * nanoseconds */
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let n = self.n;
+        match property_name {
+            "human_size" => return human::size_cell(n, num_fmt::SizeUnits::Binary, xc),
+            "human_size_si" => return human::size_cell(n, num_fmt::SizeUnits::Decimal, xc),
+            "human_duration" => return human::duration_cell(n, xc),
+            _ => {},
+        }
+        let v = match property_name {
+            "bswap16" => TryInto::<u16>::try_into(n).ok().map(|x| x.swap_bytes() as u64),
+            "bswap32" => TryInto::<u32>::try_into(n).ok().map(|x| x.swap_bytes() as u64),
+            "bswap64" => Some(n.swap_bytes()),
+            "to_le" => Some(n.to_le()),
+            "to_be" => Some(n.to_be()),
+            _ => None,
+        };
+        v.map(|v| DataCell::U64(U64Cell::with_fmt(v, self.fmt_pack)))
+            .ok_or(Error::NotApplicable)
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        self.fmt_pack.write_int(self.n, w, xc).map_err(Error::Output)
+    }
+
+    /* always decimal, JSON has no other notation */
+    fn output_as_json<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        write!(w, "{}", self.n)?;
+        Ok(())
+    }
+
+}
+
+/* U128Cell *****************************************************************/
+/* 128-bit values such as GUIDs and UUIDs; low and high give the 64-bit
+ * halves, which is how most formats that use them split them */
+#[derive(Clone, Debug)]
+pub struct U128Cell {
+    pub n: u128,
+    pub fmt_pack: num_fmt::MiniNumFmtPack,
+}
+
+impl U128Cell {
+
+    pub fn new(n: u128) -> Self {
+        U128Cell::with_fmt(n, num_fmt::MiniNumFmtPack::default())
+    }
+    pub fn with_fmt(n: u128, fmt_pack: num_fmt::MiniNumFmtPack) -> Self {
+        U128Cell { n, fmt_pack }
+    }
+    pub fn hex(n: u128) -> Self {
+        U128Cell::with_fmt(n, U64Cell::hex(0).fmt_pack)
+    }
+}
+
+impl DataCellOps for U128Cell {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let n = self.n;
+        match property_name {
+            "low" => Ok(DataCell::U64(U64Cell::with_fmt(n as u64, self.fmt_pack))),
+            "high" => Ok(DataCell::U64(U64Cell::with_fmt((n >> 64) as u64, self.fmt_pack))),
+            "bswap128" => Ok(DataCell::U128(U128Cell::with_fmt(n.swap_bytes(), self.fmt_pack))),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        self.fmt_pack.write_int(self.n, w, xc).map_err(Error::Output)
+    }
+
+    fn output_as_json<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        write!(w, "{}", self.n)?;
+        Ok(())
+    }
+
+}
+
+/* I64Cell ******************************************************************/
+#[derive(Clone, Debug)]
+pub struct I64Cell {
+    pub n: i64,
+    pub fmt_pack: num_fmt::MiniNumFmtPack,
+}
+
+impl I64Cell {
+
+    pub fn new(n: i64) -> Self {
+        I64Cell::with_fmt(n, num_fmt::MiniNumFmtPack::default())
+    }
+    pub fn with_fmt(n: i64, fmt_pack: num_fmt::MiniNumFmtPack) -> Self {
+        I64Cell { n, fmt_pack }
+    }
+}
+
+impl DataCellOps for I64Cell {
+
+    /* abs is unsigned so that it works for i64::MIN too */
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "abs" => Ok(DataCell::U64(U64Cell::with_fmt(self.n.unsigned_abs(), self.fmt_pack))),
+            "is_negative" => Ok(DataCell::Bool(self.n < 0)),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        self.fmt_pack.write_int(self.n, w, xc).map_err(Error::Output)
+    }
+
+    fn output_as_json<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        write!(w, "{}", self.n)?;
+        Ok(())
+    }
+
+}
+
+/* F64Cell ******************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct F64Cell {
+    pub v: f64,
+    pub fmt_pack: num_fmt::MiniFloatFmtPack,
+}
+
+impl F64Cell {
+    pub fn new(v: f64) -> Self {
+        F64Cell::with_fmt(v, num_fmt::MiniFloatFmtPack::default())
+    }
+    pub fn with_fmt(v: f64, fmt_pack: num_fmt::MiniFloatFmtPack) -> Self {
+        F64Cell { v, fmt_pack }
+    }
+}
+
+impl DataCellOps for F64Cell {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "abs" => Ok(DataCell::F64(F64Cell::with_fmt(self.v.abs(), self.fmt_pack))),
+            "is_negative" => Ok(DataCell::Bool(self.v < 0.0)),
+            "is_nan" => Ok(DataCell::Bool(self.v.is_nan())),
+            "bits" => Ok(DataCell::from_u64_cell(U64Cell::hex(self.v.to_bits()))),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
     fn output_as_human_readable<'w, 'x>(
         &self,
         w: &mut (dyn Write + 'w),
         xc: &mut ExecutionContext<'x>,
     ) -> Result<(), Error<'x>> {
-        let mut buf = [0_u8; 256];
-        w.write_all(
-            self.fmt_pack.int_fmt(self.n, &mut buf).unwrap().as_bytes(),
-            xc
-        ).map_err(|e| Error::Output(e.to_error()))
+        self.fmt_pack.write_float(self.v, w, xc).map_err(Error::Output)
+    }
+
+    /* the shortest text that reads back as the same value, whatever the
+     * format; JSON has no NaN nor infinities */
+    fn output_as_json<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        if self.v.is_finite() {
+            write!(w, "{:?}", self.v)?;
+            Ok(())
+        } else {
+            w.write_all(b"null", xc)?;
+            Ok(())
+        }
     }
 
 }
 
+/* property names ***********************************************************/
+/* names are matched ignoring ASCII case, so LEN and Len work as len */
+pub(crate) fn is_property(name: &str, aliases: &[&str]) -> bool {
+    aliases.iter().any(|a| ascii_eq_ignore_case(name.as_bytes(), a.as_bytes()))
+}
+
+/* element count of containers and char count of text */
+pub(crate) const COUNT_ALIASES: &[&str] = &[ "len", "length", "count" ];
+/* length of byte cells */
+pub(crate) const BYTE_LEN_ALIASES: &[&str] = &[ "len", "length", "count", "size" ];
+
 /* ByteVector ***************************************************************/
+/* bytes owned by the cell, or borrowed from data that outlives it */
 #[derive(Debug)]
-pub struct ByteVector<'a>(pub Vector<'a, u8>);
+pub struct ByteVector<'a>(pub CowBytes<'a>);
+
+const TEXT_INFO: RecordDesc<'static> = RecordDesc::new(
+    "text_info",
+    &[ "valid_utf8", "invalid_offset", "replacements", "text" ]);
 
 impl<'a> ByteVector<'a> {
     pub fn from_byte_slice(
         allocator: AllocatorRef<'a>,
         data: &[u8]
     ) -> Result<Self, AllocError> {
-        Vector::from_slice(data, allocator).map(|bv| ByteVector(bv))
+        Vector::from_slice(data, allocator).map(|bv| ByteVector(CowBytes::Owned(bv)))
+    }
+
+    pub fn borrowed(data: &'a [u8]) -> Self {
+        ByteVector(CowBytes::Borrowed(data))
+    }
+
+}
+
+/* describes the bytes as UTF-8 text; invalid sequences get replaced in
+ * the text field and the offset of the first one is reported */
+fn text_info<'x>(
+    data: &[u8],
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let a = xc.get_main_allocator();
+    let mut ti = Record::new(&TEXT_INFO, a)?;
+    let mut decoder = Utf8Decoder::new();
+    let first_error = decoder.feed(data)
+        .and_then(|_| decoder.finish())
+        .err();
+    ti.set_field("valid_utf8", DataCell::from_u64(first_error.is_none() as u64));
+    if let Some(e) = first_error {
+        ti.set_field("invalid_offset", DataCell::from_u64(e.offset()));
     }
+    let mut text = xc.byte_vector();
+    let replacements = {
+        let mut w = Utf8LossyWriter::new(&mut text);
+        w.write_all(data, xc)?;
+        w.finish(xc)?;
+        w.replacement_count()
+    };
+    ti.set_field("replacements", DataCell::from_u64(replacements));
+    ti.set_field("text", DataCell::ByteVector(
+            Rc::new(a, RefCell::new(ByteVector(CowBytes::Owned(text))))?));
+    Ok(DataCell::Record(Rc::new(a, RefCell::new(ti))?))
+}
+
+fn byte_slice_property<'x>(
+    data: &[u8],
+    property_name: &str,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    match property_name {
+        n if is_property(n, BYTE_LEN_ALIASES) => {
+            let v = data.len().try_into().unwrap();
+            Ok(DataCell::U64(U64Cell::new(v)))
+        },
+        "text_info" => text_info(data, xc),
+        _ => Err(Error::NotApplicable)
+    }
+}
 
+fn output_byte_slice_as_human_readable<'w, 'x>(
+    data: &[u8],
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    write!(out, "b\"")?;
+    output_byte_slice_as_human_readable_text(data, out, xc)?;
+    write!(out, "\"")?;
+    Ok(())
 }
+
 impl<'a> DataCellOpsMut for ByteVector<'a> {
 
     fn get_property_mut<'x>(
         &mut self,
         property_name: &str,
-        _xc: &mut ExecutionContext<'x>,
+        xc: &mut ExecutionContext<'x>,
     ) -> Result<DataCell<'x>, Error<'x>> {
-        match property_name {
-            "len" | "length" | "count" | "size" => {
-                let v = self.0.len().try_into().unwrap();
-                Ok(DataCell::U64(U64Cell::new(v)))
-            },
-            _ => Err(Error::NotApplicable)
-        }
+        byte_slice_property(self.0.as_slice(), property_name, xc)
     }
 
     fn output_as_human_readable_mut<'w, 'x>(
@@ -272,10 +700,16 @@ impl<'a> DataCellOpsMut for ByteVector<'a> {
         out: &mut (dyn Write + 'w),
         xc: &mut ExecutionContext<'x>,
     ) -> Result<(), Error<'x>> {
-        write!(out, "b\"")?;
-        output_byte_slice_as_human_readable_text(self.0.as_slice(), out, xc)?;
-        write!(out, "\"")?;
-        Ok(())
+        output_byte_slice_as_human_readable(self.0.as_slice(), out, xc)
+    }
+
+    fn output_as_hex_dump_mut<'w, 'x>(
+        &mut self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        dump::output_hex_dump_rows(self.0.as_slice(), 0, options, out)
     }
 
 }
@@ -292,7 +726,7 @@ impl<'a, T: DataCellOps> DataCellOpsMut for DCOVector<'a, T> {
         _xc: &mut ExecutionContext<'x>,
     ) -> Result<DataCell<'x>, Error<'x>> {
         match property_name {
-            "len" | "length" | "count" => {
+            n if is_property(n, COUNT_ALIASES) => {
                 let v = self.0.len().try_into().unwrap();
                 Ok(DataCell::U64(U64Cell::new(v)))
             },
@@ -315,6 +749,177 @@ impl<'a, T: DataCellOps> DataCellOpsMut for DCOVector<'a, T> {
 
 }
 
+/* Text *********************************************************************/
+/* text is shown in double quotes with backslash escapes for quotes,
+ * backslashes and control chars; other chars are written as they are */
+fn output_text_quoted<'w, 'x>(
+    text: &str,
+    out: &mut (dyn Write + 'w),
+    xc: &mut ExecutionContext<'x>,
+) -> Result<(), Error<'x>> {
+    out.write_all(b"\"", xc)?;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if !matches!(c, '"' | '\\' | '\0'..='\x1F' | '\x7F') {
+            continue;
+        }
+        out.write_all(&text.as_bytes()[start..i], xc)?;
+        match c {
+            '"' => out.write_all(b"\\\"", xc)?,
+            '\\' => out.write_all(b"\\\\", xc)?,
+            '\n' => out.write_all(b"\\n", xc)?,
+            '\r' => out.write_all(b"\\r", xc)?,
+            '\t' => out.write_all(b"\\t", xc)?,
+            _ => write!(out, "\\x{:02X}", c as u32)?,
+        }
+        start = i + c.len_utf8();
+    }
+    out.write_all(&text.as_bytes()[start..], xc)?;
+    out.write_all(b"\"", xc)?;
+    Ok(())
+}
+
+fn text_with_mapped_chars<'x, F, I>(
+    text: &str,
+    map: F,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>>
+where F: Fn(char) -> I, I: Iterator<Item = char> {
+    let mut s = xc.string();
+    for c in text.chars().flat_map(map) {
+        s.push(c)?;
+    }
+    Ok(DataCell::Text(xc.rc(RefCell::new(s))?))
+}
+
+/* len counts chars while size counts bytes */
+impl<'a> DataCellOpsMut for String<'a> {
+
+    fn get_property_mut<'x>(
+        &mut self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let text = self.as_str();
+        match property_name {
+            n if is_property(n, COUNT_ALIASES) => Ok(DataCell::from_u64(text.chars().count() as u64)),
+            n if is_property(n, &["size"]) => Ok(DataCell::from_u64(text.len() as u64)),
+            "is_empty" => Ok(DataCell::Bool(text.is_empty())),
+            "to_upper" => text_with_mapped_chars(text, char::to_uppercase, xc),
+            "to_lower" => text_with_mapped_chars(text, char::to_lowercase, xc),
+            "bytes" => Ok(DataCell::from_byte_slice(xc.get_main_allocator(), text.as_bytes())?),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable_mut<'w, 'x>(
+        &mut self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        output_text_quoted(self.as_str(), out, xc)
+    }
+
+    fn output_as_hex_dump_mut<'w, 'x>(
+        &mut self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        dump::output_hex_dump_rows(self.as_bytes(), 0, options, out)
+    }
+
+}
+
+/* DCOMap *******************************************************************/
+/* entries with keys only known at run time, kept in insertion order */
+#[derive(Debug)]
+pub struct DCOMap<'a>(pub HashMap<'a, String<'a>, DataCell<'a>>);
+
+impl<'a> DCOMap<'a> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        DCOMap(HashMap::with_insertion_order(allocator))
+    }
+
+    /* an existing key keeps its place and gets the new value */
+    pub fn insert(
+        &mut self,
+        key: &str,
+        value: DataCell<'a>,
+    ) -> Result<(), AllocError> {
+        let key = String::from_str(key, self.0.allocator())?;
+        self.0.insert(key, value).map_err(|(e, _)| e)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&DataCell<'a>> {
+        self.0.get(key)
+    }
+
+    /* values are shared with the map rather than copied */
+    fn get_member(
+        &self,
+        name: &str,
+        xc: &mut ExecutionContext<'a>,
+    ) -> Result<DataCell<'a>, Error<'a>> {
+        match name {
+            "values" => {
+                let mut v = xc.vector();
+                v.reserve(self.0.len())?;
+                for c in self.0.values() {
+                    v.push(c.clone())?;
+                }
+                Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(v)))?))
+            },
+            _ => self.get(name).ok_or(Error::NotApplicable)?.set_or_not_applicable(),
+        }
+    }
+
+}
+
+impl<'a> DataCellOpsMut for DCOMap<'a> {
+
+    fn get_property_mut<'x>(
+        &mut self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            n if is_property(n, COUNT_ALIASES) => Ok(DataCell::from_u64(self.0.len() as u64)),
+            "is_empty" => Ok(DataCell::Bool(self.0.is_empty())),
+            "keys" => {
+                let mut v = xc.vector();
+                v.reserve(self.0.len())?;
+                for k in self.0.keys() {
+                    v.push(DataCell::from_str(xc.get_main_allocator(), k.as_str())?)?;
+                }
+                Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(v)))?))
+            },
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable_mut<'w, 'x>(
+        &mut self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        out.write_all(b"{", xc)?;
+        for (i, (k, v)) in self.0.iter().enumerate() {
+            if i != 0 {
+                out.write_all(b", ", xc)?;
+            }
+            out.write_all(k.as_bytes(), xc)?;
+            out.write_all(b": ", xc)?;
+            v.output_as_human_readable(out, xc)?;
+        }
+        out.write_all(b"}", xc)?;
+        Ok(())
+    }
+
+}
+
 /* Record *******************************************************************/
 #[derive(Debug)]
 pub struct RecordDesc<'a> {
@@ -411,15 +1016,22 @@ impl<'a> DataCellOpsMut for Record<'a> {
 }
 
 /* DataCell *****************************************************************/
-#[derive(Debug)]
+/* cloning is shallow: the clone shares the allocated data */
+#[derive(Clone, Debug)]
 pub enum DataCell<'d> {
     Nothing,
     U64(U64Cell),
+    U128(U128Cell),
+    I64(I64Cell),
+    F64(F64Cell),
+    Bool(bool),
     ByteVector(Rc<'d, RefCell<ByteVector<'d>>>),
+    Text(Rc<'d, RefCell<String<'d>>>),
     StaticId(&'d str),
     Dyn(Rc<'d, dyn DataCellOps + 'd>),
     CellVector(Rc<'d, RefCell<DCOVector<'d, DataCell<'d>>>>),
     Record(Rc<'d, RefCell<Record<'d>>>),
+    Map(Rc<'d, RefCell<DCOMap<'d>>>),
     ByteStream(Rc<'d, RefCell<dyn Stream + 'd>>),
 }
 
@@ -443,6 +1055,28 @@ impl<'d> DataCell<'d> {
         Self::from_u64_cell(U64Cell::new(n))
     }
 
+    pub fn from_u128_cell(n: U128Cell) -> Self {
+        DataCell::U128(n)
+    }
+    pub fn from_u128(n: u128) -> Self {
+        Self::from_u128_cell(U128Cell::new(n))
+    }
+
+    pub fn from_i64_cell(n: I64Cell) -> Self {
+        DataCell::I64(n)
+    }
+    pub fn from_i64(n: i64) -> Self {
+        Self::from_i64_cell(I64Cell::new(n))
+    }
+
+    pub fn from_f64(v: f64) -> Self {
+        DataCell::F64(F64Cell::new(v))
+    }
+
+    pub fn from_bool(b: bool) -> Self {
+        DataCell::Bool(b)
+    }
+
     pub fn from_static_id(s: &'d str) -> Self {
         DataCell::StaticId(s)
     }
@@ -453,6 +1087,100 @@ impl<'d> DataCell<'d> {
     ) -> Result<Self, AllocError> {
         Ok(DataCell::ByteVector(Rc::new(allocator, RefCell::new(ByteVector::from_byte_slice(allocator, data)?))?))
     }
+
+    pub fn from_str(
+        allocator: AllocatorRef<'d>,
+        text: &str,
+    ) -> Result<Self, AllocError> {
+        Ok(DataCell::Text(Rc::new(allocator, RefCell::new(String::from_str(text, allocator)?))?))
+    }
+
+    /* Text for valid UTF-8, ByteVector otherwise; meant for names stored
+     * in formats that do not say what encoding they use */
+    pub fn from_utf8_or_bytes(
+        allocator: AllocatorRef<'d>,
+        data: &[u8],
+    ) -> Result<Self, AllocError> {
+        match core::str::from_utf8(data) {
+            Ok(text) => Self::from_str(allocator, text),
+            Err(_) => Self::from_byte_slice(allocator, data),
+        }
+    }
+
+    /* no copy is made: the cell just refers to the bytes */
+    pub fn from_borrowed_bytes(
+        allocator: AllocatorRef<'d>,
+        data: &'d [u8],
+    ) -> Result<Self, AllocError> {
+        Ok(DataCell::ByteVector(Rc::new(allocator, RefCell::new(ByteVector::borrowed(data)))?))
+    }
+
+    pub fn from_map(
+        allocator: AllocatorRef<'d>,
+        map: DCOMap<'d>,
+    ) -> Result<Self, AllocError> {
+        Ok(DataCell::Map(Rc::new(allocator, RefCell::new(map))?))
+    }
+
+    /* like get_property, then falls back to the parts of the cell that
+     * can only be handed out shared, such as record fields and map entries
+     * by name */
+    pub fn get_member(
+        &self,
+        name: &str,
+        xc: &mut ExecutionContext<'d>,
+    ) -> Result<DataCell<'d>, Error<'d>> {
+        match self.get_property(name, xc) {
+            Err(Error::NotApplicable) => {},
+            r => return r,
+        }
+        match self {
+            DataCell::Record(r) => {
+                let r = r.try_borrow()?;
+                let i = r.desc.field_index(name).ok_or(Error::NotApplicable)?;
+                r.data.as_slice()[i].set_or_not_applicable()
+            },
+            DataCell::Map(m) => m.try_borrow()?.get_member(name, xc),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    /* cell[index]: vector items, bytes and record fields (in declaration
+     * order) by position and map entries by key; unset fields and indexes
+     * out of range are not applicable */
+    pub fn get_item(
+        &self,
+        index: &DataCell<'_>,
+    ) -> Result<DataCell<'d>, Error<'d>> {
+        let i = match index {
+            DataCell::U64(n) => usize::try_from(n.n).map_err(|_| Error::NotApplicable)?,
+            DataCell::U128(n) => usize::try_from(n.n).map_err(|_| Error::NotApplicable)?,
+            DataCell::Text(key) => return match self {
+                DataCell::Map(m) => m.try_borrow()?.get(key.try_borrow()?.as_str())
+                    .ok_or(Error::NotApplicable)?.set_or_not_applicable(),
+                _ => Err(Error::NotApplicable),
+            },
+            _ => return Err(Error::NotApplicable),
+        };
+        match self {
+            DataCell::CellVector(v) => v.try_borrow()?.0.as_slice().get(i)
+                .ok_or(Error::NotApplicable)?.set_or_not_applicable(),
+            DataCell::Record(r) => r.try_borrow()?.data.as_slice().get(i)
+                .ok_or(Error::NotApplicable)?.set_or_not_applicable(),
+            DataCell::ByteVector(v) => v.try_borrow()?.0.as_slice().get(i)
+                .map(|&b| DataCell::from_u64_cell(U64Cell::hex(b as u64)))
+                .ok_or(Error::NotApplicable),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn set_or_not_applicable(&self) -> Result<DataCell<'d>, Error<'d>> {
+        if self.is_nothing() {
+            Err(Error::NotApplicable)
+        } else {
+            Ok(self.clone())
+        }
+    }
 }
 
 impl<'d> DataCellOps for DataCell<'d> {
@@ -464,9 +1192,19 @@ impl<'d> DataCellOps for DataCell<'d> {
     ) -> Result<DataCell<'x>, Error<'x>> {
         match self {
             DataCell::U64(v) => v.get_property(property_name, xc),
+            DataCell::U128(v) => v.get_property(property_name, xc),
+            DataCell::I64(v) => v.get_property(property_name, xc),
+            DataCell::F64(v) => v.get_property(property_name, xc),
             DataCell::ByteVector(v) => v.get_property(property_name, xc),
+            DataCell::Text(v) => v.get_property(property_name, xc),
             DataCell::CellVector(v) => v.get_property(property_name, xc),
+            DataCell::Map(v) => v.get_property(property_name, xc),
             DataCell::Dyn(o) => o.get_property(property_name, xc),
+            DataCell::ByteStream(s) => {
+                let mut s = s.try_borrow_mut()?;
+                content_stream::ContentStream::new(&mut *s)
+                    .get_property_mut(property_name, xc)
+            },
             _ => Err(Error::NotApplicable)
         }
     }
@@ -479,7 +1217,15 @@ impl<'d> DataCellOps for DataCell<'d> {
         match self {
             DataCell::Nothing => Ok(()),
             DataCell::U64(v) => v.output_as_human_readable(w, xc),
+            DataCell::U128(v) => v.output_as_human_readable(w, xc),
+            DataCell::I64(v) => v.output_as_human_readable(w, xc),
+            DataCell::F64(v) => v.output_as_human_readable(w, xc),
+            DataCell::Bool(b) => {
+                w.write_all(if *b { b"true" } else { b"false" }, xc)
+                    .map_err(|e| Error::Output(e.to_error()))
+            },
             DataCell::ByteVector(v) => v.output_as_human_readable(w, xc),
+            DataCell::Text(v) => v.output_as_human_readable(w, xc),
             DataCell::StaticId(s) => {
                 w.write_all(s.as_bytes(), xc)
                     .map_err(|e| Error::Output(e.to_error()))
@@ -487,7 +1233,53 @@ impl<'d> DataCellOps for DataCell<'d> {
             DataCell::Dyn(v) => v.deref().output_as_human_readable(w, xc),
             DataCell::CellVector(v) => v.deref().output_as_human_readable(w, xc),
             DataCell::Record(v) => v.deref().output_as_human_readable(w, xc),
-            DataCell::ByteStream(_v) => panic!(),
+            DataCell::Map(v) => v.deref().output_as_human_readable(w, xc),
+            DataCell::ByteStream(s) => {
+                let mut s = s.try_borrow_mut()?;
+                write!(w, "b\"")?;
+                content_stream::ContentStream::new(&mut *s)
+                    .output_as_human_readable_mut(w, xc)?;
+                write!(w, "\"")?;
+                Ok(())
+            },
+        }
+    }
+
+    fn output_as_hex_dump<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        match self {
+            DataCell::ByteVector(v) => v.output_as_hex_dump(w, xc, options),
+            DataCell::Text(v) => v.output_as_hex_dump(w, xc, options),
+            DataCell::Dyn(v) => v.deref().output_as_hex_dump(w, xc, options),
+            DataCell::ByteStream(s) => {
+                let mut s = s.try_borrow_mut()?;
+                dump::output_stream_hex_dump(&mut *s, options, w, xc)
+            },
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_json<'w, 'x>(
+        &self,
+        w: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        json::output_as_json_with_options(self, options, w, xc)
+    }
+
+    fn get_property_byte_range(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'_>,
+    ) -> Option<(u64, u64)> {
+        match self {
+            DataCell::Dyn(o) => o.get_property_byte_range(property_name, xc),
+            _ => None
         }
     }
 
@@ -521,6 +1313,15 @@ impl<T: Stream> DataCellOpsMut for T {
         Ok(())
     }
 
+    fn output_as_hex_dump_mut<'w, 'x>(
+        &mut self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        options: &HexDumpOptions,
+    ) -> Result<(), Error<'x>> {
+        dump::output_stream_hex_dump(self, options, out, xc)
+    }
+
 }
 
 #[cfg(test)]
@@ -548,6 +1349,30 @@ mod tests {
         assert_eq!(Error::NotApplicable, Abc().get_property("zilch", &mut xc).unwrap_err());
     }
 
+    #[test]
+    fn borrowed_bytes() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 0x200];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let data = *b"hi\n";
+        let c = DataCell::from_borrowed_bytes(a.to_ref(), &data).unwrap();
+        match &c {
+            DataCell::ByteVector(v) => assert_eq!(v.borrow().0.as_slice().as_ptr(), data.as_ptr()),
+            _ => panic!("unexpected bytes cell"),
+        }
+        let len = c.get_property("len", &mut xc).unwrap();
+        match len {
+            DataCell::U64(n) => assert_eq!(n.n, 3),
+            _ => panic!("unexpected len cell"),
+        }
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"b\"hi\\x0A\"");
+        let ti = c.get_property("text_info", &mut xc);
+        assert!(ti.is_ok());
+    }
+
     #[test]
     fn record_human_readable() {
         use crate::mm::{ Allocator, BumpAllocator };
@@ -606,5 +1431,216 @@ mod tests {
                        "Rectangle(width: 9, height: +0x0A, mode: WEIRDO)");
         }
     }
-}
 
+    fn u64_prop(c: &DataCell<'_>, name: &str) -> Option<u64> {
+        let mut xc = ExecutionContext::nop();
+        match c.get_property(name, &mut xc) {
+            Ok(DataCell::U64(v)) => Some(v.n),
+            Ok(_) => panic!("not u64"),
+            Err(e) => { assert_eq!(e, Error::NotApplicable); None },
+        }
+    }
+
+    #[test]
+    fn signed_float_and_bool_cells() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        fn text<'x>(c: &DataCell<'_>, xc: &mut ExecutionContext<'x>) -> Vector<'x, u8> {
+            let mut o = xc.byte_vector();
+            c.output_as_human_readable(&mut o, xc).unwrap();
+            o
+        }
+        let n = DataCell::from_i64(i64::MIN);
+        assert_eq!(text(&n, &mut xc).as_slice(), b"-9223372036854775808");
+        assert_eq!(u64_prop(&n, "abs"), Some(1 << 63));
+        assert!(matches!(n.get_property("is_negative", &mut xc), Ok(DataCell::Bool(true))));
+        let f = DataCell::from_f64(-0.1);
+        assert_eq!(text(&f, &mut xc).as_slice(), b"-0.1");
+        assert_eq!(text(&f.get_property("abs", &mut xc).unwrap(), &mut xc).as_slice(), b"0.1");
+        assert!(matches!(f.get_property("is_negative", &mut xc), Ok(DataCell::Bool(true))));
+        assert_eq!(text(&DataCell::from_f64(2.0), &mut xc).as_slice(), b"2.0");
+        assert_eq!(text(&DataCell::from_bool(false), &mut xc).as_slice(), b"false");
+        assert!(matches!(DataCell::from_bool(true).get_property("abs", &mut xc), Err(Error::NotApplicable)));
+    }
+
+    #[test]
+    fn text_cells() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let t = DataCell::from_str(a.to_ref(), "\"ăx\"\\\t\x01").unwrap();
+        let mut o = xc.byte_vector();
+        t.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), "\"\\\"ăx\\\"\\\\\\t\\x01\"");
+        assert_eq!(u64_prop(&t, "len"), Some(7));
+        assert_eq!(u64_prop(&t, "size"), Some(8));
+        let mut o = xc.byte_vector();
+        t.get_property("to_upper", &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), "\"\\\"ĂX\\\"\\\\\\t\\x01\"");
+        assert!(matches!(DataCell::from_utf8_or_bytes(a.to_ref(), b"ok").unwrap(), DataCell::Text(_)));
+        assert!(matches!(DataCell::from_utf8_or_bytes(a.to_ref(), b"\xFF").unwrap(), DataCell::ByteVector(_)));
+    }
+
+    #[test]
+    fn map_cells() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut m = DCOMap::new(a.to_ref());
+        m.insert("zeta", DataCell::from_u64(1)).unwrap();
+        m.insert("len", DataCell::from_str(a.to_ref(), "x").unwrap()).unwrap();
+        m.insert("alpha", DataCell::from_bool(true)).unwrap();
+        m.insert("zeta", DataCell::from_u64(2)).unwrap();
+        let c = DataCell::from_map(a.to_ref(), m).unwrap();
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), "{zeta: 2, len: \"x\", alpha: true}");
+        assert_eq!(u64_prop(&c, "len"), Some(3));
+        assert_eq!(u64_prop(&c, "zeta"), None);
+        assert_eq!(u64_prop(&c.get_member("zeta", &mut xc).unwrap(), "bswap16"), Some(0x0200));
+        assert!(matches!(c.get_member("len", &mut xc), Ok(DataCell::U64(_))));
+        assert!(matches!(c.get_member("alpha", &mut xc), Ok(DataCell::Bool(true))));
+        assert!(matches!(c.get_member("beta", &mut xc), Err(Error::NotApplicable)));
+        let mut o = xc.byte_vector();
+        c.get_property("keys", &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), "[\"zeta\"\"len\"\"alpha\"]");
+        let mut o = xc.byte_vector();
+        c.get_member("values", &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), "[2\"x\"true]");
+    }
+
+    #[test]
+    fn property_names_ignore_case() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let b = DataCell::from_borrowed_bytes(a.to_ref(), b"\x7FELF").unwrap();
+        assert_eq!(u64_prop(&b, "LEN"), Some(4));
+        assert_eq!(u64_prop(&b, "Size"), Some(4));
+        let mut v = xc.vector();
+        v.push(DataCell::from_static_id("x")).unwrap();
+        let v = DataCell::CellVector(xc.rc(RefCell::new(DCOVector(v))).unwrap());
+        assert_eq!(u64_prop(&v, "Len"), Some(1));
+        assert_eq!(u64_prop(&v, "size"), None);
+        let t = DataCell::from_str(a.to_ref(), "ăx").unwrap();
+        assert_eq!(u64_prop(&t, "Length"), Some(2));
+        assert_eq!(u64_prop(&t, "SIZE"), Some(3));
+        let m = DataCell::from_map(a.to_ref(), DCOMap::new(a.to_ref())).unwrap();
+        assert_eq!(u64_prop(&m, "COUNT"), Some(0));
+    }
+
+    #[test]
+    fn items_by_index() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut v = xc.vector();
+        v.push(DataCell::from_u64(7)).unwrap();
+        v.push(DataCell::from_static_id("x")).unwrap();
+        let v = DataCell::CellVector(xc.rc(RefCell::new(DCOVector(v))).unwrap());
+        let i = |n| DataCell::from_u64(n);
+        assert!(matches!(v.get_item(&i(0)), Ok(DataCell::U64(n)) if n.n == 7));
+        assert!(matches!(v.get_item(&i(1)), Ok(DataCell::StaticId("x"))));
+        assert!(matches!(v.get_item(&i(2)), Err(Error::NotApplicable)));
+        assert!(matches!(v.get_item(&DataCell::from_bool(true)), Err(Error::NotApplicable)));
+
+        let b = DataCell::from_byte_slice(a.to_ref(), b"\x7FELF").unwrap();
+        assert!(matches!(b.get_item(&i(0)), Ok(DataCell::U64(n)) if n.n == 0x7F));
+        assert!(matches!(DataCell::from_borrowed_bytes(a.to_ref(), b"ab").unwrap().get_item(&i(1)), Ok(DataCell::U64(n)) if n.n == 0x62));
+
+        const DESC: RecordDesc<'static> = RecordDesc::new("r", &["a", "b", "c"]);
+        let mut r = Record::new(&DESC, a.to_ref()).unwrap();
+        r.set_field("a", DataCell::from_u64(1));
+        r.set_field("c", DataCell::from_u64(3));
+        let r = DataCell::Record(xc.rc(RefCell::new(r)).unwrap());
+        assert!(matches!(r.get_item(&i(2)), Ok(DataCell::U64(n)) if n.n == 3));
+        assert!(matches!(r.get_item(&i(1)), Err(Error::NotApplicable)));
+        assert!(matches!(r.get_member("a", &mut xc), Ok(DataCell::U64(n)) if n.n == 1));
+        assert!(matches!(r.get_member("b", &mut xc), Err(Error::NotApplicable)));
+
+        let mut m = DCOMap::new(a.to_ref());
+        m.insert("k", DataCell::from_u64(5)).unwrap();
+        let m = DataCell::from_map(a.to_ref(), m).unwrap();
+        let k = DataCell::from_str(a.to_ref(), "k").unwrap();
+        assert!(matches!(m.get_item(&k), Ok(DataCell::U64(n)) if n.n == 5));
+        assert!(matches!(m.get_item(&i(0)), Err(Error::NotApplicable)));
+    }
+
+    #[test]
+    fn u64_byte_swaps() {
+        let c = DataCell::from_u64_cell(U64Cell::hex(0x3E00));
+        assert_eq!(u64_prop(&c, "bswap16"), Some(0x003E));
+        assert_eq!(u64_prop(&c, "bswap32"), Some(0x003E0000));
+        assert_eq!(u64_prop(&c, "bswap64"), Some(0x003E000000000000));
+        let c = DataCell::from_u64(0x0102030405060708);
+        #[cfg(target_endian = "little")] {
+            assert_eq!(u64_prop(&c, "to_le"), Some(0x0102030405060708));
+            assert_eq!(u64_prop(&c, "to_be"), Some(0x0807060504030201));
+        }
+        #[cfg(target_endian = "big")] {
+            assert_eq!(u64_prop(&c, "to_le"), Some(0x0807060504030201));
+            assert_eq!(u64_prop(&c, "to_be"), Some(0x0102030405060708));
+        }
+        assert_eq!(u64_prop(&c, "zilch"), None);
+        assert_eq!(u64_prop(&DataCell::from_u64(0x12345), "bswap16"), None);
+        assert_eq!(u64_prop(&DataCell::from_u64(0x12345), "bswap32"), Some(0x45230100));
+    }
+
+    #[test]
+    fn u64_byte_swap_keeps_format() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_u64_cell(U64Cell::hex(0x0102));
+        let mut o = xc.byte_vector();
+        c.get_property("bswap16", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"0x201");
+    }
+
+    #[test]
+    fn u128_halves_and_output() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_u128_cell(U128Cell::hex(0x0011_2233_4455_6677_8899_AABB_CCDD_EEFF));
+        assert_eq!(u64_prop(&c, "low"), Some(0x8899_AABB_CCDD_EEFF));
+        assert_eq!(u64_prop(&c, "high"), Some(0x0011_2233_4455_6677));
+        let mut o = xc.byte_vector();
+        c.get_property("bswap128", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"0xFFEEDDCCBBAA99887766554433221100");
+        let mut o = xc.byte_vector();
+        DataCell::from_u128(u128::MAX).output_as_json(&mut o, &mut xc, &JsonOptions::default()).unwrap();
+        assert_eq!(o.as_slice(), b"340282366920938463463374607431768211455");
+    }
+
+    #[test]
+    fn byte_vector_text_info() {
+        use crate::mm::{ Allocator, BumpAllocator };
+        let mut buffer = [0_u8; 2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let c = DataCell::from_byte_slice(a.to_ref(), b"ab\xFFc").unwrap();
+        let mut o = xc.byte_vector();
+        c.get_property("text_info", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(),
+            "text_info(valid_utf8: 0, invalid_offset: 2, replacements: 1, text: b\"ab\\xEF\\xBF\\xBDc\")");
+
+        let c = DataCell::from_byte_slice(a.to_ref(), "\u{e9}t\u{e9}".as_bytes()).unwrap();
+        let mut o = xc.byte_vector();
+        c.get_property("text_info", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(),
+            "text_info(valid_utf8: 1, replacements: 0, text: b\"\\xC3\\xA9t\\xC3\\xA9\")");
+    }
+}
@@ -0,0 +1,301 @@
+use core::fmt;
+
+use crate::ExecutionContext;
+use crate::io::stream::Write;
+use crate::mm::Vector;
+
+use super::DataCell;
+use super::DataCellOps;
+use super::Error;
+use super::json;
+use super::json::JsonOptions;
+
+pub const DEFAULT_PLACEHOLDER: &str = "<redacted>";
+
+/* PathItem *****************************************************************/
+/* one step from a cell to a nested one: a record field, a map key or a
+ * vector item */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathItem<'p> {
+    Field(&'p str),
+    Index(usize),
+}
+
+impl PathItem<'_> {
+
+    /* "*" matches any item, decimal numbers match vector indexes */
+    pub fn matches(&self, pattern: &str) -> bool {
+        pattern == "*" || match *self {
+            PathItem::Field(name) => name == pattern,
+            PathItem::Index(i) => pattern.parse::<usize>() == Ok(i),
+        }
+    }
+
+}
+
+/* map keys only live while the map is borrowed, so the path to an entry
+ * is a copy of the path to the map with the key added */
+pub(crate) fn entry_path<'k, 'x>(
+    path: &[PathItem<'k>],
+    key: &'k str,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<Vector<'x, PathItem<'k>>, Error<'x>> {
+    let mut p = Vector::from_slice(path, xc.get_main_allocator())?;
+    p.push(PathItem::Field(key)).map_err(|(e, _)| e)?;
+    Ok(p)
+}
+
+/* pattern is a dot-separated list of path item patterns, as many as the
+ * path has items */
+pub fn path_matches(pattern: &str, path: &[PathItem<'_>]) -> bool {
+    let mut parts = pattern.split('.');
+    path.iter().all(|item| parts.next().is_some_and(|p| item.matches(p)))
+        && parts.next().is_none()
+}
+
+/* Redaction ****************************************************************/
+pub type RedactPredicate<'r> = dyn Fn(&[PathItem<'_>], &DataCell<'_>) -> bool + 'r;
+
+/* which values a report must not show: cells whose path matches one of the
+ * patterns or for which the predicate holds are replaced by the
+ * placeholder, along with everything nested in them */
+pub struct Redaction<'r> {
+    paths: &'r [&'r str],
+    predicate: Option<&'r RedactPredicate<'r>>,
+    placeholder: &'r str,
+}
+
+impl<'r> Redaction<'r> {
+
+    pub fn new(paths: &'r [&'r str]) -> Self {
+        Redaction { paths, predicate: None, placeholder: DEFAULT_PLACEHOLDER }
+    }
+
+    pub fn with_predicate(mut self, predicate: &'r RedactPredicate<'r>) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+
+    pub fn with_placeholder(mut self, placeholder: &'r str) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.predicate.is_none()
+    }
+
+    pub fn placeholder(&self) -> &'r str {
+        self.placeholder
+    }
+
+    pub fn matches(&self, path: &[PathItem<'_>], cell: &DataCell<'_>) -> bool {
+        self.paths.iter().any(|p| path_matches(p, path))
+            || self.predicate.is_some_and(|f| f(path, cell))
+    }
+
+    /* same text as DataCell::output_as_human_readable, with redacted
+     * values replaced; root gives the path of the cell itself, usually
+     * the property or expression it was computed from */
+    pub fn output_as_human_readable<'w, 'x>(
+        &self,
+        cell: &DataCell<'x>,
+        root: &str,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        let mut path = root_path(root, xc)?;
+        self.output_cell(cell, &mut path, out, xc)
+    }
+
+    /* as json::output_as_json_with_options, with redacted values replaced
+     * by the placeholder as a JSON string */
+    pub fn output_as_json<'w, 'x>(
+        &self,
+        cell: &DataCell<'x>,
+        root: &str,
+        options: &JsonOptions,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        let mut path = root_path(root, xc)?;
+        json::output_cell(cell, options, Some(self), &mut path, out, xc)
+    }
+
+    fn output_cell<'p, 'w, 'x: 'p>(
+        &self,
+        cell: &DataCell<'x>,
+        path: &mut Vector<'x, PathItem<'p>>,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        if self.matches(path.as_slice(), cell) {
+            out.write_all(self.placeholder.as_bytes(), xc)?;
+            return Ok(());
+        }
+        match cell {
+            DataCell::Record(r) => {
+                let r = r.try_borrow()?;
+                out.write_all(r.desc.record_name.as_bytes(), xc)?;
+                out.write_all(b"(", xc)?;
+                let mut first = true;
+                for (&name, v) in r.desc.field_names.iter().zip(r.data.as_slice()) {
+                    if v.is_nothing() { continue; }
+                    if first {
+                        first = false;
+                    } else {
+                        out.write_all(b", ", xc)?;
+                    }
+                    out.write_all(name.as_bytes(), xc)?;
+                    out.write_all(b": ", xc)?;
+                    path.push(PathItem::Field(name)).map_err(|(e, _)| e)?;
+                    self.output_cell(v, path, out, xc)?;
+                    path.pop();
+                }
+                out.write_all(b")", xc)?;
+                Ok(())
+            },
+            DataCell::CellVector(v) => {
+                let v = v.try_borrow()?;
+                out.write_all(b"[", xc)?;
+                for (i, c) in v.0.as_slice().iter().enumerate() {
+                    path.push(PathItem::Index(i)).map_err(|(e, _)| e)?;
+                    self.output_cell(c, path, out, xc)?;
+                    path.pop();
+                }
+                out.write_all(b"]", xc)?;
+                Ok(())
+            },
+            DataCell::Map(m) => {
+                let m = m.try_borrow()?;
+                out.write_all(b"{", xc)?;
+                for (i, (k, v)) in m.0.iter().enumerate() {
+                    if i != 0 {
+                        out.write_all(b", ", xc)?;
+                    }
+                    out.write_all(k.as_bytes(), xc)?;
+                    out.write_all(b": ", xc)?;
+                    let mut p = entry_path(path.as_slice(), k.as_str(), xc)?;
+                    self.output_cell(v, &mut p, out, xc)?;
+                }
+                out.write_all(b"}", xc)?;
+                Ok(())
+            },
+            _ => cell.output_as_human_readable(out, xc),
+        }
+    }
+
+}
+
+fn root_path<'p, 'x>(
+    root: &'p str,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<Vector<'x, PathItem<'p>>, Error<'x>> {
+    let mut path = Vector::new(xc.get_main_allocator());
+    for name in root.split('.').filter(|n| !n.is_empty()) {
+        path.push(PathItem::Field(name)).map_err(|(e, _)| e)?;
+    }
+    Ok(path)
+}
+
+impl fmt::Debug for Redaction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redaction")
+            .field("paths", &self.paths)
+            .field("predicate", &self.predicate.is_some())
+            .field("placeholder", &self.placeholder)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use crate::data_cell::DCOMap;
+    use crate::data_cell::DCOVector;
+    use crate::data_cell::Record;
+    use crate::data_cell::RecordDesc;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    const ENTRY: RecordDesc<'static> = RecordDesc::new(
+        "entry", &[ "name", "size", "secret" ]);
+    const REPORT: RecordDesc<'static> = RecordDesc::new(
+        "report", &[ "owner", "entries" ]);
+
+    #[test]
+    fn paths_and_predicate() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let a = xc.get_main_allocator();
+        let mut entries: Vector<DataCell> = Vector::new(a);
+        for (name, size) in [(&b"a.txt"[..], 3), (b"key.pem", 1700)] {
+            let mut e = Record::new(&ENTRY, a).unwrap();
+            e.set_field("name", DataCell::from_byte_slice(a, name).unwrap());
+            e.set_field("size", DataCell::from_u64(size));
+            e.set_field("secret", DataCell::from_static_id("hunter2"));
+            entries.push(DataCell::Record(xc.rc(RefCell::new(e)).unwrap())).unwrap();
+        }
+        let mut r = Record::new(&REPORT, a).unwrap();
+        r.set_field("owner", DataCell::from_static_id("alice"));
+        r.set_field("entries", DataCell::CellVector(
+                xc.rc(RefCell::new(DCOVector(entries))).unwrap()));
+        let report = DataCell::Record(xc.rc(RefCell::new(r)).unwrap());
+
+        let mut o = xc.byte_vector();
+        let patterns = [ "x.owner", "x.entries.*.secret", "x.entries.1.name" ];
+        Redaction::new(&patterns).output_as_human_readable(&report, "x", &mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "report(owner: <redacted>, entries: [",
+            "entry(name: b\"a.txt\", size: 3, secret: <redacted>)",
+            "entry(name: <redacted>, size: 1700, secret: <redacted>)])"));
+        let mut o = xc.byte_vector();
+        Redaction::new(&patterns).output_as_json(&report, "x", &JsonOptions::default(), &mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            r#"{"owner":"<redacted>","entries":["#,
+            r#"{"name":"YS50eHQ=","size":3,"secret":"<redacted>"},"#,
+            r#"{"name":"<redacted>","size":1700,"secret":"<redacted>"}]}"#));
+
+        let big = |path: &[PathItem<'_>], c: &DataCell<'_>| {
+            path.last() == Some(&PathItem::Field("size"))
+                && matches!(c, DataCell::U64(n) if n.n > 1000)
+        };
+        let mut o = xc.byte_vector();
+        Redaction::new(&[ "entries" ]).with_predicate(&big).with_placeholder("#")
+            .output_as_human_readable(&report, "", &mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(),
+            "report(owner: alice, entries: #)");
+
+        let mut o = xc.byte_vector();
+        Redaction::new(&[ "entries.0" ]).with_predicate(&big)
+            .output_as_human_readable(&report, "", &mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "report(owner: alice, entries: [<redacted>",
+            "entry(name: b\"key.pem\", size: <redacted>, secret: hunter2)])"));
+
+        assert!(path_matches("a.*.3", &[PathItem::Field("a"), PathItem::Field("b"), PathItem::Index(3)]));
+        assert!(!path_matches("a.*", &[PathItem::Field("a")]));
+        assert!(!path_matches("a", &[PathItem::Field("a"), PathItem::Index(0)]));
+        assert!(Redaction::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn map_entries_by_key() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let a = xc.get_main_allocator();
+        let mut m = DCOMap::new(a);
+        m.insert("user", DataCell::from_static_id("bob")).unwrap();
+        m.insert("token", DataCell::from_str(a, "t0k3n").unwrap()).unwrap();
+        let c = DataCell::from_map(a, m).unwrap();
+        let mut o = xc.byte_vector();
+        Redaction::new(&[ "env.token" ]).output_as_human_readable(&c, "env", &mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"{user: bob, token: <redacted>}");
+        let mut o = xc.byte_vector();
+        Redaction::new(&[ "env.token" ]).output_as_json(&c, "env", &JsonOptions::default(), &mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), br#"{"user":"bob","token":"<redacted>"}"#);
+    }
+}
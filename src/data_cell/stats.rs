@@ -0,0 +1,299 @@
+use core::cell::RefCell;
+use core::fmt::Write as FmtWrite;
+
+use crate::ExecutionContext;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::SeekFrom;
+use crate::io::stream::Write;
+use crate::mm::Vector;
+use crate::num::fmt::MiniFloatFmtPack;
+
+use super::DCOVector;
+use super::DataCell;
+use super::DataCellOps;
+use super::Error;
+use super::F64Cell;
+use super::json::JsonOptions;
+
+/* content is read in chunks of this size */
+const CHUNK_SIZE: usize = 0x1000;
+const LOG2_FRAC_BITS: u32 = 32;
+
+/* log2(x) with 32 fractional bits, for x > 0; the mantissa is squared
+ * once per fractional bit, kept with 62 fractional bits */
+fn log2_fx32(x: u64) -> u64 {
+    let int = 63 - x.leading_zeros();
+    let one = 1_u128 << 62;
+    let mut m = if int <= 62 {
+        (x as u128) << (62 - int)
+    } else {
+        (x as u128) >> (int - 62)
+    };
+    let mut frac = 0_u64;
+    for bit in (0..LOG2_FRAC_BITS).rev() {
+        m = (m * m) >> 62;
+        if m >= 2 * one {
+            m >>= 1;
+            frac |= 1 << bit;
+        }
+    }
+    ((int as u64) << LOG2_FRAC_BITS) | frac
+}
+
+/* Entropy ******************************************************************/
+/* Shannon entropy in bits per byte, kept in thousandths and shown with 3
+ * decimals; the bits property gives it as a float for arithmetic */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Entropy {
+    pub millibits: u64,
+}
+
+impl DataCellOps for Entropy {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "millibits" => Ok(DataCell::from_u64(self.millibits)),
+            "bits" => Ok(DataCell::F64(F64Cell::with_fmt(
+                self.millibits as f64 / 1000.0,
+                MiniFloatFmtPack::fixed(3).unwrap()))),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        write!(out, "{}.{:03}", self.millibits / 1000, self.millibits % 1000)?;
+        Ok(())
+    }
+
+    /* the same text is a valid JSON number */
+    fn output_as_json<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+        _options: &JsonOptions,
+    ) -> Result<(), Error<'x>> {
+        self.output_as_human_readable(out, xc)
+    }
+
+}
+
+/* ByteHistogram ************************************************************/
+/* count of each byte value; the text output lists the values that occur */
+#[derive(Debug)]
+pub struct ByteHistogram {
+    counts: [u64; 256],
+    total: u64,
+}
+
+impl Default for ByteHistogram {
+    fn default() -> Self {
+        ByteHistogram::new()
+    }
+}
+
+impl ByteHistogram {
+
+    pub fn new() -> Self {
+        ByteHistogram { counts: [0; 256], total: 0 }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        for &b in data {
+            self.counts[b as usize] += 1;
+        }
+        self.total += data.len() as u64;
+    }
+
+    /* whole content of the stream */
+    pub fn load<'x, T: ?Sized + RandomAccessRead>(
+        stream: &mut T,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<Self, Error<'x>> {
+        let mut h = ByteHistogram::new();
+        if let Some(content) = stream.content_slice() {
+            for chunk in content.chunks(CHUNK_SIZE) {
+                xc.check_cancelled()?;
+                h.feed(chunk);
+            }
+            return Ok(h);
+        }
+        stream.seek(SeekFrom::Start(0), xc)?;
+        let mut buffer = [0_u8; CHUNK_SIZE];
+        loop {
+            let n = stream.read_uninterrupted(&mut buffer, xc)?;
+            h.feed(&buffer[0..n]);
+            if n < buffer.len() { break; }
+        }
+        Ok(h)
+    }
+
+    pub fn count(&self, b: u8) -> u64 {
+        self.counts[b as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn distinct(&self) -> usize {
+        self.counts.iter().filter(|&&c| c != 0).count()
+    }
+
+    /* None for no data */
+    pub fn entropy(&self) -> Option<Entropy> {
+        if self.total == 0 {
+            return None;
+        }
+        let log_total = log2_fx32(self.total);
+        let sum: u128 = self.counts.iter()
+            .filter(|&&c| c != 0)
+            .map(|&c| c as u128 * (log_total - log2_fx32(c)) as u128)
+            .sum();
+        let bits = sum / self.total as u128;
+        let half = 1_u128 << (LOG2_FRAC_BITS - 1);
+        Some(Entropy { millibits: ((bits * 1000 + half) >> LOG2_FRAC_BITS) as u64 })
+    }
+
+    fn counts_cell<'x>(
+        &self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        let mut v: Vector<'x, DataCell<'x>> = Vector::new(xc.get_main_allocator());
+        v.reserve(self.counts.len())?;
+        for &c in self.counts.iter() {
+            v.push(DataCell::from_u64(c))?;
+        }
+        Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(v)))?))
+    }
+
+}
+
+fn entropy_cell<'x>(
+    e: Entropy,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    crate::dyn_rc!(entropy_rc, DataCellOps);
+    Ok(DataCell::Dyn(entropy_rc(xc.rc(e)?)))
+}
+
+impl DataCellOps for ByteHistogram {
+
+    fn get_property<'x>(
+        &self,
+        property_name: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> Result<DataCell<'x>, Error<'x>> {
+        match property_name {
+            "total" => Ok(DataCell::from_u64(self.total)),
+            "distinct" => Ok(DataCell::from_u64(self.distinct() as u64)),
+            "counts" => self.counts_cell(xc),
+            "entropy" => entropy_cell(self.entropy().ok_or(Error::NotApplicable)?, xc),
+            _ => Err(Error::NotApplicable),
+        }
+    }
+
+    fn output_as_human_readable<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        _xc: &mut ExecutionContext<'x>,
+    ) -> Result<(), Error<'x>> {
+        write!(out, "byte_histogram(total: {}", self.total)?;
+        for (b, &c) in self.counts.iter().enumerate() {
+            if c != 0 {
+                write!(out, ", 0x{:02X}: {}", b, c)?;
+            }
+        }
+        write!(out, ")")?;
+        Ok(())
+    }
+
+}
+
+pub fn byte_histogram<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    crate::dyn_rc!(byte_histogram_rc, DataCellOps);
+    let h = ByteHistogram::load(stream, xc)?;
+    Ok(DataCell::Dyn(byte_histogram_rc(xc.rc(h)?)))
+}
+
+/* NotApplicable for empty content */
+pub fn entropy<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let e = ByteHistogram::load(stream, xc)?.entropy().ok_or(Error::NotApplicable)?;
+    entropy_cell(e, xc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn log2_fixed_point() {
+        assert_eq!(log2_fx32(1), 0);
+        assert_eq!(log2_fx32(256), 8 << 32);
+        assert_eq!(log2_fx32(u64::MAX) >> 32, 63);
+        // log2(3) = 1.5849625007...
+        assert_eq!((log2_fx32(3) * 1_000_000_000) >> 32, 1_584_962_500);
+    }
+
+    #[test]
+    fn entropy_of_known_distributions() {
+        let mut h = ByteHistogram::new();
+        assert_eq!(h.entropy(), None);
+        h.feed(b"aaaa");
+        assert_eq!(h.entropy(), Some(Entropy { millibits: 0 }));
+        h.feed(b"bbbb");
+        assert_eq!(h.entropy(), Some(Entropy { millibits: 1000 }));
+        let mut h = ByteHistogram::new();
+        for b in 0..=255_u8 {
+            h.feed(&[b, b]);
+        }
+        assert_eq!(h.entropy(), Some(Entropy { millibits: 8000 }));
+        assert_eq!(h.distinct(), 256);
+        let mut h = ByteHistogram::new();
+        h.feed(b"abc");
+        // log2(3)
+        assert_eq!(h.entropy(), Some(Entropy { millibits: 1585 }));
+    }
+
+    #[test]
+    fn stream_properties() {
+        let mut buffer = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(b"hello\x00");
+        let h = byte_histogram(&mut s, &mut xc).unwrap();
+        let mut o = xc.byte_vector();
+        h.output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(),
+            "byte_histogram(total: 6, 0x00: 1, 0x65: 1, 0x68: 1, 0x6C: 2, 0x6F: 1)");
+        let mut o = xc.byte_vector();
+        h.get_property("entropy", &mut xc).unwrap().output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"2.252");
+        let mut o = xc.byte_vector();
+        h.get_property("entropy", &mut xc).unwrap().get_property("bits", &mut xc).unwrap()
+            .output_as_human_readable(&mut o, &mut xc).unwrap();
+        assert_eq!(o.as_slice(), b"2.252");
+        match h.get_property("counts", &mut xc).unwrap() {
+            DataCell::CellVector(v) => assert_eq!(v.borrow().0.len(), 256),
+            _ => panic!(),
+        }
+        let mut s = BufferAsROStream::new(b"");
+        assert!(matches!(entropy(&mut s, &mut xc), Err(Error::NotApplicable)));
+    }
+}
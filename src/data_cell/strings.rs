@@ -0,0 +1,222 @@
+use core::cell::RefCell;
+
+use crate::ExecutionContext;
+use crate::io::stream::RandomAccessRead;
+use crate::io::stream::SeekFrom;
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::Rc;
+use crate::mm::Vector;
+
+use super::DCOVector;
+use super::DataCell;
+use super::Error;
+use super::Record;
+use super::RecordDesc;
+use super::U64Cell;
+
+pub const DEFAULT_MIN_LEN: usize = 4;
+/* content is read in chunks of this size */
+const CHUNK_SIZE: usize = 0x1000;
+
+const STRING: RecordDesc<'static> = RecordDesc::new(
+    "string",
+    &["offset", "encoding", "text"]);
+
+/* StringsOptions ***********************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StringsOptions {
+    pub min_len: usize, // in chars
+    pub ascii: bool,
+    pub utf16le: bool,
+}
+
+impl Default for StringsOptions {
+    fn default() -> Self {
+        StringsOptions { min_len: DEFAULT_MIN_LEN, ascii: true, utf16le: true }
+    }
+}
+
+/* printable ASCII, tab included, as the strings tool takes it */
+pub fn is_printable(b: u8) -> bool {
+    (0x20..0x7F).contains(&b) || b == b'\t'
+}
+
+/* Run **********************************************************************/
+struct Run<'a> {
+    offset: u64,
+    text: Vector<'a, u8>,
+}
+
+impl<'a> Run<'a> {
+    fn new(allocator: AllocatorRef<'a>) -> Self {
+        Run { offset: 0, text: Vector::new(allocator) }
+    }
+    fn push(&mut self, b: u8, offset: u64) -> Result<(), (AllocError, u8)> {
+        if self.text.is_empty() {
+            self.offset = offset;
+        }
+        self.text.push(b)
+    }
+}
+
+/* StringScanner ************************************************************/
+/* finds the runs of printable chars in content fed in any number of pieces;
+ * UTF-16LE runs are searched at both even and odd offsets, each with a run
+ * of its own, and only cover chars below 0x7F */
+pub struct StringScanner<'a> {
+    options: StringsOptions,
+    offset: u64,
+    prev: Option<u8>,
+    ascii: Run<'a>,
+    utf16le: [Run<'a>; 2],
+    found: Vector<'a, (u64, DataCell<'a>)>,
+}
+
+impl<'a> StringScanner<'a> {
+
+    pub fn new(options: StringsOptions, allocator: AllocatorRef<'a>) -> Self {
+        StringScanner {
+            options,
+            offset: 0,
+            prev: None,
+            ascii: Run::new(allocator),
+            utf16le: [Run::new(allocator), Run::new(allocator)],
+            found: Vector::new(allocator),
+        }
+    }
+
+    fn flush(
+        found: &mut Vector<'a, (u64, DataCell<'a>)>,
+        run: &mut Run<'a>,
+        encoding: &'static str,
+        min_len: usize,
+    ) -> Result<(), Error<'a>> {
+        if !run.text.is_empty() && run.text.len() >= min_len {
+            let a = found.allocator();
+            let mut r = Record::new(&STRING, a)?;
+            r.set_field("offset", DataCell::from_u64_cell(U64Cell::hex(run.offset)));
+            r.set_field("encoding", DataCell::from_static_id(encoding));
+            r.set_field("text", DataCell::from_byte_slice(a, run.text.as_slice())?);
+            let cell = DataCell::Record(Rc::new(a, RefCell::new(r)).map_err(|(e, _)| e)?);
+            found.push((run.offset, cell)).map_err(|(e, _)| e)?;
+        }
+        run.text.truncate(0);
+        Ok(())
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), Error<'a>> {
+        let min_len = self.options.min_len;
+        for &b in data {
+            if self.options.ascii {
+                if is_printable(b) {
+                    self.ascii.push(b, self.offset).map_err(|(e, _)| e)?;
+                } else {
+                    Self::flush(&mut self.found, &mut self.ascii, "ascii", min_len)?;
+                }
+            }
+            if let (true, Some(p)) = (self.options.utf16le, self.prev) {
+                let run = &mut self.utf16le[((self.offset - 1) & 1) as usize];
+                if b == 0 && is_printable(p) {
+                    run.push(p, self.offset - 1).map_err(|(e, _)| e)?;
+                } else {
+                    Self::flush(&mut self.found, run, "utf16le", min_len)?;
+                }
+            }
+            self.prev = Some(b);
+            self.offset += 1;
+        }
+        Ok(())
+    }
+
+    /* string records in order of offset */
+    pub fn finish(mut self) -> Result<Vector<'a, DataCell<'a>>, Error<'a>> {
+        let min_len = self.options.min_len;
+        Self::flush(&mut self.found, &mut self.ascii, "ascii", min_len)?;
+        for run in self.utf16le.iter_mut() {
+            Self::flush(&mut self.found, run, "utf16le", min_len)?;
+        }
+        /* no two runs start at the same offset: an ASCII run needs a
+         * printable byte where a UTF-16LE one needs a zero */
+        self.found.as_mut_slice().sort_unstable_by_key(|e| core::cmp::Reverse(e.0));
+        let mut v = Vector::new(self.found.allocator());
+        v.reserve(self.found.len())?;
+        while let Some((_, cell)) = self.found.pop() {
+            v.push(cell).map_err(|(e, _)| e)?;
+        }
+        Ok(v)
+    }
+
+}
+
+pub fn strings_with_options<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    options: StringsOptions,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    let mut scanner = StringScanner::new(options, xc.get_main_allocator());
+    if let Some(content) = stream.content_slice() {
+        for chunk in content.chunks(CHUNK_SIZE) {
+            xc.check_cancelled()?;
+            scanner.feed(chunk)?;
+        }
+    } else {
+        stream.seek(SeekFrom::Start(0), xc)?;
+        let mut buffer = [0_u8; CHUNK_SIZE];
+        loop {
+            let n = stream.read_uninterrupted(&mut buffer, xc)?;
+            scanner.feed(&buffer[0..n])?;
+            if n < buffer.len() { break; }
+        }
+    }
+    let v = scanner.finish()?;
+    Ok(DataCell::CellVector(xc.rc(RefCell::new(DCOVector(v)))?))
+}
+
+pub fn strings<'x, T: ?Sized + RandomAccessRead>(
+    stream: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> Result<DataCell<'x>, Error<'x>> {
+    strings_with_options(stream, StringsOptions::default(), xc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_cell::DataCellOps;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    const CONTENT: &[u8] = b"\x01abc\x00hello\tworld\x00\x7F\x00w\x00i\x00d\x00e\x00\x00xyz1";
+
+    fn text<'x>(c: &DataCell<'x>, xc: &mut ExecutionContext<'x>) -> Vector<'x, u8> {
+        let mut o = xc.byte_vector();
+        c.output_as_human_readable(&mut o, xc).unwrap();
+        o
+    }
+
+    #[test]
+    fn ascii_and_utf16le_runs() {
+        let mut buffer = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(CONTENT);
+        let c = strings(&mut s, &mut xc).unwrap();
+        assert_eq!(core::str::from_utf8(text(&c, &mut xc).as_slice()).unwrap(), concat!(
+            "[string(offset: 0x05, encoding: ascii, text: b\"hello\\x09world\")",
+            "string(offset: 0x13, encoding: utf16le, text: b\"wide\")",
+            "string(offset: 0x1C, encoding: ascii, text: b\"xyz1\")]"));
+
+        // same runs when the content comes in small reads
+        let s = BufferAsROStream::new(CONTENT);
+        let mut r = ChunkedReader::new(s, 3, 0);
+        let opts = StringsOptions { min_len: 3, ascii: true, utf16le: false };
+        let c = strings_with_options(&mut r, opts, &mut xc).unwrap();
+        match c {
+            DataCell::CellVector(v) => assert_eq!(v.borrow().0.len(), 3),
+            _ => panic!(),
+        }
+    }
+}
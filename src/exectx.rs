@@ -1,13 +1,29 @@
+use crate::ext::ExtError;
+use crate::ext::ExtSlot;
+use crate::ext::ExtSlots;
 use crate::mm::AllocatorRef;
 use crate::mm::Box;
 use crate::mm::Rc;
 use crate::mm::AllocError;
 use crate::mm::Allocator;
+use crate::mm::BudgetAllocator;
 use crate::mm::NOP_ALLOCATOR;
 use crate::mm::String;
 use crate::mm::Vector;
 use crate::io::stream::Write;
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
 use crate::io::stream::NULL_STREAM;
+use crate::time::TimeSource;
+use core::cell::Ref;
+use core::cell::RefMut;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use core::ops::Deref;
+use core::task::Waker;
+use core::ops::DerefMut;
 
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
 pub enum LogLevel {
@@ -18,6 +34,64 @@ pub enum LogLevel {
     Debug,
 }
 
+impl LogLevel {
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            LogLevel::Critical => "crit",
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_str().fmt(f)
+    }
+}
+
+/* how log_event() lays out a line: plain is just the message while
+ * structured prefixes it with "[seq=N ts=T level=L target=M] ", ts being
+ * there only when a timestamp hook or a time source is set */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LogFormat {
+    Plain,
+    Structured,
+}
+
+/* CancellationToken ********************************************************/
+/* set from any thread to ask the work running with a context holding the
+ * token to stop; stream operations, decompressors and parsers poll it and
+ * fail with ErrorCode::Cancelled */
+#[derive(Debug, Default)]
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+}
+
+impl CancellationToken {
+
+    pub const fn new() -> Self {
+        CancellationToken { cancelled: AtomicBool::new(false) }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+}
+
 /* ExecutionContext *********************************************************/
 pub struct ExecutionContext<'a> {
     main_allocator: AllocatorRef<'a>,
@@ -25,7 +99,29 @@ pub struct ExecutionContext<'a> {
     log_stream: &'a mut (dyn Write + 'a),
     log_level: LogLevel,
     logging_error_mask: u8,
-    // TODO: some TLS-style storage
+    max_log_line_len: Option<usize>,
+    backoff_hook: Option<&'a (dyn Fn(u32) + 'a)>,
+    waker_hook: Option<&'a (dyn Fn(&Waker) + 'a)>,
+    log_bytes_left: Option<usize>,
+    fuel: Option<u64>,
+    log_format: LogFormat,
+    log_seq: u64,
+    timestamp_hook: Option<&'a (dyn Fn() -> u64 + 'a)>,
+    target_log_levels: &'a [(&'a str, LogLevel)],
+    time_source: Option<&'a (dyn TimeSource + 'a)>,
+    cancellation_token: Option<&'a CancellationToken>,
+    ext_slots: Option<&'a ExtSlots<'a>>,
+}
+
+/* ExecLimits ***************************************************************/
+/* caps for a child context, None meaning only the parent's cap applies; the
+ * memory budget is an allocator the caller keeps for as long as the child
+ * and which should wrap the parent's main allocator */
+#[derive(Copy, Clone, Default)]
+pub struct ExecLimits<'c> {
+    pub memory: Option<&'c BudgetAllocator<'c>>,
+    pub max_log_bytes: Option<usize>,
+    pub fuel: Option<u64>,
 }
 
 impl<'a> ExecutionContext<'a> {
@@ -39,6 +135,18 @@ impl<'a> ExecutionContext<'a> {
         ExecutionContext {
             main_allocator, error_allocator, log_stream, log_level,
             logging_error_mask: 0,
+            max_log_line_len: None,
+            backoff_hook: None,
+            waker_hook: None,
+            log_bytes_left: None,
+            fuel: None,
+            log_format: LogFormat::Plain,
+            log_seq: 0,
+            timestamp_hook: None,
+            target_log_levels: &[],
+            time_source: None,
+            cancellation_token: None,
+            ext_slots: None,
         }
     }
 
@@ -59,6 +167,18 @@ impl<'a> ExecutionContext<'a> {
             log_stream: NULL_STREAM.get(),
             log_level: LogLevel::Critical,
             logging_error_mask: 0,
+            max_log_line_len: None,
+            backoff_hook: None,
+            waker_hook: None,
+            log_bytes_left: None,
+            fuel: None,
+            log_format: LogFormat::Plain,
+            log_seq: 0,
+            timestamp_hook: None,
+            target_log_levels: &[],
+            time_source: None,
+            cancellation_token: None,
+            ext_slots: None,
         }
     }
 
@@ -69,9 +189,91 @@ impl<'a> ExecutionContext<'a> {
             log_stream: NULL_STREAM.get(),
             log_level: LogLevel::Critical,
             logging_error_mask: 0,
+            max_log_line_len: None,
+            backoff_hook: self.backoff_hook,
+            waker_hook: self.waker_hook,
+            log_bytes_left: None,
+            fuel: self.fuel,
+            log_format: LogFormat::Plain,
+            log_seq: 0,
+            timestamp_hook: self.timestamp_hook,
+            target_log_levels: &[],
+            time_source: self.time_source,
+            cancellation_token: self.cancellation_token,
+            ext_slots: self.ext_slots,
         }
     }
 
+    /* a context for running untrusted work under the given limits, on top
+     * of the ones of this context; what the child uses from the log and fuel
+     * budgets is charged to this context when the guard is dropped */
+    pub fn child<'c>(
+        &'c mut self,
+        limits: ExecLimits<'c>,
+    ) -> ChildContext<'c> {
+        let log_bytes_left = min_limit(self.log_bytes_left, limits.max_log_bytes);
+        let fuel = min_limit(self.fuel, limits.fuel);
+        let xc = ExecutionContext {
+            main_allocator: match limits.memory {
+                Some(a) => a.to_ref(),
+                None => self.main_allocator,
+            },
+            /* errors must stay reportable when the budget runs out */
+            error_allocator: self.error_allocator,
+            log_stream: &mut *self.log_stream,
+            log_level: self.log_level,
+            logging_error_mask: 0,
+            max_log_line_len: self.max_log_line_len,
+            backoff_hook: self.backoff_hook,
+            waker_hook: self.waker_hook,
+            log_bytes_left,
+            fuel,
+            log_format: self.log_format,
+            log_seq: self.log_seq,
+            timestamp_hook: self.timestamp_hook,
+            target_log_levels: self.target_log_levels,
+            time_source: self.time_source,
+            cancellation_token: self.cancellation_token,
+            /* the slots cannot be shared with the shorter lifetime */
+            ext_slots: None,
+        };
+        ChildContext {
+            xc,
+            parent_logging_error_mask: &mut self.logging_error_mask,
+            parent_log_seq: &mut self.log_seq,
+            parent_log_bytes_left: &mut self.log_bytes_left,
+            parent_fuel: &mut self.fuel,
+            initial_log_bytes_left: log_bytes_left,
+            initial_fuel: fuel,
+        }
+    }
+
+    /* steps of work left before consume_fuel() fails; None if unlimited */
+    pub fn get_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /* evaluation code calls this once per step and stops with a limit
+     * error when it returns false; the fuel left is then 0 */
+    pub fn consume_fuel(&mut self, steps: u64) -> bool {
+        match self.fuel {
+            None => true,
+            Some(f) if f >= steps => {
+                self.fuel = Some(f - steps);
+                true
+            },
+            Some(_) => {
+                self.fuel = Some(0);
+                false
+            },
+        }
+    }
+
+    /* bytes that can still go to the log; None if unlimited */
+    pub fn get_log_bytes_left(&self) -> Option<usize> {
+        self.log_bytes_left
+    }
+
     pub fn get_main_allocator(&self) -> AllocatorRef<'a> {
         self.main_allocator
     }
@@ -92,6 +294,215 @@ impl<'a> ExecutionContext<'a> {
         self.log_level
     }
 
+    /* changes the log level until the returned guard is dropped; the guard
+     * derefs to this context so it can be passed along in its place */
+    pub fn push_log_level<'g>(
+        &'g mut self,
+        new_level: LogLevel,
+    ) -> LogLevelGuard<'g, 'a> {
+        let saved_level = self.log_level;
+        self.log_level = new_level;
+        LogLevelGuard { xc: self, saved_level }
+    }
+
+    pub fn set_log_format(&mut self, format: LogFormat) {
+        self.log_format = format;
+    }
+
+    pub fn get_log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
+    /* gives the ts of structured log lines, in whatever unit the caller
+     * picks, instead of the ticks of the time source; the sequence number is
+     * there either way */
+    pub fn set_timestamp_hook(&mut self, hook: Option<&'a (dyn Fn() -> u64 + 'a)>) {
+        self.timestamp_hook = hook;
+    }
+
+    /* log levels for targets, overriding the context level; a rule for
+     * "a::b" also covers "a::b::c" and the longest matching rule wins */
+    pub fn set_target_log_levels(&mut self, levels: &'a [(&'a str, LogLevel)]) {
+        self.target_log_levels = levels;
+    }
+
+    pub fn get_target_log_level(&self, target: &str) -> LogLevel {
+        let mut best: Option<(&str, LogLevel)> = None;
+        for &(prefix, level) in self.target_log_levels {
+            let matches = target.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+            if matches && best.is_none_or(|(p, _)| p.len() < prefix.len()) {
+                best = Some((prefix, level));
+            }
+        }
+        best.map_or(self.log_level, |(_, level)| level)
+    }
+
+    pub fn set_time_source(&mut self, source: Option<&'a (dyn TimeSource + 'a)>) {
+        self.time_source = source;
+    }
+
+    pub fn get_time_source(&self) -> Option<&'a (dyn TimeSource + 'a)> {
+        self.time_source
+    }
+
+    /* None without a time source */
+    pub fn ticks(&self) -> Option<u64> {
+        self.time_source.map(|t| t.ticks())
+    }
+
+    /* runs f and gives its result along with the ticks it took */
+    pub fn time<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> (R, Option<u64>) {
+        let start = self.ticks();
+        let r = f(self);
+        let elapsed = match (start, self.ticks()) {
+            (Some(a), Some(b)) => Some(b.wrapping_sub(a)),
+            _ => None,
+        };
+        (r, elapsed)
+    }
+
+    pub fn set_cancellation_token(&mut self, token: Option<&'a CancellationToken>) {
+        self.cancellation_token = token;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_some_and(|t| t.is_cancelled())
+    }
+
+    /* for long-running loops to call between steps */
+    pub fn check_cancelled(&self) -> IOResult<'a, ()> {
+        if self.is_cancelled() {
+            Err(IOError::with_str(ErrorCode::Cancelled, "operation cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /* typed values kept with the context, see crate::ext; without slots
+     * attached nothing can be stored */
+    pub fn set_ext_slots(&mut self, slots: Option<&'a ExtSlots<'a>>) {
+        self.ext_slots = slots;
+    }
+
+    pub fn set_ext<T: ExtSlot<'a>>(&self, value: T) -> Result<Option<T>, (ExtError, T)> {
+        match self.ext_slots {
+            Some(slots) => slots.set(value),
+            None => Err((ExtError::NoStorage, value)),
+        }
+    }
+
+    pub fn get_ext<T: ExtSlot<'a>>(&self) -> Option<Ref<'a, T>> {
+        self.ext_slots.and_then(|slots| slots.get())
+    }
+
+    pub fn get_ext_mut<T: ExtSlot<'a>>(&self) -> Option<RefMut<'a, T>> {
+        self.ext_slots.and_then(|slots| slots.get_mut())
+    }
+
+    pub fn take_ext<T: ExtSlot<'a>>(&self) -> Option<T> {
+        self.ext_slots.and_then(|slots| slots.take())
+    }
+
+    /* log messages longer than this (in bytes) are cut and end with a
+     * marker telling how many bytes were dropped */
+    pub fn set_max_log_line_len(&mut self, max_len: Option<usize>) {
+        self.max_log_line_len = max_len;
+    }
+
+    pub fn get_max_log_line_len(&self) -> Option<usize> {
+        self.max_log_line_len
+    }
+
+    /* called with the attempt number before a stream operation that failed
+     * with WouldBlock is retried; it can sleep, poll or yield */
+    pub fn set_backoff_hook(&mut self, hook: Option<&'a (dyn Fn(u32) + 'a)>) {
+        self.backoff_hook = hook;
+    }
+
+    pub fn backoff(&self, attempt: u32) {
+        if let Some(hook) = self.backoff_hook {
+            hook(attempt);
+        }
+    }
+
+    /* called by async adapters with the waker of a task whose operation
+     * cannot progress yet; the hook should wake it once the underlying
+     * resource is ready (e.g. on an interrupt) */
+    pub fn set_waker_hook(&mut self, hook: Option<&'a (dyn Fn(&Waker) + 'a)>) {
+        self.waker_hook = hook;
+    }
+
+    /* false if there is no hook to take the waker */
+    pub fn register_waker(&self, waker: &Waker) -> bool {
+        match self.waker_hook {
+            Some(hook) => {
+                hook(waker);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /* writes one log line if the level is enabled for the target, which the
+     * log macros set to the calling module path */
+    pub fn log_event(
+        &mut self,
+        log_level: LogLevel,
+        target: &str,
+        args: fmt::Arguments<'_>,
+    ) {
+        if log_level > self.get_target_log_level(target) {
+            return;
+        }
+        self.log_seq += 1;
+        let seq = self.log_seq;
+        let ts = match (self.log_format, self.timestamp_hook) {
+            (LogFormat::Plain, _) => None,
+            (LogFormat::Structured, Some(hook)) => Some(hook()),
+            (LogFormat::Structured, None) => self.ticks(),
+        };
+        match (self.log_format, ts) {
+            (LogFormat::Plain, _) => self.log_fmt(log_level, args),
+            (LogFormat::Structured, None) => self.log_fmt(log_level, format_args!(
+                "[seq={} level={} target={}] {}", seq, log_level, target, args)),
+            (LogFormat::Structured, Some(ts)) => self.log_fmt(log_level, format_args!(
+                "[seq={} ts={} level={} target={}] {}", seq, ts, log_level, target, args)),
+        }
+    }
+
+    /* writes one log line as it is; once the log byte budget runs out the
+     * rest is dropped and counts as a logging error */
+    pub fn log_fmt(&mut self, log_level: LogLevel, args: fmt::Arguments<'_>) {
+        use fmt::Write as FmtWrite;
+        let mut out = BudgetWriter {
+            out: &mut *self.log_stream,
+            left: self.log_bytes_left,
+        };
+        let r = match self.max_log_line_len {
+            None => out.write_fmt(args),
+            Some(max_len) => {
+                let mut w = TruncatingWriter {
+                    out: &mut out,
+                    left: max_len,
+                    dropped: 0,
+                };
+                let r = w.write_fmt(args);
+                let dropped = w.dropped;
+                r.and_then(|_| if dropped != 0 {
+                    write!(out, "...[{} more bytes]", dropped)
+                } else {
+                    Ok(())
+                })
+            },
+        };
+        let r = r.and_then(|_| out.write_str("\n"));
+        self.log_bytes_left = out.left;
+        if r.is_err() {
+            self.set_logging_error(log_level);
+        }
+    }
+
     pub fn get_logging_error_mask(&self) -> u8 {
         self.logging_error_mask
     }
@@ -135,6 +546,139 @@ impl<'a> ExecutionContext<'a> {
     }
 }
 
+fn min_limit<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/* the largest char boundary of s not above n */
+fn char_floor(s: &str, mut n: usize) -> usize {
+    while !s.is_char_boundary(n) {
+        n -= 1;
+    }
+    n
+}
+
+/* BudgetWriter *************************************************************/
+/* writes to the log stream while the byte budget lasts, cutting the text
+ * that overflows it at a char boundary and failing from there on */
+struct BudgetWriter<'w, 'a> {
+    out: &'w mut (dyn Write + 'a),
+    left: Option<usize>,
+}
+
+impl fmt::Write for BudgetWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.left {
+            None => self.out.write_str(s),
+            Some(left) if s.len() <= left => {
+                self.left = Some(left - s.len());
+                self.out.write_str(s)
+            },
+            Some(left) => {
+                self.left = Some(0);
+                self.out.write_str(&s[0..char_floor(s, left)])?;
+                Err(fmt::Error)
+            },
+        }
+    }
+}
+
+/* TruncatingWriter *********************************************************/
+/* passes through the first bytes of a formatted message, cutting at a char
+ * boundary, and counts the bytes dropped after that */
+struct TruncatingWriter<'w> {
+    out: &'w mut dyn fmt::Write,
+    left: usize,
+    dropped: usize,
+}
+
+impl fmt::Write for TruncatingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.dropped != 0 || s.len() > self.left {
+            let n = char_floor(s, if self.dropped != 0 { 0 } else { self.left });
+            self.left = 0;
+            self.dropped += s.len() - n;
+            self.out.write_str(&s[0..n])
+        } else {
+            self.left -= s.len();
+            self.out.write_str(s)
+        }
+    }
+}
+
+/* LogLevelGuard ************************************************************/
+pub struct LogLevelGuard<'g, 'a> {
+    xc: &'g mut ExecutionContext<'a>,
+    saved_level: LogLevel,
+}
+
+impl<'g, 'a> Deref for LogLevelGuard<'g, 'a> {
+    type Target = ExecutionContext<'a>;
+    fn deref(&self) -> &Self::Target {
+        self.xc
+    }
+}
+
+impl<'g, 'a> DerefMut for LogLevelGuard<'g, 'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.xc
+    }
+}
+
+impl<'g, 'a> Drop for LogLevelGuard<'g, 'a> {
+    fn drop(&mut self) {
+        self.xc.log_level = self.saved_level;
+    }
+}
+
+/* ChildContext *************************************************************/
+/* the context made by ExecutionContext::child(); derefs to it so it can be
+ * passed along in its place */
+pub struct ChildContext<'c> {
+    xc: ExecutionContext<'c>,
+    parent_logging_error_mask: &'c mut u8,
+    parent_log_seq: &'c mut u64,
+    parent_log_bytes_left: &'c mut Option<usize>,
+    parent_fuel: &'c mut Option<u64>,
+    initial_log_bytes_left: Option<usize>,
+    initial_fuel: Option<u64>,
+}
+
+impl<'c> Deref for ChildContext<'c> {
+    type Target = ExecutionContext<'c>;
+    fn deref(&self) -> &Self::Target {
+        &self.xc
+    }
+}
+
+impl<'c> DerefMut for ChildContext<'c> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.xc
+    }
+}
+
+impl<'c> Drop for ChildContext<'c> {
+    fn drop(&mut self) {
+        *self.parent_logging_error_mask |= self.xc.logging_error_mask;
+        *self.parent_log_seq = self.xc.log_seq;
+        if let (Some(p), Some(a), Some(b)) = (
+            self.parent_log_bytes_left.as_mut(),
+            self.initial_log_bytes_left,
+            self.xc.log_bytes_left,
+        ) {
+            *p -= a - b;
+        }
+        if let (Some(p), Some(a), Some(b)) =
+            (self.parent_fuel.as_mut(), self.initial_fuel, self.xc.fuel) {
+            *p -= a - b;
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! xc_err {
     ( $xc:expr, $err_data:expr, $oom_msg:expr, $( $x:tt )+ ) => {
@@ -155,10 +699,7 @@ macro_rules! xc_err {
 macro_rules! log_msg {
     ( $xc: expr, $log_level: expr, $f:literal $( $x:tt )* ) => {
         {
-            use core::fmt::Write;
-            if $log_level <= $xc.get_log_level() && write!($xc.get_log_stream(), concat!($f, "\n") $( $x )*).is_err() {
-                $xc.set_logging_error($log_level);
-            }
+            $xc.log_event($log_level, module_path!(), format_args!($f $( $x )*));
         }
     }
 }
@@ -298,6 +839,30 @@ mod tests {
         assert_eq!(log_buffer[..expected.len()], *expected);
     }
 
+    #[test]
+    fn log_line_truncation() {
+        use crate::io::stream::buffer::BufferAsRWStream;
+        let mut log_buffer = [0_u8; 0x100];
+        let mut log = BufferAsRWStream::new(&mut log_buffer, 0);
+        let mut xc = ExecutionContext::new(
+            NOP_ALLOCATOR.to_ref(),
+            NOP_ALLOCATOR.to_ref(),
+            &mut log,
+            LogLevel::Critical,
+        );
+        xc.set_max_log_line_len(Some(10));
+        assert_eq!(xc.get_max_log_line_len(), Some(10));
+        log_crit!(xc, "short");
+        log_crit!(xc, "exactly 10");
+        log_crit!(xc, "{}-{}", "this is a bit", "longer");
+        log_crit!(xc, "\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}");
+        xc.set_max_log_line_len(None);
+        log_crit!(xc, "no limit anymore");
+        assert_eq!(xc.get_logging_error_mask(), 0);
+        let expected = "short\nexactly 10\nthis is a ...[10 more bytes]\n\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}...[2 more bytes]\nno limit anymore\n";
+        assert_eq!(log_buffer[..expected.len()], *expected.as_bytes());
+    }
+
     #[test]
     fn log_level() {
         use crate::io::stream::Zero;
@@ -318,6 +883,144 @@ mod tests {
         assert_eq!(xc.get_logging_error_mask(), 10);
     }
 
+    #[test]
+    fn push_log_level_restores_on_drop() {
+        use crate::io::stream::Zero;
+        let mut log = Zero::new();
+        let mut xc = ExecutionContext::new(
+            NOP_ALLOCATOR.to_ref(),
+            NOP_ALLOCATOR.to_ref(),
+            &mut log,
+            LogLevel::Error,
+        );
+        {
+            let mut xc = xc.push_log_level(LogLevel::Info);
+            assert_eq!(xc.get_log_level(), LogLevel::Info);
+            log_info!(xc, "shown");
+            assert_eq!(xc.get_logging_error_mask(), 8);
+            {
+                let xc = xc.push_log_level(LogLevel::Critical);
+                assert_eq!(xc.get_log_level(), LogLevel::Critical);
+            }
+            assert_eq!(xc.get_log_level(), LogLevel::Info);
+        }
+        assert_eq!(xc.get_log_level(), LogLevel::Error);
+        log_warn!(xc, "hidden");
+        assert_eq!(xc.get_logging_error_mask(), 8);
+    }
+
+    #[test]
+    fn log_level_guard_passes_as_context() {
+        fn level_seen(xc: &mut ExecutionContext<'_>) -> LogLevel {
+            xc.get_log_level()
+        }
+        let mut xc = ExecutionContext::nop();
+        let mut g = xc.push_log_level(LogLevel::Debug);
+        assert_eq!(level_seen(&mut g), LogLevel::Debug);
+    }
+
+    #[test]
+    fn structured_log_events() {
+        use crate::io::stream::buffer::BufferAsRWStream;
+        let mut log_buffer = [0_u8; 0x100];
+        let mut log = BufferAsRWStream::new(&mut log_buffer, 0);
+        let mut xc = ExecutionContext::new(
+            NOP_ALLOCATOR.to_ref(),
+            NOP_ALLOCATOR.to_ref(),
+            &mut log,
+            LogLevel::Warning,
+        );
+        let levels = [("elf", LogLevel::Debug), ("elf::notes", LogLevel::Error)];
+        xc.set_target_log_levels(&levels);
+        assert_eq!(xc.get_target_log_level("elf::sections"), LogLevel::Debug);
+        assert_eq!(xc.get_target_log_level("elf::notes::gnu"), LogLevel::Error);
+        assert_eq!(xc.get_target_log_level("elfish"), LogLevel::Warning);
+        xc.log_event(LogLevel::Info, "elf", format_args!("plain {}", 1));
+        xc.log_event(LogLevel::Info, "zip", format_args!("hidden"));
+        xc.set_log_format(LogFormat::Structured);
+        xc.log_event(LogLevel::Warning, "zip", format_args!("w"));
+        xc.log_event(LogLevel::Warning, "elf::notes", format_args!("hidden"));
+        let ts = || 1234_u64;
+        xc.set_timestamp_hook(Some(&ts));
+        {
+            let mut child = xc.child(ExecLimits::default());
+            child.log_event(LogLevel::Debug, "elf::sections", format_args!("d"));
+        }
+        log_crit!(xc, "c");
+        assert_eq!(xc.get_log_format(), LogFormat::Structured);
+        let expected = concat!(
+            "plain 1\n",
+            "[seq=2 level=warn target=zip] w\n",
+            "[seq=3 ts=1234 level=debug target=elf::sections] d\n",
+            "[seq=4 ts=1234 level=crit target=halfbit::exectx::tests] c\n");
+        assert_eq!(log_buffer[..expected.len()], *expected.as_bytes());
+    }
+
+    #[test]
+    fn time_source_ticks() {
+        use core::cell::Cell;
+        struct Counter(Cell<u64>);
+        impl TimeSource for Counter {
+            fn ticks(&self) -> u64 {
+                self.0.set(self.0.get() + 10);
+                self.0.get()
+            }
+            fn ticks_per_second(&self) -> u64 {
+                1000
+            }
+        }
+        let mut xc = ExecutionContext::nop();
+        assert_eq!(xc.time(|_| 7), (7, None));
+        let c = Counter(Cell::new(0));
+        xc.set_time_source(Some(&c));
+        assert_eq!(xc.ticks(), Some(10));
+        let (r, elapsed) = xc.time(|xc| xc.ticks());
+        assert_eq!((r, elapsed), (Some(30), Some(20)));
+        assert_eq!(xc.get_time_source().unwrap().ticks_to_ns(20), 20_000_000);
+    }
+
+    #[test]
+    fn child_context_limits() {
+        use crate::io::stream::buffer::BufferAsRWStream;
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut log_buffer = [0_u8; 0x40];
+        let mut log = BufferAsRWStream::new(&mut log_buffer, 0);
+        {
+            let mut xc = ExecutionContext::new(a.to_ref(), a.to_ref(), &mut log, LogLevel::Info);
+            let budget = BudgetAllocator::new(xc.get_main_allocator(), 0x10);
+            {
+                let mut child = xc.child(ExecLimits {
+                    memory: Some(&budget),
+                    max_log_bytes: Some(12),
+                    fuel: Some(10),
+                });
+                assert_eq!(child.boxed([0_u8; 0x11]).unwrap_err().0, AllocError::NotEnoughMemory);
+                assert!(child.boxed(5_u64).is_ok());
+                assert_eq!(child.get_error_allocator().name(), a.name());
+                assert!(child.consume_fuel(4));
+                {
+                    let mut grandchild = child.child(ExecLimits { fuel: Some(100), ..ExecLimits::default() });
+                    assert_eq!(grandchild.get_fuel(), Some(6));
+                    assert!(grandchild.consume_fuel(5));
+                    assert!(!grandchild.consume_fuel(2));
+                }
+                assert_eq!(child.get_fuel(), Some(0));
+                log_info!(child, "first");
+                assert_eq!(child.get_logging_error_mask(), 0);
+                log_info!(child, "second");
+                assert_eq!(child.get_log_bytes_left(), Some(0));
+                assert_eq!(child.get_logging_error_mask(), 8);
+            }
+            assert_eq!(budget.get_used(), 0);
+            assert_eq!(xc.get_fuel(), None);
+            assert_eq!(xc.get_log_bytes_left(), None);
+            assert_eq!(xc.get_logging_error_mask(), 8);
+            log_info!(xc, "third");
+        }
+        assert_eq!(&log_buffer[0..18], b"first\nsecondthird\n");
+    }
+
     #[test]
     fn obtain_string() {
         use core::fmt::Write;
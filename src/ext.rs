@@ -0,0 +1,192 @@
+/* typed values kept along an execution context, at most one per type, so
+ * that code called many times (like format parsers computing properties)
+ * can cache state between calls; the embedder owns the slots and attaches
+ * them to the context, which borrows them for its whole lifetime; for that
+ * to work the slots do not drop their values by themselves, call clear()
+ * once done (skipping it just leaks them, as a bump allocator would) */
+
+use core::any::TypeId;
+use core::cell::Ref;
+use core::cell::RefCell;
+use core::cell::RefMut;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::Box;
+use crate::mm::HashMap;
+
+/* ExtSlot ******************************************************************/
+/* types that can be stored in the slots; Key is the type itself with its
+ * lifetimes made 'static and its TypeId tells the slots apart, which works
+ * for types borrowing from the context allocator too */
+/// # Safety
+/// Key must not be the Key of any other type, including the same type
+/// with other lifetimes.
+pub unsafe trait ExtSlot<'a>: 'a {
+    type Key: 'static;
+}
+
+/* ExtSlot for types without lifetime parameters */
+#[macro_export]
+macro_rules! ext_slot {
+    ( $t:ty ) => {
+        unsafe impl<'a> $crate::ext::ExtSlot<'a> for $t {
+            type Key = $t;
+        }
+    }
+}
+
+/* ExtError *****************************************************************/
+#[derive(Debug, PartialEq)]
+pub enum ExtError {
+    NoStorage, // the context has no slots attached
+    Busy, // a value from the slots is still borrowed
+    Alloc(AllocError),
+}
+
+impl fmt::Display for ExtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtError::NoStorage => "no extension storage".fmt(f),
+            ExtError::Busy => "extension storage in use".fmt(f),
+            ExtError::Alloc(e) => write!(f, "allocation error ({})", e),
+        }
+    }
+}
+
+/* ExtSlots *****************************************************************/
+trait Erased {}
+impl<T: ?Sized> Erased for T {}
+
+type ErasedBox<'a> = Box<'a, dyn Erased + 'a>;
+
+/* the value behind an erased box known to hold a T */
+unsafe fn unerase<'a, T>(b: ErasedBox<'a>) -> T {
+    let (allocator, ptr) = b.to_parts();
+    Box::from_parts(allocator, ptr.cast::<T>()).into_inner()
+}
+
+pub struct ExtSlots<'a> {
+    map: RefCell<ManuallyDrop<HashMap<'a, TypeId, ErasedBox<'a>>>>,
+}
+
+impl<'a> ExtSlots<'a> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        ExtSlots { map: RefCell::new(ManuallyDrop::new(HashMap::new(allocator))) }
+    }
+
+    /* stores value in the slot of its type, giving back the one replaced */
+    pub fn set<T: ExtSlot<'a>>(&self, value: T) -> Result<Option<T>, (ExtError, T)> {
+        let mut map = match self.map.try_borrow_mut() {
+            Ok(map) => map,
+            Err(_) => return Err((ExtError::Busy, value)),
+        };
+        let b = Box::new(map.allocator(), value).map_err(|(e, v)| (ExtError::Alloc(e), v))?;
+        let (allocator, ptr) = unsafe { b.to_parts() };
+        let ptr: NonNull<dyn Erased + 'a> = ptr;
+        let b = unsafe { Box::from_parts(allocator, ptr) };
+        match map.insert(TypeId::of::<T::Key>(), b) {
+            Ok(old) => Ok(old.map(|b| unsafe { unerase(b) })),
+            Err((e, (_, b))) => Err((ExtError::Alloc(e), unsafe { unerase(b) })),
+        }
+    }
+
+    /* None when the slot is empty or the slots are being changed */
+    pub fn get<T: ExtSlot<'a>>(&self) -> Option<Ref<'_, T>> {
+        let map = self.map.try_borrow().ok()?;
+        Ref::filter_map(map, |m| m.get(&TypeId::of::<T::Key>())
+            .map(|b| unsafe { &*(&**b as *const (dyn Erased + 'a) as *const T) })).ok()
+    }
+
+    pub fn get_mut<T: ExtSlot<'a>>(&self) -> Option<RefMut<'_, T>> {
+        let map = self.map.try_borrow_mut().ok()?;
+        RefMut::filter_map(map, |m| m.get_mut(&TypeId::of::<T::Key>())
+            .map(|b| unsafe { &mut *(&mut **b as *mut (dyn Erased + 'a) as *mut T) })).ok()
+    }
+
+    /* empties the slot of T */
+    pub fn take<T: ExtSlot<'a>>(&self) -> Option<T> {
+        let mut map = self.map.try_borrow_mut().ok()?;
+        map.remove(&TypeId::of::<T::Key>()).map(|b| unsafe { unerase(b) })
+    }
+
+    /* drops all values and releases the memory of the map */
+    pub fn clear(&self) -> Result<(), ExtError> {
+        let mut map = self.map.try_borrow_mut().map_err(|_| ExtError::Busy)?;
+        let allocator = map.allocator();
+        let old = core::mem::replace(&mut *map, ManuallyDrop::new(HashMap::new(allocator)));
+        drop(ManuallyDrop::into_inner(old));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.try_borrow().map_or(0, |m| m.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExecutionContext;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::NOP_ALLOCATOR;
+    use crate::mm::Vector;
+
+    /* what a parser would keep: offsets of the names in a string table */
+    struct NameIndex<'a>(Vector<'a, u32>);
+    unsafe impl<'a> ExtSlot<'a> for NameIndex<'a> {
+        type Key = NameIndex<'static>;
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Hits(u64);
+    ext_slot!(Hits);
+
+    #[test]
+    fn typed_slots() {
+        let mut buf = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buf);
+        let slots = ExtSlots::new(a.to_ref());
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        assert_eq!(xc.set_ext(Hits(1)).unwrap_err().0, ExtError::NoStorage);
+        xc.set_ext_slots(Some(&slots));
+        assert!(xc.get_ext::<Hits>().is_none());
+        assert_eq!(xc.set_ext(Hits(1)).ok(), Some(None));
+        let mut names = Vector::new(xc.get_main_allocator());
+        names.push(0_u32).unwrap();
+        names.push(5).unwrap();
+        xc.set_ext(NameIndex(names)).ok().unwrap();
+        assert_eq!(slots.len(), 2);
+        {
+            let index = xc.get_ext::<NameIndex<'_>>().unwrap();
+            assert_eq!(index.0.as_slice(), &[0, 5]);
+            assert_eq!(*xc.get_ext::<Hits>().unwrap(), Hits(1));
+            assert_eq!(xc.set_ext(Hits(3)).unwrap_err().0, ExtError::Busy);
+        }
+        xc.get_ext_mut::<Hits>().unwrap().0 += 1;
+        assert_eq!(xc.set_ext(Hits(7)).ok(), Some(Some(Hits(2))));
+        assert_eq!(xc.take_ext::<Hits>(), Some(Hits(7)));
+        assert_eq!(xc.take_ext::<Hits>(), None);
+        assert!(!slots.is_empty());
+        {
+            let _index = xc.get_ext::<NameIndex<'_>>().unwrap();
+            assert_eq!(slots.clear(), Err(ExtError::Busy));
+        }
+        slots.clear().unwrap();
+        assert!(slots.is_empty());
+
+        let slots = ExtSlots::new(NOP_ALLOCATOR.to_ref());
+        assert_eq!(slots.set(Hits(1)).unwrap_err(),
+                   (ExtError::Alloc(AllocError::UnsupportedOperation), Hits(1)));
+    }
+}
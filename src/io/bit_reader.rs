@@ -0,0 +1,322 @@
+use crate::ExecutionContext;
+use crate::num::PrimitiveInt;
+pub use crate::num::bits::BitOrder;
+use crate::xc_err;
+use super::ErrorCode;
+use super::IOError;
+use super::IOResult;
+use super::stream::Null;
+use super::stream::Read;
+
+const READ_AHEAD_SIZE: usize = 0x200;
+
+enum Source<'r, R> {
+    Stream(&'r mut (dyn Read + 'r)),
+    ReadAhead(R), // through buf[pos..len]
+    Slice(&'r [u8]),
+}
+
+/* BitReader ****************************************************************/
+/* reads bit fields from a stream or a byte slice; bytes are taken from a
+ * borrowed stream only when needed, so after align_to_byte() the stream is
+ * positioned right after the last byte any bit was read from; an owned
+ * stream is read ahead in chunks instead, which is what decompressors want
+ * as they go through their input byte by byte */
+pub struct BitReader<'r, R: Read = Null> {
+    src: Source<'r, R>,
+    order: BitOrder,
+    buf: [u8; READ_AHEAD_SIZE],
+    pos: usize,
+    len: usize,
+    acc: u64, // pending bits, right-aligned
+    acc_len: u32,
+    bit_pos: u64,
+}
+
+impl<'r> BitReader<'r> {
+
+    pub fn new(stream: &'r mut (dyn Read + 'r), order: BitOrder) -> Self {
+        BitReader::with_source(Source::Stream(stream), order)
+    }
+
+    pub fn from_slice(bytes: &'r [u8], order: BitOrder) -> Self {
+        BitReader::with_source(Source::Slice(bytes), order)
+    }
+
+}
+
+impl<'r, R: Read> BitReader<'r, R> {
+
+    /* the stream ends up positioned past the bytes read ahead */
+    pub fn with_read_ahead(stream: R, order: BitOrder) -> Self {
+        BitReader::with_source(Source::ReadAhead(stream), order)
+    }
+
+    fn with_source(src: Source<'r, R>, order: BitOrder) -> Self {
+        BitReader {
+            src,
+            order,
+            buf: [0; READ_AHEAD_SIZE],
+            pos: 0,
+            len: 0,
+            acc: 0,
+            acc_len: 0,
+            bit_pos: 0,
+        }
+    }
+
+    /* the stream given to with_read_ahead() */
+    pub fn into_inner(self) -> Option<R> {
+        match self.src {
+            Source::ReadAhead(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /* count of bits read or skipped so far */
+    pub fn bit_position(&self) -> u64 {
+        self.bit_pos
+    }
+
+    pub fn is_byte_aligned(&self) -> bool {
+        self.acc_len.is_multiple_of(8)
+    }
+
+    fn next_byte<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u8> {
+        match &mut self.src {
+            Source::Stream(s) => s.read_u8(xc).map_err(|e| e.to_error()),
+            Source::ReadAhead(stream) => {
+                if self.pos == self.len {
+                    let n = stream.read_uninterrupted(&mut self.buf, xc)
+                        .map_err(|e| e.to_error())?;
+                    if n == 0 {
+                        return Err(IOError::with_str(
+                            ErrorCode::UnexpectedEnd, "bit reader reached the end of stream"));
+                    }
+                    self.pos = 0;
+                    self.len = n;
+                }
+                self.pos += 1;
+                Ok(self.buf[self.pos - 1])
+            },
+            Source::Slice(bytes) => match bytes.split_first() {
+                Some((&b, rest)) => {
+                    *bytes = rest;
+                    Ok(b)
+                },
+                None => Err(IOError::with_str(
+                    ErrorCode::UnexpectedEnd, "bit reader reached the end of slice")),
+            },
+        }
+    }
+
+    /* n is at most 32, so the accumulator never holds more than 39 bits */
+    fn read_bits_upto_32<'x>(
+        &mut self,
+        n: u32,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u64> {
+        while self.acc_len < n {
+            let b = self.next_byte(xc)? as u64;
+            self.acc = match self.order {
+                BitOrder::MsbFirst => (self.acc << 8) | b,
+                BitOrder::LsbFirst => self.acc | (b << self.acc_len),
+            };
+            self.acc_len += 8;
+        }
+        let mask = u64::lsb_mask(n as usize);
+        let v = match self.order {
+            BitOrder::MsbFirst => {
+                let v = (self.acc >> (self.acc_len - n)) & mask;
+                self.acc &= u64::lsb_mask((self.acc_len - n) as usize);
+                v
+            },
+            BitOrder::LsbFirst => {
+                let v = self.acc & mask;
+                self.acc >>= n;
+                v
+            },
+        };
+        self.acc_len -= n;
+        self.bit_pos += n as u64;
+        Ok(v)
+    }
+
+    /* reads a field of n bits, n being at most 64; on error, the bits read
+     * so far are lost */
+    pub fn read_bits<'x>(
+        &mut self,
+        n: u32,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u64> {
+        if n <= 32 {
+            return self.read_bits_upto_32(n, xc);
+        }
+        if n > 64 {
+            return Err(xc_err!(xc, ErrorCode::UnsupportedOperation,
+                               "bit field wider than 64 bits",
+                               "cannot read a {}-bit field", n));
+        }
+        let first = self.read_bits_upto_32(32, xc)?;
+        let second = self.read_bits_upto_32(n - 32, xc)?;
+        Ok(match self.order {
+            BitOrder::MsbFirst => (first << (n - 32)) | second,
+            BitOrder::LsbFirst => first | (second << 32),
+        })
+    }
+
+    pub fn read_bit<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, bool> {
+        self.read_bits_upto_32(1, xc).map(|v| v != 0)
+    }
+
+    pub fn skip_bits<'x>(
+        &mut self,
+        mut n: u64,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        while n != 0 {
+            let chunk = core::cmp::min(n, 32) as u32;
+            self.read_bits_upto_32(chunk, xc)?;
+            n -= chunk as u64;
+        }
+        Ok(())
+    }
+
+    /* drops the bits left in the current byte and returns their count */
+    pub fn align_to_byte(&mut self) -> u32 {
+        let n = self.acc_len % 8;
+        self.acc_len -= n;
+        self.bit_pos += n as u64;
+        self.acc = match self.order {
+            BitOrder::MsbFirst => self.acc & u64::lsb_mask(self.acc_len as usize),
+            BitOrder::LsbFirst => self.acc >> n,
+        };
+        n
+    }
+
+    /* reads whole bytes; the reader must be byte-aligned */
+    pub fn read_bytes<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if !self.is_byte_aligned() {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedOperation,
+                "byte read on a bit reader that is not byte-aligned"));
+        }
+        for b in buf.iter_mut() {
+            *b = self.read_bits_upto_32(8, xc)? as u8;
+        }
+        Ok(())
+    }
+
+    /* reads a whole byte; the reader must be byte-aligned */
+    pub fn read_byte<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u8> {
+        let mut b = [0_u8; 1];
+        self.read_bytes(&mut b, xc)?;
+        Ok(b[0])
+    }
+
+}
+
+impl<R: Read> core::fmt::Debug for BitReader<'_, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "BitReader({:?}, bit_position: {})", self.order, self.bit_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsOnePassROStream;
+
+    #[test]
+    fn msb_first_fields() {
+        let mut xc = ExecutionContext::nop();
+        let mut r = BitReader::from_slice(&[0b1011_0011, 0b1100_0101], BitOrder::MsbFirst);
+        assert_eq!(r.read_bits(3, &mut xc).unwrap(), 0b101);
+        assert!(r.read_bit(&mut xc).unwrap());
+        assert_eq!(r.read_bits(6, &mut xc).unwrap(), 0b00_1111);
+        assert_eq!(r.bit_position(), 10);
+        assert!(!r.is_byte_aligned());
+        assert_eq!(r.read_bits(6, &mut xc).unwrap(), 0b00_0101);
+        assert!(r.is_byte_aligned());
+        let e = r.read_bit(&mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnexpectedEnd);
+    }
+
+    #[test]
+    fn lsb_first_fields() {
+        let mut xc = ExecutionContext::nop();
+        // DEFLATE-style: BFINAL=1, BTYPE=01, then a 7-bit value spanning bytes
+        let mut r = BitReader::from_slice(&[0b1010_1011, 0b0000_0001], BitOrder::LsbFirst);
+        assert!(r.read_bit(&mut xc).unwrap());
+        assert_eq!(r.read_bits(2, &mut xc).unwrap(), 0b01);
+        assert_eq!(r.read_bits(7, &mut xc).unwrap(), 0b0110101);
+        assert_eq!(r.align_to_byte(), 6);
+        assert_eq!(r.bit_position(), 16);
+    }
+
+    #[test]
+    fn wide_fields() {
+        let mut xc = ExecutionContext::nop();
+        let bytes = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0xFF];
+        let mut r = BitReader::from_slice(&bytes, BitOrder::MsbFirst);
+        assert_eq!(r.read_bits(64, &mut xc).unwrap(), 0x0123_4567_89AB_CDEF);
+        let mut r = BitReader::from_slice(&bytes, BitOrder::LsbFirst);
+        assert_eq!(r.read_bits(64, &mut xc).unwrap(), 0xEFCD_AB89_6745_2301);
+        let mut r = BitReader::from_slice(&bytes, BitOrder::MsbFirst);
+        r.read_bits(4, &mut xc).unwrap();
+        assert_eq!(r.read_bits(40, &mut xc).unwrap(), 0x12_3456_789A);
+        let e = r.read_bits(65, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnsupportedOperation);
+    }
+
+    #[test]
+    fn stream_source_and_byte_reads() {
+        let mut xc = ExecutionContext::nop();
+        let mut s = BufferAsOnePassROStream::new(b"\xF0abc");
+        let mut r = BitReader::new(&mut s, BitOrder::MsbFirst);
+        assert_eq!(r.read_bits(4, &mut xc).unwrap(), 0xF);
+        let mut buf = [0_u8; 2];
+        assert!(r.read_bytes(&mut buf, &mut xc).is_err());
+        assert_eq!(r.align_to_byte(), 4);
+        r.read_bytes(&mut buf, &mut xc).unwrap();
+        assert_eq!(buf, *b"ab");
+        r.skip_bits(8, &mut xc).unwrap();
+        assert!(r.read_bit(&mut xc).is_err());
+    }
+
+    #[test]
+    fn owned_stream_read_ahead() {
+        let mut xc = ExecutionContext::nop();
+        let data = [0b1011_0011_u8, 0x12, 0x34, 0x56, 0x78];
+        let src = BufferAsOnePassROStream::new(&data);
+        let mut r = BitReader::with_read_ahead(src, BitOrder::LsbFirst);
+        assert_eq!(r.read_bits(3, &mut xc).unwrap(), 0b011);
+        assert!(r.read_byte(&mut xc).is_err());
+        assert_eq!(r.align_to_byte(), 5);
+        assert_eq!(r.read_byte(&mut xc).unwrap(), 0x12);
+        assert_eq!(r.read_bits(24, &mut xc).unwrap(), 0x78_5634);
+        assert_eq!(r.bit_position(), 40);
+        let e = r.read_bit(&mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnexpectedEnd);
+        let mut src = r.into_inner().unwrap();
+        assert_eq!(src.read_uninterrupted(&mut [0_u8; 4], &mut xc).unwrap(), 0);
+        assert!(BitReader::from_slice(&data, BitOrder::MsbFirst).into_inner().is_none());
+    }
+}
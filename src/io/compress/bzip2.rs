@@ -0,0 +1,456 @@
+use crate::ExecutionContext;
+use crate::io::BitOrder;
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::mm::AllocatorRef;
+use crate::mm::Vector;
+use crate::xc_err;
+use super::Crc32Bzip2;
+use crate::io::BitReader;
+use super::huffman::Huffman;
+
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const END_MAGIC: u64 = 0x1772_4538_5090;
+const MAX_GROUPS: usize = 6;
+const MAX_ALPHA_SIZE: usize = 258;
+const MAX_SELECTORS: usize = 18002;
+const GROUP_SIZE: usize = 50;
+const RUN_A: usize = 0;
+const RUN_B: usize = 1;
+
+fn invalid(msg: &'static str) -> IOError<'static> {
+    IOError::with_str(ErrorCode::InvalidData, msg)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    StreamHeader,
+    BlockHeader,
+    Block,
+    Done,
+}
+
+/* Bzip2Reader **************************************************************/
+/* decompresses a bzip2 stream as it is read; only the first stream is
+ * decoded when several are concatenated; block CRCs are checked at the end
+ * of each block and the stream CRC before reporting the end of data; the
+ * block being decoded is kept in memory, taking 4 bytes per byte of block */
+pub struct Bzip2Reader<'a, T: Read> {
+    input: BitReader<'a, T>,
+    state: State,
+    max_block_size: usize,
+    tt: Vector<'a, u32>,
+    selectors: Vector<'a, u8>,
+    trees: [Huffman<MAX_ALPHA_SIZE>; MAX_GROUPS],
+    t_pos: u32,
+    left: usize,
+    last: u8,
+    same_count: u8,
+    repeat_left: u8,
+    block_crc: u32,
+    crc: Crc32Bzip2,
+    combined_crc: u32,
+    total_out: u64,
+}
+
+impl<'a, T: Read> Bzip2Reader<'a, T> {
+
+    /* nothing is allocated until the first block is decoded */
+    pub fn new(src: T, allocator: AllocatorRef<'a>) -> Self {
+        const EMPTY_TREE: Huffman<MAX_ALPHA_SIZE> = Huffman::new();
+        Bzip2Reader {
+            input: BitReader::with_read_ahead(src, BitOrder::MsbFirst),
+            state: State::StreamHeader,
+            max_block_size: 0,
+            tt: Vector::new(allocator),
+            selectors: Vector::new(allocator),
+            trees: [EMPTY_TREE; MAX_GROUPS],
+            t_pos: 0,
+            left: 0,
+            last: 0,
+            same_count: 0,
+            repeat_left: 0,
+            block_crc: 0,
+            crc: Crc32Bzip2::new(),
+            combined_crc: 0,
+            total_out: 0,
+        }
+    }
+
+    /* count of decompressed bytes produced so far */
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /* true once the end of stream was reached and its CRC checked */
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /* the source may be positioned past the end of the compressed data as
+     * input is read in chunks */
+    pub fn into_inner(self) -> T {
+        self.input.into_inner().expect("input is read ahead")
+    }
+
+    fn read_stream_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut magic = [0_u8; 4];
+        for b in magic.iter_mut() {
+            *b = self.input.read_byte(xc)?;
+        }
+        if magic[0..3] != *b"BZh" || !(b'1'..=b'9').contains(&magic[3]) {
+            return Err(invalid("not a bzip2 stream"));
+        }
+        self.max_block_size = (magic[3] - b'0') as usize * 100_000;
+        Ok(())
+    }
+
+    fn read_u32<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        Ok(self.input.read_bits(32, xc)? as u32)
+    }
+
+    /* symbol map, huffman tables and selectors; returns the map from
+     * symbol values to bytes and the alphabet size */
+    fn read_tables<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ([u8; 256], usize)> {
+        let mut seq_to_unseq = [0_u8; 256];
+        let mut in_use = 0;
+        let used_groups = self.input.read_bits(16, xc)?;
+        for i in 0..16 {
+            if used_groups & (0x8000 >> i) == 0 {
+                continue;
+            }
+            let used = self.input.read_bits(16, xc)?;
+            for j in 0..16 {
+                if used & (0x8000 >> j) != 0 {
+                    seq_to_unseq[in_use] = (i * 16 + j) as u8;
+                    in_use += 1;
+                }
+            }
+        }
+        if in_use == 0 {
+            return Err(invalid("bzip2 block uses no bytes"));
+        }
+        let alpha_size = in_use + 2;
+
+        let group_count = self.input.read_bits(3, xc)? as usize;
+        if !(2..=MAX_GROUPS).contains(&group_count) {
+            return Err(invalid("bad bzip2 huffman group count"));
+        }
+        let selector_count = self.input.read_bits(15, xc)? as usize;
+        if selector_count == 0 || selector_count > MAX_SELECTORS {
+            return Err(invalid("bad bzip2 selector count"));
+        }
+        self.selectors.truncate(0);
+        self.selectors.reserve(selector_count).map_err(|e| xc_err!(
+            xc, ErrorCode::NoSpace, "bzip2 selectors out of memory",
+            "cannot allocate {} bzip2 selectors: {}", selector_count, e))?;
+        let mut mtf = [0_u8, 1, 2, 3, 4, 5];
+        for _ in 0..selector_count {
+            let mut j = 0;
+            while self.input.read_bits(1, xc)? != 0 {
+                j += 1;
+                if j >= group_count {
+                    return Err(invalid("bad bzip2 selector"));
+                }
+            }
+            let g = mtf[j];
+            mtf.copy_within(0..j, 1);
+            mtf[0] = g;
+            self.selectors.push(g).unwrap();
+        }
+
+        let mut lengths = [0_u8; MAX_ALPHA_SIZE];
+        for g in 0..group_count {
+            let mut len = self.input.read_bits(5, xc)?;
+            for l in lengths[0..alpha_size].iter_mut() {
+                loop {
+                    if !(1..=20).contains(&len) {
+                        return Err(invalid("bad bzip2 code length"));
+                    }
+                    if self.input.read_bits(1, xc)? == 0 {
+                        break;
+                    }
+                    if self.input.read_bits(1, xc)? == 0 {
+                        len += 1;
+                    } else {
+                        len -= 1;
+                    }
+                }
+                *l = len as u8;
+            }
+            if !self.trees[g].build(&lengths[0..alpha_size]) {
+                return Err(invalid("bad bzip2 huffman code"));
+            }
+        }
+        Ok((seq_to_unseq, alpha_size))
+    }
+
+    fn push_block_bytes<'x>(
+        &mut self,
+        b: u8,
+        count: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if self.tt.len() + count > self.max_block_size {
+            return Err(invalid("bzip2 block larger than declared"));
+        }
+        self.tt.reserve(count).map_err(|e| xc_err!(
+            xc, ErrorCode::NoSpace, "bzip2 block out of memory",
+            "cannot grow bzip2 block to {} bytes: {}", self.tt.len() + count, e))?;
+        for _ in 0..count {
+            self.tt.push(b as u32).unwrap();
+        }
+        Ok(())
+    }
+
+    /* decodes a whole block into tt and undoes the BWT so that walking
+     * from t_pos gives the block bytes, still run-length encoded */
+    fn read_block<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        self.block_crc = self.read_u32(xc)?;
+        if self.input.read_bits(1, xc)? != 0 {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedOperation, "randomised bzip2 blocks not supported"));
+        }
+        let orig_ptr = self.input.read_bits(24, xc)? as usize;
+        let (seq_to_unseq, alpha_size) = self.read_tables(xc)?;
+        let end_of_block = alpha_size - 1;
+
+        let mut mtf = [0_u8; 256];
+        for (i, m) in mtf.iter_mut().enumerate() {
+            *m = i as u8;
+        }
+        let mut byte_counts = [0_u32; 256];
+        let mut selector = 0;
+        let mut group_left = 0;
+        let mut tree = 0;
+        let mut run = 0_usize;
+        let mut run_weight = 1_usize;
+        self.tt.truncate(0);
+        loop {
+            if group_left == 0 {
+                if selector == self.selectors.len() {
+                    return Err(invalid("bzip2 block ran out of selectors"));
+                }
+                tree = self.selectors.as_slice()[selector] as usize;
+                selector += 1;
+                group_left = GROUP_SIZE;
+            }
+            group_left -= 1;
+            let symbol = self.trees[tree].decode(&mut self.input, xc)?;
+            if symbol == RUN_A || symbol == RUN_B {
+                if run_weight > self.max_block_size {
+                    return Err(invalid("bzip2 run too long"));
+                }
+                run += (symbol + 1) * run_weight;
+                run_weight <<= 1;
+                continue;
+            }
+            if run != 0 {
+                let b = seq_to_unseq[mtf[0] as usize];
+                self.push_block_bytes(b, run, xc)?;
+                byte_counts[b as usize] += run as u32;
+                run = 0;
+                run_weight = 1;
+            }
+            if symbol == end_of_block {
+                break;
+            }
+            let i = symbol - 1;
+            let m = mtf[i];
+            mtf.copy_within(0..i, 1);
+            mtf[0] = m;
+            let b = seq_to_unseq[m as usize];
+            self.push_block_bytes(b, 1, xc)?;
+            byte_counts[b as usize] += 1;
+        }
+        if orig_ptr >= self.tt.len() {
+            return Err(invalid("bad bzip2 BWT origin"));
+        }
+
+        let mut next = [0_u32; 256];
+        let mut sum = 0;
+        for (n, &count) in next.iter_mut().zip(byte_counts.iter()) {
+            *n = sum;
+            sum += count;
+        }
+        let tt = self.tt.as_mut_slice();
+        for i in 0..tt.len() {
+            let b = (tt[i] & 0xFF) as usize;
+            tt[next[b] as usize] |= (i as u32) << 8;
+            next[b] += 1;
+        }
+        self.t_pos = tt[orig_ptr] >> 8;
+        self.left = tt.len();
+        self.same_count = 0;
+        self.repeat_left = 0;
+        self.crc = Crc32Bzip2::new();
+        Ok(())
+    }
+
+    fn read_block_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, State> {
+        xc.check_cancelled()?;
+        let magic = self.input.read_bits(48, xc)?;
+        if magic == END_MAGIC {
+            if self.read_u32(xc)? != self.combined_crc {
+                return Err(invalid("bzip2 stream CRC mismatch"));
+            }
+            return Ok(State::Done);
+        }
+        if magic != BLOCK_MAGIC {
+            return Err(invalid("bad bzip2 block magic"));
+        }
+        self.read_block(xc)?;
+        Ok(State::Block)
+    }
+
+    /* next byte of the current block; None at its end */
+    fn next_block_byte(&mut self) -> Option<u8> {
+        loop {
+            if self.repeat_left != 0 {
+                self.repeat_left -= 1;
+                return Some(self.last);
+            }
+            if self.left == 0 {
+                return None;
+            }
+            let entry = self.tt.as_slice()[self.t_pos as usize];
+            self.t_pos = entry >> 8;
+            self.left -= 1;
+            let b = entry as u8;
+            if self.same_count == 4 {
+                // after 4 equal bytes comes the count of extra repeats
+                self.repeat_left = b;
+                self.same_count = 0;
+                continue;
+            }
+            if self.same_count != 0 && b == self.last {
+                self.same_count += 1;
+            } else {
+                self.same_count = 1;
+                self.last = b;
+            }
+            return Some(b);
+        }
+    }
+
+}
+
+impl<'a, T: Read> Read for Bzip2Reader<'a, T> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            match self.state {
+                State::StreamHeader => {
+                    self.read_stream_header(xc)?;
+                    self.state = State::BlockHeader;
+                },
+                State::BlockHeader => {
+                    self.state = self.read_block_header(xc)?;
+                },
+                State::Block => match self.next_block_byte() {
+                    Some(b) => {
+                        self.crc.update(&[b]);
+                        self.total_out += 1;
+                        buf[n] = b;
+                        n += 1;
+                    },
+                    None => {
+                        if self.crc.value() != self.block_crc {
+                            return Err(invalid("bzip2 block CRC mismatch"));
+                        }
+                        self.combined_crc = self.combined_crc.rotate_left(1) ^ self.block_crc;
+                        self.state = State::BlockHeader;
+                    },
+                },
+                State::Done => break,
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Read> core::fmt::Debug for Bzip2Reader<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Bzip2Reader({:?}, total_out: {})", self.state, self.total_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsOnePassROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    fn bunzip2<'x>(
+        data: &[u8],
+        out: &mut [u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, usize> {
+        let src = ChunkedReader::new(BufferAsOnePassROStream::new(data), 5, 0);
+        let mut r = Bzip2Reader::new(src, xc.get_main_allocator());
+        let n = r.read_uninterrupted(out, xc).map_err(|e| e.to_error())?;
+        assert!(r.is_done());
+        assert_eq!(r.total_out(), n as u64);
+        Ok(n)
+    }
+
+    #[test]
+    fn runs_and_repeats() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let bz = b"\x42\x5A\x68\x31\x31\x41\x59\x26\x53\x59\x78\x8D\xCB\x84\x00\x00\x1A\xF1\x00\x01\x10\x01\x00\x60\x04\x34\x01\x20\x00\x50\x80\x18\x08\xA4\x19\x3D\x4B\x7A\xE3\x83\xB0\x6D\xA6\x03\x43\x8E\x86\x0D\xAB\x01\xA1\x8F\x06\xC0\x7E\x2E\xE4\x8A\x70\xA1\x20\xF1\x1B\x97\x08";
+        let line = b"aaaaaaaaaabbbbbbbbbbbbbbbbbbbbbbbbbb, banana bandana, banana bandana!\n";
+        let mut out = [0_u8; 256];
+        let n = bunzip2(bz, &mut out, &mut xc).unwrap();
+        assert_eq!(n, line.len() * 3);
+        for chunk in out[0..n].chunks(line.len()) {
+            assert_eq!(chunk, &line[..]);
+        }
+
+        let mut bad = *bz;
+        bad[10] ^= 1;
+        let e = bunzip2(&bad, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_msg(), "bzip2 block CRC mismatch");
+        let e = bunzip2(&bz[0..40], &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnexpectedEnd);
+    }
+
+    #[test]
+    fn small_and_empty_streams() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let bz = b"\x42\x5A\x68\x39\x31\x41\x59\x26\x53\x59\xE9\xAD\xBE\x77\x00\x00\x01\x81\x80\x31\x64\x04\x00\x20\x00\x31\x0C\x01\x06\x4F\x43\x64\x09\x45\xDC\x91\x4E\x14\x24\x3A\x6B\x6F\x9D\xC0";
+        let mut out = [0_u8; 16];
+        let n = bunzip2(bz, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], *b"halfbit");
+        let empty = b"\x42\x5A\x68\x31\x17\x72\x45\x38\x50\x90\x00\x00\x00\x00";
+        assert_eq!(bunzip2(empty, &mut out, &mut xc).unwrap(), 0);
+        let e = bunzip2(b"BZh0", &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+    }
+}
@@ -0,0 +1,245 @@
+/* Crc32 ********************************************************************/
+/* the CRC-32 of zip, gzip and PNG (reflected polynomial 0xEDB88320) */
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+#[derive(Copy, Clone, Debug)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+
+    pub fn new() -> Self {
+        Crc32(0xFFFF_FFFF)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut c = self.0;
+        for &b in data {
+            c = CRC32_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+        }
+        self.0 = c;
+    }
+
+    /* checksum of the data fed so far */
+    pub fn value(&self) -> u32 {
+        !self.0
+    }
+
+    pub fn of(data: &[u8]) -> u32 {
+        let mut c = Crc32::new();
+        c.update(data);
+        c.value()
+    }
+
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+/* Crc32Bzip2 ***************************************************************/
+/* the CRC-32 of bzip2: same polynomial as Crc32 but not reflected */
+const fn crc32_bzip2_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = (i as u32) << 24;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 0x8000_0000 != 0 { 0x04C1_1DB7 ^ (c << 1) } else { c << 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_BZIP2_TABLE: [u32; 256] = crc32_bzip2_table();
+
+#[derive(Copy, Clone, Debug)]
+pub struct Crc32Bzip2(u32);
+
+impl Crc32Bzip2 {
+
+    pub fn new() -> Self {
+        Crc32Bzip2(0xFFFF_FFFF)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut c = self.0;
+        for &b in data {
+            c = CRC32_BZIP2_TABLE[((c >> 24) ^ b as u32) as usize] ^ (c << 8);
+        }
+        self.0 = c;
+    }
+
+    pub fn value(&self) -> u32 {
+        !self.0
+    }
+
+    pub fn of(data: &[u8]) -> u32 {
+        let mut c = Crc32Bzip2::new();
+        c.update(data);
+        c.value()
+    }
+
+}
+
+impl Default for Crc32Bzip2 {
+    fn default() -> Self {
+        Crc32Bzip2::new()
+    }
+}
+
+/* Crc64 ********************************************************************/
+/* the CRC-64 of xz (ECMA-182 polynomial, reflected) */
+const fn crc64_table() -> [u64; 256] {
+    let mut table = [0_u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u64;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xC96C_5795_D787_0F42 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC64_TABLE: [u64; 256] = crc64_table();
+
+#[derive(Copy, Clone, Debug)]
+pub struct Crc64(u64);
+
+impl Crc64 {
+
+    pub fn new() -> Self {
+        Crc64(u64::MAX)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut c = self.0;
+        for &b in data {
+            c = CRC64_TABLE[((c ^ b as u64) & 0xFF) as usize] ^ (c >> 8);
+        }
+        self.0 = c;
+    }
+
+    pub fn value(&self) -> u64 {
+        !self.0
+    }
+
+    pub fn of(data: &[u8]) -> u64 {
+        let mut c = Crc64::new();
+        c.update(data);
+        c.value()
+    }
+
+}
+
+impl Default for Crc64 {
+    fn default() -> Self {
+        Crc64::new()
+    }
+}
+
+/* Adler32 ******************************************************************/
+/* the checksum of zlib streams */
+const ADLER_MOD: u32 = 65521;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+
+    pub fn new() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        /* 5552 bytes is the most that can be summed before b can overflow */
+        for chunk in data.chunks(5552) {
+            for &x in chunk {
+                self.a += x as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER_MOD;
+            self.b %= ADLER_MOD;
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+
+    pub fn of(data: &[u8]) -> u32 {
+        let mut c = Adler32::new();
+        c.update(data);
+        c.value()
+    }
+
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Adler32::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_known_values() {
+        assert_eq!(Crc32::of(b""), 0);
+        assert_eq!(Crc32::of(b"123456789"), 0xCBF4_3926);
+        let mut c = Crc32::default();
+        c.update(b"1234");
+        c.update(b"56789");
+        assert_eq!(c.value(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn crc32_bzip2_and_crc64_known_values() {
+        assert_eq!(Crc32Bzip2::of(b"123456789"), 0xFC89_1918);
+        assert_eq!(Crc64::of(b""), 0);
+        assert_eq!(Crc64::of(b"123456789"), 0x995D_C9BB_DF19_39FA);
+    }
+
+    #[test]
+    fn adler32_known_values() {
+        assert_eq!(Adler32::of(b""), 1);
+        assert_eq!(Adler32::of(b"Wikipedia"), 0x11E6_0398);
+        let big = [0xFF_u8; 10000];
+        let mut c = Adler32::new();
+        for b in big.iter() {
+            c.update(core::slice::from_ref(b));
+        }
+        assert_eq!(c.value(), Adler32::of(&big));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::ExecutionContext;
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::io::BitReader;
+
+/* longest code of bzip2; DEFLATE stops at 15 */
+pub(crate) const MAX_CODE_BITS: usize = 20;
+
+/* Huffman ******************************************************************/
+/* canonical code given as symbol counts per length and the symbols sorted
+ * by code; codes are read one bit at a time, first bit being the most
+ * significant one of the code, for both DEFLATE and bzip2 */
+pub(crate) struct Huffman<const N: usize> {
+    counts: [u16; MAX_CODE_BITS + 1],
+    symbols: [u16; N],
+}
+
+impl<const N: usize> Huffman<N> {
+
+    pub(crate) const fn new() -> Self {
+        Huffman { counts: [0; MAX_CODE_BITS + 1], symbols: [0; N] }
+    }
+
+    /* lengths of 0 mark unused symbols; fails on over-subscribed sets of
+     * lengths; incomplete ones are accepted and their missing codes fail
+     * when decoded */
+    pub(crate) fn build(&mut self, lengths: &[u8]) -> bool {
+        self.counts = [0; MAX_CODE_BITS + 1];
+        for &l in lengths {
+            if l as usize > MAX_CODE_BITS {
+                return false;
+            }
+            self.counts[l as usize] += 1;
+        }
+        self.counts[0] = 0;
+        let mut left = 1_i32;
+        for len in 1..=MAX_CODE_BITS {
+            left = (left << 1) - self.counts[len] as i32;
+            if left < 0 {
+                return false;
+            }
+        }
+        let mut offsets = [0_u16; MAX_CODE_BITS + 1];
+        for len in 1..MAX_CODE_BITS {
+            offsets[len + 1] = offsets[len] + self.counts[len];
+        }
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                self.symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        true
+    }
+
+    pub(crate) fn decode<'x, T: Read>(
+        &self,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, usize> {
+        let mut code = 0_i32; // bits read so far
+        let mut first = 0_i32; // first code of the current length
+        let mut index = 0_i32; // index of that code in symbols
+        for len in 1..=MAX_CODE_BITS {
+            code |= input.read_bits(1, xc)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(IOError::with_str(ErrorCode::InvalidData, "invalid huffman code"))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::BitOrder;
+    use crate::io::stream::BufferAsOnePassROStream;
+
+    #[test]
+    fn canonical_codes() {
+        let mut xc = ExecutionContext::nop();
+        let mut h: Huffman<4> = Huffman::new();
+        assert!(!h.build(&[1, 1, 1, 0]));
+        // A=10 B=0 C=110 D=111
+        assert!(h.build(&[2, 1, 3, 3]));
+        let data = [0b0101_1011_u8, 0b1000_0000];
+        let mut i = BitReader::with_read_ahead(BufferAsOnePassROStream::new(&data), BitOrder::MsbFirst);
+        let mut symbols = [0_usize; 4];
+        for s in symbols.iter_mut() {
+            *s = h.decode(&mut i, &mut xc).unwrap();
+        }
+        assert_eq!(symbols, [1, 0, 2, 3]);
+        assert!(h.build(&[1, 0, 0, 0]));
+        let mut i = BitReader::with_read_ahead(BufferAsOnePassROStream::new(&[0xFF]), BitOrder::MsbFirst);
+        assert!(h.decode(&mut i, &mut xc).is_err());
+    }
+}
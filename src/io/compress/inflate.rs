@@ -0,0 +1,560 @@
+use crate::ExecutionContext;
+use crate::io::BitOrder;
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::mm::AllocatorRef;
+use crate::mm::AllocError;
+use crate::mm::Vector;
+use super::Adler32;
+use super::Crc32;
+use crate::io::BitReader;
+use super::huffman::Huffman;
+
+/* InflateFormat ************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InflateFormat {
+    Raw, // bare DEFLATE blocks
+    Gzip, // RFC 1952 member: header, DEFLATE blocks, CRC-32 and size
+    Zlib, // RFC 1950: 2-byte header, DEFLATE blocks, Adler-32
+}
+
+const WINDOW_SIZE: usize = 0x8000;
+const MAX_LIT_CODES: usize = 288;
+const MAX_DIST_CODES: usize = 32;
+
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258 ];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0 ];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577 ];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13 ];
+/* order in which code length code lengths are stored */
+const CL_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15 ];
+
+fn invalid(msg: &'static str) -> IOError<'static> {
+    IOError::with_str(ErrorCode::InvalidData, msg)
+}
+
+/* InflateReader ************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    Header,
+    BlockHeader,
+    Stored(u16),
+    Codes,
+    Trailer,
+    Done,
+}
+
+/* decompresses DEFLATE data as it is read; for gzip only the first member
+ * is decoded; checksums and sizes in trailers are checked before reporting
+ * the end of data */
+pub struct InflateReader<'a, T: Read> {
+    input: BitReader<'a, T>,
+    format: InflateFormat,
+    state: State,
+    last_block: bool,
+    lit: Huffman<MAX_LIT_CODES>,
+    dist: Huffman<MAX_DIST_CODES>,
+    window: Vector<'a, u8>,
+    window_pos: usize,
+    window_full: bool,
+    copy_len: usize,
+    copy_dist: usize,
+    crc: Crc32,
+    adler: Adler32,
+    total_out: u64,
+}
+
+impl<'a, T: Read> InflateReader<'a, T> {
+
+    /* allocates the 32KiB history window */
+    pub fn new(
+        src: T,
+        format: InflateFormat,
+        allocator: AllocatorRef<'a>,
+    ) -> Result<Self, AllocError> {
+        let mut window = Vector::new(allocator);
+        window.reserve(WINDOW_SIZE)?;
+        for _ in 0..WINDOW_SIZE {
+            window.push(0_u8).unwrap();
+        }
+        Ok(InflateReader {
+            input: BitReader::with_read_ahead(src, BitOrder::LsbFirst),
+            format,
+            state: State::Header,
+            last_block: false,
+            lit: Huffman::new(),
+            dist: Huffman::new(),
+            window,
+            window_pos: 0,
+            window_full: false,
+            copy_len: 0,
+            copy_dist: 0,
+            crc: Crc32::new(),
+            adler: Adler32::new(),
+            total_out: 0,
+        })
+    }
+
+    pub fn format(&self) -> InflateFormat {
+        self.format
+    }
+
+    /* count of decompressed bytes produced so far */
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /* true once the end of data was reached and the trailer checked */
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /* the source may be positioned past the end of the compressed data as
+     * input is read in chunks */
+    pub fn into_inner(self) -> T {
+        self.input.into_inner().expect("input is read ahead")
+    }
+
+    fn gzip_header_byte<'x>(
+        &mut self,
+        hcrc: &mut Crc32,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u8> {
+        let b = self.input.read_byte(xc)?;
+        hcrc.update(&[b]);
+        Ok(b)
+    }
+
+    fn read_gzip_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut hcrc = Crc32::new();
+        let mut fixed = [0_u8; 10];
+        for b in fixed.iter_mut() {
+            *b = self.gzip_header_byte(&mut hcrc, xc)?;
+        }
+        if fixed[0..2] != *b"\x1F\x8B" {
+            return Err(invalid("not a gzip stream"));
+        }
+        if fixed[2] != 8 {
+            return Err(invalid("unsupported gzip compression method"));
+        }
+        let flags = fixed[3];
+        if flags & 0xE0 != 0 {
+            return Err(invalid("gzip header reserved flags set"));
+        }
+        if flags & 0x04 != 0 { // FEXTRA
+            let lo = self.gzip_header_byte(&mut hcrc, xc)? as u16;
+            let hi = self.gzip_header_byte(&mut hcrc, xc)? as u16;
+            for _ in 0..(lo | (hi << 8)) {
+                self.gzip_header_byte(&mut hcrc, xc)?;
+            }
+        }
+        for &flag in &[0x08_u8, 0x10] { // FNAME, FCOMMENT
+            if flags & flag != 0 {
+                while self.gzip_header_byte(&mut hcrc, xc)? != 0 {}
+            }
+        }
+        if flags & 0x02 != 0 { // FHCRC
+            let expected = hcrc.value() & 0xFFFF;
+            let lo = self.input.read_byte(xc)? as u32;
+            let hi = self.input.read_byte(xc)? as u32;
+            if lo | (hi << 8) != expected {
+                return Err(invalid("gzip header CRC mismatch"));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_zlib_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let cmf = self.input.read_byte(xc)?;
+        let flg = self.input.read_byte(xc)?;
+        if !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) || cmf & 0x0F != 8 || cmf >> 4 > 7 {
+            return Err(invalid("bad zlib header"));
+        }
+        if flg & 0x20 != 0 {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedOperation, "zlib preset dictionaries not supported"));
+        }
+        Ok(())
+    }
+
+    fn read_u32<'x>(
+        &mut self,
+        big_endian: bool,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        let mut v = 0_u32;
+        for i in 0..4 {
+            let b = self.input.read_byte(xc)? as u32;
+            v = if big_endian { (v << 8) | b } else { v | (b << (8 * i)) };
+        }
+        Ok(v)
+    }
+
+    fn check_trailer<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        self.input.align_to_byte();
+        match self.format {
+            InflateFormat::Raw => {},
+            InflateFormat::Gzip => {
+                if self.read_u32(false, xc)? != self.crc.value() {
+                    return Err(invalid("gzip data CRC mismatch"));
+                }
+                if self.read_u32(false, xc)? != self.total_out as u32 {
+                    return Err(invalid("gzip data size mismatch"));
+                }
+            },
+            InflateFormat::Zlib => {
+                if self.read_u32(true, xc)? != self.adler.value() {
+                    return Err(invalid("zlib data checksum mismatch"));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn build_fixed_tables(&mut self) {
+        let mut lengths = [0_u8; MAX_LIT_CODES];
+        for (i, l) in lengths.iter_mut().enumerate() {
+            *l = match i {
+                0..=143 => 8,
+                144..=255 => 9,
+                256..=279 => 7,
+                _ => 8,
+            };
+        }
+        self.lit.build(&lengths);
+        self.dist.build(&[5; 30]);
+    }
+
+    fn read_dynamic_tables<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let nlen = self.input.read_bits(5, xc)? as usize + 257;
+        let ndist = self.input.read_bits(5, xc)? as usize + 1;
+        let ncode = self.input.read_bits(4, xc)? as usize + 4;
+        if nlen > 286 || ndist > 30 {
+            return Err(invalid("bad deflate code counts"));
+        }
+        let mut cl_lengths = [0_u8; 19];
+        for &i in &CL_ORDER[0..ncode] {
+            cl_lengths[i] = self.input.read_bits(3, xc)? as u8;
+        }
+        let mut cl: Huffman<19> = Huffman::new();
+        if !cl.build(&cl_lengths) {
+            return Err(invalid("bad deflate code length code"));
+        }
+        let mut lengths = [0_u8; 286 + 30];
+        let mut i = 0;
+        while i < nlen + ndist {
+            let symbol = cl.decode(&mut self.input, xc)?;
+            if symbol < 16 {
+                lengths[i] = symbol as u8;
+                i += 1;
+                continue;
+            }
+            let (len, repeat) = match symbol {
+                16 => {
+                    if i == 0 {
+                        return Err(invalid("deflate length repeat with no previous length"));
+                    }
+                    (lengths[i - 1], 3 + self.input.read_bits(2, xc)? as usize)
+                },
+                17 => (0, 3 + self.input.read_bits(3, xc)? as usize),
+                _ => (0, 11 + self.input.read_bits(7, xc)? as usize),
+            };
+            if i + repeat > nlen + ndist {
+                return Err(invalid("deflate code lengths overflow"));
+            }
+            for l in &mut lengths[i..i + repeat] {
+                *l = len;
+            }
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(invalid("deflate block without end-of-block code"));
+        }
+        if !self.lit.build(&lengths[0..nlen]) || !self.dist.build(&lengths[nlen..nlen + ndist]) {
+            return Err(invalid("bad deflate huffman code"));
+        }
+        Ok(())
+    }
+
+    fn read_block_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, State> {
+        if self.last_block {
+            return Ok(State::Trailer);
+        }
+        xc.check_cancelled()?;
+        self.last_block = self.input.read_bits(1, xc)? != 0;
+        Ok(match self.input.read_bits(2, xc)? {
+            0 => {
+                self.input.align_to_byte();
+                let len = self.input.read_bits(16, xc)?;
+                if self.input.read_bits(16, xc)? != !len & 0xFFFF {
+                    return Err(invalid("deflate stored block length mismatch"));
+                }
+                State::Stored(len as u16)
+            },
+            1 => {
+                self.build_fixed_tables();
+                State::Codes
+            },
+            2 => {
+                self.read_dynamic_tables(xc)?;
+                State::Codes
+            },
+            _ => return Err(invalid("reserved deflate block type")),
+        })
+    }
+
+    fn put(&mut self, b: u8) {
+        self.window.as_mut_slice()[self.window_pos] = b;
+        self.window_pos = (self.window_pos + 1) % WINDOW_SIZE;
+        self.window_full |= self.window_pos == 0;
+        match self.format {
+            InflateFormat::Raw => {},
+            InflateFormat::Gzip => self.crc.update(&[b]),
+            InflateFormat::Zlib => self.adler.update(&[b]),
+        }
+        self.total_out += 1;
+    }
+
+    /* next byte of a block with huffman codes; None at the end of block */
+    fn next_coded_byte<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, Option<u8>> {
+        if self.copy_len == 0 {
+            let symbol = self.lit.decode(&mut self.input, xc)?;
+            if symbol < 256 {
+                return Ok(Some(symbol as u8));
+            }
+            if symbol == 256 {
+                return Ok(None);
+            }
+            let s = symbol - 257;
+            if s >= LEN_BASE.len() {
+                return Err(invalid("bad deflate length code"));
+            }
+            let len = LEN_BASE[s] as usize + self.input.read_bits(LEN_EXTRA[s] as u32, xc)? as usize;
+            let d = self.dist.decode(&mut self.input, xc)?;
+            if d >= DIST_BASE.len() {
+                return Err(invalid("bad deflate distance code"));
+            }
+            let dist = DIST_BASE[d] as usize + self.input.read_bits(DIST_EXTRA[d] as u32, xc)? as usize;
+            let available = if self.window_full { WINDOW_SIZE } else { self.window_pos };
+            if dist > available {
+                return Err(invalid("deflate distance too far back"));
+            }
+            self.copy_len = len;
+            self.copy_dist = dist;
+        }
+        self.copy_len -= 1;
+        let i = (self.window_pos + WINDOW_SIZE - self.copy_dist) % WINDOW_SIZE;
+        Ok(Some(self.window.as_slice()[i]))
+    }
+
+}
+
+impl<'a, T: Read> Read for InflateReader<'a, T> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            let b = match self.state {
+                State::Header => {
+                    match self.format {
+                        InflateFormat::Raw => {},
+                        InflateFormat::Gzip => self.read_gzip_header(xc)?,
+                        InflateFormat::Zlib => self.read_zlib_header(xc)?,
+                    }
+                    self.state = State::BlockHeader;
+                    continue;
+                },
+                State::BlockHeader => {
+                    self.state = self.read_block_header(xc)?;
+                    continue;
+                },
+                State::Stored(0) => {
+                    self.state = State::BlockHeader;
+                    continue;
+                },
+                State::Stored(left) => {
+                    self.state = State::Stored(left - 1);
+                    self.input.read_byte(xc)?
+                },
+                State::Codes => match self.next_coded_byte(xc)? {
+                    Some(b) => b,
+                    None => {
+                        self.state = State::BlockHeader;
+                        continue;
+                    },
+                },
+                State::Trailer => {
+                    self.check_trailer(xc)?;
+                    self.state = State::Done;
+                    break;
+                },
+                State::Done => break,
+            };
+            self.put(b);
+            buf[n] = b;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Read> core::fmt::Debug for InflateReader<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "InflateReader({:?}, {:?}, total_out: {})",
+               self.format, self.state, self.total_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsOnePassROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    fn inflate_all<'x>(
+        data: &[u8],
+        format: InflateFormat,
+        out: &mut [u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, usize> {
+        let a = xc.get_main_allocator();
+        let src = ChunkedReader::new(BufferAsOnePassROStream::new(data), 7, 0);
+        let mut r = InflateReader::new(src, format, a).unwrap();
+        let mut n = 0;
+        loop {
+            let chunk_len = core::cmp::min(out.len() - n, 5);
+            let m = r.read(&mut out[n..n + chunk_len], xc)?;
+            if m == 0 { break; }
+            n += m;
+        }
+        assert!(r.is_done() || out.len() == n);
+        Ok(n)
+    }
+
+    #[test]
+    fn gzip_fixed_huffman() {
+        let token = crate::CancellationToken::new();
+        let mut buffer = [0_u8; 0x9000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let gz = b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x02\x03\xCB\x48\xCD\xC9\xC9\xD7\x51\xC8\x40\xA2\x14\x32\x12\x73\xD2\x92\x32\x4B\xB8\x00\xA5\x46\x75\x62\x1C\x00\x00\x00";
+        let mut out = [0_u8; 64];
+        let n = inflate_all(gz, InflateFormat::Gzip, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], *b"hello, hello, hello halfbit\n");
+
+        let mut bad = *gz;
+        bad[gz.len() - 8] ^= 1;
+        let e = inflate_all(&bad, InflateFormat::Gzip, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+        let e = inflate_all(&gz[0..20], InflateFormat::Gzip, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnexpectedEnd);
+
+        token.cancel();
+        xc.set_cancellation_token(Some(&token));
+        let e = inflate_all(gz, InflateFormat::Gzip, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::Cancelled);
+    }
+
+    #[test]
+    fn gzip_name_and_header_crc() {
+        let mut buffer = [0_u8; 0x9000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let gz = b"\x1F\x8B\x08\x0A\x00\x00\x00\x00\x00\x03\x61\x2E\x74\x78\x74\x00\x72\xD9\x4B\x4C\x4A\x06\x00\xC2\x41\x24\x35\x03\x00\x00\x00";
+        let mut out = [0_u8; 8];
+        let n = inflate_all(gz, InflateFormat::Gzip, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], *b"abc");
+        let mut bad = *gz;
+        bad[16] ^= 1;
+        let e = inflate_all(&bad, InflateFormat::Gzip, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_msg(), "gzip header CRC mismatch");
+    }
+
+    #[test]
+    fn raw_matches_across_window() {
+        let mut buffer = [0_u8; 0x9000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let raw = b"\x0B\xC9\x48\x55\x28\x2C\xCD\x4C\xCE\x56\x48\x2A\xCA\x2F\xCF\x53\x48\xCB\xAF\x50\xC8\x2A\xCD\x2D\x28\x56\xC8\x2F\x4B\x2D\x52\x28\x01\x4A\xE7\x24\x56\x55\x2A\xA4\xE4\xA7\xEB\x29\x84\xD0\x4C\x31\x03\x23\x13\x33\x0B\x2B\x1B\x3B\x07\x27\x17\x37\x0F\x2F\x1F\xBF\x80\xA0\x90\xB0\x88\xA8\x98\xB8\x84\xA4\x94\xB4\x8C\xAC\x9C\xBC\x82\xA2\x92\xB2\x8A\xAA\x9A\x7A\xC8\xD0\x72\x2E\x00";
+        let mut expected = [0_u8; 350];
+        let sentence = b"The quick brown fox jumps over the lazy dog. ";
+        for half in expected.chunks_mut(175) {
+            for (i, b) in half.iter_mut().enumerate() {
+                *b = if i < 135 { sentence[i % sentence.len()] } else { (i - 135) as u8 };
+            }
+        }
+        let mut out = [0_u8; 400];
+        let n = inflate_all(raw, InflateFormat::Raw, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], expected[..]);
+    }
+
+    #[test]
+    fn raw_dynamic_huffman() {
+        let mut buffer = [0_u8; 0x9000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let raw = b"\x45\x8E\x8B\x0D\x00\x31\x08\x42\x67\xE5\xB3\xFF\x0C\xA7\x50\x73\x35\xD5\xE4\xA1\x22\x00\x02\xE2\x16\x4F\xEE\x3B\x44\xCE\x0F\x50\xF1\xC4\x09\x9B\x43\xB6\x8E\x4C\x6F\x8B\xD2\xBA\xD8\x91\xBA\x0B\x70\xC6\x45\xCA\xBA\x45\x11\x62\x10\x9F\x0E\xBE\x23\xD8\x65\xA9\xD6\x43\x1E\xA2\x5A\xFA\xBF\x34\xA7\x7E";
+        let mut out = [0_u8; 256];
+        let n = inflate_all(raw, InflateFormat::Raw, &mut out, &mut xc).unwrap();
+        assert_eq!(n, 200);
+        assert_eq!(out[0..16], *b"aaabaacbaabadbaa");
+        assert_eq!(Crc32::of(&out[0..n]), 2105633143);
+    }
+
+    #[test]
+    fn zlib_stored_block() {
+        let mut buffer = [0_u8; 0x9000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let z = b"\x78\x01\x01\x07\x00\xF8\xFF\x73\x74\x6F\x72\x65\x64\x21\x0B\xEF\x02\xB3";
+        let mut out = [0_u8; 16];
+        let n = inflate_all(z, InflateFormat::Zlib, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], *b"stored!");
+        let mut bad = *z;
+        bad[1] = 0x02;
+        assert!(inflate_all(&bad, InflateFormat::Zlib, &mut out, &mut xc).is_err());
+    }
+
+    #[test]
+    fn window_needs_memory() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let src = BufferAsOnePassROStream::new(b"");
+        assert!(InflateReader::new(src, InflateFormat::Raw, a.to_ref()).is_err());
+    }
+}
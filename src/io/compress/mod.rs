@@ -0,0 +1,17 @@
+pub mod checksum;
+pub use checksum::Crc32;
+pub use checksum::Crc32Bzip2;
+pub use checksum::Crc64;
+pub use checksum::Adler32;
+
+mod huffman;
+
+pub mod inflate;
+pub use inflate::InflateReader;
+pub use inflate::InflateFormat;
+
+pub mod bzip2;
+pub use bzip2::Bzip2Reader;
+
+pub mod xz;
+pub use xz::XzReader;
@@ -0,0 +1,972 @@
+use crate::ExecutionContext;
+use crate::io::BitOrder;
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::mm::AllocatorRef;
+use crate::mm::Vector;
+use crate::xc_err;
+use super::Crc32;
+use super::Crc64;
+use crate::io::BitReader;
+
+const HEADER_MAGIC: &[u8; 6] = b"\xFD7zXZ\x00";
+const FOOTER_MAGIC: &[u8; 2] = b"YZ";
+const FILTER_LZMA2: u64 = 0x21;
+const CHECK_NONE: u8 = 0;
+const CHECK_CRC32: u8 = 1;
+const CHECK_CRC64: u8 = 4;
+
+const STATES: usize = 12;
+const POS_STATES_MAX: usize = 16;
+const LIT_STATES_MAX: usize = 16; // lc + lp is at most 4 in LZMA2
+const LIT_PROBS: usize = 0x300;
+const MATCH_LEN_MIN: usize = 2;
+const END_POS_MODEL_INDEX: u32 = 14;
+const PROB_INIT: u16 = 0x400;
+
+fn invalid(msg: &'static str) -> IOError<'static> {
+    IOError::with_str(ErrorCode::InvalidData, msg)
+}
+
+/* size of the check field for each check type */
+fn check_size(check_id: u8) -> usize {
+    match check_id {
+        0 => 0,
+        1..=3 => 4,
+        4..=6 => 8,
+        7..=9 => 16,
+        10..=12 => 32,
+        _ => 64,
+    }
+}
+
+/* multibyte integer from a block header */
+fn varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut v = 0_u64;
+    for i in 0..9 {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        v |= ((b & 0x7F) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return if i != 0 && b == 0 { None } else { Some(v) };
+        }
+    }
+    None
+}
+
+/* Dict *********************************************************************/
+/* LZMA history; grows with the output up to the dictionary size declared
+ * by the block, then wraps around */
+struct Dict<'a> {
+    buf: Vector<'a, u8>,
+    cap: usize,
+    pos: usize,
+    total: u64, // bytes put since the last reset
+}
+
+impl<'a> Dict<'a> {
+
+    fn reset(&mut self) {
+        self.pos = 0;
+        self.total = 0;
+    }
+
+    fn filled(&self) -> usize {
+        core::cmp::min(self.total, self.cap as u64) as usize
+    }
+
+    fn put<'x>(
+        &mut self,
+        b: u8,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if self.pos == self.buf.len() {
+            self.buf.push(b).map_err(|(e, _)| xc_err!(
+                xc, ErrorCode::NoSpace, "xz dictionary out of memory",
+                "cannot grow xz dictionary to {} bytes: {}", self.pos + 1, e))?;
+        } else {
+            self.buf.as_mut_slice()[self.pos] = b;
+        }
+        self.pos += 1;
+        if self.pos == self.cap {
+            self.pos = 0;
+        }
+        self.total += 1;
+        Ok(())
+    }
+
+    /* byte at the given distance back, 1 being the last one put; the
+     * distance must be at most filled() */
+    fn get(&self, dist: usize) -> u8 {
+        let i = if self.pos >= dist { self.pos - dist } else { self.pos + self.cap - dist };
+        self.buf.as_slice()[i]
+    }
+
+}
+
+/* RangeDecoder *************************************************************/
+/* kept normalized between calls, so that at the end of a chunk it has
+ * consumed exactly the bytes of that chunk */
+struct RangeDecoder {
+    range: u32,
+    code: u32,
+}
+
+impl RangeDecoder {
+
+    fn init<'x, T: Read>(
+        &mut self,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if input.read_byte(xc)? != 0 {
+            return Err(invalid("bad lzma range coder start"));
+        }
+        self.code = 0;
+        for _ in 0..4 {
+            self.code = (self.code << 8) | input.read_byte(xc)? as u32;
+        }
+        self.range = u32::MAX;
+        Ok(())
+    }
+
+    fn normalize<'x, T: Read>(
+        &mut self,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if self.range < 1 << 24 {
+            self.range <<= 8;
+            self.code = (self.code << 8) | input.read_byte(xc)? as u32;
+        }
+        Ok(())
+    }
+
+    fn bit<'x, T: Read>(
+        &mut self,
+        prob: &mut u16,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        let bound = (self.range >> 11) * *prob as u32;
+        let b = if self.code < bound {
+            self.range = bound;
+            *prob += (0x800 - *prob) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *prob -= *prob >> 5;
+            1
+        };
+        self.normalize(input, xc)?;
+        Ok(b)
+    }
+
+    /* most significant bit first; probs has 1 << bits entries */
+    fn bit_tree<'x, T: Read>(
+        &mut self,
+        probs: &mut [u16],
+        bits: u32,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        let mut m = 1;
+        for _ in 0..bits {
+            m = (m << 1) | self.bit(&mut probs[m as usize], input, xc)?;
+        }
+        Ok(m - (1 << bits))
+    }
+
+    /* least significant bit first */
+    fn bit_tree_reverse<'x, T: Read>(
+        &mut self,
+        probs: &mut [u16],
+        bits: u32,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        let mut m = 1;
+        let mut v = 0;
+        for i in 0..bits {
+            let b = self.bit(&mut probs[m as usize], input, xc)?;
+            m = (m << 1) | b;
+            v |= b << i;
+        }
+        Ok(v)
+    }
+
+    /* bits with a fixed probability of 1/2 */
+    fn direct<'x, T: Read>(
+        &mut self,
+        bits: u32,
+        input: &mut BitReader<'_, T>,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        let mut v = 0_u32;
+        for _ in 0..bits {
+            self.range >>= 1;
+            self.code = self.code.wrapping_sub(self.range);
+            let t = 0_u32.wrapping_sub(self.code >> 31);
+            self.code = self.code.wrapping_add(self.range & t);
+            v = (v << 1).wrapping_add(t.wrapping_add(1));
+            self.normalize(input, xc)?;
+        }
+        Ok(v)
+    }
+
+}
+
+/* LZMA probabilities *******************************************************/
+struct LenProbs {
+    choice: u16,
+    choice2: u16,
+    low: [u16; POS_STATES_MAX * 8],
+    mid: [u16; POS_STATES_MAX * 8],
+    high: [u16; 256],
+}
+
+impl LenProbs {
+    const fn new() -> Self {
+        LenProbs {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [PROB_INIT; POS_STATES_MAX * 8],
+            mid: [PROB_INIT; POS_STATES_MAX * 8],
+            high: [PROB_INIT; 256],
+        }
+    }
+}
+
+struct Probs {
+    is_match: [u16; STATES * POS_STATES_MAX],
+    is_rep: [u16; STATES],
+    is_rep_g0: [u16; STATES],
+    is_rep_g1: [u16; STATES],
+    is_rep_g2: [u16; STATES],
+    is_rep0_long: [u16; STATES * POS_STATES_MAX],
+    pos_slot: [u16; 4 * 64],
+    spec_pos: [u16; 115],
+    align: [u16; 16],
+    len: LenProbs,
+    rep_len: LenProbs,
+}
+
+impl Probs {
+    const fn new() -> Self {
+        Probs {
+            is_match: [PROB_INIT; STATES * POS_STATES_MAX],
+            is_rep: [PROB_INIT; STATES],
+            is_rep_g0: [PROB_INIT; STATES],
+            is_rep_g1: [PROB_INIT; STATES],
+            is_rep_g2: [PROB_INIT; STATES],
+            is_rep0_long: [PROB_INIT; STATES * POS_STATES_MAX],
+            pos_slot: [PROB_INIT; 4 * 64],
+            spec_pos: [PROB_INIT; 115],
+            align: [PROB_INIT; 16],
+            len: LenProbs::new(),
+            rep_len: LenProbs::new(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    StreamHeader,
+    BlockHeader,
+    ChunkHeader,
+    Uncompressed(usize),
+    Lzma,
+    BlockEnd,
+    Done,
+}
+
+/* XzReader *****************************************************************/
+/* decompresses an xz stream as it is read; blocks must use the LZMA2
+ * filter alone; CRC32 and CRC64 checks are verified at the end of each
+ * block, other checks are skipped; the index and footer are checked before
+ * reporting the end of data; only the first stream is decoded when several
+ * are concatenated; the dictionary grows with the output up to the size the
+ * block declares */
+pub struct XzReader<'a, T: Read> {
+    input: BitReader<'a, T>,
+    state: State,
+    check_id: u8,
+    stream_flags: [u8; 2],
+    crc32: Crc32,
+    crc64: Crc64,
+    // current block
+    header_size: u64,
+    declared_packed: Option<u64>,
+    declared_unpacked: Option<u64>,
+    block_in_start: u64,
+    block_out_start: u64,
+    // blocks done, to match against the index
+    block_count: u64,
+    unpadded_sum: u64,
+    unpacked_sum: u64,
+    // LZMA2 and LZMA
+    need_dict_reset: bool,
+    need_props: bool,
+    chunk_in_start: u64,
+    chunk_packed: u64,
+    unpacked_left: usize,
+    dict: Dict<'a>,
+    rc: RangeDecoder,
+    probs: Probs,
+    lit_probs: Vector<'a, u16>,
+    lc: u32,
+    lp: u32,
+    pb: u32,
+    lzma_state: usize,
+    reps: [u32; 4],
+    copy_len: usize,
+    total_out: u64,
+}
+
+impl<'a, T: Read> XzReader<'a, T> {
+
+    /* nothing is allocated until the first block is decoded */
+    pub fn new(src: T, allocator: AllocatorRef<'a>) -> Self {
+        XzReader {
+            input: BitReader::with_read_ahead(src, BitOrder::LsbFirst),
+            state: State::StreamHeader,
+            check_id: CHECK_NONE,
+            stream_flags: [0; 2],
+            crc32: Crc32::new(),
+            crc64: Crc64::new(),
+            header_size: 0,
+            declared_packed: None,
+            declared_unpacked: None,
+            block_in_start: 0,
+            block_out_start: 0,
+            block_count: 0,
+            unpadded_sum: 0,
+            unpacked_sum: 0,
+            need_dict_reset: true,
+            need_props: true,
+            chunk_in_start: 0,
+            chunk_packed: 0,
+            unpacked_left: 0,
+            dict: Dict { buf: Vector::new(allocator), cap: 1, pos: 0, total: 0 },
+            rc: RangeDecoder { range: 0, code: 0 },
+            probs: Probs::new(),
+            lit_probs: Vector::new(allocator),
+            lc: 0,
+            lp: 0,
+            pb: 0,
+            lzma_state: 0,
+            reps: [0; 4],
+            copy_len: 0,
+            total_out: 0,
+        }
+    }
+
+    /* count of decompressed bytes produced so far */
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /* true once the end of stream was reached and its index checked */
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /* the source may be positioned past the end of the compressed data as
+     * input is read in chunks */
+    pub fn into_inner(self) -> T {
+        self.input.into_inner().expect("input is read ahead")
+    }
+
+    fn read_bytes<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        for b in buf.iter_mut() {
+            *b = self.input.read_byte(xc)?;
+        }
+        Ok(())
+    }
+
+    fn read_u16be<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, usize> {
+        let hi = self.input.read_byte(xc)? as usize;
+        Ok((hi << 8) | self.input.read_byte(xc)? as usize)
+    }
+
+    fn read_stream_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut h = [0_u8; 12];
+        self.read_bytes(&mut h, xc)?;
+        if h[0..6] != *HEADER_MAGIC {
+            return Err(invalid("not an xz stream"));
+        }
+        if Crc32::of(&h[6..8]) != u32::from_le_bytes([h[8], h[9], h[10], h[11]]) {
+            return Err(invalid("xz stream header CRC mismatch"));
+        }
+        if h[6] != 0 || h[7] & 0xF0 != 0 {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedOperation, "unsupported xz stream flags"));
+        }
+        self.stream_flags = [h[6], h[7]];
+        self.check_id = h[7];
+        Ok(())
+    }
+
+    fn read_block_header<'x>(
+        &mut self,
+        size_byte: u8,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let size = (size_byte as usize + 1) * 4;
+        let mut h = [0_u8; 0x400];
+        h[0] = size_byte;
+        self.read_bytes(&mut h[1..size], xc)?;
+        let (h, crc) = h[0..size].split_at(size - 4);
+        if Crc32::of(h) != u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return Err(invalid("xz block header CRC mismatch"));
+        }
+        let flags = h[1];
+        if flags & 0x3C != 0 {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedOperation, "unsupported xz block flags"));
+        }
+        let bad_header = || invalid("bad xz block header");
+        let mut pos = 2;
+        self.declared_packed = if flags & 0x40 != 0 {
+            Some(varint(h, &mut pos).ok_or_else(bad_header)?)
+        } else { None };
+        self.declared_unpacked = if flags & 0x80 != 0 {
+            Some(varint(h, &mut pos).ok_or_else(bad_header)?)
+        } else { None };
+        let filter_id = varint(h, &mut pos).ok_or_else(bad_header)?;
+        let props_size = varint(h, &mut pos).ok_or_else(bad_header)?;
+        if flags & 3 != 0 || filter_id != FILTER_LZMA2 {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedOperation, "xz filters other than LZMA2 not supported"));
+        }
+        if props_size != 1 || pos >= h.len() {
+            return Err(bad_header());
+        }
+        let dict_bits = h[pos];
+        pos += 1;
+        if dict_bits > 40 || h[pos..].iter().any(|&b| b != 0) {
+            return Err(bad_header());
+        }
+        let dict_size = if dict_bits == 40 {
+            u32::MAX as u64
+        } else {
+            (2 | (dict_bits as u64 & 1)) << (dict_bits / 2 + 11)
+        };
+        self.dict.cap = core::cmp::min(dict_size, usize::MAX as u64) as usize;
+        self.dict.reset();
+        self.header_size = size as u64;
+        self.block_in_start = self.input.bit_position() / 8;
+        self.block_out_start = self.total_out;
+        self.need_dict_reset = true;
+        self.need_props = true;
+        self.crc32 = Crc32::new();
+        self.crc64 = Crc64::new();
+        Ok(())
+    }
+
+    fn finish_block<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let packed = self.input.bit_position() / 8 - self.block_in_start;
+        let unpacked = self.total_out - self.block_out_start;
+        if self.declared_packed.is_some_and(|v| v != packed)
+            || self.declared_unpacked.is_some_and(|v| v != unpacked) {
+            return Err(invalid("xz block size mismatch"));
+        }
+        for _ in 0..(4 - packed % 4) % 4 {
+            if self.input.read_byte(xc)? != 0 {
+                return Err(invalid("bad xz block padding"));
+            }
+        }
+        let mut check = [0_u8; 64];
+        let check_len = check_size(self.check_id);
+        self.read_bytes(&mut check[0..check_len], xc)?;
+        let ok = match self.check_id {
+            CHECK_CRC32 => check[0..4] == self.crc32.value().to_le_bytes(),
+            CHECK_CRC64 => check[0..8] == self.crc64.value().to_le_bytes(),
+            _ => true,
+        };
+        if !ok {
+            return Err(invalid("xz block check mismatch"));
+        }
+        self.block_count += 1;
+        self.unpadded_sum += self.header_size + packed + check_len as u64;
+        self.unpacked_sum += unpacked;
+        Ok(())
+    }
+
+    fn index_varint<'x>(
+        &mut self,
+        crc: &mut Crc32,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u64> {
+        let mut v = 0_u64;
+        for i in 0..9 {
+            let b = self.input.read_byte(xc)?;
+            crc.update(&[b]);
+            v |= ((b & 0x7F) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                if i != 0 && b == 0 {
+                    break;
+                }
+                return Ok(v);
+            }
+        }
+        Err(invalid("bad xz index"))
+    }
+
+    /* the index indicator was read already */
+    fn read_index_and_footer<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let index_start = self.input.bit_position() / 8 - 1;
+        let mut crc = Crc32::new();
+        crc.update(&[0]);
+        let count = self.index_varint(&mut crc, xc)?;
+        let mut unpadded_sum = 0_u64;
+        let mut unpacked_sum = 0_u64;
+        for _ in 0..count {
+            unpadded_sum = unpadded_sum.wrapping_add(self.index_varint(&mut crc, xc)?);
+            unpacked_sum = unpacked_sum.wrapping_add(self.index_varint(&mut crc, xc)?);
+        }
+        if count != self.block_count || unpadded_sum != self.unpadded_sum
+            || unpacked_sum != self.unpacked_sum {
+            return Err(invalid("xz index does not match blocks"));
+        }
+        while !(self.input.bit_position() / 8 - index_start).is_multiple_of(4) {
+            let b = self.input.read_byte(xc)?;
+            if b != 0 {
+                return Err(invalid("bad xz index padding"));
+            }
+            crc.update(&[b]);
+        }
+        let mut f = [0_u8; 16];
+        self.read_bytes(&mut f, xc)?;
+        if f[0..4] != crc.value().to_le_bytes() {
+            return Err(invalid("xz index CRC mismatch"));
+        }
+        let index_size = self.input.bit_position() / 8 - 12 - index_start;
+        let footer = &f[4..];
+        if Crc32::of(&footer[4..10]).to_le_bytes() != footer[0..4]
+            || footer[10..12] != *FOOTER_MAGIC {
+            return Err(invalid("bad xz stream footer"));
+        }
+        let backward_size = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+        if (backward_size as u64 + 1) * 4 != index_size || footer[8..10] != self.stream_flags {
+            return Err(invalid("xz stream footer does not match"));
+        }
+        Ok(())
+    }
+
+    fn set_props<'x>(
+        &mut self,
+        props: u8,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if props > (4 * 5 + 4) * 9 + 8 {
+            return Err(invalid("bad lzma properties"));
+        }
+        let props = props as u32;
+        self.lc = props % 9;
+        self.lp = (props / 9) % 5;
+        self.pb = props / 45;
+        if self.lc + self.lp > 4 {
+            return Err(invalid("bad lzma properties"));
+        }
+        let needed = LIT_PROBS << (self.lc + self.lp);
+        debug_assert!(needed <= LIT_PROBS * LIT_STATES_MAX);
+        if self.lit_probs.len() < needed {
+            self.lit_probs.reserve(needed - self.lit_probs.len()).map_err(|e| xc_err!(
+                xc, ErrorCode::NoSpace, "lzma probabilities out of memory",
+                "cannot allocate {} lzma probabilities: {}", needed, e))?;
+            while self.lit_probs.len() < needed {
+                self.lit_probs.push(PROB_INIT).unwrap();
+            }
+        }
+        self.need_props = false;
+        Ok(())
+    }
+
+    fn reset_lzma_state(&mut self) {
+        self.probs = Probs::new();
+        for p in self.lit_probs.as_mut_slice() {
+            *p = PROB_INIT;
+        }
+        self.lzma_state = 0;
+        self.reps = [0; 4];
+    }
+
+    fn read_chunk_header<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, State> {
+        xc.check_cancelled()?;
+        let control = self.input.read_byte(xc)?;
+        if control == 0 {
+            return Ok(State::BlockEnd);
+        }
+        if control == 1 || control >= 0xE0 {
+            self.dict.reset();
+            self.need_dict_reset = false;
+        } else if self.need_dict_reset {
+            return Err(invalid("lzma2 chunk without dictionary reset"));
+        }
+        if control < 0x80 {
+            if control > 2 {
+                return Err(invalid("bad lzma2 control byte"));
+            }
+            return Ok(State::Uncompressed(self.read_u16be(xc)? + 1));
+        }
+        self.unpacked_left = ((control as usize & 0x1F) << 16) + self.read_u16be(xc)? + 1;
+        self.chunk_packed = self.read_u16be(xc)? as u64 + 1;
+        if control >= 0xC0 {
+            let props = self.input.read_byte(xc)?;
+            self.set_props(props, xc)?;
+        } else if self.need_props {
+            return Err(invalid("lzma2 chunk without properties"));
+        }
+        if control >= 0xA0 {
+            self.reset_lzma_state();
+        }
+        self.chunk_in_start = self.input.bit_position() / 8;
+        self.rc.init(&mut self.input, xc)?;
+        Ok(State::Lzma)
+    }
+
+    fn finish_lzma_chunk(&mut self) -> IOResult<'static, ()> {
+        if self.copy_len != 0 || self.rc.code != 0
+            || self.input.bit_position() / 8 - self.chunk_in_start != self.chunk_packed {
+            return Err(invalid("bad lzma2 chunk end"));
+        }
+        Ok(())
+    }
+
+    fn decode_len<'x>(
+        &mut self,
+        rep: bool,
+        pos_state: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, usize> {
+        let p = if rep { &mut self.probs.rep_len } else { &mut self.probs.len };
+        let (probs, bits, base) = if self.rc.bit(&mut p.choice, &mut self.input, xc)? == 0 {
+            (&mut p.low[pos_state * 8..pos_state * 8 + 8], 3, 0)
+        } else if self.rc.bit(&mut p.choice2, &mut self.input, xc)? == 0 {
+            (&mut p.mid[pos_state * 8..pos_state * 8 + 8], 3, 8)
+        } else {
+            (&mut p.high[..], 8, 16)
+        };
+        let v = self.rc.bit_tree(probs, bits, &mut self.input, xc)?;
+        Ok(MATCH_LEN_MIN + base + v as usize)
+    }
+
+    fn decode_distance<'x>(
+        &mut self,
+        len: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u32> {
+        let len_state = core::cmp::min(len - MATCH_LEN_MIN, 3);
+        let slot = self.rc.bit_tree(
+            &mut self.probs.pos_slot[len_state * 64..len_state * 64 + 64], 6,
+            &mut self.input, xc)?;
+        if slot < 4 {
+            return Ok(slot);
+        }
+        let direct_bits = (slot >> 1) - 1;
+        let mut dist = (2 | (slot & 1)) << direct_bits;
+        if slot < END_POS_MODEL_INDEX {
+            dist += self.rc.bit_tree_reverse(
+                &mut self.probs.spec_pos[(dist - slot) as usize..], direct_bits,
+                &mut self.input, xc)?;
+        } else {
+            dist = dist.wrapping_add(self.rc.direct(direct_bits - 4, &mut self.input, xc)? << 4);
+            dist = dist.wrapping_add(self.rc.bit_tree_reverse(
+                &mut self.probs.align, 4, &mut self.input, xc)?);
+        }
+        Ok(dist)
+    }
+
+    fn decode_literal<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u8> {
+        let prev = if self.dict.total == 0 { 0 } else { self.dict.get(1) as usize };
+        let lp_mask = (1_u64 << self.lp) - 1;
+        let lit_state = (((self.dict.total & lp_mask) as usize) << self.lc)
+            + (prev >> (8 - self.lc));
+        let probs = &mut self.lit_probs.as_mut_slice()[lit_state * LIT_PROBS..(lit_state + 1) * LIT_PROBS];
+        let mut symbol = 1_usize;
+        if self.lzma_state < 7 {
+            while symbol < 0x100 {
+                symbol = (symbol << 1) | self.rc.bit(&mut probs[symbol], &mut self.input, xc)? as usize;
+            }
+        } else {
+            let rep0 = self.reps[0] as usize + 1;
+            if rep0 > self.dict.filled() {
+                return Err(invalid("lzma distance too far back"));
+            }
+            let mut match_byte = self.dict.get(rep0) as usize;
+            let mut offset = 0x100;
+            while symbol < 0x100 {
+                match_byte <<= 1;
+                let match_bit = match_byte & offset;
+                let b = self.rc.bit(&mut probs[offset + match_bit + symbol], &mut self.input, xc)? as usize;
+                symbol = (symbol << 1) | b;
+                offset &= if b != 0 { match_bit } else { !match_bit };
+            }
+        }
+        self.lzma_state = match self.lzma_state {
+            0..=3 => 0,
+            4..=9 => self.lzma_state - 3,
+            _ => self.lzma_state - 6,
+        };
+        Ok(symbol as u8)
+    }
+
+    /* decodes a literal, returned as is, or a match, setting copy_len */
+    fn decode_lzma_symbol<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, Option<u8>> {
+        let pos_state = (self.dict.total & ((1 << self.pb) - 1)) as usize;
+        let s = self.lzma_state;
+        let is_match = &mut self.probs.is_match[s * POS_STATES_MAX + pos_state];
+        if self.rc.bit(is_match, &mut self.input, xc)? == 0 {
+            return self.decode_literal(xc).map(Some);
+        }
+        let len = if self.rc.bit(&mut self.probs.is_rep[s], &mut self.input, xc)? == 0 {
+            let len = self.decode_len(false, pos_state, xc)?;
+            let dist = self.decode_distance(len, xc)?;
+            if dist == u32::MAX {
+                return Err(invalid("lzma end marker in lzma2 chunk"));
+            }
+            self.reps = [dist, self.reps[0], self.reps[1], self.reps[2]];
+            self.lzma_state = if s < 7 { 7 } else { 10 };
+            len
+        } else if self.rc.bit(&mut self.probs.is_rep_g0[s], &mut self.input, xc)? == 0 {
+            let long = &mut self.probs.is_rep0_long[s * POS_STATES_MAX + pos_state];
+            if self.rc.bit(long, &mut self.input, xc)? == 0 {
+                self.lzma_state = if s < 7 { 9 } else { 11 };
+                1
+            } else {
+                self.lzma_state = if s < 7 { 8 } else { 11 };
+                self.decode_len(true, pos_state, xc)?
+            }
+        } else {
+            let i = if self.rc.bit(&mut self.probs.is_rep_g1[s], &mut self.input, xc)? == 0 {
+                1
+            } else if self.rc.bit(&mut self.probs.is_rep_g2[s], &mut self.input, xc)? == 0 {
+                2
+            } else {
+                3
+            };
+            self.reps[0..=i].rotate_right(1);
+            self.lzma_state = if s < 7 { 8 } else { 11 };
+            self.decode_len(true, pos_state, xc)?
+        };
+        if self.reps[0] as usize >= self.dict.filled() {
+            return Err(invalid("lzma distance too far back"));
+        }
+        // the byte being produced is already counted out of unpacked_left
+        if len > self.unpacked_left + 1 {
+            return Err(invalid("lzma match past the end of lzma2 chunk"));
+        }
+        self.copy_len = len;
+        Ok(None)
+    }
+
+    fn next_lzma_byte<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, u8> {
+        if self.copy_len == 0 {
+            if let Some(b) = self.decode_lzma_symbol(xc)? {
+                return Ok(b);
+            }
+        }
+        self.copy_len -= 1;
+        Ok(self.dict.get(self.reps[0] as usize + 1))
+    }
+
+}
+
+impl<'a, T: Read> Read for XzReader<'a, T> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            let b = match self.state {
+                State::StreamHeader => {
+                    self.read_stream_header(xc)?;
+                    self.state = State::BlockHeader;
+                    continue;
+                },
+                State::BlockHeader => {
+                    let size_byte = self.input.read_byte(xc)?;
+                    if size_byte == 0 {
+                        self.read_index_and_footer(xc)?;
+                        self.state = State::Done;
+                    } else {
+                        self.read_block_header(size_byte, xc)?;
+                        self.state = State::ChunkHeader;
+                    }
+                    continue;
+                },
+                State::ChunkHeader => {
+                    self.state = self.read_chunk_header(xc)?;
+                    continue;
+                },
+                State::Uncompressed(0) => {
+                    self.state = State::ChunkHeader;
+                    continue;
+                },
+                State::Uncompressed(left) => {
+                    self.state = State::Uncompressed(left - 1);
+                    self.input.read_byte(xc)?
+                },
+                State::Lzma => {
+                    if self.unpacked_left == 0 {
+                        self.finish_lzma_chunk()?;
+                        self.state = State::ChunkHeader;
+                        continue;
+                    }
+                    self.unpacked_left -= 1;
+                    self.next_lzma_byte(xc)?
+                },
+                State::BlockEnd => {
+                    self.finish_block(xc)?;
+                    self.state = State::BlockHeader;
+                    continue;
+                },
+                State::Done => break,
+            };
+            self.dict.put(b, xc)?;
+            match self.check_id {
+                CHECK_CRC32 => self.crc32.update(&[b]),
+                CHECK_CRC64 => self.crc64.update(&[b]),
+                _ => {},
+            }
+            self.total_out += 1;
+            buf[n] = b;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Read> core::fmt::Debug for XzReader<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "XzReader({:?}, total_out: {})", self.state, self.total_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsOnePassROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    const WORDS_CRC64: &[u8] = b"\xFD\x37\x7A\x58\x5A\x00\x00\x04\xE6\xD6\xB4\x46\x02\x00\x21\x01\x00\x00\x00\x00\x37\x27\x97\xD6\xE0\x02\x9B\x00\xC3\x5D\x00\x36\x1A\x4A\xEE\xE6\x4F\xCC\xFA\x31\x1B\x10\xB7\xEB\x42\x7B\x6B\x83\xCA\xEF\x1B\x2B\x46\xDB\x30\xD6\x97\x25\xDC\x32\x79\x44\xDA\x93\x43\xD7\xEE\x70\xE3\x39\x64\xFC\xFD\x07\x69\x64\x63\xF7\x0C\x23\xB1\xF5\x20\x3A\x97\x15\x0E\x8F\x4E\x52\x07\x01\xD7\x7D\x38\x7E\xFD\xC9\x7B\x4E\xFE\x72\x58\xBA\x7F\x5B\x44\x95\x87\x69\xE8\x28\x36\x29\x9A\x9F\xED\x19\x7A\x0D\x50\x05\x84\x1A\x78\x4E\x3F\x74\x29\x62\x5D\x26\xF5\xD7\x26\x6A\x75\xFC\xFD\x2C\x72\x9C\xA6\x9A\x08\x64\x34\xCE\x23\xAA\xB9\x86\xE7\x15\x7E\x5C\x4F\x9C\xE9\x1E\xAB\x29\x44\xD3\xF2\x1C\x89\xDF\xB1\x24\x43\xAB\x24\xC0\x34\xDC\x66\x04\xFB\x53\xF1\x76\x78\x25\x8D\x35\x78\x55\x33\xA4\x73\x20\x4E\xC1\xC7\xF8\xF5\xB5\x9C\x54\xB4\x1D\xB9\x48\xCC\x2D\x6A\x06\xA0\xC4\x07\xFB\xEE\x13\x52\x9E\x9D\x17\xB9\x81\xE3\xE5\x5E\xEE\xC1\x3C\x00\x00\xFC\x35\x27\xD7\xB0\x10\x57\xB6\x00\x01\xDF\x01\x9C\x05\x00\x00\x60\xCF\x00\xEC\xB1\xC4\x67\xFB\x02\x00\x00\x00\x00\x04\x59\x5A";
+
+    /* 120 words picked by an LCG, as the vectors were made */
+    fn words(out: &mut [u8]) -> usize {
+        let words: [&[u8]; 10] = [
+            b"half", b"bit", b"byte", b"stream", b"xz", b"lzma", b"window",
+            b"match", b"literal", b"range" ];
+        let mut x = 1_u32;
+        let mut n = 0;
+        for _ in 0..120 {
+            x = x.wrapping_mul(1103515245).wrapping_add(12345) & 0x7FFF_FFFF;
+            let w = words[((x >> 16) % 10) as usize];
+            out[n..n + w.len()].copy_from_slice(w);
+            out[n + w.len()] = b' ';
+            n += w.len() + 1;
+        }
+        n
+    }
+
+    fn unxz<'x>(
+        data: &[u8],
+        out: &mut [u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, usize> {
+        let src = ChunkedReader::new(BufferAsOnePassROStream::new(data), 11, 0);
+        let mut r = XzReader::new(src, xc.get_main_allocator());
+        let n = r.read_uninterrupted(out, xc).map_err(|e| e.to_error())?;
+        assert!(r.is_done());
+        assert_eq!(r.total_out(), n as u64);
+        Ok(n)
+    }
+
+    #[test]
+    fn lzma2_with_crc64() {
+        let mut buffer = [0_u8; 0x8000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut expected = [0_u8; 0x400];
+        let len = words(&mut expected);
+        assert_eq!(len, 668);
+        let mut out = [0_u8; 0x400];
+        let n = unxz(WORDS_CRC64, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], expected[0..len]);
+
+        let mut bad = [0_u8; 260];
+        bad.copy_from_slice(WORDS_CRC64);
+        bad[230] ^= 1; // in the CRC64 check
+        let e = unxz(&bad, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_msg(), "xz block check mismatch");
+        let e = unxz(&WORDS_CRC64[0..100], &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnexpectedEnd);
+    }
+
+    #[test]
+    fn lzma2_literal_position_bits() {
+        let mut buffer = [0_u8; 0x8000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        // lc=1 lp=2 pb=1, CRC32 check
+        let xz = b"\xFD\x37\x7A\x58\x5A\x00\x00\x01\x69\x22\xDE\x36\x02\x00\x21\x01\x00\x00\x00\x00\x37\x27\x97\xD6\xE0\x02\x9B\x00\xC2\x40\x00\x36\x1A\x4A\xFA\xBA\xB7\xB9\xEE\xFF\x5D\x8C\x49\x62\x20\xD4\x3A\xFE\xEA\x76\xB6\x3E\xCF\x7C\x44\x0D\x10\xF8\x41\xE4\x0B\x6C\x32\xED\x4F\x40\xF5\xC7\x4A\x1F\x1D\x52\x6C\x75\x5C\xE6\x31\xAE\x63\x3E\xD4\x60\x93\x7F\xC0\x0E\x35\x9B\xA9\x29\x19\x0A\x6C\x86\x25\x4A\xEB\xF4\x27\x60\x62\xB6\x5D\x81\x36\x77\x83\xFC\x4D\xB3\xEF\x72\x4C\x6E\xAC\xD4\x4D\x1C\xD9\x19\xCE\x77\x3E\x23\x60\x6A\x98\xAB\x6A\xA5\x50\x62\x2C\x2A\x5B\xD9\xA9\xA3\x32\xD4\xCE\xCC\xC7\x13\xA8\x40\x5F\xC8\x07\xC4\xF3\xCF\x67\x0F\x57\x4F\x1E\x33\x25\xB6\xE5\x02\x51\xFF\x55\x1E\xBB\x39\x5A\xFF\x02\x8F\x09\x9E\x56\x5A\x6A\x39\x3E\x44\x56\x5A\xE3\x08\xF1\x05\xB7\xCE\xA4\x7A\x75\xA8\x00\xF9\xBF\x37\x26\x82\x05\x2C\x05\x09\x28\x91\xE6\x2A\x4E\x69\x5E\x80\x45\x3A\xEB\xB5\x76\x86\xD4\xDB\xD6\x5D\xDC\x83\x61\xD9\xB0\x00\x00\x00\x9F\x07\xA9\x60\x00\x01\xDA\x01\x9C\x05\x00\x00\xD3\x5E\xCD\xBC\x3E\x30\x0D\x8B\x02\x00\x00\x00\x00\x01\x59\x5A";
+        let mut expected = [0_u8; 0x400];
+        let len = words(&mut expected);
+        let mut out = [0_u8; 0x400];
+        let n = unxz(xz, &mut out, &mut xc).unwrap();
+        assert_eq!(out[0..n], expected[0..len]);
+    }
+
+    #[test]
+    fn uncompressed_chunk_without_check() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let xz = b"\xFD\x37\x7A\x58\x5A\x00\x00\x00\xFF\x12\xD9\x41\x02\x00\x21\x01\x00\x00\x00\x00\x37\x27\x97\xD6\x01\x00\x3F\x00\xBB\x77\x33\xEF\xAB\x66\x22\xDE\x9A\x56\x11\xCD\x89\x45\x01\xBC\x78\x34\xF0\xAC\x67\x23\xDF\x9B\x57\x12\xCE\x8A\x46\x02\xBD\x79\x35\xF1\xAD\x68\x24\xE0\x9C\x58\x13\xCF\x8B\x47\x03\xBE\x7A\x36\xF2\xAE\x69\x25\xE1\x9D\x59\x14\xD0\x8C\x48\x04\xBF\x7B\x37\x00\x00\x01\x50\x40\xEF\xA9\xE1\xEC\x06\x72\x9E\x7A\x01\x00\x00\x00\x00\x00\x59\x5A";
+        let mut out = [0_u8; 0x80];
+        let n = unxz(xz, &mut out, &mut xc).unwrap();
+        assert_eq!(n, 64);
+        for (i, &b) in out[0..n].iter().enumerate() {
+            assert_eq!(b, ((i as u64 * 2654435761) >> 13) as u8);
+        }
+        let mut bad = *xz;
+        bad[7] = 0x0A; // SHA-256 in the stream flags, breaking their CRC
+        let e = unxz(&bad, &mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_msg(), "xz stream header CRC mismatch");
+    }
+}
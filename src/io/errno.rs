@@ -0,0 +1,76 @@
+use super::ErrorCode;
+
+impl ErrorCode {
+
+    pub fn from_errno(errno: libc::c_int) -> ErrorCode {
+        match errno {
+            libc::EINTR => ErrorCode::Interrupted,
+            libc::EAGAIN => ErrorCode::WouldBlock,
+            libc::EBADF => ErrorCode::BadOsHandle,
+            libc::ENOSPC | libc::EDQUOT | libc::EFBIG => ErrorCode::NoSpace,
+            libc::ESPIPE | libc::EOVERFLOW => ErrorCode::UnsupportedPosition,
+            libc::ENOSYS | libc::ENOTSUP | libc::ENOTTY => ErrorCode::UnsupportedOperation,
+            libc::EBUSY | libc::ENOMEM | libc::ENOBUFS | libc::EMFILE | libc::ENFILE =>
+                ErrorCode::ResourceUnavailable,
+            libc::EILSEQ => ErrorCode::InvalidData,
+            libc::ECANCELED => ErrorCode::Cancelled,
+            // these alias the ones above on some systems
+            e if e == libc::EWOULDBLOCK => ErrorCode::WouldBlock,
+            e if e == libc::EOPNOTSUPP => ErrorCode::UnsupportedOperation,
+            _ => ErrorCode::Unsuccessful,
+        }
+    }
+
+    pub fn to_errno(&self) -> libc::c_int {
+        match self {
+            ErrorCode::Unsuccessful => libc::EIO,
+            ErrorCode::UnsupportedOperation => libc::ENOTSUP,
+            ErrorCode::Interrupted => libc::EINTR,
+            ErrorCode::WouldBlock => libc::EAGAIN,
+            ErrorCode::BadOsHandle => libc::EBADF,
+            ErrorCode::UnexpectedEnd => libc::EIO,
+            ErrorCode::UnsupportedPosition => libc::EINVAL,
+            ErrorCode::NoSpace => libc::ENOSPC,
+            ErrorCode::ResourceUnavailable => libc::EBUSY,
+            ErrorCode::InvalidData => libc::EILSEQ,
+            ErrorCode::Cancelled => libc::ECANCELED,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errno_round_trip() {
+        for &ec in &[
+            ErrorCode::UnsupportedOperation,
+            ErrorCode::Interrupted,
+            ErrorCode::WouldBlock,
+            ErrorCode::BadOsHandle,
+            ErrorCode::NoSpace,
+            ErrorCode::ResourceUnavailable,
+            ErrorCode::InvalidData,
+        ] {
+            assert_eq!(ErrorCode::from_errno(ec.to_errno()), ec);
+        }
+    }
+
+    #[test]
+    fn common_errno_values() {
+        assert_eq!(ErrorCode::from_errno(libc::EINTR), ErrorCode::Interrupted);
+        assert_eq!(ErrorCode::from_errno(libc::EWOULDBLOCK), ErrorCode::WouldBlock);
+        assert_eq!(ErrorCode::from_errno(libc::ESPIPE), ErrorCode::UnsupportedPosition);
+        assert_eq!(ErrorCode::from_errno(libc::EDQUOT), ErrorCode::NoSpace);
+        assert_eq!(ErrorCode::from_errno(libc::ENOMEM), ErrorCode::ResourceUnavailable);
+    }
+
+    #[test]
+    fn unknown_errno_is_unsuccessful() {
+        assert_eq!(ErrorCode::from_errno(0), ErrorCode::Unsuccessful);
+        assert_eq!(ErrorCode::from_errno(libc::EACCES), ErrorCode::Unsuccessful);
+        assert_eq!(ErrorCode::Unsuccessful.to_errno(), libc::EIO);
+    }
+}
@@ -2,6 +2,7 @@ use core::convert::From;
 
 use crate::mm::String;
 use crate::error::Error;
+use crate::num::pos::PosError;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[non_exhaustive]
@@ -15,6 +16,8 @@ pub enum ErrorCode {
     UnsupportedPosition, // seek to a negative offset or to some large position past end that is not supported by the stream handler
     NoSpace,
     ResourceUnavailable,
+    InvalidData, // stream content does not match the expected encoding/format
+    Cancelled, // the cancellation token of the execution context was set
 }
 
 impl ErrorCode {
@@ -29,10 +32,36 @@ impl ErrorCode {
             ErrorCode::UnsupportedPosition => "unsupported position",
             ErrorCode::NoSpace => "no space",
             ErrorCode::ResourceUnavailable => "resource unavailable",
+            ErrorCode::InvalidData => "invalid data",
+            ErrorCode::Cancelled => "cancelled",
         }
     }
+
+    /* stable numeric value, for protocols and files */
+    pub fn to_u8(self) -> u8 {
+        ERROR_CODES_BY_VALUE.iter().position(|&c| c == self).unwrap_or(0) as u8
+    }
+
+    /* unknown values map to Unsuccessful */
+    pub fn from_u8(v: u8) -> ErrorCode {
+        ERROR_CODES_BY_VALUE.get(v as usize).copied().unwrap_or(ErrorCode::Unsuccessful)
+    }
 }
 
+const ERROR_CODES_BY_VALUE: [ErrorCode; 11] = [
+    ErrorCode::Unsuccessful,
+    ErrorCode::UnsupportedOperation,
+    ErrorCode::Interrupted,
+    ErrorCode::WouldBlock,
+    ErrorCode::BadOsHandle,
+    ErrorCode::UnexpectedEnd,
+    ErrorCode::UnsupportedPosition,
+    ErrorCode::NoSpace,
+    ErrorCode::ResourceUnavailable,
+    ErrorCode::InvalidData,
+    ErrorCode::Cancelled,
+];
+
 impl core::fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         core::fmt::Display::fmt(self.as_str(), f)
@@ -90,7 +119,29 @@ impl<'a> From<IOError<'a>> for IOPartialError<'a> {
     }
 }
 
+impl<'a> From<PosError> for IOError<'a> {
+    fn from(src: PosError) -> Self {
+        IOError::with_str(ErrorCode::UnsupportedPosition, src.to_str())
+    }
+}
+
+#[cfg(feature = "use-libc")]
+pub mod errno;
+
 pub mod stream;
+pub mod remote;
+
+#[cfg(feature = "use-std")]
+pub mod std_stream;
+
+#[cfg(feature = "use-net")]
+pub mod net;
+
+pub mod compress;
+
+pub mod bit_reader;
+pub use bit_reader::BitReader;
+pub use bit_reader::BitOrder;
 pub use stream::Null as NullStream;
 
 #[cfg(test)]
@@ -140,6 +191,14 @@ mod tests {
         error_code_fmt(ErrorCode::NoSpace, "no space");
     }
 
+    #[test]
+    fn error_code_u8_round_trip() {
+        for &c in ERROR_CODES_BY_VALUE.iter() {
+            assert_eq!(ErrorCode::from_u8(c.to_u8()), c);
+        }
+        assert_eq!(ErrorCode::from_u8(200), ErrorCode::Unsuccessful);
+    }
+
     #[test]
     fn partial_error_from_parts() {
         let s = String::map_str("big boo-boo");
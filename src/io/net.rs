@@ -0,0 +1,205 @@
+extern crate std;
+use core::time::Duration;
+use std::io::Read as StdRead;
+use std::io::Write as StdWrite;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::io::stream::Write;
+use crate::io::stream::std_file::convert_error;
+use crate::ExecutionContext;
+
+/* when a read or write timeout is set and passes, the operation fails with
+ * ErrorCode::WouldBlock on all platforms, so the retry helpers of Read and
+ * Write apply */
+
+/* TcpStreamWrapper *********************************************************/
+#[derive(Debug)]
+pub struct TcpStreamWrapper(TcpStream);
+
+impl TcpStreamWrapper {
+
+    pub fn new(stream: TcpStream) -> Self {
+        TcpStreamWrapper(stream)
+    }
+
+    pub fn connect<'a, A: ToSocketAddrs>(
+        addr: A,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, Self> {
+        TcpStream::connect(addr)
+            .map(TcpStreamWrapper)
+            .map_err(|e| convert_error(e, "tcp connect failed", xc))
+    }
+
+    pub fn set_read_timeout<'a>(
+        &self,
+        timeout: Option<Duration>,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, ()> {
+        self.0.set_read_timeout(timeout)
+            .map_err(|e| convert_error(e, "setting read timeout failed", xc))
+    }
+
+    pub fn set_write_timeout<'a>(
+        &self,
+        timeout: Option<Duration>,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, ()> {
+        self.0.set_write_timeout(timeout)
+            .map_err(|e| convert_error(e, "setting write timeout failed", xc))
+    }
+
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> TcpStream {
+        self.0
+    }
+
+}
+
+impl Read for TcpStreamWrapper {
+    fn read<'a>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdRead::read(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "tcp read failed", xc))
+    }
+}
+
+impl Write for TcpStreamWrapper {
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdWrite::write(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "tcp write failed", xc))
+    }
+}
+
+/* UnixStreamWrapper ********************************************************/
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixStreamWrapper(UnixStream);
+
+#[cfg(unix)]
+impl UnixStreamWrapper {
+
+    pub fn new(stream: UnixStream) -> Self {
+        UnixStreamWrapper(stream)
+    }
+
+    pub fn connect<'a, P: AsRef<Path>>(
+        path: P,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, Self> {
+        UnixStream::connect(path)
+            .map(UnixStreamWrapper)
+            .map_err(|e| convert_error(e, "unix socket connect failed", xc))
+    }
+
+    pub fn set_read_timeout<'a>(
+        &self,
+        timeout: Option<Duration>,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, ()> {
+        self.0.set_read_timeout(timeout)
+            .map_err(|e| convert_error(e, "setting read timeout failed", xc))
+    }
+
+    pub fn set_write_timeout<'a>(
+        &self,
+        timeout: Option<Duration>,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, ()> {
+        self.0.set_write_timeout(timeout)
+            .map_err(|e| convert_error(e, "setting write timeout failed", xc))
+    }
+
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> UnixStream {
+        self.0
+    }
+
+}
+
+#[cfg(unix)]
+impl Read for UnixStreamWrapper {
+    fn read<'a>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdRead::read(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "unix socket read failed", xc))
+    }
+}
+
+#[cfg(unix)]
+impl Write for UnixStreamWrapper {
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdWrite::write(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "unix socket write failed", xc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use crate::io::ErrorCode;
+
+    #[test]
+    fn tcp_loopback_and_timeout() {
+        let mut xc = ExecutionContext::nop();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = TcpStreamWrapper::connect(addr, &mut xc).unwrap();
+        let mut server = TcpStreamWrapper::new(listener.accept().unwrap().0);
+        client.write_all(b"ping", &mut xc).unwrap();
+        let mut buf = [0_u8; 4];
+        server.read_exact(&mut buf, &mut xc).unwrap();
+        assert_eq!(buf, *b"ping");
+
+        server.set_read_timeout(Some(Duration::from_millis(10)), &mut xc).unwrap();
+        let e = server.read(&mut buf, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::WouldBlock);
+        drop(client);
+        assert_eq!(server.read(&mut buf, &mut xc).unwrap(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_pair() {
+        let mut xc = ExecutionContext::nop();
+        let (a, b) = UnixStream::pair().unwrap();
+        let mut a = UnixStreamWrapper::new(a);
+        let mut b = UnixStreamWrapper::new(b);
+        b.write_all(b"pong", &mut xc).unwrap();
+        let mut buf = [0_u8; 4];
+        a.read_exact(&mut buf, &mut xc).unwrap();
+        assert_eq!(buf, *b"pong");
+        a.set_read_timeout(Some(Duration::from_millis(10)), &mut xc).unwrap();
+        let e = a.read(&mut buf, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::WouldBlock);
+        assert!(UnixStreamWrapper::connect("/nonexistent/halfbit.sock", &mut xc).is_err());
+    }
+}
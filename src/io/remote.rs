@@ -0,0 +1,416 @@
+use core::cmp::min;
+
+use crate::ExecutionContext;
+use crate::xc_err;
+use super::ErrorCode;
+use super::IOError;
+use super::IOResult;
+use super::stream::Read;
+use super::stream::Write;
+use super::stream::Seek;
+use super::stream::SeekFrom;
+use super::stream::Truncate;
+
+/* remote stream protocol ***************************************************/
+/* the client sends one request and waits for its response before sending
+ * the next one, so any byte stream going both ways (serial link, pipe pair,
+ * socket) can carry it; all integers are big-endian
+ *
+ * requests:
+ *   OP_READ u32:max_len          -> STATUS_OK u32:len data[len]
+ *   OP_SEEK u8:whence u64:disp   -> STATUS_OK u64:position
+ *   OP_CLOSE                     -> no response, server stops
+ * any request can also get:
+ *   STATUS_ERROR u8:error_code u16:msg_len msg[msg_len]
+ */
+pub const OP_READ: u8 = 1;
+pub const OP_SEEK: u8 = 2;
+pub const OP_CLOSE: u8 = 3;
+
+pub const STATUS_OK: u8 = 0;
+pub const STATUS_ERROR: u8 = 1;
+
+pub const WHENCE_START: u8 = 0;
+pub const WHENCE_CURRENT: u8 = 1;
+pub const WHENCE_END: u8 = 2;
+
+/* largest data payload in a read response */
+pub const MAX_READ_LEN: usize = 0x1000;
+
+/* longest error message sent by the server; longer ones are cut */
+const MAX_MSG_LEN: usize = 0xFF;
+
+fn transport_error<'x>(
+    e: IOError<'x>,
+    xc: &mut ExecutionContext<'x>,
+) -> IOError<'x> {
+    xc_err!(xc, e.get_error_code(), "remote transport failed",
+            "remote transport failed: {}", e)
+}
+
+/* RemoteClient *************************************************************/
+/* stream served by a remote peer through the given transport; reads are
+ * split in requests of at most MAX_READ_LEN bytes */
+#[derive(Debug)]
+pub struct RemoteClient<T: Read + Write> {
+    transport: T,
+}
+
+impl<T: Read + Write> RemoteClient<T> {
+
+    pub fn new(transport: T) -> Self {
+        RemoteClient { transport }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /* tells the server to stop serving */
+    pub fn close<'x>(
+        mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, T> {
+        self.transport.write_all(&[OP_CLOSE], xc)?;
+        Ok(self.transport)
+    }
+
+    fn request<'x>(
+        &mut self,
+        req: &[u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        self.transport.write_all(req, xc)
+            .map_err(|e| transport_error(e.to_error(), xc))?;
+        let status = self.transport.read_u8(xc)
+            .map_err(|e| transport_error(e.to_error(), xc))?;
+        match status {
+            STATUS_OK => Ok(()),
+            STATUS_ERROR => Err(self.receive_error(xc)),
+            _ => Err(xc_err!(xc, ErrorCode::InvalidData,
+                             "bad remote response status",
+                             "bad remote response status: {}", status)),
+        }
+    }
+
+    fn receive_error<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOError<'x> {
+        let mut hdr = [0_u8; 3];
+        if let Err(e) = self.transport.read_exact(&mut hdr, xc) {
+            return transport_error(e.to_error(), xc);
+        }
+        let code = ErrorCode::from_u8(hdr[0]);
+        let msg_len = u16::from_be_bytes([hdr[1], hdr[2]]) as usize;
+        let mut msg = [0_u8; MAX_MSG_LEN];
+        let kept_len = min(msg_len, msg.len());
+        if let Err(e) = self.transport.read_exact(&mut msg[0..kept_len], xc) {
+            return transport_error(e.to_error(), xc);
+        }
+        let mut left = msg_len - kept_len;
+        let mut skip = [0_u8; 0x40];
+        while left != 0 {
+            let n = min(left, skip.len());
+            if let Err(e) = self.transport.read_exact(&mut skip[0..n], xc) {
+                return transport_error(e.to_error(), xc);
+            }
+            left -= n;
+        }
+        let msg = core::str::from_utf8(&msg[0..kept_len])
+            .unwrap_or("<non-UTF-8 message>");
+        xc_err!(xc, code, "remote error", "remote: {}", msg)
+    }
+
+}
+
+impl<T: Read + Write> Read for RemoteClient<T> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let max_len = min(buf.len(), MAX_READ_LEN);
+        let mut req = [0_u8; 5];
+        req[0] = OP_READ;
+        req[1..5].copy_from_slice(&(max_len as u32).to_be_bytes());
+        self.request(&req, xc)?;
+        let len = self.transport.read_u32be(xc)
+            .map_err(|e| transport_error(e.to_error(), xc))? as usize;
+        if len > max_len {
+            return Err(xc_err!(xc, ErrorCode::InvalidData,
+                               "remote sent more data than requested",
+                               "remote sent {} bytes instead of at most {}",
+                               len, max_len));
+        }
+        self.transport.read_exact(&mut buf[0..len], xc)
+            .map_err(|e| transport_error(e.to_error(), xc))?;
+        Ok(len)
+    }
+}
+
+impl<T: Read + Write> Seek for RemoteClient<T> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        let (whence, disp) = match target {
+            SeekFrom::Start(disp) => (WHENCE_START, disp),
+            SeekFrom::Current(disp) => (WHENCE_CURRENT, disp as u64),
+            SeekFrom::End(disp) => (WHENCE_END, disp as u64),
+        };
+        let mut req = [0_u8; 10];
+        req[0] = OP_SEEK;
+        req[1] = whence;
+        req[2..10].copy_from_slice(&disp.to_be_bytes());
+        self.request(&req, xc)?;
+        self.transport.read_u64be(xc)
+            .map_err(|e| transport_error(e.to_error(), xc))
+    }
+}
+
+impl<T: Read + Write> Write for RemoteClient<T> {}
+impl<T: Read + Write> Truncate for RemoteClient<T> {}
+
+/* server *******************************************************************/
+fn send_error<'x, T: ?Sized + Write>(
+    e: IOError<'_>,
+    transport: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> IOResult<'x, ()> {
+    let msg = e.get_msg();
+    let mut msg_len = min(msg.len(), MAX_MSG_LEN);
+    while !msg.is_char_boundary(msg_len) {
+        msg_len -= 1;
+    }
+    let mut hdr = [STATUS_ERROR, e.get_error_code().to_u8(), 0, 0];
+    hdr[2..4].copy_from_slice(&(msg_len as u16).to_be_bytes());
+    transport.write_all(&hdr, xc)?;
+    transport.write_all(&msg.as_bytes()[0..msg_len], xc)?;
+    Ok(())
+}
+
+/* handles one request from the transport; returns false when the client
+ * closed the session or the transport ended before a new request; errors
+ * from the served stream are sent to the client, only transport errors are
+ * returned */
+pub fn serve_request<'x, S, T>(
+    stream: &mut S,
+    transport: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> IOResult<'x, bool>
+where S: ?Sized + Read + Seek, T: ?Sized + Read + Write {
+    let mut op = [0_u8; 1];
+    if transport.read_uninterrupted(&mut op, xc)? == 0 {
+        return Ok(false);
+    }
+    match op[0] {
+        OP_READ => {
+            let max_len = min(transport.read_u32be(xc)? as usize, MAX_READ_LEN);
+            let mut buf = [0_u8; MAX_READ_LEN];
+            match stream.read(&mut buf[0..max_len], xc) {
+                Ok(n) => {
+                    let mut hdr = [STATUS_OK, 0, 0, 0, 0];
+                    hdr[1..5].copy_from_slice(&(n as u32).to_be_bytes());
+                    transport.write_all(&hdr, xc)?;
+                    transport.write_all(&buf[0..n], xc)?;
+                },
+                Err(e) => send_error(e, transport, xc)?,
+            }
+        },
+        OP_SEEK => {
+            let whence = transport.read_u8(xc)?;
+            let disp = transport.read_u64be(xc)?;
+            let target = match whence {
+                WHENCE_START => Some(SeekFrom::Start(disp)),
+                WHENCE_CURRENT => Some(SeekFrom::Current(disp as i64)),
+                WHENCE_END => Some(SeekFrom::End(disp as i64)),
+                _ => None,
+            };
+            let r = match target {
+                Some(target) => stream.seek(target, xc),
+                None => Err(IOError::with_str(ErrorCode::InvalidData,
+                                              "bad seek origin")),
+            };
+            match r {
+                Ok(pos) => {
+                    let mut resp = [0_u8; 9];
+                    resp[0] = STATUS_OK;
+                    resp[1..9].copy_from_slice(&pos.to_be_bytes());
+                    transport.write_all(&resp, xc)?;
+                },
+                Err(e) => send_error(e, transport, xc)?,
+            }
+        },
+        OP_CLOSE => return Ok(false),
+        _ => {
+            // the rest of the request cannot be parsed; the session is over
+            let e = IOError::with_str(ErrorCode::InvalidData, "bad request");
+            send_error(e, transport, xc)?;
+            return Err(xc_err!(xc, ErrorCode::InvalidData,
+                               "bad remote request",
+                               "bad remote request: {}", op[0]));
+        },
+    }
+    Ok(true)
+}
+
+/* serves requests until the client closes the session */
+pub fn serve<'x, S, T>(
+    stream: &mut S,
+    transport: &mut T,
+    xc: &mut ExecutionContext<'x>,
+) -> IOResult<'x, ()>
+where S: ?Sized + Read + Seek, T: ?Sized + Read + Write {
+    while serve_request(stream, transport, xc)? {}
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::ByteVectorStream;
+    use crate::io::stream::RandomAccessRead;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::Vector;
+
+    /* reads requests from one buffer, writes responses to another */
+    struct Duplex<'a, 'i, 'o> {
+        input: &'a mut BufferAsROStream<'i>,
+        output: &'a mut ByteVectorStream<'o>,
+    }
+    impl Read for Duplex<'_, '_, '_> {
+        fn read<'x>(
+            &mut self,
+            buf: &mut [u8],
+            xc: &mut ExecutionContext<'x>
+        ) -> IOResult<'x, usize> {
+            self.input.read(buf, xc)
+        }
+    }
+    impl Write for Duplex<'_, '_, '_> {
+        fn write<'x>(
+            &mut self,
+            buf: &[u8],
+            xc: &mut ExecutionContext<'x>
+        ) -> IOResult<'x, usize> {
+            self.output.write(buf, xc)
+        }
+    }
+
+    /* runs the server on each request as soon as it is complete */
+    #[derive(Debug)]
+    struct Loopback<'a> {
+        served: BufferAsROStream<'a>,
+        requests: Vector<'a, u8>,
+        responses: Vector<'a, u8>,
+        response_pos: usize,
+    }
+    impl Read for Loopback<'_> {
+        fn read<'x>(
+            &mut self,
+            buf: &mut [u8],
+            xc: &mut ExecutionContext<'x>
+        ) -> IOResult<'x, usize> {
+            if self.response_pos == self.responses.len() {
+                let a = self.requests.allocator();
+                let mut input = BufferAsROStream::new(self.requests.as_slice());
+                let mut output = ByteVectorStream::new(Vector::new(a));
+                let mut d = Duplex { input: &mut input, output: &mut output };
+                serve_request(&mut self.served, &mut d, xc).unwrap();
+                self.responses = output.into_inner();
+                self.response_pos = 0;
+                self.requests.truncate(0);
+            }
+            let rest = &self.responses.as_slice()[self.response_pos..];
+            let n = min(rest.len(), buf.len());
+            buf[0..n].copy_from_slice(&rest[0..n]);
+            self.response_pos += n;
+            Ok(n)
+        }
+    }
+    impl Write for Loopback<'_> {
+        fn write<'x>(
+            &mut self,
+            buf: &[u8],
+            _xc: &mut ExecutionContext<'x>
+        ) -> IOResult<'x, usize> {
+            self.requests.append_from_slice(buf).unwrap();
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn server_answers_requests() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut served = BufferAsROStream::new(b"0123456789");
+        let mut input = BufferAsROStream::new(
+            b"\x02\x00\x00\x00\x00\x00\x00\x00\x00\x07\x01\x00\x00\x00\x08\x03");
+        let mut output = ByteVectorStream::new(Vector::new(a.to_ref()));
+        let mut d = Duplex { input: &mut input, output: &mut output };
+        serve(&mut served, &mut d, &mut xc).unwrap();
+        assert_eq!(output.as_ref().as_slice(),
+                   b"\x00\x00\x00\x00\x00\x00\x00\x00\x07\x00\x00\x00\x00\x03789");
+    }
+
+    #[test]
+    fn server_reports_stream_errors() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut served = BufferAsROStream::new(b"0123456789");
+        let mut input = BufferAsROStream::new(
+            b"\x02\x01\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF");
+        let mut output = ByteVectorStream::new(Vector::new(a.to_ref()));
+        let mut d = Duplex { input: &mut input, output: &mut output };
+        assert!(serve_request(&mut served, &mut d, &mut xc).unwrap());
+        assert!(!serve_request(&mut served, &mut d, &mut xc).unwrap());
+        let resp = output.as_ref().as_slice();
+        assert_eq!(resp[0..2], [STATUS_ERROR, ErrorCode::UnsupportedPosition.to_u8()]);
+        assert_eq!(&resp[4..], b"seek to negative position");
+    }
+
+    #[test]
+    fn server_rejects_bad_request() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut served = BufferAsROStream::new(b"");
+        let mut input = BufferAsROStream::new(b"\x09");
+        let mut output = ByteVectorStream::new(Vector::new(a.to_ref()));
+        let mut d = Duplex { input: &mut input, output: &mut output };
+        let e = serve_request(&mut served, &mut d, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+        assert_eq!(output.as_ref().as_slice()[0], STATUS_ERROR);
+    }
+
+    #[test]
+    fn client_reads_and_seeks_remote_stream() {
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut c = RemoteClient::new(Loopback {
+            served: BufferAsROStream::new(b"remote content"),
+            requests: Vector::new(a.to_ref()),
+            responses: Vector::new(a.to_ref()),
+            response_pos: 0,
+        });
+        let s: &mut dyn RandomAccessRead = &mut c;
+        let mut buf = [0_u8; 7];
+        assert_eq!(s.seek_read(7, &mut buf, &mut xc).unwrap(), 7);
+        assert_eq!(buf, *b"content");
+        assert_eq!(s.seek(SeekFrom::End(-14), &mut xc).unwrap(), 0);
+        assert_eq!(s.read(&mut buf[0..6], &mut xc).unwrap(), 6);
+        assert_eq!(buf[0..6], *b"remote");
+        let e = s.seek(SeekFrom::Current(-7), &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnsupportedPosition);
+        assert_eq!(e.get_msg(), "remote: seek to negative position");
+        let lb = c.close(&mut xc).unwrap();
+        assert_eq!(lb.requests.as_slice(), [OP_CLOSE]);
+    }
+}
@@ -0,0 +1,121 @@
+extern crate std;
+use std::io::Read as StdRead;
+use std::io::Write as StdWrite;
+use std::io::StdinLock;
+use std::io::StdoutLock;
+use std::io::StderrLock;
+
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::io::stream::Write;
+use crate::io::stream::std_file::convert_error;
+use crate::ExecutionContext;
+
+/* StdIn ********************************************************************/
+/* the process standard input, kept locked for the lifetime of the object */
+#[derive(Debug)]
+pub struct StdIn(StdinLock<'static>);
+
+impl StdIn {
+    pub fn new() -> Self {
+        StdIn(std::io::stdin().lock())
+    }
+}
+
+impl Default for StdIn {
+    fn default() -> Self {
+        StdIn::new()
+    }
+}
+
+impl Read for StdIn {
+    fn read<'a>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdRead::read(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "stdin read failed", xc))
+    }
+}
+
+/* StdOut *******************************************************************/
+/* the process standard output, kept locked for the lifetime of the object;
+ * writes are buffered by std, use flush() to push them out */
+#[derive(Debug)]
+pub struct StdOut(StdoutLock<'static>);
+
+impl StdOut {
+    pub fn new() -> Self {
+        StdOut(std::io::stdout().lock())
+    }
+
+    pub fn flush<'a>(
+        &mut self,
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, ()> {
+        StdWrite::flush(&mut self.0)
+            .map_err(|e| convert_error(e, "stdout flush failed", xc))
+    }
+}
+
+impl Default for StdOut {
+    fn default() -> Self {
+        StdOut::new()
+    }
+}
+
+impl Write for StdOut {
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdWrite::write(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "stdout write failed", xc))
+    }
+}
+
+/* StdErr *******************************************************************/
+/* the process standard error, kept locked for the lifetime of the object */
+#[derive(Debug)]
+pub struct StdErr(StderrLock<'static>);
+
+impl StdErr {
+    pub fn new() -> Self {
+        StdErr(std::io::stderr().lock())
+    }
+}
+
+impl Default for StdErr {
+    fn default() -> Self {
+        StdErr::new()
+    }
+}
+
+impl Write for StdErr {
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        StdWrite::write(&mut self.0, buf)
+            .map_err(|e| convert_error(e, "stderr write failed", xc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_writes_and_flush() {
+        let mut xc = ExecutionContext::nop();
+        let mut out = StdOut::new();
+        assert_eq!(out.write(b"", &mut xc).unwrap(), 0);
+        out.flush(&mut xc).unwrap();
+        drop(out);
+        let mut err = StdErr::default();
+        assert_eq!(err.write(b"", &mut xc).unwrap(), 0);
+    }
+}
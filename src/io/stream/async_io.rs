@@ -0,0 +1,216 @@
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::ExecutionContext;
+use super::Read;
+use super::Write;
+
+/* AsyncRead ****************************************************************/
+/* a read that cannot complete right away returns Poll::Pending after
+ * arranging for the waker in the task context to be woken when it is worth
+ * trying again */
+pub trait AsyncRead {
+    fn poll_read<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> Poll<IOResult<'a, usize>>;
+}
+
+/* AsyncWrite ***************************************************************/
+pub trait AsyncWrite {
+    fn poll_write<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> Poll<IOResult<'a, usize>>;
+}
+
+/* hands the waker to the hook of the execution context; without a hook the
+ * task is woken right away so that the executor polls it again */
+fn wait_for_readiness(waker: &Waker, exe_ctx: &mut ExecutionContext<'_>) {
+    if !exe_ctx.register_waker(waker) {
+        waker.wake_by_ref();
+    }
+}
+
+/* SyncAsAsync **************************************************************/
+/* a sync stream seen as an async one: WouldBlock turns into Poll::Pending,
+ * Interrupted is retried and everything else is ready */
+#[derive(Debug)]
+pub struct SyncAsAsync<T>(pub T);
+
+impl<T> SyncAsAsync<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Read> AsyncRead for SyncAsAsync<T> {
+    fn poll_read<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> Poll<IOResult<'a, usize>> {
+        loop {
+            match self.0.read(buf, exe_ctx) {
+                Err(e) => match e.get_error_code() {
+                    ErrorCode::Interrupted => {},
+                    ErrorCode::WouldBlock => {
+                        wait_for_readiness(cx.waker(), exe_ctx);
+                        return Poll::Pending;
+                    },
+                    _ => return Poll::Ready(Err(e)),
+                },
+                r => return Poll::Ready(r),
+            }
+        }
+    }
+}
+
+impl<T: Write> AsyncWrite for SyncAsAsync<T> {
+    fn poll_write<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> Poll<IOResult<'a, usize>> {
+        loop {
+            match self.0.write(buf, exe_ctx) {
+                Err(e) => match e.get_error_code() {
+                    ErrorCode::Interrupted => {},
+                    ErrorCode::WouldBlock => {
+                        wait_for_readiness(cx.waker(), exe_ctx);
+                        return Poll::Pending;
+                    },
+                    _ => return Poll::Ready(Err(e)),
+                },
+                r => return Poll::Ready(r),
+            }
+        }
+    }
+}
+
+/* AsyncAsSync **************************************************************/
+/* an async stream seen as a sync one: each operation is polled once with a
+ * waker that does nothing and Poll::Pending turns into WouldBlock, so the
+ * retry helpers of Read and Write can drive it */
+#[derive(Debug)]
+pub struct AsyncAsSync<T>(pub T);
+
+impl<T> AsyncAsSync<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+fn pending_error() -> IOError<'static> {
+    IOError::with_str(ErrorCode::WouldBlock, "async operation pending")
+}
+
+impl<T: AsyncRead> Read for AsyncAsSync<T> {
+    fn read<'a>(
+        &mut self,
+        buf: &mut [u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        let mut cx = Context::from_waker(Waker::noop());
+        match self.0.poll_read(&mut cx, buf, exe_ctx) {
+            Poll::Ready(r) => r,
+            Poll::Pending => Err(pending_error()),
+        }
+    }
+}
+
+impl<T: AsyncWrite> Write for AsyncAsSync<T> {
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        let mut cx = Context::from_waker(Waker::noop());
+        match self.0.poll_write(&mut cx, buf, exe_ctx) {
+            Poll::Ready(r) => r,
+            Poll::Pending => Err(pending_error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use crate::io::stream::BufferAsOnePassROStream;
+    use crate::io::stream::RetryPolicy;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    /* reports WouldBlock every other call */
+    struct Sluggish<T> {
+        inner: T,
+        ready: bool,
+    }
+    impl<T: Read> Read for Sluggish<T> {
+        fn read<'a>(
+            &mut self,
+            buf: &mut [u8],
+            exe_ctx: &mut ExecutionContext<'a>
+        ) -> IOResult<'a, usize> {
+            self.ready = !self.ready;
+            if self.ready {
+                self.inner.read(buf, exe_ctx)
+            } else {
+                Err(IOError::with_str(ErrorCode::WouldBlock, "not yet"))
+            }
+        }
+    }
+
+    #[test]
+    fn sync_as_async_read() {
+        let registrations = Cell::new(0);
+        let hook = |_: &Waker| registrations.set(registrations.get() + 1);
+        let mut xc = ExecutionContext::nop();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut r = SyncAsAsync(Sluggish {
+            inner: BufferAsOnePassROStream::new(b"abc"),
+            ready: true,
+        });
+        let mut buf = [0_u8; 2];
+        assert!(r.poll_read(&mut cx, &mut buf, &mut xc).is_pending());
+        assert_eq!(registrations.get(), 0);
+        xc.set_waker_hook(Some(&hook));
+        match r.poll_read(&mut cx, &mut buf, &mut xc) {
+            Poll::Ready(Ok(2)) => assert_eq!(buf, *b"ab"),
+            _ => panic!(),
+        }
+        assert!(r.poll_read(&mut cx, &mut buf, &mut xc).is_pending());
+        assert_eq!(registrations.get(), 1);
+    }
+
+    #[test]
+    fn async_as_sync_round_trip() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut r = AsyncAsSync(SyncAsAsync(Sluggish {
+            inner: BufferAsOnePassROStream::new(b"abc"),
+            ready: true,
+        }));
+        let mut buf = [0_u8; 3];
+        let e = r.read(&mut buf, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::WouldBlock);
+        assert_eq!(r.read_with_retry(&mut buf, RetryPolicy::new(2), &mut xc).unwrap(), 3);
+        assert_eq!(buf, *b"abc");
+
+        let mut w = AsyncAsSync(SyncAsAsync(xc.byte_vector()));
+        w.write_all(b"xyz", &mut xc).unwrap();
+        assert_eq!(w.into_inner().into_inner().as_slice(), b"xyz");
+    }
+}
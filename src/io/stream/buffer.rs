@@ -2,6 +2,7 @@ use crate::io::IOResult;
 use crate::io::IOError;
 use crate::io::ErrorCode;
 use crate::ExecutionContext;
+use crate::num::pos::usize_to_u64;
 use super::Read;
 use super::Write;
 use super::Seek;
@@ -57,15 +58,18 @@ impl Read for BufferAsROStream<'_> {
         buf: &mut [u8],
         _exe_ctx: &mut ExecutionContext<'a>
     ) -> IOResult<'a, usize> {
-        if self.position >= self.buffer.len() as u64 {
+        if self.position >= usize_to_u64(self.buffer.len()) {
             return Ok(0);
         }
         let pos = self.position as usize;
         let n = core::cmp::min(buf.len(), self.buffer.len() - pos);
         buf[0..n].copy_from_slice(&self.buffer[pos..pos + n]);
-        self.position += n as u64;
+        self.position += usize_to_u64(n);
         Ok(n)
     }
+    fn content_slice(&self) -> Option<&[u8]> {
+        Some(self.buffer)
+    }
 }
 impl Seek for BufferAsROStream<'_> {
     fn seek<'a>(
@@ -76,7 +80,7 @@ impl Seek for BufferAsROStream<'_> {
         self.position = match target {
             SeekFrom::Start(disp) => disp,
             SeekFrom::Current(disp) => relative_position(self.position, disp)?,
-            SeekFrom::End(disp) => relative_position(self.buffer.len() as u64, disp)?,
+            SeekFrom::End(disp) => relative_position(usize_to_u64(self.buffer.len()), disp)?,
         };
         Ok(self.position)
     }
@@ -84,6 +88,7 @@ impl Seek for BufferAsROStream<'_> {
 impl Write for BufferAsROStream<'_> {}
 impl Truncate for BufferAsROStream<'_> {}
 
+#[derive(Debug)]
 pub struct BufferAsRWStream<'a> {
     buffer: &'a mut [u8],
     position: u64,
@@ -109,13 +114,13 @@ impl Read for BufferAsRWStream<'_> {
         buf: &mut [u8],
         _exe_ctx: &mut ExecutionContext<'a>
     ) -> IOResult<'a, usize> {
-        if self.position >= self.size as u64 {
+        if self.position >= usize_to_u64(self.size) {
             return Ok(0);
         }
         let pos = self.position as usize;
         let n = core::cmp::min(buf.len(), self.size - pos);
         buf[0..n].copy_from_slice(&self.buffer[pos..pos + n]);
-        self.position += n as u64;
+        self.position += usize_to_u64(n);
         Ok(n)
     }
 }
@@ -129,7 +134,7 @@ impl Seek for BufferAsRWStream<'_> {
         self.position = match target {
             SeekFrom::Start(disp) => disp,
             SeekFrom::Current(disp) => relative_position(self.position, disp)?,
-            SeekFrom::End(disp) => relative_position(self.size as u64, disp)?,
+            SeekFrom::End(disp) => relative_position(usize_to_u64(self.size), disp)?,
         };
         Ok(self.position)
     }
@@ -141,7 +146,7 @@ impl Write for BufferAsRWStream<'_> {
         buf: &[u8],
         _exe_ctx: &mut ExecutionContext<'a>
     ) -> IOResult<'a, usize> {
-        if self.position >= (self.buffer.len() as u64) {
+        if self.position >= usize_to_u64(self.buffer.len()) {
             Err(IOError::with_str(ErrorCode::NoSpace, "buffer limit reached"))
         } else {
             let pos = self.position as usize;
@@ -150,7 +155,7 @@ impl Write for BufferAsRWStream<'_> {
             let end_pos = pos + write_size;
             self.buffer[pos..end_pos].copy_from_slice(&buf[0..write_size]);
             self.size = core::cmp::max(self.size, end_pos);
-            self.position = end_pos as u64;
+            self.position = usize_to_u64(end_pos);
             Ok(write_size)
         }
     }
@@ -0,0 +1,324 @@
+use crate::io::IOResult;
+use crate::io::IOError;
+use crate::io::ErrorCode;
+use crate::mm::Vector;
+use crate::xc_err;
+use crate::ExecutionContext;
+use super::Read;
+use super::Write;
+use super::Seek;
+use super::SeekFrom;
+use super::Truncate;
+use super::RandomAccessRead;
+use super::relative_position;
+
+fn total_len(a: u64, b: u64) -> IOResult<'static, u64> {
+    a.checked_add(b).ok_or_else(|| IOError::with_str(
+        ErrorCode::UnsupportedPosition, "chained streams too large for u64"))
+}
+
+/* Chain ********************************************************************/
+/* reads first until it ends, then second; when both can seek, positions
+ * past the end of first land in second; the length of first is taken
+ * when first needed and is assumed not to change afterwards */
+#[derive(Debug)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+    first_len: Option<u64>,
+    in_second: bool,
+    position: u64,
+}
+
+impl<A, B> Chain<A, B> {
+
+    pub fn new(first: A, second: B) -> Self {
+        Chain {
+            first,
+            second,
+            first_len: None,
+            in_second: false,
+            position: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+
+}
+
+impl<A: Read, B: Read> Read for Chain<A, B> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if !self.in_second {
+            let n = self.first.read(buf, xc)?;
+            if n != 0 {
+                self.position += n as u64;
+                return Ok(n);
+            }
+            if self.first_len.is_none() {
+                // no seek so far, so all of first was read
+                self.first_len = Some(self.position);
+            }
+            self.in_second = true;
+        }
+        let n = self.second.read(buf, xc)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<A: Seek, B: Seek> Seek for Chain<A, B> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        let first_len = match self.first_len {
+            Some(n) => n,
+            None => {
+                let n = self.first.seek(SeekFrom::End(0), xc)?;
+                self.first_len = Some(n);
+                n
+            }
+        };
+        let pos = match target {
+            SeekFrom::Start(disp) => disp,
+            SeekFrom::Current(disp) => relative_position(self.position, disp)?,
+            SeekFrom::End(disp) => {
+                let second_len = self.second.seek(SeekFrom::End(0), xc)?;
+                relative_position(total_len(first_len, second_len)?, disp)?
+            },
+        };
+        if pos < first_len {
+            self.first.seek(SeekFrom::Start(pos), xc)?;
+            self.second.seek(SeekFrom::Start(0), xc)?;
+            self.in_second = false;
+        } else {
+            self.second.seek(SeekFrom::Start(pos - first_len), xc)?;
+            self.in_second = true;
+        }
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+impl<A, B> Write for Chain<A, B> {}
+impl<A, B> Truncate for Chain<A, B> {}
+
+/* MultiChain ***************************************************************/
+/* any number of streams read one after another as a single stream, each
+ * segment being read from its start; segment lengths are taken on the first
+ * seek and are assumed not to change afterwards */
+#[derive(Debug)]
+pub struct MultiChain<'a> {
+    segments: Vector<'a, &'a mut (dyn RandomAccessRead + 'a)>,
+    ends: Vector<'a, u64>, // end offset of each segment in the chain
+    index: usize,
+    positioned: bool, // the current segment is at the matching offset
+    position: u64,
+}
+
+impl<'a> MultiChain<'a> {
+
+    pub fn new(segments: Vector<'a, &'a mut (dyn RandomAccessRead + 'a)>) -> Self {
+        let ends = Vector::new(segments.allocator());
+        MultiChain {
+            segments,
+            ends,
+            index: 0,
+            positioned: false,
+            position: 0,
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn into_inner(self) -> Vector<'a, &'a mut (dyn RandomAccessRead + 'a)> {
+        self.segments
+    }
+
+    fn fill_ends<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if self.ends.len() == self.segments.len() {
+            return Ok(());
+        }
+        self.ends.reserve(self.segments.len())
+            .map_err(|e| xc_err!(
+                xc, ErrorCode::NoSpace,
+                "multi-chain out of memory",
+                "multi-chain cannot store {} segment ends: {}",
+                self.segments.len(), e))?;
+        let mut end = 0_u64;
+        for s in self.segments.as_mut_slice() {
+            end = total_len(end, s.seek(SeekFrom::End(0), xc)?)?;
+            self.ends.push(end).unwrap();
+        }
+        // all segments were moved
+        self.positioned = false;
+        Ok(())
+    }
+
+    fn segment_start(&self, index: usize) -> u64 {
+        if index == 0 { 0 } else { self.ends.as_slice()[index - 1] }
+    }
+
+}
+
+impl<'a> Read for MultiChain<'a> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.index < self.segments.len() {
+            let s = &mut self.segments.as_mut_slice()[self.index];
+            if !self.positioned {
+                s.seek(SeekFrom::Start(0), xc)?;
+                self.positioned = true;
+            }
+            let n = s.read(buf, xc)?;
+            if n != 0 {
+                self.position += n as u64;
+                return Ok(n);
+            }
+            self.index += 1;
+            self.positioned = false;
+        }
+        Ok(0)
+    }
+}
+
+impl<'a> Seek for MultiChain<'a> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        self.fill_ends(xc)?;
+        let pos = match target {
+            SeekFrom::Start(disp) => disp,
+            SeekFrom::Current(disp) => relative_position(self.position, disp)?,
+            SeekFrom::End(disp) => {
+                let total = self.ends.as_slice().last().copied().unwrap_or(0);
+                relative_position(total, disp)?
+            },
+        };
+        let index = self.ends.as_slice().partition_point(|&end| end <= pos);
+        if index < self.segments.len() {
+            let start = self.segment_start(index);
+            self.segments.as_mut_slice()[index]
+                .seek(SeekFrom::Start(pos - start), xc)?;
+            self.positioned = true;
+        }
+        self.index = index;
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+impl<'a> Write for MultiChain<'a> {}
+impl<'a> Truncate for MultiChain<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::BufferAsOnePassROStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn chain_reads_sequentially() {
+        let mut xc = ExecutionContext::nop();
+        let mut c = Chain::new(
+            BufferAsOnePassROStream::new(b"abc"),
+            BufferAsOnePassROStream::new(b"defgh"));
+        let mut buf = [0_u8; 16];
+        assert_eq!(c.read(&mut buf, &mut xc).unwrap(), 3);
+        assert_eq!(c.read_uninterrupted(&mut buf, &mut xc).unwrap(), 5);
+        assert_eq!(buf[0..5], *b"defgh");
+        assert_eq!(c.read(&mut buf, &mut xc).unwrap(), 0);
+    }
+
+    #[test]
+    fn chain_seeks_across_boundary() {
+        let mut xc = ExecutionContext::nop();
+        let mut c = Chain::new(
+            BufferAsROStream::new(b"0123"),
+            BufferAsROStream::new(b"456789"));
+        let mut buf = [0_u8; 4];
+        assert_eq!(c.seek_read(2, &mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf, *b"2345");
+        assert_eq!(c.seek(SeekFrom::End(-3), &mut xc).unwrap(), 7);
+        assert_eq!(c.read(&mut buf, &mut xc).unwrap(), 3);
+        assert_eq!(buf[0..3], *b"789");
+        assert_eq!(c.seek(SeekFrom::Current(-9), &mut xc).unwrap(), 1);
+        assert_eq!(c.read_uninterrupted(&mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf, *b"1234");
+        assert!(c.seek(SeekFrom::Current(-6), &mut xc).is_err());
+        let (_, mut b) = c.into_inner();
+        assert_eq!(b.read(&mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf, *b"5678");
+    }
+
+    #[test]
+    fn multi_chain_reads_and_seeks() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::nop();
+        let mut s0 = BufferAsROStream::new(b"abc");
+        let mut s1 = BufferAsROStream::new(b"");
+        let mut s2 = BufferAsROStream::new(b"defg");
+        let mut segments: Vector<&mut dyn RandomAccessRead> = Vector::new(a.to_ref());
+        segments.push(&mut s0).unwrap();
+        segments.push(&mut s1).unwrap();
+        segments.push(&mut s2).unwrap();
+        let mut c = MultiChain::new(segments);
+        assert_eq!(c.segment_count(), 3);
+        let mut buf = [0_u8; 8];
+        assert_eq!(c.read_uninterrupted(&mut buf, &mut xc).unwrap(), 7);
+        assert_eq!(buf[0..7], *b"abcdefg");
+        assert_eq!(c.seek(SeekFrom::End(0), &mut xc).unwrap(), 7);
+        assert_eq!(c.read(&mut buf, &mut xc).unwrap(), 0);
+        assert_eq!(c.seek_read(2, &mut buf[0..3], &mut xc).unwrap(), 3);
+        assert_eq!(buf[0..3], *b"cde");
+        assert_eq!(c.seek(SeekFrom::Start(3), &mut xc).unwrap(), 3);
+        assert_eq!(c.read(&mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf[0..4], *b"defg");
+        assert_eq!(c.seek(SeekFrom::Start(20), &mut xc).unwrap(), 20);
+        assert_eq!(c.read(&mut buf, &mut xc).unwrap(), 0);
+    }
+
+    #[test]
+    fn multi_chain_rewinds_segments() {
+        let mut buffer = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::nop();
+        let mut s0 = BufferAsROStream::new(b"xy");
+        let mut s1 = BufferAsROStream::new(b"z");
+        s0.seek(SeekFrom::End(0), &mut xc).unwrap();
+        s1.seek(SeekFrom::End(0), &mut xc).unwrap();
+        let mut segments: Vector<&mut dyn RandomAccessRead> = Vector::new(a.to_ref());
+        segments.push(&mut s0).unwrap();
+        segments.push(&mut s1).unwrap();
+        let mut c = MultiChain::new(segments);
+        let mut buf = [0_u8; 4];
+        assert_eq!(c.read_uninterrupted(&mut buf, &mut xc).unwrap(), 3);
+        assert_eq!(buf[0..3], *b"xyz");
+    }
+}
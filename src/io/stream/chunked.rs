@@ -0,0 +1,122 @@
+use core::cmp::min;
+
+use crate::io::IOResult;
+use crate::io::IOError;
+use crate::io::ErrorCode;
+use crate::ExecutionContext;
+use super::Read;
+use super::Seek;
+use super::SeekFrom;
+
+/* ChunkedReader ************************************************************/
+/* passes reads to the wrapped stream, each one limited to at most max_chunk
+ * bytes; when interrupt_every is k > 0, every k-th read fails with
+ * Interrupted without touching the wrapped stream; meant for testing code
+ * that has to cope with short and interrupted reads */
+#[derive(Debug)]
+pub struct ChunkedReader<T: Read> {
+    inner: T,
+    max_chunk: usize,
+    interrupt_every: usize,
+    read_count: usize,
+}
+
+impl<T: Read> ChunkedReader<T> {
+
+    /* a max_chunk of 0 is taken as 1 so that reads still make progress */
+    pub fn new(inner: T, max_chunk: usize, interrupt_every: usize) -> Self {
+        ChunkedReader {
+            inner,
+            max_chunk: if max_chunk == 0 { 1 } else { max_chunk },
+            interrupt_every,
+            read_count: 0,
+        }
+    }
+
+    pub fn max_chunk(&self) -> usize {
+        self.max_chunk
+    }
+
+    /* count of read() calls so far, interrupted ones included */
+    pub fn read_count(&self) -> usize {
+        self.read_count
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+}
+
+impl<T: Read> Read for ChunkedReader<T> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        self.read_count += 1;
+        if self.interrupt_every != 0
+            && self.read_count.is_multiple_of(self.interrupt_every) {
+            return Err(IOError::with_str(
+                ErrorCode::Interrupted, "injected interruption"));
+        }
+        let n = min(buf.len(), self.max_chunk);
+        self.inner.read(&mut buf[0..n], xc)
+    }
+}
+
+impl<T: Read + Seek> Seek for ChunkedReader<T> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        self.inner.seek(target, xc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsOnePassROStream;
+    use crate::io::stream::BufferAsROStream;
+
+    #[test]
+    fn reads_are_cut_to_chunk_size() {
+        let mut xc = ExecutionContext::nop();
+        let mut r = ChunkedReader::new(BufferAsOnePassROStream::new(b"abcdefg"), 3, 0);
+        let mut buf = [0_u8; 8];
+        assert_eq!(r.read(&mut buf, &mut xc).unwrap(), 3);
+        assert_eq!(buf[0..3], *b"abc");
+        assert_eq!(r.read_uninterrupted(&mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf[0..4], *b"defg");
+        assert_eq!(r.read_count(), 4);
+    }
+
+    #[test]
+    fn injected_interruptions() {
+        let mut xc = ExecutionContext::nop();
+        let mut r = ChunkedReader::new(BufferAsOnePassROStream::new(b"abcdefg"), 2, 2);
+        let mut buf = [0_u8; 8];
+        assert_eq!(r.read(&mut buf, &mut xc).unwrap(), 2);
+        let e = r.read(&mut buf, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::Interrupted);
+        let mut buf = [0_u8; 5];
+        r.read_exact(&mut buf, &mut xc).unwrap();
+        assert_eq!(buf, *b"cdefg");
+    }
+
+    #[test]
+    fn zero_chunk_and_seek() {
+        let mut xc = ExecutionContext::nop();
+        let mut r = ChunkedReader::new(BufferAsROStream::new(b"0123"), 0, 0);
+        assert_eq!(r.max_chunk(), 1);
+        assert_eq!(r.seek(SeekFrom::Start(2), &mut xc).unwrap(), 2);
+        assert_eq!(r.read_u8(&mut xc).unwrap(), b'2');
+        assert_eq!(r.into_inner().content_slice(), Some(&b"0123"[..]));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::io::IOResult;
+use crate::ExecutionContext;
+use super::Read;
+use super::Seek;
+use super::SeekFrom;
+
+/* CountingReader ***********************************************************/
+/* passes reads and seeks to the borrowed stream and counts the bytes read;
+ * the content slice of the stream is not exposed, so that users needing the
+ * whole content go through read() and get counted as well */
+#[derive(Debug)]
+pub struct CountingReader<'a, T: ?Sized + Read> {
+    stream: &'a mut T,
+    bytes_read: u64,
+    read_count: u64,
+}
+
+impl<'a, T: ?Sized + Read> CountingReader<'a, T> {
+
+    pub fn new(stream: &'a mut T) -> Self {
+        CountingReader { stream, bytes_read: 0, read_count: 0 }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /* count of successful read() calls so far */
+    pub fn read_count(&self) -> u64 {
+        self.read_count
+    }
+
+}
+
+impl<'a, T: ?Sized + Read> Read for CountingReader<'a, T> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let n = self.stream.read(buf, xc)?;
+        self.bytes_read += n as u64;
+        self.read_count += 1;
+        Ok(n)
+    }
+}
+
+impl<'a, T: ?Sized + Read + Seek> Seek for CountingReader<'a, T> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        self.stream.seek(target, xc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::RandomAccessRead;
+
+    #[test]
+    fn counts_bytes_read() {
+        let mut xc = ExecutionContext::nop();
+        let mut s = BufferAsROStream::new(b"0123456789");
+        let mut r = CountingReader::new(&mut s);
+        assert_eq!(r.content_slice(), None);
+        let mut buf = [0_u8; 4];
+        assert_eq!(r.seek_read(8, &mut buf, &mut xc).unwrap(), 2);
+        r.read_exact(&mut buf, &mut xc).unwrap_err();
+        assert_eq!(r.seek(SeekFrom::Start(1), &mut xc).unwrap(), 1);
+        r.read_exact(&mut buf, &mut xc).unwrap();
+        assert_eq!(buf, *b"1234");
+        assert_eq!(r.bytes_read(), 6);
+        assert_eq!(r.read_count(), 4);
+    }
+}
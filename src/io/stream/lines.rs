@@ -0,0 +1,186 @@
+use crate::exectx::ExecutionContext;
+use crate::mm::Vector;
+use crate::xc_err;
+
+use super::ErrorCode;
+use super::IOPartialResult;
+use super::Read;
+
+/* longest line read_line() takes, its end included */
+pub const DEFAULT_MAX_LINE_LEN: usize = 0x10000;
+
+/* appends the next line to the vector, without its LF or CR LF end (a lone
+ * CR is kept as content); returns the count of bytes consumed, which is 0
+ * only at the end of the stream; the stream is read one byte at a time so
+ * that nothing past the line is consumed;
+ * NoSpace is returned once max_len bytes are consumed without reaching the
+ * end of the line, with those bytes appended and the rest of the line left
+ * in the stream, so the caller can drop it or keep reading; NoSpace is
+ * also returned when the vector cannot grow, in which case the last byte
+ * consumed is lost */
+pub fn read_line<'a, T: ?Sized + Read>(
+    stream: &mut T,
+    line: &mut Vector<'_, u8>,
+    max_len: usize,
+    exe_ctx: &mut ExecutionContext<'a>,
+) -> IOPartialResult<'a, usize> {
+    let mut consumed = 0_usize;
+    let mut pending_cr = false;
+    while consumed < max_len {
+        let mut buf = [0_u8; 1];
+        match stream.read(&mut buf, exe_ctx) {
+            Ok(0) => break,
+            Ok(_) => {},
+            Err(e) => match e.get_error_code() {
+                ErrorCode::Interrupted => continue,
+                _ => return Err(super::IOPartialError::from_error_and_size(e, consumed)),
+            },
+        }
+        consumed += 1;
+        let b = buf[0];
+        if b == b'\n' {
+            return Ok(consumed);
+        }
+        if pending_cr {
+            line.push(b'\r').map_err(|_| line_alloc_error(consumed, exe_ctx))?;
+        }
+        pending_cr = b == b'\r';
+        if !pending_cr {
+            line.push(b).map_err(|_| line_alloc_error(consumed, exe_ctx))?;
+        }
+    }
+    if pending_cr {
+        line.push(b'\r').map_err(|_| line_alloc_error(consumed, exe_ctx))?;
+    }
+    if consumed == max_len && max_len != 0 {
+        return Err(xc_err!(exe_ctx, (ErrorCode::NoSpace, consumed),
+            "line too long",
+            "line longer than {} bytes", max_len));
+    }
+    Ok(consumed)
+}
+
+fn line_alloc_error<'a>(
+    consumed: usize,
+    exe_ctx: &mut ExecutionContext<'a>,
+) -> super::IOPartialError<'a> {
+    xc_err!(exe_ctx, (ErrorCode::NoSpace, consumed),
+        "no memory for line",
+        "no memory for line after {} bytes", consumed)
+}
+
+/* Lines ********************************************************************/
+/* lines of a stream as byte vectors, as read_line() gives them; a line that
+ * is too long is reported with NoSpace and the rest of it is skipped; the
+ * iteration stops after any other error */
+pub struct Lines<'x, 'a, T: Read> {
+    inner: T,
+    max_len: usize,
+    exe_ctx: &'x mut ExecutionContext<'a>,
+    done: bool,
+}
+
+impl<'x, 'a, T: Read> Lines<'x, 'a, T> {
+
+    pub fn new(inner: T, max_len: usize, exe_ctx: &'x mut ExecutionContext<'a>) -> Self {
+        Lines { inner, max_len, exe_ctx, done: false }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /* false if the stream failed */
+    fn skip_line(&mut self) -> bool {
+        let mut buf = [0_u8; 1];
+        loop {
+            match self.inner.read(&mut buf, self.exe_ctx) {
+                Ok(0) => return true,
+                Ok(_) => if buf[0] == b'\n' { return true; },
+                Err(e) if e.get_error_code() == ErrorCode::Interrupted => {},
+                Err(_) => return false,
+            }
+        }
+    }
+
+}
+
+impl<'x, 'a, T: Read> Iterator for Lines<'x, 'a, T> {
+    type Item = IOPartialResult<'a, Vector<'a, u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut line = self.exe_ctx.byte_vector();
+        match read_line(&mut self.inner, &mut line, self.max_len, self.exe_ctx) {
+            Ok(0) => {
+                self.done = true;
+                None
+            },
+            Ok(_) => Some(Ok(line)),
+            Err(e) => {
+                self.done = e.get_error_code() != ErrorCode::NoSpace || !self.skip_line();
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::ChunkedReader;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn lf_crlf_and_lone_cr() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = ChunkedReader::new(BufferAsROStream::new(b"ab\r\n\ncd\re\r\nlast\r"), 1, 2);
+        let mut line = xc.byte_vector();
+        assert_eq!(s.read_line(&mut line, &mut xc).unwrap(), 4);
+        assert_eq!(line.as_slice(), b"ab");
+        line.truncate(0);
+        assert_eq!(s.read_line(&mut line, &mut xc).unwrap(), 1);
+        assert_eq!(line.as_slice(), b"");
+        assert_eq!(s.read_line(&mut line, &mut xc).unwrap(), 6);
+        assert_eq!(line.as_slice(), b"cd\re");
+        line.truncate(0);
+        assert_eq!(s.read_line(&mut line, &mut xc).unwrap(), 5);
+        assert_eq!(line.as_slice(), b"last\r");
+        line.truncate(0);
+        assert_eq!(s.read_line(&mut line, &mut xc).unwrap(), 0);
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    fn long_lines() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = BufferAsROStream::new(b"abcdef\nxy\n");
+        let mut line = xc.byte_vector();
+        let e = s.read_line_with_limit(&mut line, 4, &mut xc).unwrap_err();
+        assert_eq!((e.get_error_code(), e.get_processed_size()), (ErrorCode::NoSpace, 4));
+        assert_eq!(line.as_slice(), b"abcd");
+        line.truncate(0);
+        assert_eq!(s.read_line_with_limit(&mut line, 4, &mut xc).unwrap(), 3);
+        assert_eq!(line.as_slice(), b"ef");
+
+        let s = BufferAsROStream::new(b"abcdef\nxyz\n\n0123");
+        let lines: std::vec::Vec<_> = s.lines(5, &mut xc)
+            .map(|r| r.map(|l| std::vec::Vec::from(l.as_slice())).map_err(|e| e.get_error_code()))
+            .collect();
+        assert_eq!(lines, [
+            Err(ErrorCode::NoSpace),
+            Ok(b"xyz".to_vec()),
+            Ok(b"".to_vec()),
+            Ok(b"0123".to_vec()),
+        ]);
+    }
+}
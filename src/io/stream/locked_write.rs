@@ -0,0 +1,224 @@
+extern crate std;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use super::Write;
+
+use crate::mm::Vector;
+use crate::io::IOResult;
+use crate::io::IOPartialResult;
+use crate::io::IOError;
+use crate::io::ErrorCode;
+use crate::ExecutionContext;
+
+/* LockedWrite **************************************************************/
+/* shares one output stream between threads; each write (and each write_all)
+ * is performed while holding the lock so data from different threads does
+ * not get mixed inside a single call */
+#[derive(Debug)]
+pub struct LockedWrite<W: Write> {
+    inner: Mutex<W>,
+}
+
+impl<W: Write> LockedWrite<W> {
+
+    pub fn new(inner: W) -> Self {
+        LockedWrite { inner: Mutex::new(inner) }
+    }
+
+    pub fn into_inner(self) -> W {
+        match self.inner.into_inner() {
+            Ok(w) => w,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn lock<'a>(&self) -> IOResult<'a, MutexGuard<'_, W>> {
+        self.inner.lock().map_err(|_| IOError::with_str(
+            ErrorCode::ResourceUnavailable,
+            "locked stream poisoned by a panicking thread"))
+    }
+
+    pub fn line_writer<'b>(
+        &'b self,
+        buffer: Vector<'b, u8>,
+    ) -> LockedLineWrite<'b, W> {
+        LockedLineWrite { target: self, buffer }
+    }
+
+}
+
+impl<W: Write> Write for &LockedWrite<W> {
+
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        self.lock()?.write(buf, exe_ctx)
+    }
+
+    fn write_all<'a>(
+        &mut self,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOPartialResult<'a, ()> {
+        self.lock()?.write_all(buf, exe_ctx)
+    }
+
+}
+
+impl<W: Write> Write for LockedWrite<W> {
+
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        (&*self).write(buf, exe_ctx)
+    }
+
+    fn write_all<'a>(
+        &mut self,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOPartialResult<'a, ()> {
+        (&*self).write_all(buf, exe_ctx)
+    }
+
+}
+
+/* LockedLineWrite **********************************************************/
+/* per-thread handle that accumulates data until a new line is seen and then
+ * sends whole lines to the shared stream with a single locked write_all;
+ * this keeps formatted log messages (which arrive in many small pieces)
+ * from interleaving with the ones from other threads */
+pub struct LockedLineWrite<'b, W: Write> {
+    target: &'b LockedWrite<W>,
+    buffer: Vector<'b, u8>,
+}
+
+impl<'b, W: Write> LockedLineWrite<'b, W> {
+
+    pub fn flush<'a>(
+        &mut self,
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOPartialResult<'a, ()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let r = self.target.lock()?.write_all(self.buffer.as_slice(), exe_ctx);
+        self.buffer.truncate(0);
+        r
+    }
+
+}
+
+impl<'b, W: Write> Write for LockedLineWrite<'b, W> {
+
+    fn write<'a>(
+        &mut self,
+        buf: &[u8],
+        exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        let n = self.buffer.write(buf, exe_ctx)?;
+        if buf[0..n].contains(&b'\n') {
+            let lines_len = self.buffer.as_slice().iter()
+                .rposition(|&b| b == b'\n').unwrap() + 1;
+            self.target.lock()?.write_all(
+                &self.buffer.as_slice()[0..lines_len], exe_ctx)?;
+            self.buffer.as_mut_slice().copy_within(lines_len.., 0);
+            let tail_len = self.buffer.len() - lines_len;
+            self.buffer.truncate(tail_len);
+        } else if n < buf.len() {
+            // buffer full; pass what we have to keep things moving
+            self.flush(exe_ctx)?;
+        }
+        Ok(n)
+    }
+
+}
+
+impl<'b, W: Write> Drop for LockedLineWrite<'b, W> {
+    fn drop(&mut self) {
+        let mut xc = ExecutionContext::nop();
+        let _ = self.flush(&mut xc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::vec::Vec;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::LogLevel;
+    use crate::log_info;
+
+    #[test]
+    fn write_through_shared_ref() {
+        let lw = LockedWrite::new(Vec::<u8>::new());
+        let mut xc = ExecutionContext::nop();
+        let mut w = &lw;
+        w.write_all(b"abc", &mut xc).unwrap();
+        w.write_all(b"def", &mut xc).unwrap();
+        assert_eq!(lw.into_inner(), b"abcdef");
+    }
+
+    #[test]
+    fn poisoned_lock_reports_resource_unavailable() {
+        let lw = LockedWrite::new(Vec::<u8>::new());
+        let _ = thread::scope(|s| s.spawn(|| {
+            let _g = lw.inner.lock().unwrap();
+            panic!("poison");
+        }).join());
+        let mut xc = ExecutionContext::nop();
+        let mut w = &lw;
+        let e = w.write(b"x", &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::ResourceUnavailable);
+    }
+
+    #[test]
+    fn line_writer_sends_whole_lines() {
+        let lw = LockedWrite::new(Vec::<u8>::new());
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut xc = ExecutionContext::nop();
+        {
+            let mut w = lw.line_writer(Vector::new(a.to_ref()));
+            w.write_all(b"first ", &mut xc).unwrap();
+            assert!(lw.inner.lock().unwrap().is_empty());
+            w.write_all(b"line\nsecond", &mut xc).unwrap();
+            assert_eq!(*lw.inner.lock().unwrap(), b"first line\n");
+        }
+        assert_eq!(lw.into_inner(), b"first line\nsecond");
+    }
+
+    #[test]
+    fn log_lines_from_threads_do_not_interleave() {
+        let lw = LockedWrite::new(Vec::<u8>::new());
+        thread::scope(|s| {
+            for t in 0..4 {
+                let lw = &lw;
+                s.spawn(move || {
+                    let mut buf = [0_u8; 0x400];
+                    let a = BumpAllocator::new(&mut buf);
+                    let mut log = lw.line_writer(Vector::new(a.to_ref()));
+                    let mut xc = ExecutionContext::new(
+                        a.to_ref(), a.to_ref(), &mut log, LogLevel::Info);
+                    for i in 0..50 {
+                        log_info!(xc, "thread {} message {} {}", t, i, "end");
+                    }
+                });
+            }
+        });
+        let out = lw.into_inner();
+        let text = std::str::from_utf8(&out).unwrap();
+        assert_eq!(text.lines().count(), 200);
+        for line in text.lines() {
+            assert!(line.starts_with("thread "));
+            assert!(line.ends_with(" end"));
+        }
+    }
+}
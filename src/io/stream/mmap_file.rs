@@ -0,0 +1,278 @@
+extern crate std;
+use core::fmt;
+use core::ptr::NonNull;
+use std::fs::File;
+use std::path::Path;
+use std::os::raw::c_void;
+
+use super::Read;
+use super::Write;
+use super::Seek;
+use super::SeekFrom;
+use super::Truncate;
+use super::relative_position;
+use super::std_file::convert_error;
+
+use crate::io::IOResult;
+use crate::io::IOError;
+use crate::io::ErrorCode;
+use crate::ExecutionContext;
+
+#[cfg(unix)]
+mod sys {
+    extern crate std;
+    use std::os::raw::c_void;
+    use std::os::raw::c_int;
+    use std::os::raw::c_long;
+    use std::os::unix::io::AsRawFd;
+    use std::fs::File;
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+
+    extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int,
+                fd: c_int, offset: c_long) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    pub struct Mapping;
+
+    pub unsafe fn map(
+        file: &File,
+        len: usize,
+    ) -> std::io::Result<(*mut c_void, Mapping)> {
+        let p = mmap(core::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE,
+                     file.as_raw_fd(), 0);
+        if p as isize == -1 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok((p, Mapping))
+        }
+    }
+
+    pub unsafe fn unmap(ptr: *mut c_void, len: usize, _m: &Mapping) {
+        munmap(ptr, len);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    extern crate std;
+    use std::os::raw::c_void;
+    use std::os::windows::io::AsRawHandle;
+    use std::fs::File;
+
+    const PAGE_READONLY: u32 = 0x02;
+    const FILE_MAP_READ: u32 = 0x04;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateFileMappingW(file: *mut c_void, attributes: *mut c_void,
+                              protect: u32, max_size_high: u32,
+                              max_size_low: u32, name: *const u16) -> *mut c_void;
+        fn MapViewOfFile(mapping: *mut c_void, access: u32, offset_high: u32,
+                         offset_low: u32, size: usize) -> *mut c_void;
+        fn UnmapViewOfFile(addr: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub struct Mapping(*mut c_void);
+
+    pub unsafe fn map(
+        file: &File,
+        len: usize,
+    ) -> std::io::Result<(*mut c_void, Mapping)> {
+        let h = CreateFileMappingW(file.as_raw_handle() as *mut c_void,
+            core::ptr::null_mut(), PAGE_READONLY, 0, 0, core::ptr::null());
+        if h.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        let p = MapViewOfFile(h, FILE_MAP_READ, 0, 0, len);
+        if p.is_null() {
+            let e = std::io::Error::last_os_error();
+            CloseHandle(h);
+            Err(e)
+        } else {
+            Ok((p, Mapping(h)))
+        }
+    }
+
+    pub unsafe fn unmap(ptr: *mut c_void, _len: usize, m: &Mapping) {
+        UnmapViewOfFile(ptr);
+        CloseHandle(m.0);
+    }
+}
+
+/* MmapFile *****************************************************************/
+/* read-only stream over a file mapped in memory; the whole content is also
+ * available as a slice so parsers can look at it without copying; empty
+ * files are not mapped at all */
+pub struct MmapFile {
+    ptr: NonNull<u8>,
+    len: usize,
+    position: u64,
+    mapping: Option<sys::Mapping>,
+}
+
+impl MmapFile {
+
+    /// # Safety
+    /// The file must not be truncated or modified (by this or any other
+    /// process) while the mapping is alive, as the slice would change
+    /// under its readers or access pages that no longer exist.
+    pub unsafe fn map<'x>(
+        file: &File,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, MmapFile> {
+        let size = file.metadata()
+            .map_err(|e| convert_error(e, "mmap stat failed", xc))?
+            .len();
+        if size > usize::MAX as u64 {
+            return Err(IOError::with_str(
+                ErrorCode::Unsuccessful, "file too large to map"));
+        }
+        let len = size as usize;
+        if len == 0 {
+            return Ok(MmapFile {
+                ptr: NonNull::dangling(), len, position: 0, mapping: None
+            });
+        }
+        let (p, m) = sys::map(file, len)
+            .map_err(|e| convert_error(e, "mmap failed", xc))?;
+        Ok(MmapFile {
+            ptr: NonNull::new(p as *mut u8).unwrap(),
+            len,
+            position: 0,
+            mapping: Some(m),
+        })
+    }
+
+    /// # Safety
+    /// Same as for [`MmapFile::map`].
+    pub unsafe fn open<'x, P: AsRef<Path>>(
+        path: P,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, MmapFile> {
+        let f = File::open(path)
+            .map_err(|e| convert_error(e, "open failed", xc))?;
+        MmapFile::map(&f, xc)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+}
+
+impl Drop for MmapFile {
+    fn drop(&mut self) {
+        if let Some(m) = &self.mapping {
+            unsafe { sys::unmap(self.ptr.as_ptr() as *mut c_void, self.len, m) };
+        }
+    }
+}
+
+impl fmt::Debug for MmapFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MmapFile(len: {}, position: {})", self.len, self.position)
+    }
+}
+
+impl Read for MmapFile {
+    fn read<'a>(
+        &mut self,
+        buf: &mut [u8],
+        _exe_ctx: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, usize> {
+        if self.position >= self.len as u64 {
+            return Ok(0);
+        }
+        let pos = self.position as usize;
+        let n = core::cmp::min(buf.len(), self.len - pos);
+        buf[0..n].copy_from_slice(&self.as_slice()[pos..pos + n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+    fn content_slice(&self) -> Option<&[u8]> {
+        Some(self.as_slice())
+    }
+}
+
+impl Seek for MmapFile {
+    fn seek<'a>(
+        &mut self,
+        target: SeekFrom,
+        _xc: &mut ExecutionContext<'a>
+    ) -> IOResult<'a, u64> {
+        self.position = match target {
+            SeekFrom::Start(disp) => disp,
+            SeekFrom::Current(disp) => relative_position(self.position, disp)?,
+            SeekFrom::End(disp) => relative_position(self.len as u64, disp)?,
+        };
+        Ok(self.position)
+    }
+}
+impl Write for MmapFile {}
+impl Truncate for MmapFile {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use crate::io::stream::RandomAccessRead;
+
+    fn temp_file(name: &str, content: &[u8]) -> std::path::PathBuf {
+        let mut path = env::temp_dir();
+        path.push(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn map_and_read() {
+        let path = temp_file("halfbit-mmap-test.dat", b"0123456789ABCDEF");
+        let mut xc = ExecutionContext::nop();
+        let mut m = unsafe { MmapFile::open(&path, &mut xc) }.unwrap();
+        assert_eq!(m.len(), 16);
+        assert_eq!(m.as_slice(), b"0123456789ABCDEF");
+        assert_eq!(m.content_slice(), Some(&b"0123456789ABCDEF"[..]));
+        let mut buf = [0_u8; 4];
+        assert_eq!(m.seek_read(10, &mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf, *b"ABCD");
+        assert_eq!(m.read(&mut buf, &mut xc).unwrap(), 2);
+        assert_eq!(buf[0..2], *b"EF");
+        assert_eq!(m.read(&mut buf, &mut xc).unwrap(), 0);
+        assert_eq!(m.seek(SeekFrom::End(-1), &mut xc).unwrap(), 15);
+        assert!(m.write(b"x", &mut xc).is_err());
+        assert!(std::format!("{:?}", m).contains("len: 16"));
+    }
+
+    #[test]
+    fn empty_file_is_not_mapped() {
+        let path = temp_file("halfbit-mmap-empty-test.dat", b"");
+        let mut xc = ExecutionContext::nop();
+        let mut m = unsafe { MmapFile::open(&path, &mut xc) }.unwrap();
+        assert!(m.is_empty());
+        assert_eq!(m.as_slice(), b"");
+        let mut buf = [0_u8; 4];
+        assert_eq!(m.read(&mut buf, &mut xc).unwrap(), 0);
+    }
+
+    #[test]
+    fn open_missing_file_fails() {
+        let mut path = env::temp_dir();
+        path.push("halfbit-mmap-no-such-file.dat");
+        let mut xc = ExecutionContext::nop();
+        let e = unsafe { MmapFile::open(&path, &mut xc) }.unwrap_err();
+        assert!(e.get_msg().contains("open failed"));
+    }
+}
@@ -6,6 +6,8 @@ use crate::xc_err;
 use crate::conv::int_be_decode;
 use crate::conv::int_le_decode;
 use crate::num::PrimitiveInt;
+use crate::num::pos::add_signed_offset;
+use crate::mm::Vector;
 
 use super::ErrorCode;
 use super::IOError;
@@ -14,6 +16,7 @@ use super::IOResult;
 use super::IOPartialResult;
 
 /* SeekFrom *****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum SeekFrom {
     Start(u64),
     Current(i64),
@@ -25,22 +28,28 @@ pub fn relative_position<'a>(
     pos: u64,
     disp: i64
 ) -> IOResult<'static, u64> {
-    if disp < 0 {
-        let udisp = -disp as u64;
-        if udisp <= pos {
-            Ok(pos - udisp)
-        } else {
-            Err(IOError::with_str(
-                ErrorCode::UnsupportedPosition,
-                "seek to negative position"))
-        }
-    } else if let Some(new_pos) = pos.checked_add(disp as u64) {
-        Ok(new_pos)
-    } else {
-        Err(IOError::with_str(
-            ErrorCode::UnsupportedPosition,
-            "seek to position too large for u64"))
+    Ok(add_signed_offset(pos, disp)?)
+}
+
+/* RetryPolicy **************************************************************/
+/* how many times an operation is tried when it fails with Interrupted or
+ * WouldBlock; 0 attempts count as 1 */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+}
+
+impl RetryPolicy {
+
+    pub const fn new(max_attempts: u32) -> Self {
+        RetryPolicy { max_attempts }
+    }
+
+    fn should_retry(&self, attempt: u32, error_code: ErrorCode) -> bool {
+        attempt < self.max_attempts
+            && matches!(error_code, ErrorCode::Interrupted | ErrorCode::WouldBlock)
     }
+
 }
 
 /* Read *********************************************************************/
@@ -55,6 +64,12 @@ pub trait Read {
                 ErrorCode::UnsupportedOperation, "read not supported"))
     }
 
+    /* the whole content when it is already in memory, so that callers can
+     * look at it without seek/read round-trips */
+    fn content_slice(&self) -> Option<&[u8]> {
+        None
+    }
+
     fn read_uninterrupted<'a>(
         &mut self,
         buf: &mut [u8],
@@ -64,6 +79,9 @@ pub trait Read {
         let mut buf = &mut buf[..];
 
         while buf.len() != 0 {
+            if let Err(e) = exe_ctx.check_cancelled() {
+                return Err(IOPartialError::from_error_and_size(e, size_read));
+            }
             match self.read(buf, exe_ctx) {
                 Ok(n) => {
                     if n == 0 { break; }
@@ -204,6 +222,74 @@ pub trait Read {
         self.read_exact(&mut buf, exe_ctx).map(|_| int_le_decode(&buf).unwrap())
     }
 
+    /* see lines::read_line(); lines are limited to DEFAULT_MAX_LINE_LEN */
+    fn read_line<'a>(
+        &mut self,
+        line: &mut Vector<'_, u8>,
+        exe_ctx: &mut ExecutionContext<'a>,
+    ) -> IOPartialResult<'a, usize> {
+        lines::read_line(self, line, lines::DEFAULT_MAX_LINE_LEN, exe_ctx)
+    }
+
+    fn read_line_with_limit<'a>(
+        &mut self,
+        line: &mut Vector<'_, u8>,
+        max_len: usize,
+        exe_ctx: &mut ExecutionContext<'a>,
+    ) -> IOPartialResult<'a, usize> {
+        lines::read_line(self, line, max_len, exe_ctx)
+    }
+
+    fn lines<'x, 'a>(
+        self,
+        max_len: usize,
+        exe_ctx: &'x mut ExecutionContext<'a>,
+    ) -> Lines<'x, 'a, Self>
+    where Self: Sized {
+        Lines::new(self, max_len, exe_ctx)
+    }
+
+    /* read() retried as the policy allows; the backoff hook of the context
+     * runs before each retry that follows a WouldBlock */
+    fn read_with_retry<'a>(
+        &mut self,
+        buf: &mut [u8],
+        policy: RetryPolicy,
+        exe_ctx: &mut ExecutionContext<'a>,
+    ) -> IOResult<'a, usize> {
+        let mut attempt = 1_u32;
+        loop {
+            match self.read(buf, exe_ctx) {
+                Err(e) if policy.should_retry(attempt, e.get_error_code()) => {
+                    if e.get_error_code() == ErrorCode::WouldBlock {
+                        exe_ctx.backoff(attempt);
+                    }
+                    attempt += 1;
+                },
+                r => return r,
+            }
+        }
+    }
+
+    /* read() retried on Interrupted; Ok(None) when no data is available
+     * yet, so that callers can go wait for readiness on their own */
+    fn poll_read<'a>(
+        &mut self,
+        buf: &mut [u8],
+        exe_ctx: &mut ExecutionContext<'a>,
+    ) -> IOResult<'a, Option<usize>> {
+        loop {
+            match self.read(buf, exe_ctx) {
+                Ok(n) => return Ok(Some(n)),
+                Err(e) => match e.get_error_code() {
+                    ErrorCode::Interrupted => {},
+                    ErrorCode::WouldBlock => return Ok(None),
+                    _ => return Err(e),
+                }
+            }
+        }
+    }
+
 }
 
 /* Write ********************************************************************/
@@ -224,6 +310,9 @@ pub trait Write {
         let mut size_written = 0_usize;
         let mut buf = &buf[..];
         while buf.len() > 0 {
+            if let Err(e) = exe_ctx.check_cancelled() {
+                return Err(IOPartialError::from_error_and_size(e, size_written));
+            }
             match self.write(buf, exe_ctx) {
                 Ok(n) => {
                     size_written += n;
@@ -238,6 +327,28 @@ pub trait Write {
         Ok(())
     }
 
+    /* write() retried as the policy allows; the backoff hook of the context
+     * runs before each retry that follows a WouldBlock */
+    fn write_with_retry<'a>(
+        &mut self,
+        buf: &[u8],
+        policy: RetryPolicy,
+        exe_ctx: &mut ExecutionContext<'a>,
+    ) -> IOResult<'a, usize> {
+        let mut attempt = 1_u32;
+        loop {
+            match self.write(buf, exe_ctx) {
+                Err(e) if policy.should_retry(attempt, e.get_error_code()) => {
+                    if e.get_error_code() == ErrorCode::WouldBlock {
+                        exe_ctx.backoff(attempt);
+                    }
+                    attempt += 1;
+                },
+                r => return r,
+            }
+        }
+    }
+
 }
 
 /* Seek *********************************************************************/
@@ -361,9 +472,53 @@ pub use buffer::BufferAsRWStream;
 pub use buffer::BufferAsROStream;
 pub use buffer::BufferAsOnePassROStream;
 
+pub mod vec_stream;
+pub use vec_stream::ByteVectorStream;
+
+pub mod chain;
+pub use chain::Chain;
+pub use chain::MultiChain;
+
+pub mod replay;
+pub use replay::RecordingStream;
+pub use replay::ReplayStream;
+
+pub mod window;
+pub use window::Window;
+
+pub mod tee;
+pub use tee::Tee;
+pub use tee::Broadcast;
+
+pub mod ring_log;
+pub use ring_log::RingLogStream;
+
+pub mod chunked;
+pub use chunked::ChunkedReader;
+
+pub mod counting;
+pub use counting::CountingReader;
+
+pub mod lines;
+pub use lines::Lines;
+
+pub mod async_io;
+pub use async_io::AsyncRead;
+pub use async_io::AsyncWrite;
+
 #[cfg(feature = "use-std")]
 pub mod std_file;
 
+#[cfg(all(feature = "use-std", any(unix, windows)))]
+pub mod mmap_file;
+#[cfg(all(feature = "use-std", any(unix, windows)))]
+pub use mmap_file::MmapFile;
+
+#[cfg(feature = "use-std")]
+pub mod locked_write;
+#[cfg(feature = "use-std")]
+pub use locked_write::LockedWrite;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -739,6 +894,27 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn cancelled_read_and_write() {
+        use crate::CancellationToken;
+        use crate::io::stream::buffer::BufferAsRWStream;
+        let token = CancellationToken::new();
+        let mut xc = ExecutionContext::nop();
+        xc.set_cancellation_token(Some(&token));
+        let mut r = IntermittentReader(0x2030220, 0x10);
+        let mut buf = [0_u8; 6];
+        assert_eq!(r.read_uninterrupted(&mut buf, &mut xc).unwrap(), 6);
+        token.cancel();
+        let e = r.read_uninterrupted(&mut buf, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::Cancelled);
+        assert_eq!(e.get_processed_size(), 0);
+        let mut out_buf = [0_u8; 4];
+        let mut out = BufferAsRWStream::new(&mut out_buf, 0);
+        assert_eq!(out.write_all(b"ab", &mut xc).unwrap_err().get_error_code(), ErrorCode::Cancelled);
+        token.reset();
+        out.write_all(b"ab", &mut xc).unwrap();
+    }
+
     #[test]
     fn read_uninterrupted_ok() {
         let mut xc = ExecutionContext::nop();
@@ -771,6 +947,72 @@ mod tests {
         assert_eq!(e2.get_error_code(), ErrorCode::Unsuccessful);
     }
 
+    /* fails with the given codes first, then reads/writes one byte */
+    struct Stubborn<'c>(&'c [ErrorCode]);
+    impl Stubborn<'_> {
+        fn next<'a>(&mut self) -> IOResult<'a, usize> {
+            match self.0.split_first() {
+                Some((&c, rest)) => {
+                    self.0 = rest;
+                    Err(IOError::with_str(c, "stubborn"))
+                },
+                None => Ok(1),
+            }
+        }
+    }
+    impl Read for Stubborn<'_> {
+        fn read<'a>(
+            &mut self,
+            _buf: &mut [u8],
+            _exe_ctx: &mut ExecutionContext<'a>
+        ) -> IOResult<'a, usize> {
+            self.next()
+        }
+    }
+    impl Write for Stubborn<'_> {
+        fn write<'a>(
+            &mut self,
+            _buf: &[u8],
+            _exe_ctx: &mut ExecutionContext<'a>
+        ) -> IOResult<'a, usize> {
+            self.next()
+        }
+    }
+
+    #[test]
+    fn read_with_retry_backs_off_on_would_block() {
+        use core::cell::Cell;
+        let backoffs = Cell::new(0_u32);
+        let hook = |attempt: u32| backoffs.set(backoffs.get() * 10 + attempt);
+        let mut xc = ExecutionContext::nop();
+        xc.set_backoff_hook(Some(&hook));
+        let mut buf = [0_u8; 4];
+        let codes = [ErrorCode::WouldBlock, ErrorCode::Interrupted, ErrorCode::WouldBlock];
+        let mut s = Stubborn(&codes);
+        assert_eq!(s.read_with_retry(&mut buf, RetryPolicy::new(4), &mut xc).unwrap(), 1);
+        assert_eq!(backoffs.get(), 13);
+
+        let mut s = Stubborn(&codes);
+        let e = s.write_with_retry(&buf, RetryPolicy::new(2), &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::Interrupted);
+
+        let codes = [ErrorCode::NoSpace];
+        let mut s = Stubborn(&codes);
+        let e = s.write_with_retry(&buf, RetryPolicy::new(9), &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::NoSpace);
+    }
+
+    #[test]
+    fn poll_read_surfaces_would_block() {
+        let mut xc = ExecutionContext::nop();
+        let mut buf = [0_u8; 4];
+        let codes = [ErrorCode::Interrupted, ErrorCode::WouldBlock, ErrorCode::UnexpectedEnd];
+        let mut s = Stubborn(&codes);
+        assert_eq!(s.poll_read(&mut buf, &mut xc).unwrap(), None);
+        assert!(s.poll_read(&mut buf, &mut xc).is_err());
+        assert_eq!(s.poll_read(&mut buf, &mut xc).unwrap(), Some(1));
+    }
+
     #[derive(Debug)]
     struct SeekReadTester {
         pos: u64,
@@ -0,0 +1,490 @@
+use core::cmp::min;
+use core::convert::TryInto;
+
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::mm::AllocatorRef;
+use crate::mm::Vector;
+use crate::xc_err;
+use crate::ExecutionContext;
+use super::Read;
+use super::Write;
+use super::Seek;
+use super::SeekFrom;
+use super::Truncate;
+
+/* TraceEvent ***************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+    /* the bytes read are kept in the trace data, in event order */
+    Read {
+        position: u64,
+        requested: usize,
+        result: Result<usize, ErrorCode>,
+    },
+    Seek {
+        target: SeekFrom,
+        result: Result<u64, ErrorCode>,
+    },
+}
+
+const TAG_READ: u8 = b'R';
+const TAG_SEEK: u8 = b'S';
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 1;
+
+/* Trace ********************************************************************/
+/* reads and seeks done on a stream together with the data they returned;
+ * encode() gives a compact byte form that decode() takes back, so traces
+ * can be saved from a tool run and replayed in tests
+ *
+ * encoding (integers are big-endian):
+ *   'R' u64:position u32:requested u8:0 u32:len data[len]
+ *   'R' u64:position u32:requested u8:1 u8:error_code
+ *   'S' u8:whence u64:disp u8:0 u64:position
+ *   'S' u8:whence u64:disp u8:1 u8:error_code
+ */
+#[derive(Debug)]
+pub struct Trace<'a> {
+    events: Vector<'a, TraceEvent>,
+    data: Vector<'a, u8>,
+}
+
+impl<'a> Trace<'a> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        Trace {
+            events: Vector::new(allocator),
+            data: Vector::new(allocator),
+        }
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        self.events.as_slice()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    fn add<'x>(
+        &mut self,
+        event: TraceEvent,
+        data: &[u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        self.data.append_from_slice(data)
+            .map_err(|e| xc_err!(xc, ErrorCode::NoSpace,
+                                 "trace out of memory",
+                                 "trace cannot store {} bytes: {}", data.len(), e))?;
+        self.events.push(event)
+            .map_err(|(e, _)| xc_err!(xc, ErrorCode::NoSpace,
+                                      "trace out of memory",
+                                      "trace cannot store event: {}", e))
+    }
+
+    pub fn encode<'w, 'x>(
+        &self,
+        out: &mut (dyn Write + 'w),
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut data = self.data.as_slice();
+        for e in self.events.as_slice() {
+            let mut hdr = [0_u8; 19];
+            let hdr_len;
+            let mut payload: &[u8] = &[];
+            match *e {
+                TraceEvent::Read { position, requested, result } => {
+                    hdr[0] = TAG_READ;
+                    hdr[1..9].copy_from_slice(&position.to_be_bytes());
+                    hdr[9..13].copy_from_slice(&(requested as u32).to_be_bytes());
+                    match result {
+                        Ok(n) => {
+                            hdr[13] = STATUS_OK;
+                            hdr[14..18].copy_from_slice(&(n as u32).to_be_bytes());
+                            hdr_len = 18;
+                            let (p, rest) = data.split_at(n);
+                            payload = p;
+                            data = rest;
+                        },
+                        Err(code) => {
+                            hdr[13] = STATUS_ERROR;
+                            hdr[14] = code.to_u8();
+                            hdr_len = 15;
+                        },
+                    }
+                },
+                TraceEvent::Seek { target, result } => {
+                    let (whence, disp) = split_seek_from(target);
+                    hdr[0] = TAG_SEEK;
+                    hdr[1] = whence;
+                    hdr[2..10].copy_from_slice(&disp.to_be_bytes());
+                    match result {
+                        Ok(pos) => {
+                            hdr[10] = STATUS_OK;
+                            hdr[11..19].copy_from_slice(&pos.to_be_bytes());
+                            hdr_len = 19;
+                        },
+                        Err(code) => {
+                            hdr[10] = STATUS_ERROR;
+                            hdr[11] = code.to_u8();
+                            hdr_len = 12;
+                        },
+                    }
+                },
+            }
+            out.write_all(&hdr[0..hdr_len], xc)?;
+            out.write_all(payload, xc)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode<'x>(
+        bytes: &[u8],
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, Trace<'x>> {
+        let mut t = Trace::new(xc.get_main_allocator());
+        let mut d = Decoder { bytes, pos: 0 };
+        while d.pos < bytes.len() {
+            let tag = d.u8(xc)?;
+            match tag {
+                TAG_READ => {
+                    let position = d.u64(xc)?;
+                    let requested = d.u32(xc)? as usize;
+                    if d.u8(xc)? == STATUS_OK {
+                        let n = d.u32(xc)? as usize;
+                        if n > requested {
+                            return Err(d.error("read longer than requested", xc));
+                        }
+                        let payload = d.take(n, xc)?;
+                        t.add(TraceEvent::Read { position, requested, result: Ok(n) }, payload, xc)?;
+                    } else {
+                        let code = ErrorCode::from_u8(d.u8(xc)?);
+                        t.add(TraceEvent::Read { position, requested, result: Err(code) }, &[], xc)?;
+                    }
+                },
+                TAG_SEEK => {
+                    let whence = d.u8(xc)?;
+                    let disp = d.u64(xc)?;
+                    let target = match whence {
+                        0 => SeekFrom::Start(disp),
+                        1 => SeekFrom::Current(disp as i64),
+                        2 => SeekFrom::End(disp as i64),
+                        _ => return Err(d.error("bad seek origin", xc)),
+                    };
+                    let result = if d.u8(xc)? == STATUS_OK {
+                        Ok(d.u64(xc)?)
+                    } else {
+                        Err(ErrorCode::from_u8(d.u8(xc)?))
+                    };
+                    t.add(TraceEvent::Seek { target, result }, &[], xc)?;
+                },
+                _ => return Err(d.error("bad event tag", xc)),
+            }
+        }
+        Ok(t)
+    }
+
+}
+
+fn split_seek_from(target: SeekFrom) -> (u8, u64) {
+    match target {
+        SeekFrom::Start(disp) => (0, disp),
+        SeekFrom::Current(disp) => (1, disp as u64),
+        SeekFrom::End(disp) => (2, disp as u64),
+    }
+}
+
+struct Decoder<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Decoder<'b> {
+    fn error<'x>(
+        &self,
+        what: &str,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOError<'x> {
+        xc_err!(xc, ErrorCode::InvalidData, "bad trace",
+                "bad trace at offset {}: {}", self.pos, what)
+    }
+    fn take<'x>(
+        &mut self,
+        n: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, &'b [u8]> {
+        if self.bytes.len() - self.pos < n {
+            return Err(self.error("truncated", xc));
+        }
+        let s = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(s)
+    }
+    fn u8<'x>(&mut self, xc: &mut ExecutionContext<'x>) -> IOResult<'x, u8> {
+        Ok(self.take(1, xc)?[0])
+    }
+    fn u32<'x>(&mut self, xc: &mut ExecutionContext<'x>) -> IOResult<'x, u32> {
+        Ok(u32::from_be_bytes(self.take(4, xc)?.try_into().unwrap()))
+    }
+    fn u64<'x>(&mut self, xc: &mut ExecutionContext<'x>) -> IOResult<'x, u64> {
+        Ok(u64::from_be_bytes(self.take(8, xc)?.try_into().unwrap()))
+    }
+}
+
+/* RecordingStream **********************************************************/
+/* passes reads and seeks to the inner stream and records them in a trace;
+ * the content slice of the inner stream is not exposed, so that everything
+ * a parser looks at goes through the trace */
+#[derive(Debug)]
+pub struct RecordingStream<'a, S> {
+    inner: S,
+    trace: Trace<'a>,
+    position: u64,
+}
+
+impl<'a, S> RecordingStream<'a, S> {
+
+    pub fn new(inner: S, allocator: AllocatorRef<'a>) -> Self {
+        RecordingStream {
+            inner,
+            trace: Trace::new(allocator),
+            position: 0,
+        }
+    }
+
+    pub fn trace(&self) -> &Trace<'a> {
+        &self.trace
+    }
+
+    pub fn into_parts(self) -> (S, Trace<'a>) {
+        (self.inner, self.trace)
+    }
+
+}
+
+impl<'a, S: Read> Read for RecordingStream<'a, S> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let r = self.inner.read(buf, xc);
+        let (result, data) = match &r {
+            Ok(n) => (Ok(*n), &buf[0..*n]),
+            Err(e) => (Err(e.get_error_code()), &buf[0..0]),
+        };
+        let event = TraceEvent::Read {
+            position: self.position,
+            requested: buf.len(),
+            result,
+        };
+        self.trace.add(event, data, xc)?;
+        if let Ok(n) = r {
+            self.position += n as u64;
+        }
+        r
+    }
+}
+
+impl<'a, S: Seek> Seek for RecordingStream<'a, S> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        let r = self.inner.seek(target, xc);
+        let result = match &r {
+            Ok(pos) => Ok(*pos),
+            Err(e) => Err(e.get_error_code()),
+        };
+        self.trace.add(TraceEvent::Seek { target, result }, &[], xc)?;
+        if let Ok(pos) = r {
+            self.position = pos;
+        }
+        r
+    }
+}
+
+impl<'a, S> Write for RecordingStream<'a, S> {}
+impl<'a, S> Truncate for RecordingStream<'a, S> {}
+
+/* ReplayStream *************************************************************/
+/* serves the events of a trace in order; any read or seek that does not
+ * match the next recorded event fails with InvalidData, which points out
+ * where the code under test diverged from the recorded run */
+#[derive(Debug)]
+pub struct ReplayStream<'t> {
+    trace: &'t Trace<'t>,
+    next_event: usize,
+    data_pos: usize,
+    position: u64,
+}
+
+impl<'t> ReplayStream<'t> {
+
+    pub fn new(trace: &'t Trace<'t>) -> Self {
+        ReplayStream {
+            trace,
+            next_event: 0,
+            data_pos: 0,
+            position: 0,
+        }
+    }
+
+    /* true once all recorded events were replayed */
+    pub fn is_done(&self) -> bool {
+        self.next_event == self.trace.events.len()
+    }
+
+    fn next<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, TraceEvent> {
+        match self.trace.events.as_slice().get(self.next_event) {
+            Some(e) => {
+                self.next_event += 1;
+                Ok(*e)
+            },
+            None => Err(xc_err!(xc, ErrorCode::InvalidData,
+                                "replay past the end of the trace",
+                                "replay past the end of the trace ({} events)",
+                                self.next_event)),
+        }
+    }
+
+    fn diverged<'x>(
+        &self,
+        expected: &TraceEvent,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOError<'x> {
+        xc_err!(xc, ErrorCode::InvalidData, "replay diverged from trace",
+                "replay diverged from trace at event #{}: recorded {:?}",
+                self.next_event - 1, expected)
+    }
+
+}
+
+impl<'t> Read for ReplayStream<'t> {
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let e = self.next(xc)?;
+        match e {
+            TraceEvent::Read { position, requested, result }
+            if position == self.position && requested == buf.len() => {
+                let n = result.map_err(|code| IOError::with_str(code, "replayed read error"))?;
+                let data = self.trace.data.as_slice();
+                let n = min(n, data.len() - self.data_pos);
+                buf[0..n].copy_from_slice(&data[self.data_pos..self.data_pos + n]);
+                self.data_pos += n;
+                self.position += n as u64;
+                Ok(n)
+            },
+            _ => Err(self.diverged(&e, xc)),
+        }
+    }
+}
+
+impl<'t> Seek for ReplayStream<'t> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        let e = self.next(xc)?;
+        match e {
+            TraceEvent::Seek { target: t, result } if t == target => {
+                let pos = result.map_err(|code| IOError::with_str(code, "replayed seek error"))?;
+                self.position = pos;
+                Ok(pos)
+            },
+            _ => Err(self.diverged(&e, xc)),
+        }
+    }
+}
+
+impl<'t> Write for ReplayStream<'t> {}
+impl<'t> Truncate for ReplayStream<'t> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::RandomAccessRead;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    fn run_parser<'x>(
+        s: &mut dyn RandomAccessRead,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, [u8; 4]> {
+        let mut buf = [0_u8; 4];
+        s.seek_read(6, &mut buf[0..2], xc)?;
+        s.read(&mut buf[2..4], xc)?;
+        s.seek(SeekFrom::End(0), xc)?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn record_then_replay() {
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut rs = RecordingStream::new(BufferAsROStream::new(b"0123456789"), a.to_ref());
+        assert_eq!(run_parser(&mut rs, &mut xc).unwrap(), *b"6789");
+        assert!(rs.seek(SeekFrom::Current(-20), &mut xc).is_err());
+        let (_, trace) = rs.into_parts();
+        assert_eq!(trace.events(), [
+            TraceEvent::Seek { target: SeekFrom::Start(6), result: Ok(6) },
+            TraceEvent::Read { position: 6, requested: 2, result: Ok(2) },
+            TraceEvent::Read { position: 8, requested: 2, result: Ok(2) },
+            TraceEvent::Seek { target: SeekFrom::End(0), result: Ok(10) },
+            TraceEvent::Seek { target: SeekFrom::Current(-20), result: Err(ErrorCode::UnsupportedPosition) },
+        ]);
+        assert_eq!(trace.data(), b"6789");
+
+        let mut rp = ReplayStream::new(&trace);
+        assert_eq!(run_parser(&mut rp, &mut xc).unwrap(), *b"6789");
+        assert!(!rp.is_done());
+        let e = rp.seek(SeekFrom::Current(-20), &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnsupportedPosition);
+        assert!(rp.is_done());
+        assert!(rp.read(&mut [0_u8; 1], &mut xc).is_err());
+    }
+
+    #[test]
+    fn replay_detects_divergence() {
+        let mut buffer = [0_u8; 0x800];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut rs = RecordingStream::new(BufferAsROStream::new(b"abc"), a.to_ref());
+        rs.read(&mut [0_u8; 2], &mut xc).unwrap();
+        let (_, trace) = rs.into_parts();
+        let mut rp = ReplayStream::new(&trace);
+        let e = rp.read(&mut [0_u8; 3], &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+        assert!(e.get_msg().contains("event #0"));
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut rs = RecordingStream::new(BufferAsROStream::new(b"0123456789"), a.to_ref());
+        run_parser(&mut rs, &mut xc).unwrap();
+        rs.seek(SeekFrom::Current(-20), &mut xc).unwrap_err();
+        let mut o = xc.byte_vector();
+        rs.trace().encode(&mut o, &mut xc).unwrap();
+        let t = Trace::decode(o.as_slice(), &mut xc).unwrap();
+        assert_eq!(t.events(), rs.trace().events());
+        assert_eq!(t.data(), rs.trace().data());
+        let e = Trace::decode(&o.as_slice()[0..o.len() - 1], &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+        assert!(Trace::decode(b"X", &mut xc).is_err());
+    }
+}
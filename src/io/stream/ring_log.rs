@@ -0,0 +1,179 @@
+use core::fmt;
+
+use crate::io::IOPartialError;
+use crate::io::IOPartialResult;
+use crate::io::IOResult;
+use crate::ExecutionContext;
+use super::Write;
+
+/* RingLogStream ************************************************************/
+/* keeps the last bytes written to it in a fixed buffer, overwriting the
+ * oldest ones when full, so that recent log messages can be dumped when
+ * something goes wrong; writes never fail */
+pub struct RingLogStream<'b> {
+    buffer: &'b mut [u8],
+    start: usize,
+    len: usize,
+    dropped: u64,
+}
+
+impl<'b> RingLogStream<'b> {
+
+    pub fn new(buffer: &'b mut [u8]) -> Self {
+        RingLogStream { buffer, start: 0, len: 0, dropped: 0 }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /* how many bytes were overwritten (or never stored) since creation or
+     * the last clear() */
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.dropped = 0;
+    }
+
+    /* the content, oldest bytes first, as the concatenation of 2 slices */
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let cap = self.buffer.len();
+        if self.start + self.len <= cap {
+            (&self.buffer[self.start..self.start + self.len], &[])
+        } else {
+            (&self.buffer[self.start..], &self.buffer[..self.start + self.len - cap])
+        }
+    }
+
+    /* writes the content, oldest bytes first, to out; the content is kept,
+     * call clear() to start over */
+    pub fn replay<'x>(
+        &self,
+        out: &mut (dyn Write + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOPartialResult<'x, ()> {
+        let (first, second) = self.as_slices();
+        out.write_all(first, xc)?;
+        out.write_all(second, xc).map_err(|e| {
+            let (data, msg) = e.to_parts();
+            IOPartialError::from_parts(data.0, first.len() + data.1, msg)
+        })
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        let cap = self.buffer.len();
+        if cap == 0 {
+            self.dropped += data.len() as u64;
+            return;
+        }
+        /* only the tail of data that fits can survive */
+        let skip = data.len().saturating_sub(cap);
+        let data = &data[skip..];
+        let overflow = (self.len + data.len()).saturating_sub(cap);
+        self.dropped += (skip + overflow) as u64;
+        self.start = (self.start + overflow) % cap;
+        self.len -= overflow;
+        let pos = (self.start + self.len) % cap;
+        let n = data.len().min(cap - pos);
+        self.buffer[pos..pos + n].copy_from_slice(&data[..n]);
+        self.buffer[..data.len() - n].copy_from_slice(&data[n..]);
+        self.len += data.len();
+    }
+
+}
+
+impl<'b> Write for RingLogStream<'b> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        _xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        self.push(buf);
+        Ok(buf.len())
+    }
+}
+
+impl fmt::Debug for RingLogStream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RingLogStream(len: {}/{}, dropped: {})",
+               self.len, self.buffer.len(), self.dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exectx::LogLevel;
+    use crate::io::ErrorCode;
+    use crate::io::stream::buffer::BufferAsRWStream;
+    use crate::log_info;
+    use crate::mm::Allocator;
+    use crate::mm::NOP_ALLOCATOR;
+
+    #[test]
+    fn keeps_last_bytes() {
+        let mut xc = ExecutionContext::nop();
+        let mut buf = [0_u8; 8];
+        let mut ring = RingLogStream::new(&mut buf);
+        assert!(ring.is_empty());
+        ring.write_all(b"abcde", &mut xc).unwrap();
+        assert_eq!(ring.as_slices(), (&b"abcde"[..], &b""[..]));
+        ring.write_all(b"fghij", &mut xc).unwrap();
+        assert_eq!(ring.as_slices(), (&b"cdefgh"[..], &b"ij"[..]));
+        assert_eq!((ring.len(), ring.dropped()), (8, 2));
+        ring.write_all(b"0123456789xy", &mut xc).unwrap();
+        assert_eq!(ring.as_slices(), (&b"456789"[..], &b"xy"[..]));
+        assert_eq!(ring.dropped(), 14);
+        ring.clear();
+        assert_eq!((ring.len(), ring.dropped()), (0, 0));
+
+        let mut ring = RingLogStream::new(&mut []);
+        ring.write_all(b"abc", &mut xc).unwrap();
+        assert_eq!((ring.len(), ring.dropped()), (0, 3));
+    }
+
+    #[test]
+    fn replay_after_logging() {
+        let mut ring_buf = [0_u8; 32];
+        let mut ring = RingLogStream::new(&mut ring_buf);
+        let mut xc = ExecutionContext::new(
+            NOP_ALLOCATOR.to_ref(),
+            NOP_ALLOCATOR.to_ref(),
+            &mut ring,
+            LogLevel::Info,
+        );
+        for i in 0..10 {
+            log_info!(xc, "step {}", i);
+        }
+        assert_eq!(ring.capacity(), 32);
+        assert_eq!(ring.len(), 32);
+
+        let mut xc = ExecutionContext::nop();
+        let mut out_buf = [0_u8; 40];
+        let mut out = BufferAsRWStream::new(&mut out_buf, 0);
+        ring.replay(&mut out, &mut xc).unwrap();
+        let (first, second) = ring.as_slices();
+        assert!(!second.is_empty());
+        assert_eq!(&out_buf[..first.len()], first);
+        assert_eq!(&out_buf[first.len()..32], second);
+        assert!(out_buf[..32].ends_with(b"step 8\nstep 9\n"));
+
+        let mut out_buf = [0_u8; 30];
+        let mut out = BufferAsRWStream::new(&mut out_buf, 0);
+        let e = ring.replay(&mut out, &mut xc).unwrap_err();
+        assert_eq!(e.get_processed_size(), 30);
+        assert_eq!(e.get_error_code(), ErrorCode::NoSpace);
+    }
+}
@@ -27,6 +27,11 @@ fn convert_error_with_allocator<'a>(
 ) -> IOError<'a> {
     let ec: ErrorCode = match e.kind() {
         StdIOErrorKind::Interrupted => ErrorCode::Interrupted,
+        StdIOErrorKind::WouldBlock | StdIOErrorKind::TimedOut => ErrorCode::WouldBlock,
+        StdIOErrorKind::UnexpectedEof => ErrorCode::UnexpectedEnd,
+        StdIOErrorKind::NotConnected | StdIOErrorKind::ConnectionReset
+            | StdIOErrorKind::ConnectionAborted | StdIOErrorKind::BrokenPipe
+            => ErrorCode::ResourceUnavailable,
         _ => ErrorCode::Unsuccessful
     };
     let mut msg = String::new(a);
@@ -35,7 +40,7 @@ fn convert_error_with_allocator<'a>(
     IOError::new(ec, msg)
 }
 
-fn convert_error<'a>(
+pub(crate) fn convert_error<'a>(
     e: std::io::Error,
     msg_pfx: &'static str,
     exe_ctx: &mut ExecutionContext<'a>,
@@ -143,4 +148,19 @@ mod tests {
         assert!(e.get_msg().contains("seek failed"));
     }
 
+    #[test]
+    fn error_kind_mapping() {
+        let mut xc = ExecutionContext::nop();
+        let e = convert_error(StdIOErrorKind::Interrupted.into(), "x", &mut xc);
+        assert_eq!(e.get_error_code(), ErrorCode::Interrupted);
+        let e = convert_error(StdIOErrorKind::WouldBlock.into(), "x", &mut xc);
+        assert_eq!(e.get_error_code(), ErrorCode::WouldBlock);
+        let e = convert_error(StdIOErrorKind::TimedOut.into(), "x", &mut xc);
+        assert_eq!(e.get_error_code(), ErrorCode::WouldBlock);
+        let e = convert_error(StdIOErrorKind::BrokenPipe.into(), "x", &mut xc);
+        assert_eq!(e.get_error_code(), ErrorCode::ResourceUnavailable);
+        let e = convert_error(StdIOErrorKind::NotFound.into(), "x", &mut xc);
+        assert_eq!(e.get_error_code(), ErrorCode::Unsuccessful);
+    }
+
 }
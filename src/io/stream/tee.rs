@@ -0,0 +1,188 @@
+use core::fmt;
+
+use crate::io::ErrorCode;
+use crate::io::IOResult;
+use crate::mm::Vector;
+use crate::xc_err;
+use crate::ExecutionContext;
+use super::Write;
+
+/* SinkFailure **************************************************************/
+/* which sink failed a write and how much of the buffer it had taken; sinks
+ * before it took the whole buffer, sinks after it were not written to */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SinkFailure {
+    pub sink: usize,
+    pub committed: usize,
+    pub code: ErrorCode,
+}
+
+fn write_to_sinks<'x>(
+    sinks: &mut [&mut (dyn Write + '_)],
+    buf: &[u8],
+    failure: &mut Option<SinkFailure>,
+    xc: &mut ExecutionContext<'x>,
+) -> IOResult<'x, usize> {
+    *failure = None;
+    for (i, s) in sinks.iter_mut().enumerate() {
+        if let Err(e) = s.write_all(buf, xc) {
+            let f = SinkFailure {
+                sink: i,
+                committed: e.get_processed_size(),
+                code: e.get_error_code(),
+            };
+            *failure = Some(f);
+            return Err(xc_err!(xc, f.code, "tee sink failed",
+                               "tee sink #{} failed after {}/{} bytes: {}",
+                               i, f.committed, buf.len(), e.get_msg()));
+        }
+    }
+    Ok(buf.len())
+}
+
+/* Tee **********************************************************************/
+/* writes everything to both sinks, first then second */
+pub struct Tee<'a> {
+    sinks: [&'a mut (dyn Write + 'a); 2],
+    failure: Option<SinkFailure>,
+}
+
+impl<'a> Tee<'a> {
+
+    pub fn new(
+        first: &'a mut (dyn Write + 'a),
+        second: &'a mut (dyn Write + 'a),
+    ) -> Self {
+        Tee { sinks: [first, second], failure: None }
+    }
+
+    /* details on the failure of the last write; None if it succeeded */
+    pub fn failure(&self) -> Option<SinkFailure> {
+        self.failure
+    }
+
+}
+
+impl<'a> Write for Tee<'a> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        write_to_sinks(&mut self.sinks, buf, &mut self.failure, xc)
+    }
+}
+
+impl fmt::Debug for Tee<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tee(failure: {:?})", self.failure)
+    }
+}
+
+/* Broadcast ****************************************************************/
+/* writes everything to each sink, in order */
+pub struct Broadcast<'a> {
+    sinks: Vector<'a, &'a mut (dyn Write + 'a)>,
+    failure: Option<SinkFailure>,
+}
+
+impl<'a> Broadcast<'a> {
+
+    pub fn new(sinks: Vector<'a, &'a mut (dyn Write + 'a)>) -> Self {
+        Broadcast { sinks, failure: None }
+    }
+
+    pub fn sink_count(&self) -> usize {
+        self.sinks.len()
+    }
+
+    /* details on the failure of the last write; None if it succeeded */
+    pub fn failure(&self) -> Option<SinkFailure> {
+        self.failure
+    }
+
+    pub fn into_sinks(self) -> Vector<'a, &'a mut (dyn Write + 'a)> {
+        self.sinks
+    }
+
+}
+
+impl<'a> Write for Broadcast<'a> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        write_to_sinks(self.sinks.as_mut_slice(), buf, &mut self.failure, xc)
+    }
+}
+
+impl fmt::Debug for Broadcast<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Broadcast(sinks: {}, failure: {:?})", self.sinks.len(), self.failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsRWStream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn tee_writes_to_both() {
+        let mut buffer = [0_u8; 0x200];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut v1 = xc.byte_vector();
+        let mut v2 = xc.byte_vector();
+        let mut t = Tee::new(&mut v1, &mut v2);
+        t.write_all(b"hello", &mut xc).unwrap();
+        t.write_all(b" 42", &mut xc).unwrap();
+        assert_eq!(t.failure(), None);
+        assert_eq!(v1.as_slice(), b"hello 42");
+        assert_eq!(v2.as_slice(), b"hello 42");
+    }
+
+    #[test]
+    fn tee_reports_failing_sink() {
+        let mut buffer = [0_u8; 0x200];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut v = xc.byte_vector();
+        let mut small = [0_u8; 3];
+        let mut s = BufferAsRWStream::new(&mut small[..], 0);
+        let mut t = Tee::new(&mut v, &mut s);
+        let e = t.write(b"abcde", &mut xc).unwrap_err();
+        assert_eq!(t.failure(), Some(SinkFailure {
+            sink: 1, committed: 3, code: e.get_error_code() }));
+        assert!(e.get_msg().starts_with("tee sink #1 failed after 3/5 bytes"));
+        assert_eq!(v.as_slice(), b"abcde");
+        assert_eq!(small, *b"abc");
+    }
+
+    #[test]
+    fn broadcast_stops_at_failing_sink() {
+        let mut buffer = [0_u8; 0x200];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut v1 = xc.byte_vector();
+        let mut v3 = xc.byte_vector();
+        let mut small = [0_u8; 1];
+        let mut s = BufferAsRWStream::new(&mut small[..], 0);
+        let mut sinks: Vector<&mut dyn Write> = Vector::new(a.to_ref());
+        assert!(sinks.push(&mut v1).is_ok());
+        assert!(sinks.push(&mut s).is_ok());
+        assert!(sinks.push(&mut v3).is_ok());
+        let mut b = Broadcast::new(sinks);
+        assert_eq!(b.sink_count(), 3);
+        b.write_all(b"x", &mut xc).unwrap();
+        assert!(b.write(b"yz", &mut xc).is_err());
+        assert_eq!(b.failure().unwrap().sink, 1);
+        assert_eq!(b.failure().unwrap().committed, 0);
+        drop(b);
+        assert_eq!(v1.as_slice(), b"xyz");
+        assert_eq!(v3.as_slice(), b"x");
+    }
+}
@@ -0,0 +1,236 @@
+use core::cmp::min;
+use core::convert::AsRef;
+use core::convert::AsMut;
+
+use super::Read;
+use super::Write;
+use super::Seek;
+use super::SeekFrom;
+use super::Truncate;
+use super::relative_position;
+
+use crate::io::ErrorCode;
+use crate::io::IOError;
+use crate::io::IOResult;
+use crate::mm::Vector;
+use crate::num::pos::u64_to_usize_checked;
+use crate::num::pos::usize_to_u64;
+use crate::xc_err;
+use crate::ExecutionContext;
+
+/* ByteVectorStream *********************************************************/
+/* in-memory file backed by a byte vector; writes overwrite the bytes at the
+ * current position and grow the vector through its allocator when going
+ * past the end; writing after a seek past the end fills the gap with zeros */
+#[derive(Debug)]
+pub struct ByteVectorStream<'a> {
+    data: Vector<'a, u8>,
+    pos: usize,
+}
+
+impl<'a> ByteVectorStream<'a> {
+
+    pub fn new(data: Vector<'a, u8>) -> ByteVectorStream<'a> {
+        ByteVectorStream { data, pos: 0 }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.pos as u64
+    }
+
+    pub fn into_inner(self) -> Vector<'a, u8> {
+        self.data
+    }
+
+    /* extends the vector with zeros up to the given size */
+    fn zero_fill<'x>(
+        &mut self,
+        size: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        if size <= self.data.len() {
+            return Ok(());
+        }
+        self.data.reserve(size - self.data.len())
+            .map_err(|e| xc_err!(
+                xc, ErrorCode::NoSpace,
+                "byte-vector stream out of memory",
+                "byte-vector stream cannot grow to {} bytes: {}", size, e))?;
+        while self.data.len() < size {
+            self.data.push(0).unwrap();
+        }
+        Ok(())
+    }
+
+}
+
+impl<'a> AsRef<Vector<'a, u8>> for ByteVectorStream<'a> {
+    fn as_ref(&self) -> &Vector<'a, u8> {
+        &self.data
+    }
+}
+
+impl<'a> AsMut<Vector<'a, u8>> for ByteVectorStream<'a> {
+    fn as_mut(&mut self) -> &mut Vector<'a, u8> {
+        &mut self.data
+    }
+}
+
+impl<'a> Seek for ByteVectorStream<'a> {
+    fn seek<'x>(
+        &mut self,
+        disp: SeekFrom,
+        _xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        self.pos = u64_to_usize_checked(match disp {
+            SeekFrom::Start(disp) => disp,
+            SeekFrom::Current(disp) => relative_position(usize_to_u64(self.pos), disp)?,
+            SeekFrom::End(disp) => relative_position(usize_to_u64(self.data.len()), disp)?,
+        })?;
+        Ok(usize_to_u64(self.pos))
+    }
+}
+
+impl<'a> Read for ByteVectorStream<'a> {
+
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        _exe_ctx: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        if self.pos < self.data.len() {
+            let n = min(self.data.len() - self.pos, buf.len());
+            buf[0..n].copy_from_slice(&self.data.as_slice()[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn content_slice(&self) -> Option<&[u8]> {
+        Some(self.data.as_slice())
+    }
+
+}
+
+impl<'a> Write for ByteVectorStream<'a> {
+
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        if buf.len() > usize::MAX - self.pos {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedPosition,
+                "write past the maximum vector size"));
+        }
+        self.zero_fill(self.pos, xc)?;
+        let overwrite_len = min(self.data.len() - self.pos, buf.len());
+        self.data.as_mut_slice()[self.pos..self.pos + overwrite_len]
+            .copy_from_slice(&buf[0..overwrite_len]);
+        self.pos += overwrite_len;
+        let tail = &buf[overwrite_len..];
+        if !tail.is_empty() {
+            if let Err(e) = self.data.append_from_slice(tail) {
+                if overwrite_len != 0 {
+                    return Ok(overwrite_len);
+                }
+                return Err(xc_err!(
+                    xc, ErrorCode::NoSpace,
+                    "byte-vector stream out of memory",
+                    "byte-vector stream write failed: {}", e));
+            }
+            self.pos += tail.len();
+        }
+        Ok(buf.len())
+    }
+
+}
+
+impl<'a> Truncate for ByteVectorStream<'a> {
+
+    /* sets the size keeping the current position, even if it ends up past
+     * the end; growing fills with zeros */
+    fn truncate<'x>(
+        &mut self,
+        size: u64,
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, ()> {
+        let size = u64_to_usize_checked(size)?;
+        if size < self.data.len() {
+            self.data.truncate(size);
+            Ok(())
+        } else {
+            self.zero_fill(size, xc)
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::Stream;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::no_sup_allocator;
+
+    #[test]
+    fn write_read_seek() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = ByteVectorStream::new(Vector::new(a.to_ref()));
+        let stream: &mut dyn Stream = &mut s;
+        stream.write_all(b"0123456789", &mut xc).unwrap();
+        assert_eq!(stream.seek(SeekFrom::Start(2), &mut xc).unwrap(), 2);
+        stream.write_all(b"ab", &mut xc).unwrap();
+        let mut buf = [0_u8; 4];
+        assert_eq!(stream.read(&mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf, *b"4567");
+        assert_eq!(stream.read(&mut buf, &mut xc).unwrap(), 2);
+        assert_eq!(stream.read(&mut buf, &mut xc).unwrap(), 0);
+        stream.seek(SeekFrom::End(-1), &mut xc).unwrap();
+        stream.write_all(b"XYZ", &mut xc).unwrap();
+        assert_eq!(s.position(), 12);
+        assert_eq!(s.into_inner().as_slice(), b"01ab45678XYZ");
+    }
+
+    #[test]
+    fn write_past_end_fills_gap_with_zeros() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = ByteVectorStream::new(Vector::from_slice(b"ab", a.to_ref()).unwrap());
+        s.seek(SeekFrom::Current(4), &mut xc).unwrap();
+        s.write_all(b"c", &mut xc).unwrap();
+        assert_eq!(s.as_ref().as_slice(), b"ab\0\0c");
+    }
+
+    #[test]
+    fn truncate_shrinks_and_grows() {
+        let mut buffer = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut s = ByteVectorStream::new(Vector::from_slice(b"abcdef", a.to_ref()).unwrap());
+        s.seek(SeekFrom::Start(5), &mut xc).unwrap();
+        s.truncate(3, &mut xc).unwrap();
+        assert_eq!(s.as_ref().as_slice(), b"abc");
+        assert_eq!(s.position(), 5);
+        s.truncate(4, &mut xc).unwrap();
+        assert_eq!(s.as_ref().as_slice(), b"abc\0");
+        assert_eq!(s.content_slice(), Some(&b"abc\0"[..]));
+    }
+
+    #[test]
+    fn write_without_memory_fails() {
+        let mut xc = ExecutionContext::nop();
+        let a = no_sup_allocator();
+        let mut s = ByteVectorStream::new(Vector::new(a.to_ref()));
+        let e = s.write(b"abc", &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::NoSpace);
+        assert!(s.as_mut().is_empty());
+    }
+}
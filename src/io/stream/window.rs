@@ -0,0 +1,187 @@
+use core::cmp::min;
+
+use crate::io::IOResult;
+use crate::io::IOError;
+use crate::io::ErrorCode;
+use crate::ExecutionContext;
+use super::Read;
+use super::Write;
+use super::Seek;
+use super::SeekFrom;
+use super::Truncate;
+use super::RandomAccessRead;
+use super::Stream;
+use super::relative_position;
+
+/* Window *******************************************************************/
+/* the byte range [offset, offset + len) of a stream seen as a stream on its
+ * own, with positions starting at 0; seeking past the end of the window is
+ * rejected and reads and writes stop at its end; if the underlying stream
+ * ends before the window does, reads stop there */
+#[derive(Debug)]
+pub struct Window<'a, T: ?Sized + RandomAccessRead> {
+    stream: &'a mut T,
+    offset: u64,
+    len: u64,
+    position: u64,
+}
+
+impl<'a, T: ?Sized + RandomAccessRead> Window<'a, T> {
+
+    /* the length is cut so that the window does not pass u64::MAX */
+    pub fn new(stream: &'a mut T, offset: u64, len: u64) -> Self {
+        Window {
+            stream,
+            offset,
+            len: min(len, u64::MAX - offset),
+            position: 0,
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /* size left between the current position and the end of the window */
+    fn available(&self, size: usize) -> usize {
+        min(size as u64, self.len - self.position) as usize
+    }
+
+}
+
+impl<'a, T: ?Sized + RandomAccessRead> Read for Window<'a, T> {
+
+    fn read<'x>(
+        &mut self,
+        buf: &mut [u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let n = self.available(buf.len());
+        if n == 0 {
+            return Ok(0);
+        }
+        self.stream.seek(SeekFrom::Start(self.offset + self.position), xc)?;
+        let n = self.stream.read(&mut buf[0..n], xc)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+
+    fn content_slice(&self) -> Option<&[u8]> {
+        let content = self.stream.content_slice()?;
+        let start = min(self.offset, content.len() as u64) as usize;
+        let end = min(self.offset + self.len, content.len() as u64) as usize;
+        Some(&content[start..end])
+    }
+
+}
+
+impl<'a, T: ?Sized + RandomAccessRead> Seek for Window<'a, T> {
+    fn seek<'x>(
+        &mut self,
+        target: SeekFrom,
+        _xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, u64> {
+        let pos = match target {
+            SeekFrom::Start(disp) => disp,
+            SeekFrom::Current(disp) => relative_position(self.position, disp)?,
+            SeekFrom::End(disp) => relative_position(self.len, disp)?,
+        };
+        if pos > self.len {
+            return Err(IOError::with_str(
+                ErrorCode::UnsupportedPosition, "seek past the end of window"));
+        }
+        self.position = pos;
+        Ok(pos)
+    }
+}
+
+impl<'a, T: ?Sized + Stream> Write for Window<'a, T> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        let n = self.available(buf.len());
+        if n == 0 && !buf.is_empty() {
+            return Err(IOError::with_str(
+                ErrorCode::NoSpace, "write past the end of window"));
+        }
+        self.stream.seek(SeekFrom::Start(self.offset + self.position), xc)?;
+        let n = self.stream.write(&buf[0..n], xc)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<'a, T: ?Sized + Stream> Truncate for Window<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::stream::BufferAsROStream;
+    use crate::io::stream::BufferAsRWStream;
+
+    #[test]
+    fn reads_stay_inside_window() {
+        let mut xc = ExecutionContext::nop();
+        let mut s = BufferAsROStream::new(b"0123456789");
+        let mut w = Window::new(&mut s, 3, 4);
+        let mut buf = [0_u8; 8];
+        assert_eq!(w.read(&mut buf, &mut xc).unwrap(), 4);
+        assert_eq!(buf[0..4], *b"3456");
+        assert_eq!(w.read(&mut buf, &mut xc).unwrap(), 0);
+        assert_eq!(w.seek_read(1, &mut buf[0..2], &mut xc).unwrap(), 2);
+        assert_eq!(buf[0..2], *b"45");
+        assert_eq!(w.seek(SeekFrom::End(-1), &mut xc).unwrap(), 3);
+        assert_eq!(w.seek(SeekFrom::Current(-3), &mut xc).unwrap(), 0);
+        let e = w.seek(SeekFrom::Start(5), &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::UnsupportedPosition);
+        assert!(w.seek(SeekFrom::Current(-1), &mut xc).is_err());
+        assert_eq!(w.content_slice(), Some(&b"3456"[..]));
+    }
+
+    #[test]
+    fn window_past_stream_end() {
+        let mut xc = ExecutionContext::nop();
+        let mut s = BufferAsROStream::new(b"0123");
+        let mut w = Window::new(&mut s, 2, 10);
+        let mut buf = [0_u8; 8];
+        assert_eq!(w.read_uninterrupted(&mut buf, &mut xc).unwrap(), 2);
+        assert_eq!(buf[0..2], *b"23");
+        assert_eq!(w.content_slice(), Some(&b"23"[..]));
+        let w = Window::new(&mut s, u64::MAX - 1, 10);
+        assert_eq!(w.len(), 1);
+    }
+
+    #[test]
+    fn nested_windows() {
+        let mut xc = ExecutionContext::nop();
+        let mut s = BufferAsROStream::new(b"0123456789");
+        let mut outer = Window::new(&mut s, 2, 6);
+        let mut inner = Window::new(&mut outer, 1, 3);
+        let mut buf = [0_u8; 8];
+        assert_eq!(inner.read_uninterrupted(&mut buf, &mut xc).unwrap(), 3);
+        assert_eq!(buf[0..3], *b"345");
+    }
+
+    #[test]
+    fn writes_stay_inside_window() {
+        let mut xc = ExecutionContext::nop();
+        let mut data = *b"..........";
+        let mut s = BufferAsRWStream::new(&mut data[..], 10);
+        let mut w = Window::new(&mut s, 4, 3);
+        assert_eq!(w.write(b"abcd", &mut xc).unwrap(), 3);
+        let e = w.write(b"e", &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::NoSpace);
+        assert!(w.truncate(1, &mut xc).is_err());
+        assert_eq!(data, *b"....abc...");
+    }
+}
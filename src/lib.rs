@@ -15,12 +15,27 @@ pub mod io; // input/output
 
 pub mod exectx; // execution context
 pub use exectx::ExecutionContext;
+pub use exectx::CancellationToken;
+pub use exectx::LogFormat;
 pub use exectx::LogLevel;
+pub use exectx::ChildContext;
+pub use exectx::ExecLimits;
+pub use exectx::LogLevelGuard;
+
+pub mod time; // clocks
+
+pub mod ext; // typed per-context storage
+pub use time::TimeSource;
 
 pub mod data_cell;
 
 pub mod conv; // converters
 
+pub mod report; // report tables
+
+#[cfg(feature = "bench")]
+pub mod bench; // throughput benchmark entry points
+
 
 pub fn lib_name() -> &'static str {
     "halfbit"
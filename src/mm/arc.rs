@@ -0,0 +1,496 @@
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ptr;
+use core::mem;
+use core::cmp::max;
+use core::num::NonZeroUsize;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use core::sync::atomic::fence;
+
+#[cfg(feature = "nightly")]
+use core::marker::Unsize;
+
+use crate::num::Pow2Usize;
+
+use super::Allocator;
+use super::AllocatorRef;
+use super::AllocError;
+
+/* Arc **********************************************************************/
+/* thread-safe counterpart of Rc: same layout (control block right before
+ * the payload) but with atomic counters; all strong refs together hold one
+ * weak ref which is released after the payload is dropped.
+ * The allocator must be Sync since the last reference can be dropped from
+ * any thread. */
+pub struct ArcPayload<T: ?Sized>(UnsafeCell<T>);
+
+struct ArcCtlBlock<'a> {
+    strong: AtomicUsize,
+    weak: AtomicUsize,
+    allocator: AllocatorRef<'a>,
+}
+
+pub struct Arc<'a, T>
+where T: ?Sized {
+    data: &'a ArcPayload<T>,
+}
+
+pub struct ArcWeak<'a, T>
+where T: ?Sized {
+    data: &'a ArcPayload<T>,
+}
+
+unsafe impl<'a, T: ?Sized + Send + Sync> Send for Arc<'a, T> {}
+unsafe impl<'a, T: ?Sized + Send + Sync> Sync for Arc<'a, T> {}
+unsafe impl<'a, T: ?Sized + Send + Sync> Send for ArcWeak<'a, T> {}
+unsafe impl<'a, T: ?Sized + Send + Sync> Sync for ArcWeak<'a, T> {}
+
+fn arc_alignment(payload_align: usize) -> Pow2Usize {
+    Pow2Usize::new(max(mem::align_of::<ArcCtlBlock<'_>>(), payload_align)).unwrap()
+}
+
+fn arc_align_of_val<T: ?Sized>(payload: &ArcPayload<T>) -> Pow2Usize {
+    arc_alignment(mem::align_of_val(payload))
+}
+
+fn arc_ctl_alloc_size(align: Pow2Usize) -> usize {
+    align.align_up(mem::size_of::<ArcCtlBlock<'_>>()).unwrap()
+}
+
+unsafe fn arc_ctl_block<'a, T: ?Sized>(payload: &ArcPayload<T>) -> &ArcCtlBlock<'a> {
+    let uptr = payload as *const ArcPayload<T> as *const u8 as usize;
+    let uptr = uptr - mem::size_of::<ArcCtlBlock<'_>>();
+    &*(uptr as *const ArcCtlBlock<'a>)
+}
+
+unsafe fn release_weak<T: ?Sized>(payload: &ArcPayload<T>) {
+    let ctl = arc_ctl_block(payload);
+    if ctl.weak.fetch_sub(1, Ordering::Release) == 1 {
+        fence(Ordering::Acquire);
+        let allocator = ctl.allocator;
+        let align = arc_align_of_val(payload);
+        let ctl_alloc_size = arc_ctl_alloc_size(align);
+        let uptr = payload.0.get() as *const u8 as usize - ctl_alloc_size;
+        let size = NonZeroUsize::new(mem::size_of_val(payload) + ctl_alloc_size).unwrap();
+        allocator.free(NonNull::new(uptr as *mut u8).unwrap(), size, align);
+    }
+}
+
+impl<'a, T> Arc<'a, T>
+where T: Sized {
+
+    /* allocates the block and initializes the control part; the payload is
+     * left uninitialized */
+    fn alloc_block<A: Allocator + Sync>(
+        allocator: &'a A,
+        strong: usize,
+    ) -> Result<*mut ArcPayload<T>, AllocError> {
+        let align = arc_alignment(mem::align_of::<ArcPayload<T>>());
+        let ctl_alloc_size = arc_ctl_alloc_size(align);
+        let size = NonZeroUsize::new(ctl_alloc_size + mem::size_of::<ArcPayload<T>>()).unwrap();
+        let ptr = unsafe { allocator.alloc(size, align) }?;
+        let uptr = (ptr.as_ptr() as usize) + ctl_alloc_size;
+        let data_ptr = uptr as *mut ArcPayload<T>;
+        let ctl_ptr = (uptr - mem::size_of::<ArcCtlBlock<'a>>()) as *mut ArcCtlBlock<'a>;
+        unsafe {
+            ptr::write(ctl_ptr, ArcCtlBlock {
+                strong: AtomicUsize::new(strong),
+                weak: AtomicUsize::new(1),
+                allocator: allocator.to_ref(),
+            });
+        }
+        Ok(data_ptr)
+    }
+
+    pub fn new<A: Allocator + Sync>(
+        allocator: &'a A,
+        value: T,
+    ) -> Result<Self, (AllocError, T)> {
+        match Self::alloc_block(allocator, 1) {
+            Ok(data_ptr) => {
+                unsafe {
+                    ptr::write(data_ptr, ArcPayload(UnsafeCell::new(value)));
+                    Ok(Arc { data: &*data_ptr })
+                }
+            },
+            Err(e) => Err((e, value))
+        }
+    }
+
+    /* builds a value that holds weak references to itself; the weak ref
+     * passed to data_fn cannot be upgraded until data_fn returns */
+    pub fn new_cyclic<A: Allocator + Sync, F>(
+        allocator: &'a A,
+        data_fn: F,
+    ) -> Result<Self, AllocError>
+    where F: FnOnce(&ArcWeak<'a, T>) -> T {
+        let data_ptr = Self::alloc_block(allocator, 0)?;
+        let weak = ArcWeak { data: unsafe { &*data_ptr } };
+        let value = data_fn(&weak);
+        unsafe {
+            ptr::write(data_ptr, ArcPayload(UnsafeCell::new(value)));
+            arc_ctl_block(weak.data).strong.store(1, Ordering::Release);
+        }
+        // the weak ref we made becomes the one held by the strong refs
+        let data = weak.data;
+        mem::forget(weak);
+        Ok(Arc { data })
+    }
+
+    /* extracts the value if this is the only strong reference */
+    pub fn try_unwrap(arc: Arc<'a, T>) -> Result<T, Arc<'a, T>> {
+        let ctl = unsafe { arc_ctl_block(arc.data) };
+        if ctl.strong.compare_exchange(1, 0, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return Err(arc);
+        }
+        fence(Ordering::Acquire);
+        let data = arc.data;
+        mem::forget(arc);
+        unsafe {
+            let value = ptr::read(data.0.get());
+            release_weak(data);
+            Ok(value)
+        }
+    }
+
+}
+
+impl<T> Arc<'_, T>
+where T: ?Sized {
+
+    pub fn strong_count(arc: &Arc<'_, T>) -> usize {
+        unsafe { arc_ctl_block(arc.data) }.strong.load(Ordering::SeqCst)
+    }
+
+    pub fn weak_count(arc: &Arc<'_, T>) -> usize {
+        unsafe { arc_ctl_block(arc.data) }.weak.load(Ordering::SeqCst) - 1
+    }
+
+    pub fn get_mut<'a>(arc: &'a mut Arc<'_, T>) -> Option<&'a mut T> {
+        let ctl = unsafe { arc_ctl_block(arc.data) };
+        // holding the only strong ref mutably means no new weak refs can
+        // appear so checking both counters is enough
+        if ctl.weak.load(Ordering::Acquire) == 1 && ctl.strong.load(Ordering::Acquire) == 1 {
+            Some(unsafe { &mut *arc.data.0.get() })
+        } else {
+            None
+        }
+    }
+
+    pub fn ptr_eq<'a, 'b>(a: &Arc<'a, T>, b: &Arc<'b, T>) -> bool {
+        NonNull::new(a.data as *const ArcPayload<T> as *mut ArcPayload<T>) ==
+        NonNull::new(b.data as *const ArcPayload<T> as *mut ArcPayload<T>)
+    }
+
+    #[cfg(feature = "nightly")]
+    pub fn to_dyn<'a, U>(arc: Arc<'a, T>) -> Arc<'a, U>
+    where
+        T: Unsize<U>,
+        U: ?Sized
+    {
+        let data = arc.data;
+        mem::forget(arc);
+        Arc { data }
+    }
+
+    /// # Safety
+    /// The payload must be turned back into an Arc with from_payload or
+    /// the reference it held leaks.
+    pub unsafe fn to_payload<'a>(arc: Arc<'a, T>) -> &'a ArcPayload<T> {
+        let data = arc.data;
+        mem::forget(arc);
+        data
+    }
+    /// # Safety
+    /// The payload must come from to_payload (with the same or an unsized
+    /// version of T) and be used this way only once.
+    pub unsafe fn from_payload<'a>(payload: &'a ArcPayload<T>) -> Arc<'a, T> {
+        Arc { data: payload }
+    }
+
+    pub fn downgrade<'a>(arc: &Arc<'a, T>) -> ArcWeak<'a, T> {
+        unsafe { arc_ctl_block(arc.data) }.weak.fetch_add(1, Ordering::Relaxed);
+        ArcWeak { data: arc.data }
+    }
+
+}
+
+impl<'a, T> AsRef<T> for Arc<'a, T> where T: ?Sized {
+
+    fn as_ref(&self) -> &T {
+        unsafe { &*self.data.0.get() }
+    }
+
+}
+
+impl<'a, T> Borrow<T> for Arc<'a, T> where T: ?Sized {
+
+    fn borrow(&self) -> &T {
+        self.as_ref()
+    }
+
+}
+
+impl<'a, T> Clone for Arc<'a, T> where T: ?Sized {
+
+    fn clone(&self) -> Arc<'a, T> {
+        unsafe { arc_ctl_block(self.data) }.strong.fetch_add(1, Ordering::Relaxed);
+        Arc { data: self.data }
+    }
+
+}
+
+impl<'a, T> fmt::Debug for Arc<'a, T> where T: ?Sized + fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Arc[{}+{}]{{{:?}}}", Arc::strong_count(self), Arc::weak_count(self), self.as_ref())
+    }
+}
+
+impl<'a, T> Deref for Arc<'a, T> where T: ?Sized {
+
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.as_ref()
+    }
+
+}
+
+impl<'a, T> Drop for Arc<'a, T> where T: ?Sized {
+
+    fn drop(&mut self) {
+        let ctl = unsafe { arc_ctl_block(self.data) };
+        if ctl.strong.fetch_sub(1, Ordering::Release) == 1 {
+            fence(Ordering::Acquire);
+            unsafe {
+                ptr::drop_in_place(self.data.0.get());
+                release_weak(self.data);
+            }
+        }
+    }
+
+}
+
+impl<'a, T> ArcWeak<'a, T> where T: ?Sized {
+
+    pub fn upgrade(&self) -> Option<Arc<'a, T>> {
+        let ctl = unsafe { arc_ctl_block(self.data) };
+        let mut n = ctl.strong.load(Ordering::Relaxed);
+        loop {
+            if n == 0 {
+                return None;
+            }
+            match ctl.strong.compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(Arc { data: self.data }),
+                Err(m) => n = m,
+            }
+        }
+    }
+
+    pub fn strong_count(&self) -> usize {
+        unsafe { arc_ctl_block(self.data) }.strong.load(Ordering::SeqCst)
+    }
+
+    pub fn weak_count(&self) -> usize {
+        let ctl = unsafe { arc_ctl_block(self.data) };
+        let weak = ctl.weak.load(Ordering::SeqCst);
+        if ctl.strong.load(Ordering::SeqCst) == 0 { weak } else { weak - 1 }
+    }
+
+}
+
+impl<'a, T> Clone for ArcWeak<'a, T> where T: ?Sized {
+
+    fn clone(&self) -> ArcWeak<'a, T> {
+        unsafe { arc_ctl_block(self.data) }.weak.fetch_add(1, Ordering::Relaxed);
+        ArcWeak { data: self.data }
+    }
+
+}
+
+impl<'a, T> Drop for ArcWeak<'a, T> where T: ?Sized {
+
+    fn drop(&mut self) {
+        unsafe { release_weak(self.data); }
+    }
+
+}
+
+#[cfg(not(feature = "nightly"))]
+#[macro_export]
+macro_rules! dyn_arc {
+    ( $func_name:ident, $trait:path ) => {
+        fn $func_name<'a, T: $trait>(arc: $crate::mm::Arc<'a, T>) -> $crate::mm::Arc<'a, dyn $trait + 'a> {
+            unsafe {
+                let data = $crate::mm::Arc::to_payload(arc);
+                $crate::mm::Arc::from_payload(data)
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! convert_arc {
+    ( $func_name:ident, $from:ty, $to:ty ) => {
+        fn $func_name<'a>(arc: $crate::mm::Arc<'a, $from>) -> $crate::mm::Arc<'a, $to> {
+            unsafe {
+                let data = $crate::mm::Arc::to_payload(arc);
+                $crate::mm::Arc::from_payload(data)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+#[macro_export]
+macro_rules! dyn_arc {
+    ( $func_name:ident, $trait:path ) => {
+        fn $func_name<'a, T: $trait>(arc: $crate::mm::Arc<'a, T>) -> $crate::mm::Arc<'a, dyn $trait + 'a> {
+            $crate::mm::Arc::to_dyn(arc)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::thread;
+    use core::sync::atomic::AtomicBool;
+
+    /* thread-safe allocator for the tests; counts live blocks */
+    struct StdAlloc {
+        live: AtomicUsize,
+    }
+
+    unsafe impl Allocator for StdAlloc {
+        unsafe fn alloc(
+            &self,
+            size: NonZeroUsize,
+            align: Pow2Usize
+        ) -> Result<NonNull<u8>, AllocError> {
+            let layout = std::alloc::Layout::from_size_align(size.get(), align.get()).unwrap();
+            self.live.fetch_add(1, Ordering::SeqCst);
+            NonNull::new(std::alloc::alloc(layout)).ok_or(AllocError::NotEnoughMemory)
+        }
+        unsafe fn free(
+            &self,
+            ptr: NonNull<u8>,
+            size: NonZeroUsize,
+            align: Pow2Usize
+        ) {
+            let layout = std::alloc::Layout::from_size_align(size.get(), align.get()).unwrap();
+            self.live.fetch_sub(1, Ordering::SeqCst);
+            std::alloc::dealloc(ptr.as_ptr(), layout);
+        }
+    }
+
+    fn std_alloc() -> StdAlloc {
+        StdAlloc { live: AtomicUsize::new(0) }
+    }
+
+    #[derive(Debug)]
+    struct SetOnDrop<'a>(&'a AtomicBool);
+
+    impl<'a> Drop for SetOnDrop<'a> {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn counts_and_drop() {
+        let a = std_alloc();
+        let dropped = AtomicBool::new(false);
+        let mut a1 = Arc::new(&a, SetOnDrop(&dropped)).unwrap();
+        assert!(Arc::get_mut(&mut a1).is_some());
+        let a2 = a1.clone();
+        let w = Arc::downgrade(&a1);
+        assert_eq!(Arc::strong_count(&a1), 2);
+        assert_eq!(Arc::weak_count(&a1), 1);
+        assert!(Arc::get_mut(&mut a1).is_none());
+        assert!(Arc::ptr_eq(&a1, &a2));
+        core::mem::drop(a1);
+        core::mem::drop(a2);
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(w.upgrade().is_none());
+        assert_eq!(w.weak_count(), 1);
+        assert_eq!(a.live.load(Ordering::SeqCst), 1);
+        core::mem::drop(w);
+        assert_eq!(a.live.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn shared_across_threads() {
+        let a = std_alloc();
+        let counter = Arc::new(&a, AtomicUsize::new(0)).unwrap();
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let c = counter.clone();
+                s.spawn(move || {
+                    for _ in 0..1000 {
+                        c.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.load(Ordering::SeqCst), 4000);
+        assert_eq!(Arc::strong_count(&counter), 1);
+        core::mem::drop(counter);
+        assert_eq!(a.live.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn try_unwrap() {
+        let a = std_alloc();
+        let a1 = Arc::new(&a, 5_u32).unwrap();
+        let a2 = a1.clone();
+        let a1 = Arc::try_unwrap(a1).unwrap_err();
+        core::mem::drop(a2);
+        assert_eq!(Arc::try_unwrap(a1).unwrap(), 5);
+        assert_eq!(a.live.load(Ordering::SeqCst), 0);
+    }
+
+    struct Node<'a> {
+        me: ArcWeak<'a, Node<'a>>,
+        id: u32,
+    }
+
+    #[test]
+    fn new_cyclic() {
+        let a = std_alloc();
+        {
+            let n = Arc::new_cyclic(&a, |w| {
+                assert!(w.upgrade().is_none());
+                Node { me: w.clone(), id: 3 }
+            }).unwrap();
+            assert_eq!(n.me.upgrade().unwrap().id, 3);
+            assert_eq!(Arc::weak_count(&n), 1);
+        }
+        assert_eq!(a.live.load(Ordering::SeqCst), 0);
+    }
+
+    dyn_arc!(make_fmt_debug_arc, fmt::Debug);
+
+    #[test]
+    fn dyn_arc_debug() {
+        let a = std_alloc();
+        let d: Arc<'_, dyn fmt::Debug> = make_fmt_debug_arc(Arc::new(&a, 123_u32).unwrap());
+        let s = std::format!("{:?}", d);
+        assert_eq!(s, "Arc[1+0]{123}");
+        core::mem::drop(d);
+        assert_eq!(a.live.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn debug_fmt() {
+        let a = std_alloc();
+        let a1 = Arc::new(&a, 7_u8).unwrap();
+        let _w = Arc::downgrade(&a1);
+        assert_eq!(std::format!("{:?}", a1), "Arc[1+1]{7}");
+    }
+}
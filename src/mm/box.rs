@@ -12,6 +12,7 @@ use crate::num::Pow2Usize;
 use super::Allocator;
 use super::AllocatorRef;
 use super::AllocError;
+use super::Vector;
 
 pub struct Box<'a, T: ?Sized> {
     allocator: AllocatorRef<'a>,
@@ -39,9 +40,90 @@ impl<'a, T: Sized> Box<'a, T> {
             Err(e) => Err((e, value))
         }
     }
+
+    /* moves the value out, freeing its memory */
+    pub fn into_inner(self) -> T {
+        let (allocator, ptr) = unsafe { self.to_parts() };
+        let value = unsafe { core::ptr::read(ptr.as_ptr()) };
+        let size = core::mem::size_of::<T>();
+        if size != 0 {
+            let size = NonZeroUsize::new(size).unwrap();
+            let align = Pow2Usize::new(core::mem::align_of::<T>()).unwrap();
+            unsafe { allocator.free(ptr.cast::<u8>(), size, align) };
+        }
+        value
+    }
+}
+
+/* Box<[T]> and Box<str> ***************************************************/
+/* fixed size allocations holding exactly the items they were built from;
+ * smaller than a Vector as they carry no capacity */
+impl<'a, T> Box<'a, [T]> {
+    pub fn from_slice(
+        allocator: AllocatorRef<'a>,
+        src: &[T],
+    ) -> Result<Self, AllocError>
+    where T: Copy {
+        let size = core::mem::size_of_val(src);
+        let ptr = if size == 0 {
+            NonNull::<T>::dangling()
+        } else {
+            let align = Pow2Usize::new(core::mem::align_of::<T>()).unwrap();
+            let ptr = unsafe {
+                allocator.alloc(NonZeroUsize::new(size).unwrap(), align)
+            }?.cast::<T>();
+            unsafe {
+                core::ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+            }
+            ptr
+        };
+        let ptr = core::ptr::slice_from_raw_parts_mut(ptr.as_ptr(), src.len());
+        Ok(Box { allocator, ptr: NonNull::new(ptr).unwrap() })
+    }
+
+    /* collects the items in a vector first so the allocation gets resized
+     * to fit the items once the iterator is exhausted */
+    pub fn from_iter<I: IntoIterator<Item = T>>(
+        allocator: AllocatorRef<'a>,
+        items: I,
+    ) -> Result<Self, AllocError> {
+        let items = items.into_iter();
+        let mut v = Vector::new(allocator);
+        v.reserve(items.size_hint().0)?;
+        for item in items {
+            v.push(item).map_err(|(e, _)| e)?;
+        }
+        v.into_boxed_slice().map_err(|(e, _)| e)
+    }
+}
+
+impl<'a> Box<'a, str> {
+    pub fn from_str(
+        allocator: AllocatorRef<'a>,
+        src: &str,
+    ) -> Result<Self, AllocError> {
+        Box::from_slice(allocator, src.as_bytes())
+            .map(|b| unsafe { b.into_boxed_str_unchecked() })
+    }
+}
+
+impl<'a> Box<'a, [u8]> {
+    /// # Safety
+    /// The bytes must be valid UTF-8.
+    pub unsafe fn into_boxed_str_unchecked(self) -> Box<'a, str> {
+        let (allocator, ptr) = self.to_parts();
+        Box::from_parts(allocator, NonNull::new_unchecked(ptr.as_ptr() as *mut str))
+    }
 }
 
 impl<'a, T: ?Sized> Box<'a, T> {
+    /* the value stays allocated (and is never dropped) for as long as the
+     * allocator is around */
+    pub fn leak(self) -> &'a mut T {
+        let (_, ptr) = unsafe { self.to_parts() };
+        unsafe { &mut *ptr.as_ptr() }
+    }
+
     pub unsafe fn to_parts(self) -> (AllocatorRef<'a>, NonNull<T>) {
         let x = core::mem::ManuallyDrop::new(self);
         (x.allocator, x.ptr)
@@ -90,7 +172,7 @@ impl<'a, T: ?Sized> Drop for Box<'a, T> {
         unsafe{ core::ptr::drop_in_place(self.ptr.as_ptr()); }
         if size != 0 {
             let size = NonZeroUsize::new(size).unwrap();
-            let align = Pow2Usize::new(core::mem::align_of_val(&v)).unwrap();
+            let align = Pow2Usize::new(core::mem::align_of_val(v)).unwrap();
             unsafe { self.allocator.free(self.ptr.cast::<u8>(), size, align) };
         }
     }
@@ -105,6 +187,18 @@ impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for Box<'a, T> {
     }
 }
 
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for Box<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.deref(), f)
+    }
+}
+
+impl<'a, 'b, T: ?Sized + PartialEq> PartialEq<Box<'b, T>> for Box<'a, T> {
+    fn eq(&self, other: &Box<'b, T>) -> bool {
+        self.deref() == other.deref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,6 +236,15 @@ mod tests {
         assert!(!a.is_in_use());
     }
 
+    #[test]
+    fn into_inner_frees_the_box() {
+        let mut buffer = [0u8; 16];
+        let a = SingleAlloc::new(&mut buffer);
+        let b = Box::new(a.to_ref(), 0xAA55u16).unwrap();
+        assert_eq!(b.into_inner(), 0xAA55u16);
+        assert!(!a.is_in_use());
+    }
+
     use core::sync::atomic::{ AtomicUsize, Ordering };
     struct IncOnDrop<'a> {
         drop_counter: &'a AtomicUsize,
@@ -258,4 +361,95 @@ mod tests {
         }
         assert!(!a.is_in_use());
     }
+
+    #[test]
+    fn boxed_slice_from_slice() {
+        let mut buffer = [0u8; 16];
+        let a = SingleAlloc::new(&mut buffer);
+        {
+            let b = Box::from_slice(a.to_ref(), &[1_u16, 2, 3]).unwrap();
+            assert_eq!(*b, [1, 2, 3]);
+            assert_eq!(core::mem::size_of_val(&*b), 6);
+            assert!(a.is_in_use());
+        }
+        assert!(!a.is_in_use());
+    }
+
+    #[test]
+    fn empty_boxed_slice_needs_no_allocation() {
+        let a = no_sup_allocator();
+        let b: Box<'_, [u32]> = Box::from_slice(a.to_ref(), &[]).unwrap();
+        assert!(b.is_empty());
+        let b: Box<'_, [u32]> = Box::from_iter(a.to_ref(), core::iter::empty()).unwrap();
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn boxed_slice_from_iter_fits_items() {
+        use crate::mm::BumpAllocator;
+        let mut buf = [0_u8; 64];
+        let ba = BumpAllocator::new(&mut buf);
+        {
+            let b = Box::from_iter(ba.to_ref(), (1..=5_u32).filter(|x| x % 2 == 1)).unwrap();
+            assert_eq!(*b, [1, 3, 5]);
+            assert_eq!(ba.space_left(), 64 - 12);
+        }
+        assert_eq!(ba.space_left(), 64);
+    }
+
+    #[test]
+    fn boxed_slice_drops_items() {
+        let drop_count = AtomicUsize::new(0);
+        let mut buffer = [0u8; 64];
+        let a = SingleAlloc::new(&mut buffer);
+        {
+            let items = (0..3).map(|_| IncOnDrop { drop_counter: &drop_count });
+            let b = Box::from_iter(a.to_ref(), items).unwrap();
+            assert_eq!(b.len(), 3);
+            assert_eq!(drop_count.load(Ordering::SeqCst), 0);
+        }
+        assert_eq!(drop_count.load(Ordering::SeqCst), 3);
+        assert!(!a.is_in_use());
+    }
+
+    #[test]
+    fn boxed_str() {
+        extern crate std;
+        use std::format;
+        let mut buffer = [0u8; 16];
+        let a = SingleAlloc::new(&mut buffer);
+        {
+            let b = Box::from_str(a.to_ref(), "token").unwrap();
+            assert_eq!(&*b, "token");
+            assert_eq!(format!("<{}>", b), "<token>");
+            let mut other_buf = [0u8; 8];
+            let other = SingleAlloc::new(&mut other_buf);
+            assert!(b == Box::from_str(other.to_ref(), "token").unwrap());
+        }
+        assert!(!a.is_in_use());
+    }
+
+    #[test]
+    fn vector_and_string_into_boxed() {
+        use crate::mm::BumpAllocator;
+        use crate::mm::Vector;
+        use crate::mm::String;
+        let mut buf = [0_u8; 64];
+        let ba = BumpAllocator::new(&mut buf);
+        let mut v = Vector::new(ba.to_ref());
+        v.push(7_u8).unwrap();
+        v.push(8_u8).unwrap();
+        v.push(9_u8).unwrap();
+        let b = v.into_boxed_slice().unwrap();
+        assert_eq!(*b, [7, 8, 9]);
+        assert_eq!(ba.space_left(), 64 - 3);
+        let mut s = String::new(ba.to_ref());
+        s.push_str("ab").unwrap();
+        let bs = s.into_boxed_str().unwrap();
+        assert_eq!(&*bs, "ab");
+        let mapped = Vector::map_slice(b"xyz");
+        let (e, mapped) = mapped.into_boxed_slice().unwrap_err();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(mapped.as_slice(), b"xyz");
+    }
 }
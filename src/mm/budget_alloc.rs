@@ -0,0 +1,122 @@
+use core::ptr::NonNull;
+use core::cell::Cell;
+
+use crate::num::NonZeroUsize;
+use crate::num::Pow2Usize;
+
+use super::Allocator;
+use super::AllocError;
+use super::AllocatorRef;
+
+/* BudgetAllocator **********************************************************/
+/* passes requests to another allocator as long as the bytes held stay
+ * within the budget; freed and shrunk blocks give their bytes back */
+pub struct BudgetAllocator<'a> {
+    inner: AllocatorRef<'a>,
+    budget: usize,
+    used: Cell<usize>,
+}
+
+impl<'a> BudgetAllocator<'a> {
+    pub fn new(inner: AllocatorRef<'a>, budget: usize) -> Self {
+        BudgetAllocator { inner, budget, used: Cell::new(0) }
+    }
+    pub fn get_budget(&self) -> usize {
+        self.budget
+    }
+    pub fn get_used(&self) -> usize {
+        self.used.get()
+    }
+    pub fn space_left(&self) -> usize {
+        self.budget - self.used.get()
+    }
+    fn reserve(&self, size: usize) -> Result<(), AllocError> {
+        if size > self.space_left() {
+            Err(AllocError::NotEnoughMemory)
+        } else {
+            self.used.set(self.used.get() + size);
+            Ok(())
+        }
+    }
+    fn release(&self, size: usize) {
+        self.used.set(self.used.get() - size);
+    }
+}
+
+unsafe impl<'a> Allocator for BudgetAllocator<'a> {
+    unsafe fn alloc(
+        &self,
+        size: NonZeroUsize,
+        align: Pow2Usize
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.reserve(size.get())?;
+        self.inner.alloc(size, align).inspect_err(|_| self.release(size.get()))
+    }
+    unsafe fn free(
+        &self,
+        ptr: NonNull<u8>,
+        size: NonZeroUsize,
+        align: Pow2Usize) {
+        self.inner.free(ptr, size, align);
+        self.release(size.get());
+    }
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        current_size: NonZeroUsize,
+        new_larger_size: NonZeroUsize,
+        align: Pow2Usize
+    ) -> Result<NonNull<u8>, AllocError> {
+        let extra = new_larger_size.get() - current_size.get();
+        self.reserve(extra)?;
+        self.inner.grow(ptr, current_size, new_larger_size, align)
+            .inspect_err(|_| self.release(extra))
+    }
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        current_size: NonZeroUsize,
+        new_smaller_size: NonZeroUsize,
+        align: Pow2Usize
+    ) -> Result<NonNull<u8>, AllocError> {
+        let p = self.inner.shrink(ptr, current_size, new_smaller_size, align)?;
+        self.release(current_size.get() - new_smaller_size.get());
+        Ok(p)
+    }
+    fn supports_contains(&self) -> bool {
+        self.inner.supports_contains()
+    }
+    fn contains(&self, ptr: NonNull<u8>) -> bool {
+        self.inner.contains(ptr)
+    }
+    fn name(&self) -> &'static str { "budget-allocator" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::BumpAllocator;
+    use crate::mm::Vector;
+
+    #[test]
+    fn budget_is_enforced_and_given_back() {
+        let mut buf = [0_u8; 0x400];
+        let bump = BumpAllocator::new(&mut buf);
+        let a = BudgetAllocator::new(bump.to_ref(), 0x20);
+        assert!(a.name().contains("budget"));
+        {
+            let mut v: Vector<'_, u8> = Vector::new(a.to_ref());
+            v.reserve(0x10).unwrap();
+            assert_eq!(a.get_used(), v.cap());
+            assert_eq!(v.reserve(0x40), Err(AllocError::NotEnoughMemory));
+            assert_eq!(a.get_used(), v.cap());
+            let b = a.to_ref().alloc_item([0_u8; 0x20]);
+            assert_eq!(b.unwrap_err().0, AllocError::NotEnoughMemory);
+            let b = a.to_ref().alloc_item(7_u8).unwrap();
+            assert_eq!(*b, 7);
+            assert_eq!(a.get_used(), v.cap() + 1);
+        }
+        assert_eq!(a.get_used(), 0);
+        assert_eq!(a.space_left(), a.get_budget());
+    }
+}
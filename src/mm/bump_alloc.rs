@@ -1,4 +1,4 @@
-use core::cell::UnsafeCell;
+use core::cell::Cell;
 use core::marker::PhantomData;
 
 use crate::num::NonZeroUsize;
@@ -9,45 +9,39 @@ use super::NonNull;
 use super::Allocator;
 use super::AllocError;
 
-struct BumpAllocatorState<'a> {
-    begin_addr: usize,
-    current_addr: usize,
-    end_addr: usize,
-    lifeline: PhantomData<&'a u8>,
-}
-
+/* BumpAllocator ************************************************************/
+/* hands out consecutive chunks of a borrowed buffer; only the last chunk can
+ * be freed/resized in place; the only mutable state is the used size kept in
+ * a Cell and pointers are derived from the buffer pointer so that allocating
+ * through several shared references stays sound */
 pub struct BumpAllocator<'a> {
-    state: UnsafeCell<BumpAllocatorState<'a>>
+    base: NonNull<u8>,
+    size: usize,
+    used: Cell<usize>,
+    lifeline: PhantomData<&'a mut [u8]>,
 }
 
 impl<'a> BumpAllocator<'a> {
     pub fn new(buffer: &'a mut [u8]) -> Self {
-        let b = buffer.as_ptr() as usize;
-        let e = b + buffer.len();
         BumpAllocator {
-            state: BumpAllocatorState {
-                begin_addr: b,
-                current_addr: b,
-                end_addr: e,
-                lifeline: PhantomData
-            }.into()
+            base: NonNull::new(buffer.as_mut_ptr()).unwrap(),
+            size: buffer.len(),
+            used: Cell::new(0),
+            lifeline: PhantomData,
         }
     }
+    fn offset_of(&self, ptr: NonNull<u8>) -> usize {
+        (ptr.as_ptr() as usize).wrapping_sub(self.base.as_ptr() as usize)
+    }
     fn is_last_allocation(
         &self,
         ptr: NonNull<u8>,
         size: NonZeroUsize
     ) -> bool {
-        let state: &'a BumpAllocatorState<'a> = unsafe {
-            &*(self.state.get() as *mut BumpAllocatorState<'a>)
-        };
-        state.current_addr == (ptr.as_ptr() as usize) + size.get()
+        self.contains(ptr) && self.offset_of(ptr) + size.get() == self.used.get()
     }
     pub fn space_left(&self) -> usize {
-        let state: &'a BumpAllocatorState<'a> = unsafe {
-            &*(self.state.get() as *mut BumpAllocatorState<'a>)
-        };
-        state.end_addr - state.current_addr
+        self.size - self.used.get()
     }
 }
 
@@ -57,14 +51,13 @@ unsafe impl<'a> Allocator for BumpAllocator<'a> {
         size: NonZeroUsize,
         align: Pow2Usize
     ) -> Result<NonNull<u8>, AllocError> {
-        let state: &'a mut BumpAllocatorState<'a> = &mut
-            *(self.state.get() as *mut BumpAllocatorState<'a>);
-        usize_align_up(state.current_addr, align)
-            .map_or(None, |v| v.checked_add(size.get()))
-            .map_or(None, |v| if v <= state.end_addr {
-                let addr = state.current_addr;
-                state.current_addr = v;
-                NonNull::new(addr as *mut u8)
+        let base_addr = self.base.as_ptr() as usize;
+        usize_align_up(base_addr + self.used.get(), align)
+            .map(|addr| addr - base_addr)
+            .and_then(|ofs| ofs.checked_add(size.get()).map(|end| (ofs, end)))
+            .and_then(|(ofs, end)| if end <= self.size {
+                self.used.set(end);
+                NonNull::new(self.base.as_ptr().add(ofs))
             } else { None })
             .ok_or(AllocError::NotEnoughMemory)
     }
@@ -75,9 +68,7 @@ unsafe impl<'a> Allocator for BumpAllocator<'a> {
         _align: Pow2Usize
     ) {
         if self.is_last_allocation(ptr, current_size) {
-            let state: &'a mut BumpAllocatorState<'a> = &mut
-                *(self.state.get() as *mut BumpAllocatorState<'a>);
-            state.current_addr -= current_size.get();
+            self.used.set(self.used.get() - current_size.get());
         }
     }
     unsafe fn grow(
@@ -89,11 +80,9 @@ unsafe impl<'a> Allocator for BumpAllocator<'a> {
     ) -> Result<NonNull<u8>, AllocError> {
         if self.is_last_allocation(ptr, current_size) &&
             align.is_non_null_ptr_aligned(ptr) {
-            let state: &'a mut BumpAllocatorState<'a> = &mut 
-                *(self.state.get() as *mut BumpAllocatorState<'a>);
             let extra_size = new_larger_size.get() - current_size.get();
-            if extra_size <= state.end_addr - state.current_addr {
-                state.current_addr += extra_size;
+            if extra_size <= self.space_left() {
+                self.used.set(self.used.get() + extra_size);
                 Ok(ptr)
             } else {
                 Err(AllocError::NotEnoughMemory)
@@ -115,20 +104,17 @@ unsafe impl<'a> Allocator for BumpAllocator<'a> {
             Err(AllocError::UnsupportedAlignment)
         } else {
             if self.is_last_allocation(ptr, current_size) {
-                let state: &'a mut BumpAllocatorState<'a> = &mut
-                    *(self.state.get() as *mut BumpAllocatorState<'a>);
-                state.current_addr -= current_size.get() - new_smaller_size.get();
+                self.used.set(
+                    self.used.get() - (current_size.get() - new_smaller_size.get()));
             }
             Ok(ptr)
         }
     }
     fn supports_contains(&self) -> bool { true }
     fn contains(&self, ptr: NonNull<u8>) -> bool {
-        let state: &'a BumpAllocatorState<'a> = unsafe {
-            &*(self.state.get() as *mut BumpAllocatorState<'a>)
-        };
         let addr = ptr.as_ptr() as usize;
-        state.begin_addr <= addr && addr < state.end_addr
+        let begin_addr = self.base.as_ptr() as usize;
+        begin_addr <= addr && addr < begin_addr + self.size
     }
     fn name(&self) -> &'static str { "bump-allocator" }
 }
@@ -167,6 +153,41 @@ mod tests {
             AllocError::NotEnoughMemory);
     }
 
+    #[test]
+    fn alloc_returns_aligned_address() {
+        let mut buffer = [0_u8; 32];
+        let a = BumpAllocator::new(&mut buffer);
+        let _p1 = unsafe {
+            a.alloc(NonZeroUsize::new(1).unwrap(), Pow2Usize::one())
+        }.unwrap();
+        let p2 = unsafe {
+            a.alloc(NonZeroUsize::new(4).unwrap(), Pow2Usize::new(8).unwrap())
+        }.unwrap();
+        assert_eq!((p2.as_ptr() as usize) & 7, 0);
+    }
+
+    #[test]
+    fn alignment_padding_goes_before_the_block() {
+        #[repr(align(16))]
+        struct Aligned([u8; 16]);
+        let mut buffer = Aligned([0_u8; 16]);
+        let base = buffer.0.as_mut_ptr();
+        let a = BumpAllocator::new(&mut buffer.0);
+        let p1 = unsafe {
+            a.alloc(NonZeroUsize::new(1).unwrap(), Pow2Usize::one())
+        }.unwrap();
+        assert_eq!(p1.as_ptr(), base);
+        let p2 = unsafe {
+            a.alloc(NonZeroUsize::new(8).unwrap(), Pow2Usize::new(8).unwrap())
+        }.unwrap();
+        assert_eq!(p2.as_ptr(), base.wrapping_add(8));
+        assert_eq!(
+            unsafe {
+                a.alloc(NonZeroUsize::new(1).unwrap(), Pow2Usize::one())
+            }.unwrap_err(),
+            AllocError::NotEnoughMemory);
+    }
+
     #[test]
     fn dropping_last_allocation_reclaims_memory() {
         let mut buffer = [0_u8; 1];
@@ -374,4 +395,59 @@ mod tests {
         assert!(!a.contains(NonNull::new(unsafe { b.offset(-1) }).unwrap()));
     }
 
+    #[test]
+    fn allocations_through_several_references_do_not_alias() {
+        let mut buffer = [0_u8; 16];
+        let a = BumpAllocator::new(&mut buffer);
+        let r1 = &a;
+        let r2 = &a;
+        let p1 = unsafe { r1.alloc(NonZeroUsize::new(4).unwrap(), Pow2Usize::one()) }.unwrap();
+        unsafe { p1.as_ptr().write_bytes(0x11, 4) };
+        let p2 = unsafe { r2.alloc(NonZeroUsize::new(4).unwrap(), Pow2Usize::one()) }.unwrap();
+        unsafe { p2.as_ptr().write_bytes(0x22, 4) };
+        let p3 = unsafe { r1.grow(p2, NonZeroUsize::new(4).unwrap(), NonZeroUsize::new(8).unwrap(), Pow2Usize::one()) }.unwrap();
+        assert_eq!(p3, p2);
+        unsafe { p3.as_ptr().add(4).write_bytes(0x33, 4) };
+        let s1 = unsafe { core::slice::from_raw_parts(p1.as_ptr(), 4) };
+        let s3 = unsafe { core::slice::from_raw_parts(p3.as_ptr(), 8) };
+        assert_eq!(s1, [0x11; 4]);
+        assert_eq!(s3, [0x22, 0x22, 0x22, 0x22, 0x33, 0x33, 0x33, 0x33]);
+        assert_eq!(r2.space_left(), 4);
+        unsafe { r2.free(p3, NonZeroUsize::new(8).unwrap(), Pow2Usize::one()) };
+        assert_eq!(r1.space_left(), 12);
+    }
+
+    #[test]
+    fn items_from_shared_allocator_refs_coexist() {
+        let mut buffer = [0_u8; 64];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut b1 = a.to_ref().alloc_item(1_u32).unwrap();
+        let mut b2 = a.to_ref().alloc_item(2_u32).unwrap();
+        *b1 += 10;
+        *b2 += 20;
+        assert_eq!((*b1, *b2), (11, 22));
+    }
+
+    #[test]
+    fn foreign_pointer_is_not_last_allocation() {
+        let mut buffer = [0_u8; 8];
+        let mut other = [0_u8; 8];
+        let a = BumpAllocator::new(&mut buffer);
+        let _p = unsafe { a.alloc(NonZeroUsize::new(4).unwrap(), Pow2Usize::one()) }.unwrap();
+        unsafe {
+            a.free(NonNull::new(other.as_mut_ptr()).unwrap(),
+                NonZeroUsize::new(4).unwrap(), Pow2Usize::one())
+        };
+        assert_eq!(a.space_left(), 4);
+    }
+
+    #[test]
+    fn empty_buffer_allocations_fail() {
+        let mut buffer = [0_u8; 0];
+        let a = BumpAllocator::new(&mut buffer);
+        assert_eq!(
+            unsafe { a.alloc(NonZeroUsize::new(1).unwrap(), Pow2Usize::one()) }.unwrap_err(),
+            AllocError::NotEnoughMemory);
+    }
+
 }
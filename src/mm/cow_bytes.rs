@@ -0,0 +1,140 @@
+use core::convert::AsRef;
+
+use super::AllocatorRef;
+use super::AllocError;
+use super::Vector;
+
+/* CowBytes *****************************************************************/
+/* byte buffer that starts either borrowed (for instance from a mapped file)
+ * or owned; it gets copied to an owned vector only when mutation is needed */
+#[derive(Debug)]
+pub enum CowBytes<'a> {
+    Borrowed(&'a [u8]),
+    Owned(Vector<'a, u8>),
+}
+
+impl<'a> CowBytes<'a> {
+
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        matches!(self, CowBytes::Owned(_))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            CowBytes::Borrowed(b) => b,
+            CowBytes::Owned(v) => v.as_slice(),
+        }
+    }
+
+    /* the owned vector, copying the borrowed bytes first if needed; on
+     * allocation failure the content stays borrowed */
+    pub fn to_mut(
+        &mut self,
+        allocator: AllocatorRef<'a>,
+    ) -> Result<&mut Vector<'a, u8>, AllocError> {
+        if let CowBytes::Borrowed(b) = self {
+            *self = CowBytes::Owned(Vector::from_slice(b, allocator)?);
+        }
+        match self {
+            CowBytes::Owned(v) => Ok(v),
+            CowBytes::Borrowed(_) => unreachable!(),
+        }
+    }
+
+    pub fn into_owned(
+        self,
+        allocator: AllocatorRef<'a>,
+    ) -> Result<Vector<'a, u8>, AllocError> {
+        match self {
+            CowBytes::Borrowed(b) => Vector::from_slice(b, allocator),
+            CowBytes::Owned(v) => Ok(v),
+        }
+    }
+
+}
+
+impl<'a> From<&'a [u8]> for CowBytes<'a> {
+    fn from(b: &'a [u8]) -> Self {
+        CowBytes::Borrowed(b)
+    }
+}
+
+impl<'a> From<Vector<'a, u8>> for CowBytes<'a> {
+    fn from(v: Vector<'a, u8>) -> Self {
+        CowBytes::Owned(v)
+    }
+}
+
+impl<'a> AsRef<[u8]> for CowBytes<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl<'a, 'b> PartialEq<CowBytes<'b>> for CowBytes<'a> {
+    fn eq(&self, other: &CowBytes<'b>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Allocator;
+    use super::super::BumpAllocator;
+    use super::super::no_sup_allocator;
+
+    #[test]
+    fn borrowed_needs_no_allocation() {
+        let data = [1_u8, 2, 3];
+        let c = CowBytes::from(&data[..]);
+        assert!(!c.is_owned());
+        assert_eq!(c.len(), 3);
+        assert_eq!(c.as_slice().as_ptr(), data.as_ptr());
+    }
+
+    #[test]
+    fn to_mut_copies_once() {
+        let data = [1_u8, 2, 3];
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut c = CowBytes::from(&data[..]);
+        c.to_mut(a.to_ref()).unwrap().push(4).unwrap();
+        assert!(c.is_owned());
+        let left = a.space_left();
+        c.to_mut(a.to_ref()).unwrap().as_mut_slice()[0] = 9;
+        assert_eq!(a.space_left(), left);
+        assert_eq!(c.as_slice(), [9, 2, 3, 4]);
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn to_mut_failure_keeps_borrowed_content() {
+        let data = [1_u8, 2, 3];
+        let a = no_sup_allocator();
+        let mut c = CowBytes::from(&data[..]);
+        assert_eq!(c.to_mut(a.to_ref()).unwrap_err(), AllocError::UnsupportedOperation);
+        assert!(!c.is_owned());
+        assert!(c == CowBytes::Borrowed(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn into_owned() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let c = CowBytes::Borrowed(b"abc");
+        assert_eq!(c.into_owned(a.to_ref()).unwrap().as_slice(), b"abc");
+        let v = Vector::from_slice(b"xyz", a.to_ref()).unwrap();
+        let c = CowBytes::from(v);
+        assert!(c.is_owned());
+        assert_eq!(c.into_owned(no_sup_allocator().to_ref()).unwrap().as_slice(), b"xyz");
+    }
+}
@@ -0,0 +1,397 @@
+use core::ptr::NonNull;
+use core::mem::MaybeUninit;
+
+use crate::num::NonZeroUsize;
+use crate::num::Pow2Usize;
+
+use super::Allocator;
+use super::AllocatorRef;
+use super::AllocError;
+
+/* Deque ********************************************************************/
+/* double-ended queue kept in a growable ring buffer; items occupy len slots
+ * starting at head and wrapping around to the start of the buffer */
+#[derive(Debug)]
+pub struct Deque<'a, T> {
+    ptr: NonNull<T>,
+    head: usize,
+    len: usize,
+    cap: usize,
+    allocator: AllocatorRef<'a>,
+}
+
+impl<'a, T> Deque<'a, T> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Deque<'a, T> {
+        if core::mem::size_of::<T>() == 0 {
+            panic!("zero sized types!");
+        }
+        Deque {
+            ptr: NonNull::dangling(),
+            head: 0,
+            len: 0,
+            cap: 0,
+            allocator,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn slot(&self, index: usize) -> usize {
+        let i = self.head + index;
+        if i >= self.cap { i - self.cap } else { i }
+    }
+
+    fn item_ptr(&self, index: usize) -> *mut T {
+        unsafe { self.ptr.as_ptr().add(self.slot(index)) }
+    }
+
+    pub fn reserve(&mut self, count: usize) -> Result<(), AllocError> {
+        let item_size = core::mem::size_of::<T>();
+        let max_cap = usize::MAX / item_size;
+        if count > max_cap - self.len {
+            return Err(AllocError::UnsupportedSize);
+        }
+        let len_needed = self.len + count;
+        if len_needed <= self.cap {
+            return Ok(());
+        }
+        let mut cap_to_try = Pow2Usize::from_smaller_or_equal_usize(len_needed)
+            .map(|x| core::cmp::min(x.get(), max_cap)).unwrap_or(len_needed);
+        let old_cap = self.cap;
+        loop {
+            match unsafe { self.allocator.alloc_or_grow(
+                    self.ptr.cast::<u8>(),
+                    old_cap * item_size,
+                    NonZeroUsize::new(cap_to_try * item_size).unwrap(),
+                    Pow2Usize::new(core::mem::align_of::<T>()).unwrap())
+            } {
+                Ok(new_ptr) => {
+                    self.ptr = new_ptr.cast::<T>();
+                    self.cap = cap_to_try;
+                    break;
+                },
+                Err(e) => {
+                    if cap_to_try == len_needed {
+                        return Err(e);
+                    }
+                    cap_to_try = len_needed;
+                }
+            }
+        }
+        // items that wrapped around in the old buffer go after the ones at
+        // its end if the space added fits them; otherwise the items from
+        // head to the old end move to the end of the new buffer
+        if self.head + self.len > old_cap {
+            let wrapped = self.head + self.len - old_cap;
+            let head_len = old_cap - self.head;
+            unsafe {
+                if self.cap - old_cap >= wrapped {
+                    core::ptr::copy_nonoverlapping(
+                        self.ptr.as_ptr(),
+                        self.ptr.as_ptr().add(old_cap),
+                        wrapped);
+                } else {
+                    let new_head = self.cap - head_len;
+                    core::ptr::copy(
+                        self.ptr.as_ptr().add(self.head),
+                        self.ptr.as_ptr().add(new_head),
+                        head_len);
+                    self.head = new_head;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn push_back(&mut self, v: T) -> Result<(), (AllocError, T)> {
+        if let Err(e) = self.reserve(1) {
+            return Err((e, v));
+        }
+        unsafe { core::ptr::write(self.item_ptr(self.len), v); }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn push_front(&mut self, v: T) -> Result<(), (AllocError, T)> {
+        if let Err(e) = self.reserve(1) {
+            return Err((e, v));
+        }
+        self.head = if self.head == 0 { self.cap - 1 } else { self.head - 1 };
+        self.len += 1;
+        unsafe { core::ptr::write(self.item_ptr(0), v); }
+        Ok(())
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let v = unsafe { core::ptr::read(self.item_ptr(0)) };
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(v)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { core::ptr::read(self.item_ptr(self.len)) })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            Some(unsafe { &*self.item_ptr(index) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len {
+            Some(unsafe { &mut *self.item_ptr(index) })
+        } else {
+            None
+        }
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        if self.len == 0 { None } else { self.get(self.len - 1) }
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(0)
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        if self.len == 0 { None } else { self.get_mut(self.len - 1) }
+    }
+
+    /* the items as 2 slices: the ones from head to the end of the buffer
+     * and then the ones that wrapped around */
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let first_len = core::cmp::min(self.len, self.cap - self.head);
+        unsafe {
+            (core::slice::from_raw_parts(self.ptr.as_ptr().add(self.head), first_len),
+             core::slice::from_raw_parts(self.ptr.as_ptr(), self.len - first_len))
+        }
+    }
+
+    /* moves items so that they are all in one slice */
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        if self.head + self.len > self.cap {
+            let buf = unsafe {
+                core::slice::from_raw_parts_mut(
+                    self.ptr.as_ptr() as *mut MaybeUninit<T>, self.cap)
+            };
+            buf.rotate_left(self.head);
+            self.head = 0;
+        }
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                self.ptr.as_ptr().add(self.head), self.len)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_back().is_some() { }
+        self.head = 0;
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        let (a, b) = self.as_slices();
+        a.iter().chain(b.iter())
+    }
+
+}
+
+impl<'a, T> Drop for Deque<'a, T> {
+    fn drop(&mut self) {
+        self.clear();
+        if self.cap != 0 {
+            unsafe {
+                self.allocator.free(
+                    self.ptr.cast::<u8>(),
+                    NonZeroUsize::new(core::mem::size_of::<T>() * self.cap).unwrap(),
+                    Pow2Usize::new(core::mem::align_of::<T>()).unwrap()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::no_sup_allocator;
+    use super::super::BumpAllocator;
+
+    #[test]
+    fn new_deque_is_empty() {
+        let a = no_sup_allocator();
+        let mut d: Deque<'_, u16> = Deque::new(a.to_ref());
+        assert!(d.is_empty());
+        assert!(d.pop_front().is_none());
+        assert!(d.pop_back().is_none());
+        assert!(d.front().is_none());
+        assert!(d.back().is_none());
+    }
+
+    #[test]
+    fn failed_push_returns_original_value() {
+        let a = no_sup_allocator();
+        let mut d: Deque<'_, u16> = Deque::new(a.to_ref());
+        assert_eq!(d.push_back(1).unwrap_err(), (AllocError::UnsupportedOperation, 1));
+        assert_eq!(d.push_front(2).unwrap_err(), (AllocError::UnsupportedOperation, 2));
+    }
+
+    #[test]
+    fn fifo_order() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut d = Deque::new(a.to_ref());
+        for i in 0..10_u32 {
+            d.push_back(i).unwrap();
+            if i % 3 == 2 {
+                assert_eq!(d.pop_front(), Some(i / 3));
+            }
+        }
+        let mut expected = 3;
+        while let Some(v) = d.pop_front() {
+            assert_eq!(v, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 10);
+    }
+
+    #[test]
+    fn push_front_and_back() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut d = Deque::new(a.to_ref());
+        d.push_back(2_u8).unwrap();
+        d.push_front(1).unwrap();
+        d.push_back(3).unwrap();
+        d.push_front(0).unwrap();
+        assert_eq!(d.len(), 4);
+        assert_eq!(d.front(), Some(&0));
+        assert_eq!(d.back(), Some(&3));
+        *d.front_mut().unwrap() = 10;
+        *d.back_mut().unwrap() = 13;
+        assert_eq!(d.get(2), Some(&2));
+        assert!(d.get(4).is_none());
+        assert_eq!(d.pop_back(), Some(13));
+        assert_eq!(d.pop_front(), Some(10));
+        assert_eq!(d.iter().copied().sum::<u8>(), 3);
+    }
+
+    #[test]
+    fn grow_while_wrapped_keeps_order() {
+        let mut buf = [0_u8; 0x200];
+        let a = BumpAllocator::new(&mut buf);
+        let mut d = Deque::new(a.to_ref());
+        d.reserve(4).unwrap();
+        for i in 0..4_u32 {
+            d.push_back(i).unwrap();
+        }
+        d.pop_front();
+        d.pop_front();
+        d.push_back(4).unwrap();
+        d.push_back(5).unwrap(); // wrapped now
+        assert_eq!(d.cap(), 4);
+        d.push_back(6).unwrap();
+        assert!(d.cap() > 4);
+        let mut expected = 2;
+        for v in d.iter() {
+            assert_eq!(*v, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 7);
+    }
+
+    #[test]
+    fn grow_to_tight_capacity_while_wrapped() {
+        use super::super::BudgetAllocator;
+        let mut buf = [0xEE_u8; 0x20];
+        {
+            let a = BumpAllocator::new(&mut buf);
+            /* the budget makes the power of 2 capacities fail */
+            let b = BudgetAllocator::new(a.to_ref(), 6);
+            let mut d = Deque::new(b.to_ref());
+            d.reserve(5).unwrap();
+            assert_eq!(d.cap(), 5);
+            for i in 0..5_u8 {
+                d.push_back(i).unwrap();
+            }
+            for _ in 0..3 {
+                d.pop_front();
+            }
+            for i in 5..8_u8 {
+                d.push_back(i).unwrap();
+            }
+            d.push_back(8).unwrap();
+            assert_eq!(d.cap(), 6);
+            assert!(d.iter().copied().eq(3..9));
+        }
+        assert!(buf[6..].iter().all(|&b| b == 0xEE));
+    }
+
+    #[test]
+    fn make_contiguous_unwraps() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut d = Deque::new(a.to_ref());
+        d.reserve(8).unwrap();
+        for i in 0..8_u16 {
+            d.push_back(i).unwrap();
+        }
+        for _ in 0..5 {
+            d.pop_front();
+        }
+        for i in 8..12_u16 {
+            d.push_back(i).unwrap();
+        }
+        let (s1, s2) = d.as_slices();
+        assert_eq!(s1, [5, 6, 7]);
+        assert_eq!(s2, [8, 9, 10, 11]);
+        assert_eq!(d.make_contiguous(), [5, 6, 7, 8, 9, 10, 11]);
+        let (s1, s2) = d.as_slices();
+        assert_eq!(s1.len(), 7);
+        assert!(s2.is_empty());
+        assert_eq!(d.pop_back(), Some(11));
+        assert_eq!(d.pop_front(), Some(5));
+    }
+
+    #[test]
+    fn drop_releases_items_and_buffer() {
+        extern crate std;
+        use std::rc::Rc as StdRc;
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let tracker = StdRc::new(());
+        {
+            let mut d = Deque::new(a.to_ref());
+            d.push_back(tracker.clone()).unwrap();
+            d.push_front(tracker.clone()).unwrap();
+            assert_eq!(StdRc::strong_count(&tracker), 3);
+        }
+        assert_eq!(StdRc::strong_count(&tracker), 1);
+        assert_eq!(a.space_left(), 0x100);
+    }
+}
@@ -0,0 +1,137 @@
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use crate::num::NonZeroUsize;
+use crate::num::Pow2Usize;
+use crate::num::usize_align_up;
+
+use super::NonNull;
+use super::Allocator;
+use super::AllocError;
+
+/* FragmentingAllocator *****************************************************/
+/* test double handing out chunks of a borrowed buffer with a gap before each
+ * of them, so no two blocks are ever adjacent, and refusing every grow, so
+ * containers have to move their data to a new block; freed space is never
+ * reused */
+pub struct FragmentingAllocator<'a> {
+    base: NonNull<u8>,
+    size: usize,
+    gap: usize,
+    used: Cell<usize>,
+    live_blocks: Cell<usize>,
+    refused_grows: Cell<usize>,
+    lifeline: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> FragmentingAllocator<'a> {
+    pub fn new(buffer: &'a mut [u8], gap: NonZeroUsize) -> Self {
+        FragmentingAllocator {
+            base: NonNull::new(buffer.as_mut_ptr()).unwrap(),
+            size: buffer.len(),
+            gap: gap.get(),
+            used: Cell::new(0),
+            live_blocks: Cell::new(0),
+            refused_grows: Cell::new(0),
+            lifeline: PhantomData,
+        }
+    }
+    pub fn live_blocks(&self) -> usize {
+        self.live_blocks.get()
+    }
+    pub fn refused_grows(&self) -> usize {
+        self.refused_grows.get()
+    }
+    pub fn space_left(&self) -> usize {
+        self.size - self.used.get()
+    }
+}
+
+unsafe impl<'a> Allocator for FragmentingAllocator<'a> {
+    unsafe fn alloc(
+        &self,
+        size: NonZeroUsize,
+        align: Pow2Usize
+    ) -> Result<NonNull<u8>, AllocError> {
+        let base_addr = self.base.as_ptr() as usize;
+        let ptr = (base_addr + self.used.get()).checked_add(self.gap)
+            .and_then(|addr| usize_align_up(addr, align))
+            .map(|addr| addr - base_addr)
+            .and_then(|ofs| ofs.checked_add(size.get()).map(|end| (ofs, end)))
+            .and_then(|(ofs, end)| if end <= self.size {
+                self.used.set(end);
+                NonNull::new(self.base.as_ptr().add(ofs))
+            } else { None })
+            .ok_or(AllocError::NotEnoughMemory)?;
+        self.live_blocks.set(self.live_blocks.get() + 1);
+        Ok(ptr)
+    }
+    unsafe fn free(
+        &self,
+        ptr: NonNull<u8>,
+        _current_size: NonZeroUsize,
+        _align: Pow2Usize
+    ) {
+        if !self.contains(ptr) || self.live_blocks.get() == 0 {
+            panic!("bad pointer");
+        }
+        self.live_blocks.set(self.live_blocks.get() - 1);
+    }
+    unsafe fn grow(
+        &self,
+        _ptr: NonNull<u8>,
+        _current_size: NonZeroUsize,
+        _new_larger_size: NonZeroUsize,
+        _align: Pow2Usize
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.refused_grows.set(self.refused_grows.get() + 1);
+        Err(AllocError::OperationFailed)
+    }
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        _current_size: NonZeroUsize,
+        _new_smaller_size: NonZeroUsize,
+        align: Pow2Usize
+    ) -> Result<NonNull<u8>, AllocError> {
+        if align.is_non_null_ptr_aligned(ptr) {
+            Ok(ptr)
+        } else {
+            Err(AllocError::UnsupportedAlignment)
+        }
+    }
+    fn supports_contains(&self) -> bool { true }
+    fn contains(&self, ptr: NonNull<u8>) -> bool {
+        let addr = ptr.as_ptr() as usize;
+        let begin_addr = self.base.as_ptr() as usize;
+        begin_addr <= addr && addr < begin_addr + self.size
+    }
+    fn name(&self) -> &'static str { "fragmenting-allocator" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_apart_and_grow_fails() {
+        let mut buffer = [0_u8; 64];
+        let a = FragmentingAllocator::new(&mut buffer, NonZeroUsize::new(8).unwrap());
+        let one = Pow2Usize::one();
+        let four = NonZeroUsize::new(4).unwrap();
+        let p = unsafe { a.alloc(four, one) }.unwrap();
+        let q = unsafe { a.alloc(four, one) }.unwrap();
+        assert_eq!(q.as_ptr() as usize - p.as_ptr() as usize, 12);
+        assert_eq!(
+            unsafe { a.grow(p, four, NonZeroUsize::new(5).unwrap(), one) },
+            Err(AllocError::OperationFailed));
+        assert_eq!(a.refused_grows(), 1);
+        unsafe { a.free(p, four, one); }
+        assert_eq!(a.live_blocks(), 1);
+        assert_eq!(a.space_left(), 40);
+        assert_eq!(
+            unsafe { a.alloc(NonZeroUsize::new(33).unwrap(), one) },
+            Err(AllocError::NotEnoughMemory));
+        assert!(a.name().contains("fragmenting"));
+    }
+}
@@ -0,0 +1,739 @@
+use core::borrow::Borrow;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::marker::PhantomData;
+
+use super::AllocatorRef;
+use super::AllocError;
+use super::Vector;
+
+/* FnvHasher ****************************************************************/
+/* 64-bit FNV-1a; not DoS resistant but small, fast and needs no state
+ * besides the running hash */
+#[derive(Copy, Clone, Debug)]
+pub struct FnvHasher(u64);
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+impl FnvHasher {
+    pub fn new() -> Self {
+        FnvHasher(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher::new()
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut h = self.0;
+        for &b in bytes {
+            h ^= b as u64;
+            h = h.wrapping_mul(FNV_PRIME);
+        }
+        self.0 = h;
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn hash_of<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut h = FnvHasher::new();
+    key.hash(&mut h);
+    h.finish()
+}
+
+/* HashMap ******************************************************************/
+#[derive(Debug)]
+enum Slot<K, V> {
+    Empty,
+    Deleted,
+    Full(K, V),
+}
+
+const MIN_CAP: usize = 8;
+
+fn free_slot_in<K: Hash, V>(slots: &[Slot<K, V>], key: &K) -> usize {
+    let mask = slots.len() - 1;
+    let mut i = (hash_of(key) as usize) & mask;
+    loop {
+        match &slots[i] {
+            Slot::Full(_, _) => i = (i + 1) & mask,
+            _ => return i,
+        }
+    }
+}
+
+/* open addressing with linear probing; the slot count is always a power of
+ * 2 and at most 3/4 of the slots are in use (counting deleted markers) so
+ * that lookups always end on an empty slot;
+ * iteration goes in slot order, which depends on the hashes and the history
+ * of the map, unless the map was created with_insertion_order(): then the
+ * indexes of used slots are also kept in insertion order, making iteration
+ * deterministic at the cost of a linear time remove */
+#[derive(Debug)]
+pub struct HashMap<'a, K, V> {
+    slots: Vector<'a, Slot<K, V>>,
+    len: usize,
+    deleted: usize,
+    order: Option<Vector<'a, usize>>,
+}
+
+impl<'a, K: Hash + Eq, V> HashMap<'a, K, V> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        HashMap {
+            slots: Vector::new(allocator),
+            len: 0,
+            deleted: 0,
+            order: None,
+        }
+    }
+
+    /* iterating yields items in the order their keys were first inserted;
+     * replacing a value keeps the position, removing and inserting again
+     * moves the key to the end */
+    pub fn with_insertion_order(allocator: AllocatorRef<'a>) -> Self {
+        HashMap {
+            slots: Vector::new(allocator),
+            len: 0,
+            deleted: 0,
+            order: Some(Vector::new(allocator)),
+        }
+    }
+
+    pub fn allocator(&self) -> AllocatorRef<'a> {
+        self.slots.allocator()
+    }
+
+    pub fn is_insertion_ordered(&self) -> bool {
+        self.order.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where K: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let slots = self.slots.as_slice();
+        let mut i = (hash_of(key) as usize) & self.mask();
+        loop {
+            match &slots[i] {
+                Slot::Empty => return None,
+                Slot::Full(k, _) if k.borrow() == key => return Some(i),
+                _ => i = (i + 1) & self.mask(),
+            }
+        }
+    }
+
+    fn free_slot_for(&self, key: &K) -> usize {
+        free_slot_in(self.slots.as_slice(), key)
+    }
+
+    fn rehash(&mut self, new_cap: usize) -> Result<(), AllocError> {
+        let mut slots: Vector<'a, Slot<K, V>> = Vector::new(self.slots.allocator());
+        slots.reserve(new_cap)?;
+        for _ in 0..new_cap {
+            slots.push(Slot::Empty).map_err(|_| ()).unwrap();
+        }
+        let mut old = core::mem::replace(&mut self.slots, slots);
+        self.deleted = 0;
+        match &mut self.order {
+            None => while let Some(slot) = old.pop() {
+                if let Slot::Full(k, v) = slot {
+                    let i = free_slot_in(self.slots.as_slice(), &k);
+                    self.slots.as_mut_slice()[i] = Slot::Full(k, v);
+                }
+            },
+            Some(order) => for index in order.as_mut_slice() {
+                let slot = core::mem::replace(
+                    &mut old.as_mut_slice()[*index], Slot::Empty);
+                if let Slot::Full(k, v) = slot {
+                    let i = free_slot_in(self.slots.as_slice(), &k);
+                    self.slots.as_mut_slice()[i] = Slot::Full(k, v);
+                    *index = i;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /* count of items the map can hold before it has to allocate */
+    pub fn capacity(&self) -> usize {
+        (self.slots.len() * 3 / 4).saturating_sub(self.deleted)
+    }
+
+    /* bytes allocated for the table itself; keys and values that own
+     * memory are not looked into */
+    pub fn table_size(&self) -> usize {
+        self.slots.cap() * core::mem::size_of::<Slot<K, V>>()
+            + self.order.as_ref().map_or(0, |o| o.cap() * core::mem::size_of::<usize>())
+    }
+
+    /* makes sure that additional items can be added without allocating;
+     * when the table grows it is sized to be at most half full */
+    pub fn reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        if let Some(order) = &mut self.order {
+            order.reserve(additional)?;
+        }
+        let needed = self.len.checked_add(additional)
+            .ok_or(AllocError::UnsupportedSize)?;
+        let cap = self.slots.len();
+        if needed.saturating_add(self.deleted).saturating_mul(4) <= cap * 3 {
+            return Ok(());
+        }
+        let mut new_cap = if cap == 0 { MIN_CAP } else { cap };
+        while needed.checked_mul(2).ok_or(AllocError::UnsupportedSize)? > new_cap {
+            new_cap = new_cap.checked_mul(2).ok_or(AllocError::UnsupportedSize)?;
+        }
+        self.rehash(new_cap) // same size if there were just too many deleted markers
+    }
+
+    pub fn insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, (AllocError, (K, V))> {
+        if let Some(i) = self.find(&key) {
+            if let Slot::Full(_, v) = &mut self.slots.as_mut_slice()[i] {
+                return Ok(Some(core::mem::replace(v, value)));
+            }
+            unreachable!();
+        }
+        if let Err(e) = self.reserve(1) {
+            return Err((e, (key, value)));
+        }
+        let i = self.free_slot_for(&key);
+        self.place(i, key, value);
+        Ok(None)
+    }
+
+    fn place(&mut self, i: usize, key: K, value: V) -> &mut V {
+        if let Some(order) = &mut self.order {
+            order.push(i).map_err(|_| ()).unwrap(); // reserved beforehand
+        }
+        let slot = &mut self.slots.as_mut_slice()[i];
+        if let Slot::Deleted = slot {
+            self.deleted -= 1;
+        }
+        *slot = Slot::Full(key, value);
+        self.len += 1;
+        match slot {
+            Slot::Full(_, v) => v,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where K: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        let i = self.find(key)?;
+        match &self.slots.as_slice()[i] {
+            Slot::Full(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where K: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        let i = self.find(key)?;
+        match &mut self.slots.as_mut_slice()[i] {
+            Slot::Full(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where K: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        self.find(key).is_some()
+    }
+
+    fn take(&mut self, i: usize) -> (K, V) {
+        let slot = core::mem::replace(&mut self.slots.as_mut_slice()[i], Slot::Deleted);
+        self.len -= 1;
+        self.deleted += 1;
+        if let Some(order) = &mut self.order {
+            let pos = order.as_slice().iter().position(|&x| x == i).unwrap();
+            order.remove(pos);
+        }
+        match slot {
+            Slot::Full(k, v) => (k, v),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where K: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        let i = self.find(key)?;
+        Some(self.take(i))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where K: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.slots.as_mut_slice() {
+            *slot = Slot::Empty;
+        }
+        self.len = 0;
+        self.deleted = 0;
+        if let Some(order) = &mut self.order {
+            order.truncate(0);
+        }
+    }
+
+    /* the entry for the given key; room for a new item is reserved upfront
+     * so that filling in a vacant entry cannot fail */
+    pub fn entry<'m>(
+        &'m mut self,
+        key: K,
+    ) -> Result<Entry<'m, 'a, K, V>, (AllocError, K)> {
+        if let Some(index) = self.find(&key) {
+            return Ok(Entry::Occupied(OccupiedEntry { map: self, index }));
+        }
+        if let Err(e) = self.reserve(1) {
+            return Err((e, key));
+        }
+        let index = self.free_slot_for(&key);
+        Ok(Entry::Vacant(VacantEntry { map: self, key, index }))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.slots.as_slice(),
+            order: self.order.as_ref().map(|o| o.as_slice().iter()),
+            next_slot: 0,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let slots = self.slots.as_mut_slice();
+        IterMut {
+            base: slots.as_mut_ptr(),
+            slot_count: slots.len(),
+            order: self.order.as_ref().map(|o| o.as_slice().iter()),
+            next_slot: 0,
+            _slots: PhantomData,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+}
+
+impl<'a, 'm, K: Hash + Eq, V> IntoIterator for &'m HashMap<'a, K, V> {
+    type Item = (&'m K, &'m V);
+    type IntoIter = Iter<'m, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/* Iter *********************************************************************/
+/* index of the next used slot, either from the insertion order or by
+ * scanning the slots */
+fn next_full_slot(
+    order: &mut Option<core::slice::Iter<'_, usize>>,
+    next_slot: &mut usize,
+    slot_count: usize,
+    is_full: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    if let Some(o) = order {
+        return o.next().copied();
+    }
+    while *next_slot < slot_count {
+        let i = *next_slot;
+        *next_slot += 1;
+        if is_full(i) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+pub struct Iter<'m, K, V> {
+    slots: &'m [Slot<K, V>],
+    order: Option<core::slice::Iter<'m, usize>>,
+    next_slot: usize,
+}
+
+impl<'m, K, V> Iterator for Iter<'m, K, V> {
+    type Item = (&'m K, &'m V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let slots = self.slots;
+        let i = next_full_slot(
+            &mut self.order, &mut self.next_slot, slots.len(),
+            |i| matches!(slots[i], Slot::Full(_, _)))?;
+        match &slots[i] {
+            Slot::Full(k, v) => Some((k, v)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub struct IterMut<'m, K, V> {
+    base: *mut Slot<K, V>,
+    slot_count: usize,
+    order: Option<core::slice::Iter<'m, usize>>,
+    next_slot: usize,
+    _slots: PhantomData<&'m mut [Slot<K, V>]>,
+}
+
+impl<'m, K, V> Iterator for IterMut<'m, K, V> {
+    type Item = (&'m K, &'m mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let base = self.base;
+        let i = next_full_slot(
+            &mut self.order, &mut self.next_slot, self.slot_count,
+            |i| matches!(unsafe { &*base.add(i) }, Slot::Full(_, _)))?;
+        /* each used slot index comes up once, so the references handed out
+         * never alias */
+        match unsafe { &mut *base.add(i) } {
+            Slot::Full(k, v) => Some((&*k, v)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/* Entry ********************************************************************/
+pub enum Entry<'m, 'a, K, V> {
+    Occupied(OccupiedEntry<'m, 'a, K, V>),
+    Vacant(VacantEntry<'m, 'a, K, V>),
+}
+
+pub struct OccupiedEntry<'m, 'a, K, V> {
+    map: &'m mut HashMap<'a, K, V>,
+    index: usize,
+}
+
+pub struct VacantEntry<'m, 'a, K, V> {
+    map: &'m mut HashMap<'a, K, V>,
+    key: K,
+    index: usize,
+}
+
+impl<'m, 'a, K: Hash + Eq, V> Entry<'m, 'a, K, V> {
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(o) => o.key(),
+            Entry::Vacant(v) => &v.key,
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'m mut V {
+        match self {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => v.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'m mut V {
+        match self {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => v.insert(f()),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(o) = &mut self {
+            f(o.get_mut());
+        }
+        self
+    }
+
+}
+
+impl<'m, 'a, K: Hash + Eq, V> OccupiedEntry<'m, 'a, K, V> {
+
+    fn parts(&self) -> (&K, &V) {
+        match &self.map.slots.as_slice()[self.index] {
+            Slot::Full(k, v) => (k, v),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        self.parts().0
+    }
+
+    pub fn get(&self) -> &V {
+        self.parts().1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        match &mut self.map.slots.as_mut_slice()[self.index] {
+            Slot::Full(_, v) => v,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn into_mut(self) -> &'m mut V {
+        match &mut self.map.slots.as_mut_slice()[self.index] {
+            Slot::Full(_, v) => v,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.take(self.index)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+}
+
+impl<'m, 'a, K: Hash + Eq, V> VacantEntry<'m, 'a, K, V> {
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'m mut V {
+        self.map.place(self.index, self.key, value)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::no_sup_allocator;
+
+    #[test]
+    fn fnv_known_values() {
+        let mut h = FnvHasher::new();
+        h.write(b"");
+        assert_eq!(h.finish(), 0xCBF29CE484222325);
+        let mut h = FnvHasher::default();
+        h.write(b"a");
+        assert_eq!(h.finish(), 0xAF63DC4C8601EC8C);
+    }
+
+    #[test]
+    fn empty_map() {
+        let a = no_sup_allocator();
+        let m: HashMap<'_, u32, u32> = HashMap::new(a.to_ref());
+        assert!(m.is_empty());
+        assert!(m.get(&1).is_none());
+        assert_eq!(m.iter().count(), 0);
+    }
+
+    #[test]
+    fn insert_failure_returns_key_and_value() {
+        let a = no_sup_allocator();
+        let mut m: HashMap<'_, u32, &str> = HashMap::new(a.to_ref());
+        let (e, (k, v)) = m.insert(5, "five").unwrap_err();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(k, 5);
+        assert_eq!(v, "five");
+    }
+
+    #[test]
+    fn insert_get_replace() {
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        for i in 0..100_u32 {
+            assert_eq!(m.insert(i, i * 10).unwrap(), None);
+        }
+        assert_eq!(m.len(), 100);
+        for i in 0..100_u32 {
+            assert_eq!(m.get(&i), Some(&(i * 10)));
+        }
+        assert_eq!(m.insert(7, 777).unwrap(), Some(70));
+        assert_eq!(m.len(), 100);
+        assert!(!m.contains_key(&100));
+    }
+
+    #[test]
+    fn borrowed_key_lookup() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        m.insert("len", 1).unwrap();
+        m.insert("size", 2).unwrap();
+        *m.get_mut("size").unwrap() += 40;
+        assert_eq!(m.get("size"), Some(&42));
+        assert_eq!(m.get("count"), None);
+    }
+
+    #[test]
+    fn remove_and_reinsert() {
+        let mut buf = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        for round in 0..20_u32 {
+            for i in 0..5_u32 {
+                m.insert(i + round, i).unwrap();
+            }
+            for i in 0..5_u32 {
+                assert_eq!(m.remove(&(i + round)), Some(i));
+            }
+            assert!(m.is_empty());
+        }
+        assert_eq!(m.remove(&3), None);
+        m.insert(3, 33).unwrap();
+        assert_eq!(m.remove_entry(&3), Some((3, 33)));
+    }
+
+    #[test]
+    fn clear_empties_map() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        m.insert(1_u8, 'a').unwrap();
+        m.insert(2_u8, 'b').unwrap();
+        m.clear();
+        assert!(m.is_empty());
+        assert!(m.get(&1).is_none());
+        m.insert(1_u8, 'c').unwrap();
+        assert_eq!(m.get(&1), Some(&'c'));
+    }
+
+    #[test]
+    fn iterate_items() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        for i in 1..=10_u32 {
+            m.insert(i, i * i).unwrap();
+        }
+        assert_eq!(m.keys().sum::<u32>(), 55);
+        assert_eq!(m.values().sum::<u32>(), 385);
+        for (_, v) in m.iter_mut() {
+            *v += 1;
+        }
+        let mut total = 0;
+        for (_, v) in &m {
+            total += *v;
+        }
+        assert_eq!(total, 395);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        for w in &["a", "b", "a", "c", "a", "b"] {
+            *m.entry(*w).unwrap().or_insert(0) += 1;
+        }
+        assert_eq!(m.get("a"), Some(&3));
+        assert_eq!(m.get("b"), Some(&2));
+        assert_eq!(m.get("c"), Some(&1));
+
+        m.entry("c").unwrap().and_modify(|v| *v = 10).or_insert(0);
+        assert_eq!(m.get("c"), Some(&10));
+        assert_eq!(*m.entry("d").unwrap().or_insert_with(|| 4), 4);
+
+        match m.entry("b").unwrap() {
+            Entry::Occupied(o) => {
+                assert_eq!(*o.key(), "b");
+                assert_eq!(o.remove(), 2);
+            },
+            Entry::Vacant(_) => panic!(),
+        }
+        match m.entry("b").unwrap() {
+            Entry::Occupied(_) => panic!(),
+            Entry::Vacant(v) => assert_eq!(v.into_key(), "b"),
+        }
+        assert_eq!(m.len(), 3);
+    }
+
+    #[test]
+    fn insertion_order_iteration() {
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::with_insertion_order(a.to_ref());
+        assert!(m.is_insertion_ordered());
+        for w in &["zeta", "alpha", "mid", "beta", "omega"] {
+            m.insert(*w, w.len()).unwrap();
+        }
+        m.insert("alpha", 0).unwrap();
+        assert_eq!(m.remove("mid"), Some(3));
+        *m.entry("mid").unwrap().or_insert(0) += 30;
+        for i in 0..40_usize {
+            m.insert(["a", "b", "c", "d", "e", "f", "g", "h"][i % 8], i).unwrap();
+        }
+        let mut keys = m.keys();
+        for w in &["zeta", "alpha", "beta", "omega", "mid", "a", "b", "c", "d", "e", "f", "g", "h"] {
+            assert_eq!(keys.next(), Some(w));
+        }
+        assert_eq!(keys.next(), None);
+        drop(keys);
+        for (i, (_, v)) in m.iter_mut().enumerate() {
+            *v = i;
+        }
+        assert!(m.values().copied().eq(0..13));
+        m.clear();
+        m.insert("x", 1).unwrap();
+        assert_eq!(m.iter().count(), 1);
+    }
+
+    #[test]
+    fn reserve_and_capacity() {
+        let mut buf = [0_u8; 0x2000];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = HashMap::new(a.to_ref());
+        assert_eq!(m.capacity(), 0);
+        m.reserve(100).unwrap();
+        assert!(m.capacity() >= 100);
+        let slot_count = m.slots.len();
+        for i in 0..100_u32 {
+            m.insert(i, i).unwrap();
+        }
+        assert_eq!(m.slots.len(), slot_count);
+        assert_eq!(m.reserve(usize::MAX).unwrap_err(), AllocError::UnsupportedSize);
+
+        let na = no_sup_allocator();
+        let mut m: HashMap<'_, u32, u32> = HashMap::with_insertion_order(na.to_ref());
+        assert_eq!(m.reserve(1).unwrap_err(), AllocError::UnsupportedOperation);
+        assert_eq!(m.reserve(0), Ok(()));
+    }
+
+    #[test]
+    fn entry_failure_returns_key() {
+        let a = no_sup_allocator();
+        let mut m: HashMap<'_, u32, u32> = HashMap::new(a.to_ref());
+        let (e, k) = m.entry(9).err().unwrap();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(k, 9);
+    }
+}
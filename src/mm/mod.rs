@@ -172,6 +172,12 @@ pub use single_alloc::SingleAlloc as SingleAlloc;
 pub mod bump_alloc;
 pub use bump_alloc::BumpAllocator as BumpAllocator;
 
+pub mod budget_alloc;
+pub use budget_alloc::BudgetAllocator as BudgetAllocator;
+
+pub mod fragmenting_alloc;
+pub use fragmenting_alloc::FragmentingAllocator as FragmentingAllocator;
+
 #[cfg(feature = "use-libc")]
 pub mod libc_malloc;
 #[cfg(feature = "use-libc")]
@@ -183,6 +189,12 @@ pub use r#box::Box as Box;
 pub mod vector;
 pub use vector::Vector as Vector;
 
+pub mod small_vector;
+pub use small_vector::SmallVector as SmallVector;
+
+pub mod cow_bytes;
+pub use cow_bytes::CowBytes as CowBytes;
+
 pub mod string;
 pub use string::String as String;
 
@@ -190,6 +202,25 @@ pub mod rc;
 pub use rc::Rc as Rc;
 pub use rc::RcWeak as RcWeak;
 
+#[cfg(feature = "use-std")]
+pub mod arc;
+#[cfg(feature = "use-std")]
+pub use arc::Arc as Arc;
+#[cfg(feature = "use-std")]
+pub use arc::ArcWeak as ArcWeak;
+
+pub mod trie;
+pub use trie::Trie as Trie;
+
+pub mod hash_map;
+pub use hash_map::HashMap as HashMap;
+
+pub mod ord_map;
+pub use ord_map::OrdMap as OrdMap;
+
+pub mod deque;
+pub use deque::Deque as Deque;
+
 impl<'a> AllocatorRef<'a> {
     pub fn alloc_item<T: Sized>(self, v: T) -> Result<Box<'a, T>, (AllocError, T)> {
         Box::new(self, v)
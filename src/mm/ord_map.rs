@@ -0,0 +1,308 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::ops::Bound;
+use core::ops::RangeBounds;
+
+use super::AllocatorRef;
+use super::AllocError;
+use super::Vector;
+
+/* OrdMap *******************************************************************/
+/* ordered map kept as a sorted vector of key/value pairs; lookups are binary
+ * searches and iteration is in key order which makes output deterministic;
+ * inserting/removing shifts the tail so this suits maps that are built once
+ * and queried many times (like section or segment tables) */
+#[derive(Debug)]
+pub struct OrdMap<'a, K, V> {
+    items: Vector<'a, (K, V)>,
+}
+
+impl<'a, K: Ord, V> OrdMap<'a, K, V> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        OrdMap { items: Vector::new(allocator) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.items.as_slice().binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+
+    /* index of the first item not matching the given lower bound */
+    fn lower_index<Q>(&self, bound: Bound<&Q>) -> usize
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        match bound {
+            Bound::Unbounded => 0,
+            Bound::Included(k) => self.search(k).unwrap_or_else(|i| i),
+            Bound::Excluded(k) => self.search(k).map(|i| i + 1).unwrap_or_else(|i| i),
+        }
+    }
+
+    /* index past the last item matching the given upper bound */
+    fn upper_index<Q>(&self, bound: Bound<&Q>) -> usize
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        match bound {
+            Bound::Unbounded => self.items.len(),
+            Bound::Included(k) => self.search(k).map(|i| i + 1).unwrap_or_else(|i| i),
+            Bound::Excluded(k) => self.search(k).unwrap_or_else(|i| i),
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, (AllocError, (K, V))> {
+        match self.search(&key) {
+            Ok(i) => Ok(Some(core::mem::replace(&mut self.items.as_mut_slice()[i].1, value))),
+            Err(i) => self.items.insert(i, (key, value)).map(|_| None),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.search(key).ok().map(|i| &self.items.as_slice()[i].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        let i = self.search(key).ok()?;
+        Some(&mut self.items.as_mut_slice()[i].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.search(key).is_ok()
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        let i = self.search(key).ok()?;
+        Some(self.items.remove(i))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn clear(&mut self) {
+        self.items.truncate(0);
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.items.as_slice().first().map(|(k, v)| (k, v))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.items.as_slice().last().map(|(k, v)| (k, v))
+    }
+
+    /* item with the largest key less than or equal to the given one; handy
+     * for finding the region that contains some offset */
+    pub fn floor_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where K: Borrow<Q>, Q: Ord + ?Sized {
+        let i = match self.search(key) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let (k, v) = &self.items.as_slice()[i];
+        Some((k, v))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { items: self.items.as_slice().iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { items: self.items.as_mut_slice().iter_mut() }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V>
+    where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let start = self.lower_index(range.start_bound());
+        let end = self.upper_index(range.end_bound());
+        let items = if start < end {
+            &self.items.as_slice()[start..end]
+        } else {
+            &[]
+        };
+        Iter { items: items.iter() }
+    }
+
+}
+
+impl<'a, K: Ord, V: PartialEq> PartialEq for OrdMap<'a, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(
+            |((ka, va), (kb, vb))| ka.cmp(kb) == Ordering::Equal && va == vb)
+    }
+}
+
+impl<'a, 'm, K: Ord, V> IntoIterator for &'m OrdMap<'a, K, V> {
+    type Item = (&'m K, &'m V);
+    type IntoIter = Iter<'m, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/* Iter *********************************************************************/
+pub struct Iter<'m, K, V> {
+    items: core::slice::Iter<'m, (K, V)>,
+}
+
+impl<'m, K, V> Iterator for Iter<'m, K, V> {
+    type Item = (&'m K, &'m V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.items.next().map(|(k, v)| (k, v))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<'m, K, V> DoubleEndedIterator for Iter<'m, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.items.next_back().map(|(k, v)| (k, v))
+    }
+}
+
+pub struct IterMut<'m, K, V> {
+    items: core::slice::IterMut<'m, (K, V)>,
+}
+
+impl<'m, K, V> Iterator for IterMut<'m, K, V> {
+    type Item = (&'m K, &'m mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.items.next().map(|(k, v)| (&*k, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::no_sup_allocator;
+
+    #[test]
+    fn empty_map() {
+        let a = no_sup_allocator();
+        let m: OrdMap<'_, u32, u32> = OrdMap::new(a.to_ref());
+        assert!(m.is_empty());
+        assert!(m.get(&0).is_none());
+        assert!(m.first_key_value().is_none());
+        assert_eq!(m.range(1..5).count(), 0);
+    }
+
+    #[test]
+    fn insert_failure_returns_key_and_value() {
+        let a = no_sup_allocator();
+        let mut m: OrdMap<'_, u32, char> = OrdMap::new(a.to_ref());
+        let (e, kv) = m.insert(1, 'x').unwrap_err();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(kv, (1, 'x'));
+    }
+
+    #[test]
+    fn iteration_is_sorted() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = OrdMap::new(a.to_ref());
+        for &k in &[5_u32, 1, 9, 3, 7] {
+            assert_eq!(m.insert(k, k * 2).unwrap(), None);
+        }
+        assert_eq!(m.insert(3, 33).unwrap(), Some(6));
+        let mut keys = [0_u32; 5];
+        for (i, k) in m.keys().enumerate() {
+            keys[i] = *k;
+        }
+        assert_eq!(keys, [1, 3, 5, 7, 9]);
+        assert_eq!(m.keys().next_back(), Some(&9));
+        assert_eq!(m.first_key_value(), Some((&1, &2)));
+        assert_eq!(m.last_key_value(), Some((&9, &18)));
+        assert_eq!(m.get(&3), Some(&33));
+    }
+
+    #[test]
+    fn str_keys() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = OrdMap::new(a.to_ref());
+        m.insert("e_machine", 1).unwrap();
+        m.insert("e_class", 2).unwrap();
+        m.insert("e_type", 3).unwrap();
+        *m.get_mut("e_type").unwrap() += 1;
+        assert_eq!(m.values().copied().sum::<i32>(), 7);
+        assert_eq!(m.iter().next(), Some((&"e_class", &2)));
+        assert_eq!(m.remove("e_class"), Some(2));
+        assert!(!m.contains_key("e_class"));
+        assert_eq!(m.len(), 2);
+    }
+
+    #[test]
+    fn range_queries() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = OrdMap::new(a.to_ref());
+        for k in (0..100_u64).step_by(10) {
+            m.insert(k, k / 10).unwrap();
+        }
+        assert_eq!(m.range(20..40).map(|(_, v)| *v).sum::<u64>(), 2 + 3);
+        assert_eq!(m.range(20..=40).map(|(_, v)| *v).sum::<u64>(), 2 + 3 + 4);
+        assert_eq!(m.range(..15).count(), 2);
+        assert_eq!(m.range(85..).count(), 1);
+        assert_eq!(m.range((Bound::Excluded(30), Bound::Unbounded)).count(), 6);
+        assert_eq!(m.range(50..50).count(), 0);
+    }
+
+    #[test]
+    fn floor_lookup() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m = OrdMap::new(a.to_ref());
+        m.insert(0x1000_u64, ".text").unwrap();
+        m.insert(0x4000_u64, ".data").unwrap();
+        assert_eq!(m.floor_key_value(&0x0FFF), None);
+        assert_eq!(m.floor_key_value(&0x1000), Some((&0x1000, &".text")));
+        assert_eq!(m.floor_key_value(&0x3FFF), Some((&0x1000, &".text")));
+        assert_eq!(m.floor_key_value(&0x9000), Some((&0x4000, &".data")));
+    }
+
+    #[test]
+    fn clear_and_compare() {
+        let mut buf = [0_u8; 0x400];
+        let a = BumpAllocator::new(&mut buf);
+        let mut m1 = OrdMap::new(a.to_ref());
+        let mut m2 = OrdMap::new(a.to_ref());
+        m1.insert(2, 'b').unwrap();
+        m1.insert(1, 'a').unwrap();
+        m2.insert(1, 'a').unwrap();
+        m2.insert(2, 'b').unwrap();
+        assert!(m1 == m2);
+        for (_, v) in m2.iter_mut() {
+            *v = 'z';
+        }
+        assert!(m1 != m2);
+        m1.clear();
+        assert!(m1.is_empty());
+    }
+}
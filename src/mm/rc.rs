@@ -72,23 +72,35 @@ unsafe fn free_if_unreferenced<T: ?Sized>(payload: &RcPayload<T>) {
 impl<'a, T> Rc<'a, T>
 where T: Sized {
 
-    pub fn new(
+    /* allocates the block and initializes the control part; the payload is
+     * left uninitialized */
+    fn alloc_block(
         allocator: AllocatorRef<'a>,
-        value: T,
-    ) -> Result<Self, (AllocError, T)> {
-
+        strong: usize,
+        weak: usize,
+    ) -> Result<*mut RcPayload<T>, AllocError> {
         let align = rc_align_of::<T>();
         let ctl_alloc_size = rc_ctl_alloc_size(align);
         let size = NonZeroUsize::new(ctl_alloc_size + mem::size_of::<RcPayload<T>>()).unwrap();
-        match unsafe { allocator.alloc(size, align) } {
-            Ok(ptr) => {
-                let uptr = (ptr.as_ptr() as usize) + ctl_alloc_size;
-                let data_ptr = uptr as *mut RcPayload<T>;
-                let uptr = uptr - mem::size_of::<RcCtlBlock<'a>>();
-                let ctl_ptr = uptr as *mut RcCtlBlock<'a>;
+        let ptr = unsafe { allocator.alloc(size, align) }?;
+        let uptr = (ptr.as_ptr() as usize) + ctl_alloc_size;
+        let data_ptr = uptr as *mut RcPayload<T>;
+        let uptr = uptr - mem::size_of::<RcCtlBlock<'a>>();
+        let ctl_ptr = uptr as *mut RcCtlBlock<'a>;
+        unsafe {
+            ptr::write(ctl_ptr, RcCtlBlock { strong, weak, allocator });
+        }
+        Ok(data_ptr)
+    }
+
+    pub fn new(
+        allocator: AllocatorRef<'a>,
+        value: T,
+    ) -> Result<Self, (AllocError, T)> {
+        match Self::alloc_block(allocator, 1, 0) {
+            Ok(data_ptr) => {
                 unsafe {
                     ptr::write(data_ptr, RcPayload(UnsafeCell::new(value)));
-                    ptr::write(ctl_ptr, RcCtlBlock { strong: 1, weak: 0, allocator: allocator });
                     Ok(Rc { data: &*data_ptr })
                 }
             },
@@ -96,6 +108,42 @@ where T: Sized {
         }
     }
 
+    /* builds a value that holds weak references to itself; the weak ref
+     * passed to data_fn cannot be upgraded until data_fn returns */
+    pub fn new_cyclic<F>(
+        allocator: AllocatorRef<'a>,
+        data_fn: F,
+    ) -> Result<Self, AllocError>
+    where F: FnOnce(&RcWeak<'a, T>) -> T {
+        let data_ptr = Self::alloc_block(allocator, 0, 1)?;
+        let weak = RcWeak { data: unsafe { &*data_ptr } };
+        let value = data_fn(&weak);
+        unsafe {
+            ptr::write(data_ptr, RcPayload(UnsafeCell::new(value)));
+            rc_ctl_block(weak.data).strong = 1;
+        }
+        let data = weak.data;
+        mem::drop(weak);
+        Ok(Rc { data })
+    }
+
+    /* extracts the value if this is the only strong reference; weak refs
+     * keep the memory block but can no longer be upgraded */
+    pub fn try_unwrap(rc: Rc<'a, T>) -> Result<T, Rc<'a, T>> {
+        let rc_block = unsafe { rc_ctl_block(rc.data) };
+        if rc_block.strong != 1 {
+            return Err(rc);
+        }
+        rc_block.strong = 0;
+        let data = rc.data;
+        mem::forget(rc);
+        unsafe {
+            let value = ptr::read(data.0.get());
+            free_if_unreferenced(data);
+            Ok(value)
+        }
+    }
+
 }
 
 impl<T> Rc<'_, T>
@@ -120,6 +168,12 @@ where T: ?Sized {
         }
     }
 
+    /* bytes taken from the allocator by the block holding the counters
+     * and the payload */
+    pub fn alloc_size(rc: &Rc<'_, T>) -> usize {
+        mem::size_of_val(rc.data) + rc_ctl_alloc_size(rc_align_of_val(rc.data))
+    }
+
     pub fn ptr_eq<'a, 'b>(a: &Rc<'a, T>, b: &Rc<'b, T>) -> bool {
         NonNull::new(a.data as *const RcPayload<T> as *mut RcPayload<T>) ==
         NonNull::new(b.data as *const RcPayload<T> as *mut RcPayload<T>)
@@ -202,8 +256,12 @@ impl<'a, T> Drop for Rc<'a, T> where T: ?Sized {
         assert!(rc_block.strong > 0);
         rc_block.strong -= 1;
         if rc_block.strong == 0 {
+            // hold a weak ref while dropping the payload so weak refs stored
+            // inside it (self references) do not free the block under us
+            rc_block.weak += 1;
             unsafe {
                 ptr::drop_in_place(self.data.0.get());
+                rc_ctl_block(self.data).weak -= 1;
                 free_if_unreferenced(self.data);
             }
         }
@@ -482,6 +540,60 @@ mod tests {
         let _x: &RcPayload<dyn fmt::Debug> = &RcPayload(UnsafeCell::new(0_u32));
     }
 
+    #[test]
+    fn try_unwrap() {
+        let mut buffer = [0u8; 64];
+        let a = SingleAlloc::new(&mut buffer);
+        let dropometer = AtomicUsize::new(0);
+        let rc1 = Rc::new(a.to_ref(), IncOnDrop { drop_counter: &dropometer }).unwrap();
+        let rc2 = rc1.clone();
+        let rc1 = Rc::try_unwrap(rc1).unwrap_err();
+        core::mem::drop(rc2);
+        let w = Rc::downgrade(&rc1);
+        let v = Rc::try_unwrap(rc1).unwrap();
+        assert_eq!(dropometer.load(Ordering::SeqCst), 0);
+        assert!(w.upgrade().is_none());
+        assert!(a.is_in_use());
+        core::mem::drop(w);
+        assert!(!a.is_in_use());
+        core::mem::drop(v);
+        assert_eq!(dropometer.load(Ordering::SeqCst), 1);
+    }
+
+    struct Node<'a> {
+        me: RcWeak<'a, Node<'a>>,
+        upgradable_during_init: bool,
+        id: u32,
+    }
+
+    #[test]
+    fn new_cyclic() {
+        let mut buffer = [0u8; 128];
+        let a = SingleAlloc::new(&mut buffer);
+        {
+            let rc = Rc::new_cyclic(a.to_ref(), |w| Node {
+                me: w.clone(),
+                upgradable_during_init: w.upgrade().is_some(),
+                id: 7,
+            }).unwrap();
+            assert!(!rc.upgradable_during_init);
+            assert_eq!(Rc::strong_count(&rc), 1);
+            assert_eq!(Rc::weak_count(&rc), 1);
+            let again = rc.me.upgrade().unwrap();
+            assert_eq!(again.id, 7);
+            assert!(Rc::ptr_eq(&rc, &again));
+        }
+        assert!(!a.is_in_use());
+    }
+
+    #[test]
+    fn new_cyclic_alloc_failure() {
+        let mut buffer = [0u8; 8];
+        let a = SingleAlloc::new(&mut buffer);
+        let e = Rc::<u64>::new_cyclic(a.to_ref(), |_| 1).unwrap_err();
+        assert_eq!(e, AllocError::NotEnoughMemory);
+    }
+
     #[repr(align(64))]
     struct Align64(u32);
 
@@ -1,5 +1,6 @@
 use core::ptr::NonNull;
-use core::cell::UnsafeCell;
+use core::cell::Cell;
+use core::marker::PhantomData;
 
 use crate::num::NonZeroUsize;
 use crate::num::Pow2Usize;
@@ -7,28 +8,27 @@ use crate::num::Pow2Usize;
 use super::Allocator;
 use super::AllocError;
 
-pub struct SingleAllocState<'a> {
-    buffer: &'a mut [u8],
-    used: usize,
-}
+/* SingleAlloc **************************************************************/
+/* allocator serving at most one live block out of a borrowed buffer; the
+ * used size lives in a Cell so no &mut is ever made from &self */
 pub struct SingleAlloc<'a> {
-    state: UnsafeCell<SingleAllocState<'a>>
+    buffer: NonNull<u8>,
+    size: usize,
+    used: Cell<usize>,
+    lifeline: PhantomData<&'a mut [u8]>,
 }
 
 impl<'a> SingleAlloc<'a> {
     pub fn new(buffer: &'a mut [u8]) -> Self {
         SingleAlloc {
-            state: SingleAllocState {
-                buffer: buffer,
-                used: 0usize,
-            }.into(),
+            buffer: NonNull::new(buffer.as_mut_ptr()).unwrap(),
+            size: buffer.len(),
+            used: Cell::new(0),
+            lifeline: PhantomData,
         }
     }
     pub fn is_in_use(&self) -> bool {
-        let state: &'a SingleAllocState<'a> = unsafe {
-            &*(self.state.get() as *mut SingleAllocState<'a>)
-        };
-        state.used != 0
+        self.used.get() != 0
     }
 
     fn check_allocation(
@@ -37,16 +37,13 @@ impl<'a> SingleAlloc<'a> {
         size: NonZeroUsize,
         align: Pow2Usize,
     ) {
-        let state: &'a SingleAllocState<'a> = unsafe {
-            &*(self.state.get() as *mut SingleAllocState<'a>)
-        };
-        if state.used == 0 {
+        if self.used.get() == 0 {
             panic!("cannot free what hasn't been allocated!");
-        } else if state.buffer.as_ptr() != ptr.as_ptr() {
+        } else if self.buffer != ptr {
             panic!("bad pointer");
-        } else if state.used != size.get() {
+        } else if self.used.get() != size.get() {
             panic!("bad size");
-        } else if ((state.buffer.as_ptr() as usize) & (align.get() - 1)) != 0 {
+        } else if !align.is_non_null_ptr_aligned(self.buffer) {
             panic!("bad alignment");
         }
     }
@@ -58,17 +55,15 @@ unsafe impl<'a> Allocator for SingleAlloc<'a> {
         size: NonZeroUsize,
         align: Pow2Usize
     ) -> Result<NonNull<u8>, AllocError> {
-        let state: &'a mut SingleAllocState<'a> = &mut
-            *(self.state.get() as *mut SingleAllocState<'a>);
-        if state.used != 0 {
+        if self.used.get() != 0 {
             Err(AllocError::OperationFailed)
-        } else if ((state.buffer.as_ptr() as usize) & (align.get() - 1)) != 0 {
+        } else if !align.is_non_null_ptr_aligned(self.buffer) {
             Err(AllocError::UnsupportedAlignment)
-        } else if size.get() > state.buffer.len() {
+        } else if size.get() > self.size {
             Err(AllocError::NotEnoughMemory)
         } else {
-            state.used = size.get();
-            Ok(NonNull::new(state.buffer.as_mut_ptr()).unwrap())
+            self.used.set(size.get());
+            Ok(self.buffer)
         }
     }
     unsafe fn free(
@@ -77,9 +72,7 @@ unsafe impl<'a> Allocator for SingleAlloc<'a> {
         size: NonZeroUsize,
         align: Pow2Usize) {
         self.check_allocation(ptr, size, align);
-        let state: &'a mut SingleAllocState<'a> = &mut 
-            *(self.state.get() as *mut SingleAllocState<'a>);
-        state.used = 0;
+        self.used.set(0);
     }
     unsafe fn grow(
         &self,
@@ -89,12 +82,10 @@ unsafe impl<'a> Allocator for SingleAlloc<'a> {
         align: Pow2Usize
     ) -> Result<NonNull<u8>, AllocError> {
         self.check_allocation(ptr, current_size, align);
-        let state: &'a mut SingleAllocState<'a> = &mut 
-            *(self.state.get() as *mut SingleAllocState<'a>);
-        if new_larger_size.get() > state.buffer.len() {
+        if new_larger_size.get() > self.size {
             Err(AllocError::NotEnoughMemory)
         } else {
-            state.used = new_larger_size.get();
+            self.used.set(new_larger_size.get());
             Ok(ptr)
         }
     }
@@ -106,18 +97,13 @@ unsafe impl<'a> Allocator for SingleAlloc<'a> {
         align: Pow2Usize
     ) -> Result<NonNull<u8>, AllocError> {
         self.check_allocation(ptr, current_size, align);
-        let state: &'a mut SingleAllocState<'a> = &mut
-            *(self.state.get() as *mut SingleAllocState<'a>);
-        state.used = new_smaller_size.get();
+        self.used.set(new_smaller_size.get());
         Ok(ptr)
     }
     fn supports_contains(&self) -> bool { true }
     fn contains(&self, ptr: NonNull<u8>) -> bool {
-        let state: &'a SingleAllocState<'a> = unsafe {
-            &*(self.state.get() as *mut SingleAllocState<'a>)
-        };
-        let begin = state.buffer.as_ptr() as usize;
-        let end = begin + state.buffer.len();
+        let begin = self.buffer.as_ptr() as usize;
+        let end = begin + self.size;
         let ptr = ptr.as_ptr() as usize;
         ptr >= begin && ptr < end
     }
@@ -344,5 +330,21 @@ mod tests {
         assert_eq!(unsafe { a.alloc(NonZeroUsize::new(1).unwrap(), Pow2Usize::one()) }.unwrap_err(), AllocError::OperationFailed);
     }
 
+    #[test]
+    fn alloc_free_realloc_through_two_references() {
+        let mut buf = [0u8; 7];
+        let a = single_alloc(&mut buf);
+        let (r1, r2) = (&a, &a);
+        let size = NonZeroUsize::new(4).unwrap();
+        let p = unsafe { r1.alloc(size, Pow2Usize::one()) }.unwrap();
+        unsafe { p.as_ptr().write_bytes(0x5A, 4) };
+        assert!(r2.is_in_use());
+        assert_eq!(unsafe { r2.alloc(size, Pow2Usize::one()) }.unwrap_err(), AllocError::OperationFailed);
+        unsafe { r2.free(p, size, Pow2Usize::one()) };
+        assert!(!r1.is_in_use());
+        let q = unsafe { r2.alloc(size, Pow2Usize::one()) }.unwrap();
+        assert_eq!(unsafe { *q.as_ptr().add(3) }, 0x5A);
+    }
+
 }
 
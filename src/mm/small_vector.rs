@@ -0,0 +1,362 @@
+use core::mem::MaybeUninit;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+use super::AllocatorRef;
+use super::AllocError;
+use super::Vector;
+
+/* SmallVector **************************************************************/
+/* vector that keeps up to N items inline and moves them to a heap Vector
+ * once more are needed; the items stay on the heap after spilling even if
+ * the vector shrinks back so slices do not move around on every push/pop */
+pub struct SmallVector<'a, T, const N: usize> {
+    inline: [MaybeUninit<T>; N],
+    inline_len: usize,
+    heap: Vector<'a, T>,
+    spilled: bool,
+}
+
+impl<'a, T, const N: usize> SmallVector<'a, T, N> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> SmallVector<'a, T, N> {
+        SmallVector {
+            // an array of MaybeUninit needs no initialization
+            inline: unsafe { MaybeUninit::uninit().assume_init() },
+            inline_len: 0,
+            heap: Vector::new(allocator),
+            spilled: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        if self.spilled { self.heap.len() } else { self.inline_len }
+    }
+
+    pub fn cap(&self) -> usize {
+        if self.spilled { self.heap.cap() } else { N }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        self.spilled
+    }
+
+    pub fn allocator(&self) -> AllocatorRef<'a> {
+        self.heap.allocator()
+    }
+
+    fn spill(&mut self, count: usize) -> Result<(), AllocError> {
+        if count > usize::MAX - self.inline_len {
+            return Err(AllocError::UnsupportedSize);
+        }
+        self.heap.reserve(self.inline_len + count)?;
+        for i in 0..self.inline_len {
+            let v = unsafe { self.inline[i].as_ptr().read() };
+            if self.heap.push(v).is_err() {
+                unreachable!("push after reserve failed");
+            }
+        }
+        self.inline_len = 0;
+        self.spilled = true;
+        Ok(())
+    }
+
+    pub fn reserve(&mut self, count: usize) -> Result<(), AllocError> {
+        if self.spilled {
+            self.heap.reserve(count)
+        } else if count <= N - self.inline_len {
+            Ok(())
+        } else {
+            self.spill(count)
+        }
+    }
+
+    pub fn push(&mut self, v: T) -> Result<(), (AllocError, T)> {
+        if let Err(e) = self.reserve(1) {
+            return Err((e, v));
+        }
+        if self.spilled {
+            return self.heap.push(v);
+        }
+        self.inline[self.inline_len] = MaybeUninit::new(v);
+        self.inline_len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.spilled {
+            self.heap.pop()
+        } else if self.inline_len == 0 {
+            None
+        } else {
+            self.inline_len -= 1;
+            Some(unsafe { self.inline[self.inline_len].as_ptr().read() })
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, v: T) -> Result<(), (AllocError, T)> {
+        assert!(index <= self.len(), "insert index out of range");
+        if let Err(e) = self.reserve(1) {
+            return Err((e, v));
+        }
+        if self.spilled {
+            return self.heap.insert(index, v);
+        }
+        unsafe {
+            let p = self.inline.as_mut_ptr().add(index);
+            core::ptr::copy(p, p.add(1), self.inline_len - index);
+            p.write(MaybeUninit::new(v));
+        }
+        self.inline_len += 1;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        if self.spilled {
+            return self.heap.remove(index);
+        }
+        assert!(index < self.inline_len, "remove index out of range");
+        self.inline_len -= 1;
+        unsafe {
+            let p = self.inline.as_mut_ptr().add(index);
+            let v = p.read().assume_init();
+            core::ptr::copy(p.add(1), p, self.inline_len - index);
+            v
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if self.spilled {
+            self.heap.truncate(len);
+            return;
+        }
+        while self.inline_len > len {
+            self.inline_len -= 1;
+            unsafe {
+                core::ptr::drop_in_place(self.inline[self.inline_len].as_mut_ptr());
+            }
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        if self.spilled {
+            self.heap.as_slice()
+        } else {
+            unsafe {
+                core::slice::from_raw_parts(
+                    self.inline.as_ptr() as *const T, self.inline_len)
+            }
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.spilled {
+            self.heap.as_mut_slice()
+        } else {
+            unsafe {
+                core::slice::from_raw_parts_mut(
+                    self.inline.as_mut_ptr() as *mut T, self.inline_len)
+            }
+        }
+    }
+
+    pub fn append_from_slice(&mut self, src: &[T]) -> Result<(), AllocError>
+    where T: Copy {
+        self.reserve(src.len())?;
+        if self.spilled {
+            return self.heap.append_from_slice(src);
+        }
+        for (d, s) in self.inline[self.inline_len..].iter_mut().zip(src) {
+            *d = MaybeUninit::new(*s);
+        }
+        self.inline_len += src.len();
+        Ok(())
+    }
+
+    pub fn from_slice(
+        src: &[T],
+        allocator: AllocatorRef<'a>,
+    ) -> Result<Self, AllocError>
+    where T: Copy {
+        let mut v: Self = SmallVector::new(allocator);
+        v.append_from_slice(src)?;
+        Ok(v)
+    }
+
+    pub fn dup<'b>(
+        &self,
+        allocator: AllocatorRef<'b>,
+    ) -> Result<SmallVector<'b, T, N>, AllocError>
+    where T: Copy {
+        SmallVector::from_slice(self.as_slice(), allocator)
+    }
+
+    /* converts to a plain Vector; this allocates unless already spilled */
+    pub fn into_vector(mut self) -> Result<Vector<'a, T>, (AllocError, Self)> {
+        if !self.spilled {
+            if let Err(e) = self.spill(0) {
+                return Err((e, self));
+            }
+        }
+        let allocator = self.heap.allocator();
+        Ok(core::mem::replace(&mut self.heap, Vector::new(allocator)))
+    }
+}
+
+impl<'a, T, const N: usize> Drop for SmallVector<'a, T, N> {
+    fn drop(&mut self) {
+        if !self.spilled {
+            self.truncate(0);
+        }
+    }
+}
+
+impl<'a, T: core::fmt::Debug, const N: usize> core::fmt::Debug for SmallVector<'a, T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SmallVector")
+            .field("items", &self.as_slice())
+            .field("spilled", &self.spilled)
+            .finish()
+    }
+}
+
+impl<'a, 'b, T: PartialEq, const N: usize, const M: usize>
+PartialEq<SmallVector<'b, T, M>> for SmallVector<'a, T, N> {
+    fn eq(&self, other: &SmallVector<'b, T, M>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<'a, T: Display, const N: usize> Display for SmallVector<'a, T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut first = true;
+        for v in self.as_slice() {
+            if first {
+                first = false;
+            } else {
+                write!(f, ", ")?;
+            }
+            Display::fmt(v, f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::no_sup_allocator;
+    use super::super::Allocator;
+    use super::super::BumpAllocator;
+
+    #[test]
+    fn inline_items_need_no_allocator() {
+        let a = no_sup_allocator();
+        let mut v: SmallVector<'_, u16, 2> = SmallVector::new(a.to_ref());
+        assert!(v.is_empty());
+        v.push(1).unwrap();
+        v.push(2).unwrap();
+        assert_eq!(v.as_slice(), [1, 2]);
+        assert!(!v.is_spilled());
+        let (e, x) = v.push(3).unwrap_err();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(x, 3);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.pop(), Some(2));
+        assert_eq!(v.pop(), Some(1));
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn spills_to_heap() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let mut v: SmallVector<'_, u32, 2> = SmallVector::new(a.to_ref());
+        for i in 0..5 {
+            v.push(i).unwrap();
+        }
+        assert!(v.is_spilled());
+        assert!(v.cap() >= 5);
+        assert_eq!(v.as_slice(), [0, 1, 2, 3, 4]);
+        v.as_mut_slice()[0] = 10;
+        assert_eq!(v.remove(0), 10);
+        assert_eq!(v.pop(), Some(4));
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn insert_and_remove_inline() {
+        let a = no_sup_allocator();
+        let mut v: SmallVector<'_, char, 4> = SmallVector::new(a.to_ref());
+        v.push('a').unwrap();
+        v.push('c').unwrap();
+        v.insert(1, 'b').unwrap();
+        v.insert(0, '_').unwrap();
+        assert_eq!(v.as_slice(), ['_', 'a', 'b', 'c']);
+        assert_eq!(v.remove(0), '_');
+        assert_eq!(v.as_slice(), ['a', 'b', 'c']);
+        v.truncate(1);
+        assert_eq!(v.as_slice(), ['a']);
+    }
+
+    #[test]
+    fn append_from_slice_and_compare() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let v1: SmallVector<'_, u8, 4> = SmallVector::from_slice(b"abc", a.to_ref()).unwrap();
+        assert!(!v1.is_spilled());
+        let mut v2: SmallVector<'_, u8, 2> = SmallVector::new(a.to_ref());
+        v2.append_from_slice(b"ab").unwrap();
+        v2.append_from_slice(b"c").unwrap();
+        assert!(v2.is_spilled());
+        assert!(v1 == v2);
+        let v3 = v2.dup(a.to_ref()).unwrap();
+        assert!(v3 == v1);
+    }
+
+    #[test]
+    fn into_vector_moves_items() {
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let v: SmallVector<'_, u16, 4> = SmallVector::from_slice(&[1, 2], a.to_ref()).unwrap();
+        let v = v.into_vector().unwrap();
+        assert_eq!(v.as_slice(), [1, 2]);
+    }
+
+    #[test]
+    fn drop_releases_inline_and_heap_items() {
+        extern crate std;
+        use std::rc::Rc as StdRc;
+        let mut buf = [0_u8; 0x100];
+        let a = BumpAllocator::new(&mut buf);
+        let tracker = StdRc::new(());
+        {
+            let mut v: SmallVector<'_, StdRc<()>, 2> = SmallVector::new(a.to_ref());
+            v.push(tracker.clone()).unwrap();
+            assert_eq!(StdRc::strong_count(&tracker), 2);
+        }
+        assert_eq!(StdRc::strong_count(&tracker), 1);
+        {
+            let mut v: SmallVector<'_, StdRc<()>, 2> = SmallVector::new(a.to_ref());
+            for _ in 0..3 {
+                v.push(tracker.clone()).unwrap();
+            }
+            assert_eq!(StdRc::strong_count(&tracker), 4);
+        }
+        assert_eq!(StdRc::strong_count(&tracker), 1);
+        assert_eq!(a.space_left(), 0x100);
+    }
+
+    #[test]
+    fn display() {
+        extern crate std;
+        use std::format;
+        let a = no_sup_allocator();
+        let v: SmallVector<'_, u8, 3> = SmallVector::from_slice(&[1, 2, 3], a.to_ref()).unwrap();
+        assert_eq!(format!("{}", v), "1, 2, 3");
+    }
+}
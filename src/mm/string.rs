@@ -1,6 +1,16 @@
 use super::Vector;
 use super::AllocatorRef;
 use super::AllocError;
+use super::Box;
+use core::str::Utf8Error;
+use crate::io::ErrorCode;
+use crate::io::IOResult;
+use crate::io::stream::Read;
+use crate::xc_err;
+use crate::ExecutionContext;
+use core::borrow::Borrow;
+use core::hash::Hash;
+use core::hash::Hasher;
 use core::fmt::Debug;
 use core::fmt::Write as FmtWrite;
 use core::fmt::Result as FmtResult;
@@ -31,9 +41,91 @@ impl<'a> String<'a> {
     ) -> Result<String<'a>, AllocError> {
         Vector::from_slice(data.as_bytes(), allocator).map(|v| String { data: v })
     }
+    /* takes ownership of the bytes if they are valid UTF-8; on failure the
+     * error gives the bytes back along with where decoding stopped */
+    pub fn from_utf8(
+        data: Vector<'a, u8>
+    ) -> Result<String<'a>, FromUtf8Error<'a>> {
+        match core::str::from_utf8(data.as_slice()) {
+            Ok(_) => Ok(String { data }),
+            Err(error) => Err(FromUtf8Error { bytes: data, error }),
+        }
+    }
+    /* reads at most max_len bytes of UTF-8 text, validating each chunk as
+     * it arrives; a char split by the max_len limit is dropped while one
+     * split by the end of the stream is reported as invalid data */
+    pub fn from_read<'x, R: Read + ?Sized>(
+        allocator: AllocatorRef<'a>,
+        reader: &mut R,
+        max_len: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, String<'a>> {
+        let mut data: Vector<'a, u8> = Vector::new(allocator);
+        let mut decoder = Utf8Decoder::new();
+        let mut buf = [0_u8; 256];
+        while data.len() < max_len {
+            let chunk_len = core::cmp::min(buf.len(), max_len - data.len());
+            let n = match reader.read(&mut buf[0..chunk_len], xc) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => match e.get_error_code() {
+                    ErrorCode::Interrupted => continue,
+                    _ => return Err(e),
+                }
+            };
+            decoder.feed(&buf[0..n]).map_err(|e| xc_err!(
+                xc, ErrorCode::InvalidData,
+                "invalid UTF-8 in stream",
+                "invalid UTF-8 at offset {}", e.offset()))?;
+            data.append_from_slice(&buf[0..n]).map_err(|e| xc_err!(
+                xc, ErrorCode::NoSpace,
+                "string read out of memory",
+                "string read failed: {}", e))?;
+        }
+        if !decoder.is_at_char_boundary() {
+            if data.len() < max_len {
+                return Err(xc_err!(
+                    xc, ErrorCode::InvalidData,
+                    "truncated UTF-8 at end of stream",
+                    "truncated UTF-8 at offset {}",
+                    decoder.offset() - decoder.incomplete_len() as u64));
+            }
+            let valid_len = data.len() - decoder.incomplete_len();
+            data.truncate(valid_len);
+        }
+        Ok(String { data })
+    }
+    /* same as from_read but allocates from the execution context */
+    pub fn from_stream<'x>(
+        read: &mut (dyn Read + '_),
+        max_len: usize,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, String<'x>> {
+        String::from_read(xc.get_main_allocator(), read, max_len, xc)
+    }
     pub fn as_str(&self) -> &str {
         unsafe { core::str::from_utf8_unchecked(self.data.as_slice()) }
     }
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+    pub fn into_bytes(self) -> Vector<'a, u8> {
+        self.data
+    }
+    pub fn into_boxed_str(self) -> Result<Box<'a, str>, (AllocError, Self)> {
+        self.data.into_boxed_slice()
+            .map(|b| unsafe { b.into_boxed_str_unchecked() })
+            .map_err(|(e, data)| (e, String { data }))
+    }
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn cap(&self) -> usize {
+        self.data.cap()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
     pub fn push(&mut self, c: char) -> Result<(), AllocError> {
         let mut buf = [0_u8; 4];
         self.data.append_from_slice(c.encode_utf8(&mut buf).as_bytes())
@@ -45,6 +137,75 @@ impl<'a> String<'a> {
         self.data.append_from_slice(s.as_bytes())?;
         Ok(())
     }
+    pub fn push_str(
+        &mut self,
+        s: &str,
+    ) -> Result<(), AllocError> {
+        self.append_str(s)
+    }
+    /* panics if index is not on a char boundary */
+    pub fn insert_str(
+        &mut self,
+        index: usize,
+        s: &str,
+    ) -> Result<(), AllocError> {
+        assert!(self.as_str().is_char_boundary(index), "insert index not on char boundary");
+        self.data.reserve(s.len())?;
+        for (i, b) in s.bytes().enumerate() {
+            if self.data.insert(index + i, b).is_err() {
+                unreachable!("insert after reserve failed");
+            }
+        }
+        Ok(())
+    }
+    pub fn insert(
+        &mut self,
+        index: usize,
+        c: char,
+    ) -> Result<(), AllocError> {
+        let mut buf = [0_u8; 4];
+        self.insert_str(index, c.encode_utf8(&mut buf))
+    }
+    /* panics if new_len is not on a char boundary */
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len() {
+            assert!(self.as_str().is_char_boundary(new_len), "truncate length not on char boundary");
+            self.data.truncate(new_len);
+        }
+    }
+    pub fn split_at(&self, mid: usize) -> (&str, &str) {
+        self.as_str().split_at(mid)
+    }
+    pub fn find(&self, pat: &str) -> Option<usize> {
+        self.as_str().find(pat)
+    }
+    pub fn trim(&self) -> &str {
+        self.as_str().trim()
+    }
+    pub fn replace<'b>(
+        &self,
+        from: &str,
+        to: &str,
+        allocator: AllocatorRef<'b>,
+    ) -> Result<String<'b>, AllocError> {
+        let mut o = String::new(allocator);
+        let mut last = 0;
+        for (pos, _) in self.as_str().match_indices(from) {
+            o.append_str(&self.as_str()[last..pos])?;
+            o.append_str(to)?;
+            last = pos + from.len();
+        }
+        o.append_str(&self.as_str()[last..])?;
+        Ok(o)
+    }
+    pub fn to_lowercase_ascii<'b>(
+        &self,
+        allocator: AllocatorRef<'b>,
+    ) -> Result<String<'b>, AllocError> {
+        let mut o = self.dup(allocator)?;
+        o.data.as_mut_slice().make_ascii_lowercase();
+        Ok(o)
+    }
     pub fn dup<'b>(
         &self,
         allocator: AllocatorRef<'b>,
@@ -62,6 +223,21 @@ impl FmtWrite for String<'_> {
     }
 }
 
+impl Eq for String<'_> {}
+
+/* hashes like str so that maps with String keys can be searched by &str */
+impl Hash for String<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Borrow<str> for String<'_> {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
 impl<'a> Debug for String<'a> {
     fn fmt(&self, fmt: &mut FmtFormatter<'_>) -> FmtResult {
         core::fmt::Debug::fmt(self.as_str(), fmt)
@@ -74,6 +250,143 @@ impl<'a> FmtDisplay for String<'a> {
     }
 }
 
+/* FromUtf8Error ************************************************************/
+#[derive(Debug)]
+pub struct FromUtf8Error<'a> {
+    bytes: Vector<'a, u8>,
+    error: Utf8Error,
+}
+
+impl<'a> FromUtf8Error<'a> {
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+    pub fn into_bytes(self) -> Vector<'a, u8> {
+        self.bytes
+    }
+    pub fn utf8_error(&self) -> Utf8Error {
+        self.error
+    }
+}
+
+impl<'a> FmtDisplay for FromUtf8Error<'a> {
+    fn fmt(&self, fmt: &mut FmtFormatter<'_>) -> FmtResult {
+        FmtDisplay::fmt(&self.error, fmt)
+    }
+}
+
+/* Utf8Decoder **************************************************************/
+/* incremental UTF-8 validator; chunks can split chars anywhere and errors
+ * report the offset (from the start of the first chunk) of the invalid
+ * sequence; rejects the same inputs as core::str::from_utf8 */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Utf8Decoder {
+    offset: u64,
+    char_start: u64,
+    need: u8, // continuation bytes still expected for the current char
+    lo: u8, // valid range for the next continuation byte
+    hi: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Utf8DecodeError {
+    offset: u64,
+}
+
+impl Utf8DecodeError {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl FmtDisplay for Utf8DecodeError {
+    fn fmt(&self, fmt: &mut FmtFormatter<'_>) -> FmtResult {
+        write!(fmt, "invalid UTF-8 sequence at offset {}", self.offset)
+    }
+}
+
+impl Default for Utf8Decoder {
+    fn default() -> Self {
+        Utf8Decoder::new()
+    }
+}
+
+impl Utf8Decoder {
+
+    pub fn new() -> Self {
+        Utf8Decoder { offset: 0, char_start: 0, need: 0, lo: 0x80, hi: 0xBF }
+    }
+
+    /* number of bytes fed so far */
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn is_at_char_boundary(&self) -> bool {
+        self.need == 0
+    }
+
+    /* bytes of the last char that are still waiting for the rest of it */
+    pub fn incomplete_len(&self) -> usize {
+        (self.offset - self.char_start) as usize
+    }
+
+    /* validates the chunk; on error the decoder is left at the offending
+     * byte so the caller can decide whether to resync or give up */
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), Utf8DecodeError> {
+        for &b in chunk {
+            if self.need == 0 {
+                self.char_start = self.offset;
+                let (need, lo, hi) = match b {
+                    0x00..=0x7F => (0, 0x80, 0xBF),
+                    0xC2..=0xDF => (1, 0x80, 0xBF),
+                    0xE0 => (2, 0xA0, 0xBF),
+                    0xED => (2, 0x80, 0x9F),
+                    0xE1..=0xEF => (2, 0x80, 0xBF),
+                    0xF0 => (3, 0x90, 0xBF),
+                    0xF1..=0xF3 => (3, 0x80, 0xBF),
+                    0xF4 => (3, 0x80, 0x8F),
+                    _ => return Err(Utf8DecodeError { offset: self.offset }),
+                };
+                self.need = need;
+                self.lo = lo;
+                self.hi = hi;
+            } else if b >= self.lo && b <= self.hi {
+                self.need -= 1;
+                self.lo = 0x80;
+                self.hi = 0xBF;
+            } else {
+                return Err(Utf8DecodeError { offset: self.char_start });
+            }
+            self.offset += 1;
+        }
+        Ok(())
+    }
+
+    /* after an error, drops the partial char (the offending byte will be
+     * looked at again as the start of a new char) or skips the invalid
+     * lead byte, so that validation can go on with the rest of the input */
+    pub fn recover(&mut self) {
+        if self.need == 0 {
+            self.offset += 1;
+        }
+        self.need = 0;
+        self.char_start = self.offset;
+        self.lo = 0x80;
+        self.hi = 0xBF;
+    }
+
+    /* checks the input did not end in the middle of a char */
+    pub fn finish(&self) -> Result<(), Utf8DecodeError> {
+        if self.need == 0 {
+            Ok(())
+        } else {
+            Err(Utf8DecodeError { offset: self.char_start })
+        }
+    }
+
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,5 +440,216 @@ mod tests {
         let c = b.dup(a.to_ref()).unwrap();
         assert_eq!(c.as_str(), "abc /\\ \"def\"");
     }
-}
 
+    #[test]
+    fn from_utf8() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let s = String::from_utf8(Vector::from_slice(b"abc", a.to_ref()).unwrap()).unwrap();
+        assert_eq!(s.as_str(), "abc");
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.into_bytes().as_slice(), b"abc");
+    }
+
+    #[test]
+    fn from_utf8_reports_error_and_returns_bytes() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let e = String::from_utf8(Vector::from_slice(b"ab\xFFc", a.to_ref()).unwrap()).unwrap_err();
+        assert_eq!(e.utf8_error().valid_up_to(), 2);
+        assert_eq!(e.as_bytes(), b"ab\xFFc");
+        let mut s = String::new(a.to_ref());
+        write!(s, "{}", e).unwrap();
+        assert!(s.as_str().contains("index 2"));
+        assert_eq!(e.into_bytes().len(), 4);
+    }
+
+    #[test]
+    fn push_str_and_insert() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut s = String::new(a.to_ref());
+        assert!(s.is_empty());
+        s.push_str("held").unwrap();
+        s.insert(2, 'l').unwrap();
+        s.insert_str(0, "\u{101234} ").unwrap();
+        assert_eq!(s.as_str(), "\u{101234} helld");
+    }
+
+    #[test]
+    #[should_panic(expected = "insert index not on char boundary")]
+    fn insert_inside_char_panics() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut s = String::from_str("\u{e9}", a.to_ref()).unwrap();
+        let _ = s.insert(1, 'x');
+    }
+
+    #[test]
+    fn truncate() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut s = String::from_str("e_machine", a.to_ref()).unwrap();
+        s.truncate(20);
+        assert_eq!(s.as_str(), "e_machine");
+        s.truncate(1);
+        assert_eq!(s.as_str(), "e");
+    }
+
+    #[test]
+    fn split_find_trim() {
+        let s = String::map_str("  elf_header.e_machine ");
+        assert_eq!(s.trim(), "elf_header.e_machine");
+        let dot = s.find(".").unwrap();
+        assert_eq!(dot, 12);
+        assert_eq!(s.split_at(dot), ("  elf_header", ".e_machine "));
+        assert!(s.find("pe_").is_none());
+    }
+
+    #[test]
+    fn replace() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let s = String::map_str("a.b.c");
+        assert_eq!(s.replace(".", "::", a.to_ref()).unwrap().as_str(), "a::b::c");
+        assert_eq!(s.replace("x", "y", a.to_ref()).unwrap().as_str(), "a.b.c");
+        assert_eq!(s.replace("a.b.c", "", a.to_ref()).unwrap().as_str(), "");
+    }
+
+    #[test]
+    fn to_lowercase_ascii() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let s = String::map_str("ELF \u{C9}Class");
+        assert_eq!(s.to_lowercase_ascii(a.to_ref()).unwrap().as_str(), "elf \u{C9}class");
+    }
+
+    struct ChunkReader<'d> {
+        data: &'d [u8],
+        chunk_len: usize,
+    }
+    impl Read for ChunkReader<'_> {
+        fn read<'x>(
+            &mut self,
+            buf: &mut [u8],
+            _xc: &mut ExecutionContext<'x>
+        ) -> IOResult<'x, usize> {
+            let n = core::cmp::min(core::cmp::min(buf.len(), self.chunk_len), self.data.len());
+            buf[0..n].copy_from_slice(&self.data[0..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn from_read_validates_across_chunks() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::nop();
+        let text = "h\u{e9}llo \u{101234}!";
+        let mut r = ChunkReader { data: text.as_bytes(), chunk_len: 1 };
+        let s = String::from_read(a.to_ref(), &mut r, 100, &mut xc).unwrap();
+        assert_eq!(s.as_str(), text);
+    }
+
+    #[test]
+    fn from_read_stops_at_max_len_on_char_boundary() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::nop();
+        let mut r = ChunkReader { data: "ab\u{e9}cd".as_bytes(), chunk_len: 3 };
+        let s = String::from_read(a.to_ref(), &mut r, 3, &mut xc).unwrap();
+        assert_eq!(s.as_str(), "ab");
+        let mut r = ChunkReader { data: "ab\u{e9}cd".as_bytes(), chunk_len: 3 };
+        let s = String::from_read(a.to_ref(), &mut r, 4, &mut xc).unwrap();
+        assert_eq!(s.as_str(), "ab\u{e9}");
+    }
+
+    #[test]
+    fn from_read_reports_invalid_utf8() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::nop();
+        let mut r = ChunkReader { data: b"abc\xC3(", chunk_len: 4 };
+        let e = String::from_read(a.to_ref(), &mut r, 100, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+        let mut r = ChunkReader { data: b"abc\xC3", chunk_len: 2 };
+        let e = String::from_read(a.to_ref(), &mut r, 100, &mut xc).unwrap_err();
+        assert_eq!(e.get_error_code(), ErrorCode::InvalidData);
+    }
+
+    #[test]
+    fn from_stream_uses_main_allocator() {
+        let mut buffer = [0; 256];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut r = ChunkReader { data: b".strtab\0.text", chunk_len: 5 };
+        let s = String::from_stream(&mut r, 7, &mut xc).unwrap();
+        assert_eq!(s.as_str(), ".strtab");
+    }
+
+    #[test]
+    fn map_keys_found_by_str() {
+        let mut buffer = [0; 1024];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut m = HashMap::new(a.to_ref());
+        m.insert(String::from_str("abc", a.to_ref()).unwrap(), 1).unwrap();
+        m.insert(String::from_str("de", a.to_ref()).unwrap(), 2).unwrap();
+        assert_eq!(m.get("abc"), Some(&1));
+        assert_eq!(m.get("de"), Some(&2));
+        assert_eq!(m.get("ab"), None);
+    }
+
+
+    #[test]
+    fn utf8_decoder_matches_core_validation() {
+        let samples: [&[u8]; 10] = [
+            b"plain ascii",
+            "\u{e9}\u{20ac}\u{10348}".as_bytes(),
+            b"\xC0\x80", // overlong
+            b"\xE0\x80\x80", // overlong
+            b"\xED\xA0\x80", // surrogate
+            b"\xF4\x90\x80\x80", // above max code point
+            b"ab\xFFcd",
+            b"ab\xE2\x82(",
+            b"\x80",
+            b"\xF0\x90\x8D\x88x",
+        ];
+        for sample in samples.iter() {
+            let expected = core::str::from_utf8(sample).map_err(|e| e.valid_up_to() as u64);
+            for chunk_len in 1..5 {
+                let mut d = Utf8Decoder::new();
+                let r = sample.chunks(chunk_len).try_for_each(|c| d.feed(c))
+                    .and_then(|_| d.finish())
+                    .map_err(|e| e.offset());
+                assert_eq!(r, expected.map(|_| ()), "{:?} in chunks of {}", sample, chunk_len);
+            }
+        }
+    }
+
+    #[test]
+    fn utf8_decoder_tracks_incomplete_char() {
+        let mut d = Utf8Decoder::new();
+        d.feed(b"a\xF0\x90").unwrap();
+        assert!(!d.is_at_char_boundary());
+        assert_eq!(d.incomplete_len(), 2);
+        assert_eq!(d.finish(), Err(Utf8DecodeError { offset: 1 }));
+        d.feed(b"\x8D\x88").unwrap();
+        assert!(d.is_at_char_boundary());
+        assert_eq!(d.offset(), 5);
+        d.finish().unwrap();
+    }
+
+    #[test]
+    fn utf8_decoder_recovers_after_errors() {
+        let mut v = Utf8Decoder::new();
+        assert_eq!(v.feed(b"a\xFF"), Err(Utf8DecodeError { offset: 1 }));
+        v.recover();
+        assert_eq!(v.offset(), 2);
+        assert_eq!(v.feed(b"\xE2\x82("), Err(Utf8DecodeError { offset: 2 }));
+        v.recover();
+        assert_eq!(v.offset(), 4);
+        v.feed(b"(").unwrap();
+        v.finish().unwrap();
+    }
+}
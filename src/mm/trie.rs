@@ -0,0 +1,297 @@
+use super::AllocatorRef;
+use super::AllocError;
+use super::Vector;
+
+const NO_NODE: usize = 0; // root is never anybody's child or sibling
+
+#[derive(Debug)]
+struct TrieNode<V> {
+    value: Option<V>,
+    first_child: usize,
+    next_sibling: usize,
+    byte: u8,
+}
+
+/* Trie *********************************************************************/
+/* maps byte strings to values; nodes are kept in a single vector and linked
+ * as first-child / next-sibling so that no per-node allocation is needed */
+#[derive(Debug)]
+pub struct Trie<'a, V> {
+    nodes: Vector<'a, TrieNode<V>>,
+    len: usize,
+}
+
+impl<'a, V> Trie<'a, V> {
+
+    pub fn new(allocator: AllocatorRef<'a>) -> Self {
+        Trie {
+            nodes: Vector::new(allocator),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /* makes room for keys adding up to the given number of bytes, so that
+     * inserting them allocates no more nodes */
+    pub fn reserve(&mut self, key_bytes: usize) -> Result<(), AllocError> {
+        let root_needed = if self.nodes.is_empty() { 1 } else { 0 };
+        self.nodes.reserve(key_bytes.saturating_add(root_needed))
+    }
+
+    fn child(&self, node: usize, byte: u8) -> Option<usize> {
+        let nodes = self.nodes.as_slice();
+        let mut i = nodes[node].first_child;
+        while i != NO_NODE {
+            if nodes[i].byte == byte {
+                return Some(i);
+            }
+            i = nodes[i].next_sibling;
+        }
+        None
+    }
+
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut node = 0;
+        for &b in key {
+            node = self.child(node, b)?;
+        }
+        Some(node)
+    }
+
+    fn add_child(&mut self, node: usize, byte: u8) -> usize {
+        let index = self.nodes.len();
+        let next_sibling = self.nodes.as_slice()[node].first_child;
+        self.nodes.push(TrieNode {
+            value: None,
+            first_child: NO_NODE,
+            next_sibling,
+            byte,
+        }).map_err(|_| ()).expect("trie nodes not reserved");
+        self.nodes.as_mut_slice()[node].first_child = index;
+        index
+    }
+
+    /* inserts the value for the given key returning the previous value
+     * associated with it, if any; on allocation failure the trie is left
+     * unchanged and the value is handed back to the caller */
+    pub fn insert(
+        &mut self,
+        key: &[u8],
+        value: V,
+    ) -> Result<Option<V>, (AllocError, V)> {
+        let root_needed = if self.nodes.is_empty() { 1 } else { 0 };
+        if let Err(e) = self.nodes.reserve(key.len() + root_needed) {
+            return Err((e, value));
+        }
+        if root_needed != 0 {
+            self.nodes.push(TrieNode {
+                value: None,
+                first_child: NO_NODE,
+                next_sibling: NO_NODE,
+                byte: 0,
+            }).map_err(|_| ()).unwrap();
+        }
+        let mut node = 0;
+        for &b in key {
+            node = match self.child(node, b) {
+                Some(n) => n,
+                None => self.add_child(node, b),
+            };
+        }
+        let old = self.nodes.as_mut_slice()[node].value.replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        Ok(old)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.find(key).and_then(|n| self.nodes.as_slice()[n].value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        let n = self.find(key)?;
+        self.nodes.as_mut_slice()[n].value.as_mut()
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /* removes the value for the given key; nodes are kept around to be
+     * reused by later inserts */
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let n = self.find(key)?;
+        let old = self.nodes.as_mut_slice()[n].value.take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    /* finds the longest key that is a prefix of data; returns the length
+     * of that key together with its value */
+    pub fn longest_prefix_match(&self, data: &[u8]) -> Option<(usize, &V)> {
+        self.prefix_matches(data).last()
+    }
+
+    /* all keys that are prefixes of data, shortest first */
+    pub fn prefix_matches<'t, 'd>(&'t self, data: &'d [u8]) -> PrefixMatches<'t, 'd, 'a, V> {
+        PrefixMatches {
+            trie: self,
+            data,
+            node: if self.nodes.is_empty() { None } else { Some(0) },
+            pos: 0,
+        }
+    }
+
+}
+
+/* PrefixMatches ************************************************************/
+/* yields the length of each matching key together with its value */
+pub struct PrefixMatches<'t, 'd, 'a, V> {
+    trie: &'t Trie<'a, V>,
+    data: &'d [u8],
+    node: Option<usize>, // node of data[..pos]
+    pos: usize,
+}
+
+impl<'t, 'd, 'a, V> Iterator for PrefixMatches<'t, 'd, 'a, V> {
+    type Item = (usize, &'t V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.node?;
+            let len = self.pos;
+            self.node = self.data.get(len).and_then(|&b| self.trie.child(node, b));
+            self.pos += 1;
+            if let Some(v) = self.trie.nodes.as_slice()[node].value.as_ref() {
+                return Some((len, v));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+    use crate::mm::no_sup_allocator;
+
+    #[test]
+    fn empty_trie() {
+        let a = no_sup_allocator();
+        let t: Trie<'_, u32> = Trie::new(a.to_ref());
+        assert!(t.is_empty());
+        assert_eq!(t.len(), 0);
+        assert!(t.get(b"").is_none());
+        assert!(t.longest_prefix_match(b"abc").is_none());
+    }
+
+    #[test]
+    fn insert_failure_returns_value() {
+        let a = no_sup_allocator();
+        let mut t: Trie<'_, u32> = Trie::new(a.to_ref());
+        let (e, v) = t.insert(b"abc", 7).unwrap_err();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(v, 7);
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let mut buffer = [0_u8; 2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut t = Trie::new(a.to_ref());
+        assert_eq!(t.insert(b"abc", 1).unwrap(), None);
+        assert_eq!(t.insert(b"abd", 2).unwrap(), None);
+        assert_eq!(t.insert(b"a", 3).unwrap(), None);
+        assert_eq!(t.insert(b"abc", 4).unwrap(), Some(1));
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.get(b"abc"), Some(&4));
+        assert_eq!(t.get(b"abd"), Some(&2));
+        assert_eq!(t.get(b"a"), Some(&3));
+        assert!(t.get(b"ab").is_none());
+        assert!(t.get(b"abcd").is_none());
+        assert!(!t.contains_key(b"b"));
+    }
+
+    #[test]
+    fn get_mut_updates_value() {
+        let mut buffer = [0_u8; 1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut t = Trie::new(a.to_ref());
+        t.insert(b"xy", 10).unwrap();
+        *t.get_mut(b"xy").unwrap() += 5;
+        assert_eq!(t.get(b"xy"), Some(&15));
+        assert!(t.get_mut(b"x").is_none());
+    }
+
+    #[test]
+    fn remove_keeps_other_keys() {
+        let mut buffer = [0_u8; 1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut t = Trie::new(a.to_ref());
+        t.insert(b"ab", 1).unwrap();
+        t.insert(b"abc", 2).unwrap();
+        assert_eq!(t.remove(b"ab"), Some(1));
+        assert_eq!(t.remove(b"ab"), None);
+        assert_eq!(t.len(), 1);
+        assert_eq!(t.get(b"abc"), Some(&2));
+        assert_eq!(t.longest_prefix_match(b"abx"), None);
+    }
+
+    #[test]
+    fn longest_prefix_match() {
+        let mut buffer = [0_u8; 2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut t = Trie::new(a.to_ref());
+        t.insert(b"\x7FELF", "elf").unwrap();
+        t.insert(b"MZ", "dos_exe").unwrap();
+        t.insert(b"MZ\x90", "dos_exe_90").unwrap();
+        assert_eq!(t.longest_prefix_match(b"\x7FELF\x02\x01"), Some((4, &"elf")));
+        assert_eq!(t.longest_prefix_match(b"MZ\x90\x00"), Some((3, &"dos_exe_90")));
+        assert_eq!(t.longest_prefix_match(b"MZP"), Some((2, &"dos_exe")));
+        assert_eq!(t.longest_prefix_match(b"M"), None);
+        assert_eq!(t.longest_prefix_match(b""), None);
+    }
+
+    #[test]
+    fn prefix_matches_shortest_first() {
+        let mut buffer = [0_u8; 2000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut t = Trie::new(a.to_ref());
+        t.insert(b"", 0).unwrap();
+        t.insert(b"PK", 2).unwrap();
+        t.insert(b"PK\x03\x04", 4).unwrap();
+        t.insert(b"PKX", 3).unwrap();
+        let mut m = t.prefix_matches(b"PK\x03\x04\x14");
+        assert_eq!(m.next(), Some((0, &0)));
+        assert_eq!(m.next(), Some((2, &2)));
+        assert_eq!(m.next(), Some((4, &4)));
+        assert_eq!(m.next(), None);
+        assert_eq!(t.prefix_matches(b"P").count(), 1);
+    }
+
+    #[test]
+    fn empty_key_matches_everything() {
+        let mut buffer = [0_u8; 1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut t = Trie::new(a.to_ref());
+        t.insert(b"", 0).unwrap();
+        t.insert(b"q", 1).unwrap();
+        assert_eq!(t.longest_prefix_match(b"zzz"), Some((0, &0)));
+        assert_eq!(t.longest_prefix_match(b"qq"), Some((1, &1)));
+    }
+}
@@ -2,20 +2,12 @@ use core::ptr::NonNull;
 use core::fmt::Display;
 use core::fmt::Formatter;
 use core::cmp::min;
-use core::convert::AsRef;
-use core::convert::AsMut;
-use core::convert::TryInto;
 
 use crate::num::NonZeroUsize;
 use crate::num::Pow2Usize;
 
 use crate::io::stream::Write;
-use crate::io::stream::Read;
-use crate::io::stream::Seek;
-use crate::io::stream::SeekFrom;
-use crate::io::stream::relative_position;
 use crate::io::ErrorCode as IOErrorCode;
-use crate::io::IOError;
 use crate::io::IOResult;
 
 use crate::xc_err;
@@ -24,6 +16,7 @@ use crate::ExecutionContext;
 use super::Allocator;
 use super::AllocatorRef;
 use super::AllocError;
+use super::Box;
 
 #[derive(Debug)]
 pub struct Vector<'a, T> {
@@ -71,6 +64,10 @@ impl<'a, T> Vector<'a, T> {
         self.len == 0
     }
 
+    pub fn allocator(&self) -> AllocatorRef<'a> {
+        self.allocator
+    }
+
     pub fn reserve(&mut self, count: usize) -> Result<(), AllocError> {
         let item_size = core::mem::size_of::<T>();
         debug_assert!(item_size != 0);
@@ -98,6 +95,9 @@ impl<'a, T> Vector<'a, T> {
                     return Ok(());
                 },
                 Err(e) => {
+                    if self.cap != 0 && self.relocate(cap_to_try).is_ok() {
+                        return Ok(());
+                    }
                     if cap_to_try == len_needed {
                         return Err(e);
                     }
@@ -107,6 +107,28 @@ impl<'a, T> Vector<'a, T> {
         }
     }
 
+    /* moves the items to a new block, for allocators that cannot grow the
+     * current one */
+    fn relocate(&mut self, new_cap: usize) -> Result<(), AllocError> {
+        let item_size = core::mem::size_of::<T>();
+        let item_align = Pow2Usize::new(core::mem::align_of::<T>()).unwrap();
+        let new_ptr = unsafe {
+            self.allocator.alloc(
+                NonZeroUsize::new(new_cap * item_size).unwrap(),
+                item_align)?
+        }.cast::<T>();
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.ptr.as_ptr(), new_ptr.as_ptr(), self.len);
+            self.allocator.free(
+                self.ptr.cast::<u8>(),
+                NonZeroUsize::new(self.cap * item_size).unwrap(),
+                item_align);
+        }
+        self.ptr = new_ptr;
+        self.cap = new_cap;
+        Ok(())
+    }
+
     pub fn push(&mut self, v: T) -> Result<(), (AllocError, T)> {
         if let Err(e) = self.reserve(1) {
             return Err((e, v));
@@ -131,6 +153,40 @@ impl<'a, T> Vector<'a, T> {
         }
     }
 
+    pub fn insert(&mut self, index: usize, v: T) -> Result<(), (AllocError, T)> {
+        assert!(index <= self.len, "insert index out of range");
+        if let Err(e) = self.reserve(1) {
+            return Err((e, v));
+        }
+        unsafe {
+            let p = self.ptr.as_ptr().add(index);
+            core::ptr::copy(p, p.add(1), self.len - index);
+            core::ptr::write(p, v);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "remove index out of range");
+        self.len -= 1;
+        unsafe {
+            let p = self.ptr.as_ptr().add(index);
+            let v = core::ptr::read(p);
+            core::ptr::copy(p.add(1), p, self.len - index);
+            v
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            self.len -= 1;
+            unsafe {
+                core::ptr::drop_in_place(self.ptr.as_ptr().add(self.len));
+            }
+        }
+    }
+
     pub fn as_slice(&self) -> &[T] {
         unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
     }
@@ -191,6 +247,42 @@ impl<'a, T> Vector<'a, T> {
     where T: Copy {
         Vector::from_slice(self.as_slice(), allocator)
     }
+
+    /* gives the spare capacity back to the allocator and hands the items
+     * over to a box; mapped slices cannot be converted as they are not
+     * owned */
+    pub fn into_boxed_slice(self) -> Result<Box<'a, [T]>, (AllocError, Self)> {
+        let item_size = core::mem::size_of::<T>();
+        let align = Pow2Usize::new(core::mem::align_of::<T>()).unwrap();
+        if self.cap < self.len {
+            return Err((AllocError::UnsupportedOperation, self));
+        }
+        let ptr = if self.len == self.cap {
+            self.ptr
+        } else if self.len == 0 {
+            unsafe {
+                self.allocator.free(
+                    self.ptr.cast::<u8>(),
+                    NonZeroUsize::new(item_size * self.cap).unwrap(),
+                    align);
+            }
+            NonNull::dangling()
+        } else {
+            match unsafe {
+                self.allocator.shrink(
+                    self.ptr.cast::<u8>(),
+                    NonZeroUsize::new(item_size * self.cap).unwrap(),
+                    NonZeroUsize::new(item_size * self.len).unwrap(),
+                    align)
+            } {
+                Ok(p) => p.cast::<T>(),
+                Err(e) => return Err((e, self)),
+            }
+        };
+        let v = core::mem::ManuallyDrop::new(self);
+        let ptr = core::ptr::slice_from_raw_parts_mut(ptr.as_ptr(), v.len);
+        Ok(unsafe { Box::from_parts(v.allocator, NonNull::new(ptr).unwrap()) })
+    }
 }
 
 impl<'a, T> Drop for Vector<'a, T> {
@@ -254,74 +346,12 @@ impl<'a> Write for Vector<'a, u8> {
     }
 }
 
-/* ByteVectorStream *********************************************************/
-pub struct ByteVectorStream<'a> {
-    data: Vector<'a, u8>,
-    pos: usize,
-}
-
-impl<'a> ByteVectorStream<'a> {
-
-    pub fn new(data: Vector<'a, u8>) -> ByteVectorStream {
-        ByteVectorStream { data, pos: 0 }
-    }
-
-}
-
-impl<'a> AsRef<Vector<'a, u8>> for ByteVectorStream<'a> {
-    fn as_ref(&self) -> &Vector<'a, u8> {
-        &self.data
-    }
-}
-
-impl<'a> AsMut<Vector<'a, u8>> for ByteVectorStream<'a> {
-    fn as_mut(&mut self) -> &mut Vector<'a, u8> {
-        &mut self.data
-    }
-}
-
-impl<'a> Seek for ByteVectorStream<'a> {
-    fn seek<'x>(
-        &mut self,
-        disp: SeekFrom,
-        _xc: &mut ExecutionContext<'x>
-    ) -> IOResult<'x, u64> {
-        self.pos = match disp {
-            SeekFrom::Start(disp) => disp,
-            SeekFrom::Current(disp) => relative_position(self.pos as u64, disp)?,
-            SeekFrom::End(disp) => relative_position(self.data.len() as u64, disp)?,
-        }.try_into().map_err(|_| IOError::with_str(IOErrorCode::UnsupportedPosition,
-                                                   "seek to position too large for usize"))?;
-        Ok(self.pos as u64)
-    }
-}
-
-impl<'a> Read for ByteVectorStream<'a> {
-
-    fn read<'x>(
-        &mut self,
-        buf: &mut [u8],
-        _exe_ctx: &mut ExecutionContext<'x>
-    ) -> IOResult<'x, usize> {
-        if self.pos < self.data.len() {
-            let n = min(self.data.len() - self.pos, buf.len());
-            buf[0..n].copy_from_slice(&self.data.as_slice()[self.pos..self.pos + n]);
-            Ok(n)
-        } else {
-            Ok(0)
-        }
-    }
-
-}
-
-impl<'a> Write for ByteVectorStream<'a> {
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::super::no_sup_allocator;
     use super::super::SingleAlloc;
+    use super::super::FragmentingAllocator;
 
     #[test]
     fn new_vector_is_empty() {
@@ -430,6 +460,64 @@ mod tests {
         assert_eq!(v.cap(), usize::MAX / 2 + 2);
     }
 
+    #[test]
+    fn reserve_moves_items_when_grow_fails() {
+        let mut buffer = [0u8; 512];
+        let a = FragmentingAllocator::new(&mut buffer, NonZeroUsize::new(3).unwrap());
+        let ar = a.to_ref();
+        let mut v = ar.vector::<u32>();
+        for i in 0..20_u32 {
+            v.push(i * 3).unwrap();
+        }
+        assert_eq!(v.len(), 20);
+        assert_eq!(v.cap(), 32);
+        assert!(v.as_slice().iter().enumerate().all(|(i, &x)| x == i as u32 * 3));
+        assert_eq!(a.refused_grows(), 5);
+        assert_eq!(a.live_blocks(), 1);
+        // no room for 64 items: the exact size is tried after the refused grow
+        v.reserve(13).unwrap();
+        assert_eq!(v.cap(), 33);
+        assert_eq!(a.refused_grows(), 7);
+        drop(v);
+        assert_eq!(a.live_blocks(), 0);
+    }
+
+    #[test]
+    fn insert_and_remove_in_the_middle() {
+        let mut buffer = [0u8; 16];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut v = Vector::from_slice(&[1_u16, 2, 4], a.to_ref()).unwrap();
+        v.insert(2, 3).unwrap();
+        v.insert(0, 0).unwrap();
+        v.insert(5, 5).unwrap();
+        assert_eq!(v.as_slice(), [0_u16, 1, 2, 3, 4, 5]);
+        assert_eq!(v.remove(0), 0);
+        assert_eq!(v.remove(4), 5);
+        assert_eq!(v.remove(1), 2);
+        assert_eq!(v.as_slice(), [1_u16, 3, 4]);
+    }
+
+    #[test]
+    fn failed_insert_returns_original_value() {
+        let a = no_sup_allocator();
+        let mut v: Vector<'_, u16> = Vector::new(a.to_ref());
+        let (e, x) = v.insert(0, 0x55AA_u16).unwrap_err();
+        assert_eq!(e, AllocError::UnsupportedOperation);
+        assert_eq!(x, 0x55AA_u16);
+    }
+
+    #[test]
+    fn truncate_drops_tail() {
+        let mut buffer = [0u8; 8];
+        let a = SingleAlloc::new(&mut buffer);
+        let mut v = Vector::from_slice(&[1_u16, 2, 3, 4], a.to_ref()).unwrap();
+        v.truncate(5);
+        assert_eq!(v.len(), 4);
+        v.truncate(1);
+        assert_eq!(v.as_slice(), [1_u16]);
+        assert_eq!(v.cap(), 4);
+    }
+
     #[test]
     fn get_slice_from_vector() {
         let mut buffer = [0u8; 4];
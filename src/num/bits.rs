@@ -0,0 +1,139 @@
+/* bit field access in integers and in byte slices; BitReader in io reads
+ * the same kind of fields from streams */
+
+use super::BitMaskError;
+use super::PrimitiveInt;
+
+/* BitOrder *****************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BitOrder {
+    /* bits are taken from the most significant bit of each byte and the
+     * first bit read is the most significant bit of the value (media
+     * containers, instruction encodings) */
+    MsbFirst,
+    /* bits are taken from the least significant bit of each byte and the
+     * first bit read is the least significant bit of the value (DEFLATE) */
+    LsbFirst,
+}
+
+/* the count bits of value starting at bit pos (0 being the least
+ * significant), right-aligned */
+pub fn extract_bits<T: PrimitiveInt>(
+    value: T,
+    pos: usize,
+    count: usize,
+) -> Result<T, BitMaskError> {
+    T::try_incl_bit_range_mask(pos, count)?;
+    Ok(if count == 0 { T::ZERO } else { (value >> pos) & T::lsb_mask(count) })
+}
+
+/* value with the count bits starting at bit pos replaced by the low bits of
+ * field; the bits of field above count are ignored */
+pub fn insert_bits<T: PrimitiveInt>(
+    value: T,
+    pos: usize,
+    count: usize,
+    field: T,
+) -> Result<T, BitMaskError> {
+    let mask = T::try_incl_bit_range_mask(pos, count)?;
+    Ok(if count == 0 { value } else { (value & !mask) | ((field << pos) & mask) })
+}
+
+/* the byte and the shift inside it of a bit in a slice */
+fn slice_bit(bit_pos: usize, order: BitOrder) -> (usize, u32) {
+    let sh = (bit_pos % 8) as u32;
+    (bit_pos / 8, match order {
+        BitOrder::MsbFirst => 7 - sh,
+        BitOrder::LsbFirst => sh,
+    })
+}
+
+fn check_slice_range(len: usize, bit_pos: usize, count: usize) -> Option<()> {
+    let end = bit_pos.checked_add(count)?;
+    if count <= 64 && end <= len.saturating_mul(8) {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/* a field of count bits (at most 64) starting at bit_pos of the slice, read
+ * in the given order as BitReader does; None if it does not fit */
+pub fn extract_slice_bits(
+    bytes: &[u8],
+    bit_pos: usize,
+    count: usize,
+    order: BitOrder,
+) -> Option<u64> {
+    check_slice_range(bytes.len(), bit_pos, count)?;
+    let mut v = 0_u64;
+    for i in 0..count {
+        let (index, sh) = slice_bit(bit_pos + i, order);
+        let bit = ((bytes[index] >> sh) & 1) as u64;
+        v = match order {
+            BitOrder::MsbFirst => (v << 1) | bit,
+            BitOrder::LsbFirst => v | (bit << i),
+        };
+    }
+    Some(v)
+}
+
+/* the reverse of extract_slice_bits(); the bits of value above count are
+ * ignored and the slice is left untouched if the field does not fit */
+pub fn insert_slice_bits(
+    bytes: &mut [u8],
+    bit_pos: usize,
+    count: usize,
+    value: u64,
+    order: BitOrder,
+) -> Option<()> {
+    check_slice_range(bytes.len(), bit_pos, count)?;
+    for i in 0..count {
+        let (index, sh) = slice_bit(bit_pos + i, order);
+        let bit = match order {
+            BitOrder::MsbFirst => (value >> (count - 1 - i)) & 1,
+            BitOrder::LsbFirst => (value >> i) & 1,
+        } as u8;
+        bytes[index] = (bytes[index] & !(1 << sh)) | (bit << sh);
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_fields() {
+        assert_eq!(extract_bits(0xABCD_u16, 4, 8), Ok(0xBC));
+        assert_eq!(extract_bits(0xABCD_u16, 12, 4), Ok(0xA));
+        assert_eq!(extract_bits(-1_i8, 0, 8), Ok(-1));
+        assert_eq!(extract_bits(-1_i32, 28, 4), Ok(0xF));
+        assert_eq!(extract_bits(7_u8, 8, 0), Ok(0));
+        assert_eq!(extract_bits(7_u8, 5, 4), Err(BitMaskError::TooManyBits));
+        assert_eq!(extract_bits(7_u8, usize::MAX, 1), Err(BitMaskError::RangeOverflow));
+        assert_eq!(insert_bits(0xABCD_u16, 4, 8, 0x1FF), Ok(0xAFFD));
+        assert_eq!(insert_bits(0_u64, 60, 4, 0xC), Ok(0xC000_0000_0000_0000));
+        assert_eq!(insert_bits(5_u8, 8, 0, 1), Ok(5));
+        assert_eq!(insert_bits(5_u32, 30, 3, 1), Err(BitMaskError::TooManyBits));
+    }
+
+    #[test]
+    fn slice_fields() {
+        let bytes = [0b1011_0011, 0b1100_0101];
+        assert_eq!(extract_slice_bits(&bytes, 0, 3, BitOrder::MsbFirst), Some(0b101));
+        assert_eq!(extract_slice_bits(&bytes, 4, 6, BitOrder::MsbFirst), Some(0b00_1111));
+        assert_eq!(extract_slice_bits(&bytes, 3, 7, BitOrder::LsbFirst), Some(0b0110110));
+        assert_eq!(extract_slice_bits(&bytes, 0, 16, BitOrder::LsbFirst), Some(0xC5B3));
+        assert_eq!(extract_slice_bits(&bytes, 9, 8, BitOrder::MsbFirst), None);
+        assert_eq!(extract_slice_bits(&[0; 9], 0, 65, BitOrder::MsbFirst), None);
+
+        let mut out = [0xFF_u8; 2];
+        insert_slice_bits(&mut out, 4, 6, 0b00_1111, BitOrder::MsbFirst).unwrap();
+        assert_eq!(out, [0b1111_0011, 0b1111_1111]);
+        insert_slice_bits(&mut out, 3, 7, 0, BitOrder::LsbFirst).unwrap();
+        assert_eq!(out, [0b0000_0011, 0b1111_1100]);
+        assert!(insert_slice_bits(&mut out, 15, 2, 0, BitOrder::LsbFirst).is_none());
+        assert_eq!(out, [0b0000_0011, 0b1111_1100]);
+    }
+}
@@ -0,0 +1,218 @@
+/* decimal fixed-point numbers for formats that store durations and
+ * measurements as scaled integers; they stay exact where going through f64
+ * would round */
+
+use core::convert::TryFrom;
+use core::ops::Add;
+use core::ops::Div;
+use core::ops::Mul;
+use core::ops::Neg;
+use core::ops::Sub;
+
+use crate::ExecutionContext;
+use crate::io::IOResult;
+use crate::io::stream::Write;
+use super::fmt::MiniNumFmtPack;
+
+/* FixedPoint ***************************************************************/
+/* a count of units of 10^-SCALE; SCALE goes up to 18 so that one whole
+ * still fits the i64 */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixedPoint<const SCALE: u32> {
+    raw: i64,
+}
+
+impl<const SCALE: u32> FixedPoint<SCALE> {
+
+    pub const UNIT: i64 = {
+        assert!(SCALE <= 18);
+        10_i64.pow(SCALE)
+    };
+    pub const ZERO: Self = FixedPoint { raw: 0 };
+    pub const ONE: Self = FixedPoint { raw: Self::UNIT };
+    pub const MIN: Self = FixedPoint { raw: i64::MIN };
+    pub const MAX: Self = FixedPoint { raw: i64::MAX };
+
+    pub const fn from_raw(raw: i64) -> Self {
+        FixedPoint { raw }
+    }
+
+    pub const fn raw(self) -> i64 {
+        self.raw
+    }
+
+    pub fn from_int(n: i64) -> Option<Self> {
+        n.checked_mul(Self::UNIT).map(Self::from_raw)
+    }
+
+    /* num / den rounded toward zero, as for a duration over a timescale */
+    pub fn from_ratio(num: i64, den: i64) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        Self::from_wide(num as i128 * Self::UNIT as i128 / den as i128)
+    }
+
+    fn from_wide(raw: i128) -> Option<Self> {
+        i64::try_from(raw).ok().map(Self::from_raw)
+    }
+
+    /* the integer part, rounded toward zero */
+    pub fn trunc(self) -> i64 {
+        self.raw / Self::UNIT
+    }
+
+    /* what is left after the integer part, with the sign of self */
+    pub fn fract(self) -> Self {
+        Self::from_raw(self.raw % Self::UNIT)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.raw.checked_add(other.raw).map(Self::from_raw)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.raw.checked_sub(other.raw).map(Self::from_raw)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.raw.checked_neg().map(Self::from_raw)
+    }
+
+    /* products and quotients are rounded toward zero */
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        Self::from_wide(self.raw as i128 * other.raw as i128 / Self::UNIT as i128)
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.raw == 0 {
+            return None;
+        }
+        Self::from_wide(self.raw as i128 * Self::UNIT as i128 / other.raw as i128)
+    }
+
+    /* the same value with another scale; dropped digits are truncated */
+    pub fn rescale<const S: u32>(self) -> Option<FixedPoint<S>> {
+        FixedPoint::<S>::from_wide(
+            self.raw as i128 * FixedPoint::<S>::UNIT as i128 / Self::UNIT as i128)
+    }
+
+    /* always decimal with SCALE digits after the point; see
+     * MiniNumFmtPack::fixed_fmt() */
+    pub fn fmt(
+        self,
+        fmt_pack: MiniNumFmtPack,
+        buf: &mut [u8],
+    ) -> Result<&str, core::fmt::Error> {
+        fmt_pack.fixed_fmt(self.raw, SCALE, buf)
+    }
+
+    pub fn write<'x>(
+        self,
+        fmt_pack: MiniNumFmtPack,
+        out: &mut (dyn Write + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+        let s = self.fmt(fmt_pack, &mut buf).unwrap();
+        out.write_all(s.as_bytes(), xc).map_err(|e| e.to_error())
+    }
+
+}
+
+/* operators panic on overflow like the checked_* methods returning None */
+impl<const SCALE: u32> Add for FixedPoint<SCALE> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other).expect("fixed-point overflow")
+    }
+}
+
+impl<const SCALE: u32> Sub for FixedPoint<SCALE> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other).expect("fixed-point overflow")
+    }
+}
+
+impl<const SCALE: u32> Neg for FixedPoint<SCALE> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        self.checked_neg().expect("fixed-point overflow")
+    }
+}
+
+impl<const SCALE: u32> Mul for FixedPoint<SCALE> {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        self.checked_mul(other).expect("fixed-point overflow")
+    }
+}
+
+impl<const SCALE: u32> Div for FixedPoint<SCALE> {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        self.checked_div(other).expect("fixed-point division by zero or overflow")
+    }
+}
+
+impl<const SCALE: u32> core::fmt::Display for FixedPoint<SCALE> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+        let s = MiniNumFmtPack::default().fixed_fmt(self.raw, SCALE, &mut buf)?;
+        f.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::fmt::FieldWidth;
+    use crate::num::fmt::GroupSeparator;
+    use crate::num::fmt::GroupSize;
+    use crate::num::fmt::PadFill;
+
+    type Milli = FixedPoint<3>;
+
+    #[test]
+    fn exact_arithmetic() {
+        let a = Milli::from_raw(1001);
+        let b = Milli::from_ratio(1, 3).unwrap();
+        assert_eq!(b.raw(), 333);
+        assert_eq!((a + b).raw(), 1334);
+        assert_eq!((b - a).raw(), -668);
+        assert_eq!((a * Milli::from_int(3).unwrap()).raw(), 3003);
+        assert_eq!((a / Milli::from_raw(500)).raw(), 2002);
+        assert_eq!((-a).trunc(), -1);
+        assert_eq!((-a).fract().raw(), -1);
+        assert!(b < a && -a < b);
+        assert_eq!(Milli::from_ratio(90_000 * 61 + 45, 90_000).unwrap().raw(), 61_000);
+        assert_eq!(Milli::from_ratio(1, 0), None);
+        assert_eq!(Milli::MAX.checked_add(Milli::from_raw(1)), None);
+        assert_eq!(Milli::MAX.checked_mul(Milli::from_int(2).unwrap()), None);
+        assert_eq!(Milli::ONE.checked_div(Milli::ZERO), None);
+        assert_eq!(Milli::from_int(i64::MAX / 100), None);
+        assert_eq!(a.rescale::<6>(), Some(FixedPoint::<6>::from_raw(1_001_000)));
+        assert_eq!(Milli::from_raw(-1999).rescale::<0>(), Some(FixedPoint::<0>::from_raw(-1)));
+        assert_eq!(Milli::MAX.rescale::<18>(), None);
+    }
+
+    #[test]
+    fn formatting() {
+        let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+        let fmt = MiniNumFmtPack::default();
+        assert_eq!(Milli::from_raw(1001).fmt(fmt, &mut buf), Ok("1.001"));
+        assert_eq!(Milli::from_raw(-5).fmt(fmt, &mut buf), Ok("-0.005"));
+        assert_eq!(FixedPoint::<0>::from_raw(42).fmt(fmt, &mut buf), Ok("42"));
+        assert_eq!(FixedPoint::<18>::MIN.fmt(fmt, &mut buf), Ok("-9.223372036854775808"));
+        let fmt = fmt
+            .with_grouping(GroupSize::new(3).unwrap(), GroupSeparator::Comma)
+            .with_padding(FieldWidth::new(12).unwrap(), PadFill::Space);
+        assert_eq!(Milli::from_raw(-1234567).fmt(fmt, &mut buf), Ok("  -1,234.567"));
+        let fmt = fmt.with_padding(FieldWidth::new(9).unwrap(), PadFill::Zero);
+        assert_eq!(Milli::from_raw(12345).fmt(fmt, &mut buf), Ok("0,012.345"));
+        extern crate std;
+        use std::string::ToString;
+        assert_eq!(FixedPoint::<2>::from_raw(-150).to_string(), "-1.50");
+    }
+}
@@ -1,9 +1,13 @@
 use core::num::NonZeroU8;
+use core::num::NonZeroU16;
 use core::num::NonZeroU32;
 use core::str;
 use core::convert::{ TryFrom, TryInto };
 use crate::num::PrimitiveInt;
 use crate::num::PrimitiveUInt;
+use crate::io::IOResult;
+use crate::io::stream::Write;
+use crate::ExecutionContext;
 
 #[derive(Clone, Copy,  Debug, PartialEq)]
 pub struct Radix(NonZeroU8);
@@ -206,6 +210,124 @@ impl From<ZeroSign> for u8 {
     fn from(v: ZeroSign) -> u8 { v as u8 }
 }
 
+/* digits per group, 0 meaning no grouping */
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct GroupSize(u8);
+impl GroupSize {
+    pub fn new(n: u8) -> Option<Self> {
+        if n <= 7 { Some(GroupSize(n)) } else { None }
+    }
+    pub fn none() -> Self {
+        GroupSize(0)
+    }
+    pub fn unwrap(self) -> usize {
+        self.0.into()
+    }
+}
+impl From<GroupSize> for u8 {
+    fn from(v: GroupSize) -> u8 { v.0 }
+}
+impl TryFrom<u8> for GroupSize {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        GroupSize::new(v).ok_or(())
+    }
+}
+
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub enum GroupSeparator {
+    Underscore,
+    Comma,
+    Space,
+    Apostrophe,
+}
+impl GroupSeparator {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            GroupSeparator::Underscore => b'_',
+            GroupSeparator::Comma => b',',
+            GroupSeparator::Space => b' ',
+            GroupSeparator::Apostrophe => b'\'',
+        }
+    }
+}
+impl From<GroupSeparator> for u8 {
+    fn from(v: GroupSeparator) -> u8 { v as u8 }
+}
+impl TryFrom<u8> for GroupSeparator {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(GroupSeparator::Underscore),
+            1 => Ok(GroupSeparator::Comma),
+            2 => Ok(GroupSeparator::Space),
+            3 => Ok(GroupSeparator::Apostrophe),
+            _ => Err(())
+        }
+    }
+}
+
+/* length the text is padded to, 0 meaning no padding */
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct FieldWidth(u8);
+impl FieldWidth {
+    pub fn new(n: u8) -> Option<Self> {
+        if n <= 31 { Some(FieldWidth(n)) } else { None }
+    }
+    pub fn unwrap(self) -> usize {
+        self.0.into()
+    }
+}
+impl From<FieldWidth> for u8 {
+    fn from(v: FieldWidth) -> u8 { v.0 }
+}
+impl TryFrom<u8> for FieldWidth {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        FieldWidth::new(v).ok_or(())
+    }
+}
+
+/* spaces go before the sign, zeros between the radix prefix and the
+ * digits, as more digits */
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub enum PadFill {
+    Space,
+    Zero,
+}
+impl From<PadFill> for u8 {
+    fn from(v: PadFill) -> u8 { v as u8 }
+}
+impl TryFrom<u8> for PadFill {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(PadFill::Space),
+            1 => Ok(PadFill::Zero),
+            _ => Err(())
+        }
+    }
+}
+
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub enum DigitCase {
+    Upper,
+    Lower,
+}
+impl From<DigitCase> for u8 {
+    fn from(v: DigitCase) -> u8 { v as u8 }
+}
+impl TryFrom<u8> for DigitCase {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(DigitCase::Upper),
+            1 => Ok(DigitCase::Lower),
+            _ => Err(())
+        }
+    }
+}
+
 struct ReverseFillBuffer<'a> {
     buf: &'a mut [u8],
     pos: usize,
@@ -259,6 +381,27 @@ impl MiniNumFmtPack {
     const ZERO_SIGN_BIT_POS: u8 = Self::POSITIVE_SIGN_BIT_POS + Self::POSITIVE_SIGN_BIT_COUNT;
     const ZERO_SIGN_BIT_COUNT: u8 = 2;
 
+    const GROUP_SIZE_BIT_POS: u8 = Self::ZERO_SIGN_BIT_POS + Self::ZERO_SIGN_BIT_COUNT;
+    const GROUP_SIZE_BIT_COUNT: u8 = 3;
+
+    const GROUP_SEPARATOR_BIT_POS: u8 = Self::GROUP_SIZE_BIT_POS + Self::GROUP_SIZE_BIT_COUNT;
+    const GROUP_SEPARATOR_BIT_COUNT: u8 = 2;
+
+    const FIELD_WIDTH_BIT_POS: u8 = Self::GROUP_SEPARATOR_BIT_POS + Self::GROUP_SEPARATOR_BIT_COUNT;
+    const FIELD_WIDTH_BIT_COUNT: u8 = 5;
+
+    const PAD_FILL_BIT_POS: u8 = Self::FIELD_WIDTH_BIT_POS + Self::FIELD_WIDTH_BIT_COUNT;
+    const PAD_FILL_BIT_COUNT: u8 = 1;
+
+    const DIGIT_CASE_BIT_POS: u8 = Self::PAD_FILL_BIT_POS + Self::PAD_FILL_BIT_COUNT;
+    const DIGIT_CASE_BIT_COUNT: u8 = 1;
+
+    const USED_BIT_COUNT: u8 = Self::DIGIT_CASE_BIT_POS + Self::DIGIT_CASE_BIT_COUNT;
+
+    /* longest text int_fmt produces: sign, "0rNN_" prefix, 128 digits and
+     * a separator between each two of them; padding never exceeds that */
+    pub const MAX_TEXT_LEN: usize = 1 + 5 + 128 + 127;
+
     pub fn new(
         radix: Radix,
         radix_notation: RadixNotation,
@@ -281,10 +424,24 @@ impl MiniNumFmtPack {
     fn get_bits_u8(self, pos: u8, count: u8) -> u8 {
         self.get_bits(pos, count).try_into().unwrap()
     }
-    // fn set_bits(&mut self, pos: u8, count: u8, v: u32) {
-    //     assert!((v & u32::lsb_mask(count.into())) == v);
-    //     self.pack = NonZeroU32::new((self.pack.get() & u32::excl_bit_range_mask(pos.into(), count.into())) | (v << pos)).unwrap();
-    // }
+    fn with_bits(self, pos: u8, count: u8, v: u8) -> Self {
+        let v = v as u32;
+        assert!((v & u32::lsb_mask(count.into())) == v);
+        let rest = self.pack.get() & u32::excl_bit_range_mask(pos.into(), count.into());
+        MiniNumFmtPack { pack: NonZeroU32::new(rest | (v << pos)).unwrap() }
+    }
+    pub fn with_grouping(self, size: GroupSize, separator: GroupSeparator) -> Self {
+        self.with_bits(Self::GROUP_SIZE_BIT_POS, Self::GROUP_SIZE_BIT_COUNT, size.into())
+            .with_bits(Self::GROUP_SEPARATOR_BIT_POS, Self::GROUP_SEPARATOR_BIT_COUNT,
+                       separator.into())
+    }
+    pub fn with_padding(self, width: FieldWidth, fill: PadFill) -> Self {
+        self.with_bits(Self::FIELD_WIDTH_BIT_POS, Self::FIELD_WIDTH_BIT_COUNT, width.into())
+            .with_bits(Self::PAD_FILL_BIT_POS, Self::PAD_FILL_BIT_COUNT, fill.into())
+    }
+    pub fn with_digit_case(self, case: DigitCase) -> Self {
+        self.with_bits(Self::DIGIT_CASE_BIT_POS, Self::DIGIT_CASE_BIT_COUNT, case.into())
+    }
     pub fn default() -> MiniNumFmtPack {
         MiniNumFmtPack::new(
             Radix::new(10).unwrap(),
@@ -293,6 +450,32 @@ impl MiniNumFmtPack {
             PositiveSign::Hidden,
             ZeroSign::Hidden)
     }
+    /* the packed fields, for storing the format along with a number */
+    pub fn to_u32(self) -> u32 {
+        self.pack.get()
+    }
+    pub fn from_u32(v: u32) -> Option<MiniNumFmtPack> {
+        if v.checked_shr(Self::USED_BIT_COUNT.into()).unwrap_or(0) != 0 {
+            return None;
+        }
+        let bits = |pos: u8, count: u8| -> u8 {
+            ((v >> pos) & u32::lsb_mask(count.into())).try_into().unwrap()
+        };
+        Some(MiniNumFmtPack::new(
+            Radix::new(bits(Self::RADIX_BIT_POS, Self::RADIX_BIT_COUNT))?,
+            bits(Self::RADIX_NOTATION_BIT_POS, Self::RADIX_NOTATION_BIT_COUNT).try_into().ok()?,
+            MinDigitCount::new(bits(Self::MIN_DIGIT_COUNT_BIT_POS, Self::MIN_DIGIT_COUNT_BIT_COUNT))?,
+            bits(Self::POSITIVE_SIGN_BIT_POS, Self::POSITIVE_SIGN_BIT_COUNT).try_into().ok()?,
+            bits(Self::ZERO_SIGN_BIT_POS, Self::ZERO_SIGN_BIT_COUNT).try_into().ok()?)
+            .with_grouping(
+                bits(Self::GROUP_SIZE_BIT_POS, Self::GROUP_SIZE_BIT_COUNT).try_into().ok()?,
+                bits(Self::GROUP_SEPARATOR_BIT_POS, Self::GROUP_SEPARATOR_BIT_COUNT).try_into().ok()?)
+            .with_padding(
+                bits(Self::FIELD_WIDTH_BIT_POS, Self::FIELD_WIDTH_BIT_COUNT).try_into().ok()?,
+                bits(Self::PAD_FILL_BIT_POS, Self::PAD_FILL_BIT_COUNT).try_into().ok()?)
+            .with_digit_case(
+                bits(Self::DIGIT_CASE_BIT_POS, Self::DIGIT_CASE_BIT_COUNT).try_into().ok()?))
+    }
     pub fn get_radix(self) -> Radix {
         Radix::new(self.get_bits_u8(Self::RADIX_BIT_POS, Self::RADIX_BIT_COUNT)).unwrap()
     }
@@ -308,6 +491,21 @@ impl MiniNumFmtPack {
     pub fn get_zero_sign(self) -> ZeroSign {
         self.get_bits_u8(Self::ZERO_SIGN_BIT_POS, Self::ZERO_SIGN_BIT_COUNT).try_into().unwrap()
     }
+    pub fn get_group_size(self) -> GroupSize {
+        self.get_bits_u8(Self::GROUP_SIZE_BIT_POS, Self::GROUP_SIZE_BIT_COUNT).try_into().unwrap()
+    }
+    pub fn get_group_separator(self) -> GroupSeparator {
+        self.get_bits_u8(Self::GROUP_SEPARATOR_BIT_POS, Self::GROUP_SEPARATOR_BIT_COUNT).try_into().unwrap()
+    }
+    pub fn get_field_width(self) -> FieldWidth {
+        self.get_bits_u8(Self::FIELD_WIDTH_BIT_POS, Self::FIELD_WIDTH_BIT_COUNT).try_into().unwrap()
+    }
+    pub fn get_pad_fill(self) -> PadFill {
+        self.get_bits_u8(Self::PAD_FILL_BIT_POS, Self::PAD_FILL_BIT_COUNT).try_into().unwrap()
+    }
+    pub fn get_digit_case(self) -> DigitCase {
+        self.get_bits_u8(Self::DIGIT_CASE_BIT_POS, Self::DIGIT_CASE_BIT_COUNT).try_into().unwrap()
+    }
 
     pub fn int_fmt<'a, T: IntFmt>(
         self,
@@ -316,16 +514,119 @@ impl MiniNumFmtPack {
     ) -> Result<&'a str, ()> {
         let radix = self.get_radix();
         let radix_prefix = self.get_radix_notation().prefix(radix);
-        let min_digit_count = self.get_min_digit_count();
-        let positive_sign = self.get_positive_sign();
-        let zero_sign = self.get_zero_sign();
-        n.int_fmt_buf(
+        let mut plain_buf = [0_u8; 1 + 5 + 128];
+        let plain = n.int_fmt_buf(
             radix,
             radix_prefix,
-            min_digit_count,
-            positive_sign,
-            zero_sign,
-            buf)
+            self.get_min_digit_count(),
+            self.get_positive_sign(),
+            self.get_zero_sign(),
+            &mut plain_buf)?.as_bytes();
+        let sign_len = match plain.first() {
+            Some(b' ') | Some(b'+') | Some(b'-') => 1,
+            _ => 0,
+        };
+        let (head, digits) = plain.split_at(sign_len + radix_prefix.len());
+        self.layout(head, digits, b"", buf)
+    }
+
+    /* a decimal fixed-point number given as a count of units of
+     * 10^-scale; the radix is always 10, the sign, minimum digit count and
+     * grouping apply to the integer part and the padding to the whole text */
+    pub fn fixed_fmt(
+        self,
+        raw: i64,
+        scale: u32,
+        buf: &mut [u8],
+    ) -> Result<&str, core::fmt::Error> {
+        let unit = 10_u64.checked_pow(scale).ok_or(core::fmt::Error)?;
+        let m = raw.unsigned_abs();
+        let mut int_buf = [0_u8; 1 + 20];
+        let mut int = (m / unit).uint_fmt_buf(
+            Radix::new(10).unwrap(), "", self.get_min_digit_count(), &mut int_buf)
+            .map_err(|_| core::fmt::Error)?;
+        if raw < 0 {
+            int.push(b'-')
+        } else if raw == 0 {
+            self.get_zero_sign().push_sign(&mut int)
+        } else {
+            self.get_positive_sign().push_sign(&mut int)
+        }.map_err(|_| core::fmt::Error)?;
+        let plain = int.to_used_slice();
+        let sign_len = match plain.first() {
+            Some(b' ') | Some(b'+') | Some(b'-') => 1,
+            _ => 0,
+        };
+        let (head, digits) = plain.split_at(sign_len);
+        let mut tail = [0_u8; 1 + 20];
+        let mut tail_len = 0;
+        if scale != 0 {
+            tail_len = 1 + scale as usize;
+            tail[0] = b'.';
+            let mut frac = m % unit;
+            for b in tail[1..tail_len].iter_mut().rev() {
+                *b = b'0' + (frac % 10) as u8;
+                frac /= 10;
+            }
+        }
+        self.layout(head, digits, &tail[0..tail_len], buf).map_err(|_| core::fmt::Error)
+    }
+
+    /* the sign and prefix followed by the digits, grouped and padded, and
+     * by the tail as given */
+    fn layout<'a>(
+        self,
+        head: &[u8],
+        digits: &[u8],
+        tail: &[u8],
+        buf: &'a mut [u8],
+    ) -> Result<&'a str, ()> {
+        let group_size = self.get_group_size().unwrap();
+        let grouped_len = |n: usize| if group_size == 0 || n == 0 {
+            n
+        } else {
+            n + (n - 1) / group_size
+        };
+        let width = self.get_field_width().unwrap();
+        let mut digit_count = digits.len();
+        if self.get_pad_fill() == PadFill::Zero {
+            while head.len() + grouped_len(digit_count + 1) + tail.len() <= width {
+                digit_count += 1;
+            }
+        }
+        let len = head.len() + grouped_len(digit_count) + tail.len();
+        let space_count = width.saturating_sub(len);
+        let buf = buf.get_mut(0..space_count + len).ok_or(())?;
+        let (spaces, rest) = buf.split_at_mut(space_count);
+        spaces.fill(b' ');
+        let (head_out, rest) = rest.split_at_mut(head.len());
+        head_out.copy_from_slice(head);
+        let (digits_out, tail_out) = rest.split_at_mut(rest.len() - tail.len());
+        tail_out.copy_from_slice(tail);
+        let separator = self.get_group_separator().to_byte();
+        let lower = self.get_digit_case() == DigitCase::Lower;
+        let mut src = digits.iter().rev();
+        for (pos, b) in digits_out.iter_mut().rev().enumerate() {
+            *b = if group_size != 0 && pos % (group_size + 1) == group_size {
+                separator
+            } else {
+                let d = src.next().copied().unwrap_or(b'0');
+                if lower { d.to_ascii_lowercase() } else { d }
+            };
+        }
+        str::from_utf8(buf).map_err(|_| ())
+    }
+
+    /* formats the number straight into the stream */
+    pub fn write_int<'x, T: IntFmt>(
+        self,
+        n: T,
+        out: &mut (dyn Write + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut buf = [0_u8; Self::MAX_TEXT_LEN];
+        let s = self.int_fmt(n, &mut buf).unwrap();
+        out.write_all(s.as_bytes(), xc).map_err(|e| e.to_error())
     }
 }
 
@@ -401,6 +702,348 @@ impl<T: PrimitiveInt> IntFmt for T {
     }
 }
 
+/* MiniFloatFmtPack *********************************************************/
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub enum FloatNotation {
+    Shortest = 1, // fewest digits that read back as the same value
+    Fixed = 2, // precision digits after the point
+    Scientific = 3, // one digit before the point, precision after it
+}
+impl From<FloatNotation> for u8 {
+    fn from(v: FloatNotation) -> u8 { v as u8 }
+}
+impl TryFrom<u8> for FloatNotation {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            1 => Ok(FloatNotation::Shortest),
+            2 => Ok(FloatNotation::Fixed),
+            3 => Ok(FloatNotation::Scientific),
+            _ => Err(())
+        }
+    }
+}
+
+/* digits after the decimal point; ignored by the shortest notation */
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct FloatPrecision(u8);
+impl FloatPrecision {
+    pub fn new(n: u8) -> Option<Self> {
+        if n <= 63 { Some(FloatPrecision(n)) } else { None }
+    }
+    pub fn unwrap(self) -> usize {
+        self.0.into()
+    }
+}
+impl From<FloatPrecision> for u8 {
+    fn from(v: FloatPrecision) -> u8 { v.0 }
+}
+impl TryFrom<u8> for FloatPrecision {
+    type Error = ();
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        FloatPrecision::new(v).ok_or(())
+    }
+}
+
+/* core::fmt::Write over a caller buffer; fails once the buffer is full */
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+impl core::fmt::Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.buf.get_mut(self.len..end).ok_or(core::fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct MiniFloatFmtPack {
+    pack: NonZeroU16,
+}
+impl Default for MiniFloatFmtPack {
+    fn default() -> Self {
+        MiniFloatFmtPack::new(
+            FloatNotation::Shortest,
+            FloatPrecision::new(0).unwrap(),
+            PositiveSign::Hidden)
+    }
+}
+impl MiniFloatFmtPack {
+
+    const NOTATION_BIT_POS: u8 = 0;
+    const NOTATION_BIT_COUNT: u8 = 2;
+
+    const PRECISION_BIT_POS: u8 = Self::NOTATION_BIT_POS + Self::NOTATION_BIT_COUNT;
+    const PRECISION_BIT_COUNT: u8 = 6;
+
+    const POSITIVE_SIGN_BIT_POS: u8 = Self::PRECISION_BIT_POS + Self::PRECISION_BIT_COUNT;
+    const POSITIVE_SIGN_BIT_COUNT: u8 = 2;
+
+    const USED_BIT_COUNT: u8 = Self::POSITIVE_SIGN_BIT_POS + Self::POSITIVE_SIGN_BIT_COUNT;
+
+    /* longest text float_fmt produces: sign, 309 digits of f64::MAX, the
+     * point and 63 decimals */
+    pub const MAX_TEXT_LEN: usize = 1 + 309 + 1 + 63;
+
+    pub fn new(
+        notation: FloatNotation,
+        precision: FloatPrecision,
+        positive_sign: PositiveSign,
+    ) -> MiniFloatFmtPack {
+        MiniFloatFmtPack {
+            pack: NonZeroU16::new(
+                ((u8::from(notation) as u16) << Self::NOTATION_BIT_POS) |
+                ((u8::from(precision) as u16) << Self::PRECISION_BIT_POS) |
+                ((u8::from(positive_sign) as u16) << Self::POSITIVE_SIGN_BIT_POS)).unwrap()
+        }
+    }
+    pub fn fixed(precision: u8) -> Option<MiniFloatFmtPack> {
+        Some(MiniFloatFmtPack::new(
+            FloatNotation::Fixed,
+            FloatPrecision::new(precision)?,
+            PositiveSign::Hidden))
+    }
+    fn get_bits_u8(self, pos: u8, count: u8) -> u8 {
+        ((self.pack.get() >> pos) & u16::lsb_mask(count.into())).try_into().unwrap()
+    }
+    pub fn to_u16(self) -> u16 {
+        self.pack.get()
+    }
+    pub fn from_u16(v: u16) -> Option<MiniFloatFmtPack> {
+        if v >> Self::USED_BIT_COUNT != 0 {
+            return None;
+        }
+        let bits = |pos: u8, count: u8| -> u8 {
+            ((v >> pos) & u16::lsb_mask(count.into())).try_into().unwrap()
+        };
+        Some(MiniFloatFmtPack::new(
+            bits(Self::NOTATION_BIT_POS, Self::NOTATION_BIT_COUNT).try_into().ok()?,
+            bits(Self::PRECISION_BIT_POS, Self::PRECISION_BIT_COUNT).try_into().ok()?,
+            bits(Self::POSITIVE_SIGN_BIT_POS, Self::POSITIVE_SIGN_BIT_COUNT).try_into().ok()?))
+    }
+    pub fn get_notation(self) -> FloatNotation {
+        self.get_bits_u8(Self::NOTATION_BIT_POS, Self::NOTATION_BIT_COUNT).try_into().unwrap()
+    }
+    pub fn get_precision(self) -> FloatPrecision {
+        self.get_bits_u8(Self::PRECISION_BIT_POS, Self::PRECISION_BIT_COUNT).try_into().unwrap()
+    }
+    pub fn get_positive_sign(self) -> PositiveSign {
+        self.get_bits_u8(Self::POSITIVE_SIGN_BIT_POS, Self::POSITIVE_SIGN_BIT_COUNT).try_into().unwrap()
+    }
+
+    /* NaN gets no sign; the digits come from core::fmt, which needs
+     * neither std nor an allocator; fails if the buffer is too small */
+    pub fn float_fmt(self, v: f64, buf: &mut [u8]) -> Result<&str, core::fmt::Error> {
+        use core::fmt::Write as _;
+        let mut w = SliceWriter { buf, len: 0 };
+        if !v.is_nan() && v.is_sign_positive() {
+            let sign = match self.get_positive_sign() {
+                PositiveSign::Hidden => "",
+                PositiveSign::Space => " ",
+                PositiveSign::Plus => "+",
+            };
+            w.write_str(sign)?;
+        }
+        let precision = self.get_precision().unwrap();
+        match self.get_notation() {
+            FloatNotation::Shortest => write!(w, "{:?}", v),
+            FloatNotation::Fixed => write!(w, "{:.*}", precision, v),
+            FloatNotation::Scientific => write!(w, "{:.*e}", precision, v),
+        }?;
+        let SliceWriter { buf, len } = w;
+        str::from_utf8(&buf[..len]).map_err(|_| core::fmt::Error)
+    }
+
+    pub fn write_float<'x>(
+        self,
+        v: f64,
+        out: &mut (dyn Write + '_),
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOResult<'x, ()> {
+        let mut buf = [0_u8; Self::MAX_TEXT_LEN];
+        let s = self.float_fmt(v, &mut buf).unwrap();
+        out.write_all(s.as_bytes(), xc).map_err(|e| e.to_error())
+    }
+}
+
+/* human_size / human_duration *********************************************/
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub enum SizeUnits {
+    Binary, // KiB, MiB... in powers of 1024
+    Decimal, // kB, MB... in powers of 1000
+}
+
+/* value / div with one decimal, rounded to nearest */
+fn write_tenths(
+    f: &mut core::fmt::Formatter<'_>,
+    value: u64,
+    div: u64,
+    unit: &str,
+) -> core::fmt::Result {
+    let tenths = (value as u128 * 10 + div as u128 / 2) / div as u128;
+    write!(f, "{}.{} {}", tenths / 10, tenths % 10, unit)
+}
+
+/* byte count with one decimal in the largest unit it reaches, as in
+ * "1.5 GiB"; counts under one unit are shown exactly, as in "512 B" */
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct HumanSize {
+    pub bytes: u64,
+    pub units: SizeUnits,
+}
+pub fn human_size(bytes: u64, units: SizeUnits) -> HumanSize {
+    HumanSize { bytes, units }
+}
+impl core::fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (base, names): (u64, [&str; 6]) = match self.units {
+            SizeUnits::Binary => (1024, ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+            SizeUnits::Decimal => (1000, ["kB", "MB", "GB", "TB", "PB", "EB"]),
+        };
+        if self.bytes < base {
+            return write!(f, "{} B", self.bytes);
+        }
+        let mut div = base;
+        let mut i = 0;
+        /* moves up a unit also when rounding would show a full one */
+        while i + 1 < names.len() && self.bytes >= div * base - div / 20 {
+            div *= base;
+            i += 1;
+        }
+        write_tenths(f, self.bytes, div, names[i])
+    }
+}
+
+/* nanoseconds with one decimal up to seconds, as in "12.5 ms", then as
+ * minutes and seconds, hours and minutes, or days and hours */
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct HumanDuration {
+    pub ns: u64,
+}
+pub fn human_duration(ns: u64) -> HumanDuration {
+    HumanDuration { ns }
+}
+impl core::fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const US: u64 = 1000;
+        const MS: u64 = 1000 * US;
+        const S: u64 = 1000 * MS;
+        let ns = self.ns;
+        /* limits are lowered by half a tenth so that rounding never shows
+         * "1000.0 us" */
+        if ns < US {
+            write!(f, "{} ns", ns)
+        } else if ns < MS - US / 20 {
+            write_tenths(f, ns, US, "us")
+        } else if ns < S - MS / 20 {
+            write_tenths(f, ns, MS, "ms")
+        } else if ns < 60 * S - S / 20 {
+            write_tenths(f, ns, S, "s")
+        } else {
+            let s = ns / S + (ns % S >= S / 2) as u64;
+            if s < 3600 {
+                write!(f, "{}m {:02}s", s / 60, s % 60)
+            } else if s < 86400 {
+                write!(f, "{}h {:02}m", s / 3600, s / 60 % 60)
+            } else {
+                write!(f, "{}d {:02}h", s / 86400, s / 3600 % 24)
+            }
+        }
+    }
+}
+
+/* parse_int ****************************************************************/
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub struct ParseIntOptions {
+    pub default_radix: Radix, // radix of numbers without a prefix
+    pub allow_sign: bool,
+    pub separator: Option<GroupSeparator>,
+}
+impl Default for ParseIntOptions {
+    fn default() -> Self {
+        ParseIntOptions {
+            default_radix: Radix::new(10).unwrap(),
+            allow_sign: true,
+            separator: Some(GroupSeparator::Underscore),
+        }
+    }
+}
+
+#[derive(Clone, Copy,  Debug, PartialEq)]
+pub enum ParseIntError {
+    NoDigits,
+    InvalidChar(usize), // offset of the char in the text
+    Overflow,
+}
+impl core::fmt::Display for ParseIntError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseIntError::NoDigits => write!(f, "number without digits"),
+            ParseIntError::InvalidChar(offset) => write!(f, "invalid char at offset {}", offset),
+            ParseIntError::Overflow => write!(f, "number out of range"),
+        }
+    }
+}
+
+/* radix and length of a prefix int_fmt emits: 0x, 0b, 0o, 0d or 0rN_ */
+fn parse_radix_prefix(text: &[u8]) -> Option<(u8, usize)> {
+    match text {
+        [b'0', b'x', ..] | [b'0', b'X', ..] => Some((16, 2)),
+        [b'0', b'b', ..] | [b'0', b'B', ..] => Some((2, 2)),
+        [b'0', b'o', ..] | [b'0', b'O', ..] => Some((8, 2)),
+        [b'0', b'd', ..] | [b'0', b'D', ..] => Some((10, 2)),
+        [b'0', b'r', d, b'_', ..] if d.is_ascii_digit() =>
+            Radix::new(d - b'0').map(|r| (r.unwrap(), 4)),
+        [b'0', b'r', d0, d1, b'_', ..] if d0.is_ascii_digit() && d1.is_ascii_digit() =>
+            Radix::new((d0 - b'0') * 10 + (d1 - b'0')).map(|r| (r.unwrap(), 5)),
+        _ => None,
+    }
+}
+
+/* reads back what int_fmt produces: leading spaces (padding or a space
+ * sign), then an optional sign, a radix prefix and the digits in either
+ * case, which may have separators between them */
+pub fn parse_int<T: PrimitiveInt>(
+    text: &str,
+    options: &ParseIntOptions,
+) -> Result<T, ParseIntError> {
+    let bytes = text.as_bytes();
+    let mut pos = bytes.iter().take_while(|&&b| b == b' ').count();
+    let negative = match bytes.get(pos) {
+        Some(&sign) if options.allow_sign && (sign == b'-' || sign == b'+') => {
+            pos += 1;
+            sign == b'-'
+        },
+        _ => false,
+    };
+    let (radix, prefix_len) = parse_radix_prefix(&bytes[pos..])
+        .unwrap_or((options.default_radix.unwrap(), 0));
+    pos += prefix_len;
+    let digits = &bytes[pos..];
+    if digits.is_empty() {
+        return Err(ParseIntError::NoDigits);
+    }
+    let separator = options.separator.map(GroupSeparator::to_byte);
+    let r = T::reinterpret_u8(radix);
+    let mut n = T::ZERO;
+    for (i, &b) in digits.iter().enumerate() {
+        if Some(b) == separator && i != 0 && i + 1 != digits.len() {
+            continue;
+        }
+        let d = (b as char).to_digit(radix.into())
+            .ok_or(ParseIntError::InvalidChar(pos + i))?;
+        let d = T::reinterpret_u8(d as u8);
+        n = n.checked_mul(r)
+            .and_then(|n| if negative { n.checked_sub(d) } else { n.checked_add(d) })
+            .ok_or(ParseIntError::Overflow)?;
+    }
+    Ok(n)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -479,5 +1122,216 @@ mod tests {
             let mut buf = [0_u8; 32];
             assert_eq!(nf.int_fmt(-0x12345_i32, &mut buf).unwrap(), "-0x012345");
         }
+        {
+            let mut buf = [0_u8; 40];
+            assert_eq!(nf.int_fmt(u128::MAX, &mut buf).unwrap(), "+0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF");
+            assert_eq!(MiniNumFmtPack::default().int_fmt(i128::MIN, &mut buf).unwrap(),
+                "-170141183460469231731687303715884105728");
+        }
+        assert_eq!(MiniNumFmtPack::from_u32(nf.to_u32()), Some(nf));
+        assert_eq!(MiniNumFmtPack::from_u32(0), None);
+        assert_eq!(MiniNumFmtPack::from_u32(1 << 20), None);
+    }
+
+    #[test]
+    fn grouping_padding_and_case() {
+        let dec = MiniNumFmtPack::default()
+            .with_grouping(GroupSize::new(3).unwrap(), GroupSeparator::Comma);
+        let hex = MiniNumFmtPack::new(
+            Radix::new(16).unwrap(),
+            RadixNotation::DefaultPrefix,
+            MinDigitCount::new(1).unwrap(),
+            PositiveSign::Hidden,
+            ZeroSign::Hidden).with_grouping(GroupSize::new(4).unwrap(), GroupSeparator::Underscore);
+        let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+        assert_eq!(dec.int_fmt(1_000_000_u32, &mut buf).unwrap(), "1,000,000");
+        assert_eq!(dec.int_fmt(-12345_i32, &mut buf).unwrap(), "-12,345");
+        assert_eq!(dec.int_fmt(999_u16, &mut buf).unwrap(), "999");
+        assert_eq!(hex.int_fmt(0x1234_5678_u32, &mut buf).unwrap(), "0x1234_5678");
+        assert_eq!(hex.with_digit_case(DigitCase::Lower).int_fmt(0xABCDE_u32, &mut buf).unwrap(),
+            "0xa_bcde");
+        let padded = hex.with_padding(FieldWidth::new(12).unwrap(), PadFill::Space);
+        assert_eq!(padded.int_fmt(0x12345_u32, &mut buf).unwrap(), "    0x1_2345");
+        let zeroed = hex.with_padding(FieldWidth::new(12).unwrap(), PadFill::Zero);
+        assert_eq!(zeroed.int_fmt(0x12345_u32, &mut buf).unwrap(), " 0x0001_2345");
+        let zeroed = MiniNumFmtPack::default().with_padding(FieldWidth::new(5).unwrap(), PadFill::Zero);
+        assert_eq!(zeroed.int_fmt(-42_i8, &mut buf).unwrap(), "-0042");
+        assert_eq!(zeroed.int_fmt(123456_u32, &mut buf).unwrap(), "123456");
+        let mut small = [0_u8; 6];
+        assert!(dec.int_fmt(1_000_000_u32, &mut small).is_err());
+
+        let all = zeroed.with_grouping(GroupSize::new(7).unwrap(), GroupSeparator::Apostrophe)
+            .with_digit_case(DigitCase::Lower);
+        assert_eq!(MiniNumFmtPack::from_u32(all.to_u32()), Some(all));
+        assert_eq!(all.get_field_width(), FieldWidth::new(5).unwrap());
+        assert_eq!(all.get_group_separator(), GroupSeparator::Apostrophe);
+        assert_eq!(GroupSize::new(8), None);
+        assert_eq!(FieldWidth::new(32), None);
+    }
+
+    #[test]
+    fn mini_float_fmt_pack() {
+        let mut buf = [0_u8; MiniFloatFmtPack::MAX_TEXT_LEN];
+        let shortest = MiniFloatFmtPack::default();
+        assert_eq!(shortest.float_fmt(1.0, &mut buf).unwrap(), "1.0");
+        assert_eq!(shortest.float_fmt(0.1, &mut buf).unwrap(), "0.1");
+        let fixed = MiniFloatFmtPack::fixed(3).unwrap();
+        assert_eq!(fixed.float_fmt(1.58496, &mut buf).unwrap(), "1.585");
+        assert_eq!(fixed.float_fmt(-2.0, &mut buf).unwrap(), "-2.000");
+        let sci = MiniFloatFmtPack::new(
+            FloatNotation::Scientific,
+            FloatPrecision::new(2).unwrap(),
+            PositiveSign::Plus);
+        assert_eq!(sci.float_fmt(1234.5, &mut buf).unwrap(), "+1.23e3");
+        assert_eq!(sci.float_fmt(f64::NAN, &mut buf).unwrap(), "NaN");
+        assert_eq!(sci.float_fmt(f64::NEG_INFINITY, &mut buf).unwrap(), "-inf");
+        let widest = MiniFloatFmtPack::new(
+            FloatNotation::Fixed,
+            FloatPrecision::new(63).unwrap(),
+            PositiveSign::Plus);
+        assert_eq!(widest.float_fmt(-f64::MAX, &mut buf).unwrap().len(), MiniFloatFmtPack::MAX_TEXT_LEN);
+        let mut small = [0_u8; 4];
+        assert!(fixed.float_fmt(1.5, &mut small).is_err());
+        assert_eq!(MiniFloatFmtPack::from_u16(sci.to_u16()), Some(sci));
+        assert_eq!(MiniFloatFmtPack::from_u16(0), None);
+        assert_eq!(MiniFloatFmtPack::from_u16(1 << 10), None);
+        assert_eq!(FloatPrecision::new(64), None);
+    }
+
+    #[test]
+    fn human_sizes_and_durations() {
+        extern crate std;
+        use std::string::ToString;
+        let bin = |n| human_size(n, SizeUnits::Binary).to_string();
+        let dec = |n| human_size(n, SizeUnits::Decimal).to_string();
+        assert_eq!(bin(0), "0 B");
+        assert_eq!(bin(1023), "1023 B");
+        assert_eq!(bin(1024), "1.0 KiB");
+        assert_eq!(bin(1536), "1.5 KiB");
+        assert_eq!(bin(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(bin(5 << 30), "5.0 GiB");
+        assert_eq!(bin(u64::MAX), "16.0 EiB");
+        assert_eq!(dec(999), "999 B");
+        assert_eq!(dec(1_250_000), "1.3 MB");
+        assert_eq!(dec(999_960), "1.0 MB");
+        let d = |ns| human_duration(ns).to_string();
+        assert_eq!(d(850), "850 ns");
+        assert_eq!(d(1500), "1.5 us");
+        assert_eq!(d(999_999), "1.0 ms");
+        assert_eq!(d(12_345_678), "12.3 ms");
+        assert_eq!(d(4_200_000_000), "4.2 s");
+        assert_eq!(d(59_990_000_000), "1m 00s");
+        assert_eq!(d(65_000_000_000), "1m 05s");
+        assert_eq!(d(7_500_000_000_000), "2h 05m");
+        assert_eq!(d(u64::MAX), "213503d 23h");
+    }
+
+    #[test]
+    fn parse_int_reads_int_fmt_output() {
+        let opts = ParseIntOptions::default();
+        assert_eq!(parse_int::<u32>("0x1234_abcd", &opts), Ok(0x1234_ABCD));
+        assert_eq!(parse_int::<i8>("-128", &opts), Ok(-128));
+        assert_eq!(parse_int::<i8>("128", &opts), Err(ParseIntError::Overflow));
+        assert_eq!(parse_int::<u8>("-1", &opts), Err(ParseIntError::Overflow));
+        assert_eq!(parse_int::<u8>("-0", &opts), Ok(0));
+        assert_eq!(parse_int::<u16>("0r36_ZZ", &opts), Ok(36 * 36 - 1));
+        assert_eq!(parse_int::<u16>("0o17", &opts), Ok(15));
+        assert_eq!(parse_int::<u16>("0x", &opts), Err(ParseIntError::NoDigits));
+        assert_eq!(parse_int::<u16>("0x_1", &opts), Err(ParseIntError::InvalidChar(2)));
+        assert_eq!(parse_int::<u16>("1_", &opts), Err(ParseIntError::InvalidChar(1)));
+        assert_eq!(parse_int::<u16>("12a", &opts), Err(ParseIntError::InvalidChar(2)));
+        assert_eq!(parse_int::<u16>("0r37_1", &opts), Err(ParseIntError::InvalidChar(1)));
+        let hex = ParseIntOptions {
+            default_radix: Radix::new(16).unwrap(),
+            allow_sign: false,
+            separator: None,
+        };
+        assert_eq!(parse_int::<u64>("ff", &hex), Ok(0xFF));
+        assert_eq!(parse_int::<i64>("-1", &hex), Err(ParseIntError::InvalidChar(0)));
+
+        let packs = [
+            MiniNumFmtPack::default(),
+            MiniNumFmtPack::new(
+                Radix::new(16).unwrap(),
+                RadixNotation::DefaultPrefix,
+                MinDigitCount::new(6).unwrap(),
+                PositiveSign::Space,
+                ZeroSign::Minus)
+                .with_grouping(GroupSize::new(4).unwrap(), GroupSeparator::Underscore)
+                .with_digit_case(DigitCase::Lower),
+            MiniNumFmtPack::new(
+                Radix::new(7).unwrap(),
+                RadixNotation::PrefixZeroRadix,
+                MinDigitCount::new(1).unwrap(),
+                PositiveSign::Plus,
+                ZeroSign::Hidden)
+                .with_padding(FieldWidth::new(30).unwrap(), PadFill::Space),
+        ];
+        for nf in packs.iter() {
+            for &n in [0_i64, 1, -1, 0x12345, i64::MIN, i64::MAX].iter() {
+                let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+                let text = nf.int_fmt(n, &mut buf).unwrap();
+                assert_eq!(parse_int::<i64>(text, &opts), Ok(n), "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn write_int_matches_int_fmt() {
+        use crate::mm::Allocator;
+        use crate::mm::BumpAllocator;
+        use crate::mm::Vector;
+        let mut abuf = [0_u8; 0x4000];
+        let a = BumpAllocator::new(&mut abuf);
+        let mut xc = ExecutionContext::nop();
+        let packs = [
+            MiniNumFmtPack::default(),
+            MiniNumFmtPack::new(
+                Radix::new(16).unwrap(),
+                RadixNotation::DefaultPrefix,
+                MinDigitCount::new(6).unwrap(),
+                PositiveSign::Plus,
+                ZeroSign::Space),
+            MiniNumFmtPack::new(
+                Radix::new(2).unwrap(),
+                RadixNotation::PrefixZeroRadix,
+                MinDigitCount::new(100).unwrap(),
+                PositiveSign::Space,
+                ZeroSign::Minus),
+            MiniNumFmtPack::new(
+                Radix::new(2).unwrap(),
+                RadixNotation::PrefixZeroRadix,
+                MinDigitCount::new(128).unwrap(),
+                PositiveSign::Space,
+                ZeroSign::Minus)
+                .with_grouping(GroupSize::new(1).unwrap(), GroupSeparator::Space)
+                .with_padding(FieldWidth::new(31).unwrap(), PadFill::Zero),
+        ];
+        for nf in packs.iter() {
+            for &n in [0_i64, 1, -1, 0x12345, i64::MIN, i64::MAX].iter() {
+                let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+                let expected = nf.int_fmt(n, &mut buf).unwrap();
+                let mut v: Vector<'_, u8> = Vector::new(a.to_ref());
+                nf.write_int(n, &mut v, &mut xc).unwrap();
+                assert_eq!(v.as_slice(), expected.as_bytes());
+            }
+            let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+            let expected = nf.int_fmt(u64::MAX, &mut buf).unwrap();
+            let mut v: Vector<'_, u8> = Vector::new(a.to_ref());
+            nf.write_int(u64::MAX, &mut v, &mut xc).unwrap();
+            assert_eq!(v.as_slice(), expected.as_bytes());
+            for &n in [i128::MIN, i128::MAX].iter() {
+                let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+                let expected = nf.int_fmt(n, &mut buf).unwrap();
+                let mut v: Vector<'_, u8> = Vector::new(a.to_ref());
+                nf.write_int(n, &mut v, &mut xc).unwrap();
+                assert_eq!(v.as_slice(), expected.as_bytes());
+            }
+            let mut buf = [0_u8; MiniNumFmtPack::MAX_TEXT_LEN];
+            let expected = nf.int_fmt(u128::MAX, &mut buf).unwrap();
+            let mut v: Vector<'_, u8> = Vector::new(a.to_ref());
+            nf.write_int(u128::MAX, &mut v, &mut xc).unwrap();
+            assert_eq!(v.as_slice(), expected.as_bytes());
+        }
     }
 }
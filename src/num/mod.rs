@@ -1,13 +1,40 @@
 use core::ptr::NonNull;
 
+pub mod bits;
+pub mod fixed;
 pub mod fmt;
+pub mod pos;
 
 pub const BITS_PER_BYTE: usize = 8;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BitMaskError {
+    TooManyBits, // mask wider than the integer type
+    RangeOverflow, // bit position + bit count overflows usize
+}
+
+impl BitMaskError {
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            BitMaskError::TooManyBits => "mask wider than integer type",
+            BitMaskError::RangeOverflow => "bit range end overflows",
+        }
+    }
+
+}
+
+impl core::fmt::Display for BitMaskError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.to_str().fmt(f)
+    }
+}
+
 pub trait PrimitiveInt:
     Copy +
     core::ops::Shl<u8, Output = Self> +
     core::ops::Shl<usize, Output = Self> +
+    core::ops::Shr<usize, Output = Self> +
     core::ops::BitAnd<Output = Self> +
     core::ops::BitOr<Output = Self> +
     core::ops::Not<Output = Self> +
@@ -30,32 +57,43 @@ where
 
     fn reinterpret_u8(v: u8) -> Self;
     fn trunc_to_u8(self) -> u8;
-    fn lsb_mask_checked(n: usize) -> Option<Self> {
+    fn try_lsb_mask(n: usize) -> Result<Self, BitMaskError> {
         let bit_count = Self::SIZE * BITS_PER_BYTE;
         if n < bit_count {
-            Some((Self::ONE << n) - Self::ONE)
+            Ok((Self::ONE << n) - Self::ONE)
         } else if n == bit_count {
-            Some(!Self::ZERO)
+            Ok(!Self::ZERO)
         } else {
-            None
+            Err(BitMaskError::TooManyBits)
         }
     }
+    fn try_msb_mask(n: usize) -> Result<Self, BitMaskError> {
+        Self::try_lsb_mask(n).map(|x| !x)
+    }
+    fn try_incl_bit_range_mask(pos: usize, count: usize) -> Result<Self, BitMaskError> {
+        let end = pos.checked_add(count).ok_or(BitMaskError::RangeOverflow)?;
+        Ok(Self::try_lsb_mask(end)? & Self::try_msb_mask(pos)?)
+    }
+    fn try_excl_bit_range_mask(pos: usize, count: usize) -> Result<Self, BitMaskError> {
+        Self::try_incl_bit_range_mask(pos, count).map(|x| !x)
+    }
+    fn lsb_mask_checked(n: usize) -> Option<Self> {
+        Self::try_lsb_mask(n).ok()
+    }
     fn lsb_mask(n: usize) -> Self {
         Self::lsb_mask_checked(n).unwrap()
     }
     fn msb_mask_checked(n: usize) -> Option<Self> {
-        Self::lsb_mask_checked(n).map(|x| !x)
+        Self::try_msb_mask(n).ok()
     }
     fn msb_mask(n: usize) -> Self {
         Self::msb_mask_checked(n).unwrap()
     }
     fn incl_bit_range_mask_checked(pos: usize, count: usize) -> Option<Self> {
-        pos.checked_add(count)
-            .and_then(|end| Self::lsb_mask_checked(end))
-            .and_then(|em| Self::msb_mask_checked(pos).map(|pm| em & pm))
+        Self::try_incl_bit_range_mask(pos, count).ok()
     }
     fn excl_bit_range_mask_checked(pos: usize, count: usize) -> Option<Self> {
-        Self::incl_bit_range_mask_checked(pos, count).map(|x| !x)
+        Self::try_excl_bit_range_mask(pos, count).ok()
     }
     fn incl_bit_range_mask(pos: usize, count: usize) -> Self {
         Self::incl_bit_range_mask_checked(pos, count).unwrap()
@@ -66,6 +104,21 @@ where
     fn reinterpret_as_uint(self) -> Self::SameSizeUInt;
     fn reinterpret_as_sint(self) -> Self::SameSizeSInt;
     fn neg_wrapping(self) -> Self;
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_shl(self, rhs: u32) -> Option<Self>;
+    fn checked_shr(self, rhs: u32) -> Option<Self>;
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn wrapping_shl(self, rhs: u32) -> Self;
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+    fn leading_zeros(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+    fn count_ones(self) -> u32;
     fn abs_uint(self) -> Self::SameSizeUInt {
         let p =
             if self >= Self::ZERO {
@@ -77,6 +130,28 @@ where
     }
 }
 
+/* the PrimitiveInt methods that forward to the inherent ones of the same
+ * name, which take precedence over the trait methods being defined */
+macro_rules! forward_int_ops {
+    () => {
+        fn checked_add(self, rhs: Self) -> Option<Self> { self.checked_add(rhs) }
+        fn checked_sub(self, rhs: Self) -> Option<Self> { self.checked_sub(rhs) }
+        fn checked_mul(self, rhs: Self) -> Option<Self> { self.checked_mul(rhs) }
+        fn checked_shl(self, rhs: u32) -> Option<Self> { self.checked_shl(rhs) }
+        fn checked_shr(self, rhs: u32) -> Option<Self> { self.checked_shr(rhs) }
+        fn wrapping_add(self, rhs: Self) -> Self { self.wrapping_add(rhs) }
+        fn wrapping_sub(self, rhs: Self) -> Self { self.wrapping_sub(rhs) }
+        fn wrapping_mul(self, rhs: Self) -> Self { self.wrapping_mul(rhs) }
+        fn wrapping_shl(self, rhs: u32) -> Self { self.wrapping_shl(rhs) }
+        fn saturating_add(self, rhs: Self) -> Self { self.saturating_add(rhs) }
+        fn saturating_sub(self, rhs: Self) -> Self { self.saturating_sub(rhs) }
+        fn saturating_mul(self, rhs: Self) -> Self { self.saturating_mul(rhs) }
+        fn leading_zeros(self) -> u32 { self.leading_zeros() }
+        fn trailing_zeros(self) -> u32 { self.trailing_zeros() }
+        fn count_ones(self) -> u32 { self.count_ones() }
+    };
+}
+
 pub trait PrimitiveUInt: PrimitiveInt { }
 pub trait PrimitiveSInt: PrimitiveInt { }
 
@@ -95,6 +170,7 @@ impl PrimitiveInt for u8 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveUInt for u8 {}
 
@@ -113,6 +189,7 @@ impl PrimitiveInt for u16 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveUInt for u16 {}
 
@@ -131,6 +208,7 @@ impl PrimitiveInt for u32 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveUInt for u32 {}
 
@@ -149,9 +227,29 @@ impl PrimitiveInt for u64 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveUInt for u64 {}
 
+impl PrimitiveInt for u128 {
+    const SIZE: usize = core::mem::size_of::<Self>();
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+    type SameSizeUInt = u128;
+    type SameSizeSInt = i128;
+    fn reinterpret_u8(v: u8) -> Self { v as Self }
+    fn trunc_to_u8(self) -> u8 { self as u8 }
+    fn reinterpret_as_uint(self) -> Self::SameSizeUInt {
+        self as Self::SameSizeUInt
+    }
+    fn reinterpret_as_sint(self) -> Self::SameSizeSInt {
+        self as Self::SameSizeSInt
+    }
+    fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
+}
+impl PrimitiveUInt for u128 {}
+
 impl PrimitiveInt for usize {
     const SIZE: usize = core::mem::size_of::<Self>();
     const ZERO: Self = 0;
@@ -167,6 +265,7 @@ impl PrimitiveInt for usize {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveUInt for usize {}
 
@@ -185,6 +284,7 @@ impl PrimitiveInt for i8 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveSInt for i8 {}
 
@@ -203,6 +303,7 @@ impl PrimitiveInt for i16 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveSInt for i16 {}
 
@@ -221,6 +322,7 @@ impl PrimitiveInt for i32 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveSInt for i32 {}
 
@@ -239,9 +341,29 @@ impl PrimitiveInt for i64 {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveSInt for i64 {}
 
+impl PrimitiveInt for i128 {
+    const SIZE: usize = core::mem::size_of::<Self>();
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+    type SameSizeUInt = u128;
+    type SameSizeSInt = i128;
+    fn reinterpret_u8(v: u8) -> Self { v as Self }
+    fn trunc_to_u8(self) -> u8 { self as u8 }
+    fn reinterpret_as_uint(self) -> Self::SameSizeUInt {
+        self as Self::SameSizeUInt
+    }
+    fn reinterpret_as_sint(self) -> Self::SameSizeSInt {
+        self as Self::SameSizeSInt
+    }
+    fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
+}
+impl PrimitiveSInt for i128 {}
+
 impl PrimitiveInt for isize {
     const SIZE: usize = core::mem::size_of::<Self>();
     const ZERO: Self = 0;
@@ -257,6 +379,7 @@ impl PrimitiveInt for isize {
         self as Self::SameSizeSInt
     }
     fn neg_wrapping(self) -> Self { self.wrapping_neg() }
+    forward_int_ops!();
 }
 impl PrimitiveSInt for isize {}
 
@@ -265,6 +388,7 @@ pub fn is_power_of_2<T: PrimitiveUInt> (n: T) -> bool {
 }
 
 pub use core::num::NonZeroUsize;
+pub use fixed::FixedPoint;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Pow2Usize(NonZeroUsize);
@@ -300,19 +424,11 @@ impl Pow2Usize {
     }
 
     pub fn shl(self, count: u32) -> Option<Self> {
-        if count >= (core::mem::size_of::<usize>() as u32) * 8 {
-            None
-        } else {
-            Pow2Usize::new(self.get().wrapping_shl(count))
-        }
+        self.get().checked_shl(count).and_then(Pow2Usize::new)
     }
 
     pub fn shr(self, count: u32) -> Option<Self> {
-        if count >= (core::mem::size_of::<usize>() as u32) * 8 {
-            None
-        } else {
-            Pow2Usize::new(self.get().wrapping_shr(count))
-        }
+        self.get().checked_shr(count).and_then(Pow2Usize::new)
     }
 
     pub fn from_smaller_or_equal_usize(n: usize) -> Option<Self> {
@@ -351,11 +467,16 @@ impl Pow2Usize {
     }
 }
 
-use core::num::Wrapping;
+/* n rounded up to a multiple of align, which must be a power of 2; None
+ * on overflow */
+pub fn align_up<T: PrimitiveUInt>(n: T, align: T) -> Option<T> {
+    debug_assert!(is_power_of_2(align));
+    let mask = align - T::ONE;
+    n.checked_add(mask).map(|v| v & !mask)
+}
+
 pub fn usize_align_up (n: usize, align: Pow2Usize) -> Option<usize> {
-    let mask = Wrapping(align.get()) - Wrapping(1usize);
-    let aligned = (Wrapping(n) + mask).0 & !mask.0;
-    if aligned < n { None } else { Some(aligned) }
+    align_up(n, align.get())
 }
 
 #[cfg(test)]
@@ -371,6 +492,25 @@ mod tests {
         assert!(is_power_of_2(4usize));
     }
 
+    #[test]
+    fn generic_arithmetic() {
+        fn sum_or_max<T: PrimitiveInt>(a: T, b: T) -> T {
+            a.checked_add(b).unwrap_or_else(|| a.saturating_add(b))
+        }
+        assert_eq!(sum_or_max(200_u8, 100), 255);
+        assert_eq!(sum_or_max(-100_i8, -100), -128);
+        assert_eq!(sum_or_max(1_u128, 2), 3);
+        assert_eq!(PrimitiveInt::wrapping_sub(0_u16, 1), 0xFFFF);
+        assert_eq!(PrimitiveInt::checked_shl(1_u32, 32), None);
+        assert_eq!(PrimitiveInt::saturating_mul(-3_i64, i64::MAX), i64::MIN);
+        assert_eq!(PrimitiveInt::leading_zeros(1_u64), 63);
+        assert_eq!(PrimitiveInt::trailing_zeros(0x100_usize), 8);
+        assert_eq!(PrimitiveInt::count_ones(-1_i128), 128);
+        assert_eq!(align_up(13_u32, 8), Some(16));
+        assert_eq!(align_up(0xFFF9_u16, 8), None);
+        assert_eq!(usize_align_up(usize::MAX - 2, Pow2Usize::new(8).unwrap()), None);
+    }
+
     #[test]
     fn pow2usize_max() {
         assert_eq!(Pow2Usize::max().get().swap_bytes(), 0x80usize);
@@ -493,6 +633,7 @@ mod tests {
     #[test] fn u64_lsb64_mask() { assert_eq!(u64::lsb_mask(64), 0xFFFFFFFFFFFFFFFF); }
     #[should_panic(expected = "called `Option::unwrap()` on a `None` value")]
     #[test] fn u64_lsb65_mask() { u64::lsb_mask(65); }
+    #[test] fn u128_msb127_mask() { assert_eq!(u128::msb_mask(127), 1 << 127); }
     #[test] fn usize_lsb_near_max_mask() { assert_eq!(usize::lsb_mask(usize::SIZE * BITS_PER_BYTE - 1), (!0_usize) >> 1); }
     #[test] fn usize_lsb_max_mask() { assert_eq!(usize::lsb_mask(usize::SIZE * BITS_PER_BYTE), !0_usize); }
     #[should_panic(expected = "called `Option::unwrap()` on a `None` value")]
@@ -519,6 +660,19 @@ mod tests {
     #[test] fn usize_msb_max_mask() { assert_eq!(usize::msb_mask(usize::SIZE * BITS_PER_BYTE), 0_usize); }
     #[should_panic(expected = "called `Option::unwrap()` on a `None` value")]
     #[test] fn usize_msb_over_max_mask() { usize::msb_mask(usize::SIZE * BITS_PER_BYTE + 1); }
+    #[test] fn u8_try_lsb8_mask() { assert_eq!(u8::try_lsb_mask(8), Ok(0xFF)); }
+    #[test] fn u8_try_lsb9_mask() { assert_eq!(u8::try_lsb_mask(9), Err(BitMaskError::TooManyBits)); }
+    #[test] fn u16_try_msb17_mask() { assert_eq!(u16::try_msb_mask(17), Err(BitMaskError::TooManyBits)); }
+    #[test] fn u32_try_incl_bit_range_mask() { assert_eq!(u32::try_incl_bit_range_mask(4, 8), Ok(0xFF0)); }
+    #[test] fn u32_try_excl_bit_range_mask() { assert_eq!(u32::try_excl_bit_range_mask(4, 8), Ok(!0xFF0)); }
+    #[test] fn u32_try_incl_bit_range_mask_full() { assert_eq!(u32::try_incl_bit_range_mask(0, 32), Ok(!0)); }
+    #[test] fn u32_try_incl_bit_range_mask_too_wide() { assert_eq!(u32::try_incl_bit_range_mask(30, 3), Err(BitMaskError::TooManyBits)); }
+    #[test] fn u64_try_incl_bit_range_mask_overflow() { assert_eq!(u64::try_incl_bit_range_mask(usize::MAX, 2), Err(BitMaskError::RangeOverflow)); }
+    #[test] fn bit_mask_error_display() {
+        extern crate std;
+        use std::string::ToString;
+        assert_eq!(BitMaskError::RangeOverflow.to_string(), "bit range end overflows");
+    }
 
 }
 
@@ -0,0 +1,80 @@
+/* conversions between stream positions (u64) and in-memory sizes and
+ * offsets (usize) */
+
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PosError {
+    Negative, // position before the start
+    U64Overflow, // position past u64::MAX
+    UsizeOverflow, // position does not fit in usize
+}
+
+impl PosError {
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            PosError::Negative => "seek to negative position",
+            PosError::U64Overflow => "seek to position too large for u64",
+            PosError::UsizeOverflow => "position too large for usize",
+        }
+    }
+
+}
+
+impl core::fmt::Display for PosError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.to_str().fmt(f)
+    }
+}
+
+pub type PosResult<T> = Result<T, PosError>;
+
+/* u64 to usize, failing on targets with narrower usize */
+pub fn u64_to_usize_checked(pos: u64) -> PosResult<usize> {
+    usize::try_from(pos).map_err(|_| PosError::UsizeOverflow)
+}
+
+/* usize to u64; no supported target has usize wider than 64 bits */
+pub const fn usize_to_u64(n: usize) -> u64 {
+    n as u64
+}
+
+pub fn add_offset(pos: u64, offset: u64) -> PosResult<u64> {
+    pos.checked_add(offset).ok_or(PosError::U64Overflow)
+}
+
+pub fn add_len(pos: u64, len: usize) -> PosResult<u64> {
+    add_offset(pos, usize_to_u64(len))
+}
+
+/* position moved by a signed displacement, as for a relative seek */
+pub fn add_signed_offset(pos: u64, disp: i64) -> PosResult<u64> {
+    if disp < 0 {
+        pos.checked_sub(disp.unsigned_abs()).ok_or(PosError::Negative)
+    } else {
+        add_offset(pos, disp as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_and_offsets() {
+        assert_eq!(u64_to_usize_checked(0x1234), Ok(0x1234));
+        if usize::BITS < 64 {
+            assert_eq!(u64_to_usize_checked(u64::MAX), Err(PosError::UsizeOverflow));
+        }
+        assert_eq!(usize_to_u64(usize::MAX) as usize, usize::MAX);
+        assert_eq!(add_offset(u64::MAX - 1, 1), Ok(u64::MAX));
+        assert_eq!(add_offset(u64::MAX, 1), Err(PosError::U64Overflow));
+        assert_eq!(add_len(5, 3), Ok(8));
+        assert_eq!(add_signed_offset(10, -10), Ok(0));
+        assert_eq!(add_signed_offset(10, -11), Err(PosError::Negative));
+        assert_eq!(add_signed_offset(u64::MAX, i64::MIN), Ok(u64::MAX / 2));
+        assert_eq!(add_signed_offset(u64::MAX - 2, 3), Err(PosError::U64Overflow));
+        assert_eq!(PosError::Negative.to_str(), "seek to negative position");
+    }
+}
@@ -0,0 +1,249 @@
+use crate::ExecutionContext;
+use crate::io::ErrorCode as IOErrorCode;
+use crate::io::IOPartialResult;
+use crate::io::IOResult;
+use crate::io::stream::Write;
+use crate::mm::AllocError;
+use crate::mm::AllocatorRef;
+use crate::mm::Vector;
+use crate::xc_err;
+
+const SPACES: [u8; 32] = [b' '; 32];
+
+/* TableLayout **************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TableLayout<'s> {
+    /* cells padded with spaces to the widest cell of their column, plus
+     * gap spaces; the last cell of a row is not padded */
+    Aligned { gap: usize },
+    /* cells as they are, joined by the delimiter */
+    Delimited(&'s [u8]),
+}
+
+/* number of chars in UTF-8 text; other bytes count as one char each */
+fn text_width(text: &[u8]) -> usize {
+    text.iter().filter(|&&b| b & 0xC0 != 0x80).count()
+}
+
+/* TableWriter **************************************************************/
+/* rows of text cells kept until flush, which writes them to the output in
+ * the given layout; cell text is written through the Write impl of the
+ * table, ended by end_cell or end_row */
+pub struct TableWriter<'a, 'o> {
+    out: &'o mut (dyn Write + 'o),
+    layout: TableLayout<'o>,
+    text: Vector<'a, u8>,
+    cell_ends: Vector<'a, usize>, // end of each cell in text
+    row_ends: Vector<'a, usize>, // end of each row in cell_ends
+}
+
+impl<'a, 'o> TableWriter<'a, 'o> {
+
+    pub fn new(
+        out: &'o mut (dyn Write + 'o),
+        layout: TableLayout<'o>,
+        allocator: AllocatorRef<'a>,
+    ) -> Self {
+        TableWriter {
+            out,
+            layout,
+            text: Vector::new(allocator),
+            cell_ends: Vector::new(allocator),
+            row_ends: Vector::new(allocator),
+        }
+    }
+
+    pub fn layout(&self) -> TableLayout<'o> {
+        self.layout
+    }
+
+    /* rows ended since the last flush */
+    pub fn row_count(&self) -> usize {
+        self.row_ends.len()
+    }
+
+    pub fn end_cell(&mut self) -> Result<(), AllocError> {
+        self.cell_ends.push(self.text.len()).map_err(|(e, _)| e)
+    }
+
+    /* ends the current cell too */
+    pub fn end_row(&mut self) -> Result<(), AllocError> {
+        self.end_cell()?;
+        self.row_ends.push(self.cell_ends.len()).map_err(|(e, _)| e)
+    }
+
+    /* drops the cells written since the last end_row */
+    pub fn cancel_row(&mut self) {
+        let cells = self.row_ends.as_slice().last().copied().unwrap_or(0);
+        self.cell_ends.truncate(cells);
+        self.text.truncate(self.text_end(cells));
+    }
+
+    pub fn push_cell(&mut self, text: &[u8]) -> Result<(), AllocError> {
+        self.text.append_from_slice(text)?;
+        self.end_cell()
+    }
+
+    /* text of the given cell of the given row, among those not flushed */
+    pub fn cell(&self, row: usize, column: usize) -> Option<&[u8]> {
+        let (first, end) = self.row_cells(row)?;
+        if first + column >= end {
+            return None;
+        }
+        let i = first + column;
+        Some(&self.text.as_slice()[self.text_end(i)..self.text_end(i + 1)])
+    }
+
+    /* end of the text of the first cells */
+    fn text_end(&self, cells: usize) -> usize {
+        if cells == 0 { 0 } else { self.cell_ends.as_slice()[cells - 1] }
+    }
+
+    /* range of cell indexes for a row */
+    fn row_cells(&self, row: usize) -> Option<(usize, usize)> {
+        let ends = self.row_ends.as_slice();
+        let end = *ends.get(row)?;
+        Some((if row == 0 { 0 } else { ends[row - 1] }, end))
+    }
+
+    /* width of each column over the rows kept; columns past the buffer
+     * size are not padded */
+    fn column_widths(&self, widths: &mut [usize]) {
+        for row in 0..self.row_count() {
+            let (first, end) = self.row_cells(row).unwrap();
+            for (column, w) in widths.iter_mut().enumerate().take(end - first) {
+                *w = core::cmp::max(*w, text_width(self.cell(row, column).unwrap()));
+            }
+        }
+    }
+
+    /* writes the complete rows and drops them; text written after the last
+     * end_row is kept for the next flush */
+    pub fn flush<'x>(
+        &mut self,
+        xc: &mut ExecutionContext<'x>,
+    ) -> IOPartialResult<'x, ()> {
+        let mut widths = [0_usize; 16];
+        if let TableLayout::Aligned { .. } = self.layout {
+            self.column_widths(&mut widths);
+        }
+        for row in 0..self.row_count() {
+            let (first, end) = self.row_cells(row).unwrap();
+            let text = self.text.as_slice();
+            let cell_ends = self.cell_ends.as_slice();
+            for i in first..end {
+                let start = if i == 0 { 0 } else { cell_ends[i - 1] };
+                let cell = &text[start..cell_ends[i]];
+                self.out.write_all(cell, xc)?;
+                if i + 1 == end { break; }
+                match self.layout {
+                    TableLayout::Aligned { gap } => {
+                        let width = widths.get(i - first).copied().unwrap_or(0);
+                        let mut pad = width.saturating_sub(text_width(cell)) + gap;
+                        while pad > 0 {
+                            let n = core::cmp::min(pad, SPACES.len());
+                            self.out.write_all(&SPACES[0..n], xc)?;
+                            pad -= n;
+                        }
+                    },
+                    TableLayout::Delimited(d) => self.out.write_all(d, xc)?,
+                }
+            }
+            self.out.write_all(b"\n", xc)?;
+        }
+        let cells = self.row_ends.as_slice().last().copied().unwrap_or(0);
+        let text_len = self.text_end(cells);
+        self.text.as_mut_slice().copy_within(text_len.., 0);
+        self.text.truncate(self.text.len() - text_len);
+        self.cell_ends.as_mut_slice().copy_within(cells.., 0);
+        self.cell_ends.truncate(self.cell_ends.len() - cells);
+        for e in self.cell_ends.as_mut_slice() {
+            *e -= text_len;
+        }
+        self.row_ends.truncate(0);
+        Ok(())
+    }
+
+}
+
+impl Write for TableWriter<'_, '_> {
+    fn write<'x>(
+        &mut self,
+        buf: &[u8],
+        xc: &mut ExecutionContext<'x>
+    ) -> IOResult<'x, usize> {
+        self.text.append_from_slice(buf)
+            .map(|_| buf.len())
+            .map_err(|e| xc_err!(
+                xc, IOErrorCode::NoSpace,
+                "table cell out of memory",
+                "table cell append failed: {}", e))
+    }
+}
+
+impl core::fmt::Write for TableWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.text.append_from_slice(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
+
+impl core::fmt::Debug for TableWriter<'_, '_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TableWriter")
+            .field("layout", &self.layout)
+            .field("rows", &self.row_count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::Allocator;
+    use crate::mm::BumpAllocator;
+
+    #[test]
+    fn aligned_and_delimited() {
+        let mut buffer = [0_u8; 0x1000];
+        let a = BumpAllocator::new(&mut buffer);
+        let mut xc = ExecutionContext::with_allocator_and_logless(a.to_ref());
+        let mut o = xc.byte_vector();
+        {
+            let mut t = TableWriter::new(&mut o, TableLayout::Aligned { gap: 2 }, xc.get_main_allocator());
+            for row in [&["\"a\"", "tof_ids", "[elf]"][..], &["\"caf\u{e9}\"", "x", "1"], &["\"b\"", "first_byte_of_it"]] {
+                for (i, c) in row.iter().enumerate() {
+                    t.write_all(c.as_bytes(), &mut xc).unwrap();
+                    if i + 1 < row.len() { t.end_cell().unwrap(); }
+                }
+                t.end_row().unwrap();
+            }
+            t.write_all(b"kept", &mut xc).unwrap();
+            assert_eq!(t.cell(1, 0), Some(&b"\"caf\xC3\xA9\""[..]));
+            assert_eq!(t.cell(2, 2), None);
+            t.flush(&mut xc).unwrap();
+            assert_eq!(t.row_count(), 0);
+            t.end_row().unwrap();
+            t.flush(&mut xc).unwrap();
+        }
+        assert_eq!(core::str::from_utf8(o.as_slice()).unwrap(), concat!(
+            "\"a\"     tof_ids           [elf]\n",
+            "\"caf\u{e9}\"  x                 1\n",
+            "\"b\"     first_byte_of_it\n",
+            "kept\n"));
+
+        let mut o = xc.byte_vector();
+        {
+            let mut t = TableWriter::new(&mut o, TableLayout::Delimited(b"\t"), xc.get_main_allocator());
+            t.push_cell(b"dropped").unwrap();
+            t.write_all(b"partial", &mut xc).unwrap();
+            t.cancel_row();
+            t.push_cell(b"\"a\"").unwrap();
+            t.push_cell(b"tof_ids").unwrap();
+            t.write_all(b"[elf]", &mut xc).unwrap();
+            t.end_row().unwrap();
+            t.flush(&mut xc).unwrap();
+            t.flush(&mut xc).unwrap();
+        }
+        assert_eq!(o.as_slice(), b"\"a\"\ttof_ids\t[elf]\n");
+    }
+}
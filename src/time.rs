@@ -0,0 +1,95 @@
+/* clocks for timing work and stamping logs; the crate has no clock of its
+ * own so the embedder plugs one into the execution context */
+
+#[cfg(feature = "use-std")]
+extern crate std;
+
+/* TimeSource ***************************************************************/
+pub trait TimeSource {
+    /* a monotonic counter; only differences between readings mean
+     * something */
+    fn ticks(&self) -> u64;
+    /* how many ticks make a second */
+    fn ticks_per_second(&self) -> u64;
+    /* nanoseconds since the Unix epoch, when there is a wall clock */
+    fn wall_clock_ns(&self) -> Option<u64> {
+        None
+    }
+    fn ticks_to_ns(&self, ticks: u64) -> u64 {
+        let ns = ticks as u128 * 1_000_000_000 / self.ticks_per_second() as u128;
+        ns.min(u64::MAX as u128) as u64
+    }
+}
+
+/* StdTimeSource ************************************************************/
+/* nanoseconds since the source was made, plus the system clock */
+#[cfg(feature = "use-std")]
+pub struct StdTimeSource {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "use-std")]
+impl StdTimeSource {
+    pub fn new() -> Self {
+        StdTimeSource { start: std::time::Instant::now() }
+    }
+}
+
+#[cfg(feature = "use-std")]
+impl Default for StdTimeSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "use-std")]
+impl TimeSource for StdTimeSource {
+    fn ticks(&self) -> u64 {
+        self.start.elapsed().as_nanos().min(u64::MAX as u128) as u64
+    }
+    fn ticks_per_second(&self) -> u64 {
+        1_000_000_000
+    }
+    fn wall_clock_ns(&self) -> Option<u64> {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH).ok()
+            .map(|d| d.as_nanos().min(u64::MAX as u128) as u64)
+    }
+    fn ticks_to_ns(&self, ticks: u64) -> u64 {
+        ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    struct Manual(Cell<u64>);
+    impl TimeSource for Manual {
+        fn ticks(&self) -> u64 {
+            self.0.get()
+        }
+        fn ticks_per_second(&self) -> u64 {
+            32768
+        }
+    }
+
+    #[test]
+    fn ticks_to_nanoseconds() {
+        let t = Manual(Cell::new(5));
+        assert_eq!(t.ticks(), 5);
+        assert_eq!(t.wall_clock_ns(), None);
+        assert_eq!(t.ticks_to_ns(16384), 500_000_000);
+        assert_eq!(t.ticks_to_ns(u64::MAX), u64::MAX);
+    }
+
+    #[cfg(feature = "use-std")]
+    #[test]
+    fn std_source_is_monotonic() {
+        let t = StdTimeSource::new();
+        let a = t.ticks();
+        assert!(t.ticks() >= a);
+        assert!(t.wall_clock_ns().unwrap() > 1_500_000_000_000_000_000);
+    }
+}